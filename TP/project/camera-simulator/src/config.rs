@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::io;
+use std::str::FromStr;
+use std::{fs::File, io::Read, path::Path};
+
+/// Images each camera takes per minute, unless configured
+const DEFAULT_IMAGES_PER_MINUTE: f64 = 6.0;
+/// Probability of an image showing an incident, unless configured
+const DEFAULT_INCIDENT_PROBABILITY: f64 = 0.05;
+/// Labels of the incidents, unless configured. They are words the local heuristic of the
+/// camera system recognizes
+const DEFAULT_INCIDENT_LABELS: [&str; 3] = ["fire", "fight", "crash"];
+
+/// Represents how a camera takes images
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeedRate {
+    pub images_per_minute: f64,
+    /// Probability of each image showing an incident, between 0 and 1
+    pub incident_probability: f64,
+}
+
+/// Represents the configuration of the camera simulator
+#[derive(Debug, Clone)]
+pub struct Config {
+    camera_config: String,
+    rate: FeedRate,
+    incident_labels: Vec<String>,
+    incident_samples: Option<String>,
+    normal_samples: Option<String>,
+    duration: Option<u64>,
+    seed: Option<u64>,
+    /// Rates of the cameras that don't take images as the rest
+    camera_rates: HashMap<u8, FeedRate>,
+}
+
+impl Config {
+    /// Reads the configuration from a file
+    pub fn from_file(path: &Path) -> std::io::Result<Self> {
+        let mut file = File::open(path)?;
+        let mut contents = String::new();
+
+        file.read_to_string(&mut contents)?;
+
+        let json = contents.trim().trim_matches(|c| c == '{' || c == '}');
+
+        let mut config_map = HashMap::new();
+        let mut camera_maps = Vec::new();
+        let mut inside_cameras = false;
+        let mut current_camera = HashMap::new();
+
+        for line in json.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let parts: Vec<&str> = line.splitn(2, ':').collect();
+            if inside_cameras {
+                if line.starts_with('{') {
+                    current_camera.clear();
+                } else if line.starts_with('}') {
+                    camera_maps.push(current_camera.clone());
+                    current_camera.clear();
+                } else if parts.len() == 2 {
+                    let key = parts[0].trim_matches('"').trim();
+                    let value = parts[1].trim().trim_matches(|c| c == '"' || c == ',');
+                    current_camera.insert(key.to_string(), value.to_string());
+                }
+            } else if parts.len() == 2 {
+                let key = parts[0].trim_matches('"').trim();
+                let value = parts[1].trim().trim_matches(|c| c == '"' || c == ',');
+
+                if key == "cameras" {
+                    inside_cameras = true;
+                    continue;
+                }
+
+                config_map.insert(key.to_string(), value.to_string());
+            }
+        }
+
+        let rate = FeedRate {
+            images_per_minute: optional(&mut config_map, "images_per_minute")?
+                .unwrap_or(DEFAULT_IMAGES_PER_MINUTE),
+            incident_probability: optional(&mut config_map, "incident_probability")?
+                .unwrap_or(DEFAULT_INCIDENT_PROBABILITY),
+        };
+        check_rate(&rate)?;
+
+        let mut camera_rates = HashMap::new();
+        for mut camera in camera_maps {
+            let id = optional::<u8>(&mut camera, "id")?.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "Missing id of camera")
+            })?;
+            let camera_rate = FeedRate {
+                images_per_minute: optional(&mut camera, "images_per_minute")?
+                    .unwrap_or(rate.images_per_minute),
+                incident_probability: optional(&mut camera, "incident_probability")?
+                    .unwrap_or(rate.incident_probability),
+            };
+            check_rate(&camera_rate)?;
+            camera_rates.insert(id, camera_rate);
+        }
+
+        let incident_labels = match config_map.remove("incident_labels") {
+            Some(labels) => labels
+                .split(',')
+                .map(str::trim)
+                .filter(|label| !label.is_empty())
+                .map(str::to_string)
+                .collect(),
+            None => DEFAULT_INCIDENT_LABELS.map(str::to_string).to_vec(),
+        };
+        if incident_labels.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid incident_labels",
+            ));
+        }
+
+        Ok(Config {
+            camera_config: config_map.remove("camera_config").ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "Missing camera_config")
+            })?,
+            rate,
+            incident_labels,
+            incident_samples: config_map.remove("incident_samples"),
+            normal_samples: config_map.remove("normal_samples"),
+            duration: optional(&mut config_map, "duration")?,
+            seed: optional(&mut config_map, "seed")?,
+            camera_rates,
+        })
+    }
+
+    /// Returns the path of the configuration of the camera system, which has its cameras and
+    /// the folder of their images
+    pub fn get_camera_config(&self) -> &Path {
+        Path::new(&self.camera_config)
+    }
+
+    /// Returns how a camera takes images
+    pub fn get_rate(&self, camera_id: u8) -> FeedRate {
+        self.camera_rates
+            .get(&camera_id)
+            .copied()
+            .unwrap_or(self.rate)
+    }
+
+    /// Returns the labels of the incidents in the images
+    pub fn get_incident_labels(&self) -> &[String] {
+        &self.incident_labels
+    }
+
+    /// Returns the folder of the sample images of incidents, if they aren't generated
+    pub fn get_incident_samples(&self) -> Option<&Path> {
+        self.incident_samples.as_deref().map(Path::new)
+    }
+
+    /// Returns the folder of the sample images without incidents, if they aren't generated
+    pub fn get_normal_samples(&self) -> Option<&Path> {
+        self.normal_samples.as_deref().map(Path::new)
+    }
+
+    /// Returns how many seconds the simulation lasts, if it doesn't go on until stopped
+    pub fn get_duration(&self) -> Option<u64> {
+        self.duration
+    }
+
+    /// Returns the seed of the simulation, so a run can be repeated
+    pub fn get_seed(&self) -> Option<u64> {
+        self.seed
+    }
+}
+
+/// Reads an optional value of the configuration
+fn optional<T: FromStr>(
+    config_map: &mut HashMap<String, String>,
+    key: &str,
+) -> std::io::Result<Option<T>> {
+    match config_map.remove(key) {
+        Some(value) => value
+            .parse::<T>()
+            .map(Some)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid {}", key))),
+        None => Ok(None),
+    }
+}
+
+/// Returns an error if a camera can't take images at a rate
+fn check_rate(rate: &FeedRate) -> std::io::Result<()> {
+    if rate.images_per_minute.is_nan() || rate.images_per_minute <= 0.0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid images_per_minute",
+        ));
+    }
+    if !(0.0..=1.0).contains(&rate.incident_probability) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid incident_probability",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    #[test]
+    fn test_cameras_override_the_rate() {
+        let path = env::temp_dir().join(format!("camera-simulator-{}.json", process::id()));
+        fs::write(
+            &path,
+            r#"{
+    "camera_config": "project/camera-system/config.json",
+    "images_per_minute": 12.0,
+    "incident_labels": "fire, smoke",
+    "seed": 7,
+    "cameras": [
+        {
+            "id": 2,
+            "incident_probability": 0.5
+        }
+    ]
+}"#,
+        )
+        .unwrap();
+        let config = Config::from_file(&path);
+        let _ = fs::remove_file(&path);
+        let config = config.unwrap();
+
+        assert_eq!(
+            config.get_rate(0),
+            FeedRate {
+                images_per_minute: 12.0,
+                incident_probability: 0.05,
+            }
+        );
+        assert_eq!(
+            config.get_rate(2),
+            FeedRate {
+                images_per_minute: 12.0,
+                incident_probability: 0.5,
+            }
+        );
+        assert_eq!(config.get_incident_labels(), ["fire", "smoke"]);
+        assert_eq!(config.get_seed(), Some(7));
+        assert_eq!(config.get_duration(), None);
+        assert!(check_rate(&FeedRate {
+            images_per_minute: 0.0,
+            incident_probability: 0.5,
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,272 @@
+use std::{
+    fs, io,
+    io::Cursor,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use image::{ImageOutputFormat, Rgb, RgbImage};
+
+use crate::config::FeedRate;
+
+const IMAGE_EXTENSIONS: [&str; 3] = ["jpg", "jpeg", "png"];
+/// Extension of the images while they are written, which the camera system ignores
+const PARTIAL_EXTENSION: &str = "part";
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 240;
+
+/// Represents the images a camera takes: when it takes the next one and how many it took
+#[derive(Debug, Clone)]
+pub struct Feed {
+    pub camera_id: u8,
+    folder: PathBuf,
+    rate: FeedRate,
+    next_image: Instant,
+    taken: u64,
+}
+
+impl Feed {
+    /// Creates the feed of a camera, whose images go into its folder inside the images folder
+    pub fn new(camera_id: u8, images_folder: &Path, rate: FeedRate, now: Instant) -> Self {
+        Feed {
+            camera_id,
+            folder: images_folder.join(camera_id.to_string()),
+            rate,
+            next_image: now,
+            taken: 0,
+        }
+    }
+
+    /// Returns when the camera takes its next image
+    pub fn next_image(&self) -> Instant {
+        self.next_image
+    }
+
+    /// Returns the interval between the images of the camera
+    fn interval(&self) -> Duration {
+        Duration::from_secs_f64(60.0 / self.rate.images_per_minute)
+    }
+}
+
+/// Represents a source of images for the cameras, which generates them or copies them from
+/// folders of samples
+pub struct ImageSource {
+    random: Random,
+    incident_labels: Vec<String>,
+    incident_samples: Vec<PathBuf>,
+    normal_samples: Vec<PathBuf>,
+}
+
+impl ImageSource {
+    /// Creates a source of images. The images are generated unless there are samples of their
+    /// kind
+    pub fn new(
+        seed: u64,
+        incident_labels: Vec<String>,
+        incident_samples: Option<&Path>,
+        normal_samples: Option<&Path>,
+    ) -> io::Result<Self> {
+        Ok(ImageSource {
+            random: Random::new(seed),
+            incident_labels,
+            incident_samples: samples_in(incident_samples)?,
+            normal_samples: samples_in(normal_samples)?,
+        })
+    }
+
+    /// Takes the next image of a camera, writing it into its folder, and schedules the one
+    /// after it. Returns the label of the incident in the image, if it shows one
+    pub fn take_image(&mut self, feed: &mut Feed) -> io::Result<Option<String>> {
+        let label = if self.random.happens(feed.rate.incident_probability) {
+            let index = self.random.index(self.incident_labels.len());
+            Some(self.incident_labels[index].clone())
+        } else {
+            None
+        };
+
+        let samples = match label {
+            Some(_) => &self.incident_samples,
+            None => &self.normal_samples,
+        };
+        let (bytes, extension) = if samples.is_empty() {
+            (generate_image(&mut self.random, label.is_some())?, "png")
+        } else {
+            let sample = &samples[self.random.index(samples.len())];
+            let mut bytes = fs::read(sample)?;
+            // Decoders ignore what follows the end of the image. It keeps the camera system
+            // from skipping the copies of a sample as duplicates
+            bytes.extend_from_slice(format!("\n{}", self.random.next_u64()).as_bytes());
+            let extension = sample
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .unwrap_or("png");
+            (bytes, extension)
+        };
+
+        // The local heuristic of the camera system finds the label in the name
+        let mut name = format!("sim-{}", feed.taken);
+        if let Some(label) = &label {
+            name = format!("{}-{}", name, label);
+        }
+        write_image(&feed.folder, &name, extension, &bytes)?;
+
+        feed.taken += 1;
+        feed.next_image += feed.interval();
+        Ok(label)
+    }
+}
+
+/// Returns the images in a folder of samples, in the order of their names
+fn samples_in(folder: Option<&Path>) -> io::Result<Vec<PathBuf>> {
+    let folder = match folder {
+        Some(folder) => folder,
+        None => return Ok(vec![]),
+    };
+    let mut samples: Vec<PathBuf> = fs::read_dir(folder)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| IMAGE_EXTENSIONS.contains(&extension))
+        })
+        .collect();
+    if samples.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No sample images in {}", folder.display()),
+        ));
+    }
+    samples.sort();
+    Ok(samples)
+}
+
+/// Writes an image into the folder of a camera. It is moved into place once written, so the
+/// camera system never reads half of it
+fn write_image(folder: &Path, name: &str, extension: &str, bytes: &[u8]) -> io::Result<()> {
+    fs::create_dir_all(folder)?;
+    let partial = folder.join(format!("{}.{}", name, PARTIAL_EXTENSION));
+    fs::write(&partial, bytes)?;
+    fs::rename(partial, folder.join(format!("{}.{}", name, extension)))
+}
+
+/// Generates a street at a random time of day, as a PNG. The incidents are a blaze of orange
+/// and red in the middle of it
+fn generate_image(random: &mut Random, incident: bool) -> io::Result<Vec<u8>> {
+    let light = 0.3 + 0.7 * random.next();
+    let shade = |value: f64| (value * light).clamp(0.0, 255.0) as u8;
+    let (center_x, center_y) = (
+        (WIDTH / 4 + random.index(WIDTH as usize / 2) as u32) as f64,
+        (HEIGHT / 2 + random.index(HEIGHT as usize / 4) as u32) as f64,
+    );
+    let radius = 20.0 + 40.0 * random.next();
+
+    let mut image = RgbImage::new(WIDTH, HEIGHT);
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let noise = 20.0 * random.next();
+        *pixel = if y < HEIGHT / 2 {
+            Rgb([
+                shade(90.0 + noise),
+                shade(140.0 + noise),
+                shade(210.0 + noise),
+            ])
+        } else {
+            Rgb([
+                shade(80.0 + noise),
+                shade(80.0 + noise),
+                shade(85.0 + noise),
+            ])
+        };
+
+        let distance = ((x as f64 - center_x).powi(2) + (y as f64 - center_y).powi(2)).sqrt();
+        if incident && distance < radius {
+            let heat = 1.0 - distance / radius;
+            *pixel = Rgb([255, (80.0 + 150.0 * heat) as u8, (20.0 * heat) as u8]);
+        }
+    }
+
+    let mut bytes = Cursor::new(Vec::new());
+    image
+        .write_to(&mut bytes, ImageOutputFormat::Png)
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(bytes.into_inner())
+}
+
+/// Generates random numbers from a seed, using xorshift
+#[derive(Debug, Clone)]
+struct Random {
+    state: u64,
+}
+
+impl Random {
+    fn new(seed: u64) -> Self {
+        // The generator never leaves zero
+        Random { state: seed.max(1) }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// Returns a number between 0 and 1
+    fn next(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns a position in a list of a length
+    fn index(&mut self, length: usize) -> usize {
+        (self.next() * length as f64) as usize % length.max(1)
+    }
+
+    fn happens(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.next() < probability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn test_images_are_named_after_their_incident() {
+        let folder = env::temp_dir().join(format!("camera-simulator-images-{}", process::id()));
+        let now = Instant::now();
+        let mut source = ImageSource::new(7, vec!["fire".to_string()], None, None).unwrap();
+        let mut always = Feed::new(
+            3,
+            &folder,
+            FeedRate {
+                images_per_minute: 30.0,
+                incident_probability: 1.0,
+            },
+            now,
+        );
+        let mut never = Feed::new(
+            4,
+            &folder,
+            FeedRate {
+                images_per_minute: 30.0,
+                incident_probability: 0.0,
+            },
+            now,
+        );
+
+        let incident = source.take_image(&mut always);
+        let normal = source.take_image(&mut never);
+        let written = (
+            fs::read_dir(folder.join("3")).map(|entries| entries.count()),
+            image::open(folder.join("3").join("sim-0-fire.png")).is_ok(),
+            image::open(folder.join("4").join("sim-0.png")).is_ok(),
+        );
+        let _ = fs::remove_dir_all(&folder);
+
+        assert_eq!(incident.unwrap(), Some("fire".to_string()));
+        assert_eq!(normal.unwrap(), None);
+        assert_eq!(written.0.unwrap(), 1);
+        assert!(written.1 && written.2);
+        assert_eq!(always.next_image(), now + Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,136 @@
+//! The camera simulator is a program that feeds the cameras of the camera system with images, as if
+//! they were taking them. Some of the images show incidents, so the whole loop from their detection to
+//! the drones attending them can be shown and load tested without real images.
+
+use camera_system::config::Config as CameraConfig;
+use config::Config;
+use feed::{Feed, ImageSource};
+use std::env::args;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+mod config;
+mod feed;
+
+const CLIENT_ARGS: usize = 2;
+/// Seconds between the summaries of the images taken
+const SUMMARY_INTERVAL: u64 = 60;
+
+fn main() {
+    let argv = args().collect::<Vec<String>>();
+    if argv.len() != CLIENT_ARGS {
+        println!("Cantidad de argumentos inválidos");
+        let app_name = &argv[0];
+        println!("{:?} <config-path>", app_name);
+
+        return;
+    }
+
+    let config = match Config::from_file(Path::new(&argv[1])) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("Error reading the configuration file: {:?}", e);
+            std::process::exit(1);
+        }
+    };
+    let camera_config = match CameraConfig::from_file(config.get_camera_config()) {
+        Ok(camera_config) => camera_config,
+        Err(e) => {
+            println!(
+                "Error reading the configuration of the camera system: {:?}",
+                e
+            );
+            std::process::exit(1);
+        }
+    };
+
+    let seed = config.get_seed().unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or_default()
+    });
+    let mut source = match ImageSource::new(
+        seed,
+        config.get_incident_labels().to_vec(),
+        config.get_incident_samples(),
+        config.get_normal_samples(),
+    ) {
+        Ok(source) => source,
+        Err(e) => {
+            println!("Error reading the sample images: {:?}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let start = Instant::now();
+    let images_folder = camera_config.get_images_folder();
+    let mut feeds: Vec<Feed> = camera_config
+        .get_cameras()
+        .iter()
+        .map(|camera| {
+            Feed::new(
+                camera.id,
+                Path::new(&images_folder),
+                config.get_rate(camera.id),
+                start,
+            )
+        })
+        .collect();
+    if feeds.is_empty() {
+        println!("The camera system has no cameras");
+        return;
+    }
+    println!(
+        "Feeding {} cameras in {}",
+        feeds.len(),
+        Path::new(&images_folder).display()
+    );
+
+    run(&mut feeds, &mut source, start, config.get_duration());
+}
+
+/// Takes the images of the cameras as they are due, until the simulation ends
+fn run(feeds: &mut [Feed], source: &mut ImageSource, start: Instant, duration: Option<u64>) {
+    let end = duration.map(|duration| start + Duration::from_secs(duration));
+    let mut images = 0;
+    let mut incidents = 0;
+    let mut last_summary = start;
+
+    loop {
+        let feed = match feeds.iter_mut().min_by_key(|feed| feed.next_image()) {
+            Some(feed) => feed,
+            None => return,
+        };
+        if end.is_some_and(|end| feed.next_image() >= end) {
+            break;
+        }
+        thread::sleep(feed.next_image().saturating_duration_since(Instant::now()));
+
+        match source.take_image(feed) {
+            Ok(Some(label)) => {
+                images += 1;
+                incidents += 1;
+                println!(
+                    "Camera {} took an image of an incident: {}",
+                    feed.camera_id, label
+                );
+            }
+            Ok(None) => images += 1,
+            Err(e) => {
+                println!(
+                    "Error writing an image of camera {}: {:?}",
+                    feed.camera_id, e
+                );
+                return;
+            }
+        }
+
+        if last_summary.elapsed() >= Duration::from_secs(SUMMARY_INTERVAL) {
+            println!("{} images taken, {} of incidents", images, incidents);
+            last_summary = Instant::now();
+        }
+    }
+    println!("{} images taken, {} of incidents", images, incidents);
+}
@@ -0,0 +1,39 @@
+use std::{fmt, time::Duration};
+
+const SLEEPING: &str = "sleeping";
+const ALL: &str = "all";
+const REDUCED: &str = "reduced";
+
+/// Represents which cameras have their new images analyzed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnalysisPolicy {
+    /// Only the sleeping cameras. The images of an active camera wait until it sleeps
+    SleepingOnly,
+    /// Every camera, active or not
+    All,
+    /// Every camera, but an active one has at most an image analyzed per interval
+    ReducedWhenActive(Duration),
+}
+
+impl AnalysisPolicy {
+    /// Parses a policy, `sleeping`, `all` or `reduced`, with the interval between the analyses
+    /// of the active cameras for the reduced one
+    pub fn from_string(string: &str, active_interval: Duration) -> Result<Self, String> {
+        match string.trim() {
+            SLEEPING => Ok(AnalysisPolicy::SleepingOnly),
+            ALL => Ok(AnalysisPolicy::All),
+            REDUCED => Ok(AnalysisPolicy::ReducedWhenActive(active_interval)),
+            _ => Err(format!("Invalid analysis policy: {}", string)),
+        }
+    }
+}
+
+impl fmt::Display for AnalysisPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AnalysisPolicy::SleepingOnly => write!(f, "{}", SLEEPING),
+            AnalysisPolicy::All => write!(f, "{}", ALL),
+            AnalysisPolicy::ReducedWhenActive(_) => write!(f, "{}", REDUCED),
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    iter::Peekable,
+    path::{Path, PathBuf},
+    str::Chars,
+};
+
+use chrono::{DateTime, Local, NaiveDate};
+use common::coordenate::Coordenate;
+
+const SEPARATOR: &str = ";";
+const LIST_SEPARATOR: &str = ",";
+
+/// Represents an incident a camera recognized in an image, whether it was published or not
+#[derive(Debug, Clone, PartialEq)]
+pub struct Detection {
+    pub camera_id: u8,
+    pub label: String,
+    pub confidence: f32,
+    pub position: Coordenate,
+    /// Seconds since the epoch
+    pub timestamp: u64,
+    pub image: String,
+    /// False if it was dropped as a duplicate of a detection nearby
+    pub published: bool,
+}
+
+impl Detection {
+    /// Returns the detection as a JSON object, in a single line
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"camera_id\":{},\"label\":\"{}\",\"confidence\":{:?},\"x_coordinate\":{:?},\
+             \"y_coordinate\":{:?},\"timestamp\":{},\"image\":\"{}\",\"published\":{}}}",
+            self.camera_id,
+            escape(&self.label),
+            self.confidence,
+            self.position.x_coordinate,
+            self.position.y_coordinate,
+            self.timestamp,
+            escape(&self.image),
+            self.published
+        )
+    }
+
+    /// Parses a detection from a JSON object, as written by `to_json`
+    pub fn from_json(line: &str) -> Option<Self> {
+        let fields = json_fields(line)?;
+        let field = |key: &str| fields.get(key).map(String::as_str);
+
+        Some(Detection {
+            camera_id: field("camera_id")?.parse().ok()?,
+            label: field("label")?.to_string(),
+            confidence: field("confidence")?.parse().ok()?,
+            position: Coordenate {
+                x_coordinate: field("x_coordinate")?.parse().ok()?,
+                y_coordinate: field("y_coordinate")?.parse().ok()?,
+            },
+            timestamp: field("timestamp")?.parse().ok()?,
+            image: field("image")?.to_string(),
+            published: field("published")?.parse().ok()?,
+        })
+    }
+
+    /// Returns the local day the detection happened
+    pub fn day(&self) -> Option<NaiveDate> {
+        let timestamp = DateTime::from_timestamp(i64::try_from(self.timestamp).ok()?, 0)?;
+        Some(timestamp.with_timezone(&Local).date_naive())
+    }
+}
+
+/// Represents the file where every detection is kept, a JSON object per line, to audit the
+/// false positives and tune the thresholds later
+#[derive(Debug, Clone)]
+pub struct DetectionArchive {
+    path: PathBuf,
+}
+
+impl DetectionArchive {
+    /// Creates the archive of a file, which is created with the first detection
+    pub fn new(path: &Path) -> Self {
+        DetectionArchive {
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// Appends a detection to the archive
+    pub fn record(&self, detection: &Detection) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", detection.to_json())
+    }
+
+    /// Summarizes the detections of a day. The lines that can't be read are skipped
+    pub fn summary(&self, day: NaiveDate) -> io::Result<DetectionSummary> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+
+        let mut summary = DetectionSummary {
+            day,
+            detections: 0,
+            published: 0,
+            labels: BTreeMap::new(),
+            cameras: BTreeMap::new(),
+        };
+        for detection in content.lines().filter_map(Detection::from_json) {
+            if detection.day() == Some(day) {
+                summary.detections += 1;
+                if detection.published {
+                    summary.published += 1;
+                }
+                *summary.labels.entry(detection.label).or_insert(0) += 1;
+                *summary.cameras.entry(detection.camera_id).or_insert(0) += 1;
+            }
+        }
+        Ok(summary)
+    }
+}
+
+/// Represents how many detections there were in a day, in total, published, by label and by
+/// camera. It is sent as
+/// `<day>;<detections>;<published>;<label>:<count>,...;<camera>:<count>,...`
+#[derive(Debug, PartialEq)]
+pub struct DetectionSummary {
+    pub day: NaiveDate,
+    pub detections: usize,
+    pub published: usize,
+    pub labels: BTreeMap<String, usize>,
+    pub cameras: BTreeMap<u8, usize>,
+}
+
+impl fmt::Display for DetectionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let counts = |counts: Vec<String>| counts.join(LIST_SEPARATOR);
+        let data = [
+            self.day.to_string(),
+            self.detections.to_string(),
+            self.published.to_string(),
+            counts(
+                self.labels
+                    .iter()
+                    .map(|(label, count)| format!("{}:{}", label, count))
+                    .collect(),
+            ),
+            counts(
+                self.cameras
+                    .iter()
+                    .map(|(camera_id, count)| format!("{}:{}", camera_id, count))
+                    .collect(),
+            ),
+        ];
+        write!(f, "{}", data.join(SEPARATOR))
+    }
+}
+
+/// Escapes the quotes, backslashes and line breaks of a JSON string
+fn escape(string: &str) -> String {
+    string
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Reads the fields of a JSON object whose values are strings, numbers or booleans
+fn json_fields(line: &str) -> Option<HashMap<String, String>> {
+    let mut chars = line
+        .trim()
+        .strip_prefix('{')?
+        .strip_suffix('}')?
+        .chars()
+        .peekable();
+    let mut fields = HashMap::new();
+
+    loop {
+        skip_whitespace(&mut chars);
+        if chars.peek().is_none() {
+            return Some(fields);
+        }
+        let key = json_string(&mut chars)?;
+        skip_whitespace(&mut chars);
+        if chars.next()? != ':' {
+            return None;
+        }
+        skip_whitespace(&mut chars);
+
+        let value = if chars.peek() == Some(&'"') {
+            json_string(&mut chars)?
+        } else {
+            let mut value = String::new();
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                value.push(c);
+            }
+            value.trim().to_string()
+        };
+        fields.insert(key, value);
+
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            Some(',') => {}
+            None => return Some(fields),
+            Some(_) => return None,
+        }
+    }
+}
+
+/// Reads a JSON string, between quotes
+fn json_string(chars: &mut Peekable<Chars>) -> Option<String> {
+    if chars.next()? != '"' {
+        return None;
+    }
+    let mut string = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(string),
+            '\\' => match chars.next()? {
+                'n' => string.push('\n'),
+                c => string.push(c),
+            },
+            c => string.push(c),
+        }
+    }
+}
+
+/// Skips the whitespace between JSON tokens
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    fn detection(camera_id: u8, label: &str, timestamp: u64, published: bool) -> Detection {
+        Detection {
+            camera_id,
+            label: label.to_string(),
+            confidence: 87.5,
+            position: Coordenate {
+                x_coordinate: -58.37,
+                y_coordinate: -34.6,
+            },
+            timestamp,
+            image: "images/0/\"fire\", 1.jpg".to_string(),
+            published,
+        }
+    }
+
+    #[test]
+    fn test_detections_are_read_back() {
+        let fire = detection(0, "Fire", 1_700_000_000, true);
+        assert_eq!(Detection::from_json(&fire.to_json()), Some(fire));
+        assert_eq!(Detection::from_json("{\"camera_id\":0}"), None);
+        assert_eq!(Detection::from_json("not json"), None);
+    }
+
+    #[test]
+    fn test_summary_counts_the_detections_of_the_day() {
+        let path = env::temp_dir().join(format!("detections-{}.jsonl", process::id()));
+        let archive = DetectionArchive::new(&path);
+        let day = detection(0, "Fire", 1_700_000_000, true).day().unwrap();
+
+        let detections = [
+            detection(0, "Fire", 1_700_000_000, true),
+            detection(1, "Fire", 1_700_000_000, false),
+            detection(1, "Smoke", 1_700_000_000, true),
+            detection(1, "Smoke", 1_700_000_000 + 2 * 86_400, true),
+        ];
+        for detection in &detections {
+            archive.record(detection).unwrap();
+        }
+        let summary = archive.summary(day);
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(
+            summary.unwrap().to_string(),
+            format!("{};3;2;Fire:2,Smoke:1;0:1,1:2", day)
+        );
+    }
+}
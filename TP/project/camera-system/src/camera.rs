@@ -1,8 +1,20 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+use chrono::NaiveDateTime;
+
+use common::camera_data::CameraData;
+use common::coordenate::Coordenate;
 use common::incident::Incident;
 
 use common::camera_status::CameraStatus;
+use common::camera_system_status::CameraHealth;
+
+use incident_recognition::label_filter::LabelFilter;
+
+use crate::analysis_policy::AnalysisPolicy;
+use crate::metrics::Latency;
+use crate::schedule::Schedule;
 
 /// Represents a camera in the camera system
 #[derive(Clone, Debug)]
@@ -13,7 +25,18 @@ pub struct Camera {
     active_range: f64,
     status: CameraStatus,
     active_incidents: usize,
-    seen_images: HashSet<String>,
+    failed_images: HashMap<String, usize>,
+    /// Hashes of the contents of the last images, from the least to the most recently seen
+    recent_images: VecDeque<[u8; 32]>,
+    last_image: Option<u64>,
+    analysis_errors: usize,
+    latency: Latency,
+    /// When an image of the camera was last taken to be analyzed while it was active
+    last_active_analysis: Option<Instant>,
+    label_filter: LabelFilter,
+    schedule: Option<Schedule>,
+    /// Whether the camera was within its schedule the last time it was checked
+    on_schedule: bool,
 }
 
 impl Camera {
@@ -26,26 +49,112 @@ impl Camera {
             active_range,
             status: CameraStatus::Sleep,
             active_incidents: 0,
-            seen_images: HashSet::new(),
+            failed_images: HashMap::new(),
+            recent_images: VecDeque::new(),
+            last_image: None,
+            analysis_errors: 0,
+            latency: Latency::default(),
+            last_active_analysis: None,
+            label_filter: LabelFilter::default(),
+            schedule: None,
+            on_schedule: true,
         }
     }
 
+    /// Sets when the camera works. It is checked with `update_schedule`
+    pub fn with_schedule(mut self, schedule: Option<Schedule>) -> Self {
+        self.schedule = schedule;
+        self
+    }
+
+    /// Returns when the camera works, always if it has no schedule
+    pub fn schedule(&self) -> Option<&Schedule> {
+        self.schedule.as_ref()
+    }
+
+    /// Changes when the camera works. It is checked with `update_schedule`
+    pub fn set_schedule(&mut self, schedule: Option<Schedule>) {
+        self.schedule = schedule;
+    }
+
+    /// Returns true if the camera was within its schedule the last time it was checked
+    pub fn is_on_schedule(&self) -> bool {
+        self.on_schedule
+    }
+
+    /// Checks if the camera is within its schedule at a moment. Once out of it, the camera
+    /// stops following the incidents it had around. Returns true if it just got into it
+    pub fn update_schedule(&mut self, now: NaiveDateTime) -> bool {
+        let on_schedule = self
+            .schedule
+            .as_ref()
+            .is_none_or(|schedule| schedule.includes(now));
+        let started = on_schedule && !self.on_schedule;
+        if !on_schedule && self.on_schedule {
+            self.active_incidents = 0;
+            self.deactivate();
+        }
+        self.on_schedule = on_schedule;
+        started
+    }
+
+    /// Sets which labels of its images the camera alerts as incidents
+    pub fn with_label_filter(mut self, label_filter: LabelFilter) -> Self {
+        self.label_filter = label_filter;
+        self
+    }
+
+    /// Returns which labels of its images the camera alerts as incidents
+    pub fn label_filter(&self) -> &LabelFilter {
+        &self.label_filter
+    }
+
+    /// Changes which labels of its images the camera alerts as incidents
+    pub fn set_label_filter(&mut self, label_filter: LabelFilter) {
+        self.label_filter = label_filter;
+    }
+
+    /// Returns the distance within which the camera follows the incidents
+    pub fn active_range(&self) -> f64 {
+        self.active_range
+    }
+
     /// Returns the id of the camera
     pub fn id(&self) -> u8 {
         self.id
     }
 
-    /// Returns the data of the camera in string format
-    pub fn data(&self) -> String {
-        format!(
-            "{};{};{};{}",
-            self.id, self.x_coordinate, self.y_coordinate, self.status
-        )
+    /// Returns the data of the camera, as it is published
+    pub fn data(&self) -> CameraData {
+        CameraData {
+            id: self.id,
+            x_coordinate: self.x_coordinate,
+            y_coordinate: self.y_coordinate,
+            status: self.status,
+        }
+    }
+
+    /// Returns the position of the camera
+    pub fn coordenate(&self) -> Coordenate {
+        Coordenate {
+            x_coordinate: self.x_coordinate,
+            y_coordinate: self.y_coordinate,
+        }
+    }
+
+    /// Moves the camera, which stops following the incidents it had around
+    pub fn move_to(&mut self, x_coordinate: f64, y_coordinate: f64) {
+        self.x_coordinate = x_coordinate;
+        self.y_coordinate = y_coordinate;
+        self.active_incidents = 0;
+        self.deactivate();
     }
 
-    /// Returns the position of the camera as String
-    pub fn position(&self) -> String {
-        format!("{};{}", self.x_coordinate, self.y_coordinate)
+    /// Changes the range of the camera, which stops following the incidents it had around
+    pub fn set_active_range(&mut self, active_range: f64) {
+        self.active_range = active_range;
+        self.active_incidents = 0;
+        self.deactivate();
     }
 
     /// Increases the number of active incidents followed by the camera
@@ -86,24 +195,114 @@ impl Camera {
         distance < self.active_range
     }
 
+    /// Returns true if a position is within the active range of the camera
+    pub fn covers(&self, position: &Coordenate) -> bool {
+        let distance = euclidean_distance(
+            self.x_coordinate,
+            self.y_coordinate,
+            position.x_coordinate,
+            position.y_coordinate,
+        );
+
+        distance < self.active_range
+    }
+
     /// Return true if the camera is sleeping
     pub fn is_sleeping(&self) -> bool {
         self.status == CameraStatus::Sleep
     }
 
-    /// Returns true if the camera has already seen the image
-    pub fn has_already_seen(&self, image: &str) -> bool {
-        self.seen_images.contains(image)
+    /// Returns true if an image of the camera can be analyzed now under the policy, recording
+    /// it as taken
+    pub fn takes_image(&mut self, policy: &AnalysisPolicy) -> bool {
+        match policy {
+            AnalysisPolicy::SleepingOnly => self.is_sleeping(),
+            AnalysisPolicy::All => true,
+            AnalysisPolicy::ReducedWhenActive(interval) => {
+                if self.is_sleeping() {
+                    return true;
+                }
+                let due = self
+                    .last_active_analysis
+                    .is_none_or(|last| last.elapsed() >= *interval);
+                if due {
+                    self.last_active_analysis = Some(Instant::now());
+                }
+                due
+            }
+        }
+    }
+
+    /// Records that the camera analyzed an image, whether the analysis failed and how long it
+    /// took
+    pub fn record_analysis(&mut self, failed: bool, latency: Duration) {
+        self.last_image = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|elapsed| elapsed.as_secs());
+        if failed {
+            self.analysis_errors += 1;
+        }
+        self.latency.record(latency);
+    }
+
+    /// Returns how long the analyses of the images of the camera take, as topics with their
+    /// value
+    pub fn latency_messages(&self) -> Vec<(String, String)> {
+        self.latency.messages(self.id)
     }
 
-    /// Adds an image to the list of seen images
-    pub fn add_seen_image(&mut self, image: &str) {
-        self.seen_images.insert(image.to_string());
+    /// Returns how the image analysis of the camera is going
+    pub fn health(&self) -> CameraHealth {
+        CameraHealth {
+            id: self.id,
+            last_image: self.last_image,
+            analysis_errors: self.analysis_errors,
+        }
+    }
+
+    /// Remembers the hash of the content of an image, forgetting the least recently seen one
+    /// past `capacity`. Returns false if the camera recently saw an image with the same content
+    pub fn remember_image(&mut self, hash: [u8; 32], capacity: usize) -> bool {
+        let seen = match self.recent_images.iter().position(|recent| *recent == hash) {
+            Some(position) => {
+                self.recent_images.remove(position);
+                true
+            }
+            None => false,
+        };
+        self.recent_images.push_back(hash);
+        if self.recent_images.len() > capacity {
+            self.recent_images.pop_front();
+        }
+        !seen
+    }
+
+    /// Forgets the hash of an image, so an image with the same content is analyzed again
+    pub fn forget_image(&mut self, hash: [u8; 32]) {
+        self.recent_images.retain(|recent| *recent != hash);
+    }
+
+    /// Forgets the failed analyses of an image, which was processed
+    pub fn forget_failures(&mut self, image: &str) {
+        self.failed_images.remove(image);
+    }
+
+    /// Records that the analysis of an image failed. Returns true if the image can be analyzed
+    /// again, which it can until it fails `max_failures` times
+    pub fn retries_image(&mut self, image: &str, max_failures: usize) -> bool {
+        let failures = self.failed_images.entry(image.to_string()).or_insert(0);
+        *failures += 1;
+        if *failures >= max_failures {
+            self.failed_images.remove(image);
+            return false;
+        }
+        true
     }
 }
 
 /// Calculates the euclidean distance between two points
-fn euclidean_distance(x1: f64, y1: f64, x2: f64, y2: f64) -> f64 {
+pub fn euclidean_distance(x1: f64, y1: f64, x2: f64, y2: f64) -> f64 {
     ((x1 - x2).powi(2) + (y1 - y2).powi(2)).sqrt()
 }
 
@@ -126,7 +325,7 @@ mod tests {
     #[test]
     fn test_data() {
         let camera = Camera::new(1, 1.5, 1.5, 3.0);
-        assert_eq!(camera.data(), "1;1.5;1.5;0");
+        assert_eq!(camera.data().to_string(), "1;1.5;1.5;0");
     }
 
     #[test]
@@ -173,4 +372,57 @@ mod tests {
         );
         assert!(!camera.is_near(&incident));
     }
+
+    #[test]
+    fn test_active_cameras_take_images_as_the_policy_says() {
+        let mut camera = Camera::new(1, 1.5, 1.5, 3.0);
+        let reduced = AnalysisPolicy::ReducedWhenActive(Duration::from_secs(60));
+        assert!(camera.takes_image(&AnalysisPolicy::SleepingOnly));
+        assert!(camera.takes_image(&reduced));
+        assert!(camera.takes_image(&reduced));
+
+        camera.follow_incident();
+        assert!(!camera.takes_image(&AnalysisPolicy::SleepingOnly));
+        assert!(camera.takes_image(&AnalysisPolicy::All));
+        assert!(camera.takes_image(&reduced));
+        assert!(!camera.takes_image(&reduced));
+        assert!(camera.takes_image(&AnalysisPolicy::ReducedWhenActive(Duration::ZERO)));
+    }
+
+    #[test]
+    fn test_covers() {
+        let camera = Camera::new(1, 1.5, 1.5, 3.0);
+        let position = |x: f64, y: f64| Coordenate {
+            x_coordinate: x,
+            y_coordinate: y,
+        };
+        assert!(camera.covers(&position(2.0, 3.0)));
+        assert!(!camera.covers(&position(10.0, 10.0)));
+    }
+
+    #[test]
+    fn test_recent_images_are_remembered_by_content() {
+        let mut camera = Camera::new(1, 1.5, 1.5, 3.0);
+        assert!(camera.remember_image([1; 32], 2));
+        assert!(camera.remember_image([2; 32], 2));
+        assert!(!camera.remember_image([1; 32], 2));
+
+        // The second image is the least recently seen, so it is forgotten first
+        assert!(camera.remember_image([3; 32], 2));
+        assert!(!camera.remember_image([1; 32], 2));
+        assert!(camera.remember_image([2; 32], 2));
+
+        camera.forget_image([2; 32]);
+        assert!(camera.remember_image([2; 32], 2));
+    }
+
+    #[test]
+    fn test_failed_images_are_retried_a_few_times() {
+        let mut camera = Camera::new(1, 1.5, 1.5, 3.0);
+        assert!(camera.retries_image("fire.jpg", 2));
+        assert!(!camera.retries_image("fire.jpg", 2));
+        assert!(camera.retries_image("fire.jpg", 2));
+        camera.forget_failures("fire.jpg");
+        assert!(camera.retries_image("fire.jpg", 2));
+    }
 }
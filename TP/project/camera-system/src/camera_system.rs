@@ -1,16 +1,50 @@
+use chrono::{NaiveDate, NaiveDateTime};
 use std::collections::HashMap;
+use std::io;
+use std::time::{Duration, Instant};
 
-use crate::camera::Camera;
+use crate::analysis_policy::AnalysisPolicy;
+use crate::archive::{Detection, DetectionArchive, DetectionSummary};
+use crate::camera::{euclidean_distance, Camera};
+use crate::config::CameraConfig;
+use crate::metrics::Metrics;
+use crate::retention::RetentionPolicy;
 
+use ::common::camera_command::CameraCommand;
+use ::common::camera_data::CameraData;
+use ::common::camera_system_status::CameraSystemStatus;
+use ::common::coordenate::Coordenate;
 use ::common::incident::Incident;
+use ::common::spatial_grid::SpatialGrid;
 
-const SEPARATOR: &str = "|";
+/// Times the analysis of an image can fail before the camera system gives up on it
+const MAX_IMAGE_FAILURES: usize = 3;
+/// Images per camera whose content is remembered, to skip the duplicates
+const RECENT_IMAGES: usize = 100;
 
 /// Camera system struct
 #[derive(Debug)]
 pub struct CameraSystem {
     cameras: Vec<Camera>,
+    /// Positions of the cameras in the list, to find the ones near an incident
+    camera_grid: SpatialGrid<usize>,
+    /// Largest active range of the cameras, and size of the cells of the grid
+    max_range: f64,
     active_incidents: HashMap<String, Incident>,
+    /// Images waiting to be analyzed, with the id of their camera
+    pending_images: Vec<(u8, String)>,
+    /// Images already analyzed or skipped, with the id of their camera, which the retention
+    /// policy can remove. They are only tracked with a policy
+    processed_images: Vec<(u8, String)>,
+    retention: Option<RetentionPolicy>,
+    analysis_policy: AnalysisPolicy,
+    degraded: bool,
+    detection_radius: f64,
+    detection_window: Duration,
+    /// Positions of the detections published within the window, with when they were
+    recent_detections: Vec<(Coordenate, Instant)>,
+    archive: Option<DetectionArchive>,
+    metrics: Metrics,
 }
 
 impl Default for CameraSystem {
@@ -24,31 +58,91 @@ impl CameraSystem {
     pub fn new() -> Self {
         CameraSystem {
             cameras: vec![],
+            camera_grid: SpatialGrid::new(0.0),
+            max_range: 0.0,
             active_incidents: HashMap::new(),
+            pending_images: vec![],
+            processed_images: vec![],
+            retention: None,
+            analysis_policy: AnalysisPolicy::SleepingOnly,
+            degraded: false,
+            detection_radius: 0.0,
+            detection_window: Duration::ZERO,
+            recent_detections: vec![],
+            archive: None,
+            metrics: Metrics::default(),
         }
     }
 
+    /// Keeps every detection in an archive
+    pub fn with_archive(mut self, archive: DetectionArchive) -> Self {
+        self.archive = Some(archive);
+        self
+    }
+
+    /// Makes the detections within the radius of a published one, during the window, be
+    /// duplicates of it
+    pub fn with_detection_window(mut self, radius: f64, window: Duration) -> Self {
+        self.detection_radius = radius;
+        self.detection_window = window;
+        self
+    }
+
+    /// Removes the processed images the retention policy doesn't keep, if it removes any
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        if retention.is_enabled() {
+            self.retention = Some(retention);
+        }
+        self
+    }
+
+    /// Sets which cameras have their new images analyzed
+    pub fn with_analysis_policy(mut self, analysis_policy: AnalysisPolicy) -> Self {
+        self.analysis_policy = analysis_policy;
+        self
+    }
+
     /// Adds a camera to the camera system
     pub fn add_camera(&mut self, camera: Camera) {
-        self.cameras.push(camera)
+        self.cameras.push(camera);
+        self.index_cameras();
     }
 
-    /// Returns the data of the cameras in string format
-    pub fn cameras_data(&self) -> String {
-        let mut cameras_data = vec![];
-        for camera in self.cameras.iter() {
-            cameras_data.push(camera.data());
+    /// Indexes the cameras by position again, as they were added, removed or moved
+    fn index_cameras(&mut self) {
+        self.max_range = self
+            .cameras
+            .iter()
+            .map(Camera::active_range)
+            .fold(0.0, f64::max);
+        self.camera_grid = SpatialGrid::new(self.max_range);
+        for (index, camera) in self.cameras.iter().enumerate() {
+            self.camera_grid.insert(index, camera.coordenate());
         }
+    }
 
-        cameras_data.join(SEPARATOR)
+    /// Returns the positions in the list of the cameras that could be near an incident
+    fn cameras_around(&self, incident: &Incident) -> Vec<usize> {
+        let position = Coordenate {
+            x_coordinate: incident.x_coordinate,
+            y_coordinate: incident.y_coordinate,
+        };
+        self.camera_grid.within(&position, self.max_range)
     }
 
-    /// Handles a new incident by changing the status of the cameras that are near
+    /// Returns the data of the cameras, as it is published
+    pub fn cameras_data(&self) -> Vec<CameraData> {
+        self.cameras.iter().map(Camera::data).collect()
+    }
+
+    /// Handles a new incident by changing the status of the cameras that are near and
+    /// within their schedule
     pub fn new_incident(&mut self, incident: Incident) {
         let incident_id = incident.uuid.to_string();
 
-        for camera in self.cameras.iter_mut() {
-            if camera.is_near(&incident) {
+        for index in self.cameras_around(&incident) {
+            let camera = &mut self.cameras[index];
+            if camera.is_on_schedule() && camera.is_near(&incident) {
                 camera.follow_incident();
             }
         }
@@ -63,8 +157,9 @@ impl CameraSystem {
             None => return,
         };
 
-        for camera in &mut self.cameras {
-            if camera.is_near(incident) {
+        for index in self.cameras_around(incident) {
+            let camera = &mut self.cameras[index];
+            if camera.is_on_schedule() && camera.is_near(incident) {
                 camera.unfollow_incident();
             }
         }
@@ -72,21 +167,360 @@ impl CameraSystem {
         self.active_incidents.remove(incident_id);
     }
 
-    /// Mutable reference to the cameras
-    pub fn sleeping_cameras(&mut self) -> Vec<Camera> {
+    /// Checks which cameras are within their schedule at a moment. The ones whose schedule
+    /// just started follow the active incidents around them
+    pub fn update_schedules(&mut self, now: NaiveDateTime) {
+        for camera in self.cameras.iter_mut() {
+            if camera.update_schedule(now) {
+                follow_active_incidents(camera, &self.active_incidents);
+            }
+        }
+    }
+
+    /// Changes the cameras as the operator commanded, with the active range of the new ones.
+    /// Returns true if the layout of the cameras changed
+    pub fn handle_command(&mut self, command: CameraCommand, active_range: f64) -> bool {
+        let changed = match command {
+            CameraCommand::Add(x, y) => {
+                let id = match self.cameras.iter().map(Camera::id).max() {
+                    Some(id) => match id.checked_add(1) {
+                        Some(id) => id,
+                        None => return false,
+                    },
+                    None => 0,
+                };
+                let mut camera = Camera::new(id, x, y, active_range);
+                follow_active_incidents(&mut camera, &self.active_incidents);
+                self.cameras.push(camera);
+                true
+            }
+            CameraCommand::Remove(id) => {
+                let cameras = self.cameras.len();
+                self.cameras.retain(|camera| camera.id() != id);
+                self.cameras.len() != cameras
+            }
+            CameraCommand::Move(id, x, y) => {
+                match self.cameras.iter_mut().find(|camera| camera.id() == id) {
+                    Some(camera) => {
+                        camera.move_to(x, y);
+                        follow_active_incidents(camera, &self.active_incidents);
+                        true
+                    }
+                    None => false,
+                }
+            }
+        };
+        if changed {
+            self.index_cameras();
+        }
+        changed
+    }
+
+    /// Returns the ids of the cameras
+    pub fn camera_ids(&self) -> Vec<u8> {
+        self.cameras.iter().map(Camera::id).collect()
+    }
+
+    /// Returns the configuration of every camera, to save it. Only the ranges that aren't the
+    /// default one are kept
+    pub fn camera_configs(&self, default_range: f64) -> Vec<CameraConfig> {
         self.cameras
-            .iter_mut()
-            .filter(|camera| camera.is_sleeping())
-            .map(|camera| camera.clone())
+            .iter()
+            .map(|camera| CameraConfig {
+                id: camera.id(),
+                position: camera.coordenate(),
+                active_range: Some(camera.active_range())
+                    .filter(|active_range| *active_range != default_range),
+                label_filter: camera.label_filter().clone(),
+                schedule: camera.schedule().cloned(),
+            })
             .collect()
     }
 
-    /// Adds a seen image to the camera by camera id
-    pub fn add_seen_image(&mut self, camera_id: u8, image: &str) {
+    /// Changes the cameras to the ones of a reloaded configuration: the missing ones are
+    /// removed, the new ones added, and the ones moved or with another range follow the
+    /// active incidents around them again. Returns true if any camera changed
+    pub fn reload_cameras(&mut self, cameras: Vec<CameraConfig>, default_range: f64) -> bool {
+        let count = self.cameras.len();
+        self.cameras
+            .retain(|camera| cameras.iter().any(|config| config.id == camera.id()));
+        let mut changed = self.cameras.len() != count;
+
+        for config in cameras {
+            let active_range = config.get_active_range(default_range);
+            let position = &config.position;
+            let camera = match self
+                .cameras
+                .iter_mut()
+                .find(|camera| camera.id() == config.id)
+            {
+                Some(camera) => camera,
+                None => {
+                    let mut camera = Camera::new(
+                        config.id,
+                        position.x_coordinate,
+                        position.y_coordinate,
+                        active_range,
+                    )
+                    .with_label_filter(config.label_filter)
+                    .with_schedule(config.schedule);
+                    follow_active_incidents(&mut camera, &self.active_incidents);
+                    self.cameras.push(camera);
+                    changed = true;
+                    continue;
+                }
+            };
+
+            if camera.coordenate() != config.position || camera.active_range() != active_range {
+                camera.move_to(position.x_coordinate, position.y_coordinate);
+                camera.set_active_range(active_range);
+                follow_active_incidents(camera, &self.active_incidents);
+                changed = true;
+            }
+            if *camera.label_filter() != config.label_filter {
+                camera.set_label_filter(config.label_filter);
+                changed = true;
+            }
+            if camera.schedule() != config.schedule.as_ref() {
+                camera.set_schedule(config.schedule);
+                changed = true;
+            }
+        }
+        if changed {
+            self.index_cameras();
+        }
+        changed
+    }
+
+    /// Records that a camera analyzed an image, whether the analysis failed and how long it took
+    pub fn record_analysis(&mut self, camera_id: u8, failed: bool, latency: Duration) {
+        self.metrics.analyses += 1;
         for camera in &mut self.cameras {
             if camera.id() == camera_id {
-                camera.add_seen_image(image);
+                camera.record_analysis(failed, latency);
+            }
+        }
+    }
+
+    /// Records how many calls to AWS Rekognition failed so far
+    pub fn record_aws_failures(&mut self, aws_failures: u64) {
+        self.metrics.aws_failures = aws_failures;
+    }
+
+    /// Returns the metrics of the camera system and the latencies of its cameras, as topics with
+    /// their value
+    pub fn metric_messages(&self) -> Vec<(String, String)> {
+        let mut messages = self.metrics.messages();
+        for camera in &self.cameras {
+            messages.extend(camera.latency_messages());
+        }
+        messages
+    }
+
+    /// Records whether the last image was analyzed with a fallback recognition backend.
+    /// Returns true if the camera system entered or left the degraded mode
+    pub fn set_degraded(&mut self, degraded: bool) -> bool {
+        let changed = self.degraded != degraded;
+        self.degraded = degraded;
+        changed
+    }
+
+    /// Returns the status of the camera system, with the health of every camera
+    pub fn status(&self) -> CameraSystemStatus {
+        let cameras = self.cameras.iter().map(Camera::health).collect();
+        if self.degraded {
+            CameraSystemStatus::Degraded(cameras)
+        } else {
+            CameraSystemStatus::Online(cameras)
+        }
+    }
+
+    /// Records a detection at a position. Returns false if it is a duplicate of a detection
+    /// published nearby within the window, which is the same incident seen again by another
+    /// camera or in another image
+    pub fn publishes_detection(&mut self, position: Coordenate) -> bool {
+        let window = self.detection_window;
+        self.recent_detections
+            .retain(|(_, published)| published.elapsed() < window);
+
+        let duplicate = self.recent_detections.iter().any(|(published, _)| {
+            euclidean_distance(
+                published.x_coordinate,
+                published.y_coordinate,
+                position.x_coordinate,
+                position.y_coordinate,
+            ) <= self.detection_radius
+        });
+        if duplicate {
+            return false;
+        }
+
+        if !window.is_zero() {
+            self.recent_detections.push((position, Instant::now()));
+        }
+        true
+    }
+
+    /// Counts a detection, and keeps it in the archive if the camera system has one
+    pub fn record_detection(&mut self, detection: &Detection) -> io::Result<()> {
+        self.metrics.detections += 1;
+        match &self.archive {
+            Some(archive) => archive.record(detection),
+            None => Ok(()),
+        }
+    }
+
+    /// Summarizes the archived detections of a day, if the camera system has an archive
+    pub fn detection_summary(&self, day: NaiveDate) -> Option<io::Result<DetectionSummary>> {
+        self.archive.as_ref().map(|archive| archive.summary(day))
+    }
+
+    /// Queues an image of a camera to be analyzed, unless it is already queued
+    pub fn queue_image(&mut self, camera_id: u8, image: String) {
+        let image = (camera_id, image);
+        if !self.pending_images.contains(&image) {
+            self.pending_images.push(image);
+        }
+    }
+
+    /// Takes the queued images the analysis policy lets be analyzed now, with a copy of their
+    /// camera. The other images stay queued, the ones of removed cameras are dropped, and the
+    /// ones of cameras out of their schedule are skipped
+    pub fn take_images_to_analyze(&mut self) -> Vec<(Camera, String)> {
+        let mut images = vec![];
+        let mut skipped = vec![];
+        let cameras = &mut self.cameras;
+        let analysis_policy = &self.analysis_policy;
+        self.pending_images.retain(|(camera_id, image)| {
+            match cameras.iter_mut().find(|camera| camera.id() == *camera_id) {
+                Some(camera) if !camera.is_on_schedule() => {
+                    skipped.push((*camera_id, image.clone()));
+                    false
+                }
+                Some(camera) => {
+                    let taken = camera.takes_image(analysis_policy);
+                    if taken {
+                        images.push((camera.clone(), image.clone()));
+                    }
+                    !taken
+                }
+                None => false,
             }
+        });
+        for (camera_id, image) in skipped {
+            self.mark_processed(camera_id, &image);
+        }
+        images
+    }
+
+    /// Remembers the hash of the content of an image of a camera. Returns false if the camera
+    /// recently saw an image with the same content, which doesn't need to be analyzed
+    pub fn remember_image(&mut self, camera_id: u8, hash: [u8; 32]) -> bool {
+        self.metrics.images_scanned += 1;
+        match self
+            .cameras
+            .iter_mut()
+            .find(|camera| camera.id() == camera_id)
+        {
+            Some(camera) => camera.remember_image(hash, RECENT_IMAGES),
+            None => true,
+        }
+    }
+
+    /// Forgets the hash of an image of a camera, as its analysis failed
+    pub fn forget_image(&mut self, camera_id: u8, hash: [u8; 32]) {
+        if let Some(camera) = self
+            .cameras
+            .iter_mut()
+            .find(|camera| camera.id() == camera_id)
+        {
+            camera.forget_image(hash);
+        }
+    }
+
+    /// Records that an image of a camera was analyzed, or skipped, and won't be analyzed again
+    pub fn mark_processed(&mut self, camera_id: u8, image: &str) {
+        if let Some(camera) = self
+            .cameras
+            .iter_mut()
+            .find(|camera| camera.id() == camera_id)
+        {
+            camera.forget_failures(image);
+        }
+
+        let image = (camera_id, image.to_string());
+        if self.retention.is_some() && !self.processed_images.contains(&image) {
+            self.processed_images.push(image);
+        }
+    }
+
+    /// Returns the retention policy with the images it can remove, if the camera system has one
+    pub fn retention(&self) -> Option<(RetentionPolicy, Vec<(u8, String)>)> {
+        let retention = self.retention.clone()?;
+        Some((retention, self.processed_images.clone()))
+    }
+
+    /// Forgets an image of a camera that is no longer in its folder
+    pub fn remove_image(&mut self, camera_id: u8, image: &str) {
+        self.processed_images
+            .retain(|(id, processed)| *id != camera_id || processed != image);
+        self.pending_images
+            .retain(|(id, pending)| *id != camera_id || pending != image);
+        if let Some(camera) = self
+            .cameras
+            .iter_mut()
+            .find(|camera| camera.id() == camera_id)
+        {
+            camera.forget_failures(image);
+        }
+    }
+
+    /// Queues again an image whose analysis failed, unless it failed too many times. Returns
+    /// true if the image will be analyzed again
+    pub fn requeue_image(&mut self, camera_id: u8, image: &str) -> bool {
+        let retried = self
+            .cameras
+            .iter_mut()
+            .find(|camera| camera.id() == camera_id)
+            .is_some_and(|camera| camera.retries_image(image, MAX_IMAGE_FAILURES));
+        if retried {
+            self.queue_image(camera_id, image.to_string());
+        }
+        retried
+    }
+}
+
+/// Returns the data of the cameras that changed since the previous data, empty if none did.
+/// There is none if cameras were added or removed, which need the data of every camera
+pub fn changed_cameras_data(
+    previous: &[CameraData],
+    current: &[CameraData],
+) -> Option<Vec<CameraData>> {
+    let same_cameras = previous.len() == current.len()
+        && current
+            .iter()
+            .all(|camera| previous.iter().any(|previous| previous.id == camera.id));
+    if !same_cameras {
+        return None;
+    }
+
+    Some(
+        current
+            .iter()
+            .filter(|camera| !previous.contains(camera))
+            .cloned()
+            .collect(),
+    )
+}
+
+/// Makes the camera follow the active incidents near it, if it is within its schedule
+fn follow_active_incidents(camera: &mut Camera, active_incidents: &HashMap<String, Incident>) {
+    if !camera.is_on_schedule() {
+        return;
+    }
+    for incident in active_incidents.values() {
+        if camera.is_near(incident) {
+            camera.follow_incident();
         }
     }
 }
@@ -94,23 +528,124 @@ impl CameraSystem {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::schedule::Schedule;
     use ::common::incident::IncidentStatus;
 
+    /// Returns the data of the cameras as text, separated by `|`
+    fn cameras_text(camera_system: &CameraSystem) -> String {
+        let cameras: Vec<String> = camera_system
+            .cameras_data()
+            .iter()
+            .map(CameraData::to_string)
+            .collect();
+        cameras.join("|")
+    }
+
     #[test]
     fn test_add_camera() {
         let mut camera_system = CameraSystem::new();
         let camera = Camera::new(1_u8, 1.5, 1.5, 3.0);
         camera_system.add_camera(camera);
-        let camera_data = camera_system.cameras_data();
+        let camera_data = cameras_text(&camera_system);
         assert_eq!(camera_data, "1;1.5;1.5;0");
     }
 
+    #[test]
+    fn test_only_the_images_of_sleeping_cameras_are_analyzed() {
+        let mut camera_system = CameraSystem::new();
+        camera_system.add_camera(Camera::new(0_u8, 1.5, 1.5, 3.0));
+        camera_system.add_camera(Camera::new(1_u8, 20.0, 20.0, 3.0));
+        camera_system.new_incident(Incident::new(
+            "incident1".to_string(),
+            "incident1".to_string(),
+            "incident1".to_string(),
+            1.0,
+            1.0,
+            IncidentStatus::Pending,
+        ));
+        camera_system.queue_image(0, "images/0/a.jpg".to_string());
+        camera_system.queue_image(1, "images/1/b.jpg".to_string());
+        camera_system.queue_image(1, "images/1/b.jpg".to_string());
+        camera_system.queue_image(7, "images/7/c.jpg".to_string());
+
+        let images: Vec<(u8, String)> = camera_system
+            .take_images_to_analyze()
+            .into_iter()
+            .map(|(camera, image)| (camera.id(), image))
+            .collect();
+        assert_eq!(images, vec![(1, "images/1/b.jpg".to_string())]);
+
+        assert!(camera_system.requeue_image(1, "images/1/b.jpg"));
+        camera_system.close_incident(&"incident1".to_string());
+        assert_eq!(camera_system.take_images_to_analyze().len(), 2);
+    }
+
+    #[test]
+    fn test_active_cameras_are_analyzed_with_every_camera_policy() {
+        let mut camera_system = CameraSystem::new().with_analysis_policy(AnalysisPolicy::All);
+        camera_system.add_camera(Camera::new(0_u8, 1.5, 1.5, 3.0));
+        camera_system.new_incident(Incident::new(
+            "incident1".to_string(),
+            "incident1".to_string(),
+            "incident1".to_string(),
+            1.0,
+            1.0,
+            IncidentStatus::Pending,
+        ));
+        camera_system.queue_image(0, "images/0/a.jpg".to_string());
+        assert_eq!(camera_system.take_images_to_analyze().len(), 1);
+    }
+
+    #[test]
+    fn test_processed_images_are_tracked_with_a_retention_policy() {
+        let retention = RetentionPolicy {
+            max_age: Some(Duration::from_secs(60)),
+            max_size: None,
+            processed_folder: None,
+        };
+        let mut camera_system = CameraSystem::new();
+        camera_system.mark_processed(0, "images/0/a.jpg");
+        assert_eq!(camera_system.retention(), None);
+
+        let mut camera_system = CameraSystem::new().with_retention(retention.clone());
+        camera_system.add_camera(Camera::new(0_u8, 1.5, 1.5, 3.0));
+        camera_system.mark_processed(0, "images/0/a.jpg");
+        camera_system.mark_processed(0, "images/0/a.jpg");
+        camera_system.mark_processed(0, "images/0/b.jpg");
+        camera_system.remove_image(0, "images/0/a.jpg");
+        assert_eq!(
+            camera_system.retention(),
+            Some((retention, vec![(0, "images/0/b.jpg".to_string())]))
+        );
+    }
+
+    #[test]
+    fn test_detections_near_a_recent_one_are_duplicates() {
+        let position = |x: f64, y: f64| Coordenate {
+            x_coordinate: x,
+            y_coordinate: y,
+        };
+        let mut camera_system = CameraSystem::new();
+        assert!(camera_system.publishes_detection(position(1.0, 1.0)));
+        assert!(camera_system.publishes_detection(position(1.0, 1.0)));
+
+        let mut camera_system =
+            CameraSystem::new().with_detection_window(1.0, Duration::from_secs(60));
+        assert!(camera_system.publishes_detection(position(1.0, 1.0)));
+        assert!(!camera_system.publishes_detection(position(1.5, 1.5)));
+        assert!(camera_system.publishes_detection(position(3.0, 3.0)));
+
+        let mut camera_system = CameraSystem::new().with_detection_window(1.0, Duration::ZERO);
+        assert!(camera_system.publishes_detection(position(1.0, 1.0)));
+        assert!(camera_system.publishes_detection(position(1.0, 1.0)));
+    }
+
     #[test]
     fn test_new_incident() {
         let mut camera_system = CameraSystem::new();
         let camera = Camera::new(1_u8, 1.5, 1.5, 3.0);
         camera_system.add_camera(camera);
-        let camera_data1 = camera_system.cameras_data();
+        let camera_data1 = cameras_text(&camera_system);
         let incident = Incident::new(
             "incident1".to_string(),
             "incident1".to_string(),
@@ -120,7 +655,7 @@ mod tests {
             IncidentStatus::Pending,
         );
         camera_system.new_incident(incident.clone());
-        let camera_data2 = camera_system.cameras_data();
+        let camera_data2 = cameras_text(&camera_system);
         assert_eq!(camera_data1, "1;1.5;1.5;0");
         assert_eq!(camera_data2, "1;1.5;1.5;1");
     }
@@ -130,7 +665,7 @@ mod tests {
         let mut camera_system = CameraSystem::new();
         let camera = Camera::new(1_u8, 1.5, 1.5, 3.0);
         camera_system.add_camera(camera);
-        let camera_data1 = camera_system.cameras_data();
+        let camera_data1 = cameras_text(&camera_system);
         let incident = Incident::new(
             "incident1".to_string(),
             "incident1".to_string(),
@@ -140,11 +675,211 @@ mod tests {
             IncidentStatus::Pending,
         );
         camera_system.new_incident(incident.clone());
-        let camera_data2 = camera_system.cameras_data();
+        let camera_data2 = cameras_text(&camera_system);
         camera_system.close_incident(&incident.uuid);
-        let camera_data3 = camera_system.cameras_data();
+        let camera_data3 = cameras_text(&camera_system);
         assert_eq!(camera_data1, "1;1.5;1.5;0");
         assert_eq!(camera_data2, "1;1.5;1.5;1");
         assert_eq!(camera_data3, "1;1.5;1.5;0");
     }
+
+    #[test]
+    fn test_cameras_are_added_moved_and_removed() {
+        let mut camera_system = CameraSystem::new();
+        camera_system.add_camera(Camera::new(0_u8, 1.5, 1.5, 3.0));
+        camera_system.new_incident(Incident::new(
+            "incident1".to_string(),
+            "incident1".to_string(),
+            "incident1".to_string(),
+            1.0,
+            1.0,
+            IncidentStatus::Pending,
+        ));
+
+        // The new camera follows the incident it was placed near of
+        assert!(camera_system.handle_command(CameraCommand::Add(2.0, 2.0), 3.0));
+        assert_eq!(cameras_text(&camera_system), "0;1.5;1.5;1|1;2;2;1");
+
+        assert!(camera_system.handle_command(CameraCommand::Move(0, 10.0, 10.0), 3.0));
+        assert_eq!(cameras_text(&camera_system), "0;10;10;0|1;2;2;1");
+
+        assert!(camera_system.handle_command(CameraCommand::Remove(1), 3.0));
+        assert!(!camera_system.handle_command(CameraCommand::Remove(1), 3.0));
+        assert!(!camera_system.handle_command(CameraCommand::Move(1, 0.0, 0.0), 3.0));
+        let cameras = camera_system.camera_configs(3.0);
+        assert_eq!(cameras.len(), 1);
+        assert_eq!(cameras[0].id, 0);
+        assert_eq!(
+            cameras[0].position,
+            Coordenate {
+                x_coordinate: 10.0,
+                y_coordinate: 10.0
+            }
+        );
+    }
+
+    #[test]
+    fn test_reloaded_cameras_follow_the_incidents_in_their_range() {
+        let mut camera_system = CameraSystem::new();
+        camera_system.add_camera(Camera::new(0_u8, 1.5, 1.5, 3.0));
+        camera_system.add_camera(Camera::new(1_u8, 20.0, 20.0, 3.0));
+        camera_system.new_incident(Incident::new(
+            "incident1".to_string(),
+            "incident1".to_string(),
+            "incident1".to_string(),
+            1.0,
+            1.0,
+            IncidentStatus::Pending,
+        ));
+        let camera = |id: u8, x: f64, y: f64, active_range: Option<f64>| CameraConfig {
+            id,
+            position: Coordenate {
+                x_coordinate: x,
+                y_coordinate: y,
+            },
+            active_range,
+            label_filter: Default::default(),
+            schedule: None,
+        };
+
+        // The first camera doesn't reach the incident anymore, the second one is removed and
+        // the new one is placed near the incident
+        let cameras = vec![camera(0, 1.5, 1.5, Some(0.1)), camera(2, 2.0, 2.0, None)];
+        assert!(camera_system.reload_cameras(cameras.clone(), 3.0));
+        assert_eq!(cameras_text(&camera_system), "0;1.5;1.5;0|2;2;2;1");
+        assert_eq!(camera_system.camera_configs(3.0), cameras);
+
+        assert!(!camera_system.reload_cameras(cameras, 3.0));
+    }
+
+    #[test]
+    fn test_only_the_cameras_in_range_of_an_incident_wake_up() {
+        let mut camera_system = CameraSystem::new();
+        for id in 0..=250_u8 {
+            let (x, y) = (f64::from(id % 16), f64::from(id / 16));
+            camera_system.add_camera(Camera::new(id, x, y, 1.0));
+        }
+        // A camera with a longer range than the others reaches incidents farther away
+        camera_system.add_camera(Camera::new(255, 6.5, 7.0, 3.0));
+        camera_system.new_incident(Incident::new(
+            "incident1".to_string(),
+            "incident1".to_string(),
+            "incident1".to_string(),
+            5.0,
+            5.0,
+            IncidentStatus::Pending,
+        ));
+
+        let active: Vec<String> = cameras_text(&camera_system)
+            .split('|')
+            .filter(|data| data.ends_with(";1"))
+            .map(str::to_string)
+            .collect();
+        assert_eq!(active, vec!["85;5;5;1", "255;6.5;7;1"]);
+
+        camera_system.close_incident(&"incident1".to_string());
+        assert!(cameras_text(&camera_system)
+            .split('|')
+            .all(|data| data.ends_with(";0")));
+    }
+
+    #[test]
+    fn test_only_the_cameras_that_changed_are_sent() {
+        let mut camera_system = CameraSystem::new();
+        camera_system.add_camera(Camera::new(0_u8, 1.5, 1.5, 3.0));
+        camera_system.add_camera(Camera::new(1_u8, 20.0, 20.0, 3.0));
+        let previous = camera_system.cameras_data();
+        assert_eq!(changed_cameras_data(&previous, &previous), Some(vec![]));
+
+        camera_system.new_incident(Incident::new(
+            "incident1".to_string(),
+            "incident1".to_string(),
+            "incident1".to_string(),
+            1.0,
+            1.0,
+            IncidentStatus::Pending,
+        ));
+        let current = camera_system.cameras_data();
+        assert_eq!(
+            changed_cameras_data(&previous, &current),
+            Some(vec![current[0].clone()])
+        );
+
+        camera_system.handle_command(CameraCommand::Add(5.0, 5.0), 3.0);
+        let layout = camera_system.cameras_data();
+        assert_eq!(changed_cameras_data(&current, &layout), None);
+    }
+
+    #[test]
+    fn test_cameras_out_of_their_schedule_are_idle() {
+        let mut camera_system = CameraSystem::new();
+        let night = Schedule::from_strings(Some("20:00-06:00"), None).unwrap();
+        camera_system.add_camera(Camera::new(0_u8, 1.5, 1.5, 3.0).with_schedule(night));
+        camera_system.add_camera(Camera::new(1_u8, 2.5, 2.5, 3.0));
+        let at = |hour: u32| {
+            NaiveDate::from_ymd_opt(2024, 6, 1)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+        };
+
+        camera_system.update_schedules(at(12));
+        camera_system.queue_image(0, "day.jpg".to_string());
+        camera_system.new_incident(Incident::new(
+            "incident1".to_string(),
+            "incident1".to_string(),
+            "incident1".to_string(),
+            1.0,
+            1.0,
+            IncidentStatus::Pending,
+        ));
+        assert_eq!(cameras_text(&camera_system), "0;1.5;1.5;0|1;2.5;2.5;1");
+        assert!(camera_system.take_images_to_analyze().is_empty());
+
+        // Once its schedule starts the camera follows the incident, until it ends
+        camera_system.update_schedules(at(21));
+        assert_eq!(cameras_text(&camera_system), "0;1.5;1.5;1|1;2.5;2.5;1");
+        camera_system.update_schedules(at(12));
+        assert_eq!(cameras_text(&camera_system), "0;1.5;1.5;0|1;2.5;2.5;1");
+
+        camera_system.close_incident(&"incident1".to_string());
+        assert_eq!(cameras_text(&camera_system), "0;1.5;1.5;0|1;2.5;2.5;0");
+    }
+
+    #[test]
+    fn test_status_has_the_health_of_every_camera() {
+        let mut camera_system = CameraSystem::new();
+        camera_system.add_camera(Camera::new(0_u8, 1.5, 1.5, 3.0));
+        camera_system.add_camera(Camera::new(1_u8, 2.5, 2.5, 3.0));
+        camera_system.record_analysis(1, false, Duration::from_millis(100));
+        camera_system.record_analysis(1, true, Duration::from_millis(300));
+
+        let CameraSystemStatus::Online(cameras) = camera_system.status() else {
+            panic!("The camera system is running");
+        };
+        assert_eq!(cameras[0].last_image, None);
+        assert_eq!(cameras[0].analysis_errors, 0);
+        assert!(cameras[1].last_image.is_some());
+        assert_eq!(cameras[1].analysis_errors, 1);
+
+        let messages = camera_system.metric_messages();
+        assert!(messages.contains(&(
+            "camera-system-metrics/analyses".to_string(),
+            "2".to_string()
+        )));
+        assert!(messages.contains(&(
+            "camera-system-metrics/cameras/1/mean_latency_ms".to_string(),
+            "200".to_string()
+        )));
+        assert!(!messages
+            .iter()
+            .any(|(topic, _)| topic.contains("cameras/0/")));
+
+        assert!(camera_system.set_degraded(true));
+        assert!(!camera_system.set_degraded(true));
+        assert!(matches!(
+            camera_system.status(),
+            CameraSystemStatus::Degraded(cameras) if cameras.len() == 2
+        ));
+    }
 }
@@ -1,58 +1,106 @@
 use std::{
+    collections::HashMap,
+    fs,
     io::{ErrorKind, Write},
     net::TcpStream,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
     thread,
-    time::Duration,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
     vec,
 };
 
-use crate::{camera::Camera, camera_system::CameraSystem, config::Config};
-use aws_config::BehaviorVersion;
+use crate::{
+    archive::{Detection, DetectionArchive},
+    camera::Camera,
+    camera_system::{changed_cameras_data, CameraSystem},
+    config::Config,
+    image_location::gps_position,
+    image_watcher::{existing_images, hash_image, ImageWatcher},
+    retention::stored_images,
+    s3_source::S3Source,
+};
+use aws_config::{BehaviorVersion, SdkConfig};
+use chrono::Local;
+use common::camera_command::CameraCommand;
+use common::camera_data::CameraData;
+use common::camera_system_status::CameraSystemStatus;
+use common::detected_incident::DetectedIncident;
 use common::incident::Incident;
-use incident_recognition::aws_rekognition::is_incident;
+use incident_recognition::{
+    backend::Recognizer,
+    retry::{CircuitBreaker, RetryPolicy},
+    video::is_video,
+};
+use mqtt::errors::error::MqttError;
 use mqtt::model::{
     components::{
         encoded_string::EncodedString, login::Login, qos::QoS, topic_filter::TopicFilter,
-        topic_level::TopicLevel, topic_name::TopicName,
+        topic_level::TopicLevel, topic_name::TopicName, will::Will,
     },
     packet::Packet,
-    packets::{connect::Connect, publish::Publish, subscribe::Subscribe},
+    packets::{connect::Connect, puback::Puback, publish::Publish, subscribe::Subscribe},
     return_codes::connect_return_code::ConnectReturnCode,
 };
 use thread_pool::thread_pool::ThreadPool;
-use tokio::runtime::Runtime;
+use tokio::runtime::{Handle, Runtime};
 
 const NEW_INCIDENT: &[u8] = b"new-incident";
 const DETECTED_INCIDENT: &[u8] = b"detected-incident";
 const CLOSE_INCIDENT: &[u8] = b"close-incident";
 const CAMERA_DATA: &[u8] = b"camera-data";
+const CAMERA_UPDATE: &[u8] = b"camera-update";
+const CAMERA_COMMAND: &[u8] = b"camera-command";
+const CAMERA_SYSTEM_STATUS: &[u8] = b"camera-system-status";
+const DETECTION_SUMMARY: &[u8] = b"detection-summary";
 
 const UPDATE_DATA_INTERVAL: u64 = 2;
+const CAMERAS_SNAPSHOT_INTERVAL: u64 = 30;
 const READ_MESSAGE_INTERVAL: u64 = 1;
 const ANALYSE_IMAGES_INTERVAL: u64 = 3;
+const POLL_S3_INTERVAL: u64 = 10;
+const RETENTION_INTERVAL: u64 = 60;
+const WATCH_CONFIG_INTERVAL: u64 = 2;
 
 const CAMERA_THREADS_NUMBER: usize = 4;
 
-/// Runs the client
-pub fn client_run(config: Config) -> std::io::Result<()> {
+const AWS_ATTEMPTS: u32 = 3;
+const AWS_RETRY_DELAY: u64 = 500;
+const AWS_FAILURES_TO_PAUSE: usize = 5;
+const AWS_PAUSE: u64 = 30;
+
+const RECONNECT_INITIAL_BACKOFF_MILLIS: u64 = 500;
+const RECONNECT_MAX_BACKOFF_MILLIS: u64 = 30_000;
+
+/// Runs the client. The cameras the operators change are saved to the config file, and the
+/// cameras changed in the config file are reloaded
+pub fn client_run(config: Config, config_path: PathBuf) -> std::io::Result<()> {
     let key = *config.get_key();
     let active_range = config.get_active_range();
 
     let mut server_stream = connect_to_server(config.clone())?;
-    let mut camera_system = CameraSystem::new();
+    let mut camera_system = CameraSystem::new()
+        .with_detection_window(config.get_detection_radius(), config.get_detection_window())
+        .with_archive(DetectionArchive::new(Path::new(&config.get_archive_file())))
+        .with_retention(config.get_retention_policy())
+        .with_analysis_policy(config.get_analysis_policy());
 
-    for (i, camera) in config.get_cameras().iter().enumerate() {
+    for camera in config.get_cameras() {
         let camara = Camera::new(
-            i as u8,
-            camera.x_coordinate.to_owned(),
-            camera.y_coordinate.to_owned(),
-            active_range,
-        );
+            camera.id,
+            camera.position.x_coordinate,
+            camera.position.y_coordinate,
+            camera.get_active_range(active_range),
+        )
+        .with_label_filter(camera.label_filter)
+        .with_schedule(camera.schedule);
         camera_system.add_camera(camara);
     }
+    camera_system.update_schedules(Local::now().naive_local());
 
     let images_folder = config.get_images_folder().to_owned();
+    let s3_bucket = config.get_s3_bucket();
+    let s3_prefix = config.get_s3_prefix();
 
     make_initial_subscribes(&mut server_stream, &key);
 
@@ -66,27 +114,59 @@ pub fn client_run(config: Config) -> std::io::Result<()> {
         update_camera_system_status(server_stream_clone, camera_system_clone, &key);
     });
 
+    // The operators change the cameras both with commands and in the config file, so the
+    // config is shared to always save the last cameras
+    let shared_config = Arc::new(Mutex::new(config.clone()));
+
     let server_stream_clone = server_stream.clone();
     let camera_system_clone = camera_system.clone();
+    let config_clone = shared_config.clone();
+    let config_path_clone = config_path.clone();
 
     let thread_read = thread::spawn(move || {
-        read_incoming_packets(server_stream_clone, camera_system_clone, &key);
+        read_incoming_packets(
+            server_stream_clone,
+            camera_system_clone,
+            &key,
+            config_clone,
+            &config_path_clone,
+        );
+    });
+
+    let camera_system_clone = camera_system.clone();
+
+    let thread_watch_config = thread::spawn(move || {
+        watch_config(camera_system_clone, shared_config, &config_path);
     });
 
     let server_stream_clone = server_stream.clone();
     let camera_system_clone = camera_system.clone();
+    let images_folder_clone = images_folder.clone();
 
     let thread_image_recognition = thread::spawn(move || {
         image_recognition(
             server_stream_clone,
             camera_system_clone,
-            images_folder,
+            images_folder_clone,
             &key,
-            config.get_confidence_threshold(),
+            &config,
         );
     });
 
-    let threads = vec![thread_update, thread_read, thread_image_recognition];
+    let mut threads = vec![
+        thread_update,
+        thread_read,
+        thread_watch_config,
+        thread_image_recognition,
+    ];
+
+    if let Some(s3_bucket) = s3_bucket {
+        let camera_system_clone = camera_system.clone();
+
+        threads.push(thread::spawn(move || {
+            poll_s3_images(camera_system_clone, images_folder, s3_bucket, s3_prefix);
+        }));
+    }
 
     for thread in threads {
         match thread.join() {
@@ -100,11 +180,13 @@ pub fn client_run(config: Config) -> std::io::Result<()> {
     Ok(())
 }
 
-/// Read incoming packages in a loop
+/// Read incoming packages in a loop, reconnecting if the connection is lost
 fn read_incoming_packets(
     server_stream: Arc<Mutex<TcpStream>>,
     camera_system: Arc<Mutex<CameraSystem>>,
     key: &[u8; 32],
+    config: Arc<Mutex<Config>>,
+    config_path: &Path,
 ) {
     loop {
         let locked_stream = match server_stream.lock() {
@@ -133,6 +215,18 @@ fn read_incoming_packets(
 
         let incoming_publish = match Packet::from_bytes(&mut clone_stream, key) {
             Ok(Packet::Publish(publish)) => publish,
+            // The stream doesn't block, so only an error other than having nothing to read
+            // means the connection was closed
+            Ok(Packet::Disconnect(_)) => {
+                drop(locked_stream);
+                reconnect(&server_stream, &config, key);
+                continue;
+            }
+            Err(MqttError::IoError(e)) if e.kind() != ErrorKind::WouldBlock => {
+                drop(locked_stream);
+                reconnect(&server_stream, &config, key);
+                continue;
+            }
             _ => {
                 drop(locked_stream);
                 thread::sleep(Duration::from_secs(READ_MESSAGE_INTERVAL));
@@ -140,6 +234,17 @@ fn read_incoming_packets(
             }
         };
 
+        // The server stops delivering once too many messages go unacknowledged
+        if incoming_publish.qos() != &QoS::AtMost {
+            let puback = Puback::new(incoming_publish.package_identifier());
+            if clone_stream
+                .write_all(puback.to_bytes(key).as_slice())
+                .is_err()
+            {
+                println!("Error sending puback packet");
+            }
+        }
+
         drop(locked_stream);
 
         let topic_levels = incoming_publish.topic().levels();
@@ -150,18 +255,71 @@ fn read_incoming_packets(
             handle_new_incident(incoming_publish, cloned_camera_system);
         } else if topic_levels.len() == 2 && topic_levels[0] == CLOSE_INCIDENT {
             handle_close_incident(incoming_publish, cloned_camera_system);
+        } else if topic_levels.len() == 1 && topic_levels[0] == CAMERA_COMMAND {
+            handle_camera_command(incoming_publish, cloned_camera_system, &config, config_path);
+        }
+    }
+}
+
+/// Connects to the server again after the connection was lost, waiting longer after each
+/// failed attempt, and subscribes again. The stream is only locked to replace it once the new
+/// one is ready, so the threads that publish aren't blocked during the attempts and go on with
+/// the new one
+fn reconnect(server_stream: &Mutex<TcpStream>, config: &Mutex<Config>, key: &[u8; 32]) {
+    println!("Connection to the server lost, reconnecting");
+
+    let config = match config.lock() {
+        Ok(config) => config.clone(),
+        Err(_) => {
+            println!("Mutex was poisoned");
+            return;
+        }
+    };
+
+    let mut backoff = Duration::from_millis(RECONNECT_INITIAL_BACKOFF_MILLIS);
+    let mut new_stream = loop {
+        match connect_to_server(config.clone()) {
+            Ok(stream) => break stream,
+            Err(e) => {
+                eprintln!("Error reconnecting: {:?}. Retrying in {:?}", e, backoff);
+                thread::sleep(backoff);
+                backoff = next_backoff(backoff);
+            }
+        }
+    };
+
+    make_initial_subscribes(&mut new_stream, key);
+    match server_stream.lock() {
+        Ok(mut server_stream) => *server_stream = new_stream,
+        Err(_) => {
+            println!("Mutex was poisoned");
+            return;
         }
     }
+
+    println!("Reconnected to the server");
 }
 
-/// Periodically updates the camera system status
+/// Returns the time to wait before the next reconnection attempt
+fn next_backoff(backoff: Duration) -> Duration {
+    (backoff * 2).min(Duration::from_millis(RECONNECT_MAX_BACKOFF_MILLIS))
+}
+
+/// Periodically checks the schedules of the cameras, publishes the cameras that changed, updates
+/// the camera system status and metrics, and summarizes the detections of each day once it is
+/// over
 fn update_camera_system_status(
     server_stream: Arc<Mutex<TcpStream>>,
     camera_system: Arc<Mutex<CameraSystem>>,
     key: &[u8; 32],
 ) {
+    let mut today = Local::now().date_naive();
+    let mut published_metrics = HashMap::new();
+    let mut published_cameras = vec![];
+    let mut last_snapshot: Option<Instant> = None;
+
     loop {
-        let locked_camera_system = match camera_system.lock() {
+        let mut locked_camera_system = match camera_system.lock() {
             Ok(locked_camera_system) => locked_camera_system,
             Err(_) => {
                 println!("Mutex was poisoned");
@@ -169,14 +327,75 @@ fn update_camera_system_status(
             }
         };
 
-        let topic_name = TopicName::new(vec![CAMERA_DATA.to_vec()], false);
-        let cameras_data = locked_camera_system.cameras_data().as_bytes().to_vec();
+        locked_camera_system.update_schedules(Local::now().naive_local());
+
+        let cameras = locked_camera_system.cameras_data();
+        let status = locked_camera_system.status().to_string().into_bytes();
+        let metrics = locked_camera_system.metric_messages();
+
+        // Once a day is over its detections are summarized
+        let now = Local::now().date_naive();
+        let summary = if now != today {
+            locked_camera_system.detection_summary(today)
+        } else {
+            None
+        };
+        today = now;
 
         drop(locked_camera_system);
 
-        let cloned_server_stream = server_stream.clone();
+        // The summary is retained, so whoever audits the detections later gets the last one
+        match summary {
+            Some(Ok(summary)) => {
+                let topic_name = TopicName::new(vec![DETECTION_SUMMARY.to_vec()], false);
+                let summary = summary.to_string().into_bytes();
+                publish(topic_name, summary, server_stream.clone(), key, true);
+            }
+            Some(Err(e)) => println!("Error summarizing the detections: {:?}", e),
+            None => {}
+        }
+
+        // Only the cameras that changed are sent, and every camera once in a while or when
+        // they are added or removed. The snapshot is retained for the monitors that connect
+        let snapshot_due = last_snapshot
+            .is_none_or(|last| last.elapsed() >= Duration::from_secs(CAMERAS_SNAPSHOT_INTERVAL));
+        match changed_cameras_data(&published_cameras, &cameras) {
+            Some(changed) if !snapshot_due => {
+                if !changed.is_empty() {
+                    let topic_name = TopicName::new(vec![CAMERA_UPDATE.to_vec()], false);
+                    let changed = CameraData::list_to_json(&changed).into_bytes();
+                    publish(topic_name, changed, server_stream.clone(), key, false);
+                }
+            }
+            _ => {
+                let topic_name = TopicName::new(vec![CAMERA_DATA.to_vec()], false);
+                let cameras_data = CameraData::list_to_json(&cameras).into_bytes();
+                publish(topic_name, cameras_data, server_stream.clone(), key, true);
+                last_snapshot = Some(Instant::now());
+            }
+        }
+        published_cameras = cameras;
+
+        // The status is retained, so a monitor that connects later knows if the system is up
+        let topic_name = TopicName::new(vec![CAMERA_SYSTEM_STATUS.to_vec()], false);
+        publish(topic_name, status, server_stream.clone(), key, true);
 
-        publish(topic_name, cameras_data, cloned_server_stream, key);
+        // Every metric has a topic of its own, retained and published only when it changes
+        for (topic, value) in metrics {
+            if published_metrics.get(&topic) == Some(&value) {
+                continue;
+            }
+            let levels = topic.split('/').map(|level| level.as_bytes().to_vec());
+            let topic_name = TopicName::new(levels.collect(), false);
+            publish(
+                topic_name,
+                value.clone().into_bytes(),
+                server_stream.clone(),
+                key,
+                true,
+            );
+            published_metrics.insert(topic, value);
+        }
 
         thread::sleep(Duration::from_secs(UPDATE_DATA_INTERVAL));
     }
@@ -188,10 +407,10 @@ fn publish(
     message: Vec<u8>,
     server_stream: Arc<Mutex<TcpStream>>,
     key: &[u8; 32],
+    retain: bool,
 ) {
     let dup = false;
     let qos = QoS::AtMost;
-    let retain = false;
     let package_identifier = None;
     let message_bytes = message;
 
@@ -229,7 +448,13 @@ fn connect_to_server(config: Config) -> std::io::Result<TcpStream> {
 
     let client_id_bytes: Vec<u8> = id.as_bytes().to_vec();
     let client_id = EncodedString::new(client_id_bytes);
-    let will = None;
+    // The server tells the monitor the camera system is offline if it loses the connection
+    let will = Some(Will::new(
+        QoS::AtLeast,
+        true,
+        TopicName::new(vec![CAMERA_SYSTEM_STATUS.to_vec()], false),
+        EncodedString::from_string(&CameraSystemStatus::Offline.to_string()),
+    ));
 
     let username = EncodedString::new(username.as_bytes().to_vec());
     let password = Some(EncodedString::new(password.as_bytes().to_vec()));
@@ -293,6 +518,115 @@ fn handle_close_incident(incoming_publish: Publish, camera_system: Arc<Mutex<Cam
     drop(locked_camera_system);
 }
 
+/// Handles a command of an operator to add, remove or move a camera, saving the new layout
+/// so it is kept after restarting
+fn handle_camera_command(
+    incoming_publish: Publish,
+    camera_system: Arc<Mutex<CameraSystem>>,
+    config: &Mutex<Config>,
+    config_path: &Path,
+) {
+    let command_string = String::from_utf8_lossy(incoming_publish.message()).to_string();
+    let command = match CameraCommand::from_string(&command_string) {
+        Ok(command) => command,
+        Err(_) => {
+            println!("Malformated camera command");
+            return;
+        }
+    };
+
+    let mut locked_config = match config.lock() {
+        Ok(locked_config) => locked_config,
+        Err(_) => {
+            println!("Mutex was poisoned");
+            return;
+        }
+    };
+
+    let mut locked_camera_system = match camera_system.lock() {
+        Ok(locked_camera_system) => locked_camera_system,
+        Err(_) => {
+            println!("Mutex was poisoned");
+            return;
+        }
+    };
+
+    let active_range = locked_config.get_active_range();
+    if !locked_camera_system.handle_command(command.clone(), active_range) {
+        println!("Camera command {} has no camera to change", command);
+        return;
+    }
+    let cameras = locked_camera_system.camera_configs(active_range);
+
+    drop(locked_camera_system);
+
+    println!("Camera command {} applied", command);
+    *locked_config = locked_config.clone().with_cameras(cameras);
+    if let Err(e) = locked_config.save(config_path) {
+        println!("Error saving the cameras: {:?}", e);
+    }
+}
+
+/// Reloads the cameras when the config file changes, so their positions and ranges can be
+/// tuned without restarting. The rest of the configuration is only read at the start
+fn watch_config(
+    camera_system: Arc<Mutex<CameraSystem>>,
+    config: Arc<Mutex<Config>>,
+    config_path: &Path,
+) {
+    let modified = || fs::metadata(config_path).and_then(|m| m.modified()).ok();
+    let mut last_modified = modified();
+
+    loop {
+        thread::sleep(Duration::from_secs(WATCH_CONFIG_INTERVAL));
+
+        let now_modified = modified();
+        if now_modified == last_modified {
+            continue;
+        }
+        last_modified = now_modified;
+
+        // A file being edited can be invalid for a while, the cameras are kept until it's not
+        let new_config = match Config::from_file(config_path) {
+            Ok(new_config) => new_config,
+            Err(e) => {
+                println!("Error reloading the config: {:?}", e);
+                continue;
+            }
+        };
+
+        let mut locked_config = match config.lock() {
+            Ok(locked_config) => locked_config,
+            Err(_) => {
+                println!("Mutex was poisoned");
+                return;
+            }
+        };
+
+        let mut locked_camera_system = match camera_system.lock() {
+            Ok(locked_camera_system) => locked_camera_system,
+            Err(_) => {
+                println!("Mutex was poisoned");
+                return;
+            }
+        };
+
+        let reloaded = locked_camera_system
+            .reload_cameras(new_config.get_cameras(), new_config.get_active_range());
+
+        drop(locked_camera_system);
+
+        // Only the cameras and their default range are taken from the new file
+        *locked_config = locked_config
+            .clone()
+            .with_active_range(new_config.get_active_range())
+            .with_cameras(new_config.get_cameras());
+        if reloaded {
+            println!("Cameras reloaded from {}", config_path.display());
+        }
+    }
+}
+
 /// Make initial subscribes
 fn make_initial_subscribes(server_stream: &mut TcpStream, key: &[u8; 32]) {
     let new_incident = TopicFilter::new(vec![TopicLevel::Literal(NEW_INCIDENT.to_vec())], false);
@@ -303,7 +637,9 @@ fn make_initial_subscribes(server_stream: &mut TcpStream, key: &[u8; 32]) {
         ],
         false,
     );
-    let topics = vec![new_incident, close_incident];
+    let camera_command =
+        TopicFilter::new(vec![TopicLevel::Literal(CAMERA_COMMAND.to_vec())], false);
+    let topics = vec![new_incident, close_incident, camera_command];
     subscribe(topics, server_stream, key);
 }
 
@@ -334,10 +670,11 @@ fn image_recognition(
     camera_system: Arc<Mutex<CameraSystem>>,
     images_folder: String,
     key: &[u8; 32],
-    confidence_threshold: f32,
+    config: &Config,
 ) {
     let thread_pool = ThreadPool::new(CAMERA_THREADS_NUMBER);
 
+    // A single runtime drives the analyses of every image, from the threads of the pool
     let rt = match Runtime::new() {
         Ok(rt) => rt,
         Err(_) => {
@@ -346,14 +683,57 @@ fn image_recognition(
         }
     };
 
-    let config = rt.block_on(async {
-        aws_config::defaults(BehaviorVersion::v2024_03_28())
-            .region("us-east-2")
-            .load()
-            .await
-    });
+    let sdk_config = load_sdk_config(&rt);
+
+    let mut recognizer = Recognizer::new(
+        config.get_recognition_backends(),
+        sdk_config,
+        config.get_confidence_threshold(),
+        RetryPolicy {
+            attempts: AWS_ATTEMPTS,
+            base_delay: Duration::from_millis(AWS_RETRY_DELAY),
+        },
+        CircuitBreaker::new(AWS_FAILURES_TO_PAUSE, Duration::from_secs(AWS_PAUSE)),
+    );
+    if let Some(frames_per_second) = config.get_frames_per_second() {
+        recognizer = recognizer.with_frames_per_second(frames_per_second);
+    }
+    recognizer = recognizer.with_vision_credentials(config.get_vision_credentials());
+    let recognizer = Arc::new(recognizer);
+    let mut watcher = match ImageWatcher::new(Path::new(&images_folder)) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            println!("Error watching the images folder: {:?}", e);
+            return;
+        }
+    };
+
+    // The images that were already in the folders are analyzed too, as the new ones
+    match camera_system.lock() {
+        Ok(mut locked_camera_system) => {
+            for camera_id in locked_camera_system.camera_ids() {
+                for image in existing_images(Path::new(&images_folder), camera_id) {
+                    locked_camera_system.queue_image(camera_id, image);
+                }
+            }
+        }
+        Err(_) => {
+            println!("Mutex was poisoned");
+            return;
+        }
+    }
+
+    let mut paused = false;
+    let mut last_retention = Instant::now();
 
     loop {
+        let new_images = watcher.next_images(Duration::from_secs(ANALYSE_IMAGES_INTERVAL));
+
+        if last_retention.elapsed() >= Duration::from_secs(RETENTION_INTERVAL) {
+            apply_retention(&camera_system, Path::new(&images_folder));
+            last_retention = Instant::now();
+        }
+
         let mut locked_camera_system = match camera_system.lock() {
             Ok(locked_camera_system) => locked_camera_system,
             Err(_) => {
@@ -362,122 +742,295 @@ fn image_recognition(
             }
         };
 
-        for mut camera in locked_camera_system.sleeping_cameras() {
-            if let Some(path) = look_for_new_images(images_folder.clone(), camera.clone()) {
-                locked_camera_system.add_seen_image(camera.id(), path.as_str());
-
-                let server_stream = server_stream.clone();
-                let key = *key;
-                let config = config.clone();
-
-                // println!("Image found: {}", path);
-                thread_pool.execute(move || {
-                    analyze_image(
-                        server_stream,
-                        &mut camera,
-                        path,
-                        &key,
-                        &config,
-                        confidence_threshold,
-                    );
-                });
-                // println!("Image analyzed");
+        for (camera_id, image) in new_images {
+            locked_camera_system.queue_image(camera_id, image);
+        }
+
+        // While no backend can analyze images they stay queued, to be analyzed later
+        if !recognizer.is_available() {
+            if !paused {
+                println!("Image analysis paused after repeated failures");
+                paused = true;
             }
+            continue;
+        } else if paused {
+            println!("Image analysis resumed");
+            paused = false;
         }
 
-        drop(locked_camera_system);
-        thread::sleep(Duration::from_secs(ANALYSE_IMAGES_INTERVAL));
+        for (mut camera, path) in locked_camera_system.take_images_to_analyze() {
+            let server_stream = server_stream.clone();
+            let camera_system = camera_system.clone();
+            let key = *key;
+            let recognizer = recognizer.clone();
+            let runtime = rt.handle().clone();
+
+            thread_pool.execute(move || {
+                analyze_image(
+                    server_stream,
+                    camera_system,
+                    &mut camera,
+                    path,
+                    &key,
+                    &recognizer,
+                    &runtime,
+                );
+            });
+        }
     }
 
+    // The analyses in progress need the runtime, so the pool is joined before dropping it
     drop(thread_pool);
 }
 
-/// Looks for new images in the images folder
-fn look_for_new_images(images_folder: String, camera: Camera) -> Option<String> {
-    let folder_path = format!("{}/{}", images_folder, camera.id());
+/// Loads the configuration of the AWS clients
+fn load_sdk_config(rt: &Runtime) -> SdkConfig {
+    rt.block_on(async {
+        aws_config::defaults(BehaviorVersion::v2024_03_28())
+            .region("us-east-2")
+            .load()
+            .await
+    })
+}
 
-    let folder_entrys = match std::fs::read_dir(folder_path) {
-        Ok(folder_entrys) => folder_entrys,
+/// Downloads the images the cameras upload to the S3 bucket into their folders, in a loop,
+/// so they are analyzed as the ones copied there
+fn poll_s3_images(
+    camera_system: Arc<Mutex<CameraSystem>>,
+    images_folder: String,
+    bucket: String,
+    prefix: String,
+) {
+    let rt = match Runtime::new() {
+        Ok(rt) => rt,
         Err(_) => {
-            return None;
+            println!("Error creating runtime");
+            return;
         }
     };
 
-    for entry in folder_entrys {
-        let entry = match entry {
-            Ok(entry) => entry,
+    let sdk_config = load_sdk_config(&rt);
+    let mut source = S3Source::new(&sdk_config, bucket, prefix, Path::new(&images_folder));
+
+    loop {
+        let camera_ids: Vec<u8> = match camera_system.lock() {
+            Ok(locked_camera_system) => locked_camera_system.camera_ids(),
             Err(_) => {
-                continue;
+                println!("Mutex was poisoned");
+                return;
             }
         };
 
-        let path = entry.path();
+        match rt.block_on(source.download_new_images(&camera_ids)) {
+            Ok(0) => {}
+            Ok(downloaded) => println!("Downloaded {} images from S3", downloaded),
+            Err(e) => println!("{}", e),
+        }
 
-        if path.is_file() {
-            let path = match path.to_str() {
-                Some(path) => {
-                    if path.ends_with(".jpg") || path.ends_with(".jpeg") || path.ends_with(".png") {
-                        path.to_string()
-                    } else {
-                        continue;
-                    }
-                }
-                None => {
-                    continue;
-                }
-            };
+        thread::sleep(Duration::from_secs(POLL_S3_INTERVAL));
+    }
+}
 
-            if !camera.has_already_seen(&path) {
-                return Some(path);
+/// Removes the processed images the retention policy doesn't keep, and forgets them and the
+/// ones that are no longer in their folder
+fn apply_retention(camera_system: &Arc<Mutex<CameraSystem>>, images_folder: &Path) {
+    let (retention, camera_ids, processed_images) = match camera_system.lock() {
+        Ok(locked_camera_system) => match locked_camera_system.retention() {
+            Some((retention, processed_images)) => {
+                let camera_ids: Vec<u8> = locked_camera_system.camera_ids();
+                (retention, camera_ids, processed_images)
             }
+            None => return,
+        },
+        Err(_) => {
+            println!("Mutex was poisoned");
+            return;
+        }
+    };
+
+    let images = stored_images(images_folder, &camera_ids, &processed_images);
+    let mut removed: Vec<(u8, String)> = processed_images
+        .into_iter()
+        .filter(|(_, image)| !Path::new(image).exists())
+        .collect();
+    for image in retention.images_to_remove(&images, SystemTime::now()) {
+        match retention.remove(image) {
+            Ok(()) => removed.push((image.camera_id, image.path.clone())),
+            Err(e) => println!("Error removing {}: {:?}", image.path, e),
         }
     }
 
-    None
+    match camera_system.lock() {
+        Ok(mut locked_camera_system) => {
+            for (camera_id, image) in removed {
+                locked_camera_system.remove_image(camera_id, &image);
+            }
+        }
+        Err(_) => println!("Mutex was poisoned"),
+    }
 }
 
-/// Analyzes an image using AWS Rekognition
+/// Analyzes an image, or the frames of a video, with the recognition backends, recording how
+/// it went in the health of the camera and whether the system had to fall back from its
+/// preferred backend. An image whose analysis failed is queued again, and a copy of a recent
+/// image is skipped
 fn analyze_image(
     server_stream: Arc<Mutex<TcpStream>>,
+    camera_system: Arc<Mutex<CameraSystem>>,
     camera: &mut Camera,
     path: String,
     key: &[u8; 32],
-    config: &aws_config::SdkConfig,
-    confidence_threshold: f32,
+    recognizer: &Recognizer,
+    runtime: &Handle,
 ) {
-    let rt = match Runtime::new() {
-        Ok(rt) => rt,
+    // A copy of a recent image, even renamed, would cost a second analysis with the same result
+    let hash = match hash_image(Path::new(&path)) {
+        Ok(hash) => hash,
+        Err(e) => {
+            println!("Error reading {}: {:?}", path, e);
+            return;
+        }
+    };
+    match camera_system.lock() {
+        Ok(mut locked_camera_system) => {
+            if !locked_camera_system.remember_image(camera.id(), hash) {
+                println!("Image {} is a duplicate, skipped", path);
+                locked_camera_system.mark_processed(camera.id(), &path);
+                return;
+            }
+        }
         Err(_) => {
-            println!("Error creating runtime");
+            println!("Mutex was poisoned");
             return;
         }
+    }
+
+    let started = Instant::now();
+    let posible_label = if is_video(Path::new(&path)) {
+        runtime.block_on(recognizer.recognize_video(path.as_str(), camera.label_filter()))
+    } else {
+        runtime.block_on(recognizer.recognize(path.as_str(), camera.label_filter()))
     };
 
-    camera.add_seen_image(&path);
-    let posible_label = rt.block_on(is_incident(config, path.as_str(), confidence_threshold));
+    match camera_system.lock() {
+        Ok(mut locked_camera_system) => {
+            locked_camera_system.record_analysis(
+                camera.id(),
+                posible_label.is_err(),
+                started.elapsed(),
+            );
+            locked_camera_system.record_aws_failures(recognizer.aws_failures());
+            if let Ok((_, backend)) = &posible_label {
+                let degraded = recognizer.preferred_backend() != Some(*backend);
+                if locked_camera_system.set_degraded(degraded) {
+                    if degraded {
+                        println!("Image recognition degraded, falling back to {}", backend);
+                    } else {
+                        println!("Image recognition back to {}", backend);
+                    }
+                }
+            }
+            if posible_label.is_err() {
+                locked_camera_system.forget_image(camera.id(), hash);
+                if locked_camera_system.requeue_image(camera.id(), &path) {
+                    println!("Image {} will be analyzed again", path);
+                } else {
+                    locked_camera_system.mark_processed(camera.id(), &path);
+                }
+            } else {
+                locked_camera_system.mark_processed(camera.id(), &path);
+            }
+        }
+        Err(_) => {
+            println!("Mutex was poisoned");
+        }
+    }
 
-    if let Some(label) = posible_label {
-        alert_incident(server_stream, camera, key, label);
+    match posible_label {
+        Ok((Some((label, confidence)), _)) => {
+            // Mobile or wide-angle cameras tag where the image was taken, which is preferred
+            // to the position of the camera as long as the camera could have seen it
+            let position = gps_position(Path::new(&path))
+                .filter(|position| camera.covers(position))
+                .unwrap_or_else(|| camera.coordenate());
+            let published = match camera_system.lock() {
+                Ok(mut locked_camera_system) => {
+                    let published = locked_camera_system.publishes_detection(position.clone());
+                    let detection = Detection {
+                        camera_id: camera.id(),
+                        label: label.clone(),
+                        confidence,
+                        position: position.clone(),
+                        timestamp: SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .map(|elapsed| elapsed.as_secs())
+                            .unwrap_or_default(),
+                        image: path.clone(),
+                        published,
+                    };
+                    if let Err(e) = locked_camera_system.record_detection(&detection) {
+                        println!("Error archiving the detection: {:?}", e);
+                    }
+                    published
+                }
+                Err(_) => {
+                    println!("Mutex was poisoned");
+                    return;
+                }
+            };
+            if published {
+                let incident = DetectedIncident {
+                    camera_id: camera.id(),
+                    x_coordinate: position.x_coordinate,
+                    y_coordinate: position.y_coordinate,
+                    label,
+                    confidence: Some(confidence),
+                };
+                alert_incident(server_stream, &incident, key);
+            } else {
+                println!(
+                    "{} seen by camera {} was already detected nearby",
+                    label,
+                    camera.id()
+                );
+            }
+        }
+        Ok((None, _)) => {}
+        Err(e) => println!("Error analyzing {}: {}", path, e),
     }
 }
 
-/// Alerts an incident that was recognized by the cameras
+/// Alerts an incident that was recognized by a camera, in the topic of the camera
 fn alert_incident(
     server_stream: Arc<Mutex<TcpStream>>,
-    camera: &mut Camera,
+    incident: &DetectedIncident,
     key: &[u8; 32],
-    label: String,
 ) {
     let topic_name = TopicName::new(
         vec![
             DETECTED_INCIDENT.to_vec(),
-            camera.id().to_string().as_bytes().to_vec(),
+            incident.camera_id.to_string().as_bytes().to_vec(),
         ],
         false,
     );
-    let data = [camera.position().to_string(), label];
+    let message = incident.to_json().into_bytes();
+
+    publish(topic_name, message, server_stream, key, false);
+}
 
-    let message = data.join(";").as_bytes().to_vec();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    publish(topic_name, message, server_stream, key);
+    #[test]
+    fn test_backoff_doubles_until_the_maximum() {
+        let backoff = Duration::from_millis(RECONNECT_INITIAL_BACKOFF_MILLIS);
+        assert_eq!(next_backoff(backoff), Duration::from_millis(1000));
+
+        let backoff = Duration::from_millis(RECONNECT_MAX_BACKOFF_MILLIS - 1);
+        assert_eq!(
+            next_backoff(backoff),
+            Duration::from_millis(RECONNECT_MAX_BACKOFF_MILLIS)
+        );
+    }
 }
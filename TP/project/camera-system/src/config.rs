@@ -1,7 +1,20 @@
+use crate::analysis_policy::AnalysisPolicy;
+use crate::retention::RetentionPolicy;
+use crate::schedule::Schedule;
 use common::coordenate::Coordenate;
+use incident_recognition::{
+    backend::{RecognitionBackend, VisionCredentials},
+    label_filter::LabelFilter,
+};
 use std::collections::HashMap;
 use std::io;
-use std::{fs::File, io::Read, path::Path};
+use std::str::FromStr;
+use std::time::Duration;
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
 
 /// Represents the configuration of the camera system
 #[derive(Debug, Clone)]
@@ -14,7 +27,48 @@ pub struct Config {
     active_range: f64,
     images_folder: String,
     confidence_threshold: f32,
-    cameras: Vec<Coordenate>,
+    recognition_backends: Vec<RecognitionBackend>,
+    vision_credentials: VisionCredentials,
+    detection_radius: Option<f64>,
+    detection_window: Option<u64>,
+    frames_per_second: Option<f64>,
+    s3_bucket: Option<String>,
+    s3_prefix: Option<String>,
+    archive_file: Option<String>,
+    retention_max_age: Option<u64>,
+    retention_max_megabytes: Option<u64>,
+    processed_folder: Option<String>,
+    analysis_policy: AnalysisPolicy,
+    active_analysis_interval: Option<u64>,
+    cameras: Vec<CameraConfig>,
+}
+
+/// Seconds a detection suppresses the ones around it, unless configured
+const DEFAULT_DETECTION_WINDOW: u64 = 30;
+/// File where the detections are kept, unless configured
+const DEFAULT_ARCHIVE_FILE: &str = "detections.jsonl";
+const BYTES_PER_MEGABYTE: u64 = 1024 * 1024;
+/// Seconds between the analyses of the images of an active camera with the reduced policy,
+/// unless configured
+const DEFAULT_ACTIVE_ANALYSIS_INTERVAL: u64 = 10;
+
+/// Represents the configuration of a camera
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraConfig {
+    pub id: u8,
+    pub position: Coordenate,
+    /// Range of the camera, if it isn't the active range of the camera system
+    pub active_range: Option<f64>,
+    pub label_filter: LabelFilter,
+    /// When the camera works, always if it has none
+    pub schedule: Option<Schedule>,
+}
+
+impl CameraConfig {
+    /// Returns the active range of the camera, or the one of the camera system by default
+    pub fn get_active_range(&self, default_range: f64) -> f64 {
+        self.active_range.unwrap_or(default_range)
+    }
 }
 
 impl Config {
@@ -37,6 +91,11 @@ impl Config {
                 if line.starts_with('{') {
                     current_camera.clear();
                 } else if line.starts_with('}') {
+                    // The cameras added while the system runs keep their ids once saved
+                    let id = current_camera
+                        .get("id")
+                        .and_then(|v: &String| v.parse::<u8>().ok())
+                        .unwrap_or(cameras.len() as u8);
                     if let (Some(x), Some(y)) = (
                         current_camera
                             .get("x_coordinate")
@@ -45,9 +104,19 @@ impl Config {
                             .get("y_coordinate")
                             .and_then(|v: &String| v.parse::<f64>().ok()),
                     ) {
-                        cameras.push(Coordenate {
-                            x_coordinate: x,
-                            y_coordinate: y,
+                        cameras.push(CameraConfig {
+                            id,
+                            position: Coordenate {
+                                x_coordinate: x,
+                                y_coordinate: y,
+                            },
+                            active_range: optional(&mut current_camera, "active_range")?,
+                            label_filter: label_filter_from(&current_camera)?,
+                            schedule: Schedule::from_strings(
+                                current_camera.get("schedule_hours").map(String::as_str),
+                                current_camera.get("schedule_days").map(String::as_str),
+                            )
+                            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
                         });
                     }
                     current_camera.clear();
@@ -76,6 +145,31 @@ impl Config {
             }
         }
 
+        let active_analysis_interval = optional(&mut config_map, "active_analysis_interval")?;
+        let analysis_policy = match config_map.remove("analysis_policy") {
+            Some(policy) => AnalysisPolicy::from_string(
+                &policy,
+                Duration::from_secs(
+                    active_analysis_interval.unwrap_or(DEFAULT_ACTIVE_ANALYSIS_INTERVAL),
+                ),
+            )
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            None => AnalysisPolicy::SleepingOnly,
+        };
+
+        let recognition_backends =
+            recognition_backends_from(config_map.remove("recognition_backends"))?;
+        let vision_credentials = VisionCredentials {
+            google_api_key: config_map.remove("google_api_key"),
+            azure_endpoint: config_map.remove("azure_endpoint"),
+            azure_key: config_map.remove("azure_key"),
+        };
+        for backend in &recognition_backends {
+            vision_credentials
+                .check(*backend)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        }
+
         Ok(Config {
             address: config_map
                 .remove("address")
@@ -105,6 +199,27 @@ impl Config {
                 .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing active range"))?
                 .parse::<f32>()
                 .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid active_range"))?,
+            recognition_backends,
+            vision_credentials,
+            detection_radius: optional(&mut config_map, "detection_radius")?,
+            detection_window: optional(&mut config_map, "detection_window")?,
+            frames_per_second: match optional::<f64>(&mut config_map, "frames_per_second")? {
+                Some(rate) if rate <= 0.0 => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Invalid frames_per_second",
+                    ))
+                }
+                rate => rate,
+            },
+            s3_bucket: config_map.remove("s3_bucket"),
+            s3_prefix: config_map.remove("s3_prefix"),
+            archive_file: config_map.remove("archive_file"),
+            retention_max_age: optional(&mut config_map, "retention_max_age")?,
+            retention_max_megabytes: optional(&mut config_map, "retention_max_megabytes")?,
+            processed_folder: config_map.remove("processed_folder"),
+            analysis_policy,
+            active_analysis_interval,
             cameras,
         })
     }
@@ -139,11 +254,153 @@ impl Config {
         self.active_range
     }
 
-    /// Returns the cameras of the camera system
-    pub fn get_cameras(&self) -> Vec<Coordenate> {
+    /// Returns the configuration of the cameras of the camera system
+    pub fn get_cameras(&self) -> Vec<CameraConfig> {
         self.cameras.clone()
     }
 
+    /// Replaces the cameras of the camera system
+    pub fn with_cameras(mut self, cameras: Vec<CameraConfig>) -> Self {
+        self.cameras = cameras;
+        self
+    }
+
+    /// Replaces the active range of the cameras without one of their own
+    pub fn with_active_range(mut self, active_range: f64) -> Self {
+        self.active_range = active_range;
+        self
+    }
+
+    /// Writes the configuration to a file, in the format it is read from
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut lines = vec![
+            "{".to_string(),
+            format!("    \"address\": \"{}\",", self.address),
+            format!("    \"id\": \"{}\",", self.id),
+            format!("    \"username\": \"{}\",", self.username),
+            format!("    \"password\": \"{}\",", self.password),
+            format!("    \"key\": \"{}\",", self.key),
+            format!("    \"active_range\": {:?},", self.active_range),
+            format!("    \"images_folder\": \"{}\",", self.images_folder),
+            format!(
+                "    \"confidence_threshold\": {:?},",
+                self.confidence_threshold
+            ),
+            format!(
+                "    \"recognition_backends\": \"{}\",",
+                self.recognition_backends
+                    .iter()
+                    .map(RecognitionBackend::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+        ];
+        if let Some(google_api_key) = &self.vision_credentials.google_api_key {
+            lines.push(format!("    \"google_api_key\": \"{}\",", google_api_key));
+        }
+        if let Some(azure_endpoint) = &self.vision_credentials.azure_endpoint {
+            lines.push(format!("    \"azure_endpoint\": \"{}\",", azure_endpoint));
+        }
+        if let Some(azure_key) = &self.vision_credentials.azure_key {
+            lines.push(format!("    \"azure_key\": \"{}\",", azure_key));
+        }
+        if let Some(detection_radius) = self.detection_radius {
+            lines.push(format!("    \"detection_radius\": {:?},", detection_radius));
+        }
+        if let Some(detection_window) = self.detection_window {
+            lines.push(format!("    \"detection_window\": {},", detection_window));
+        }
+        if let Some(frames_per_second) = self.frames_per_second {
+            lines.push(format!(
+                "    \"frames_per_second\": {:?},",
+                frames_per_second
+            ));
+        }
+        if let Some(s3_bucket) = &self.s3_bucket {
+            lines.push(format!("    \"s3_bucket\": \"{}\",", s3_bucket));
+        }
+        if let Some(s3_prefix) = &self.s3_prefix {
+            lines.push(format!("    \"s3_prefix\": \"{}\",", s3_prefix));
+        }
+        if let Some(archive_file) = &self.archive_file {
+            lines.push(format!("    \"archive_file\": \"{}\",", archive_file));
+        }
+        lines.push(format!(
+            "    \"analysis_policy\": \"{}\",",
+            self.analysis_policy
+        ));
+        if let Some(active_analysis_interval) = self.active_analysis_interval {
+            lines.push(format!(
+                "    \"active_analysis_interval\": {},",
+                active_analysis_interval
+            ));
+        }
+        if let Some(retention_max_age) = self.retention_max_age {
+            lines.push(format!("    \"retention_max_age\": {},", retention_max_age));
+        }
+        if let Some(retention_max_megabytes) = self.retention_max_megabytes {
+            lines.push(format!(
+                "    \"retention_max_megabytes\": {},",
+                retention_max_megabytes
+            ));
+        }
+        if let Some(processed_folder) = &self.processed_folder {
+            lines.push(format!(
+                "    \"processed_folder\": \"{}\",",
+                processed_folder
+            ));
+        }
+        lines.push("    \"cameras\": [".to_string());
+        for (i, camera) in self.cameras.iter().enumerate() {
+            let separator = if i + 1 < self.cameras.len() { "," } else { "" };
+            let filter = &camera.label_filter;
+            let mut fields = vec![
+                format!("\"id\": {}", camera.id),
+                format!("\"x_coordinate\": {:?}", camera.position.x_coordinate),
+                format!("\"y_coordinate\": {:?}", camera.position.y_coordinate),
+            ];
+            if let Some(active_range) = camera.active_range {
+                fields.push(format!("\"active_range\": {:?}", active_range));
+            }
+            if let Some(confidence_threshold) = filter.confidence_threshold {
+                fields.push(format!(
+                    "\"confidence_threshold\": {:?}",
+                    confidence_threshold
+                ));
+            }
+            if !filter.allowed_labels.is_empty() {
+                fields.push(format!(
+                    "\"allowed_labels\": \"{}\"",
+                    filter.allowed_labels.join(",")
+                ));
+            }
+            if !filter.denied_labels.is_empty() {
+                fields.push(format!(
+                    "\"denied_labels\": \"{}\"",
+                    filter.denied_labels.join(",")
+                ));
+            }
+            if let Some(schedule) = &camera.schedule {
+                if let Some(hours) = schedule.hours_string() {
+                    fields.push(format!("\"schedule_hours\": \"{}\"", hours));
+                }
+                if let Some(days) = schedule.days_string() {
+                    fields.push(format!("\"schedule_days\": \"{}\"", days));
+                }
+            }
+            lines.push("        {".to_string());
+            lines.push(format!("            {}", fields.join(",\n            ")));
+            lines.push(format!("        }}{}", separator));
+        }
+        lines.push("    ]".to_string());
+        lines.push("}".to_string());
+
+        // The file is replaced at once, so a crash never leaves half a configuration
+        let temporary = path.with_extension("tmp");
+        File::create(&temporary)?.write_all((lines.join("\n") + "\n").as_bytes())?;
+        std::fs::rename(temporary, path)
+    }
+
     /// Returns the path of the root images folder
     pub fn get_images_folder(&self) -> String {
         self.images_folder.clone()
@@ -153,4 +410,284 @@ impl Config {
     pub fn get_confidence_threshold(&self) -> f32 {
         self.confidence_threshold
     }
+
+    /// Returns the distance around a published detection where the detections are duplicates,
+    /// by default the active range of the cameras
+    pub fn get_detection_radius(&self) -> f64 {
+        self.detection_radius.unwrap_or(self.active_range)
+    }
+
+    /// Returns how long a published detection makes the ones around it duplicates
+    pub fn get_detection_window(&self) -> Duration {
+        Duration::from_secs(self.detection_window.unwrap_or(DEFAULT_DETECTION_WINDOW))
+    }
+
+    /// Returns how many frames of each second of the videos are analyzed, if configured
+    pub fn get_frames_per_second(&self) -> Option<f64> {
+        self.frames_per_second
+    }
+
+    /// Returns the S3 bucket the cameras upload their images to, if they do
+    pub fn get_s3_bucket(&self) -> Option<String> {
+        self.s3_bucket.clone()
+    }
+
+    /// Returns the prefix of the folders of the cameras in the S3 bucket, empty by default
+    pub fn get_s3_prefix(&self) -> String {
+        self.s3_prefix.clone().unwrap_or_default()
+    }
+
+    /// Returns the path of the file where the detections are kept
+    pub fn get_archive_file(&self) -> String {
+        self.archive_file
+            .clone()
+            .unwrap_or(DEFAULT_ARCHIVE_FILE.to_string())
+    }
+
+    /// Returns how long and how much of the analyzed images are kept
+    pub fn get_retention_policy(&self) -> RetentionPolicy {
+        RetentionPolicy {
+            max_age: self.retention_max_age.map(Duration::from_secs),
+            max_size: self
+                .retention_max_megabytes
+                .map(|megabytes| megabytes.saturating_mul(BYTES_PER_MEGABYTE)),
+            processed_folder: self.processed_folder.as_ref().map(PathBuf::from),
+        }
+    }
+
+    /// Returns which cameras have their new images analyzed
+    pub fn get_analysis_policy(&self) -> AnalysisPolicy {
+        self.analysis_policy
+    }
+
+    /// Returns the backends that recognize incidents, in the order they are tried
+    pub fn get_recognition_backends(&self) -> Vec<RecognitionBackend> {
+        self.recognition_backends.clone()
+    }
+
+    /// Returns the credentials of the vision providers other than AWS
+    pub fn get_vision_credentials(&self) -> VisionCredentials {
+        self.vision_credentials.clone()
+    }
+}
+
+/// Reads an optional value of the configuration
+fn optional<T: FromStr>(
+    config_map: &mut HashMap<String, String>,
+    key: &str,
+) -> std::io::Result<Option<T>> {
+    match config_map.remove(key) {
+        Some(value) => value
+            .parse::<T>()
+            .map(Some)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid {}", key))),
+        None => Ok(None),
+    }
+}
+
+/// Reads the optional confidence threshold and labels of a camera. The labels are lists
+/// separated by commas
+fn label_filter_from(camera: &HashMap<String, String>) -> std::io::Result<LabelFilter> {
+    let confidence_threshold = match camera.get("confidence_threshold") {
+        Some(value) => Some(value.parse::<f32>().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid confidence_threshold of camera",
+            )
+        })?),
+        None => None,
+    };
+    let labels = |key: &str| -> Vec<String> {
+        camera
+            .get(key)
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|label| !label.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    Ok(LabelFilter {
+        confidence_threshold,
+        allowed_labels: labels("allowed_labels"),
+        denied_labels: labels("denied_labels"),
+    })
+}
+
+/// Reads the recognition backends, a list separated by commas. By default AWS Rekognition is
+/// used, and the local heuristic when AWS can't analyze an image
+fn recognition_backends_from(value: Option<String>) -> std::io::Result<Vec<RecognitionBackend>> {
+    let value = match value {
+        Some(value) => value,
+        None => return Ok(vec![RecognitionBackend::Aws, RecognitionBackend::Local]),
+    };
+    value
+        .split(',')
+        .map(RecognitionBackend::from_string)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    #[test]
+    fn test_saved_config_is_read_back() {
+        let path = env::temp_dir().join(format!("camera-config-{}.json", process::id()));
+        fs::write(
+            &path,
+            r#"{
+    "address": "127.0.0.1:8080",
+    "id": "camera-system",
+    "username": "camera-system",
+    "password": "sauron",
+    "key": "12345678901234567890123456789012",
+    "active_range": 0.005,
+    "images_folder": "images",
+    "confidence_threshold": 50.0,
+    "recognition_backends": "azure,aws,local",
+    "azure_endpoint": "https://sauron.cognitiveservices.azure.com/",
+    "azure_key": "0123456789abcdef",
+    "frames_per_second": 2.5,
+    "s3_bucket": "fiuba-sauron-cameras",
+    "retention_max_megabytes": 2,
+    "analysis_policy": "reduced",
+    "cameras": [
+        {
+            "x_coordinate": -58.37,
+            "y_coordinate": -34.6,
+            "active_range": 0.002,
+            "denied_labels": "Dog, Cat",
+            "schedule_days": "sat, sun"
+        }
+    ]
+}"#,
+        )
+        .unwrap();
+        let config = Config::from_file(&path).unwrap();
+        let cameras = vec![CameraConfig {
+            id: 3,
+            position: Coordenate {
+                x_coordinate: -58.38,
+                y_coordinate: -34.61,
+            },
+            active_range: Some(0.01),
+            label_filter: LabelFilter {
+                confidence_threshold: Some(70.0),
+                allowed_labels: vec!["Fire".to_string(), "Car Crash".to_string()],
+                denied_labels: vec!["Dog".to_string()],
+            },
+            schedule: Schedule::from_strings(Some("20:00-06:00"), Some("Sat,Sun")).unwrap(),
+        }];
+        assert_eq!(config.get_cameras()[0].id, 0);
+        assert_eq!(config.get_cameras()[0].get_active_range(0.005), 0.002);
+        assert_eq!(
+            config.get_cameras()[0].schedule,
+            Schedule::from_strings(None, Some("Sat,Sun")).unwrap()
+        );
+        assert_eq!(config.get_detection_radius(), config.get_active_range());
+        assert_eq!(config.get_detection_window(), Duration::from_secs(30));
+        assert_eq!(
+            config.get_cameras()[0].label_filter,
+            LabelFilter {
+                confidence_threshold: None,
+                allowed_labels: vec![],
+                denied_labels: vec!["Dog".to_string(), "Cat".to_string()],
+            }
+        );
+        assert_eq!(
+            config.get_recognition_backends(),
+            vec![
+                RecognitionBackend::Azure,
+                RecognitionBackend::Aws,
+                RecognitionBackend::Local
+            ]
+        );
+
+        config
+            .clone()
+            .with_cameras(cameras.clone())
+            .save(&path)
+            .unwrap();
+        let saved = Config::from_file(&path);
+        let _ = fs::remove_file(&path);
+        let saved = saved.unwrap();
+        assert_eq!(saved.get_cameras(), cameras);
+        assert_eq!(saved.get_active_range(), config.get_active_range());
+        assert_eq!(saved.get_images_folder(), config.get_images_folder());
+        assert_eq!(saved.get_frames_per_second(), Some(2.5));
+        assert_eq!(
+            saved.get_s3_bucket(),
+            Some("fiuba-sauron-cameras".to_string())
+        );
+        assert_eq!(saved.get_s3_prefix(), "");
+        assert_eq!(saved.get_archive_file(), "detections.jsonl");
+        assert_eq!(
+            saved.get_analysis_policy(),
+            AnalysisPolicy::ReducedWhenActive(Duration::from_secs(10))
+        );
+        assert_eq!(
+            saved.get_retention_policy(),
+            RetentionPolicy {
+                max_age: None,
+                max_size: Some(2 * 1024 * 1024),
+                processed_folder: None,
+            }
+        );
+        assert_eq!(
+            saved.get_recognition_backends(),
+            config.get_recognition_backends()
+        );
+        assert_eq!(
+            saved.get_vision_credentials(),
+            VisionCredentials {
+                google_api_key: None,
+                azure_endpoint: Some("https://sauron.cognitiveservices.azure.com/".to_string()),
+                azure_key: Some("0123456789abcdef".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_recognition_backends_are_read_in_order() {
+        assert_eq!(
+            recognition_backends_from(Some("local, aws".to_string())).unwrap(),
+            vec![RecognitionBackend::Local, RecognitionBackend::Aws]
+        );
+        assert!(recognition_backends_from(Some("local,vision".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_providers_need_their_credentials() {
+        let path = env::temp_dir().join(format!("camera-config-google-{}.json", process::id()));
+        fs::write(
+            &path,
+            r#"{
+    "address": "127.0.0.1:8080",
+    "id": "camera-system",
+    "username": "camera-system",
+    "password": "sauron",
+    "key": "12345678901234567890123456789012",
+    "active_range": 0.005,
+    "images_folder": "images",
+    "confidence_threshold": 50.0,
+    "recognition_backends": "google,local",
+    "cameras": [
+    ]
+}"#,
+        )
+        .unwrap();
+        let config = Config::from_file(&path);
+        let _ = fs::remove_file(&path);
+        assert!(config
+            .unwrap_err()
+            .to_string()
+            .contains("Missing google_api_key"));
+    }
 }
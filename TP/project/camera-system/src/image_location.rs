@@ -0,0 +1,95 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+use common::coordenate::Coordenate;
+use exif::{Exif, In, Reader, Tag, Value};
+
+/// Returns where an image was taken from the GPS tags of its EXIF metadata, if it has them.
+/// The longitude is the x coordinate and the latitude the y one, negative to the west and south
+pub fn gps_position(path: &Path) -> Option<Coordenate> {
+    let file = File::open(path).ok()?;
+    let exif = Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+
+    Some(Coordenate {
+        x_coordinate: gps_degrees(&exif, Tag::GPSLongitude, Tag::GPSLongitudeRef, b'W')?,
+        y_coordinate: gps_degrees(&exif, Tag::GPSLatitude, Tag::GPSLatitudeRef, b'S')?,
+    })
+}
+
+/// Reads a GPS angle, saved as degrees, minutes and seconds with a reference letter for its
+/// hemisphere, as decimal degrees
+fn gps_degrees(exif: &Exif, tag: Tag, reference_tag: Tag, negative_reference: u8) -> Option<f64> {
+    let degrees = match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Rational(parts) if parts.len() == 3 && parts.iter().all(|part| part.denom != 0) => {
+            parts[0].to_f64() + parts[1].to_f64() / 60.0 + parts[2].to_f64() / 3600.0
+        }
+        _ => return None,
+    };
+
+    match &exif.get_field(reference_tag, In::PRIMARY)?.value {
+        Value::Ascii(reference) => match reference.first()?.first()? {
+            letter if *letter == negative_reference => Some(-degrees),
+            _ => Some(degrees),
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exif::{experimental::Writer, Field, Rational};
+    use std::{env, fs, io::Cursor, process};
+
+    fn gps_field(tag: Tag, degrees: u32, minutes: u32, centiseconds: u32) -> Field {
+        Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value: Value::Rational(vec![
+                Rational::from((degrees, 1)),
+                Rational::from((minutes, 1)),
+                Rational::from((centiseconds, 100)),
+            ]),
+        }
+    }
+
+    fn reference_field(tag: Tag, reference: &str) -> Field {
+        Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![reference.as_bytes().to_vec()]),
+        }
+    }
+
+    #[test]
+    fn test_position_is_read_from_the_gps_tags() {
+        let fields = [
+            gps_field(Tag::GPSLatitude, 34, 36, 0),
+            reference_field(Tag::GPSLatitudeRef, "S"),
+            gps_field(Tag::GPSLongitude, 58, 22, 3000),
+            reference_field(Tag::GPSLongitudeRef, "W"),
+        ];
+        let mut writer = Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut metadata = Cursor::new(vec![]);
+        writer.write(&mut metadata, false).unwrap();
+
+        let folder = env::temp_dir().join(format!("image-location-{}", process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        let tagged = folder.join("tagged.tif");
+        let untagged = folder.join("untagged.jpg");
+        fs::write(&tagged, metadata.into_inner()).unwrap();
+        fs::write(&untagged, []).unwrap();
+
+        let positions = (gps_position(&tagged), gps_position(&untagged));
+        let _ = fs::remove_dir_all(&folder);
+
+        let position = positions.0.unwrap();
+        assert!((position.x_coordinate + 58.375).abs() < 1e-9);
+        assert!((position.y_coordinate + 34.6).abs() < 1e-9);
+        assert_eq!(positions.1, None);
+    }
+}
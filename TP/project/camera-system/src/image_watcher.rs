@@ -0,0 +1,291 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufRead, BufReader},
+    path::{Component, Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    time::{Duration, Instant},
+};
+
+use incident_recognition::video::is_video;
+use sha2::{Digest, Sha256};
+
+use notify::{
+    event::{AccessKind, AccessMode, CreateKind, ModifyKind, RenameMode},
+    Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+
+const IMAGE_EXTENSIONS: [&str; 3] = ["jpg", "jpeg", "png"];
+/// Time a created or modified file has to go without changes to be taken as completely
+/// written, on the platforms that don't tell when a file is closed
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// What an event means for the files it names
+#[derive(Debug, PartialEq)]
+enum FileEvent {
+    /// The files finished being written or were moved in
+    Finished,
+    /// The files were created or written to, and may still be being written
+    Changed,
+}
+
+/// Represents a watcher of the images folder, which has a folder per camera named as its id
+pub struct ImageWatcher {
+    // The watcher stops when dropped, so it is kept with its events
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    images_folder: PathBuf,
+    /// The images folder as the events name it, with its absolute path
+    watched_folder: PathBuf,
+    /// Files still being written, with the last time they changed
+    pending: HashMap<PathBuf, Instant>,
+}
+
+impl ImageWatcher {
+    /// Starts watching the images folder, creating it if it doesn't exist
+    pub fn new(images_folder: &Path) -> notify::Result<Self> {
+        fs::create_dir_all(images_folder)?;
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(images_folder, RecursiveMode::Recursive)?;
+
+        Ok(ImageWatcher {
+            _watcher: watcher,
+            events,
+            images_folder: images_folder.to_path_buf(),
+            watched_folder: images_folder.canonicalize()?,
+            pending: HashMap::new(),
+        })
+    }
+
+    /// Waits up to the timeout for new images, returning the camera and path of each once.
+    /// An image is new once it is closed after being written, moved into the folder of a
+    /// camera, or left unchanged for a while after being created or written to
+    pub fn next_images(&mut self, timeout: Duration) -> Vec<(u8, String)> {
+        let mut finished: Vec<PathBuf> = Vec::new();
+        let mut next_event = self.events.recv_timeout(timeout).ok();
+
+        while let Some(event) = next_event {
+            match event {
+                Ok(event) => match file_event(&event.kind) {
+                    Some(FileEvent::Finished) => {
+                        for path in event.paths {
+                            self.pending.remove(&path);
+                            if !finished.contains(&path) {
+                                finished.push(path);
+                            }
+                        }
+                    }
+                    Some(FileEvent::Changed) => {
+                        for path in event.paths {
+                            self.pending.insert(path, Instant::now());
+                        }
+                    }
+                    None => {}
+                },
+                Err(e) => println!("Error watching the images folder: {:?}", e),
+            }
+            next_event = self.events.try_recv().ok();
+        }
+
+        for path in take_settled(&mut self.pending, Instant::now()) {
+            if !finished.contains(&path) {
+                finished.push(path);
+            }
+        }
+
+        finished
+            .iter()
+            .filter_map(|path| {
+                let relative = path.strip_prefix(&self.watched_folder).ok()?;
+                camera_image(&self.images_folder, &self.images_folder.join(relative))
+            })
+            .collect()
+    }
+}
+
+/// Returns the images and videos already in the folder of a camera
+pub fn existing_images(images_folder: &Path, camera_id: u8) -> Vec<String> {
+    let folder_entries = match fs::read_dir(images_folder.join(camera_id.to_string())) {
+        Ok(folder_entries) => folder_entries,
+        Err(_) => return vec![],
+    };
+
+    folder_entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && (is_image(path) || is_video(path)))
+        .filter_map(|path| path.to_str().map(str::to_string))
+        .collect()
+}
+
+/// Hashes the content of an image, which is the same for its copies whatever their names.
+/// The file is read in chunks, so a video isn't loaded whole into memory
+pub fn hash_image(path: &Path) -> std::io::Result<[u8; 32]> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    loop {
+        let chunk = reader.fill_buf()?;
+        if chunk.is_empty() {
+            break;
+        }
+        hasher.update(chunk);
+        let length = chunk.len();
+        reader.consume(length);
+    }
+    Ok(hasher.finalize().into())
+}
+
+/// Returns what the event means for the files it names, if it is about new content
+fn file_event(kind: &EventKind) -> Option<FileEvent> {
+    match kind {
+        EventKind::Access(AccessKind::Close(AccessMode::Write))
+        | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => Some(FileEvent::Finished),
+        EventKind::Create(CreateKind::File) | EventKind::Modify(ModifyKind::Data(_)) => {
+            Some(FileEvent::Changed)
+        }
+        _ => None,
+    }
+}
+
+/// Removes and returns the pending files that haven't changed for the settle time, in the
+/// order they last changed
+fn take_settled(pending: &mut HashMap<PathBuf, Instant>, now: Instant) -> Vec<PathBuf> {
+    let mut settled: Vec<(Instant, PathBuf)> = pending
+        .iter()
+        .filter(|(_, changed_at)| now.duration_since(**changed_at) >= SETTLE_TIME)
+        .map(|(path, changed_at)| (*changed_at, path.clone()))
+        .collect();
+    settled.sort();
+
+    settled
+        .into_iter()
+        .map(|(_, path)| {
+            pending.remove(&path);
+            path
+        })
+        .collect()
+}
+
+/// Returns the camera an image or video belongs to, and its path, if the path is an image or
+/// video directly in the folder of a camera
+pub fn camera_image(images_folder: &Path, path: &Path) -> Option<(u8, String)> {
+    if !is_image(path) && !is_video(path) {
+        return None;
+    }
+
+    let mut components = path.strip_prefix(images_folder).ok()?.components();
+    let camera_id = match (components.next(), components.next(), components.next()) {
+        (Some(Component::Normal(camera)), Some(Component::Normal(_)), None) => {
+            camera.to_str()?.parse::<u8>().ok()?
+        }
+        _ => return None,
+    };
+    Some((camera_id, path.to_str()?.to_string()))
+}
+
+/// Returns true if the file has the extension of an image, whatever its case
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_images_are_matched_with_their_camera() {
+        let images_folder = Path::new("images");
+        assert_eq!(
+            camera_image(images_folder, Path::new("images/3/fire.jpg")),
+            Some((3, "images/3/fire.jpg".to_string()))
+        );
+        assert_eq!(
+            camera_image(images_folder, Path::new("images/3/fire.mp4")),
+            Some((3, "images/3/fire.mp4".to_string()))
+        );
+        assert_eq!(
+            camera_image(images_folder, Path::new("images/3/notes.txt")),
+            None
+        );
+        assert_eq!(
+            camera_image(images_folder, Path::new("images/test/fire.jpg")),
+            None
+        );
+        assert_eq!(
+            camera_image(images_folder, Path::new("images/3/old/fire.jpg")),
+            None
+        );
+        assert_eq!(
+            camera_image(images_folder, Path::new("other/3/fire.jpg")),
+            None
+        );
+        assert_eq!(
+            camera_image(images_folder, Path::new("images/3/FIRE.JPG")),
+            Some((3, "images/3/FIRE.JPG".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_events_are_classified_by_what_they_mean_for_the_file() {
+        assert_eq!(
+            file_event(&EventKind::Access(AccessKind::Close(AccessMode::Write))),
+            Some(FileEvent::Finished)
+        );
+        assert_eq!(
+            file_event(&EventKind::Modify(ModifyKind::Name(RenameMode::To))),
+            Some(FileEvent::Finished)
+        );
+        assert_eq!(
+            file_event(&EventKind::Create(CreateKind::File)),
+            Some(FileEvent::Changed)
+        );
+        assert_eq!(
+            file_event(&EventKind::Modify(ModifyKind::Data(
+                notify::event::DataChange::Content
+            ))),
+            Some(FileEvent::Changed)
+        );
+        assert_eq!(file_event(&EventKind::Create(CreateKind::Folder)), None);
+        assert_eq!(file_event(&EventKind::Access(AccessKind::Read)), None);
+    }
+
+    #[test]
+    fn test_files_are_taken_once_they_settle() {
+        let now = Instant::now();
+        let mut pending = HashMap::from([
+            (PathBuf::from("images/3/old.jpg"), now),
+            (
+                PathBuf::from("images/3/new.jpg"),
+                now + Duration::from_millis(400),
+            ),
+        ]);
+
+        assert!(take_settled(&mut pending, now + Duration::from_millis(100)).is_empty());
+        assert_eq!(
+            take_settled(&mut pending, now + SETTLE_TIME),
+            vec![PathBuf::from("images/3/old.jpg")]
+        );
+        // A file is only taken once
+        assert!(take_settled(&mut pending, now + SETTLE_TIME).is_empty());
+        assert_eq!(
+            take_settled(&mut pending, now + Duration::from_secs(1)),
+            vec![PathBuf::from("images/3/new.jpg")]
+        );
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_files_longer_than_a_chunk_are_hashed_whole() {
+        let path = std::env::temp_dir().join(format!("hash-image-{}.mp4", std::process::id()));
+        let content: Vec<u8> = (0..20_000u32).map(|byte| byte as u8).collect();
+        fs::write(&path, &content).unwrap();
+        let hash = hash_image(&path);
+        let _ = fs::remove_file(&path);
+
+        let expected: [u8; 32] = Sha256::digest(&content).into();
+        assert_eq!(hash.unwrap(), expected);
+    }
+}
@@ -1,4 +1,12 @@
+pub mod analysis_policy;
+pub mod archive;
 pub mod camera;
 pub mod camera_system;
 pub mod client;
 pub mod config;
+pub mod image_location;
+pub mod image_watcher;
+pub mod metrics;
+pub mod retention;
+pub mod s3_source;
+pub mod schedule;
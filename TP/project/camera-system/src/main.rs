@@ -5,11 +5,19 @@ use config::Config;
 use std::env::args;
 use std::path::Path;
 
+mod analysis_policy;
+mod archive;
 mod camera;
 mod camera_system;
 mod client;
 
 mod config;
+mod image_location;
+mod image_watcher;
+mod metrics;
+mod retention;
+mod s3_source;
+mod schedule;
 
 const CLIENT_ARGS: usize = 2;
 
@@ -33,7 +41,7 @@ fn main() {
         }
     };
 
-    if let Err(e) = client::client_run(config) {
+    if let Err(e) = client::client_run(config, path.to_path_buf()) {
         println!("Error: {:?}", e);
     }
 }
@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+/// Root of the topics where the metrics of the camera system are published
+const METRICS_TOPIC: &str = "camera-system-metrics";
+
+/// Represents the counters of the image analysis of the camera system, published so the health
+/// of the vision pipeline can be observed
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Metrics {
+    /// Images and videos read from the folders of the cameras, duplicates included
+    pub images_scanned: u64,
+    /// Images and videos analyzed by the recognition backends, failed or not
+    pub analyses: u64,
+    /// Calls to AWS Rekognition that failed after their retries
+    pub aws_failures: u64,
+    /// Incidents found over the confidence threshold, published or not
+    pub detections: u64,
+}
+
+impl Metrics {
+    /// Returns the counters as topics with their value
+    pub fn messages(&self) -> Vec<(String, String)> {
+        [
+            ("images_scanned", self.images_scanned),
+            ("analyses", self.analyses),
+            ("aws_failures", self.aws_failures),
+            ("detections", self.detections),
+        ]
+        .iter()
+        .map(|(metric, value)| (format!("{}/{}", METRICS_TOPIC, metric), value.to_string()))
+        .collect()
+    }
+}
+
+/// Represents how long the analyses of the images of a camera take
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Latency {
+    analyses: u32,
+    total: Duration,
+    last: Duration,
+    max: Duration,
+}
+
+impl Latency {
+    /// Records how long an analysis took
+    pub fn record(&mut self, latency: Duration) {
+        self.analyses = self.analyses.saturating_add(1);
+        self.total += latency;
+        self.last = latency;
+        self.max = self.max.max(latency);
+    }
+
+    /// Returns the latencies of a camera, in milliseconds, as topics with their value. A camera
+    /// that analyzed no image has none
+    pub fn messages(&self, camera_id: u8) -> Vec<(String, String)> {
+        if self.analyses == 0 {
+            return vec![];
+        }
+        let mean = self.total / self.analyses;
+        [
+            ("last_latency_ms", self.last),
+            ("mean_latency_ms", mean),
+            ("max_latency_ms", self.max),
+        ]
+        .iter()
+        .map(|(metric, latency)| {
+            (
+                format!("{}/cameras/{}/{}", METRICS_TOPIC, camera_id, metric),
+                latency.as_millis().to_string(),
+            )
+        })
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latencies_are_published_in_milliseconds() {
+        let mut latency = Latency::default();
+        assert_eq!(latency.messages(2), vec![]);
+
+        latency.record(Duration::from_millis(300));
+        latency.record(Duration::from_millis(100));
+        assert_eq!(
+            latency.messages(2),
+            vec![
+                (
+                    "camera-system-metrics/cameras/2/last_latency_ms".to_string(),
+                    "100".to_string()
+                ),
+                (
+                    "camera-system-metrics/cameras/2/mean_latency_ms".to_string(),
+                    "200".to_string()
+                ),
+                (
+                    "camera-system-metrics/cameras/2/max_latency_ms".to_string(),
+                    "300".to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::image_watcher::existing_images;
+
+/// Represents how long and how much of the analyzed images are kept in the images folder
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetentionPolicy {
+    pub max_age: Option<Duration>,
+    /// Bytes the images of the cameras can take in total
+    pub max_size: Option<u64>,
+    /// Folder where the removed images are moved to, in the folder of their camera. They are
+    /// deleted if there is none
+    pub processed_folder: Option<PathBuf>,
+}
+
+/// Represents an image in the folder of a camera
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredImage {
+    pub camera_id: u8,
+    pub path: String,
+    pub size: u64,
+    pub modified: SystemTime,
+    /// False while the image waits to be analyzed, when it can't be removed
+    pub processed: bool,
+}
+
+impl RetentionPolicy {
+    /// Returns true if the policy removes any image
+    pub fn is_enabled(&self) -> bool {
+        self.max_age.is_some() || self.max_size.is_some()
+    }
+
+    /// Returns the processed images to remove, from the oldest: the ones older than the max age,
+    /// and the oldest ones while the images take more than the max size
+    pub fn images_to_remove<'a>(
+        &self,
+        images: &'a [StoredImage],
+        now: SystemTime,
+    ) -> Vec<&'a StoredImage> {
+        let mut images: Vec<&StoredImage> = images.iter().collect();
+        images.sort_by_key(|image| image.modified);
+        let mut total_size: u64 = images.iter().map(|image| image.size).sum();
+
+        let mut removed = vec![];
+        for image in images.into_iter().filter(|image| image.processed) {
+            let age = now.duration_since(image.modified).unwrap_or_default();
+            let too_old = self.max_age.is_some_and(|max_age| age > max_age);
+            let too_big = self.max_size.is_some_and(|max_size| total_size > max_size);
+            if too_old || too_big {
+                total_size -= image.size;
+                removed.push(image);
+            }
+        }
+        removed
+    }
+
+    /// Moves an image to the processed folder, or deletes it if there is none
+    pub fn remove(&self, image: &StoredImage) -> io::Result<()> {
+        let folder = match &self.processed_folder {
+            Some(folder) => folder.join(image.camera_id.to_string()),
+            None => return fs::remove_file(&image.path),
+        };
+        let name = Path::new(&image.path)
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid image path"))?;
+
+        fs::create_dir_all(&folder)?;
+        fs::rename(&image.path, folder.join(name))
+    }
+}
+
+/// Returns the images in the folders of the cameras, with whether they were processed. The ones
+/// that can't be read are skipped
+pub fn stored_images(
+    images_folder: &Path,
+    camera_ids: &[u8],
+    processed_images: &[(u8, String)],
+) -> Vec<StoredImage> {
+    let mut images = vec![];
+    for camera_id in camera_ids {
+        for path in existing_images(images_folder, *camera_id) {
+            let metadata = match fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            images.push(StoredImage {
+                camera_id: *camera_id,
+                processed: processed_images.contains(&(*camera_id, path.clone())),
+                path,
+                size: metadata.len(),
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            });
+        }
+    }
+    images
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(name: &str, size: u64, age: u64, processed: bool) -> StoredImage {
+        StoredImage {
+            camera_id: 0,
+            path: name.to_string(),
+            size,
+            modified: SystemTime::UNIX_EPOCH + Duration::from_secs(1000 - age),
+            processed,
+        }
+    }
+
+    #[test]
+    fn test_old_processed_images_are_removed_first() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let images = [
+            image("new.jpg", 10, 10, true),
+            image("pending.jpg", 10, 500, false),
+            image("old.jpg", 10, 300, true),
+            image("older.jpg", 10, 400, true),
+        ];
+        let removed = |policy: RetentionPolicy| -> Vec<String> {
+            policy
+                .images_to_remove(&images, now)
+                .iter()
+                .map(|image| image.path.clone())
+                .collect()
+        };
+        let policy = RetentionPolicy {
+            max_age: None,
+            max_size: None,
+            processed_folder: None,
+        };
+
+        assert!(!policy.is_enabled());
+        assert!(removed(policy.clone()).is_empty());
+        assert_eq!(
+            removed(RetentionPolicy {
+                max_age: Some(Duration::from_secs(200)),
+                ..policy.clone()
+            }),
+            vec!["older.jpg", "old.jpg"]
+        );
+        assert_eq!(
+            removed(RetentionPolicy {
+                max_size: Some(25),
+                ..policy.clone()
+            }),
+            vec!["older.jpg", "old.jpg"]
+        );
+        assert_eq!(
+            removed(RetentionPolicy {
+                max_size: Some(5),
+                ..policy
+            }),
+            vec!["older.jpg", "old.jpg", "new.jpg"]
+        );
+    }
+}
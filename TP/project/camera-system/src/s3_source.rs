@@ -0,0 +1,135 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use aws_config::SdkConfig;
+
+use crate::image_watcher::camera_image;
+
+/// Extension of the images while they are downloaded, which the watcher ignores
+const PARTIAL_EXTENSION: &str = "part";
+
+/// Represents an S3 bucket where the cameras upload their images, each to the folder named
+/// as its id under a prefix, as in the images folder
+pub struct S3Source {
+    client: aws_sdk_s3::Client,
+    bucket: String,
+    prefix: String,
+    images_folder: PathBuf,
+    /// Keys of the objects already in the images folder
+    downloaded: HashSet<String>,
+}
+
+impl S3Source {
+    /// Creates the source of the images of a bucket, under a prefix
+    pub fn new(
+        sdk_config: &SdkConfig,
+        bucket: String,
+        prefix: String,
+        images_folder: &Path,
+    ) -> Self {
+        S3Source {
+            client: aws_sdk_s3::Client::new(sdk_config),
+            bucket,
+            prefix,
+            images_folder: images_folder.to_path_buf(),
+            downloaded: HashSet::new(),
+        }
+    }
+
+    /// Downloads the new images of the cameras into their folders, where the watcher finds
+    /// them as the ones copied there. Returns how many images were downloaded
+    pub async fn download_new_images(&mut self, camera_ids: &[u8]) -> Result<usize, String> {
+        let mut downloaded = 0;
+        let mut continuation_token = None;
+
+        loop {
+            let response = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .prefix(&self.prefix)
+                .set_continuation_token(continuation_token)
+                .send()
+                .await
+                .map_err(|e| format!("Error listing images: {:?}", e))?;
+
+            for key in response.contents().iter().filter_map(|object| object.key()) {
+                if self.downloaded.contains(key) {
+                    continue;
+                }
+                let path = match object_image(&self.prefix, key) {
+                    Some((camera_id, path)) if camera_ids.contains(&camera_id) => path,
+                    _ => continue,
+                };
+
+                let path = self.images_folder.join(path);
+                if !path.exists() {
+                    self.download(key, &path).await?;
+                    downloaded += 1;
+                }
+                self.downloaded.insert(key.to_string());
+            }
+
+            match response.next_continuation_token() {
+                Some(token) => continuation_token = Some(token.to_string()),
+                None => return Ok(downloaded),
+            }
+        }
+    }
+
+    /// Downloads an object to a path. It is written aside and moved there once complete, so
+    /// a half downloaded image is never analyzed
+    async fn download(&self, key: &str, path: &Path) -> Result<(), String> {
+        let object = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| format!("Error downloading {}: {:?}", key, e))?;
+        let content = object
+            .body
+            .collect()
+            .await
+            .map_err(|e| format!("Error downloading {}: {:?}", key, e))?
+            .into_bytes();
+
+        let partial = PathBuf::from(format!("{}.{}", path.display(), PARTIAL_EXTENSION));
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder).map_err(|e| format!("Error saving {}: {}", key, e))?;
+        }
+        fs::write(&partial, content).map_err(|e| format!("Error saving {}: {}", key, e))?;
+        fs::rename(&partial, path).map_err(|e| format!("Error saving {}: {}", key, e))
+    }
+}
+
+/// Returns the camera an object belongs to, and its path in the images folder, if it is an
+/// image directly in the folder of a camera under the prefix
+fn object_image(prefix: &str, key: &str) -> Option<(u8, PathBuf)> {
+    let path = Path::new(key.strip_prefix(prefix)?);
+    camera_image(Path::new(""), path).map(|(camera_id, _)| (camera_id, path.to_path_buf()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_objects_are_matched_with_their_camera() {
+        assert_eq!(
+            object_image("uploads/", "uploads/3/fire.jpg"),
+            Some((3, PathBuf::from("3/fire.jpg")))
+        );
+        assert_eq!(
+            object_image("", "3/fire.mp4"),
+            Some((3, PathBuf::from("3/fire.mp4")))
+        );
+        assert_eq!(object_image("uploads/", "uploads/3/notes.txt"), None);
+        assert_eq!(object_image("uploads/", "uploads/3/old/fire.jpg"), None);
+        assert_eq!(object_image("uploads/", "other/3/fire.jpg"), None);
+    }
+}
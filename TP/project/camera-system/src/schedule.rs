@@ -0,0 +1,146 @@
+use chrono::{Datelike, Duration, NaiveDateTime, NaiveTime, Weekday};
+
+const HOURS_SEPARATOR: char = '-';
+const DAYS_SEPARATOR: char = ',';
+const TIME_FORMAT: &str = "%H:%M";
+
+/// Represents when a camera works: between two hours, on some days of the week. Out of it the
+/// camera neither analyzes images nor wakes up on incidents
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    /// Hours the camera works, from the first to the second. If the second is earlier the
+    /// window goes on past midnight. The whole day if there are none
+    pub hours: Option<(NaiveTime, NaiveTime)>,
+    /// Days the camera works, every day if there are none. A window past midnight belongs to
+    /// the day it starts
+    pub days: Vec<Weekday>,
+}
+
+impl Schedule {
+    /// Parses a schedule from its hours, as `HH:MM-HH:MM`, and its days, a list separated by
+    /// commas such as `sat,sun`. There is none if both are missing
+    pub fn from_strings(hours: Option<&str>, days: Option<&str>) -> Result<Option<Self>, String> {
+        if hours.is_none() && days.is_none() {
+            return Ok(None);
+        }
+
+        let hours = match hours {
+            Some(hours) => {
+                let invalid = || format!("Invalid schedule hours: {}", hours);
+                let (start, end) = hours.split_once(HOURS_SEPARATOR).ok_or_else(invalid)?;
+                let time = |time: &str| NaiveTime::parse_from_str(time.trim(), TIME_FORMAT);
+                Some((
+                    time(start).map_err(|_| invalid())?,
+                    time(end).map_err(|_| invalid())?,
+                ))
+            }
+            None => None,
+        };
+        let days = match days {
+            Some(days) => days
+                .split(DAYS_SEPARATOR)
+                .map(str::trim)
+                .filter(|day| !day.is_empty())
+                .map(|day| {
+                    day.parse::<Weekday>()
+                        .map_err(|_| format!("Invalid schedule day: {}", day))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => vec![],
+        };
+
+        Ok(Some(Schedule { hours, days }))
+    }
+
+    /// Returns true if a moment is within the schedule
+    pub fn includes(&self, moment: NaiveDateTime) -> bool {
+        let time = moment.time();
+        let mut day = moment.date();
+
+        if let Some((start, end)) = self.hours {
+            let within = if start <= end {
+                start <= time && time < end
+            } else {
+                // Past midnight the window is the one of the day before
+                if time < end {
+                    day -= Duration::days(1);
+                }
+                start <= time || time < end
+            };
+            if !within {
+                return false;
+            }
+        }
+
+        self.days.is_empty() || self.days.contains(&day.weekday())
+    }
+
+    /// Returns the hours as they are read, if there are any
+    pub fn hours_string(&self) -> Option<String> {
+        self.hours.map(|(start, end)| {
+            format!(
+                "{}{}{}",
+                start.format(TIME_FORMAT),
+                HOURS_SEPARATOR,
+                end.format(TIME_FORMAT)
+            )
+        })
+    }
+
+    /// Returns the days as they are read, if there are any
+    pub fn days_string(&self) -> Option<String> {
+        if self.days.is_empty() {
+            return None;
+        }
+        let days: Vec<String> = self.days.iter().map(Weekday::to_string).collect();
+        Some(days.join(&DAYS_SEPARATOR.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn moment(day: u32, hour: u32) -> NaiveDateTime {
+        // 2024-06-01 was a Saturday
+        NaiveDate::from_ymd_opt(2024, 6, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_night_window_goes_on_past_midnight() {
+        let schedule = Schedule::from_strings(Some("20:00-06:00"), Some("Sat"))
+            .unwrap()
+            .unwrap();
+
+        assert!(schedule.includes(moment(1, 22)));
+        assert!(schedule.includes(moment(2, 3)));
+        assert!(!schedule.includes(moment(2, 22)));
+        assert!(!schedule.includes(moment(1, 3)));
+        assert!(!schedule.includes(moment(1, 12)));
+        assert_eq!(schedule.hours_string(), Some("20:00-06:00".to_string()));
+        assert_eq!(schedule.days_string(), Some("Sat".to_string()));
+    }
+
+    #[test]
+    fn test_schedules_are_parsed() {
+        assert_eq!(Schedule::from_strings(None, None), Ok(None));
+
+        let weekends = Schedule::from_strings(None, Some("sat, sunday")).unwrap();
+        assert_eq!(
+            weekends,
+            Some(Schedule {
+                hours: None,
+                days: vec![Weekday::Sat, Weekday::Sun],
+            })
+        );
+        assert!(weekends.unwrap().includes(moment(2, 12)));
+
+        assert!(Schedule::from_strings(Some("20:00"), None).is_err());
+        assert!(Schedule::from_strings(Some("25:00-06:00"), None).is_err());
+        assert!(Schedule::from_strings(None, Some("sat,holiday")).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use std::fmt;
+
+use crate::error::Error;
+
+const SEPARATOR: char = ';';
+const ADD: &str = "add";
+const REMOVE: &str = "remove";
+const MOVE: &str = "move";
+
+/// Represents a command an operator sends to the camera system to change its cameras while
+/// it runs
+#[derive(Debug, PartialEq, Clone)]
+pub enum CameraCommand {
+    /// Install a new camera at the position
+    Add(f64, f64),
+    /// Uninstall the camera
+    Remove(u8),
+    /// Move the camera to the position
+    Move(u8, f64, f64),
+}
+
+impl CameraCommand {
+    /// Parses a command in the format `add;x;y`, `remove;id` or `move;id;x;y`
+    pub fn from_string(string: &str) -> Result<Self, Error> {
+        let invalid = || Error::new(format!("Invalid camera command: {}", string));
+        let mut parts = string.split(SEPARATOR);
+        let action = parts.next();
+        let mut id = || {
+            parts
+                .next()
+                .and_then(|id| id.parse::<u8>().ok())
+                .ok_or_else(invalid)
+        };
+        let command = match action {
+            Some(ADD) => {
+                let (x, y) = coordinates(&mut parts).ok_or_else(invalid)?;
+                CameraCommand::Add(x, y)
+            }
+            Some(REMOVE) => CameraCommand::Remove(id()?),
+            Some(MOVE) => {
+                let id = id()?;
+                let (x, y) = coordinates(&mut parts).ok_or_else(invalid)?;
+                CameraCommand::Move(id, x, y)
+            }
+            _ => return Err(invalid()),
+        };
+
+        match parts.next() {
+            Some(_) => Err(invalid()),
+            None => Ok(command),
+        }
+    }
+}
+
+/// Parses the next two parts of a command as coordinates
+fn coordinates<'a>(parts: &mut impl Iterator<Item = &'a str>) -> Option<(f64, f64)> {
+    let x = parts.next()?.parse::<f64>().ok()?;
+    let y = parts.next()?.parse::<f64>().ok()?;
+    Some((x, y))
+}
+
+impl fmt::Display for CameraCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CameraCommand::Add(x, y) => write!(f, "{}{}{}{}{}", ADD, SEPARATOR, x, SEPARATOR, y),
+            CameraCommand::Remove(id) => write!(f, "{}{}{}", REMOVE, SEPARATOR, id),
+            CameraCommand::Move(id, x, y) => write!(
+                f,
+                "{}{}{}{}{}{}{}",
+                MOVE, SEPARATOR, id, SEPARATOR, x, SEPARATOR, y
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_camera_command_format() {
+        let commands = vec![
+            CameraCommand::Add(-58.37, -34.61),
+            CameraCommand::Remove(3),
+            CameraCommand::Move(3, -58.38, -34.6),
+        ];
+        let formatted: Vec<String> = commands.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            formatted,
+            vec!["add;-58.37;-34.61", "remove;3", "move;3;-58.38;-34.6"]
+        );
+        for command in commands {
+            assert_eq!(
+                CameraCommand::from_string(&command.to_string()).unwrap(),
+                command
+            );
+        }
+
+        assert!(CameraCommand::from_string("add;-58.37").is_err());
+        assert!(CameraCommand::from_string("remove;camera").is_err());
+        assert!(CameraCommand::from_string("move;3;-58.38;-34.6;1").is_err());
+        assert!(CameraCommand::from_string("rotate;3").is_err());
+    }
+}
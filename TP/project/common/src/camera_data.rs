@@ -0,0 +1,113 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{camera_status::CameraStatus, error::Error};
+
+const SEPARATOR: char = ';';
+const ENUMERATOR: char = '|';
+
+/// Represents a camera as the camera system publishes it, a JSON list of them per message.
+/// As text it is `id;x;y;status`, the format used before JSON, with `1` for an active camera
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraData {
+    pub id: u8,
+    pub x_coordinate: f64,
+    pub y_coordinate: f64,
+    pub status: CameraStatus,
+}
+
+impl CameraData {
+    /// Returns a list of cameras as a JSON array
+    pub fn list_to_json(cameras: &[CameraData]) -> String {
+        serde_json::to_string(cameras).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    /// Parses a list of cameras, as a JSON array or in the format used before, separated by
+    /// `|`
+    pub fn list_from_string(string: &str) -> Result<Vec<CameraData>, Error> {
+        let string = string.trim();
+        if string.starts_with('[') {
+            return serde_json::from_str(string)
+                .map_err(|e| Error::new(format!("Invalid camera data: {}", e)));
+        }
+
+        // The camera system may have no cameras left
+        string
+            .split(ENUMERATOR)
+            .filter(|camera| !camera.is_empty())
+            .map(CameraData::from_text)
+            .collect()
+    }
+
+    /// Parses a camera in the format used before JSON
+    fn from_text(string: &str) -> Result<CameraData, Error> {
+        let invalid = || Error::new(format!("Invalid camera data: {}", string));
+        let parts: Vec<&str> = string.split(SEPARATOR).collect();
+        if parts.len() != 4 {
+            return Err(invalid());
+        }
+
+        Ok(CameraData {
+            id: parts[0].parse().map_err(|_| invalid())?,
+            x_coordinate: parts[1].parse().map_err(|_| invalid())?,
+            y_coordinate: parts[2].parse().map_err(|_| invalid())?,
+            status: match parts[3] {
+                "1" => CameraStatus::Active,
+                "0" => CameraStatus::Sleep,
+                _ => return Err(invalid()),
+            },
+        })
+    }
+}
+
+impl fmt::Display for CameraData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}{}{}{}{}",
+            self.id,
+            SEPARATOR,
+            self.x_coordinate,
+            SEPARATOR,
+            self.y_coordinate,
+            SEPARATOR,
+            self.status
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cameras_are_read_as_json_or_as_text() {
+        let cameras = vec![
+            CameraData {
+                id: 0,
+                x_coordinate: -58.37,
+                y_coordinate: -34.6,
+                status: CameraStatus::Active,
+            },
+            CameraData {
+                id: 1,
+                x_coordinate: 1.5,
+                y_coordinate: 2.0,
+                status: CameraStatus::Sleep,
+            },
+        ];
+        let json = CameraData::list_to_json(&cameras);
+        assert!(json.starts_with("[{\"id\":0,"));
+        assert_eq!(CameraData::list_from_string(&json).unwrap(), cameras);
+
+        let text = format!("{}|{}", cameras[0], cameras[1]);
+        assert_eq!(text, "0;-58.37;-34.6;1|1;1.5;2;0");
+        assert_eq!(CameraData::list_from_string(&text).unwrap(), cameras);
+
+        assert!(CameraData::list_from_string("").unwrap().is_empty());
+        assert!(CameraData::list_from_string("[]").unwrap().is_empty());
+        assert!(CameraData::list_from_string("0;1.5").is_err());
+        assert!(CameraData::list_from_string("[{\"id\":0}]").is_err());
+    }
+}
@@ -1,5 +1,7 @@
+use serde::{Deserialize, Serialize};
+
 /// The different statuses a camera can have.
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum CameraStatus {
     Active,
     Sleep,
@@ -0,0 +1,147 @@
+use std::fmt;
+
+use crate::error::Error;
+
+const ENUMERATOR: char = '|';
+const SEPARATOR: char = ';';
+const ONLINE: &str = "online";
+const DEGRADED: &str = "degraded";
+const OFFLINE: &str = "offline";
+const NEVER: &str = "-";
+
+/// Represents how the image analysis of a camera is going
+#[derive(Debug, PartialEq, Clone)]
+pub struct CameraHealth {
+    pub id: u8,
+    /// Seconds since the Unix epoch when the camera analyzed its last image, if it did
+    pub last_image: Option<u64>,
+    /// Images the camera couldn't analyze
+    pub analysis_errors: usize,
+}
+
+/// Represents whether the camera system is running, so the monitor can tell a system
+/// without incidents from one that is down
+#[derive(Debug, PartialEq, Clone)]
+pub enum CameraSystemStatus {
+    Online(Vec<CameraHealth>),
+    /// Running, but recognizing incidents with a fallback because the preferred way failed
+    Degraded(Vec<CameraHealth>),
+    Offline,
+}
+
+impl CameraSystemStatus {
+    /// Parses a status in the format `offline`, `online|id;last_image;errors|...` or
+    /// `degraded|id;last_image;errors|...`, where the last image is `-` if the camera didn't
+    /// analyze any
+    pub fn from_string(string: &str) -> Result<Self, Error> {
+        let invalid = || Error::new(format!("Invalid camera system status: {}", string));
+        let mut parts = string.split(ENUMERATOR);
+        match parts.next() {
+            Some(OFFLINE) if parts.next().is_none() => Ok(CameraSystemStatus::Offline),
+            Some(state @ (ONLINE | DEGRADED)) => {
+                let mut cameras = Vec::new();
+                for camera in parts {
+                    let mut fields = camera.split(SEPARATOR);
+                    let id = fields.next().and_then(|id| id.parse::<u8>().ok());
+                    let last_image = match fields.next() {
+                        Some(NEVER) => Some(None),
+                        Some(last_image) => last_image.parse::<u64>().ok().map(Some),
+                        None => None,
+                    };
+                    let errors = fields.next().and_then(|errors| errors.parse().ok());
+                    match (id, last_image, errors, fields.next()) {
+                        (Some(id), Some(last_image), Some(analysis_errors), None) => {
+                            cameras.push(CameraHealth {
+                                id,
+                                last_image,
+                                analysis_errors,
+                            })
+                        }
+                        _ => return Err(invalid()),
+                    }
+                }
+                match state {
+                    ONLINE => Ok(CameraSystemStatus::Online(cameras)),
+                    _ => Ok(CameraSystemStatus::Degraded(cameras)),
+                }
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Returns the health of the cameras, while the camera system is running
+    pub fn cameras(&self) -> &[CameraHealth] {
+        match self {
+            CameraSystemStatus::Online(cameras) | CameraSystemStatus::Degraded(cameras) => cameras,
+            CameraSystemStatus::Offline => &[],
+        }
+    }
+}
+
+impl fmt::Display for CameraHealth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.last_image {
+            Some(last_image) => write!(f, "{}{}{}", self.id, SEPARATOR, last_image)?,
+            None => write!(f, "{}{}{}", self.id, SEPARATOR, NEVER)?,
+        }
+        write!(f, "{}{}", SEPARATOR, self.analysis_errors)
+    }
+}
+
+impl fmt::Display for CameraSystemStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (state, cameras) = match self {
+            CameraSystemStatus::Offline => return write!(f, "{}", OFFLINE),
+            CameraSystemStatus::Online(cameras) => (ONLINE, cameras),
+            CameraSystemStatus::Degraded(cameras) => (DEGRADED, cameras),
+        };
+        write!(f, "{}", state)?;
+        for camera in cameras {
+            write!(f, "{}{}", ENUMERATOR, camera)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_camera_system_status_format() {
+        let status = CameraSystemStatus::Online(vec![
+            CameraHealth {
+                id: 0,
+                last_image: Some(1718000000),
+                analysis_errors: 0,
+            },
+            CameraHealth {
+                id: 1,
+                last_image: None,
+                analysis_errors: 2,
+            },
+        ]);
+        assert_eq!(status.to_string(), "online|0;1718000000;0|1;-;2");
+        assert_eq!(
+            CameraSystemStatus::from_string(&status.to_string()).unwrap(),
+            status
+        );
+        assert_eq!(
+            CameraSystemStatus::from_string("online").unwrap(),
+            CameraSystemStatus::Online(vec![])
+        );
+        assert_eq!(
+            CameraSystemStatus::from_string("degraded|1;-;2").unwrap(),
+            CameraSystemStatus::Degraded(status.cameras()[1..].to_vec())
+        );
+        assert_eq!(
+            CameraSystemStatus::from_string("offline").unwrap(),
+            CameraSystemStatus::Offline
+        );
+
+        assert!(CameraSystemStatus::from_string("online|0;never;0").is_err());
+        assert!(CameraSystemStatus::from_string("online|0;-").is_err());
+        assert!(CameraSystemStatus::from_string("offline|0;-;0").is_err());
+        assert!(CameraSystemStatus::from_string("sleeping").is_err());
+    }
+}
@@ -1,5 +1,5 @@
 /// Represents a position in 2D space
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Coordenate {
     pub x_coordinate: f64,
     pub y_coordinate: f64,
@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+const SEPARATOR: char = ';';
+
+/// Represents an incident a camera recognized in an image, as the camera system publishes it
+/// in JSON to the topic of the camera
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DetectedIncident {
+    pub camera_id: u8,
+    pub x_coordinate: f64,
+    pub y_coordinate: f64,
+    pub label: String,
+    /// Confidence of the recognition, unknown for the ones published before JSON
+    #[serde(default)]
+    pub confidence: Option<f32>,
+}
+
+impl DetectedIncident {
+    /// Returns the incident as a JSON object
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Parses an incident detected by a camera, as a JSON object or in the format used before,
+    /// `x;y;label`
+    pub fn from_string(string: &str, camera_id: u8) -> Result<Self, Error> {
+        let string = string.trim();
+        let invalid = |e: String| Error::new(format!("Invalid detected incident: {}", e));
+        if string.starts_with('{') {
+            return serde_json::from_str(string).map_err(|e| invalid(e.to_string()));
+        }
+
+        let mut parts = string.splitn(3, SEPARATOR);
+        let mut coordinate = || {
+            parts
+                .next()
+                .and_then(|coordinate| coordinate.parse::<f64>().ok())
+                .ok_or_else(|| invalid(string.to_string()))
+        };
+        let x_coordinate = coordinate()?;
+        let y_coordinate = coordinate()?;
+        let label = parts
+            .next()
+            .ok_or_else(|| invalid(string.to_string()))?
+            .to_string();
+
+        Ok(DetectedIncident {
+            camera_id,
+            x_coordinate,
+            y_coordinate,
+            label,
+            confidence: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incidents_are_read_as_json_or_as_text() {
+        let incident = DetectedIncident {
+            camera_id: 3,
+            x_coordinate: -58.37,
+            y_coordinate: -34.6,
+            label: "Car; Crash".to_string(),
+            confidence: Some(87.5),
+        };
+        assert_eq!(
+            DetectedIncident::from_string(&incident.to_json(), 0).unwrap(),
+            incident
+        );
+
+        assert_eq!(
+            DetectedIncident::from_string("-58.37;-34.6;Car; Crash", 3).unwrap(),
+            DetectedIncident {
+                confidence: None,
+                ..incident
+            }
+        );
+        assert!(DetectedIncident::from_string("-58.37;Fire", 3).is_err());
+    }
+}
@@ -0,0 +1,66 @@
+use std::fmt;
+
+use crate::error::Error;
+
+const SEPARATOR: char = ';';
+
+/// Represents the anchor a drone waits at for incidents, and the range it covers from there
+#[derive(Debug, PartialEq, Clone)]
+pub struct DroneAnchor {
+    pub x_coordinate: f64,
+    pub y_coordinate: f64,
+    pub active_range: f64,
+}
+
+impl DroneAnchor {
+    /// Parses an anchor in the format `x;y;active_range`
+    pub fn from_string(string: &str) -> Result<Self, Error> {
+        let mut parts = string.split(SEPARATOR).map(|part| part.parse::<f64>().ok());
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (
+                Some(Some(x_coordinate)),
+                Some(Some(y_coordinate)),
+                Some(Some(active_range)),
+                None,
+            ) => Ok(DroneAnchor {
+                x_coordinate,
+                y_coordinate,
+                active_range,
+            }),
+            _ => Err(Error::new(format!("Invalid drone anchor: {}", string))),
+        }
+    }
+}
+
+impl fmt::Display for DroneAnchor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}{}{}",
+            self.x_coordinate, SEPARATOR, self.y_coordinate, SEPARATOR, self.active_range
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drone_anchor_format() {
+        let anchor = DroneAnchor {
+            x_coordinate: -58.37,
+            y_coordinate: -34.61,
+            active_range: 0.01,
+        };
+        assert_eq!(anchor.to_string(), "-58.37;-34.61;0.01");
+        assert_eq!(
+            DroneAnchor::from_string(&anchor.to_string()).unwrap(),
+            anchor
+        );
+
+        assert!(DroneAnchor::from_string("-58.37;-34.61").is_err());
+        assert!(DroneAnchor::from_string("-58.37;-34.61;0.01;1").is_err());
+        assert!(DroneAnchor::from_string("north;-34.61;0.01").is_err());
+    }
+}
@@ -0,0 +1,180 @@
+use std::fmt;
+
+use crate::error::Error;
+use crate::incident::Incident;
+
+const SEPARATOR: char = ';';
+const RETURN_TO_CENTRAL: &str = "return-to-central";
+const GOTO: &str = "goto";
+const HOLD: &str = "hold";
+const RESUME: &str = "resume";
+const ANCHOR: &str = "anchor";
+const ATTEND: &str = "attend";
+const ACCEPTED: &str = "accepted";
+const REJECTED: &str = "rejected";
+
+/// Represents a command an operator sends to a drone to override what it does on its own
+#[derive(Debug, PartialEq, Clone)]
+pub enum DroneCommand {
+    /// Travel to the central and wait there
+    ReturnToCentral,
+    /// Travel to the position and wait there
+    Goto(f64, f64),
+    /// Stop where it is and wait there
+    Hold,
+    /// Go back to the anchor and attend incidents again
+    Resume,
+    /// Wait for incidents at the position from now on
+    Anchor(f64, f64),
+    /// Attend the incident, which another drone rejected
+    Attend(Incident),
+}
+
+impl DroneCommand {
+    /// Parses a command in the format `return-to-central`, `goto;x;y`, `hold`, `resume`,
+    /// `anchor;x;y` or `attend;incident`
+    pub fn from_string(string: &str) -> Result<Self, Error> {
+        // The incident has its own fields, separated the same way
+        if let Some((ATTEND, incident)) = string.split_once(SEPARATOR) {
+            return Ok(DroneCommand::Attend(Incident::from_string(
+                incident.to_string(),
+            )?));
+        }
+
+        let mut parts = string.split(SEPARATOR);
+        let command = match parts.next() {
+            Some(RETURN_TO_CENTRAL) => DroneCommand::ReturnToCentral,
+            Some(HOLD) => DroneCommand::Hold,
+            Some(RESUME) => DroneCommand::Resume,
+            Some(action @ (GOTO | ANCHOR)) => {
+                let mut coordinate = || {
+                    parts
+                        .next()
+                        .and_then(|coordinate| coordinate.parse::<f64>().ok())
+                        .ok_or_else(|| Error::new(format!("Invalid {} coordinates", action)))
+                };
+                let x = coordinate()?;
+                let y = coordinate()?;
+                if action == GOTO {
+                    DroneCommand::Goto(x, y)
+                } else {
+                    DroneCommand::Anchor(x, y)
+                }
+            }
+            _ => return Err(Error::new(format!("Invalid drone command: {}", string))),
+        };
+
+        match parts.next() {
+            Some(_) => Err(Error::new(format!("Invalid drone command: {}", string))),
+            None => Ok(command),
+        }
+    }
+}
+
+impl fmt::Display for DroneCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DroneCommand::ReturnToCentral => write!(f, "{}", RETURN_TO_CENTRAL),
+            DroneCommand::Goto(x, y) => write!(f, "{}{}{}{}{}", GOTO, SEPARATOR, x, SEPARATOR, y),
+            DroneCommand::Hold => write!(f, "{}", HOLD),
+            DroneCommand::Resume => write!(f, "{}", RESUME),
+            DroneCommand::Anchor(x, y) => {
+                write!(f, "{}{}{}{}{}", ANCHOR, SEPARATOR, x, SEPARATOR, y)
+            }
+            DroneCommand::Attend(incident) => write!(f, "{}{}{}", ATTEND, SEPARATOR, incident),
+        }
+    }
+}
+
+/// Represents the answer of a drone to a command, telling if it carries it out
+#[derive(Debug, PartialEq, Clone)]
+pub struct DroneCommandAck {
+    pub command: DroneCommand,
+    pub accepted: bool,
+}
+
+impl DroneCommandAck {
+    /// Parses an acknowledgment in the format `accepted|rejected;command`
+    pub fn from_string(string: &str) -> Result<Self, Error> {
+        let (result, command) = string
+            .split_once(SEPARATOR)
+            .ok_or_else(|| Error::new("Invalid drone command acknowledgment".to_string()))?;
+        let accepted = match result {
+            ACCEPTED => true,
+            REJECTED => false,
+            _ => {
+                return Err(Error::new(
+                    "Invalid drone command acknowledgment".to_string(),
+                ))
+            }
+        };
+
+        Ok(DroneCommandAck {
+            command: DroneCommand::from_string(command)?,
+            accepted,
+        })
+    }
+}
+
+impl fmt::Display for DroneCommandAck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let result = if self.accepted { ACCEPTED } else { REJECTED };
+        write!(f, "{}{}{}", result, SEPARATOR, self.command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::incident::IncidentStatus;
+
+    #[test]
+    fn test_drone_command_format() {
+        for command in [
+            DroneCommand::ReturnToCentral,
+            DroneCommand::Goto(-58.37, -34.61),
+            DroneCommand::Hold,
+            DroneCommand::Resume,
+            DroneCommand::Anchor(-58.38, -34.6),
+            DroneCommand::Attend(
+                Incident::new(
+                    "4".to_string(),
+                    "fire".to_string(),
+                    "fire in the park".to_string(),
+                    -58.39,
+                    -34.62,
+                    IncidentStatus::Pending,
+                )
+                .with_required_drones(3),
+            ),
+        ] {
+            assert_eq!(
+                DroneCommand::from_string(&command.to_string()).unwrap(),
+                command
+            );
+        }
+        assert_eq!(
+            DroneCommand::Goto(1.5, -2.0).to_string(),
+            "goto;1.5;-2".to_string()
+        );
+
+        assert!(DroneCommand::from_string("goto;1").is_err());
+        assert!(DroneCommand::from_string("anchor;1").is_err());
+        assert!(DroneCommand::from_string("goto;1;north").is_err());
+        assert!(DroneCommand::from_string("hold;now").is_err());
+        assert!(DroneCommand::from_string("land").is_err());
+        assert!(DroneCommand::from_string("attend;4;fire").is_err());
+    }
+
+    #[test]
+    fn test_drone_command_ack_format() {
+        let ack = DroneCommandAck {
+            command: DroneCommand::Goto(1.0, 2.0),
+            accepted: false,
+        };
+        assert_eq!(ack.to_string(), "rejected;goto;1;2");
+        assert_eq!(DroneCommandAck::from_string(&ack.to_string()).unwrap(), ack);
+        assert!(DroneCommandAck::from_string("maybe;hold").is_err());
+        assert!(DroneCommandAck::from_string("accepted").is_err());
+    }
+}
@@ -0,0 +1,114 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::Error;
+
+const SEPARATOR: char = ';';
+const INFO: &str = "info";
+const WARNING: &str = "warning";
+const ERROR: &str = "error";
+
+/// Represents how serious an event of a drone is
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LogLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LogLevel::Info => write!(f, "{}", INFO),
+            LogLevel::Warning => write!(f, "{}", WARNING),
+            LogLevel::Error => write!(f, "{}", ERROR),
+        }
+    }
+}
+
+/// Represents a significant event of a drone, published so an operator can follow what a
+/// specific drone does from the monitor
+#[derive(Debug, PartialEq, Clone)]
+pub struct DroneLog {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub level: LogLevel,
+    /// Name of the event, as `incident-accepted` or `battery-low`
+    pub event: String,
+    pub detail: String,
+}
+
+impl DroneLog {
+    /// Creates an entry for an event that happens now
+    pub fn new(level: LogLevel, event: &str, detail: String) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        DroneLog {
+            timestamp,
+            level,
+            event: event.to_string(),
+            detail,
+        }
+    }
+
+    /// Parses an entry in the format `timestamp;level;event;detail`. The detail is free text,
+    /// so it may have separators too
+    pub fn from_string(string: &str) -> Result<Self, Error> {
+        let invalid = || Error::new(format!("Invalid drone log: {}", string));
+        let mut parts = string.splitn(4, SEPARATOR);
+        let timestamp = parts
+            .next()
+            .and_then(|timestamp| timestamp.parse::<u64>().ok())
+            .ok_or_else(invalid)?;
+        let level = match parts.next() {
+            Some(INFO) => LogLevel::Info,
+            Some(WARNING) => LogLevel::Warning,
+            Some(ERROR) => LogLevel::Error,
+            _ => return Err(invalid()),
+        };
+        match (parts.next(), parts.next()) {
+            (Some(event), Some(detail)) if !event.is_empty() => Ok(DroneLog {
+                timestamp,
+                level,
+                event: event.to_string(),
+                detail: detail.to_string(),
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for DroneLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}{}{}{}{}",
+            self.timestamp, SEPARATOR, self.level, SEPARATOR, self.event, SEPARATOR, self.detail
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drone_log_format() {
+        let log = DroneLog {
+            timestamp: 1718000000,
+            level: LogLevel::Warning,
+            event: "battery-low".to_string(),
+            detail: "battery 19; going to (0, 0)".to_string(),
+        };
+        assert_eq!(
+            log.to_string(),
+            "1718000000;warning;battery-low;battery 19; going to (0, 0)"
+        );
+        assert_eq!(DroneLog::from_string(&log.to_string()).unwrap(), log);
+
+        assert!(DroneLog::from_string("1718000000;info;travel-started").is_err());
+        assert!(DroneLog::from_string("1718000000;debug;travel-started;").is_err());
+        assert!(DroneLog::from_string("yesterday;info;travel-started;").is_err());
+    }
+}
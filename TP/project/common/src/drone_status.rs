@@ -6,6 +6,10 @@ pub enum DroneStatus {
     AttendingIncident,
     Recharging,
     Interrupted,
+    /// The drone lost its connection to the server, reported by its last will
+    Disconnected,
+    /// An operator stopped the drone, which waits where it is until it is told to resume
+    Held,
 }
 
 /// Represents the location of the drone when travelling
@@ -14,6 +18,8 @@ pub enum TravelLocation {
     Central,
     Anchor,
     Incident,
+    /// A position an operator sent the drone to
+    Waypoint,
 }
 
 impl std::fmt::Display for DroneStatus {
@@ -26,6 +32,9 @@ impl std::fmt::Display for DroneStatus {
             DroneStatus::Travelling(TravelLocation::Incident) => write!(f, "4"),
             DroneStatus::Recharging => write!(f, "5"),
             DroneStatus::Interrupted => write!(f, "6"),
+            DroneStatus::Disconnected => write!(f, "7"),
+            DroneStatus::Held => write!(f, "8"),
+            DroneStatus::Travelling(TravelLocation::Waypoint) => write!(f, "9"),
         }
     }
 }
@@ -40,6 +49,9 @@ impl DroneStatus {
             DroneStatus::Travelling(TravelLocation::Incident) => "Travelling to Incident",
             DroneStatus::Recharging => "Charging",
             DroneStatus::Interrupted => "Interrupted",
+            DroneStatus::Disconnected => "Disconnected",
+            DroneStatus::Held => "Held",
+            DroneStatus::Travelling(TravelLocation::Waypoint) => "Travelling to Waypoint",
         }
     }
     pub fn get_status_from_str(string: &str) -> Self {
@@ -51,6 +63,9 @@ impl DroneStatus {
             "4" => DroneStatus::Travelling(TravelLocation::Incident),
             "5" => DroneStatus::Recharging,
             "6" => DroneStatus::Interrupted,
+            "7" => DroneStatus::Disconnected,
+            "8" => DroneStatus::Held,
+            "9" => DroneStatus::Travelling(TravelLocation::Waypoint),
             _ => DroneStatus::Free,
         }
     }
@@ -77,5 +92,19 @@ mod tests {
             "4"
         );
         assert_eq!(format!("{}", DroneStatus::Recharging), "5");
+        assert_eq!(format!("{}", DroneStatus::Disconnected), "7");
+        assert_eq!(format!("{}", DroneStatus::Held), "8");
+        assert_eq!(
+            format!("{}", DroneStatus::Travelling(TravelLocation::Waypoint)),
+            "9"
+        );
+    }
+
+    #[test]
+    fn test_disconnected_status_from_str() {
+        assert_eq!(
+            DroneStatus::get_status_from_str("7"),
+            DroneStatus::Disconnected
+        );
     }
 }
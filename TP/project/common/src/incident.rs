@@ -5,6 +5,9 @@ use crate::error::Error;
 pub const SEPARATOR: char = ';';
 const ELEMENTS_COUNT: usize = 6;
 
+/// Number of drones that attend an incident that doesn't say how many it needs
+pub const DEFAULT_REQUIRED_DRONES: usize = 2;
+
 /// Represents the different statuses an incident can have
 #[derive(Debug, PartialEq, Clone)]
 pub enum IncidentStatus {
@@ -59,6 +62,8 @@ pub struct Incident {
     pub x_coordinate: f64,
     pub y_coordinate: f64,
     pub status: IncidentStatus,
+    /// Number of drones the incident needs, if it isn't the default of who attends it
+    pub required_drones: Option<usize>,
 }
 
 impl Incident {
@@ -78,14 +83,22 @@ impl Incident {
             x_coordinate,
             y_coordinate,
             status,
+            required_drones: None,
         }
     }
 
-    /// Creates a new incident from a string
+    /// Sets the number of drones the incident needs
+    pub fn with_required_drones(mut self, required_drones: usize) -> Self {
+        self.required_drones = Some(required_drones);
+        self
+    }
+
+    /// Creates a new incident from a string. The number of drones it needs is optional
+    /// and goes after the rest of the fields
     pub fn from_string(string: String) -> Result<Self, Error> {
         let splited_string: Vec<&str> = string.split(SEPARATOR).collect();
 
-        if splited_string.len() != ELEMENTS_COUNT {
+        if splited_string.len() != ELEMENTS_COUNT && splited_string.len() != ELEMENTS_COUNT + 1 {
             return Err(Error::new("Invalid incident string".to_string()));
         }
 
@@ -101,6 +114,13 @@ impl Incident {
             Err(_) => return Err(Error::new("Invalid y coordinate".to_string())),
         };
         let state = IncidentStatus::from_string(splited_string[5].to_string());
+        let required_drones = match splited_string.get(ELEMENTS_COUNT) {
+            Some(value) => match value.parse() {
+                Ok(value) if value > 0 => Some(value),
+                _ => return Err(Error::new("Invalid required drones".to_string())),
+            },
+            None => None,
+        };
 
         Ok(Incident {
             uuid: id,
@@ -109,6 +129,7 @@ impl Incident {
             x_coordinate,
             y_coordinate,
             status: state,
+            required_drones,
         })
     }
 
@@ -134,6 +155,11 @@ impl Incident {
     pub fn status(&self) -> IncidentStatus {
         self.status.clone()
     }
+
+    /// Returns the number of drones the incident needs, or the default if it doesn't say
+    pub fn required_drones_or(&self, default: usize) -> usize {
+        self.required_drones.unwrap_or(default)
+    }
 }
 
 impl fmt::Display for Incident {
@@ -147,7 +173,11 @@ impl fmt::Display for Incident {
             self.x_coordinate,
             self.y_coordinate,
             self.status
-        )
+        )?;
+        if let Some(required_drones) = self.required_drones {
+            write!(f, "{}{}", SEPARATOR, required_drones)?;
+        }
+        Ok(())
     }
 }
 
@@ -208,6 +238,35 @@ mod tests {
         assert_eq!(incident.to_string(), "incident1;incident1;incident1;1;1;0");
     }
 
+    #[test]
+    fn test_incident_with_required_drones() {
+        let incident = Incident::new(
+            "incident1".to_string(),
+            "incident1".to_string(),
+            "incident1".to_string(),
+            1.0,
+            1.0,
+            IncidentStatus::Pending,
+        )
+        .with_required_drones(3);
+
+        assert_eq!(
+            incident.to_string(),
+            "incident1;incident1;incident1;1;1;0;3"
+        );
+        assert_eq!(
+            Incident::from_string(incident.to_string()).unwrap(),
+            incident
+        );
+        assert_eq!(incident.required_drones_or(DEFAULT_REQUIRED_DRONES), 3);
+
+        let incident = Incident::from_string("incident1;a;b;1.0;1.0;0".to_string()).unwrap();
+        assert_eq!(incident.required_drones_or(DEFAULT_REQUIRED_DRONES), 2);
+
+        assert!(Incident::from_string("incident1;a;b;1.0;1.0;0;0".to_string()).is_err());
+        assert!(Incident::from_string("incident1;a;b;1.0;1.0;0;many".to_string()).is_err());
+    }
+
     #[test]
     fn test_incident_status_from_string() {
         let status = IncidentStatus::from_string("0".to_string());
@@ -13,5 +13,29 @@ pub mod coordenate;
 /// Represents the status of a drone
 pub mod drone_status;
 
+/// Represents the commands an operator sends to a drone
+pub mod drone_command;
+
+/// Represents the anchor of a drone and the range it covers
+pub mod drone_anchor;
+
+/// Represents the events a drone logs for the operators
+pub mod drone_log;
+
 /// Represents the status of a camera
 pub mod camera_status;
+
+/// Represents the commands an operator sends to the camera system
+pub mod camera_command;
+
+/// Represents the status of the camera system and the health of its cameras
+pub mod camera_system_status;
+
+/// Represents a grid to find the items near a position, as the cameras near an incident
+pub mod spatial_grid;
+
+/// Represents the data of a camera the camera system publishes
+pub mod camera_data;
+
+/// Represents an incident a camera detected in an image
+pub mod detected_incident;
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::coordenate::Coordenate;
+
+/// Cell size used when the one given isn't a positive number
+const DEFAULT_CELL_SIZE: f64 = 1.0;
+
+/// Represents a grid of square cells where items are kept by position, to find the ones near a
+/// point without going through all of them. The cells should be about as big as the distances
+/// searched
+#[derive(Debug, Clone)]
+pub struct SpatialGrid<K> {
+    cell_size: f64,
+    cells: HashMap<(i64, i64), Vec<K>>,
+    positions: HashMap<K, Coordenate>,
+}
+
+impl<K: Clone + Eq + Hash> SpatialGrid<K> {
+    /// Creates an empty grid with cells of a size
+    pub fn new(cell_size: f64) -> Self {
+        let cell_size = if cell_size.is_finite() && cell_size > 0.0 {
+            cell_size
+        } else {
+            DEFAULT_CELL_SIZE
+        };
+        SpatialGrid {
+            cell_size,
+            cells: HashMap::new(),
+            positions: HashMap::new(),
+        }
+    }
+
+    /// Adds an item at a position, or moves it there if it was already in the grid
+    pub fn insert(&mut self, key: K, position: Coordenate) {
+        self.remove(&key);
+        self.cells
+            .entry(self.cell(&position))
+            .or_default()
+            .push(key.clone());
+        self.positions.insert(key, position);
+    }
+
+    /// Removes an item, returning its position if it was in the grid
+    pub fn remove(&mut self, key: &K) -> Option<Coordenate> {
+        let position = self.positions.remove(key)?;
+        let cell = self.cell(&position);
+        if let Some(keys) = self.cells.get_mut(&cell) {
+            keys.retain(|k| k != key);
+            if keys.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+        Some(position)
+    }
+
+    /// Returns the position of an item
+    pub fn position(&self, key: &K) -> Option<&Coordenate> {
+        self.positions.get(key)
+    }
+
+    /// Returns how many items are in the grid
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Returns true if the grid has no items
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Returns the items closer than a radius to a point, from the nearest
+    pub fn within(&self, center: &Coordenate, radius: f64) -> Vec<K> {
+        let (min_x, min_y) = self.cell(&Coordenate {
+            x_coordinate: center.x_coordinate - radius,
+            y_coordinate: center.y_coordinate - radius,
+        });
+        let (max_x, max_y) = self.cell(&Coordenate {
+            x_coordinate: center.x_coordinate + radius,
+            y_coordinate: center.y_coordinate + radius,
+        });
+
+        // A radius much bigger than the cells covers more cells than there are items in
+        let covered = (max_x.saturating_sub(min_x) as u128 + 1)
+            .saturating_mul(max_y.saturating_sub(min_y) as u128 + 1);
+        let candidates: Vec<&K> = if covered > self.cells.len() as u128 {
+            self.cells
+                .iter()
+                .filter(|((x, y), _)| (min_x..=max_x).contains(x) && (min_y..=max_y).contains(y))
+                .flat_map(|(_, keys)| keys)
+                .collect()
+        } else {
+            (min_x..=max_x)
+                .flat_map(|x| (min_y..=max_y).map(move |y| (x, y)))
+                .filter_map(|cell| self.cells.get(&cell))
+                .flatten()
+                .collect()
+        };
+
+        let mut near: Vec<(K, f64)> = candidates
+            .into_iter()
+            .filter_map(|key| {
+                let position = self.positions.get(key)?;
+                let distance = distance(center, position);
+                (distance < radius).then(|| (key.clone(), distance))
+            })
+            .collect();
+        near.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        near.into_iter().map(|(key, _)| key).collect()
+    }
+
+    /// Returns the cell of a position
+    fn cell(&self, position: &Coordenate) -> (i64, i64) {
+        (
+            (position.x_coordinate / self.cell_size).floor() as i64,
+            (position.y_coordinate / self.cell_size).floor() as i64,
+        )
+    }
+}
+
+/// Calculates the euclidean distance between two positions
+fn distance(a: &Coordenate, b: &Coordenate) -> f64 {
+    ((a.x_coordinate - b.x_coordinate).powi(2) + (a.y_coordinate - b.y_coordinate).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: f64, y: f64) -> Coordenate {
+        Coordenate {
+            x_coordinate: x,
+            y_coordinate: y,
+        }
+    }
+
+    #[test]
+    fn test_items_within_the_radius_are_found_from_the_nearest() {
+        let mut grid = SpatialGrid::new(1.0);
+        grid.insert(1, at(0.5, 0.5));
+        grid.insert(2, at(-1.2, 0.1));
+        grid.insert(3, at(5.0, 5.0));
+        grid.insert(4, at(0.0, 1.9));
+
+        assert_eq!(grid.within(&at(0.0, 0.0), 2.0), vec![1, 2, 4]);
+        assert_eq!(grid.within(&at(0.0, 0.0), 0.5), Vec::<i32>::new());
+        assert_eq!(grid.within(&at(0.0, 0.0), 1000.0), vec![1, 2, 4, 3]);
+    }
+
+    #[test]
+    fn test_items_are_moved_and_removed() {
+        let mut grid = SpatialGrid::new(0.01);
+        grid.insert("camera", at(-58.37, -34.6));
+        grid.insert("camera", at(-58.4, -34.7));
+        assert_eq!(grid.len(), 1);
+        assert_eq!(grid.within(&at(-58.37, -34.6), 0.01), Vec::<&str>::new());
+        assert_eq!(grid.within(&at(-58.4, -34.7), 0.01), vec!["camera"]);
+
+        assert_eq!(grid.remove(&"camera"), Some(at(-58.4, -34.7)));
+        assert_eq!(grid.remove(&"camera"), None);
+        assert!(grid.is_empty());
+    }
+}
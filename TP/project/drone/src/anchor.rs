@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use crate::geofence::NoFlyZone;
+use crate::utils::Position;
+
+/// Number of steps of the grid of candidate anchors along the radius of the operations area
+const GRID_STEPS: usize = 10;
+
+/// Represents the area the drones with automatic anchors spread over: a circle around the
+/// central
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperationsArea {
+    pub center: Position,
+    pub radius: f64,
+}
+
+impl OperationsArea {
+    /// Returns the distance between two candidate anchors. Drones whose anchors are closer
+    /// than half of it chose the same one
+    pub fn spacing(&self) -> f64 {
+        self.radius / GRID_STEPS as f64
+    }
+
+    /// Returns the candidate anchors: the points of a grid inside the area
+    fn candidates(&self) -> Vec<Position> {
+        let steps = GRID_STEPS as i64;
+        let spacing = self.spacing();
+        let mut candidates = Vec::new();
+        for i in -steps..=steps {
+            for j in -steps..=steps {
+                let candidate = Position::new(
+                    self.center.x + i as f64 * spacing,
+                    self.center.y + j as f64 * spacing,
+                );
+                if self.center.distance_to(&candidate) <= self.radius {
+                    candidates.push(candidate);
+                }
+            }
+        }
+        candidates
+    }
+}
+
+/// Represents how a drone chooses its anchor together with the other drones, so their
+/// coverage overlaps as little as possible
+#[derive(Debug, Clone)]
+pub struct AnchorNegotiation {
+    area: OperationsArea,
+    peers: HashMap<u8, Position>,
+    settled: bool,
+}
+
+impl AnchorNegotiation {
+    /// Creates a negotiation that doesn't know the anchors of the other drones yet
+    pub fn new(area: OperationsArea) -> Self {
+        AnchorNegotiation {
+            area,
+            peers: HashMap::new(),
+            settled: false,
+        }
+    }
+
+    /// Chooses the candidate farthest from the anchors of the other drones, outside the
+    /// no-fly zones. Ties go to the one closest to the preferred anchor, so a drone alone
+    /// stays near the anchor of its config
+    pub fn choose(&mut self, preferred: Position, no_fly_zones: &[NoFlyZone]) -> Position {
+        self.settled = true;
+        let score = |candidate: &Position| {
+            let clearance = self
+                .peers
+                .values()
+                .map(|peer| peer.distance_to(candidate))
+                .fold(f64::INFINITY, f64::min);
+            (clearance, -preferred.distance_to(candidate))
+        };
+
+        self.area
+            .candidates()
+            .into_iter()
+            .filter(|candidate| !no_fly_zones.iter().any(|zone| zone.contains(candidate)))
+            .max_by(|a, b| {
+                score(a)
+                    .partial_cmp(&score(b))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .unwrap_or(preferred)
+    }
+
+    /// Records the anchor of another drone. Returns true if the drone has to choose its
+    /// anchor again, because a drone with a lower id chose the same one
+    pub fn add_peer(&mut self, drone_id: u8, peer_id: u8, anchor: Position, own: Position) -> bool {
+        self.peers.insert(peer_id, anchor);
+        self.settled && peer_id < drone_id && anchor.distance_to(&own) < self.area.spacing() / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area() -> OperationsArea {
+        OperationsArea {
+            center: Position::new(0.0, 0.0),
+            radius: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_drone_alone_stays_near_its_preferred_anchor() {
+        let mut negotiation = AnchorNegotiation::new(area());
+        let anchor = negotiation.choose(Position::new(0.31, 0.0), &[]);
+        assert!(anchor.distance_to(&Position::new(0.3, 0.0)) < 1e-9);
+    }
+
+    #[test]
+    fn test_anchors_spread_away_from_the_other_drones() {
+        let mut negotiation = AnchorNegotiation::new(area());
+        negotiation.add_peer(2, 1, Position::new(0.0, 0.0), Position::new(0.0, 0.0));
+        let anchor = negotiation.choose(Position::new(0.0, 0.0), &[]);
+        assert!(anchor.distance_to(&Position::new(0.0, 0.0)) > 0.99);
+
+        // The no-fly zones are avoided
+        let zone = NoFlyZone::new(anchor.x, anchor.y, 0.05);
+        let mut negotiation = AnchorNegotiation::new(area());
+        negotiation.add_peer(2, 1, Position::new(0.0, 0.0), Position::new(0.0, 0.0));
+        let other = negotiation.choose(Position::new(0.0, 0.0), &[zone]);
+        assert_ne!(other, anchor);
+    }
+
+    #[test]
+    fn test_drone_with_the_higher_id_chooses_again() {
+        let own = Position::new(0.5, 0.0);
+        let mut negotiation = AnchorNegotiation::new(area());
+        // Before choosing, the anchors of the others are only recorded
+        assert!(!negotiation.add_peer(2, 1, own, own));
+        negotiation.choose(own, &[]);
+
+        assert!(negotiation.add_peer(2, 1, own, own));
+        assert!(!negotiation.add_peer(2, 3, own, own));
+        assert!(!negotiation.add_peer(2, 1, Position::new(-0.5, 0.0), own));
+    }
+}
@@ -0,0 +1,41 @@
+/// Represents how the battery of a drone charges and discharges.
+/// Levels and rates are in battery units, intervals are in seconds.
+/// Besides the discharge of its status, the drone discharges a unit for each
+/// `discharge_distance` it travels
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatteryModel {
+    /// Below this level the drone goes to the nearest charging station
+    pub minimum_level: usize,
+    /// Level of a fully charged battery
+    pub maximum_level: usize,
+    /// Distance travelled for each unit discharged, in the units of the coordinates
+    pub discharge_distance: f64,
+    /// Discharged every interval while attending an incident
+    pub discharge_attending: usize,
+    /// Discharged every interval while hovering, which includes travelling
+    pub discharge_idle: usize,
+    /// Recharged every interval while in a charging station
+    pub recharge: usize,
+    /// Time between checks of the battery level
+    pub check_interval: u64,
+    /// Time between discharges
+    pub discharge_interval: u64,
+    /// Time between recharges
+    pub recharge_interval: u64,
+}
+
+impl Default for BatteryModel {
+    fn default() -> Self {
+        BatteryModel {
+            minimum_level: 20,
+            maximum_level: 100,
+            discharge_distance: 0.002,
+            discharge_attending: 2,
+            discharge_idle: 1,
+            recharge: 5,
+            check_interval: 5,
+            discharge_interval: 5,
+            recharge_interval: 1,
+        }
+    }
+}
@@ -1,23 +1,46 @@
 use std::{
     io::{ErrorKind, Write},
-    net::TcpStream,
-    sync::{Arc, Mutex, MutexGuard},
+    net::{Ipv4Addr, Shutdown, TcpListener, TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex, PoisonError, TryLockError,
+    },
     thread,
-    time::Duration,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
+use mqtt::errors::error::MqttError;
 use mqtt::model::{
     components::{
         encoded_string::EncodedString, login::Login, qos::QoS, topic_filter::TopicFilter,
-        topic_level::TopicLevel, topic_name::TopicName,
+        topic_level::TopicLevel, topic_name::TopicName, will::Will,
     },
     packet::Packet,
-    packets::{connect::Connect, publish::Publish, subscribe::Subscribe, unsubscribe::Unsubscribe},
+    packets::{connect::Connect, publish::Publish},
     return_codes::connect_return_code::ConnectReturnCode,
 };
 
-use crate::{config::Config, drone::Drone};
+use crate::{
+    battery::BatteryModel,
+    config::Config,
+    connection::{write_packets, Connection},
+    control::serve_control,
+    coordination::IncidentClaim,
+    drone::Drone,
+    event::{DroneAction, DroneEvent, IncidentTopic, Timers, TRAVEL_INTERVAL},
+    faults::FaultInjector,
+    inflight::InflightPublishes,
+    state::DroneState,
+    telemetry::TelemetryFilter,
+    utils::Position,
+    watchdog::{Heartbeat, Watchdog},
+};
 
+use common::drone_anchor::DroneAnchor;
+use common::drone_command::DroneCommand;
+use common::drone_log::{DroneLog, LogLevel};
 use common::drone_status::{DroneStatus, TravelLocation};
 
 use common::incident::Incident;
@@ -27,27 +50,36 @@ const ATTENDING_INCIDENT: &[u8] = b"attending-incident";
 const CLOSE_INCIDENT: &[u8] = b"close-incident";
 const DRONE_DATA: &[u8] = b"drone-data";
 const READY_INCIDENT: &[u8] = b"ready-incident";
-
-const READ_MESSAGE_INTERVAL: u64 = 100;
-const UPDATE_DATA_INTERVAL: u64 = 1;
-const CHECK_BATTERY_INTERVAL: u64 = 5;
-const PENDING_INCIDENTS_INTERVAL: u64 = 1;
-const WAIT_FOR_DRONE_INTERVAL: u64 = 1;
-
-const TRAVEL_INTERVAL: u64 = 1;
-const BATTERY_DISCHARGE_INTERVAL: u64 = 5;
-const BATTERY_RECHARGE_INTERVAL: u64 = 1;
-
-const DRONE_ATTENDING_DURATION: u64 = 10;
-
-const DRONE_COUNT_PER_INCIDENT: usize = 2;
-
-/// Runs the client with the specified configuration
-pub fn client_run(config: Config) -> std::io::Result<()> {
-    let server_stream = connect_to_server(config.clone())?;
-    let server_stream = Arc::new(Mutex::new(server_stream));
-
-    let drone = Arc::new(Mutex::new(Drone::new(
+const CHARGING_STATION: &[u8] = b"charging-station";
+const REJECTED_INCIDENT: &[u8] = b"rejected-incident";
+const DRONE_ROUTE: &[u8] = b"drone-route";
+const INCIDENT_CLAIM: &[u8] = b"incident-claim";
+const DRONE_HISTORY: &[u8] = b"drone-history";
+const DATA: &[u8] = b"data";
+const DRONE_COMMAND: &[u8] = b"drone-command";
+const COMMAND_ACK: &[u8] = b"ack";
+const DRONE_ANCHOR: &[u8] = b"drone-anchor";
+const INCIDENT_SYNC: &[u8] = b"incident-sync";
+const DRONE_LOG: &[u8] = b"drone-log";
+const INCIDENT_SEPARATOR: char = '|';
+
+const PUBACK_TIMEOUT: u64 = 5;
+const MAX_INFLIGHT_PUBLISHES: usize = 256;
+const MAX_PUBLISH_RESENDS: u32 = 10;
+const SHUTDOWN_CHECK_INTERVAL: u64 = 100;
+
+const CONNECT_TIMEOUT: u64 = 5;
+const RECONNECT_INITIAL_BACKOFF_MILLIS: u64 = 500;
+const RECONNECT_MAX_BACKOFF_MILLIS: u64 = 30_000;
+
+const WATCHDOG_INTERVAL: u64 = 1;
+const WORKER_TIMEOUT: u64 = 10;
+
+/// Runs the client with the specified configuration until the shutdown flag is set.
+/// Then the drone publishes its final status and disconnects from the server. If the
+/// watchdog finds a worker with a problem, only this drone stops and an error is returned
+pub fn client_run(config: Config, process_shutdown: Arc<AtomicBool>) -> std::io::Result<()> {
+    let drone = Drone::new(
         config.get_id(),
         config.get_x_central_position(),
         config.get_y_central_position(),
@@ -55,107 +87,392 @@ pub fn client_run(config: Config) -> std::io::Result<()> {
         config.get_y_anchor_position(),
         config.get_velocity(),
         config.get_active_range(),
-    )));
+    )
+    .with_battery_model(config.get_battery_model().clone())
+    .with_charging_stations(config.get_charging_stations())
+    .with_no_fly_zones(config.get_no_fly_zones())
+    .with_drones_per_incident(config.get_drones_per_incident())
+    .with_history_length(config.get_history_length())
+    .with_operations_area(config.get_operations_area())
+    .with_separation_distance(config.get_separation_distance())
+    .with_acceleration(config.get_acceleration());
+    let state_file = config.get_state_file().map(PathBuf::from);
+    let drone = match &state_file {
+        Some(state_file) => restore_state(drone, state_file),
+        None => drone,
+    };
+
+    // The control socket only listens on localhost, for debugging a running drone
+    let control_listener = match config.get_control_port() {
+        Some(port) => Some(TcpListener::bind((Ipv4Addr::LOCALHOST, port))?),
+        None => None,
+    };
 
+    let server_stream = connect_to_server(config.clone(), last_will(&drone))?;
+    let reader_stream = server_stream.try_clone()?;
+    let watchdog_stream = server_stream.try_clone()?;
+    let drone = Arc::new(Mutex::new(drone));
+
+    // The reader blocks waiting for packets, so it is only watched for ending
+    let worker_timeout = Some(Duration::from_secs(WORKER_TIMEOUT));
+    let mut watchdog = Watchdog::new();
+    let writer_heartbeat = watchdog.register("writer", worker_timeout);
+    let intervals = config.get_intervals().clone();
+    let update_heartbeat = watchdog.register(
+        "update",
+        Some(Duration::from_secs(intervals.update_data + WORKER_TIMEOUT)),
+    );
+    let reader_heartbeat = watchdog.register("reader", None);
+    let state_machine_heartbeat = watchdog.register("state machine", worker_timeout);
+
+    // The workers of this drone stop with the process, or alone if the watchdog stops them,
+    // so the rest of a fleet keeps running
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let failed = Arc::new(AtomicBool::new(false));
+
+    // Every packet goes through a single writer, so the threads never wait for each other
+    // to use the stream
+    let (sender, receiver) = mpsc::channel();
+    let connection = Connection::new(sender);
+    let inflight = InflightPublishes::new(Duration::from_secs(PUBACK_TIMEOUT))
+        .with_max_inflight(MAX_INFLIGHT_PUBLISHES)
+        .with_max_resends(MAX_PUBLISH_RESENDS);
     let key = config.get_key().to_owned();
+    let thread_write = thread::spawn(move || {
+        write_packets(server_stream, receiver, inflight, &key, writer_heartbeat)
+    });
 
     let new_incident = TopicFilter::new(vec![TopicLevel::Literal(NEW_INCIDENT.to_vec())], false);
-
-    match server_stream.lock() {
-        Ok(mut server_stream) => {
-            subscribe(new_incident, &mut server_stream, &key)?;
-        }
-        Err(_) => {
-            return Err(std::io::Error::new(ErrorKind::Other, "Mutex was poisoned"));
-        }
+    let history_request = history_request_filter(config.get_id());
+    let command = command_filter(config.get_id());
+
+    connection.subscribe(new_incident)?;
+    connection.subscribe(history_request)?;
+    connection.subscribe(command)?;
+    connection.subscribe(incident_sync_filter(config.get_id()))?;
+    if config.get_operations_area().is_some() {
+        connection.subscribe(anchor_filter())?;
+    }
+    if config.get_separation_distance().is_some() {
+        connection.subscribe(drone_data_filter())?;
+    }
+    // A restored drone may have queued incidents that were closed while it was down
+    request_incident_sync(&connection, config.get_id())?;
+
+    // Everything that happens to the drone goes through a single queue of events, starting
+    // with the one that sends it to its anchor
+    let (events, event_receiver) = mpsc::channel();
+    if events.send(DroneEvent::Started).is_err() {
+        return Err(std::io::Error::other("Error starting the drone"));
     }
 
-    let server_stream_clone = server_stream.clone();
+    let connection_cloned = connection.clone();
     let drone_clone = drone.clone();
+    let events_cloned = events.clone();
+    let faults = fault_injector(&config);
+    let telemetry_filter = TelemetryFilter::new(config.get_publish_thresholds().clone());
+    let update_interval = Duration::from_secs(intervals.update_data);
+    let shutdown_cloned = shutdown.clone();
 
     let thread_update = thread::spawn(move || {
-        update_drone_status(server_stream_clone, drone_clone, &key);
+        update_drone_status(
+            &connection_cloned,
+            drone_clone,
+            &events_cloned,
+            faults,
+            telemetry_filter,
+            update_interval,
+            update_heartbeat,
+            &shutdown_cloned,
+        );
     });
 
-    let server_stream_cloned = server_stream.clone();
+    let connection_cloned = connection.clone();
     let drone_cloned = drone.clone();
+    let shutdown_cloned = shutdown.clone();
+    let config_cloned = config.clone();
 
     let thread_read = thread::spawn(move || {
-        read_incoming_packets(server_stream_cloned, drone_cloned, &key);
+        let _heartbeat = reader_heartbeat;
+        read_incoming_packets(
+            reader_stream,
+            &connection_cloned,
+            &events,
+            drone_cloned,
+            &shutdown_cloned,
+            &config_cloned,
+        );
     });
 
-    // Thread to handle pending incidents
-    let server_stream_cloned = server_stream.clone();
+    let connection_cloned = connection.clone();
     let drone_cloned = drone.clone();
-
-    let thread_pending_incidents = thread::spawn(move || {
-        handle_pending_incidents(drone_cloned, server_stream_cloned, &key);
+    let shutdown_cloned = shutdown.clone();
+    let battery_model = config.get_battery_model().clone();
+    let reassignment_interval = intervals.reassignment;
+    let thread_state_machine = thread::spawn(move || {
+        run_state_machine(
+            drone_cloned,
+            event_receiver,
+            &connection_cloned,
+            &battery_model,
+            reassignment_interval,
+            state_machine_heartbeat,
+            &shutdown_cloned,
+        );
     });
 
-    let drone_cloned = drone.clone();
-    let thread_discharge_battery = thread::spawn(move || {
-        discharge_battery(drone_cloned);
-    });
+    let mut threads = vec![thread_update, thread_state_machine];
+
+    if let Some(state_file) = &state_file {
+        let drone_cloned = drone.clone();
+        let shutdown_cloned = shutdown.clone();
+        let state_file = state_file.clone();
+        let save_interval = Duration::from_secs(intervals.save_state);
+        let heartbeat = watchdog.register(
+            "save state",
+            Some(Duration::from_secs(intervals.save_state + WORKER_TIMEOUT)),
+        );
+        threads.push(thread::spawn(move || {
+            save_state_periodically(
+                drone_cloned,
+                &state_file,
+                save_interval,
+                heartbeat,
+                &shutdown_cloned,
+            );
+        }));
+    }
 
+    if let Some(listener) = control_listener {
+        let drone_cloned = drone.clone();
+        let shutdown_cloned = shutdown.clone();
+        threads.push(thread::spawn(move || {
+            serve_control(listener, drone_cloned, &shutdown_cloned);
+        }));
+    }
+
+    // The watchdog stops once this function returns and drops the sender, whichever way it does
+    let (_watching, stop_watching) = mpsc::channel::<()>();
     let drone_cloned = drone.clone();
-    let thread_recharge_battery = thread::spawn(move || {
-        recharge_battery(drone_cloned);
+    let shutdown_cloned = shutdown.clone();
+    let failed_cloned = failed.clone();
+    thread::spawn(move || {
+        watch_workers(
+            watchdog,
+            drone_cloned,
+            stop_watching,
+            &shutdown_cloned,
+            &failed_cloned,
+        );
     });
 
-    let x = config.get_x_anchor_position();
-    let y = config.get_y_anchor_position();
+    while !process_shutdown.load(Ordering::Relaxed) && !shutdown.load(Ordering::Relaxed) {
+        thread::sleep(Duration::from_millis(SHUTDOWN_CHECK_INTERVAL));
+    }
+    shutdown.store(true, Ordering::Relaxed);
+
+    // A stalled worker may never end, so the threads aren't waited for. Closing the connection
+    // lets the server publish the last will of the drone, and the reader and writer end
+    if failed.load(Ordering::Relaxed) {
+        let _ = watchdog_stream.shutdown(Shutdown::Both);
+        return Err(std::io::Error::other(format!(
+            "The watchdog stopped drone {}",
+            config.get_id()
+        )));
+    }
+    println!("Shutting down the drone");
 
-    travel(drone.clone(), x, y, TravelLocation::Anchor);
-    let mut locked_drone = match drone.lock() {
-        Ok(drone) => drone,
+    for thread in threads {
+        match thread.join() {
+            Ok(_) => {}
+            Err(_) => {
+                return Err(std::io::Error::other("Error joining threads"));
+            }
+        }
+    }
+
+    // The drone stops where it is, keeping its queue to take it up again after restarting
+    match drone.lock() {
+        Ok(mut drone) => drone.set_status(DroneStatus::Disconnected),
+        Err(_) => {
+            return Err(std::io::Error::other("Mutex was poisoned"));
+        }
+    }
+
+    disconnect(&connection, &drone);
+
+    // The reader waits for packets until the stream is closed
+    match thread_write.join() {
+        Ok(stream) => {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        Err(_) => {
+            return Err(std::io::Error::other("Error joining threads"));
+        }
+    }
+    if thread_read.join().is_err() {
+        return Err(std::io::Error::other("Error joining threads"));
+    }
+
+    if let Some(state_file) = &state_file {
+        save_state(&drone, state_file);
+    }
+
+    Ok(())
+}
+
+/// Resumes the drone from the state it saved before its process restarted, if there is one
+fn restore_state(drone: Drone, state_file: &Path) -> Drone {
+    match DroneState::load(state_file) {
+        Ok(state) => {
+            println!("Restoring the drone from {}", state_file.display());
+            drone.with_state(state)
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => drone,
+        Err(e) => {
+            eprintln!("Error restoring the drone: {:?}", e);
+            drone
+        }
+    }
+}
+
+/// Saves the state of the drone to the state file
+fn save_state(drone: &Arc<Mutex<Drone>>, state_file: &Path) {
+    let state = match drone.lock() {
+        Ok(drone) => drone.state(),
         Err(_) => {
-            return Err(std::io::Error::new(ErrorKind::Other, "Mutex was poisoned"));
+            return;
         }
     };
 
-    if locked_drone.is_in_anchor() {
-        locked_drone.set_status(DroneStatus::Free);
+    if let Err(e) = state.save(state_file) {
+        eprintln!("Error saving the state of the drone: {:?}", e);
     }
+}
 
-    drop(locked_drone);
+/// Saves the state of the drone periodically, so it can resume if its process dies
+fn save_state_periodically(
+    drone: Arc<Mutex<Drone>>,
+    state_file: &Path,
+    interval: Duration,
+    heartbeat: Heartbeat,
+    shutdown: &AtomicBool,
+) {
+    while !shutdown.load(Ordering::Relaxed) {
+        heartbeat.beat();
+        save_state(&drone, state_file);
+        sleep_unless_shutdown(interval, shutdown);
+    }
+}
 
-    let threads = vec![
-        thread_update,
-        thread_read,
-        thread_pending_incidents,
-        thread_discharge_battery,
-        thread_recharge_battery,
-    ];
+/// Sleeps for the specified time, waking up early if the drone shuts down meanwhile
+fn sleep_unless_shutdown(duration: Duration, shutdown: &AtomicBool) {
+    let until = Instant::now() + duration;
+    while !shutdown.load(Ordering::Relaxed) {
+        let remaining = until.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return;
+        }
+        thread::sleep(remaining.min(Duration::from_millis(SHUTDOWN_CHECK_INTERVAL)));
+    }
+}
 
-    for thread in threads {
-        match thread.join() {
-            Ok(_) => {}
-            Err(_) => {
-                return Err(std::io::Error::new(
-                    ErrorKind::Other,
-                    "Error joining threads",
-                ));
+/// Checks the workers of the drone every second until it is told to stop. If one of them stalls,
+/// panics or ends before the drone shuts down, the drone would keep running without it, so it
+/// is marked as failed and shut down after printing what every worker and the drone were doing.
+/// A drone with a state file resumes from it once it is started again
+fn watch_workers(
+    watchdog: Watchdog,
+    drone: Arc<Mutex<Drone>>,
+    stop: Receiver<()>,
+    shutdown: &AtomicBool,
+    failed: &AtomicBool,
+) {
+    while let Err(RecvTimeoutError::Timeout) =
+        stop.recv_timeout(Duration::from_secs(WATCHDOG_INTERVAL))
+    {
+        let now = Instant::now();
+        let problems = watchdog.check(now, shutdown.load(Ordering::Relaxed));
+        if problems.is_empty() {
+            continue;
+        }
+
+        // The drone may be locked by the worker that stalled, so the watchdog never waits for it
+        let drone = match drone.try_lock() {
+            Ok(drone) => format!("{:?}: {}", drone.status(), drone.telemetry()),
+            Err(TryLockError::Poisoned(drone)) => {
+                let drone = drone.into_inner();
+                format!("{:?}: {} (poisoned)", drone.status(), drone.telemetry())
             }
+            Err(TryLockError::WouldBlock) => "locked".to_string(),
+        };
+        for (worker, problem) in problems {
+            eprintln!("Watchdog: the {} worker {}", worker, problem);
         }
+        eprintln!("{}\nDrone: {}", watchdog.report(now), drone);
+        failed.store(true, Ordering::Relaxed);
+        shutdown.store(true, Ordering::Relaxed);
+        return;
     }
+}
 
-    Ok(())
+/// Publishes the final status of the drone and disconnects from the server. The server discards
+/// the last will of a drone that disconnects, so the drone has to tell the monitor it is gone
+fn disconnect(connection: &Connection, drone: &Arc<Mutex<Drone>>) {
+    let (topic_name, message) = match drone.lock() {
+        Ok(drone) => (
+            TopicName::new(
+                vec![DRONE_DATA.to_vec(), drone.id().to_string().into_bytes()],
+                false,
+            ),
+            drone.disconnected_data().into_bytes(),
+        ),
+        Err(_) => {
+            return;
+        }
+    };
+
+    if let Err(e) = connection.publish(topic_name, message, QoS::AtMost) {
+        eprintln!("Error: {:?}", e);
+    }
+    if let Err(e) = connection.disconnect() {
+        eprintln!("Error: {:?}", e);
+    }
+}
+
+/// Returns the will the server publishes if the drone loses its connection: its last data with
+/// the disconnected status, retained so the monitor stops showing it as active
+fn last_will(drone: &Drone) -> Will {
+    let topic_name = TopicName::new(
+        vec![DRONE_DATA.to_vec(), drone.id().to_string().into_bytes()],
+        false,
+    );
+    let message = EncodedString::from_string(&drone.disconnected_data());
+    Will::new(QoS::AtLeast, true, topic_name, message)
 }
 
-/// Connects to the server with the specified address
-fn connect_to_server(config: Config) -> std::io::Result<TcpStream> {
+/// Connects to the server with the specified address. Neither connecting nor waiting for the
+/// CONNACK takes longer than the connect timeout, so a server that doesn't answer can't hang
+/// the drone
+fn connect_to_server(config: Config, will: Will) -> std::io::Result<TcpStream> {
     let address = config.get_address();
     let id = config.get_id();
     let username = config.get_username();
     let password = config.get_password();
     let key = config.get_key();
+    let timeout = Duration::from_secs(CONNECT_TIMEOUT);
 
-    let mut to_server_stream = TcpStream::connect(address)?;
+    let socket_address = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| std::io::Error::other(format!("Invalid address: {}", address)))?;
+    let mut to_server_stream = TcpStream::connect_timeout(&socket_address, timeout)?;
+    to_server_stream.set_read_timeout(Some(timeout))?;
 
     let client_id_bytes: Vec<u8> = id.to_string().into_bytes();
 
     let client_id = EncodedString::new(client_id_bytes);
-    let will = None;
+    let will = Some(will);
 
     let username = EncodedString::from_string(&username.to_string());
     let password = Some(EncodedString::from_string(&password.to_string()));
@@ -163,78 +480,278 @@ fn connect_to_server(config: Config) -> std::io::Result<TcpStream> {
     let login = Some(Login::new(username, password));
     let connect = Connect::new(false, 0, client_id, will, login);
 
-    let _ = to_server_stream.write(connect.to_bytes(key).as_slice());
+    to_server_stream.write_all(connect.to_bytes(key).as_slice())?;
 
     match Packet::from_bytes(&mut to_server_stream, key) {
         Ok(Packet::Connack(connack)) => match connack.connect_return_code() {
-            ConnectReturnCode::ConnectionAccepted => Ok(to_server_stream),
-            _ => Err(std::io::Error::new(
-                ErrorKind::Other,
-                format!("Connection refused: {:?}", connack.connect_return_code()),
-            )),
+            ConnectReturnCode::ConnectionAccepted => {
+                // The reader waits for the packets of the server as long as it takes
+                to_server_stream.set_read_timeout(None)?;
+                Ok(to_server_stream)
+            }
+            _ => Err(std::io::Error::other(format!(
+                "Connection refused: {:?}",
+                connack.connect_return_code()
+            ))),
         },
-        _ => Err(std::io::Error::new(ErrorKind::Other, "No connack recibed")),
+        _ => Err(std::io::Error::other("No connack recibed")),
     }
 }
 
-/// Reads incoming packets from the server
-fn read_incoming_packets(stream: Arc<Mutex<TcpStream>>, drone: Arc<Mutex<Drone>>, key: &[u8; 32]) {
-    loop {
-        let locked_stream = match stream.lock() {
-            Ok(stream) => stream,
-            Err(_) => {
-                return;
+/// Reads incoming packets from the server, reconnecting if the connection is lost.
+/// The messages become events of the drone, and the packets it has to send in response
+/// go to the writer
+fn read_incoming_packets(
+    mut stream: TcpStream,
+    connection: &Connection,
+    events: &Sender<DroneEvent>,
+    drone: Arc<Mutex<Drone>>,
+    shutdown: &AtomicBool,
+    config: &Config,
+) {
+    let key = config.get_key();
+    while !shutdown.load(Ordering::Relaxed) {
+        match Packet::from_bytes(&mut stream, key) {
+            Ok(Packet::Publish(publish)) => {
+                // The server holds further messages until the QoS 1 ones are acknowledged
+                if publish.qos() != &QoS::AtMost {
+                    if let Err(e) = connection.puback(publish.package_identifier()) {
+                        eprintln!("Error: {:?}", e);
+                    }
+                }
+                handle_publish(publish, &drone, connection, events);
             }
-        };
+            Ok(Packet::Puback(puback)) => {
+                if let Some(packet_identifier) = puback.packet_identifier() {
+                    if let Err(e) = connection.acknowledged(packet_identifier) {
+                        eprintln!("Error: {:?}", e);
+                    }
+                }
+            }
+            // Any I/O error means the connection was closed, which is expected while
+            // the drone shuts down
+            Ok(Packet::Disconnect(_)) | Err(MqttError::IoError(_)) => {
+                if shutdown.load(Ordering::Relaxed) {
+                    return;
+                }
+                stream = match reconnect(connection, &drone, shutdown, config) {
+                    Some(stream) => stream,
+                    None => {
+                        return;
+                    }
+                };
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Connects to the server again after the connection was lost, waiting longer after each
+/// failed attempt. The writer gets a clone of the new stream, with which it subscribes again
+/// to new incidents and to the incident being attended, and sends again the QoS 1 messages
+/// that weren't acknowledged. Returns the stream to read from, or None if the drone is
+/// shutting down
+fn reconnect(
+    connection: &Connection,
+    drone: &Arc<Mutex<Drone>>,
+    shutdown: &AtomicBool,
+    config: &Config,
+) -> Option<TcpStream> {
+    println!("Connection to the server lost, reconnecting");
+
+    let mut backoff = Duration::from_millis(RECONNECT_INITIAL_BACKOFF_MILLIS);
+    let new_stream = loop {
+        if shutdown.load(Ordering::Relaxed) {
+            return None;
+        }
 
-        let mut cloned_stream = match locked_stream.try_clone() {
-            Ok(stream) => stream,
+        let will = match drone.lock() {
+            Ok(drone) => last_will(&drone),
             Err(_) => {
-                return;
+                return None;
             }
         };
-
-        match cloned_stream.set_nonblocking(true) {
-            Ok(_) => {}
-            Err(_) => {
-                return;
+        match connect_to_server(config.clone(), will) {
+            Ok(stream) => break stream,
+            Err(e) => {
+                eprintln!("Error reconnecting: {:?}. Retrying in {:?}", e, backoff);
+                sleep_unless_shutdown(backoff, shutdown);
+                backoff = next_backoff(backoff);
             }
         }
+    };
 
-        match Packet::from_bytes(&mut cloned_stream, key) {
-            Ok(Packet::Publish(publish)) => {
-                drop(locked_stream);
-                let cloned_drone = drone.clone();
-                let cloned_stream = stream.clone();
+    // The drone may have been told to stop while it was connecting
+    if shutdown.load(Ordering::Relaxed) {
+        return None;
+    }
 
-                handle_publish(publish, cloned_drone, cloned_stream, key);
-                continue;
-            }
-            Ok(Packet::Puback(_)) => {}
-            Ok(Packet::Pingresp(_)) => {}
-            Ok(Packet::Suback(_)) => {}
-            Ok(Packet::Unsuback(_)) => {}
-            Ok(Packet::Pingreq(_)) => {}
-            Ok(Packet::Disconnect(_)) => {
-                break;
-            }
-            _ => {
-                drop(locked_stream);
-                thread::sleep(Duration::from_millis(READ_MESSAGE_INTERVAL));
-                continue;
-            }
+    let filters = match drone.lock() {
+        Ok(drone) => subscribed_filters(&drone),
+        Err(_) => {
+            return None;
+        }
+    };
+
+    let writer_stream = match new_stream.try_clone() {
+        Ok(stream) => stream,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            return None;
         }
+    };
+
+    if connection.reconnected(writer_stream, filters).is_err() {
+        return None;
+    }
+    if let Err(e) = request_incident_sync(connection, config.get_id()) {
+        eprintln!("Error: {:?}", e);
+    }
+
+    println!("Reconnected to the server");
+    Some(new_stream)
+}
+
+/// Returns the time to wait before the next reconnection attempt
+fn next_backoff(backoff: Duration) -> Duration {
+    (backoff * 2).min(Duration::from_millis(RECONNECT_MAX_BACKOFF_MILLIS))
+}
+
+/// Returns the topic filter of the requests for the history of the drone
+fn history_request_filter(id: u8) -> TopicFilter {
+    TopicFilter::new(
+        vec![
+            TopicLevel::Literal(DRONE_HISTORY.to_vec()),
+            TopicLevel::Literal(id.to_string().into_bytes()),
+        ],
+        false,
+    )
+}
+
+/// Returns the topic filter of the commands operators send to the drone
+fn command_filter(id: u8) -> TopicFilter {
+    TopicFilter::new(
+        vec![
+            TopicLevel::Literal(DRONE_COMMAND.to_vec()),
+            TopicLevel::Literal(id.to_string().into_bytes()),
+        ],
+        false,
+    )
+}
+
+/// Returns the topic filter of the answers of the monitor to the incident sync requests
+/// of the drone
+fn incident_sync_filter(id: u8) -> TopicFilter {
+    TopicFilter::new(
+        vec![
+            TopicLevel::Literal(INCIDENT_SYNC.to_vec()),
+            TopicLevel::Literal(id.to_string().into_bytes()),
+            TopicLevel::Literal(DATA.to_vec()),
+        ],
+        false,
+    )
+}
+
+/// Asks the monitor for the incidents that aren't resolved, so the drone learns of the ones
+/// it missed while it was disconnected. The monitor answers to `incident-sync/<id>/data`
+fn request_incident_sync(connection: &Connection, id: u8) -> std::io::Result<()> {
+    connection.publish(
+        TopicName::new(
+            vec![INCIDENT_SYNC.to_vec(), id.to_string().into_bytes()],
+            false,
+        ),
+        Vec::new(),
+        QoS::AtLeast,
+    )
+}
+
+/// Returns the topic filter of the anchors of every drone
+fn anchor_filter() -> TopicFilter {
+    TopicFilter::new(
+        vec![
+            TopicLevel::Literal(DRONE_ANCHOR.to_vec()),
+            TopicLevel::SingleLevelWildcard,
+        ],
+        false,
+    )
+}
+
+/// Returns the topic filter of the data of every drone
+fn drone_data_filter() -> TopicFilter {
+    TopicFilter::new(
+        vec![
+            TopicLevel::Literal(DRONE_DATA.to_vec()),
+            TopicLevel::SingleLevelWildcard,
+        ],
+        false,
+    )
+}
+
+/// Returns the topic filters the drone is subscribed to: new incidents, requests for its history,
+/// commands, the answers to its incident sync requests, the anchors of the other drones if it
+/// negotiates its own, their data if it keeps its distance from them and, while it handles
+/// an incident, the drones attending it or, once enough drones arrived, its closing
+fn subscribed_filters(drone: &Drone) -> Vec<TopicFilter> {
+    let mut filters = vec![
+        TopicFilter::new(vec![TopicLevel::Literal(NEW_INCIDENT.to_vec())], false),
+        history_request_filter(drone.id()),
+        command_filter(drone.id()),
+        incident_sync_filter(drone.id()),
+    ];
+    if drone.negotiates_anchor() {
+        filters.push(anchor_filter());
+    }
+    if drone.keeps_separation() {
+        filters.push(drone_data_filter());
+    }
+
+    let handling_incident = matches!(
+        drone.status(),
+        DroneStatus::Travelling(TravelLocation::Incident) | DroneStatus::AttendingIncident
+    );
+    if let Some(incident) = drone.current_incident().filter(|_| handling_incident) {
+        let topic = if drone.attending_counter() >= drone.required_drones(&incident) {
+            IncidentTopic::Close
+        } else {
+            IncidentTopic::Attending
+        };
+        filters.push(incident_filter(topic, &incident.uuid));
+        filters.push(incident_filter(IncidentTopic::Claim, &incident.uuid));
+    }
+
+    for incident in drone.covered_incidents() {
+        filters.push(incident_filter(IncidentTopic::Claim, &incident.uuid));
+    }
 
-        drop(locked_stream);
+    filters
+}
+
+/// Returns the first level of the topic of an incident
+fn incident_action(topic: IncidentTopic) -> &'static [u8] {
+    match topic {
+        IncidentTopic::Attending => ATTENDING_INCIDENT,
+        IncidentTopic::Close => CLOSE_INCIDENT,
+        IncidentTopic::Claim => INCIDENT_CLAIM,
     }
 }
 
-/// Handles the incoming publish packet
+/// Returns the topic filter of an incident the drone follows
+fn incident_filter(topic: IncidentTopic, uuid: &str) -> TopicFilter {
+    TopicFilter::new(
+        vec![
+            TopicLevel::Literal(incident_action(topic).to_vec()),
+            TopicLevel::Literal(uuid.as_bytes().to_vec()),
+        ],
+        false,
+    )
+}
+
+/// Handles the incoming publish packet, turning it into an event of the drone
 fn handle_publish(
     publish: Publish,
-    drone: Arc<Mutex<Drone>>,
-    server_stream: Arc<Mutex<TcpStream>>,
-    key: &[u8; 32],
+    drone: &Arc<Mutex<Drone>>,
+    connection: &Connection,
+    events: &Sender<DroneEvent>,
 ) {
     let message = match String::from_utf8(publish.message().to_vec()) {
         Ok(message) => message,
@@ -250,9 +767,73 @@ fn handle_publish(
         }
     };
 
-    match action {
-        NEW_INCIDENT => handle_new_incident(message, drone),
-        ATTENDING_INCIDENT | CLOSE_INCIDENT => {
+    let event = match action {
+        NEW_INCIDENT => match Incident::from_string(message) {
+            Ok(incident) => DroneEvent::IncidentReported(incident),
+            Err(_) => {
+                return;
+            }
+        },
+        DRONE_HISTORY => {
+            handle_history_request(drone, connection);
+            return;
+        }
+        INCIDENT_SYNC => DroneEvent::IncidentsSynced(
+            message
+                .split(INCIDENT_SEPARATOR)
+                .filter(|incident| !incident.is_empty())
+                .filter_map(|incident| Incident::from_string(incident.to_string()).ok())
+                .collect(),
+        ),
+        DRONE_ANCHOR => {
+            let drone_id = topic_levels
+                .get(1)
+                .and_then(|id| String::from_utf8(id.to_vec()).ok())
+                .and_then(|id| id.parse::<u8>().ok());
+            match (drone_id, DroneAnchor::from_string(&message)) {
+                (Some(drone_id), Ok(anchor)) => DroneEvent::AnchorReceived(
+                    drone_id,
+                    Position::new(anchor.x_coordinate, anchor.y_coordinate),
+                ),
+                _ => {
+                    println!("Invalid drone anchor");
+                    return;
+                }
+            }
+        }
+        DRONE_DATA => {
+            let drone_id = topic_levels
+                .get(1)
+                .and_then(|id| String::from_utf8(id.to_vec()).ok())
+                .and_then(|id| id.parse::<u8>().ok());
+            let fields: Vec<&str> = message.split(';').collect();
+            let position = match (fields.first(), fields.get(1)) {
+                (Some(x), Some(y)) => match (x.parse::<f64>(), y.parse::<f64>()) {
+                    (Ok(x), Ok(y)) => Some(Position::new(x, y)),
+                    _ => None,
+                },
+                _ => None,
+            };
+            let disconnected = fields.get(2).is_some_and(|status| {
+                DroneStatus::get_status_from_str(status) == DroneStatus::Disconnected
+            });
+            match (drone_id, position) {
+                (Some(drone_id), _) if disconnected => DroneEvent::PeerDisconnected(drone_id),
+                (Some(drone_id), Some(position)) => DroneEvent::PeerMoved(drone_id, position),
+                _ => {
+                    println!("Invalid drone data");
+                    return;
+                }
+            }
+        }
+        DRONE_COMMAND => match DroneCommand::from_string(&message) {
+            Ok(command) => DroneEvent::Command(command),
+            Err(_) => {
+                println!("Invalid drone command");
+                return;
+            }
+        },
+        ATTENDING_INCIDENT | CLOSE_INCIDENT | INCIDENT_CLAIM => {
             let uuid = match topic_levels.get(1) {
                 Some(uuid) => match String::from_utf8(uuid.to_vec()) {
                     Ok(uuid) => uuid,
@@ -268,153 +849,86 @@ fn handle_publish(
             };
 
             match action {
-                ATTENDING_INCIDENT => handle_attending_incident(uuid, drone),
-                CLOSE_INCIDENT => handle_close_incident(uuid, drone, server_stream, key),
-                _ => {}
+                ATTENDING_INCIDENT => DroneEvent::PeerAttending(uuid),
+                CLOSE_INCIDENT => DroneEvent::IncidentClosed(uuid),
+                _ => match IncidentClaim::from_string(&message) {
+                    Some(claim) => DroneEvent::ClaimReceived(uuid, claim),
+                    None => {
+                        println!("Invalid incident claim");
+                        return;
+                    }
+                },
             }
         }
-        _ => {}
-    }
-}
-
-/// Handles the new incident
-fn handle_new_incident(message: String, drone: Arc<Mutex<Drone>>) {
-    let incident = match Incident::from_string(message) {
-        Ok(incident) => incident,
-        Err(_) => {
+        _ => {
             return;
         }
     };
 
-    let mut locked_drone = match drone.lock() {
-        Ok(drone) => drone,
-        Err(_) => {
-            return;
-        }
-    };
-
-    if locked_drone.is_within_range(incident.x_coordinate, incident.y_coordinate) {
-        locked_drone.add_incident(incident);
+    if let Err(e) = events.send(event) {
+        eprintln!("Error: {:?}", e);
     }
-
-    drop(locked_drone);
 }
 
-/// Handles the attending incident
-fn handle_attending_incident(uuid: String, drone: Arc<Mutex<Drone>>) {
-    let mut drone_locked = match drone.lock() {
-        Ok(drone) => drone,
+/// Handles a request for the history of the drone, publishing it to `drone-history/<id>/data`
+/// so the monitor can draw where the drone has been
+fn handle_history_request(drone: &Arc<Mutex<Drone>>, connection: &Connection) {
+    let (topic_name, message) = match drone.lock() {
+        Ok(drone) => (
+            TopicName::new(
+                vec![
+                    DRONE_HISTORY.to_vec(),
+                    drone.id().to_string().into_bytes(),
+                    DATA.to_vec(),
+                ],
+                false,
+            ),
+            drone.history().into_bytes(),
+        ),
         Err(_) => {
             return;
         }
     };
 
-    let incident = match drone_locked.current_incident() {
-        Some(incident) => incident,
-        None => {
-            return;
-        }
-    };
-
-    if incident.uuid != uuid {
-        return;
-    }
-
-    drone_locked.increment_attending_counter();
-
-    if drone_locked.attending_counter() == DRONE_COUNT_PER_INCIDENT
-        && drone_locked.status() != DroneStatus::AttendingIncident
-    {
-        drone_locked.set_status(DroneStatus::Interrupted);
+    if let Err(e) = connection.publish(topic_name, message, QoS::AtMost) {
+        eprintln!("Error: {:?}", e);
     }
-
-    drop(drone_locked);
 }
 
-/// Handles the closing of an incident
-fn handle_close_incident(
-    closing_incident_uuid: String,
-    drone: Arc<Mutex<Drone>>,
-    server_stream: Arc<Mutex<TcpStream>>,
-    key: &[u8; 32],
-) {
-    let mut locked_drone = match drone.lock() {
-        Ok(drone) => drone,
-        Err(_) => {
-            return;
-        }
-    };
-
-    let current_incident = match locked_drone.current_incident() {
-        Some(current_incident) => current_incident,
-        None => {
-            return;
-        }
-    };
-
-    if current_incident.uuid != closing_incident_uuid {
-        return;
-    }
-
-    // locked_drone.set_incident(None);
-    locked_drone.remove_current_incident();
-
-    let x = locked_drone.x_anchor_coordinate();
-    let y = locked_drone.y_anchor_coordinate();
-
-    drop(locked_drone);
-
-    let topic_filter = TopicFilter::new(
-        vec![
-            TopicLevel::Literal(CLOSE_INCIDENT.to_vec()),
-            TopicLevel::Literal(current_incident.uuid.clone().into_bytes()),
-        ],
-        false,
-    );
-
-    let mut stream = match server_stream.lock() {
-        Ok(stream) => stream,
-        Err(_) => {
-            println!("Mutex was poisoned");
-            return;
-        }
+/// Returns the injector of the faults of the drone. The drones of a fleet share the seed of
+/// the config, so each of them adds its id to it
+fn fault_injector(config: &Config) -> FaultInjector {
+    let fault_model = config.get_fault_model().clone();
+    let seed = match fault_model.seed {
+        Some(seed) => seed.wrapping_add(u64::from(config.get_id())),
+        None => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or_default(),
     };
-
-    match unsubscribe(topic_filter, &mut stream, key) {
-        Ok(_) => {}
-        Err(e) => eprintln!("Error: {:?}", e),
-    }
-
-    drop(stream);
-
-    thread::spawn(move || {
-        travel(drone.clone(), x, y, TravelLocation::Anchor);
-
-        let mut locked_drone = match drone.lock() {
-            Ok(drone) => drone,
-            Err(_) => {
-                return;
-            }
-        };
-
-        if locked_drone.is_in_anchor() {
-            locked_drone.set_status(DroneStatus::Free);
-        }
-
-        drop(locked_drone);
-    });
+    FaultInjector::new(fault_model, seed)
 }
 
-/// Updates the drone status
+/// Updates the drone status every interval, injecting the faults of the drone. The data is
+/// only published when the filter finds it changed enough, but it is always kept in the history
+#[allow(clippy::too_many_arguments)]
 fn update_drone_status(
-    server_stream: Arc<Mutex<TcpStream>>,
+    connection: &Connection,
     drone: Arc<Mutex<Drone>>,
-    key: &[u8; 32],
+    events: &Sender<DroneEvent>,
+    mut faults: FaultInjector,
+    mut filter: TelemetryFilter,
+    interval: Duration,
+    heartbeat: Heartbeat,
+    shutdown: &AtomicBool,
 ) {
-    loop {
-        let drone = match drone.lock() {
+    let mut last_sample = None;
+    while !shutdown.load(Ordering::Relaxed) {
+        heartbeat.beat();
+        let mut drone = match drone.lock() {
             Ok(drone) => drone,
             Err(_) => {
+                log_poisoned(connection, &drone, "status updates");
                 return;
             }
         };
@@ -423,453 +937,349 @@ fn update_drone_status(
         levels.push(drone.id().to_string().into_bytes());
 
         let topic_name = TopicName::new(levels, false);
-        let message = drone.data().into_bytes();
+        let mut telemetry = drone.telemetry();
+        telemetry.position = faults.jitter(telemetry.position);
+        // A frozen drone keeps reporting the same data
+        let telemetry = match last_sample.take() {
+            Some(sample) if faults.is_frozen() => sample,
+            _ => telemetry,
+        };
+        let message = filter
+            .should_publish(&telemetry)
+            .then(|| telemetry.to_string().into_bytes());
+        last_sample = Some(telemetry);
+        drone.record_telemetry();
+
+        // The route is only published when it changes, so the monitor can draw it
+        let route = drone.take_new_route().map(|route| {
+            let levels = vec![DRONE_ROUTE.to_vec(), drone.id().to_string().into_bytes()];
+            (TopicName::new(levels, false), route.into_bytes())
+        });
 
         drop(drone);
-        let mut stream = match server_stream.lock() {
-            Ok(server_stream) => server_stream,
-            Err(_) => {
-                return;
-            }
-        };
 
-        match publish(topic_name, message, &mut stream, QoS::AtMost, key) {
-            Ok(_) => {}
-            Err(e) => eprintln!("Error: {:?}", e),
+        if let Some(message) = message {
+            if let Err(e) = connection.publish(topic_name, message, QoS::AtMost) {
+                eprintln!("Error: {:?}", e);
+            }
         }
 
-        drop(stream);
-
-        thread::sleep(Duration::from_secs(UPDATE_DATA_INTERVAL));
-    }
-}
-
-/// Subscribes to the specified topic filter
-fn subscribe(
-    filter: TopicFilter,
-    server_stream: &mut MutexGuard<TcpStream>,
-    key: &[u8; 32],
-) -> std::io::Result<()> {
-    let mut server_stream = match server_stream.try_clone() {
-        Ok(stream) => stream,
-        Err(_) => {
-            println!("Mutex was poisoned");
-            return Err(std::io::Error::new(ErrorKind::Other, "Mutex was poisoned"));
+        if let Some((topic_name, route)) = route {
+            if let Err(e) = connection.publish(topic_name, route, QoS::AtMost) {
+                eprintln!("Error: {:?}", e);
+            }
         }
-    };
-
-    let packet_id = 1;
-    let qos = QoS::AtMost;
-    let topics_filters = vec![(filter, qos)];
-
-    let subscribe_packet = Subscribe::new(packet_id, topics_filters);
-    let _ = server_stream.write(subscribe_packet.to_bytes(key).as_slice());
-
-    Ok(())
-}
 
-/// Unsubscribes from the specified topic filter
-fn unsubscribe(
-    filter: TopicFilter,
-    server_stream: &mut MutexGuard<TcpStream>,
-    key: &[u8; 32],
-) -> std::io::Result<()> {
-    let mut server_stream = match server_stream.try_clone() {
-        Ok(stream) => stream,
-        Err(_) => {
-            return Err(std::io::Error::new(ErrorKind::Other, "Mutex was poisoned"));
+        if faults.drops_connection() {
+            println!("Injected fault: dropping the connection");
+            if let Err(e) = connection.drop_connection() {
+                eprintln!("Error: {:?}", e);
+            }
         }
-    };
-
-    let packet_id = 1;
-    let topics_filters = vec![(filter)];
-
-    let unsubscribe_packet = Unsubscribe::new(packet_id, topics_filters);
-
-    let _ = server_stream.write(unsubscribe_packet.to_bytes(key).as_slice());
-
-    Ok(())
-}
-
-/// Publishes the specified message to the server
-fn publish(
-    topic_name: TopicName,
-    message: Vec<u8>,
-    server_stream: &mut MutexGuard<TcpStream>,
-    qos: QoS,
-    key: &[u8; 32],
-) -> std::io::Result<()> {
-    let mut server_stream = match server_stream.try_clone() {
-        Ok(stream) => stream,
-        Err(_) => {
-            return Err(std::io::Error::new(ErrorKind::Other, "Mutex was poisoned"));
+        if let Some(drop) = faults.battery_fault() {
+            println!("Injected fault: the battery lost {} units", drop);
+            if let Err(e) = events.send(DroneEvent::BatteryFault(drop)) {
+                eprintln!("Error: {:?}", e);
+            }
         }
-    };
 
-    let dup = false;
-    let retain = true;
-    let package_identifier = None;
-    let message_bytes = message;
-
-    let publish_packet = Publish::new(
-        dup,
-        qos.clone(),
-        retain,
-        topic_name,
-        package_identifier,
-        message_bytes,
-    );
-
-    let _ = server_stream.write(publish_packet.to_bytes(key).as_slice());
-
-    Ok(())
+        sleep_unless_shutdown(interval, shutdown);
+    }
 }
 
-/// Travels to the specified location
-fn travel(drone: Arc<Mutex<Drone>>, x: f64, y: f64, travel_location: TravelLocation) {
-    let mut locked_drone = match drone.lock() {
-        Ok(drone) => drone,
+/// Runs the state machine of the drone until the shutdown flag is set. The events are handled
+/// one at a time, in the order they arrive, together with the ones that happen every interval
+/// and the ones the drone schedules, so no two transitions ever overlap
+fn run_state_machine(
+    drone: Arc<Mutex<Drone>>,
+    events: Receiver<DroneEvent>,
+    connection: &Connection,
+    battery_model: &BatteryModel,
+    reassignment_interval: u64,
+    heartbeat: Heartbeat,
+    shutdown: &AtomicBool,
+) {
+    let drone_id = match drone.lock() {
+        Ok(drone) => drone.id(),
         Err(_) => {
             return;
         }
     };
 
-    locked_drone.set_status(DroneStatus::Travelling(travel_location));
-    drop(locked_drone);
-
-    loop {
-        let mut locked_drone = match drone.lock() {
-            Ok(drone) => drone,
-            Err(_) => {
-                return;
-            }
-        };
-        let distance = locked_drone.distance_to(x, y);
-        let status = locked_drone.status();
-
-        if distance == 0.0 || status != DroneStatus::Travelling(travel_location) {
-            drop(locked_drone);
-            break;
-        }
-
-        locked_drone.travel_to(x, y);
-        drop(locked_drone);
-        thread::sleep(Duration::from_secs(TRAVEL_INTERVAL));
+    let now = Instant::now();
+    let mut timers = Timers::new();
+    for (event, interval) in [
+        (DroneEvent::Tick, TRAVEL_INTERVAL.as_secs()),
+        (
+            DroneEvent::BatteryDischarge,
+            battery_model.discharge_interval,
+        ),
+        (DroneEvent::BatteryRecharge, battery_model.recharge_interval),
+        (DroneEvent::BatteryCheck, battery_model.check_interval),
+        (DroneEvent::ReassignmentCheck, reassignment_interval),
+    ] {
+        timers.repeat(event, Duration::from_secs(interval), now);
     }
-}
 
-/// Handles the pending incidents of the drone queue
-fn handle_pending_incidents(
-    drone: Arc<Mutex<Drone>>,
-    server_stream: Arc<Mutex<TcpStream>>,
-    key: &[u8; 32],
-) {
-    loop {
-        let locked_drone = match drone.lock() {
-            Ok(drone) => drone,
-            Err(_) => {
-                continue;
+    while !shutdown.load(Ordering::Relaxed) {
+        heartbeat.beat();
+        let now = Instant::now();
+        let mut pending = timers.take_due(now);
+
+        if pending.is_empty() {
+            // The wait is short enough to notice the shutdown flag
+            let timeout = timers
+                .next_due()
+                .map_or(Duration::MAX, |due| due.saturating_duration_since(now))
+                .min(Duration::from_millis(SHUTDOWN_CHECK_INTERVAL));
+            match events.recv_timeout(timeout) {
+                Ok(event) => pending.push(event),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    return;
+                }
             }
-        };
-
-        if locked_drone.is_below_minimun() {
-            drop(locked_drone);
-            thread::sleep(Duration::from_secs(PENDING_INCIDENTS_INTERVAL));
-            continue;
         }
 
-        if !locked_drone.is_free() {
-            drop(locked_drone);
-            thread::sleep(Duration::from_secs(PENDING_INCIDENTS_INTERVAL));
-            continue;
-        }
-
-        match locked_drone.current_incident() {
-            Some(incident) => {
-                drop(locked_drone);
-
-                let drone = drone.clone();
-                let server_stream = server_stream.clone();
-                let key = *key;
-
-                thread::spawn(move || {
-                    handle_incident(incident, drone.clone(), server_stream.clone(), &key);
-                });
+        for event in pending {
+            let (actions, logs) = match drone.lock() {
+                Ok(mut drone) => (drone.handle_event(event), drone.take_logs()),
+                Err(_) => {
+                    log_poisoned(connection, &drone, "state machine");
+                    return;
+                }
+            };
+            for log in logs {
+                publish_log(connection, drone_id, &log);
             }
-            None => {
-                drop(locked_drone);
+            for action in actions {
+                perform(action, drone_id, connection, &mut timers);
             }
         }
-        thread::sleep(Duration::from_secs(PENDING_INCIDENTS_INTERVAL));
     }
 }
 
-/// Handles the last incident
-fn handle_incident(
-    incident: Incident,
-    drone: Arc<Mutex<Drone>>,
-    server_stream: Arc<Mutex<TcpStream>>,
-    key: &[u8; 32],
-) {
-    let topic_filter = TopicFilter::new(
-        vec![
-            TopicLevel::Literal(ATTENDING_INCIDENT.to_vec()),
-            TopicLevel::Literal(incident.uuid.clone().into_bytes()),
-        ],
+/// Carries out an action of the drone
+/// Publishes an event of the drone to its log topic, so the operators can follow it from the
+/// monitor
+fn publish_log(connection: &Connection, drone_id: u8, log: &DroneLog) {
+    let topic_name = TopicName::new(
+        vec![DRONE_LOG.to_vec(), drone_id.to_string().into_bytes()],
         false,
     );
-
-    let mut stream_locked = match server_stream.lock() {
-        Ok(stream) => stream,
-        Err(_) => {
-            return;
-        }
-    };
-
-    match subscribe(topic_filter, &mut stream_locked, key) {
-        Ok(_) => {}
-        Err(e) => {
-            eprintln!("Error: {:?}", e);
-        }
+    if let Err(e) = connection.publish(topic_name, log.to_string().into_bytes(), QoS::AtMost) {
+        eprintln!("Error: {:?}", e);
     }
+}
 
-    drop(stream_locked);
-
-    travel(
-        drone.clone(),
-        incident.x_coordinate,
-        incident.y_coordinate,
-        TravelLocation::Incident,
+/// Tells the operators a worker stops because another one panicked while it held the drone
+fn log_poisoned(connection: &Connection, drone: &Mutex<Drone>, worker: &str) {
+    let drone_id = drone.lock().unwrap_or_else(PoisonError::into_inner).id();
+    let log = DroneLog::new(
+        LogLevel::Error,
+        "mutex-poisoned",
+        format!("the {} stopped", worker),
     );
+    eprintln!("Error: {}", log);
+    publish_log(connection, drone_id, &log);
+}
 
-    let mut drone_locked = match drone.lock() {
-        Ok(drone) => drone,
-        Err(_) => {
-            return;
-        }
+fn perform(action: DroneAction, drone_id: u8, connection: &Connection, timers: &mut Timers) {
+    let incident_topic_name = |action: &[u8], uuid: String| {
+        TopicName::new(vec![action.to_vec(), uuid.into_bytes()], false)
     };
 
-    if drone_locked.is_interrupted() {
-        drone_locked.remove_current_incident();
-
-        let x = drone_locked.x_anchor_coordinate();
-        let y = drone_locked.y_anchor_coordinate();
-        drop(drone_locked);
-
-        travel(drone.clone(), x, y, TravelLocation::Anchor);
-
-        let mut locked_drone = match drone.lock() {
-            Ok(drone) => drone,
-            Err(_) => {
-                return;
-            }
-        };
-        if locked_drone.is_in_anchor() {
-            locked_drone.set_status(DroneStatus::Free);
+    let result = match action {
+        DroneAction::Subscribe(topic, uuid) => connection.subscribe(incident_filter(topic, &uuid)),
+        DroneAction::Unsubscribe(topic, uuid) => {
+            connection.unsubscribe(incident_filter(topic, &uuid))
         }
-
-        drop(locked_drone);
-
-        return;
-    }
-
-    drone_locked.set_status(DroneStatus::AttendingIncident);
-    drop(drone_locked);
-
-    let mut locked_stream = match server_stream.lock() {
-        Ok(stream) => stream,
-        Err(_) => {
-            return;
+        DroneAction::PublishClaim(uuid, claim) => connection.publish(
+            incident_topic_name(INCIDENT_CLAIM, uuid),
+            claim.to_string().into_bytes(),
+            QoS::AtLeast,
+        ),
+        // Losing it would leave the other drones waiting for this one, so it is sent until
+        // the server acknowledges it
+        DroneAction::PublishAttending(uuid) => connection.publish(
+            incident_topic_name(ATTENDING_INCIDENT, uuid),
+            Vec::new(),
+            QoS::AtLeast,
+        ),
+        DroneAction::PublishReady(uuid) => connection.publish(
+            incident_topic_name(READY_INCIDENT, uuid),
+            Vec::new(),
+            QoS::AtLeast,
+        ),
+        DroneAction::PublishRejected(uuid) => connection.publish(
+            incident_topic_name(REJECTED_INCIDENT, uuid),
+            drone_id.to_string().into_bytes(),
+            QoS::AtLeast,
+        ),
+        DroneAction::PublishChargingStation(station) => connection.publish(
+            TopicName::new(
+                vec![CHARGING_STATION.to_vec(), drone_id.to_string().into_bytes()],
+                false,
+            ),
+            format!("{};{}", station.x, station.y).into_bytes(),
+            QoS::AtLeast,
+        ),
+        DroneAction::PublishCommandAck(ack) => {
+            println!(
+                "Command {} {}",
+                ack.command,
+                if ack.accepted { "accepted" } else { "rejected" }
+            );
+            connection.publish(
+                TopicName::new(
+                    vec![
+                        DRONE_COMMAND.to_vec(),
+                        drone_id.to_string().into_bytes(),
+                        COMMAND_ACK.to_vec(),
+                    ],
+                    false,
+                ),
+                ack.to_string().into_bytes(),
+                QoS::AtLeast,
+            )
         }
-    };
-
-    let topic_name = TopicName::new(
-        vec![
-            TopicLevel::Literal(ATTENDING_INCIDENT.to_vec()).to_bytes(),
-            TopicLevel::Literal(incident.uuid.clone().into_bytes()).to_bytes(),
-        ],
-        false,
-    );
-    let message = b"".to_vec();
-
-    match publish(topic_name, message, &mut locked_stream, QoS::AtMost, key) {
-        Ok(_) => {}
-        Err(_) => println!("Drone is attending the incident. no le llego el puback"),
-    }
-
-    drop(locked_stream);
-
-    loop {
-        let locked_drone = match drone.lock() {
-            Ok(drone) => drone,
-            Err(_) => {
-                return;
-            }
-        };
-
-        if locked_drone.attending_counter() >= DRONE_COUNT_PER_INCIDENT {
-            drop(locked_drone);
-            break;
+        DroneAction::PublishAnchor(anchor) => {
+            println!(
+                "Anchored at ({}, {})",
+                anchor.x_coordinate, anchor.y_coordinate
+            );
+            connection.publish(
+                TopicName::new(
+                    vec![DRONE_ANCHOR.to_vec(), drone_id.to_string().into_bytes()],
+                    false,
+                ),
+                anchor.to_string().into_bytes(),
+                QoS::AtLeast,
+            )
         }
-
-        drop(locked_drone);
-        thread::sleep(Duration::from_secs(WAIT_FOR_DRONE_INTERVAL));
-    }
-
-    let mut locked_stream = match server_stream.lock() {
-        Ok(stream) => stream,
-        Err(_) => {
-            return;
+        DroneAction::Schedule(event, delay) => {
+            timers.schedule(event, delay, Instant::now());
+            Ok(())
         }
     };
 
-    let topic_filter = TopicFilter::new(
-        vec![
-            TopicLevel::Literal(CLOSE_INCIDENT.to_vec()),
-            TopicLevel::Literal(incident.uuid.clone().into_bytes()),
-        ],
-        false,
-    );
-
-    match subscribe(topic_filter, &mut locked_stream, key) {
-        Ok(_) => {}
-        Err(_) => println!("Drone subscribe to close incident topic. no le llego el suback"),
+    if let Err(e) = result {
+        eprintln!("Error: {:?}", e);
     }
+}
 
-    let topic_filter = TopicFilter::new(
-        vec![
-            TopicLevel::Literal(ATTENDING_INCIDENT.to_vec()),
-            TopicLevel::Literal(incident.uuid.clone().into_bytes()),
-        ],
-        false,
-    );
-
-    match unsubscribe(topic_filter, &mut locked_stream, key) {
-        Ok(_) => {}
-        Err(e) => eprintln!("Error: {:?}", e),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::incident::IncidentStatus;
+
+    fn incident() -> Incident {
+        Incident::new(
+            "1234".to_string(),
+            "Fire".to_string(),
+            "Fire in the park".to_string(),
+            1.0,
+            1.0,
+            IncidentStatus::InProgress,
+        )
     }
 
-    drop(locked_stream);
-
-    let duration_incident = Duration::from_secs(DRONE_ATTENDING_DURATION);
-
-    thread::sleep(duration_incident);
-
-    let topic_name = TopicName::new(
-        vec![
-            TopicLevel::Literal(READY_INCIDENT.to_vec()).to_bytes(),
-            TopicLevel::Literal(incident.uuid.into_bytes()).to_bytes(),
-        ],
-        false,
-    );
-    let message = b"".to_vec();
-
-    let mut locked_stream = match server_stream.lock() {
-        Ok(stream) => stream,
-        Err(_) => {
-            return;
-        }
-    };
-
-    match publish(topic_name, message, &mut locked_stream, QoS::AtMost, key) {
-        Ok(_) => {}
-        Err(e) => eprintln!("Error: {:?}", e),
+    fn filter_levels(filters: &[TopicFilter]) -> Vec<Vec<TopicLevel>> {
+        filters
+            .iter()
+            .map(|filter| filter.levels().to_vec())
+            .collect()
     }
 
-    drop(locked_stream);
-}
-
-/// Discharges the battery of the drone
-fn discharge_battery(drone: Arc<Mutex<Drone>>) {
-    loop {
-        let mut locked_drone = match drone.lock() {
-            Ok(drone) => drone,
-            Err(_) => {
-                return;
-            }
-        };
-
-        locked_drone.discharge_battery();
-        drop(locked_drone);
+    #[test]
+    fn test_backoff_doubles_until_the_maximum() {
+        let backoff = Duration::from_millis(RECONNECT_INITIAL_BACKOFF_MILLIS);
+        assert_eq!(next_backoff(backoff), Duration::from_millis(1000));
 
-        thread::sleep(Duration::from_secs(BATTERY_DISCHARGE_INTERVAL));
+        let backoff = Duration::from_millis(RECONNECT_MAX_BACKOFF_MILLIS - 1);
+        assert_eq!(
+            next_backoff(backoff),
+            Duration::from_millis(RECONNECT_MAX_BACKOFF_MILLIS)
+        );
     }
-}
-
-/// Recharges the battery of the drone
-fn recharge_battery(drone: Arc<Mutex<Drone>>) {
-    loop {
-        let locked_drone = match drone.lock() {
-            Ok(drone) => drone,
-            Err(_) => {
-                return;
-            }
-        };
-
-        if !locked_drone.is_below_minimun() || !locked_drone.is_free() {
-            drop(locked_drone);
-            thread::sleep(Duration::from_secs(CHECK_BATTERY_INTERVAL));
-            continue;
-        }
-
-        let x = locked_drone.x_central_coordinate();
-        let y = locked_drone.y_central_coordinate();
-        drop(locked_drone);
-
-        travel(drone.clone(), x, y, TravelLocation::Central);
 
-        let mut locked_drone = match drone.lock() {
-            Ok(drone) => drone,
-            Err(_) => {
-                return;
-            }
-        };
-
-        locked_drone.set_status(DroneStatus::Recharging);
-        drop(locked_drone);
-
-        loop {
-            let mut locked_drone = match drone.lock() {
-                Ok(drone) => drone,
-                Err(_) => {
-                    return;
-                }
-            };
-
-            locked_drone.recharge_battery();
-            if locked_drone.is_fully_charged() {
-                drop(locked_drone);
-                break;
-            }
-            drop(locked_drone);
-
-            thread::sleep(Duration::from_secs(BATTERY_RECHARGE_INTERVAL));
-        }
-
-        let locked_drone = match drone.lock() {
-            Ok(drone) => drone,
-            Err(_) => {
-                return;
-            }
-        };
-
-        let x = locked_drone.x_anchor_coordinate();
-        let y = locked_drone.y_anchor_coordinate();
-        drop(locked_drone);
-
-        travel(drone.clone(), x, y, TravelLocation::Anchor);
+    #[test]
+    fn test_free_drone_only_resubscribes_to_new_incidents_history_requests_commands_and_syncs() {
+        let mut drone = Drone::new(1, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0);
+        drone.set_status(DroneStatus::Free);
+        drone.add_incident(incident());
+
+        assert_eq!(
+            filter_levels(&subscribed_filters(&drone)),
+            vec![
+                vec![TopicLevel::Literal(NEW_INCIDENT.to_vec())],
+                vec![
+                    TopicLevel::Literal(DRONE_HISTORY.to_vec()),
+                    TopicLevel::Literal(b"1".to_vec())
+                ],
+                vec![
+                    TopicLevel::Literal(DRONE_COMMAND.to_vec()),
+                    TopicLevel::Literal(b"1".to_vec())
+                ],
+                vec![
+                    TopicLevel::Literal(INCIDENT_SYNC.to_vec()),
+                    TopicLevel::Literal(b"1".to_vec()),
+                    TopicLevel::Literal(DATA.to_vec())
+                ]
+            ]
+        );
+    }
 
-        let mut locked_drone = match drone.lock() {
-            Ok(drone) => drone,
-            Err(_) => {
-                return;
-            }
+    #[test]
+    fn test_drone_with_an_automatic_anchor_resubscribes_to_the_anchors() {
+        let area = crate::anchor::OperationsArea {
+            center: Position::new(1.0, 1.0),
+            radius: 1.0,
         };
+        let drone = Drone::new(1, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0).with_operations_area(Some(area));
+
+        assert_eq!(
+            filter_levels(&subscribed_filters(&drone))[4],
+            vec![
+                TopicLevel::Literal(DRONE_ANCHOR.to_vec()),
+                TopicLevel::SingleLevelWildcard
+            ]
+        );
+    }
 
-        if locked_drone.is_in_anchor() {
-            locked_drone.set_status(DroneStatus::Free);
+    #[test]
+    fn test_drone_handling_an_incident_resubscribes_to_it() {
+        let mut drone = Drone::new(1, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0);
+        drone.add_incident(incident());
+        drone.set_status(DroneStatus::Travelling(TravelLocation::Incident));
+
+        let filters = subscribed_filters(&drone);
+        assert_eq!(
+            filter_levels(&filters)[4],
+            vec![
+                TopicLevel::Literal(ATTENDING_INCIDENT.to_vec()),
+                TopicLevel::Literal(b"1234".to_vec())
+            ]
+        );
+        assert_eq!(
+            filter_levels(&filters)[5],
+            vec![
+                TopicLevel::Literal(INCIDENT_CLAIM.to_vec()),
+                TopicLevel::Literal(b"1234".to_vec())
+            ]
+        );
+
+        drone.set_status(DroneStatus::AttendingIncident);
+        for _ in 0..drone.required_drones(&incident()) {
+            drone.increment_attending_counter();
         }
-
-        drop(locked_drone);
+        let filters = subscribed_filters(&drone);
+        assert_eq!(
+            filter_levels(&filters)[4],
+            vec![
+                TopicLevel::Literal(CLOSE_INCIDENT.to_vec()),
+                TopicLevel::Literal(b"1234".to_vec())
+            ]
+        );
     }
 }
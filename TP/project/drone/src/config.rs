@@ -1,7 +1,56 @@
 use std::collections::HashMap;
 use std::io;
+use std::str::FromStr;
 use std::{fs::File, io::Read, path::Path};
 
+use common::incident::DEFAULT_REQUIRED_DRONES;
+
+use crate::anchor::OperationsArea;
+use crate::battery::BatteryModel;
+use crate::faults::FaultModel;
+use crate::geofence::NoFlyZone;
+use crate::history::DEFAULT_HISTORY_LENGTH;
+use crate::telemetry::PublishThresholds;
+use crate::utils::Position;
+
+const CHARGING_STATIONS: &str = "charging_stations";
+const NO_FLY_ZONES: &str = "no_fly_zones";
+const FLEET: &str = "fleet";
+
+/// Represents how often the drone does the work that isn't driven by messages, in seconds.
+/// Longer intervals put less load on the server, at the cost of older data in the monitor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Intervals {
+    /// Time between the telemetry messages of the drone
+    pub update_data: u64,
+    /// Time between the checks for an incident another drone gave up
+    pub reassignment: u64,
+    /// Time between the saves of the state file
+    pub save_state: u64,
+}
+
+impl Default for Intervals {
+    fn default() -> Self {
+        Intervals {
+            update_data: 1,
+            reassignment: 5,
+            save_state: 5,
+        }
+    }
+}
+
+/// Represents another drone run by the same process, which shares the configuration of the
+/// file except for the keys it sets
+#[derive(Debug, Clone)]
+struct FleetMember {
+    id: u8,
+    username: Option<String>,
+    password: Option<String>,
+    anchor: Position,
+    state_file: Option<String>,
+    control_port: Option<u16>,
+}
+
 /// Represents the configuration of a drone
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -16,6 +65,20 @@ pub struct Config {
     y_anchor_position: f64,
     velocity: f64,
     active_range: f64,
+    battery_model: BatteryModel,
+    charging_stations: Vec<Position>,
+    no_fly_zones: Vec<NoFlyZone>,
+    drones_per_incident: usize,
+    state_file: Option<String>,
+    history_length: usize,
+    fault_model: FaultModel,
+    intervals: Intervals,
+    publish_thresholds: PublishThresholds,
+    operations_area: Option<OperationsArea>,
+    separation_distance: Option<f64>,
+    acceleration: Option<f64>,
+    control_port: Option<u16>,
+    fleet: Vec<FleetMember>,
 }
 
 impl Config {
@@ -29,20 +92,72 @@ impl Config {
         let json = contents.trim().trim_matches(|c| c == '{' || c == '}');
 
         let mut config_map = HashMap::new();
+        let mut charging_stations = Vec::new();
+        let mut no_fly_zones = Vec::new();
+        let mut fleet = Vec::new();
+        let mut current_array = None;
+        let mut current_item = HashMap::new();
 
         for line in json.lines().map(str::trim).filter(|line| !line.is_empty()) {
-            let parts: Vec<&str> = line.splitn(2, ':').collect();
-            let key = parts[0].trim_matches('"').trim();
-            let value = parts[1].trim().trim_matches(|c| c == '"' || c == ',');
+            if let Some(array) = current_array {
+                if line.starts_with(']') {
+                    current_array = None;
+                } else if line.starts_with('{') {
+                    current_item.clear();
+                } else if line.starts_with('}') {
+                    match array {
+                        CHARGING_STATIONS => {
+                            charging_stations.push(charging_station_from(&current_item)?)
+                        }
+                        NO_FLY_ZONES => no_fly_zones.push(no_fly_zone_from(&current_item)?),
+                        _ => fleet.push(fleet_member_from(&mut current_item)?),
+                    }
+                    current_item.clear();
+                } else if let Some((key, value)) = key_value(line) {
+                    current_item.insert(key, value);
+                }
+                continue;
+            }
 
-            if parts.len() != 2 {
+            let Some((key, value)) = key_value(line) else {
                 continue;
+            };
+
+            match key.as_str() {
+                CHARGING_STATIONS => current_array = Some(CHARGING_STATIONS),
+                NO_FLY_ZONES => current_array = Some(NO_FLY_ZONES),
+                FLEET => current_array = Some(FLEET),
+                _ => {
+                    config_map.insert(key, value);
+                }
             }
+        }
 
-            config_map.insert(key.to_string(), value.to_string());
+        let battery_model = battery_model_from(&mut config_map)?;
+        let drones_per_incident = optional(
+            &mut config_map,
+            "drones_per_incident",
+            DEFAULT_REQUIRED_DRONES,
+        )?;
+        if drones_per_incident == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid drones_per_incident",
+            ));
         }
 
-        Ok(Config {
+        let state_file = config_map.remove("state_file");
+        let history_length = optional(&mut config_map, "history_length", DEFAULT_HISTORY_LENGTH)?;
+        let fault_model = fault_model_from(&mut config_map)?;
+        let intervals = intervals_from(&mut config_map)?;
+        let publish_thresholds = publish_thresholds_from(&mut config_map)?;
+        let auto_anchor = optional(&mut config_map, "auto_anchor", false)?;
+        let operations_radius = config_map.remove("operations_radius");
+        let separation_distance = optional_positive(&mut config_map, "separation_distance")?;
+        let acceleration = optional_positive(&mut config_map, "acceleration")?;
+        let control_port = control_port_from(&mut config_map)?;
+
+        let mut config = Config {
             address: config_map
                 .remove("address")
                 .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing address"))?,
@@ -106,7 +221,82 @@ impl Config {
                 .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing active_range"))?
                 .parse::<f64>()
                 .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid active_range"))?,
-        })
+            battery_model,
+            charging_stations,
+            no_fly_zones,
+            drones_per_incident,
+            state_file,
+            history_length,
+            fault_model,
+            intervals,
+            publish_thresholds,
+            operations_area: None,
+            separation_distance,
+            acceleration,
+            control_port,
+            fleet,
+        };
+
+        // The drones with an automatic anchor spread around the central, as far as their
+        // active range unless the config says otherwise
+        if auto_anchor {
+            let radius = match operations_radius {
+                Some(radius) => radius.parse::<f64>().map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "Invalid operations_radius")
+                })?,
+                None => config.active_range,
+            };
+            if radius <= 0.0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Invalid operations_radius",
+                ));
+            }
+            config.operations_area = Some(OperationsArea {
+                center: Position::new(config.x_central_position, config.y_central_position),
+                radius,
+            });
+        }
+
+        let mut ids = vec![config.id];
+        for member in &config.fleet {
+            if ids.contains(&member.id) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Repeated drone id {} in the fleet", member.id),
+                ));
+            }
+            ids.push(member.id);
+        }
+
+        Ok(config)
+    }
+
+    /// Returns the configuration of every drone the process runs: the one of the file and one
+    /// for each member of its fleet. Each of them connects to the server on its own, since the
+    /// server identifies the drones by their connection
+    pub fn get_fleet(&self) -> Vec<Config> {
+        let mut drone = self.clone();
+        drone.fleet.clear();
+
+        let members = self.fleet.iter().map(|member| Config {
+            id: member.id,
+            username: member
+                .username
+                .clone()
+                .unwrap_or_else(|| self.username.clone()),
+            password: member
+                .password
+                .clone()
+                .unwrap_or_else(|| self.password.clone()),
+            x_anchor_position: member.anchor.x,
+            y_anchor_position: member.anchor.y,
+            state_file: member.state_file.clone(),
+            control_port: member.control_port,
+            ..drone.clone()
+        });
+
+        std::iter::once(drone.clone()).chain(members).collect()
     }
 
     /// Returns the address of the drone
@@ -163,4 +353,691 @@ impl Config {
     pub fn get_active_range(&self) -> f64 {
         self.active_range
     }
+
+    /// Returns the battery model of the drone
+    pub fn get_battery_model(&self) -> &BatteryModel {
+        &self.battery_model
+    }
+
+    /// Returns the positions of the charging stations besides the central
+    pub fn get_charging_stations(&self) -> Vec<Position> {
+        self.charging_stations.clone()
+    }
+
+    /// Returns the number of drones that attend the incidents that don't say how many they need
+    pub fn get_drones_per_incident(&self) -> usize {
+        self.drones_per_incident
+    }
+
+    /// Returns the file the drone saves its state to, if it has to resume after restarting
+    pub fn get_state_file(&self) -> Option<&str> {
+        self.state_file.as_deref()
+    }
+
+    /// Returns the number of telemetry samples the drone keeps in its history
+    pub fn get_history_length(&self) -> usize {
+        self.history_length
+    }
+
+    /// Returns the faults injected into the drone
+    pub fn get_fault_model(&self) -> &FaultModel {
+        &self.fault_model
+    }
+
+    /// Returns how often the drone publishes its data and does its periodic work
+    pub fn get_intervals(&self) -> &Intervals {
+        &self.intervals
+    }
+
+    /// Returns how much the data of the drone has to change before it is published again
+    pub fn get_publish_thresholds(&self) -> &PublishThresholds {
+        &self.publish_thresholds
+    }
+
+    /// Returns the area the drone chooses its anchor in, if it doesn't use the one of the config
+    pub fn get_operations_area(&self) -> Option<OperationsArea> {
+        self.operations_area.clone()
+    }
+
+    /// Returns the distance the drone keeps from the other drones while it travels, if it
+    /// keeps one
+    pub fn get_separation_distance(&self) -> Option<f64> {
+        self.separation_distance
+    }
+
+    /// Returns how much the speed of the drone changes every step as it departs and arrives,
+    /// if it doesn't always move at its velocity
+    pub fn get_acceleration(&self) -> Option<f64> {
+        self.acceleration
+    }
+
+    /// Returns the port of the local control socket, if the drone has one
+    pub fn get_control_port(&self) -> Option<u16> {
+        self.control_port
+    }
+
+    /// Returns the areas the drone can't fly over
+    pub fn get_no_fly_zones(&self) -> Vec<NoFlyZone> {
+        self.no_fly_zones.clone()
+    }
+}
+
+/// Splits a line in its key and value, without quotes or trailing commas
+fn key_value(line: &str) -> Option<(String, String)> {
+    let (key, value) = line.split_once(':')?;
+    let key = key.trim().trim_matches('"');
+    let value = value.trim().trim_matches(|c| c == '"' || c == ',');
+    Some((key.to_string(), value.to_string()))
+}
+
+/// Reads the position of a charging station
+fn charging_station_from(station: &HashMap<String, String>) -> io::Result<Position> {
+    let coordinate = |key: &str| {
+        station
+            .get(key)
+            .and_then(|value| value.parse::<f64>().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid charging station"))
+    };
+    Ok(Position::new(
+        coordinate("x_coordinate")?,
+        coordinate("y_coordinate")?,
+    ))
+}
+
+/// Reads a no-fly zone
+fn no_fly_zone_from(zone: &HashMap<String, String>) -> io::Result<NoFlyZone> {
+    let field = |key: &str| {
+        zone.get(key)
+            .and_then(|value| value.parse::<f64>().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid no-fly zone"))
+    };
+    let radius = field("radius")?;
+    if radius <= 0.0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid no-fly zone",
+        ));
+    }
+    Ok(NoFlyZone::new(
+        field("x_coordinate")?,
+        field("y_coordinate")?,
+        radius,
+    ))
+}
+
+/// Reads a member of the fleet. The id and the anchor are required, while the username and
+/// the password are the ones of the file if they are missing
+fn fleet_member_from(member: &mut HashMap<String, String>) -> io::Result<FleetMember> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Invalid fleet member");
+    let id = member
+        .get("id")
+        .and_then(|value| value.parse::<u8>().ok())
+        .ok_or_else(invalid)?;
+    let coordinate = |key: &str| {
+        member
+            .get(key)
+            .and_then(|value| value.parse::<f64>().ok())
+            .ok_or_else(invalid)
+    };
+    let anchor = Position::new(
+        coordinate("x_anchor_position")?,
+        coordinate("y_anchor_position")?,
+    );
+
+    Ok(FleetMember {
+        id,
+        username: member.remove("username"),
+        password: member.remove("password"),
+        anchor,
+        state_file: member.remove("state_file"),
+        control_port: control_port_from(member)?,
+    })
+}
+
+/// Reads the battery model, using the default value of every key that is missing
+fn battery_model_from(config_map: &mut HashMap<String, String>) -> io::Result<BatteryModel> {
+    let default = BatteryModel::default();
+    let battery_model = BatteryModel {
+        minimum_level: optional(config_map, "minimum_battery_level", default.minimum_level)?,
+        maximum_level: optional(config_map, "maximum_battery_level", default.maximum_level)?,
+        discharge_distance: optional(
+            config_map,
+            "battery_discharge_distance",
+            default.discharge_distance,
+        )?,
+        discharge_attending: optional(
+            config_map,
+            "battery_discharge_attending",
+            default.discharge_attending,
+        )?,
+        discharge_idle: optional(config_map, "battery_discharge_idle", default.discharge_idle)?,
+        recharge: optional(config_map, "battery_recharge", default.recharge)?,
+        check_interval: optional(config_map, "check_battery_interval", default.check_interval)?,
+        discharge_interval: optional(
+            config_map,
+            "battery_discharge_interval",
+            default.discharge_interval,
+        )?,
+        recharge_interval: optional(
+            config_map,
+            "battery_recharge_interval",
+            default.recharge_interval,
+        )?,
+    };
+
+    if battery_model.minimum_level > battery_model.maximum_level {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "minimum_battery_level is above maximum_battery_level",
+        ));
+    }
+    if battery_model.discharge_distance <= 0.0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid battery_discharge_distance",
+        ));
+    }
+    if battery_model.recharge == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid battery_recharge",
+        ));
+    }
+
+    Ok(battery_model)
+}
+
+/// Reads the intervals of the drone, using the default value of every key that is missing
+fn intervals_from(config_map: &mut HashMap<String, String>) -> io::Result<Intervals> {
+    let default = Intervals::default();
+    let intervals = Intervals {
+        update_data: optional(config_map, "update_data_interval", default.update_data)?,
+        reassignment: optional(config_map, "reassignment_interval", default.reassignment)?,
+        save_state: optional(config_map, "save_state_interval", default.save_state)?,
+    };
+
+    for (key, interval) in [
+        ("update_data_interval", intervals.update_data),
+        ("reassignment_interval", intervals.reassignment),
+        ("save_state_interval", intervals.save_state),
+    ] {
+        if interval == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid {}", key),
+            ));
+        }
+    }
+
+    Ok(intervals)
+}
+
+/// Reads how much the data of the drone has to change before it is published again, using
+/// the default value of every key that is missing
+fn publish_thresholds_from(
+    config_map: &mut HashMap<String, String>,
+) -> io::Result<PublishThresholds> {
+    let default = PublishThresholds::default();
+    let thresholds = PublishThresholds {
+        position: optional(config_map, "publish_position_threshold", default.position)?,
+        battery: optional(config_map, "publish_battery_threshold", default.battery)?,
+        heartbeat: optional(config_map, "publish_heartbeat", default.heartbeat)?,
+    };
+
+    if thresholds.position < 0.0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid publish_position_threshold",
+        ));
+    }
+    if thresholds.heartbeat == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid publish_heartbeat",
+        ));
+    }
+
+    Ok(thresholds)
+}
+
+/// Reads the faults injected into the drone, which are disabled unless their keys are present
+fn fault_model_from(config_map: &mut HashMap<String, String>) -> io::Result<FaultModel> {
+    let default = FaultModel::default();
+    let fault_model = FaultModel {
+        connection_drop_probability: optional(
+            config_map,
+            "fault_connection_drop_probability",
+            default.connection_drop_probability,
+        )?,
+        gps_jitter: optional(config_map, "fault_gps_jitter", default.gps_jitter)?,
+        battery_fault_probability: optional(
+            config_map,
+            "fault_battery_probability",
+            default.battery_fault_probability,
+        )?,
+        battery_fault_drop: optional(config_map, "fault_battery_drop", default.battery_fault_drop)?,
+        freeze_probability: optional(
+            config_map,
+            "fault_freeze_probability",
+            default.freeze_probability,
+        )?,
+        freeze_duration: optional(config_map, "fault_freeze_duration", default.freeze_duration)?,
+        seed: match config_map.remove("fault_seed") {
+            Some(seed) => {
+                Some(seed.parse::<u64>().map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "Invalid fault_seed")
+                })?)
+            }
+            None => None,
+        },
+    };
+
+    let probabilities = [
+        fault_model.connection_drop_probability,
+        fault_model.battery_fault_probability,
+        fault_model.freeze_probability,
+    ];
+    if probabilities
+        .iter()
+        .any(|probability| !(0.0..=1.0).contains(probability))
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Fault probabilities must be between 0 and 1",
+        ));
+    }
+    if fault_model.gps_jitter < 0.0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid fault_gps_jitter",
+        ));
+    }
+
+    Ok(fault_model)
+}
+
+/// Reads the port of the control socket, if the drone has one. The drones of a fleet don't
+/// share the one of the file, as they can't listen on the same port
+fn control_port_from(config_map: &mut HashMap<String, String>) -> io::Result<Option<u16>> {
+    match config_map.remove("control_port") {
+        Some(port) => match port.parse::<u16>() {
+            Ok(port) if port > 0 => Ok(Some(port)),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid control_port",
+            )),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Parses the value of a key that has to be greater than 0, if it is present
+fn optional_positive(
+    config_map: &mut HashMap<String, String>,
+    key: &str,
+) -> io::Result<Option<f64>> {
+    match config_map.remove(key) {
+        Some(value) => match value.parse::<f64>() {
+            Ok(value) if value > 0.0 => Ok(Some(value)),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid {}", key),
+            )),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Parses the value of a key, or returns the default if it is missing
+fn optional<T: FromStr>(
+    config_map: &mut HashMap<String, String>,
+    key: &str,
+    default: T,
+) -> io::Result<T> {
+    match config_map.remove(key) {
+        Some(value) => value
+            .parse::<T>()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid {}", key))),
+        None => Ok(default),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    const REQUIRED_KEYS: &str = r#"    "address": "127.0.0.1:8080",
+    "id": 1,
+    "username": "drone1",
+    "password": "sauron",
+    "key": "12345678901234567890123456789012",
+    "x_central_position": 0.0,
+    "y_central_position": 0.0,
+    "x_anchor_position": 1.0,
+    "y_anchor_position": 1.0,
+    "velocity": 0.0004,
+    "active_range": 0.01"#;
+
+    fn config_from(name: &str, extra_keys: &str) -> io::Result<Config> {
+        let path = env::temp_dir().join(format!("drone-config-{}-{}.json", process::id(), name));
+        fs::write(&path, format!("{{\n{},\n{}\n}}", REQUIRED_KEYS, extra_keys))?;
+        let config = Config::from_file(&path);
+        let _ = fs::remove_file(&path);
+        config
+    }
+
+    #[test]
+    fn test_battery_model_keys_are_optional() {
+        let config = config_from("defaults", r#"    "unused": 0"#).unwrap();
+        assert_eq!(config.get_battery_model(), &BatteryModel::default());
+        assert_eq!(config.get_drones_per_incident(), DEFAULT_REQUIRED_DRONES);
+        assert_eq!(config.get_state_file(), None);
+        assert_eq!(config.get_history_length(), DEFAULT_HISTORY_LENGTH);
+        assert_eq!(config.get_intervals(), &Intervals::default());
+        assert_eq!(config.get_operations_area(), None);
+        assert_eq!(config.get_separation_distance(), None);
+        assert_eq!(config.get_acceleration(), None);
+
+        let config = config_from(
+            "custom",
+            r#"    "maximum_battery_level": 200,
+    "battery_recharge": 10,
+    "battery_recharge_interval": 2,
+    "state_file": "drone_1.state""#,
+        )
+        .unwrap();
+        assert_eq!(config.get_state_file(), Some("drone_1.state"));
+        let battery_model = config.get_battery_model();
+        assert_eq!(battery_model.maximum_level, 200);
+        assert_eq!(battery_model.recharge, 10);
+        assert_eq!(battery_model.recharge_interval, 2);
+        assert_eq!(battery_model.minimum_level, 20);
+    }
+
+    #[test]
+    fn test_charging_stations_are_read() {
+        let config = config_from("no-stations", r#"    "unused": 0"#).unwrap();
+        assert!(config.get_charging_stations().is_empty());
+
+        let config = config_from(
+            "stations",
+            r#"    "charging_stations": [
+        {
+            "x_coordinate": -58.38,
+            "y_coordinate": -34.60
+        },
+        {
+            "x_coordinate": -58.39,
+            "y_coordinate": -34.61
+        }
+    ],
+    "battery_recharge": 10"#,
+        )
+        .unwrap();
+        let stations: Vec<(f64, f64)> = config
+            .get_charging_stations()
+            .iter()
+            .map(|station| (station.x, station.y))
+            .collect();
+        assert_eq!(stations, vec![(-58.38, -34.60), (-58.39, -34.61)]);
+        assert_eq!(config.get_battery_model().recharge, 10);
+
+        let missing_coordinate = r#"    "charging_stations": [
+        {
+            "x_coordinate": -58.38
+        }
+    ]"#;
+        assert!(config_from("invalid-station", missing_coordinate).is_err());
+    }
+
+    #[test]
+    fn test_no_fly_zones_are_read() {
+        let config = config_from(
+            "zones",
+            r#"    "no_fly_zones": [
+        {
+            "x_coordinate": -58.38,
+            "y_coordinate": -34.60,
+            "radius": 0.002
+        }
+    ],
+    "charging_stations": [
+        {
+            "x_coordinate": -58.39,
+            "y_coordinate": -34.61
+        }
+    ]"#,
+        )
+        .unwrap();
+        let zones = config.get_no_fly_zones();
+        assert_eq!(zones.len(), 1);
+        assert_eq!(
+            (zones[0].center.x, zones[0].center.y, zones[0].radius),
+            (-58.38, -34.60, 0.002)
+        );
+        assert_eq!(config.get_charging_stations().len(), 1);
+
+        let negative_radius = r#"    "no_fly_zones": [
+        {
+            "x_coordinate": -58.38,
+            "y_coordinate": -34.60,
+            "radius": -1
+        }
+    ]"#;
+        assert!(config_from("invalid-zone", negative_radius).is_err());
+    }
+
+    #[test]
+    fn test_fleet_members_share_the_rest_of_the_config() {
+        let config = config_from("no-fleet", r#"    "unused": 0"#).unwrap();
+        assert_eq!(config.get_fleet().len(), 1);
+
+        let config = config_from(
+            "fleet",
+            r#"    "fleet": [
+        {
+            "id": 2,
+            "username": "drone2",
+            "x_anchor_position": 2.0,
+            "y_anchor_position": 3.0,
+            "state_file": "drone_2.state"
+        },
+        {
+            "id": 3,
+            "x_anchor_position": 4.0,
+            "y_anchor_position": 5.0
+        }
+    ],
+    "velocity": 0.0007"#,
+        )
+        .unwrap();
+        let fleet = config.get_fleet();
+        let drones: Vec<_> = fleet
+            .iter()
+            .map(|drone| {
+                (
+                    drone.get_id(),
+                    drone.get_username(),
+                    drone.get_password(),
+                    drone.get_x_anchor_position(),
+                    drone.get_y_anchor_position(),
+                    drone.get_state_file(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            drones,
+            vec![
+                (1, "drone1", "sauron", 1.0, 1.0, None),
+                (2, "drone2", "sauron", 2.0, 3.0, Some("drone_2.state")),
+                (3, "drone1", "sauron", 4.0, 5.0, None),
+            ]
+        );
+        assert!(fleet.iter().all(|drone| drone.get_velocity() == 0.0007));
+        assert!(fleet.iter().all(|drone| drone.get_fleet().len() == 1));
+
+        let repeated_id = r#"    "fleet": [
+        {
+            "id": 1,
+            "x_anchor_position": 2.0,
+            "y_anchor_position": 3.0
+        }
+    ]"#;
+        assert!(config_from("repeated-id", repeated_id).is_err());
+
+        let missing_anchor = r#"    "fleet": [
+        {
+            "id": 2
+        }
+    ]"#;
+        assert!(config_from("invalid-member", missing_anchor).is_err());
+    }
+
+    #[test]
+    fn test_faults_are_disabled_unless_configured() {
+        let config = config_from("no-faults", r#"    "unused": 0"#).unwrap();
+        assert_eq!(config.get_fault_model(), &FaultModel::default());
+
+        let config = config_from(
+            "faults",
+            r#"    "fault_connection_drop_probability": 0.01,
+    "fault_gps_jitter": 0.0001,
+    "fault_battery_probability": 0.05,
+    "fault_seed": 42"#,
+        )
+        .unwrap();
+        let fault_model = config.get_fault_model();
+        assert_eq!(fault_model.connection_drop_probability, 0.01);
+        assert_eq!(fault_model.gps_jitter, 0.0001);
+        assert_eq!(fault_model.battery_fault_probability, 0.05);
+        assert_eq!(fault_model.battery_fault_drop, 30);
+        assert_eq!(fault_model.freeze_probability, 0.0);
+        assert_eq!(fault_model.seed, Some(42));
+
+        assert!(config_from("likely", r#"    "fault_freeze_probability": 2"#).is_err());
+        assert!(config_from("negative", r#"    "fault_gps_jitter": -1"#).is_err());
+    }
+
+    #[test]
+    fn test_publish_thresholds_are_read() {
+        let config = config_from("no-thresholds", r#"    "unused": 0"#).unwrap();
+        assert_eq!(
+            config.get_publish_thresholds(),
+            &PublishThresholds::default()
+        );
+
+        let config = config_from(
+            "thresholds",
+            r#"    "publish_position_threshold": 0.0001,
+    "publish_heartbeat": 30"#,
+        )
+        .unwrap();
+        let thresholds = config.get_publish_thresholds();
+        assert_eq!(thresholds.position, 0.0001);
+        assert_eq!(thresholds.battery, 1);
+        assert_eq!(thresholds.heartbeat, 30);
+
+        assert!(config_from("negative", r#"    "publish_position_threshold": -1"#).is_err());
+        assert!(config_from("no-heartbeat", r#"    "publish_heartbeat": 0"#).is_err());
+    }
+
+    #[test]
+    fn test_intervals_are_read() {
+        let config = config_from(
+            "intervals",
+            r#"    "update_data_interval": 10,
+    "save_state_interval": 30"#,
+        )
+        .unwrap();
+        let intervals = config.get_intervals();
+        assert_eq!(intervals.update_data, 10);
+        assert_eq!(intervals.reassignment, 5);
+        assert_eq!(intervals.save_state, 30);
+
+        assert!(config_from("zero-interval", r#"    "update_data_interval": 0"#).is_err());
+        assert!(config_from("negative-interval", r#"    "reassignment_interval": -1"#).is_err());
+    }
+
+    #[test]
+    fn test_operations_area_is_read() {
+        let config = config_from("auto-anchor", r#"    "auto_anchor": true"#).unwrap();
+        assert_eq!(
+            config.get_operations_area(),
+            Some(OperationsArea {
+                center: Position::new(0.0, 0.0),
+                radius: 0.01,
+            })
+        );
+
+        let config = config_from(
+            "operations-radius",
+            r#"    "auto_anchor": true,
+    "operations_radius": 0.05"#,
+        )
+        .unwrap();
+        assert_eq!(config.get_operations_area().unwrap().radius, 0.05);
+
+        assert!(config_from(
+            "no-radius",
+            r#"    "auto_anchor": true,
+    "operations_radius": 0"#
+        )
+        .is_err());
+        assert!(config_from("invalid-auto-anchor", r#"    "auto_anchor": yes"#).is_err());
+    }
+
+    #[test]
+    fn test_separation_distance_is_read() {
+        let config = config_from("separation", r#"    "separation_distance": 0.0005"#).unwrap();
+        assert_eq!(config.get_separation_distance(), Some(0.0005));
+        assert!(config_from("no-separation", r#"    "separation_distance": 0"#).is_err());
+    }
+
+    #[test]
+    fn test_acceleration_is_read() {
+        let config = config_from("acceleration", r#"    "acceleration": 0.0001"#).unwrap();
+        assert_eq!(config.get_acceleration(), Some(0.0001));
+        assert!(config_from("braking", r#"    "acceleration": -0.0001"#).is_err());
+    }
+
+    #[test]
+    fn test_control_port_is_not_shared_with_the_fleet() {
+        let config = config_from(
+            "control",
+            r#"    "control_port": 7001,
+    "fleet": [
+        {
+            "id": 2,
+            "x_anchor_position": 2.0,
+            "y_anchor_position": 3.0
+        },
+        {
+            "id": 3,
+            "x_anchor_position": 4.0,
+            "y_anchor_position": 5.0,
+            "control_port": 7003
+        }
+    ]"#,
+        )
+        .unwrap();
+        let ports: Vec<_> = config
+            .get_fleet()
+            .iter()
+            .map(Config::get_control_port)
+            .collect();
+        assert_eq!(ports, vec![Some(7001), None, Some(7003)]);
+        assert!(config_from("no-control", r#"    "control_port": 0"#).is_err());
+    }
+
+    #[test]
+    fn test_invalid_battery_model_is_rejected() {
+        assert!(config_from("invalid", r#"    "battery_recharge": "fast""#).is_err());
+        assert!(config_from(
+            "inverted",
+            r#"    "minimum_battery_level": 50,
+    "maximum_battery_level": 40"#
+        )
+        .is_err());
+    }
 }
@@ -0,0 +1,300 @@
+use std::{
+    io::{self, ErrorKind, Write},
+    net::{Shutdown, TcpStream},
+    sync::mpsc::{Receiver, RecvTimeoutError, Sender},
+    time::{Duration, Instant},
+};
+
+use mqtt::model::{
+    components::{qos::QoS, topic_filter::TopicFilter, topic_name::TopicName},
+    packets::{
+        disconnect::Disconnect, puback::Puback, publish::Publish, subscribe::Subscribe,
+        unsubscribe::Unsubscribe,
+    },
+};
+
+use crate::{inflight::InflightPublishes, watchdog::Heartbeat};
+
+/// Time the writer waits for packets before checking if a QoS 1 message has to be sent again
+const RESEND_CHECK_INTERVAL: u64 = 100;
+
+/// Represents what the drone sends to the thread that writes to the server
+#[derive(Debug)]
+pub enum Outgoing<W = TcpStream> {
+    /// A message to publish. The QoS 1 ones are kept until the server acknowledges them
+    Publish(TopicName, Vec<u8>, QoS),
+    Subscribe(TopicFilter),
+    Unsubscribe(TopicFilter),
+    /// The acknowledgement of a QoS 1 message received from the server
+    Puback(Option<u16>),
+    /// The server acknowledged a QoS 1 message published by the drone
+    Acknowledged(u16),
+    /// The connection to the server was lost and replaced by a new one, which has to be
+    /// subscribed again to the filters
+    Reconnected(W, Vec<TopicFilter>),
+    /// The drone is shutting down, so it disconnects from the server
+    Disconnect,
+    /// The connection is closed without disconnecting, as if it was lost
+    DropConnection,
+}
+
+/// Represents a stream the writer can close, so the connection is lost on purpose
+pub trait Closeable {
+    fn close(&self) -> io::Result<()>;
+}
+
+impl Closeable for TcpStream {
+    fn close(&self) -> io::Result<()> {
+        self.shutdown(Shutdown::Both)
+    }
+}
+
+/// Sends packets to the thread that writes them to the server. Every thread of the drone
+/// has its own clone
+#[derive(Debug)]
+pub struct Connection<W = TcpStream> {
+    sender: Sender<Outgoing<W>>,
+}
+
+impl<W> Clone for Connection<W> {
+    fn clone(&self) -> Self {
+        Connection {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<W> Connection<W> {
+    /// Creates a connection that sends its packets to the writer receiving from the channel
+    pub fn new(sender: Sender<Outgoing<W>>) -> Self {
+        Connection { sender }
+    }
+
+    /// Publishes the specified message with the specified QoS
+    pub fn publish(&self, topic_name: TopicName, message: Vec<u8>, qos: QoS) -> io::Result<()> {
+        self.send(Outgoing::Publish(topic_name, message, qos))
+    }
+
+    /// Subscribes to the specified topic filter
+    pub fn subscribe(&self, filter: TopicFilter) -> io::Result<()> {
+        self.send(Outgoing::Subscribe(filter))
+    }
+
+    /// Unsubscribes from the specified topic filter
+    pub fn unsubscribe(&self, filter: TopicFilter) -> io::Result<()> {
+        self.send(Outgoing::Unsubscribe(filter))
+    }
+
+    /// Acknowledges a QoS 1 message received from the server
+    pub fn puback(&self, packet_identifier: Option<u16>) -> io::Result<()> {
+        self.send(Outgoing::Puback(packet_identifier))
+    }
+
+    /// Tells the writer the server acknowledged one of the messages of the drone
+    pub fn acknowledged(&self, packet_identifier: u16) -> io::Result<()> {
+        self.send(Outgoing::Acknowledged(packet_identifier))
+    }
+
+    /// Replaces the stream the writer uses after reconnecting to the server
+    pub fn reconnected(&self, stream: W, filters: Vec<TopicFilter>) -> io::Result<()> {
+        self.send(Outgoing::Reconnected(stream, filters))
+    }
+
+    /// Disconnects from the server once the packets sent before are written
+    pub fn disconnect(&self) -> io::Result<()> {
+        self.send(Outgoing::Disconnect)
+    }
+
+    /// Closes the connection without telling the server, which then publishes the last will.
+    /// The reader reconnects as it does when the connection is lost
+    pub fn drop_connection(&self) -> io::Result<()> {
+        self.send(Outgoing::DropConnection)
+    }
+
+    fn send(&self, message: Outgoing<W>) -> io::Result<()> {
+        self.sender
+            .send(message)
+            .map_err(|_| io::Error::new(ErrorKind::BrokenPipe, "The writer is not running"))
+    }
+}
+
+/// Writes the packets it receives to the server until the drone disconnects, and returns the
+/// stream it ended up using. While there is nothing to write, the QoS 1 messages the server
+/// didn't acknowledge in time are sent again. The heartbeat tells the watchdog the writer is
+/// still making progress
+pub fn write_packets<W: Write + Closeable>(
+    mut stream: W,
+    receiver: Receiver<Outgoing<W>>,
+    mut inflight: InflightPublishes,
+    key: &[u8; 32],
+    heartbeat: Heartbeat,
+) -> W {
+    loop {
+        heartbeat.beat();
+        let result = match receiver.recv_timeout(Duration::from_millis(RESEND_CHECK_INTERVAL)) {
+            Ok(Outgoing::Publish(topic_name, message, qos)) => {
+                let publish_packet = Publish::new(false, qos, true, topic_name, None, message);
+                let publish_packet = match publish_packet.qos() {
+                    QoS::AtMost => publish_packet,
+                    _ => inflight.track(publish_packet, Instant::now()),
+                };
+                stream.write_all(publish_packet.to_bytes(key).as_slice())
+            }
+            Ok(Outgoing::Subscribe(filter)) => {
+                let subscribe_packet = Subscribe::new(1, vec![(filter, QoS::AtMost)]);
+                stream.write_all(subscribe_packet.to_bytes(key).as_slice())
+            }
+            Ok(Outgoing::Unsubscribe(filter)) => {
+                let unsubscribe_packet = Unsubscribe::new(1, vec![filter]);
+                stream.write_all(unsubscribe_packet.to_bytes(key).as_slice())
+            }
+            Ok(Outgoing::Puback(packet_identifier)) => {
+                stream.write_all(Puback::new(packet_identifier).to_bytes(key).as_slice())
+            }
+            Ok(Outgoing::Acknowledged(packet_identifier)) => {
+                inflight.acknowledge(packet_identifier);
+                Ok(())
+            }
+            Ok(Outgoing::Reconnected(new_stream, filters)) => {
+                stream = new_stream;
+                let subscribe_packets = filters
+                    .into_iter()
+                    .map(|filter| Subscribe::new(1, vec![(filter, QoS::AtMost)]).to_bytes(key));
+                // The server may never have received the messages in flight
+                let publish_packets = inflight
+                    .take_all(Instant::now())
+                    .into_iter()
+                    .map(|publish| publish.to_bytes(key));
+                subscribe_packets
+                    .chain(publish_packets)
+                    .try_for_each(|packet| stream.write_all(packet.as_slice()))
+            }
+            Ok(Outgoing::Disconnect) => {
+                if let Err(e) = stream.write_all(Disconnect::new().to_bytes(key).as_slice()) {
+                    eprintln!("Error: {:?}", e);
+                }
+                return stream;
+            }
+            Ok(Outgoing::DropConnection) => stream.close(),
+            Err(RecvTimeoutError::Timeout) => inflight
+                .take_expired(Instant::now())
+                .into_iter()
+                .try_for_each(|publish| {
+                    println!(
+                        "Resending unacknowledged message {:?}",
+                        publish.package_identifier()
+                    );
+                    stream.write_all(publish.to_bytes(key).as_slice())
+                }),
+            Err(RecvTimeoutError::Disconnected) => {
+                return stream;
+            }
+        };
+
+        // A failed write means the connection was lost. The reader reconnects and sends the
+        // new stream, and the messages in flight are sent again then
+        if let Err(e) = result {
+            eprintln!("Error: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watchdog::Watchdog;
+    use mqtt::model::{components::topic_level::TopicLevel, packet::Packet};
+    use std::{sync::mpsc, thread};
+
+    const KEY: &[u8; 32] = &[0; 32];
+
+    impl Closeable for Vec<u8> {
+        fn close(&self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn topic_name() -> TopicName {
+        TopicName::new(vec![b"ready-incident".to_vec(), b"1234".to_vec()], false)
+    }
+
+    fn filter() -> TopicFilter {
+        TopicFilter::new(vec![TopicLevel::Literal(b"new-incident".to_vec())], false)
+    }
+
+    /// Returns the packets the writer sent before disconnecting
+    fn packets(bytes: &[u8]) -> Vec<Packet> {
+        let disconnect = Disconnect::new().to_bytes(KEY);
+        assert!(bytes.ends_with(&disconnect));
+
+        let mut bytes = &bytes[..bytes.len() - disconnect.len()];
+        let mut packets = Vec::new();
+        while !bytes.is_empty() {
+            packets.push(Packet::from_bytes(&mut bytes, KEY).unwrap());
+        }
+        packets
+    }
+
+    fn run_writer(messages: Vec<Outgoing<Vec<u8>>>) -> Vec<u8> {
+        let (sender, receiver) = mpsc::channel();
+        let connection = Connection::new(sender);
+        let heartbeat = Watchdog::new().register("writer", None);
+        let writer = thread::spawn(move || {
+            write_packets(
+                Vec::new(),
+                receiver,
+                InflightPublishes::new(Duration::from_secs(60)),
+                KEY,
+                heartbeat,
+            )
+        });
+
+        for message in messages {
+            connection.send(message).unwrap();
+        }
+        connection.disconnect().unwrap();
+        writer.join().unwrap()
+    }
+
+    #[test]
+    fn test_writer_sends_the_packets_in_order() {
+        let bytes = run_writer(vec![
+            Outgoing::Subscribe(filter()),
+            Outgoing::Publish(topic_name(), b"".to_vec(), QoS::AtLeast),
+            Outgoing::Publish(topic_name(), b"".to_vec(), QoS::AtMost),
+        ]);
+
+        let packets = packets(&bytes);
+        assert_eq!(packets.len(), 3);
+        assert!(matches!(packets[0], Packet::Subscribe(_)));
+        match (&packets[1], &packets[2]) {
+            (Packet::Publish(acknowledged), Packet::Publish(unacknowledged)) => {
+                assert_eq!(acknowledged.package_identifier(), Some(1));
+                assert_eq!(unacknowledged.package_identifier(), None);
+            }
+            _ => panic!("Expected two publish packets"),
+        }
+    }
+
+    #[test]
+    fn test_unacknowledged_messages_are_resent_on_the_new_stream() {
+        let bytes = run_writer(vec![
+            Outgoing::Publish(topic_name(), b"first".to_vec(), QoS::AtLeast),
+            Outgoing::Publish(topic_name(), b"second".to_vec(), QoS::AtLeast),
+            Outgoing::Acknowledged(1),
+            Outgoing::Reconnected(Vec::new(), vec![filter()]),
+        ]);
+
+        let packets = packets(&bytes);
+        assert_eq!(packets.len(), 2);
+        assert!(matches!(packets[0], Packet::Subscribe(_)));
+        match &packets[1] {
+            Packet::Publish(publish) => {
+                assert!(publish.dup());
+                assert_eq!(publish.package_identifier(), Some(2));
+                assert_eq!(publish.message(), &b"second".to_vec());
+            }
+            _ => panic!("Expected a publish packet"),
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use std::{
+    io::{BufRead, BufReader, ErrorKind, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::drone::Drone;
+
+const STATUS: &str = "status";
+const DUMP_QUEUE: &str = "dump-queue";
+const SET_VELOCITY: &str = "set-velocity";
+const POLL_INTERVAL: u64 = 100;
+
+/// Represents a command sent to the control socket of a running drone, to debug it without
+/// going through the server
+#[derive(Debug, PartialEq)]
+pub enum ControlCommand {
+    /// Shows the status, position, battery and ETA of the drone
+    Status,
+    /// Shows the incidents the drone has queued, from the one it handles
+    DumpQueue,
+    /// Changes the maximum speed of the drone
+    SetVelocity(f64),
+}
+
+impl ControlCommand {
+    /// Parses a command in the format `status`, `dump-queue` or `set-velocity <velocity>`.
+    /// The error is the answer for the operator
+    pub fn from_string(string: &str) -> Result<Self, String> {
+        let mut parts = string.split_whitespace();
+        let command = match parts.next() {
+            Some(STATUS) => ControlCommand::Status,
+            Some(DUMP_QUEUE) => ControlCommand::DumpQueue,
+            Some(SET_VELOCITY) => match parts.next().map(str::parse::<f64>) {
+                Some(Ok(velocity)) if velocity > 0.0 => ControlCommand::SetVelocity(velocity),
+                _ => return Err(format!("Usage: {} <velocity>", SET_VELOCITY)),
+            },
+            _ => {
+                return Err(format!(
+                    "Unknown command. Commands: {}, {}, {} <velocity>",
+                    STATUS, DUMP_QUEUE, SET_VELOCITY
+                ))
+            }
+        };
+
+        match parts.next() {
+            Some(_) => Err(format!("Invalid command: {}", string.trim())),
+            None => Ok(command),
+        }
+    }
+}
+
+/// Runs the command on the drone, returning the answer for the operator in a single line
+pub fn execute(drone: &mut Drone, command: ControlCommand) -> String {
+    match command {
+        ControlCommand::Status => {
+            let telemetry = drone.telemetry();
+            let mut status = format!(
+                "{} at ({}, {}), battery {}",
+                telemetry.status.to_str(),
+                telemetry.position.x,
+                telemetry.position.y,
+                telemetry.battery
+            );
+            if let Some(eta) = telemetry.eta {
+                status.push_str(&format!(", ETA {}s", eta));
+            }
+            status
+        }
+        ControlCommand::DumpQueue => {
+            let incidents: Vec<String> = drone
+                .queued_incidents()
+                .iter()
+                .map(|incident| {
+                    format!(
+                        "{} ({}, {})",
+                        incident.uuid, incident.x_coordinate, incident.y_coordinate
+                    )
+                })
+                .collect();
+            if incidents.is_empty() {
+                "No queued incidents".to_string()
+            } else {
+                incidents.join(", ")
+            }
+        }
+        ControlCommand::SetVelocity(velocity) => {
+            drone.set_velocity(velocity);
+            format!("Velocity set to {}", velocity)
+        }
+    }
+}
+
+/// Answers the commands sent to the control socket until the shutdown flag is set. The
+/// socket only listens on localhost, and the operators are served one at a time
+pub fn serve_control(listener: TcpListener, drone: Arc<Mutex<Drone>>, shutdown: &AtomicBool) {
+    if let Err(e) = listener.set_nonblocking(true) {
+        eprintln!("Error starting the control socket: {:?}", e);
+        return;
+    }
+
+    while !shutdown.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                if let Err(e) = serve_operator(stream, &drone, shutdown) {
+                    eprintln!("Error serving the control socket: {:?}", e);
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(POLL_INTERVAL));
+            }
+            Err(e) => {
+                eprintln!("Error accepting a control connection: {:?}", e);
+                return;
+            }
+        }
+    }
+}
+
+/// Answers the commands of an operator, one per line, until they close the connection
+fn serve_operator(
+    stream: TcpStream,
+    drone: &Mutex<Drone>,
+    shutdown: &AtomicBool,
+) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    // The timeout lets the drone notice the shutdown flag while the operator is idle
+    stream.set_read_timeout(Some(Duration::from_millis(POLL_INTERVAL)))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+
+    while !shutdown.load(Ordering::Relaxed) {
+        match reader.read_line(&mut line) {
+            Ok(0) => return Ok(()),
+            Ok(_) => {}
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+            Err(e) => return Err(e),
+        }
+        if line.trim().is_empty() {
+            line.clear();
+            continue;
+        }
+
+        let answer = match ControlCommand::from_string(&line) {
+            Ok(command) => match drone.lock() {
+                Ok(mut drone) => execute(&mut drone, command),
+                Err(_) => "Mutex was poisoned".to_string(),
+            },
+            Err(answer) => answer,
+        };
+        line.clear();
+        writeln!(writer, "{}", answer)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::incident::{Incident, IncidentStatus};
+
+    #[test]
+    fn test_control_commands_are_parsed() {
+        assert_eq!(
+            ControlCommand::from_string("status\n"),
+            Ok(ControlCommand::Status)
+        );
+        assert_eq!(
+            ControlCommand::from_string("dump-queue"),
+            Ok(ControlCommand::DumpQueue)
+        );
+        assert_eq!(
+            ControlCommand::from_string("set-velocity 2.5"),
+            Ok(ControlCommand::SetVelocity(2.5))
+        );
+
+        assert!(ControlCommand::from_string("set-velocity").is_err());
+        assert!(ControlCommand::from_string("set-velocity 0").is_err());
+        assert!(ControlCommand::from_string("status now").is_err());
+        assert!(ControlCommand::from_string("land").is_err());
+    }
+
+    #[test]
+    fn test_control_commands_inspect_and_change_the_drone() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0);
+        assert_eq!(
+            execute(&mut drone, ControlCommand::DumpQueue),
+            "No queued incidents"
+        );
+        drone.add_incident(Incident::new(
+            "fire".to_string(),
+            "Fire".to_string(),
+            String::new(),
+            3.0,
+            4.0,
+            IncidentStatus::Pending,
+        ));
+        assert_eq!(
+            execute(&mut drone, ControlCommand::DumpQueue),
+            "fire (3, 4)"
+        );
+
+        drone.start_trip(3.0, 0.0, common::drone_status::TravelLocation::Waypoint);
+        assert_eq!(
+            execute(&mut drone, ControlCommand::SetVelocity(3.0)),
+            "Velocity set to 3"
+        );
+        assert_eq!(
+            execute(&mut drone, ControlCommand::Status),
+            "Travelling to Waypoint at (0, 0), battery 100, ETA 1s"
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::fmt;
+
+const SEPARATOR: char = ';';
+const COMMITTED: &str = "committed";
+const ATTENDING: &str = "attending";
+const WITHDRAWN: &str = "withdrawn";
+
+/// Represents how far a drone got with the incident it claimed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClaimState {
+    /// The drone wants to attend the incident
+    Claimed,
+    /// The drone is one of the responders and is on its way
+    Committed,
+    /// The drone arrived at the incident, so no other drone can take its place
+    Attending,
+    /// The drone gave the incident up before arriving, to recharge, so another drone has to
+    /// take its place
+    Withdrawn,
+}
+
+/// Represents the claim a drone makes over an incident, so the drones that received it
+/// agree on which ones attend it
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncidentClaim {
+    pub drone_id: u8,
+    pub distance: f64,
+    pub state: ClaimState,
+}
+
+impl IncidentClaim {
+    /// Parses a claim in the format `drone_id;distance[;committed|attending|withdrawn]`
+    pub fn from_string(message: &str) -> Option<Self> {
+        let mut parts = message.split(SEPARATOR);
+        let drone_id = parts.next()?.parse::<u8>().ok()?;
+        let distance = parts.next()?.parse::<f64>().ok()?;
+        let state = match parts.next() {
+            Some(COMMITTED) => ClaimState::Committed,
+            Some(ATTENDING) => ClaimState::Attending,
+            Some(WITHDRAWN) => ClaimState::Withdrawn,
+            Some(_) => return None,
+            None => ClaimState::Claimed,
+        };
+        Some(IncidentClaim {
+            drone_id,
+            distance,
+            state,
+        })
+    }
+}
+
+impl fmt::Display for IncidentClaim {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.drone_id, SEPARATOR, self.distance)?;
+        match self.state {
+            ClaimState::Claimed => Ok(()),
+            ClaimState::Committed => write!(f, "{}{}", SEPARATOR, COMMITTED),
+            ClaimState::Attending => write!(f, "{}{}", SEPARATOR, ATTENDING),
+            ClaimState::Withdrawn => write!(f, "{}{}", SEPARATOR, WITHDRAWN),
+        }
+    }
+}
+
+/// Represents what a drone has to do after it learns of the claim of another drone
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClaimOutcome {
+    /// Nothing changes for the drone
+    Ignored,
+    /// The drone has to announce its own claim again, because the other drone doesn't know it
+    Announce(IncidentClaim),
+    /// A closer drone took the place of the drone, which stops going to the incident
+    Aborted,
+    /// The incident is attended by other drones, so the drone stops following it
+    Forgotten,
+}
+
+/// Returns the ids of the drones that attend the incident: the ones already attending it,
+/// and then the closest ones until there are enough. Ties go to the drone that committed
+/// and then to the one with the lowest id, so every drone that saw the same claims picks
+/// the same responders
+pub fn responders(claims: &HashMap<u8, IncidentClaim>, count: usize) -> Vec<u8> {
+    let mut claims: Vec<&IncidentClaim> = claims.values().collect();
+    claims.sort_by(|a, b| {
+        (a.state != ClaimState::Attending)
+            .cmp(&(b.state != ClaimState::Attending))
+            .then(a.distance.total_cmp(&b.distance))
+            .then((a.state != ClaimState::Committed).cmp(&(b.state != ClaimState::Committed)))
+            .then(a.drone_id.cmp(&b.drone_id))
+    });
+    claims
+        .into_iter()
+        .take(count)
+        .map(|claim| claim.drone_id)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(claims: &[(u8, f64, ClaimState)]) -> HashMap<u8, IncidentClaim> {
+        claims
+            .iter()
+            .map(|&(drone_id, distance, state)| {
+                (
+                    drone_id,
+                    IncidentClaim {
+                        drone_id,
+                        distance,
+                        state,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_claim_format() {
+        let claim = IncidentClaim {
+            drone_id: 3,
+            distance: 0.0125,
+            state: ClaimState::Committed,
+        };
+        assert_eq!(claim.to_string(), "3;0.0125;committed");
+        assert_eq!(IncidentClaim::from_string(&claim.to_string()), Some(claim));
+        assert_eq!(
+            IncidentClaim::from_string("4;0.5"),
+            Some(IncidentClaim {
+                drone_id: 4,
+                distance: 0.5,
+                state: ClaimState::Claimed
+            })
+        );
+        assert_eq!(
+            IncidentClaim::from_string("4;0;attending").map(|claim| claim.state),
+            Some(ClaimState::Attending)
+        );
+        assert_eq!(
+            IncidentClaim::from_string("4;0;withdrawn").map(|claim| claim.state),
+            Some(ClaimState::Withdrawn)
+        );
+        assert_eq!(IncidentClaim::from_string("4"), None);
+        assert_eq!(IncidentClaim::from_string("4;0.5;maybe"), None);
+    }
+
+    #[test]
+    fn test_closest_drones_respond_with_ties_broken_by_id() {
+        let claims = claims(&[
+            (3, 1.0, ClaimState::Claimed),
+            (1, 1.0, ClaimState::Claimed),
+            (2, 1.0, ClaimState::Claimed),
+            (4, 0.5, ClaimState::Claimed),
+        ]);
+        assert_eq!(responders(&claims, 2), vec![4, 1]);
+    }
+
+    #[test]
+    fn test_closer_drones_take_the_place_of_committed_ones() {
+        let claims = claims(&[
+            (1, 5.0, ClaimState::Committed),
+            (2, 0.5, ClaimState::Claimed),
+            (3, 0.5, ClaimState::Committed),
+        ]);
+        assert_eq!(responders(&claims, 2), vec![3, 2]);
+    }
+
+    #[test]
+    fn test_attending_drones_keep_responding() {
+        let claims = claims(&[
+            (1, 5.0, ClaimState::Attending),
+            (2, 0.5, ClaimState::Claimed),
+            (3, 0.1, ClaimState::Claimed),
+        ]);
+        assert_eq!(responders(&claims, 2), vec![1, 3]);
+    }
+}
@@ -1,18 +1,26 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
 
+use common::drone_anchor::DroneAnchor;
+use common::drone_command::{DroneCommand, DroneCommandAck};
+use common::drone_log::{DroneLog, LogLevel};
 use common::drone_status::{DroneStatus, TravelLocation};
 
-use common::incident::Incident;
+use common::incident::{Incident, IncidentStatus, DEFAULT_REQUIRED_DRONES};
 
-use crate::utils::Position;
-
-const MINIMUM_BATTERY_LEVEL: usize = 20;
-const MAXIMUM_BATTERY_LEVEL: usize = 100;
-
-const BATTERY_DISCHARGE_TRAVELLING: usize = 2;
-const BATTERY_DISCHARGE_ATTENDING: usize = 2;
-const BATTERY_DISCHARGE_IDLE: usize = 1;
-const BATTERY_RECHARGE: usize = 5;
+use crate::anchor::{AnchorNegotiation, OperationsArea};
+use crate::battery::BatteryModel;
+use crate::coordination::{self, ClaimOutcome, ClaimState, IncidentClaim};
+use crate::event::{
+    DroneAction, DroneEvent, IncidentTopic, ANCHOR_WINDOW, ATTENDING_DURATION, CLAIM_WINDOW,
+    TRAVEL_INTERVAL,
+};
+use crate::geofence::NoFlyZone;
+use crate::history::{TelemetryHistory, TelemetrySample};
+use crate::motion::Motion;
+use crate::separation::Separation;
+use crate::state::DroneState;
+use crate::utils::{Path, Position};
 
 /// Represents a drone
 #[derive(Debug, Clone)]
@@ -25,8 +33,22 @@ pub struct Drone {
     anchor: Position,
     current_incident_count: usize,
     incident_queue: VecDeque<Incident>,
-    velocity: f64,
+    motion: Motion,
     active_range: f64,
+    battery_model: BatteryModel,
+    charging_stations: Vec<Position>,
+    no_fly_zones: Vec<NoFlyZone>,
+    route: Option<Path>,
+    route_changed: bool,
+    claims: HashMap<String, HashMap<u8, IncidentClaim>>,
+    covered_incidents: Vec<Incident>,
+    drones_per_incident: usize,
+    history: TelemetryHistory,
+    distance_travelled: f64,
+    trip: Option<(Position, TravelLocation)>,
+    anchor_negotiation: Option<AnchorNegotiation>,
+    separation: Option<Separation>,
+    logs: Vec<DroneLog>,
 }
 
 impl Drone {
@@ -40,26 +62,150 @@ impl Drone {
         velocity: f64,
         active_range: f64,
     ) -> Self {
+        let battery_model = BatteryModel::default();
         Drone {
             id,
             position: Position::new(x_central, y_central),
             status: DroneStatus::Travelling(TravelLocation::Anchor),
-            battery: MAXIMUM_BATTERY_LEVEL,
+            battery: battery_model.maximum_level,
             central: Position::new(x_central, y_central),
             anchor: Position::new(x_anchor, y_anchor),
             current_incident_count: 0,
             incident_queue: VecDeque::new(),
-            velocity,
+            motion: Motion::new(velocity, None),
             active_range,
+            battery_model,
+            charging_stations: vec![Position::new(x_central, y_central)],
+            no_fly_zones: Vec::new(),
+            route: None,
+            route_changed: false,
+            claims: HashMap::new(),
+            covered_incidents: Vec::new(),
+            drones_per_incident: DEFAULT_REQUIRED_DRONES,
+            history: TelemetryHistory::default(),
+            distance_travelled: 0.0,
+            trip: None,
+            anchor_negotiation: None,
+            separation: None,
+            logs: Vec::new(),
+        }
+    }
+
+    /// Replaces the battery model of the drone, starting with a fully charged battery
+    pub fn with_battery_model(mut self, battery_model: BatteryModel) -> Self {
+        self.battery = battery_model.maximum_level;
+        self.battery_model = battery_model;
+        self
+    }
+
+    /// Resumes the drone from the state it saved before its process restarted
+    pub fn with_state(mut self, state: DroneState) -> Self {
+        self.position = state.position;
+        self.battery = state.battery.min(self.battery_model.maximum_level);
+        self.status = state.status;
+        self.incident_queue = state.incident_queue.into();
+        self
+    }
+
+    /// Returns the state the drone needs to resume after its process restarts
+    pub fn state(&self) -> DroneState {
+        DroneState {
+            position: self.position,
+            battery: self.battery,
+            status: self.status.clone(),
+            incident_queue: self.incident_queue.iter().cloned().collect(),
         }
     }
 
+    /// Makes the drone choose its anchor inside the operations area together with the other
+    /// drones, instead of using the one it was created with
+    pub fn with_operations_area(mut self, area: Option<OperationsArea>) -> Self {
+        self.anchor_negotiation = area.map(AnchorNegotiation::new);
+        self
+    }
+
+    /// Returns true if the drone chooses its anchor together with the other drones
+    pub fn negotiates_anchor(&self) -> bool {
+        self.anchor_negotiation.is_some()
+    }
+
+    /// Makes the drone speed up and slow down by the specified distance per step every step,
+    /// instead of always moving at its velocity
+    pub fn with_acceleration(mut self, acceleration: Option<f64>) -> Self {
+        self.motion = Motion::new(self.motion.max_speed(), acceleration);
+        self
+    }
+
+    /// Makes the drone keep the specified distance from the other drones while it travels
+    pub fn with_separation_distance(mut self, distance: Option<f64>) -> Self {
+        self.separation = distance.map(Separation::new);
+        self
+    }
+
+    /// Returns true if the drone keeps its distance from the other drones
+    pub fn keeps_separation(&self) -> bool {
+        self.separation.is_some()
+    }
+
+    /// Returns the anchor of the drone and the range it covers from there
+    pub fn anchor(&self) -> DroneAnchor {
+        DroneAnchor {
+            x_coordinate: self.anchor.x,
+            y_coordinate: self.anchor.y,
+            active_range: self.active_range,
+        }
+    }
+
+    /// Replaces the history of the drone with an empty one that keeps the specified number
+    /// of samples
+    pub fn with_history_length(mut self, history_length: usize) -> Self {
+        self.history = TelemetryHistory::new(history_length);
+        self
+    }
+
     /// Returns the data of the drone in string format
     pub fn data(&self) -> String {
-        format!(
-            "{};{};{};{}",
-            self.position.x, self.position.y, self.status, self.battery
-        )
+        self.telemetry().to_string()
+    }
+
+    /// Returns the current telemetry of the drone
+    pub fn telemetry(&self) -> TelemetrySample {
+        TelemetrySample {
+            position: self.position,
+            battery: self.battery,
+            status: self.status.clone(),
+            eta: self.eta().map(|eta| eta.as_secs()),
+        }
+    }
+
+    /// Returns the time the drone needs to reach the destination of its trip, moving a step
+    /// every travel interval along its route from its current speed
+    pub fn eta(&self) -> Option<Duration> {
+        let (_, location) = self.trip?;
+        if self.status != DroneStatus::Travelling(location) {
+            return None;
+        }
+        let distance = self.route.as_ref()?.remaining_distance(&self.position);
+        let steps = self.motion.steps_to(distance)?;
+        Some(TRAVEL_INTERVAL * steps)
+    }
+
+    /// Adds the current telemetry of the drone to its history
+    pub fn record_telemetry(&mut self) {
+        let sample = self.telemetry();
+        self.history.record(sample);
+    }
+
+    /// Returns the recent telemetry of the drone, from the oldest sample, in string format
+    pub fn history(&self) -> String {
+        self.history.to_string()
+    }
+
+    /// Returns the data the server publishes for the drone if it loses its connection
+    pub fn disconnected_data(&self) -> String {
+        let mut drone = self.clone();
+        drone.set_status(DroneStatus::Disconnected);
+        drone.data()
     }
 
     /// Returns the id of the drone
@@ -67,9 +213,53 @@ impl Drone {
         self.id
     }
 
+    /// Adds charging stations the drone can recharge at besides its central
+    pub fn with_charging_stations(mut self, charging_stations: Vec<Position>) -> Self {
+        self.charging_stations.extend(charging_stations);
+        self
+    }
+
+    /// Sets the areas the drone can't fly over
+    pub fn with_no_fly_zones(mut self, no_fly_zones: Vec<NoFlyZone>) -> Self {
+        self.no_fly_zones = no_fly_zones;
+        self
+    }
+
+    /// Sets the number of drones that attend the incidents that don't say how many they need
+    pub fn with_drones_per_incident(mut self, drones_per_incident: usize) -> Self {
+        self.drones_per_incident = drones_per_incident;
+        self
+    }
+
+    /// Returns the number of drones that attend an incident
+    pub fn required_drones(&self, incident: &Incident) -> usize {
+        incident.required_drones_or(self.drones_per_incident)
+    }
+
+    /// Returns true if the point is inside a no-fly zone
+    pub fn is_in_no_fly_zone(&self, x: f64, y: f64) -> bool {
+        let position = Position::new(x, y);
+        self.no_fly_zones
+            .iter()
+            .any(|zone| zone.contains(&position))
+    }
+
+    /// Returns the charging station closest to the drone
+    pub fn nearest_charging_station(&self) -> Position {
+        self.charging_stations
+            .iter()
+            .copied()
+            .min_by(|a, b| {
+                self.position
+                    .distance_to(a)
+                    .total_cmp(&self.position.distance_to(b))
+            })
+            .unwrap_or(self.central)
+    }
+
     /// Returns true if the battery is below the minimum level
     pub fn is_below_minimun(&self) -> bool {
-        self.battery < MINIMUM_BATTERY_LEVEL
+        self.battery < self.battery_model.minimum_level
     }
 
     /// Sets the status of the drone
@@ -82,48 +272,114 @@ impl Drone {
         self.position.distance_to(&Position::new(x, y))
     }
 
-    /// Returns the x central coordinate of the drone
-    pub fn x_central_coordinate(&self) -> f64 {
-        self.central.x
+    /// Starts travelling to a point, which ends the travel in progress if there is one.
+    /// The drone moves a step every tick while its status is the one of the travel
+    pub fn start_trip(&mut self, x: f64, y: f64, location: TravelLocation) {
+        self.status = DroneStatus::Travelling(location);
+        self.trip = Some((Position::new(x, y), location));
+        self.plan_route(x, y);
+        self.log(
+            LogLevel::Info,
+            "travel-started",
+            format!(
+                "{} from ({}, {}) to ({}, {})",
+                self.status.to_str(),
+                self.position.x,
+                self.position.y,
+                x,
+                y
+            ),
+        );
+    }
+
+    /// Changes the maximum speed of the drone, which applies from its next step
+    pub fn set_velocity(&mut self, velocity: f64) {
+        self.motion.set_max_speed(velocity);
+    }
+
+    /// Starts travelling back to the anchor, where the drone waits for incidents
+    pub fn return_to_anchor(&mut self) {
+        self.start_trip(self.anchor.x, self.anchor.y, TravelLocation::Anchor);
     }
 
-    /// Returns the y central coordinate of the drone
-    pub fn y_central_coordinate(&self) -> f64 {
-        self.central.y
+    /// Plans the route from the current position to a point
+    pub fn plan_route(&mut self, x: f64, y: f64) {
+        let target = Position::new(x, y);
+        self.route = Some(Path::plan(self.position, target, &self.no_fly_zones));
+        self.route_changed = true;
+    }
+
+    /// Records a significant event, so the operators can follow the drone from the monitor
+    fn log(&mut self, level: LogLevel, event: &str, detail: String) {
+        self.logs.push(DroneLog::new(level, event, detail));
     }
 
-    /// Returns the x anchor coordinate of the drone
-    pub fn x_anchor_coordinate(&self) -> f64 {
-        self.anchor.x
+    /// Returns the events logged since the last call, to be published
+    pub fn take_logs(&mut self) -> Vec<DroneLog> {
+        std::mem::take(&mut self.logs)
     }
 
-    /// Returns the y anchor coordinate of the drone
-    pub fn y_anchor_coordinate(&self) -> f64 {
-        self.anchor.y
+    /// Returns the route planned since the last call, formatted to be published
+    pub fn take_new_route(&mut self) -> Option<String> {
+        if !self.route_changed {
+            return None;
+        }
+        self.route_changed = false;
+        Some(self.route.as_ref().map(Path::to_string).unwrap_or_default())
     }
 
-    /// Moves the drone to a point
+    /// Moves the drone one step along its route to a point, planning it if the drone
+    /// wasn't heading there
     pub fn travel_to(&mut self, x: f64, y: f64) {
-        let target = Position::new(x, y);
-        let distance_to_target = self.position.distance_to(&target);
+        let heading_to_target = self
+            .route
+            .as_ref()
+            .and_then(Path::destination)
+            .is_some_and(|destination| destination.x == x && destination.y == y);
+        if !heading_to_target {
+            self.plan_route(x, y);
+        }
 
-        if distance_to_target <= self.velocity {
-            self.position = target;
-        } else {
-            self.position.move_towards(&target, self.velocity);
+        if let Some(route) = &mut self.route {
+            let from = self.position;
+            let step = self.motion.step(route.remaining_distance(&self.position));
+            route.advance(&mut self.position, step);
+            // The drones don't keep their distance on the final approach, or the ones going
+            // to the same place would never arrive
+            if let Some(separation) = &self.separation {
+                if route.remaining_distance(&self.position) > separation.distance() {
+                    let heading = from.angle_to(&self.position);
+                    let avoided = separation.avoid(self.id, self.position, heading);
+                    if !self.no_fly_zones.iter().any(|zone| zone.contains(&avoided)) {
+                        self.position = avoided;
+                    }
+                }
+            }
+            self.distance_travelled += from.distance_to(&self.position);
+            if route.is_finished() {
+                self.route = None;
+                self.motion.stop();
+            }
         }
     }
 
-    /// Discharges the battery of the drone
+    /// Discharges the battery of the drone: the baseline of its status plus a unit for each
+    /// `discharge_distance` it travelled since the last discharge. What is left of the distance
+    /// is discharged with the next one
     pub fn discharge_battery(&mut self) {
-        let battery_to_discharge = match self.status {
-            DroneStatus::Travelling(_) => BATTERY_DISCHARGE_TRAVELLING,
-            DroneStatus::Free | DroneStatus::Interrupted => BATTERY_DISCHARGE_IDLE,
-            DroneStatus::AttendingIncident => BATTERY_DISCHARGE_ATTENDING,
-            DroneStatus::Recharging => {
-                return;
-            }
+        let discharge_distance = self.battery_model.discharge_distance;
+        let travelled_units = (self.distance_travelled / discharge_distance).floor();
+        self.distance_travelled -= travelled_units * discharge_distance;
+
+        let baseline = match self.status {
+            DroneStatus::Travelling(_)
+            | DroneStatus::Free
+            | DroneStatus::Interrupted
+            | DroneStatus::Held => self.battery_model.discharge_idle,
+            DroneStatus::AttendingIncident => self.battery_model.discharge_attending,
+            DroneStatus::Recharging | DroneStatus::Disconnected => 0,
         };
+        let battery_to_discharge = baseline + travelled_units as usize;
 
         if self.battery < battery_to_discharge {
             self.battery = 0;
@@ -135,17 +391,18 @@ impl Drone {
 
     /// Recharges the battery of the drone
     pub fn recharge_battery(&mut self) {
-        if self.battery < MAXIMUM_BATTERY_LEVEL {
-            self.battery += BATTERY_RECHARGE;
+        let maximum_level = self.battery_model.maximum_level;
+        if self.battery < maximum_level {
+            self.battery += self.battery_model.recharge;
         }
-        if self.battery > MAXIMUM_BATTERY_LEVEL {
-            self.battery = MAXIMUM_BATTERY_LEVEL;
+        if self.battery > maximum_level {
+            self.battery = maximum_level;
         }
     }
 
     /// Returns true if the battery is fully charged
     pub fn is_fully_charged(&self) -> bool {
-        self.battery == MAXIMUM_BATTERY_LEVEL
+        self.battery == self.battery_model.maximum_level
     }
 
     /// Returns true if the drone is within range of a point
@@ -168,30 +425,273 @@ impl Drone {
         self.current_incident_count
     }
 
-    /// Returns true if the drone is in the anchor
-    pub fn is_in_anchor(&self) -> bool {
-        self.position.x == self.anchor.x && self.position.y == self.anchor.y
-    }
-
-    /// Returns true if the drone has been interrupted
-    pub fn is_interrupted(&self) -> bool {
-        self.status == DroneStatus::Interrupted
+    /// Returns true if the drone can carry out the command of an operator. A drone handling
+    /// an incident or recharging finishes it first, and only a drone following the operator
+    /// can resume. Waypoints, anchors and incidents inside a no-fly zone are rejected, while the
+    /// rest of the anchors and incidents are accepted even if the drone is busy, as it only
+    /// goes there afterwards
+    pub fn accepts_command(&self, command: &DroneCommand) -> bool {
+        let commanded = matches!(
+            self.status,
+            DroneStatus::Held | DroneStatus::Travelling(TravelLocation::Waypoint)
+        );
+        match command {
+            DroneCommand::Resume => commanded,
+            DroneCommand::Goto(x, y) | DroneCommand::Anchor(x, y)
+                if self.is_in_no_fly_zone(*x, *y) =>
+            {
+                false
+            }
+            DroneCommand::Attend(incident)
+                if self.is_in_no_fly_zone(incident.x_coordinate, incident.y_coordinate) =>
+            {
+                false
+            }
+            DroneCommand::Anchor(_, _) | DroneCommand::Attend(_) => true,
+            _ => commanded || self.is_free(),
+        }
     }
 
-    /// Adds an incident to the drone queue of incidents
+    /// Adds an incident to the drone queue of incidents, ahead of the ones that are farther away.
+    /// Incidents already queued are ignored, as the server sends the retained one again when
+    /// the drone restarts
     pub fn add_incident(&mut self, incident: Incident) {
+        if self
+            .incident_queue
+            .iter()
+            .any(|queued| queued.uuid == incident.uuid)
+        {
+            return;
+        }
         self.incident_queue.push_back(incident);
+        self.prioritize_incidents();
     }
 
-    /// Returns the current incident of the drone
+    /// Returns the current incident of the drone, which is the closest one unless the drone
+    /// is already handling another
     pub fn current_incident(&self) -> Option<Incident> {
         self.incident_queue.front().cloned()
     }
 
     /// Removes the current incident of the drone
     pub fn remove_current_incident(&mut self) {
-        self.incident_queue.pop_front();
+        if let Some(incident) = self.incident_queue.pop_front() {
+            self.claims.remove(&incident.uuid);
+        }
         self.current_incident_count = 0;
+        self.prioritize_incidents();
+    }
+
+    /// Starts a claim over the current incident, forgetting the claims of the other drones,
+    /// as the ones still going there announce themselves again. Returns the claim to publish
+    pub fn claim_current_incident(&mut self) -> Option<IncidentClaim> {
+        let incident = self.current_incident()?;
+        let claim = self.claim_for(&incident, ClaimState::Claimed);
+        self.claims
+            .insert(incident.uuid, HashMap::from([(self.id, claim.clone())]));
+        Some(claim)
+    }
+
+    fn claim_for(&self, incident: &Incident, state: ClaimState) -> IncidentClaim {
+        IncidentClaim {
+            drone_id: self.id,
+            distance: self.distance_to(incident.x_coordinate, incident.y_coordinate),
+            state,
+        }
+    }
+
+    /// Records the claim of another drone over the current incident or one the drone covers.
+    /// A committed drone announces itself again to the drones that claim the incident later,
+    /// and aborts if a closer one committed in its place. The claims of the drones that
+    /// withdrew are dropped, so the others can take their place
+    pub fn add_claim(&mut self, uuid: &str, claim: IncidentClaim) -> ClaimOutcome {
+        let current_incident = self
+            .current_incident()
+            .filter(|incident| incident.uuid == uuid);
+        let covered_incident = self
+            .covered_incidents
+            .iter()
+            .find(|incident| incident.uuid == uuid);
+        let required_drones = match current_incident.as_ref().or(covered_incident) {
+            Some(incident) if claim.drone_id != self.id => self.required_drones(incident),
+            _ => return ClaimOutcome::Ignored,
+        };
+
+        if claim.state == ClaimState::Withdrawn {
+            if let Some(claims) = self.claims.get_mut(uuid) {
+                claims.remove(&claim.drone_id);
+            }
+            return ClaimOutcome::Ignored;
+        }
+
+        let claim_state = claim.state;
+        let claims = self.claims.entry(uuid.to_string()).or_default();
+        claims.insert(claim.drone_id, claim);
+
+        let current_incident = match current_incident {
+            Some(incident) => incident,
+            None => {
+                if attending_count(claims) < required_drones {
+                    return ClaimOutcome::Ignored;
+                }
+                self.claims.remove(uuid);
+                self.covered_incidents
+                    .retain(|incident| incident.uuid != uuid);
+                return ClaimOutcome::Forgotten;
+            }
+        };
+
+        let own_state = match claims.get(&self.id) {
+            Some(own_claim) if own_claim.state != ClaimState::Claimed => own_claim.state,
+            _ => return ClaimOutcome::Ignored,
+        };
+
+        if claim_state == ClaimState::Claimed {
+            let own_claim = self.claim_for(&current_incident, own_state);
+            self.claims
+                .entry(uuid.to_string())
+                .or_default()
+                .insert(self.id, own_claim.clone());
+            return ClaimOutcome::Announce(own_claim);
+        }
+
+        let committed: HashMap<u8, IncidentClaim> = claims
+            .iter()
+            .filter(|(_, claim)| claim.state != ClaimState::Claimed)
+            .map(|(drone_id, claim)| (*drone_id, claim.clone()))
+            .collect();
+        if own_state == ClaimState::Committed
+            && !coordination::responders(&committed, required_drones).contains(&self.id)
+        {
+            self.status = DroneStatus::Interrupted;
+            return ClaimOutcome::Aborted;
+        }
+        ClaimOutcome::Ignored
+    }
+
+    /// Returns true if the drone is one of the responders of the current incident
+    /// given the claims it knows of
+    pub fn is_responder(&self) -> bool {
+        self.current_incident().is_some_and(|incident| {
+            self.claims.get(&incident.uuid).is_some_and(|claims| {
+                coordination::responders(claims, self.required_drones(&incident)).contains(&self.id)
+            })
+        })
+    }
+
+    /// Commits the drone to the current incident. Returns the claim to publish
+    pub fn commit_to_current_incident(&mut self) -> Option<IncidentClaim> {
+        self.update_current_claim(ClaimState::Committed)
+    }
+
+    /// Marks the drone as attending the current incident. Returns the claim to publish
+    pub fn attend_current_incident(&mut self) -> Option<IncidentClaim> {
+        self.update_current_claim(ClaimState::Attending)
+    }
+
+    fn update_current_claim(&mut self, state: ClaimState) -> Option<IncidentClaim> {
+        let incident = self.current_incident()?;
+        let claim = self.claim_for(&incident, state);
+        self.claims
+            .get_mut(&incident.uuid)?
+            .insert(self.id, claim.clone());
+        Some(claim)
+    }
+
+    /// Gives the current incident up to the drones that won it, and keeps following their
+    /// claims in case it can take their place later. Returns false if the incident is already
+    /// attended, in which case the drone forgets it
+    pub fn cover_current_incident(&mut self) -> bool {
+        let incident = match self.incident_queue.pop_front() {
+            Some(incident) => incident,
+            None => return false,
+        };
+        self.current_incident_count = 0;
+
+        let required_drones = self.required_drones(&incident);
+        let attended = self
+            .claims
+            .get(&incident.uuid)
+            .is_some_and(|claims| attending_count(claims) >= required_drones);
+        if attended {
+            self.claims.remove(&incident.uuid);
+            return false;
+        }
+        self.covered_incidents.push(incident);
+        true
+    }
+
+    /// Returns the incidents the drone has queued, from the one it handles
+    pub fn queued_incidents(&self) -> &VecDeque<Incident> {
+        &self.incident_queue
+    }
+
+    /// Returns the incidents the drone gave up but still follows
+    pub fn covered_incidents(&self) -> &[Incident] {
+        &self.covered_incidents
+    }
+
+    /// Takes back the closest covered incident the drone would be one of the responders of,
+    /// given the last claims it knows of, and makes it the current one
+    pub fn take_over_incident(&mut self) -> Option<Incident> {
+        let mut candidates: Vec<&Incident> = self
+            .covered_incidents
+            .iter()
+            .filter(|incident| {
+                let mut claims: HashMap<u8, IncidentClaim> = self
+                    .claims
+                    .get(&incident.uuid)
+                    .into_iter()
+                    .flatten()
+                    .filter(|(_, claim)| claim.state != ClaimState::Claimed)
+                    .map(|(drone_id, claim)| (*drone_id, claim.clone()))
+                    .collect();
+                claims.insert(self.id, self.claim_for(incident, ClaimState::Claimed));
+                coordination::responders(&claims, self.required_drones(incident)).contains(&self.id)
+            })
+            .collect();
+        candidates.sort_by(|a, b| {
+            self.distance_to(a.x_coordinate, a.y_coordinate)
+                .total_cmp(&self.distance_to(b.x_coordinate, b.y_coordinate))
+        });
+
+        let uuid = candidates.first()?.uuid.clone();
+        let position = self
+            .covered_incidents
+            .iter()
+            .position(|incident| incident.uuid == uuid)?;
+        let incident = self.covered_incidents.remove(position);
+        self.incident_queue.push_front(incident.clone());
+        Some(incident)
+    }
+
+    /// Orders the queue of incidents by their distance to the drone, keeping the order in which
+    /// they arrived between incidents at the same distance. The incident the drone is handling
+    /// stays first
+    pub fn prioritize_incidents(&mut self) {
+        let skip = usize::from(self.is_handling_incident());
+        let position = self.position;
+        self.incident_queue
+            .make_contiguous()
+            .get_mut(skip..)
+            .unwrap_or_default()
+            .sort_by(|a, b| {
+                let distance_to = |incident: &Incident| {
+                    position
+                        .distance_to(&Position::new(incident.x_coordinate, incident.y_coordinate))
+                };
+                distance_to(a).total_cmp(&distance_to(b))
+            });
+    }
+
+    /// Returns true if the drone is travelling to, attending or coming back from its current incident
+    fn is_handling_incident(&self) -> bool {
+        matches!(
+            self.status,
+            DroneStatus::Travelling(TravelLocation::Incident)
+                | DroneStatus::AttendingIncident
+                | DroneStatus::Interrupted
+        )
     }
 
     /// Checks if the drone is free to attend an incident
@@ -199,11 +699,521 @@ impl Drone {
         self.status == DroneStatus::Free
             || self.status == DroneStatus::Travelling(TravelLocation::Anchor)
     }
+
+    /// Handles an event, moving the drone to its next state. Returns what the drone has to do
+    /// to tell the monitor and the other drones about it
+    pub fn handle_event(&mut self, event: DroneEvent) -> Vec<DroneAction> {
+        match event {
+            DroneEvent::Started => self.on_started(),
+            DroneEvent::IncidentReported(incident) => self.on_incident_reported(incident),
+            DroneEvent::IncidentAssigned(incident) => self.on_incident_assigned(incident),
+            DroneEvent::ClaimReceived(uuid, claim) => self.on_claim_received(uuid, claim),
+            DroneEvent::ClaimWindowClosed(uuid) => self.on_claim_window_closed(uuid),
+            DroneEvent::ArrivedAtIncident => self.on_arrived_at_incident(),
+            DroneEvent::PeerAttending(uuid) => self.on_peer_attending(uuid),
+            DroneEvent::IncidentAttended(uuid) => {
+                if self.current_incident_is(&uuid) && self.status == DroneStatus::AttendingIncident
+                {
+                    return vec![DroneAction::PublishReady(uuid)];
+                }
+                Vec::new()
+            }
+            DroneEvent::IncidentClosed(uuid) => self.on_incident_closed(uuid),
+            DroneEvent::IncidentsSynced(open) => self.on_incidents_synced(open),
+            DroneEvent::ArrivedAtAnchor => self.arrive(TravelLocation::Anchor, DroneStatus::Free),
+            DroneEvent::BatteryLow => self.on_battery_low(),
+            DroneEvent::ArrivedAtStation => {
+                self.arrive(TravelLocation::Central, DroneStatus::Recharging)
+            }
+            DroneEvent::ArrivedAtWaypoint => {
+                self.arrive(TravelLocation::Waypoint, DroneStatus::Held)
+            }
+            DroneEvent::Command(command) => self.on_command(command),
+            DroneEvent::Tick => self.on_tick(),
+            DroneEvent::BatteryDischarge => {
+                self.discharge_battery();
+                Vec::new()
+            }
+            DroneEvent::BatteryRecharge => {
+                if self.status != DroneStatus::Recharging {
+                    return Vec::new();
+                }
+                self.recharge_battery();
+                if self.is_fully_charged() {
+                    self.log(
+                        LogLevel::Info,
+                        "battery-charged",
+                        format!("battery {}", self.battery),
+                    );
+                    self.return_to_anchor();
+                }
+                Vec::new()
+            }
+            DroneEvent::BatteryFault(drop) => {
+                self.battery = self.battery.saturating_sub(drop);
+                self.log(
+                    LogLevel::Error,
+                    "battery-fault",
+                    format!("lost {} units, battery {}", drop, self.battery),
+                );
+                Vec::new()
+            }
+            // A drone on its way to an incident recharges first, but one attending it stays
+            DroneEvent::BatteryCheck => {
+                if !self.is_below_minimun() {
+                    return Vec::new();
+                }
+                if self.is_free() {
+                    return self.handle_event(DroneEvent::BatteryLow);
+                }
+                if self.status == DroneStatus::Travelling(TravelLocation::Incident) {
+                    return self.withdraw_from_current_incident();
+                }
+                Vec::new()
+            }
+            DroneEvent::ReassignmentCheck => {
+                if !self.is_free() || self.is_below_minimun() || self.current_incident().is_some() {
+                    return Vec::new();
+                }
+                match self.take_over_incident() {
+                    Some(incident) => self.handle_event(DroneEvent::IncidentAssigned(incident)),
+                    None => Vec::new(),
+                }
+            }
+            DroneEvent::AnchorReceived(drone_id, anchor) => {
+                self.on_anchor_received(drone_id, anchor)
+            }
+            DroneEvent::AnchorWindowClosed => self.choose_anchor(),
+            DroneEvent::PeerMoved(drone_id, position) => {
+                if let Some(separation) = &mut self.separation {
+                    separation.update_peer(drone_id, position);
+                }
+                Vec::new()
+            }
+            DroneEvent::PeerDisconnected(drone_id) => {
+                if let Some(separation) = &mut self.separation {
+                    separation.remove_peer(drone_id);
+                }
+                Vec::new()
+            }
+        }
+    }
+
+    /// A restored drone goes back to its anchor from where it was, and takes up its queue
+    /// again. A drone with an automatic anchor waits to learn the anchors of the others first
+    fn on_started(&mut self) -> Vec<DroneAction> {
+        self.return_to_anchor();
+        if self.negotiates_anchor() {
+            return vec![DroneAction::Schedule(
+                DroneEvent::AnchorWindowClosed,
+                ANCHOR_WINDOW,
+            )];
+        }
+        vec![DroneAction::PublishAnchor(self.anchor())]
+    }
+
+    /// A drone that chose the same anchor as one with a lower id chooses another one
+    fn on_anchor_received(&mut self, drone_id: u8, anchor: Position) -> Vec<DroneAction> {
+        if drone_id == self.id {
+            return Vec::new();
+        }
+        let must_choose = match &mut self.anchor_negotiation {
+            Some(negotiation) => negotiation.add_peer(self.id, drone_id, anchor, self.anchor),
+            None => false,
+        };
+        if must_choose {
+            return self.choose_anchor();
+        }
+        Vec::new()
+    }
+
+    /// Chooses the anchor that covers the most area the other drones leave uncovered
+    fn choose_anchor(&mut self) -> Vec<DroneAction> {
+        let anchor = match &mut self.anchor_negotiation {
+            Some(negotiation) => negotiation.choose(self.anchor, &self.no_fly_zones),
+            None => return Vec::new(),
+        };
+        self.move_anchor(anchor)
+    }
+
+    /// Replaces the anchor of the drone, which goes there right away if it is free
+    fn move_anchor(&mut self, anchor: Position) -> Vec<DroneAction> {
+        self.anchor = anchor;
+        if self.is_free() {
+            self.return_to_anchor();
+        }
+        vec![DroneAction::PublishAnchor(self.anchor())]
+    }
+
+    fn current_incident_is(&self, uuid: &str) -> bool {
+        self.current_incident()
+            .is_some_and(|incident| incident.uuid == uuid)
+    }
+
+    /// Incidents inside a no-fly zone are rejected, so the monitor knows the drone won't
+    /// attend them
+    fn on_incident_reported(&mut self, incident: Incident) -> Vec<DroneAction> {
+        if !self.is_within_range(incident.x_coordinate, incident.y_coordinate) {
+            return Vec::new();
+        }
+        if self.is_in_no_fly_zone(incident.x_coordinate, incident.y_coordinate) {
+            return vec![DroneAction::PublishRejected(incident.uuid)];
+        }
+        self.add_incident(incident);
+        Vec::new()
+    }
+
+    /// The drone claims the incident and waits where it is for the claims of the other drones
+    /// that received it
+    fn on_incident_assigned(&mut self, incident: Incident) -> Vec<DroneAction> {
+        if !self.current_incident_is(&incident.uuid) {
+            return Vec::new();
+        }
+        self.status = DroneStatus::Travelling(TravelLocation::Incident);
+        self.trip = None;
+
+        let uuid = incident.uuid;
+        let mut actions = vec![
+            DroneAction::Subscribe(IncidentTopic::Attending, uuid.clone()),
+            DroneAction::Subscribe(IncidentTopic::Claim, uuid.clone()),
+        ];
+        if let Some(claim) = self.claim_current_incident() {
+            actions.push(DroneAction::PublishClaim(uuid.clone(), claim));
+        }
+        actions.push(DroneAction::Schedule(
+            DroneEvent::ClaimWindowClosed(uuid),
+            CLAIM_WINDOW,
+        ));
+        actions
+    }
+
+    fn on_claim_received(&mut self, uuid: String, claim: IncidentClaim) -> Vec<DroneAction> {
+        match self.add_claim(&uuid, claim) {
+            ClaimOutcome::Announce(own_claim) => vec![DroneAction::PublishClaim(uuid, own_claim)],
+            ClaimOutcome::Aborted => self.give_up_current_incident(),
+            ClaimOutcome::Forgotten => vec![DroneAction::Unsubscribe(IncidentTopic::Claim, uuid)],
+            ClaimOutcome::Ignored => Vec::new(),
+        }
+    }
+
+    /// Only the closest drones that claimed the incident go. The rest give it up, but keep
+    /// following its claims in case they can take the place of one of them later
+    fn on_claim_window_closed(&mut self, uuid: String) -> Vec<DroneAction> {
+        let incident = match self.current_incident() {
+            Some(incident) if incident.uuid == uuid => incident,
+            _ => return Vec::new(),
+        };
+        let claiming = self
+            .claims
+            .get(&uuid)
+            .and_then(|claims| claims.get(&self.id))
+            .is_some_and(|claim| claim.state == ClaimState::Claimed);
+        if self.status != DroneStatus::Travelling(TravelLocation::Incident) || !claiming {
+            return Vec::new();
+        }
+
+        if self.is_responder() {
+            self.log(
+                LogLevel::Info,
+                "incident-accepted",
+                format!(
+                    "{} at ({}, {})",
+                    uuid, incident.x_coordinate, incident.y_coordinate
+                ),
+            );
+            let mut actions = Vec::new();
+            if let Some(claim) = self.commit_to_current_incident() {
+                actions.push(DroneAction::PublishClaim(uuid, claim));
+            }
+            self.start_trip(
+                incident.x_coordinate,
+                incident.y_coordinate,
+                TravelLocation::Incident,
+            );
+            return actions;
+        }
+
+        self.give_up_current_incident()
+    }
+
+    /// Gives the current incident up to the drones that won it, and goes back to the anchor.
+    /// The drone keeps following the claims of the incident, so it can take their place if
+    /// they give it up too
+    fn give_up_current_incident(&mut self) -> Vec<DroneAction> {
+        let uuid = match self.current_incident() {
+            Some(incident) => incident.uuid,
+            None => return Vec::new(),
+        };
+        let mut actions = vec![DroneAction::Unsubscribe(
+            IncidentTopic::Attending,
+            uuid.clone(),
+        )];
+        if !self.cover_current_incident() {
+            actions.push(DroneAction::Unsubscribe(IncidentTopic::Claim, uuid));
+        }
+        self.return_to_anchor();
+        actions
+    }
+
+    /// Gives the current incident up to recharge, telling the other drones so one of them
+    /// takes its place. The incident stays in the queue, so the drone claims it again once
+    /// it is back at its anchor if nobody attended it meanwhile
+    fn withdraw_from_current_incident(&mut self) -> Vec<DroneAction> {
+        let incident = match self.current_incident() {
+            Some(incident) => incident,
+            None => return Vec::new(),
+        };
+        let uuid = incident.uuid.clone();
+        self.log(
+            LogLevel::Warning,
+            "incident-withdrawn",
+            format!("{} with battery {}", uuid, self.battery),
+        );
+        let claim = self.claim_for(&incident, ClaimState::Withdrawn);
+        self.claims.remove(&uuid);
+        self.current_incident_count = 0;
+
+        let mut actions = vec![
+            DroneAction::PublishClaim(uuid.clone(), claim),
+            DroneAction::Unsubscribe(IncidentTopic::Attending, uuid.clone()),
+            DroneAction::Unsubscribe(IncidentTopic::Claim, uuid),
+        ];
+        actions.extend(self.go_recharge());
+        actions
+    }
+
+    /// Once the drone arrives no other drone can take its place
+    fn on_arrived_at_incident(&mut self) -> Vec<DroneAction> {
+        let incident = match self.current_incident() {
+            Some(incident) if self.status == DroneStatus::Travelling(TravelLocation::Incident) => {
+                incident
+            }
+            _ => return Vec::new(),
+        };
+        self.status = DroneStatus::AttendingIncident;
+
+        let mut actions = Vec::new();
+        if let Some(claim) = self.attend_current_incident() {
+            actions.push(DroneAction::PublishClaim(incident.uuid.clone(), claim));
+        }
+        actions.push(DroneAction::PublishAttending(incident.uuid));
+        actions
+    }
+
+    /// Once enough drones arrived, the ones attending the incident wait for it to be solved,
+    /// and the ones that didn't arrive yet give it up
+    fn on_peer_attending(&mut self, uuid: String) -> Vec<DroneAction> {
+        let incident = match self.current_incident() {
+            Some(incident) if incident.uuid == uuid => incident,
+            _ => return Vec::new(),
+        };
+        self.increment_attending_counter();
+        if self.attending_counter() != self.required_drones(&incident) {
+            return Vec::new();
+        }
+
+        match self.status {
+            DroneStatus::AttendingIncident => vec![
+                DroneAction::Subscribe(IncidentTopic::Close, uuid.clone()),
+                DroneAction::Unsubscribe(IncidentTopic::Attending, uuid.clone()),
+                DroneAction::Schedule(DroneEvent::IncidentAttended(uuid), ATTENDING_DURATION),
+            ],
+            DroneStatus::Travelling(TravelLocation::Incident) => {
+                self.status = DroneStatus::Interrupted;
+                self.abandon_current_incident()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn on_incident_closed(&mut self, uuid: String) -> Vec<DroneAction> {
+        if !self.current_incident_is(&uuid) {
+            return Vec::new();
+        }
+        self.remove_current_incident();
+        self.return_to_anchor();
+
+        [
+            IncidentTopic::Attending,
+            IncidentTopic::Close,
+            IncidentTopic::Claim,
+        ]
+        .into_iter()
+        .map(|topic| DroneAction::Unsubscribe(topic, uuid.clone()))
+        .collect()
+    }
+
+    /// Reconciles the incidents of the drone with the ones the monitor still has open, as
+    /// the drone may have missed messages while it was disconnected. The incidents closed
+    /// meanwhile are forgotten, and the pending ones the drone never received are reported
+    /// again
+    fn on_incidents_synced(&mut self, open: Vec<Incident>) -> Vec<DroneAction> {
+        let is_open = |uuid: &str| open.iter().any(|incident| incident.uuid == uuid);
+        let mut actions = Vec::new();
+
+        if let Some(current) = self.current_incident() {
+            if self.is_handling_incident() && !is_open(&current.uuid) {
+                actions.extend(self.on_incident_closed(current.uuid));
+            }
+        }
+
+        let closed: Vec<String> = self
+            .incident_queue
+            .iter()
+            .map(|incident| incident.uuid.clone())
+            .filter(|uuid| !is_open(uuid))
+            .collect();
+        for uuid in closed {
+            self.incident_queue.retain(|incident| incident.uuid != uuid);
+            self.claims.remove(&uuid);
+        }
+
+        let covered: Vec<String> = self
+            .covered_incidents
+            .iter()
+            .map(|incident| incident.uuid.clone())
+            .filter(|uuid| !is_open(uuid))
+            .collect();
+        for uuid in covered {
+            self.covered_incidents
+                .retain(|incident| incident.uuid != uuid);
+            self.claims.remove(&uuid);
+            actions.push(DroneAction::Unsubscribe(IncidentTopic::Claim, uuid));
+        }
+        self.prioritize_incidents();
+
+        for incident in open {
+            let known = self
+                .incident_queue
+                .iter()
+                .chain(&self.covered_incidents)
+                .any(|known| known.uuid == incident.uuid);
+            if !known && incident.status == IncidentStatus::Pending {
+                actions.extend(self.on_incident_reported(incident));
+            }
+        }
+        actions
+    }
+
+    /// Forgets the current incident, which other drones attend, and goes back to the anchor
+    fn abandon_current_incident(&mut self) -> Vec<DroneAction> {
+        let uuid = match self.current_incident() {
+            Some(incident) => incident.uuid,
+            None => return Vec::new(),
+        };
+        self.remove_current_incident();
+        self.return_to_anchor();
+
+        vec![
+            DroneAction::Unsubscribe(IncidentTopic::Attending, uuid.clone()),
+            DroneAction::Unsubscribe(IncidentTopic::Claim, uuid),
+        ]
+    }
+
+    fn on_battery_low(&mut self) -> Vec<DroneAction> {
+        if !self.is_free() {
+            return Vec::new();
+        }
+        self.go_recharge()
+    }
+
+    /// Travels to the nearest charging station
+    fn go_recharge(&mut self) -> Vec<DroneAction> {
+        let station = self.nearest_charging_station();
+        self.log(
+            LogLevel::Warning,
+            "battery-low",
+            format!(
+                "battery {} below {}",
+                self.battery, self.battery_model.minimum_level
+            ),
+        );
+        self.start_trip(station.x, station.y, TravelLocation::Central);
+        vec![DroneAction::PublishChargingStation(station)]
+    }
+
+    /// The status changes right away, so the drone doesn't take an incident in the meantime
+    fn on_command(&mut self, command: DroneCommand) -> Vec<DroneAction> {
+        let accepted = self.accepts_command(&command);
+        let mut actions = Vec::new();
+        if accepted {
+            match command {
+                DroneCommand::ReturnToCentral => {
+                    self.start_trip(self.central.x, self.central.y, TravelLocation::Waypoint)
+                }
+                DroneCommand::Goto(x, y) => self.start_trip(x, y, TravelLocation::Waypoint),
+                DroneCommand::Hold => {
+                    self.status = DroneStatus::Held;
+                    self.trip = None;
+                    self.motion.stop();
+                }
+                DroneCommand::Resume => self.return_to_anchor(),
+                // The anchor of an operator is kept, instead of negotiated again
+                DroneCommand::Anchor(x, y) => {
+                    self.anchor_negotiation = None;
+                    actions = self.move_anchor(Position::new(x, y));
+                }
+                // The monitor sends the incidents the rest rejected, even if they are out of range
+                DroneCommand::Attend(ref incident) => self.add_incident(incident.clone()),
+            }
+        }
+        actions.push(DroneAction::PublishCommandAck(DroneCommandAck {
+            command,
+            accepted,
+        }));
+        actions
+    }
+
+    /// Ends the travel in progress, if the drone is still on it
+    fn arrive(&mut self, location: TravelLocation, status: DroneStatus) -> Vec<DroneAction> {
+        if self.status == DroneStatus::Travelling(location) {
+            self.status = status;
+        }
+        Vec::new()
+    }
+
+    /// Moves the drone a step along its travel and, if it is free, makes it take the closest
+    /// incident from where it is now
+    fn on_tick(&mut self) -> Vec<DroneAction> {
+        let mut actions = Vec::new();
+        if let Some((target, location)) = self.trip {
+            if self.status == DroneStatus::Travelling(location) {
+                self.travel_to(target.x, target.y);
+                if self.position == target {
+                    self.trip = None;
+                    let arrival = match location {
+                        TravelLocation::Anchor => DroneEvent::ArrivedAtAnchor,
+                        TravelLocation::Central => DroneEvent::ArrivedAtStation,
+                        TravelLocation::Incident => DroneEvent::ArrivedAtIncident,
+                        TravelLocation::Waypoint => DroneEvent::ArrivedAtWaypoint,
+                    };
+                    actions.extend(self.handle_event(arrival));
+                }
+            }
+        }
+
+        if self.is_free() && !self.is_below_minimun() {
+            self.prioritize_incidents();
+            if let Some(incident) = self.current_incident() {
+                actions.extend(self.handle_event(DroneEvent::IncidentAssigned(incident)));
+            }
+        }
+        actions
+    }
+}
+
+/// Returns how many drones are attending the incident
+fn attending_count(claims: &HashMap<u8, IncidentClaim>) -> usize {
+    claims
+        .values()
+        .filter(|claim| claim.state == ClaimState::Attending)
+        .count()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use common::incident::IncidentStatus;
 
     #[test]
     fn test_drone_data() {
@@ -227,10 +1237,830 @@ mod tests {
         assert_eq!(drone.data(), "1;1;1;100");
     }
 
+    #[test]
+    fn test_disconnected_drone_data() {
+        let drone = Drone::new(1, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0);
+        assert_eq!(drone.disconnected_data(), "1;1;7;100");
+        assert_eq!(drone.data(), "1;1;3;100");
+    }
+
     #[test]
     fn test_drone_travel_to() {
         let mut drone = Drone::new(1, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0);
         drone.travel_to(3.0, 3.0);
         assert_eq!(drone.data(), "1.7071067811865475;1.7071067811865475;3;100");
     }
+
+    #[test]
+    fn test_travelling_drone_data_has_its_eta() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 2.0, 10.0);
+        drone.start_trip(5.0, 0.0, TravelLocation::Central);
+        assert_eq!(drone.eta(), Some(Duration::from_secs(3)));
+        assert_eq!(drone.data(), "0;0;2;100;3");
+
+        drone.handle_event(DroneEvent::Tick);
+        assert_eq!(drone.data(), "2;0;2;100;2");
+
+        // The drone waiting at the incident has no destination
+        drone.set_status(DroneStatus::AttendingIncident);
+        assert_eq!(drone.eta(), None);
+    }
+
+    #[test]
+    fn test_accelerating_drone_departs_and_arrives_slowly() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 3.0, 20.0).with_acceleration(Some(1.0));
+        drone.start_trip(12.0, 0.0, TravelLocation::Waypoint);
+        assert_eq!(drone.eta(), Some(Duration::from_secs(6)));
+
+        let mut positions = Vec::new();
+        for _ in 0..6 {
+            drone.handle_event(DroneEvent::Tick);
+            positions.push(drone.position.x);
+        }
+        assert_eq!(positions, vec![1.0, 3.0, 6.0, 9.0, 11.0, 12.0]);
+        assert_eq!(drone.status(), DroneStatus::Held);
+    }
+
+    #[test]
+    fn test_drone_keeps_its_distance_while_travelling() {
+        let mut drone =
+            Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0).with_separation_distance(Some(1.0));
+        drone.handle_event(DroneEvent::PeerMoved(2, Position::new(1.0, 0.0)));
+        drone.start_trip(10.0, 0.0, TravelLocation::Waypoint);
+
+        // Both drones head the same way, so this one moves to its side
+        drone.handle_event(DroneEvent::Tick);
+        assert!(drone.position.distance_to(&Position::new(1.0, 1.0)) < 1e-9);
+
+        drone.handle_event(DroneEvent::PeerDisconnected(2));
+        tick_until_arrival(&mut drone);
+        assert_eq!(drone.position, Position::new(10.0, 0.0));
+    }
+
+    #[test]
+    fn test_drone_resumes_from_its_saved_state() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 1.0, 1.0, 1.0, 10.0);
+        drone.discharge_battery();
+        drone.travel_to(1.0, 0.0);
+        drone.add_incident(incident("1", 3.0, 0.0));
+
+        let restarted = Drone::new(1, 0.0, 0.0, 1.0, 1.0, 1.0, 10.0).with_state(drone.state());
+        assert_eq!(restarted.data(), "1;0;3;99");
+        assert_eq!(
+            restarted.current_incident().map(|incident| incident.uuid),
+            Some("1".to_string())
+        );
+
+        let mut restarted = restarted;
+        restarted.add_incident(incident("1", 3.0, 0.0));
+        restarted.remove_current_incident();
+        assert_eq!(restarted.current_incident(), None);
+    }
+
+    #[test]
+    fn test_drone_history() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0).with_history_length(2);
+        drone.record_telemetry();
+        drone.travel_to(1.0, 0.0);
+        drone.set_status(DroneStatus::Free);
+        drone.record_telemetry();
+
+        assert_eq!(drone.history(), "0;0;3;100|1;0;0;100");
+    }
+
+    fn incident(uuid: &str, x: f64, y: f64) -> Incident {
+        Incident::new(
+            uuid.to_string(),
+            uuid.to_string(),
+            uuid.to_string(),
+            x,
+            y,
+            IncidentStatus::Pending,
+        )
+    }
+
+    fn queued_uuids(drone: &Drone) -> Vec<String> {
+        drone
+            .incident_queue
+            .iter()
+            .map(|incident| incident.uuid.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_closer_incidents_are_attended_first() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0);
+        drone.set_status(DroneStatus::Free);
+
+        drone.add_incident(incident("far", 5.0, 5.0));
+        drone.add_incident(incident("near", 1.0, 0.0));
+        drone.add_incident(incident("middle", 0.0, 3.0));
+        drone.add_incident(incident("also-near", 0.0, 1.0));
+
+        assert_eq!(
+            queued_uuids(&drone),
+            vec!["near", "also-near", "middle", "far"]
+        );
+        assert_eq!(drone.current_incident().unwrap().uuid, "near");
+    }
+
+    #[test]
+    fn test_incident_being_handled_stays_first() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 10.0, 10.0);
+        drone.add_incident(incident("far", 5.0, 5.0));
+        drone.set_status(DroneStatus::Travelling(TravelLocation::Incident));
+
+        drone.add_incident(incident("middle", 0.0, 3.0));
+        drone.add_incident(incident("near", 1.0, 0.0));
+        assert_eq!(queued_uuids(&drone), vec!["far", "near", "middle"]);
+
+        // Once it is solved, the rest are ordered from where the drone is
+        drone.travel_to(5.0, 5.0);
+        drone.set_status(DroneStatus::AttendingIncident);
+        drone.travel_to(0.0, 3.0);
+        drone.set_status(DroneStatus::Free);
+        drone.remove_current_incident();
+        assert_eq!(queued_uuids(&drone), vec!["middle", "near"]);
+    }
+
+    #[test]
+    fn test_battery_discharges_with_the_distance_travelled() {
+        let battery_model = BatteryModel {
+            discharge_distance: 0.5,
+            discharge_idle: 1,
+            ..BatteryModel::default()
+        };
+        let mut drone =
+            Drone::new(1, 0.0, 0.0, 10.0, 0.0, 0.75, 1.0).with_battery_model(battery_model);
+
+        drone.travel_to(10.0, 0.0);
+        drone.discharge_battery();
+        assert_eq!(drone.data(), "0.75;0;3;98");
+
+        // The quarter left of the first step adds up with the second one
+        drone.travel_to(10.0, 0.0);
+        drone.discharge_battery();
+        assert_eq!(drone.data(), "1.5;0;3;95");
+
+        drone.discharge_battery();
+        assert_eq!(drone.data(), "1.5;0;3;94");
+    }
+
+    #[test]
+    fn test_battery_follows_its_model() {
+        let battery_model = BatteryModel {
+            minimum_level: 30,
+            maximum_level: 50,
+            discharge_idle: 15,
+            recharge: 40,
+            ..BatteryModel::default()
+        };
+        let mut drone =
+            Drone::new(1, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0).with_battery_model(battery_model);
+        drone.set_status(DroneStatus::Free);
+        assert!(drone.is_fully_charged());
+
+        drone.discharge_battery();
+        assert!(!drone.is_below_minimun());
+        drone.discharge_battery();
+        assert!(drone.is_below_minimun());
+        assert_eq!(drone.data(), "1;1;0;20");
+
+        drone.recharge_battery();
+        assert!(drone.is_fully_charged());
+        assert_eq!(drone.data(), "1;1;0;50");
+    }
+
+    #[test]
+    fn test_nearest_charging_station_is_chosen() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 10.0, 10.0);
+        let central = drone.nearest_charging_station();
+        assert_eq!((central.x, central.y), (0.0, 0.0));
+
+        drone =
+            drone.with_charging_stations(vec![Position::new(10.0, 10.0), Position::new(4.0, 0.0)]);
+        drone.travel_to(3.0, 0.0);
+        let station = drone.nearest_charging_station();
+        assert_eq!((station.x, station.y), (4.0, 0.0));
+
+        drone.travel_to(1.0, 0.0);
+        let station = drone.nearest_charging_station();
+        assert_eq!((station.x, station.y), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_drone_follows_its_route() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 0.5, 10.0)
+            .with_no_fly_zones(vec![NoFlyZone::new(5.0, 0.0, 1.0)]);
+        assert_eq!(drone.take_new_route(), None);
+
+        drone.travel_to(10.0, 0.0);
+        let route = drone.take_new_route().unwrap();
+        assert!(route.ends_with("|10;0"));
+        assert_eq!(drone.take_new_route(), None);
+
+        let mut steps = 0;
+        while drone.distance_to(10.0, 0.0) > 0.0 {
+            drone.travel_to(10.0, 0.0);
+            assert!(!drone.is_in_no_fly_zone(drone.position.x, drone.position.y));
+            steps += 1;
+            assert!(steps < 100);
+        }
+        assert_eq!(drone.take_new_route(), None);
+    }
+
+    #[test]
+    fn test_operator_commands_wait_for_incidents_and_recharges() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0)
+            .with_no_fly_zones(vec![NoFlyZone::new(5.0, 0.0, 1.0)]);
+        drone.set_status(DroneStatus::Free);
+        assert!(drone.accepts_command(&DroneCommand::Goto(2.0, 2.0)));
+        assert!(!drone.accepts_command(&DroneCommand::Goto(5.0, 0.0)));
+        assert!(!drone.accepts_command(&DroneCommand::Resume));
+
+        drone.set_status(DroneStatus::Travelling(TravelLocation::Incident));
+        assert!(!drone.accepts_command(&DroneCommand::Hold));
+        drone.set_status(DroneStatus::Recharging);
+        assert!(!drone.accepts_command(&DroneCommand::ReturnToCentral));
+
+        drone.set_status(DroneStatus::Held);
+        assert!(drone.accepts_command(&DroneCommand::Resume));
+        assert!(drone.accepts_command(&DroneCommand::Goto(2.0, 2.0)));
+    }
+
+    /// Handles ticks until the drone stops travelling, returning the actions of the last one
+    fn tick_until_arrival(drone: &mut Drone) -> Vec<DroneAction> {
+        for _ in 0..100 {
+            let actions = drone.handle_event(DroneEvent::Tick);
+            if drone.trip.is_none() {
+                return actions;
+            }
+        }
+        panic!("The drone never arrived");
+    }
+
+    #[test]
+    fn test_new_trip_ends_the_one_in_progress() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0);
+        drone.start_trip(3.0, 0.0, TravelLocation::Anchor);
+        drone.start_trip(0.0, 3.0, TravelLocation::Waypoint);
+
+        // A drone sent somewhere else while it was going back to its anchor isn't freed there
+        drone.handle_event(DroneEvent::ArrivedAtAnchor);
+        assert_eq!(
+            drone.status(),
+            DroneStatus::Travelling(TravelLocation::Waypoint)
+        );
+
+        tick_until_arrival(&mut drone);
+        assert_eq!(drone.data(), "0;3;8;100");
+    }
+
+    #[test]
+    fn test_reported_incidents_are_queued_or_rejected() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0)
+            .with_no_fly_zones(vec![NoFlyZone::new(5.0, 0.0, 1.0)]);
+
+        let far = DroneEvent::IncidentReported(incident("far", 20.0, 0.0));
+        assert!(drone.handle_event(far).is_empty());
+        let restricted = DroneEvent::IncidentReported(incident("restricted", 5.0, 0.0));
+        assert_eq!(
+            drone.handle_event(restricted),
+            vec![DroneAction::PublishRejected("restricted".to_string())]
+        );
+        let fire = DroneEvent::IncidentReported(incident("fire", 3.0, 4.0));
+        assert!(drone.handle_event(fire).is_empty());
+
+        assert_eq!(queued_uuids(&drone), vec!["fire"]);
+    }
+
+    #[test]
+    fn test_reassigned_incidents_are_queued_even_out_of_range() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0)
+            .with_no_fly_zones(vec![NoFlyZone::new(5.0, 0.0, 1.0)]);
+        drone.set_status(DroneStatus::AttendingIncident);
+
+        let far = DroneCommand::Attend(incident("far", 20.0, 0.0));
+        let restricted = DroneCommand::Attend(incident("restricted", 5.0, 0.0));
+        assert!(drone.accepts_command(&far));
+        assert!(!drone.accepts_command(&restricted));
+
+        drone.handle_event(DroneEvent::Command(far));
+        drone.handle_event(DroneEvent::Command(restricted));
+        assert_eq!(queued_uuids(&drone), vec!["far"]);
+    }
+
+    #[test]
+    fn test_free_drone_claims_the_closest_incident_on_tick() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0);
+        drone.add_incident(incident("fire", 3.0, 4.0));
+        drone.set_status(DroneStatus::AttendingIncident);
+        assert!(drone.handle_event(DroneEvent::Tick).is_empty());
+
+        drone.set_status(DroneStatus::Free);
+        let uuid = "fire".to_string();
+        assert_eq!(
+            drone.handle_event(DroneEvent::Tick),
+            vec![
+                DroneAction::Subscribe(IncidentTopic::Attending, uuid.clone()),
+                DroneAction::Subscribe(IncidentTopic::Claim, uuid.clone()),
+                DroneAction::PublishClaim(uuid.clone(), claim(1, 5.0, ClaimState::Claimed)),
+                DroneAction::Schedule(DroneEvent::ClaimWindowClosed(uuid), CLAIM_WINDOW),
+            ]
+        );
+        assert_eq!(
+            drone.status(),
+            DroneStatus::Travelling(TravelLocation::Incident)
+        );
+
+        // It waits where it is while the drones claim the incident
+        assert!(drone.handle_event(DroneEvent::Tick).is_empty());
+        assert_eq!(drone.data(), "0;0;4;100");
+    }
+
+    #[test]
+    fn test_responder_travels_to_the_incident_and_attends_it() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0).with_drones_per_incident(2);
+        drone.add_incident(incident("fire", 3.0, 4.0));
+        drone.handle_event(DroneEvent::IncidentAssigned(incident("fire", 3.0, 4.0)));
+
+        let uuid = "fire".to_string();
+        assert_eq!(
+            drone.handle_event(DroneEvent::ClaimWindowClosed(uuid.clone())),
+            vec![DroneAction::PublishClaim(
+                uuid.clone(),
+                claim(1, 5.0, ClaimState::Committed)
+            )]
+        );
+        assert!(drone
+            .handle_event(DroneEvent::ClaimWindowClosed(uuid.clone()))
+            .is_empty());
+
+        assert_eq!(
+            tick_until_arrival(&mut drone),
+            vec![
+                DroneAction::PublishClaim(uuid.clone(), claim(1, 0.0, ClaimState::Attending)),
+                DroneAction::PublishAttending(uuid.clone()),
+            ]
+        );
+        assert_eq!(drone.status(), DroneStatus::AttendingIncident);
+
+        // The incident is attended once enough drones arrive, counting this one
+        assert!(drone
+            .handle_event(DroneEvent::PeerAttending(uuid.clone()))
+            .is_empty());
+        assert_eq!(
+            drone.handle_event(DroneEvent::PeerAttending(uuid.clone())),
+            vec![
+                DroneAction::Subscribe(IncidentTopic::Close, uuid.clone()),
+                DroneAction::Unsubscribe(IncidentTopic::Attending, uuid.clone()),
+                DroneAction::Schedule(
+                    DroneEvent::IncidentAttended(uuid.clone()),
+                    ATTENDING_DURATION
+                ),
+            ]
+        );
+        assert_eq!(
+            drone.handle_event(DroneEvent::IncidentAttended(uuid.clone())),
+            vec![DroneAction::PublishReady(uuid.clone())]
+        );
+
+        assert_eq!(
+            drone.handle_event(DroneEvent::IncidentClosed(uuid.clone())),
+            vec![
+                DroneAction::Unsubscribe(IncidentTopic::Attending, uuid.clone()),
+                DroneAction::Unsubscribe(IncidentTopic::Close, uuid.clone()),
+                DroneAction::Unsubscribe(IncidentTopic::Claim, uuid),
+            ]
+        );
+        assert_eq!(drone.current_incident(), None);
+        tick_until_arrival(&mut drone);
+        assert_eq!(drone.data(), "0;0;0;100");
+    }
+
+    #[test]
+    fn test_drone_that_loses_the_claim_covers_the_incident() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0).with_drones_per_incident(2);
+        drone.add_incident(incident("fire", 3.0, 4.0));
+        drone.handle_event(DroneEvent::IncidentAssigned(incident("fire", 3.0, 4.0)));
+        for drone_id in [2, 3] {
+            let claim = claim(drone_id, 1.0, ClaimState::Claimed);
+            drone.handle_event(DroneEvent::ClaimReceived("fire".to_string(), claim));
+        }
+
+        assert_eq!(
+            drone.handle_event(DroneEvent::ClaimWindowClosed("fire".to_string())),
+            vec![DroneAction::Unsubscribe(
+                IncidentTopic::Attending,
+                "fire".to_string()
+            )]
+        );
+        assert_eq!(
+            drone.status(),
+            DroneStatus::Travelling(TravelLocation::Anchor)
+        );
+        assert_eq!(drone.covered_incidents().len(), 1);
+
+        // It takes the place of a drone that is now farther than this one
+        let far_claim = claim(3, 6.0, ClaimState::Committed);
+        drone.handle_event(DroneEvent::ClaimReceived("fire".to_string(), far_claim));
+        let actions = drone.handle_event(DroneEvent::ReassignmentCheck);
+        assert!(actions.contains(&DroneAction::Subscribe(
+            IncidentTopic::Attending,
+            "fire".to_string()
+        )));
+        assert_eq!(
+            drone.status(),
+            DroneStatus::Travelling(TravelLocation::Incident)
+        );
+    }
+
+    #[test]
+    fn test_drone_gives_up_the_incident_when_the_others_arrive_first() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0).with_drones_per_incident(2);
+        drone.add_incident(incident("fire", 30.0, 40.0));
+        drone.handle_event(DroneEvent::IncidentAssigned(incident("fire", 30.0, 40.0)));
+        drone.handle_event(DroneEvent::ClaimWindowClosed("fire".to_string()));
+        drone.handle_event(DroneEvent::Tick);
+
+        drone.handle_event(DroneEvent::PeerAttending("fire".to_string()));
+        assert_eq!(
+            drone.handle_event(DroneEvent::PeerAttending("fire".to_string())),
+            vec![
+                DroneAction::Unsubscribe(IncidentTopic::Attending, "fire".to_string()),
+                DroneAction::Unsubscribe(IncidentTopic::Claim, "fire".to_string()),
+            ]
+        );
+        assert_eq!(drone.current_incident(), None);
+        assert_eq!(
+            drone.status(),
+            DroneStatus::Travelling(TravelLocation::Anchor)
+        );
+    }
+
+    #[test]
+    fn test_drone_with_low_battery_withdraws_and_resumes_the_incident() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0)
+            .with_charging_stations(vec![Position::new(0.0, 0.0)]);
+        drone.add_incident(incident("fire", 3.0, 4.0));
+        drone.handle_event(DroneEvent::IncidentAssigned(incident("fire", 3.0, 4.0)));
+        drone.handle_event(DroneEvent::ClaimWindowClosed("fire".to_string()));
+        drone.handle_event(DroneEvent::Tick);
+
+        drone.handle_event(DroneEvent::BatteryFault(90));
+        let uuid = "fire".to_string();
+        assert_eq!(
+            drone.handle_event(DroneEvent::BatteryCheck),
+            vec![
+                DroneAction::PublishClaim(uuid.clone(), claim(1, 4.0, ClaimState::Withdrawn)),
+                DroneAction::Unsubscribe(IncidentTopic::Attending, uuid.clone()),
+                DroneAction::Unsubscribe(IncidentTopic::Claim, uuid.clone()),
+                DroneAction::PublishChargingStation(Position::new(0.0, 0.0)),
+            ]
+        );
+        assert_eq!(queued_uuids(&drone), vec!["fire"]);
+
+        // Back at its anchor with a full battery, it claims the incident again
+        tick_until_arrival(&mut drone);
+        while drone.status() == DroneStatus::Recharging {
+            drone.handle_event(DroneEvent::BatteryRecharge);
+        }
+        let actions = tick_until_arrival(&mut drone);
+        assert!(actions.contains(&DroneAction::Subscribe(IncidentTopic::Claim, uuid)));
+        assert_eq!(
+            drone.status(),
+            DroneStatus::Travelling(TravelLocation::Incident)
+        );
+    }
+
+    #[test]
+    fn test_significant_events_are_logged() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0);
+        drone.add_incident(incident("fire", 3.0, 4.0));
+        drone.handle_event(DroneEvent::IncidentAssigned(incident("fire", 3.0, 4.0)));
+        drone.handle_event(DroneEvent::ClaimWindowClosed("fire".to_string()));
+        drone.handle_event(DroneEvent::BatteryFault(90));
+        drone.handle_event(DroneEvent::BatteryCheck);
+
+        let events: Vec<(LogLevel, String)> = drone
+            .take_logs()
+            .into_iter()
+            .map(|log| (log.level, log.event))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (LogLevel::Info, "incident-accepted".to_string()),
+                (LogLevel::Info, "travel-started".to_string()),
+                (LogLevel::Error, "battery-fault".to_string()),
+                (LogLevel::Warning, "incident-withdrawn".to_string()),
+                (LogLevel::Warning, "battery-low".to_string()),
+                (LogLevel::Info, "travel-started".to_string()),
+            ]
+        );
+        assert!(drone.take_logs().is_empty());
+    }
+
+    #[test]
+    fn test_low_battery_sends_the_drone_to_recharge() {
+        let battery_model = BatteryModel {
+            minimum_level: 50,
+            discharge_distance: 1000.0,
+            discharge_idle: 60,
+            recharge: 30,
+            ..BatteryModel::default()
+        };
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0)
+            .with_battery_model(battery_model)
+            .with_charging_stations(vec![Position::new(2.0, 0.0)]);
+        for _ in 0..3 {
+            drone.travel_to(3.0, 0.0);
+        }
+        drone.set_status(DroneStatus::Free);
+        assert!(drone.handle_event(DroneEvent::BatteryCheck).is_empty());
+
+        drone.handle_event(DroneEvent::BatteryDischarge);
+        drone.add_incident(incident("fire", 3.0, 4.0));
+        assert!(drone.handle_event(DroneEvent::Tick).is_empty());
+        assert_eq!(
+            drone.handle_event(DroneEvent::BatteryCheck),
+            vec![DroneAction::PublishChargingStation(Position::new(2.0, 0.0))]
+        );
+
+        tick_until_arrival(&mut drone);
+        assert_eq!(drone.status(), DroneStatus::Recharging);
+        drone.handle_event(DroneEvent::BatteryRecharge);
+        assert_eq!(drone.status(), DroneStatus::Recharging);
+        drone.handle_event(DroneEvent::BatteryRecharge);
+        assert_eq!(
+            drone.status(),
+            DroneStatus::Travelling(TravelLocation::Anchor)
+        );
+        assert_eq!(drone.data(), "2;0;3;100;2");
+    }
+
+    #[test]
+    fn test_battery_fault_drains_the_battery() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0);
+        drone.set_status(DroneStatus::Free);
+        drone.handle_event(DroneEvent::BatteryFault(90));
+        assert_eq!(drone.data(), "0;0;0;10");
+        assert_eq!(
+            drone.handle_event(DroneEvent::BatteryCheck),
+            vec![DroneAction::PublishChargingStation(Position::new(0.0, 0.0))]
+        );
+
+        drone.handle_event(DroneEvent::BatteryFault(90));
+        assert_eq!(drone.data(), "0;0;2;0;0");
+    }
+
+    #[test]
+    fn test_commands_are_acknowledged() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0);
+        drone.set_status(DroneStatus::Free);
+
+        let ack = |command, accepted| {
+            vec![DroneAction::PublishCommandAck(DroneCommandAck {
+                command,
+                accepted,
+            })]
+        };
+        assert_eq!(
+            drone.handle_event(DroneEvent::Command(DroneCommand::Resume)),
+            ack(DroneCommand::Resume, false)
+        );
+        assert_eq!(
+            drone.handle_event(DroneEvent::Command(DroneCommand::Goto(2.0, 0.0))),
+            ack(DroneCommand::Goto(2.0, 0.0), true)
+        );
+        drone.handle_event(DroneEvent::Tick);
+        drone.handle_event(DroneEvent::Command(DroneCommand::Hold));
+        drone.handle_event(DroneEvent::Tick);
+        assert_eq!(drone.data(), "1;0;8;100");
+
+        drone.handle_event(DroneEvent::Command(DroneCommand::Resume));
+        tick_until_arrival(&mut drone);
+        assert_eq!(drone.status(), DroneStatus::Free);
+    }
+
+    #[test]
+    fn test_drones_announce_or_negotiate_their_anchor() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.5, 0.0, 1.0, 10.0);
+        assert_eq!(
+            drone.handle_event(DroneEvent::Started),
+            vec![DroneAction::PublishAnchor(drone.anchor())]
+        );
+
+        let area = OperationsArea {
+            center: Position::new(0.0, 0.0),
+            radius: 1.0,
+        };
+        let mut drone =
+            Drone::new(2, 0.0, 0.0, 0.5, 0.0, 1.0, 10.0).with_operations_area(Some(area));
+        assert_eq!(
+            drone.handle_event(DroneEvent::Started),
+            vec![DroneAction::Schedule(
+                DroneEvent::AnchorWindowClosed,
+                ANCHOR_WINDOW
+            )]
+        );
+        drone.handle_event(DroneEvent::AnchorReceived(1, Position::new(0.5, 0.0)));
+        let actions = drone.handle_event(DroneEvent::AnchorWindowClosed);
+        assert_eq!(actions, vec![DroneAction::PublishAnchor(drone.anchor())]);
+        assert!(drone.anchor.distance_to(&Position::new(0.5, 0.0)) > 1.0);
+        assert_eq!(
+            drone.status(),
+            DroneStatus::Travelling(TravelLocation::Anchor)
+        );
+
+        // The anchor of an operator is kept, even if another drone chooses the same one
+        let anchor = DroneCommand::Anchor(0.2, 0.2);
+        let actions = drone.handle_event(DroneEvent::Command(anchor.clone()));
+        assert_eq!(actions.len(), 2);
+        assert_eq!(drone.anchor, Position::new(0.2, 0.2));
+        assert!(!drone.negotiates_anchor());
+        assert!(drone
+            .handle_event(DroneEvent::AnchorReceived(1, Position::new(0.2, 0.2)))
+            .is_empty());
+    }
+
+    fn claim(drone_id: u8, distance: f64, state: ClaimState) -> IncidentClaim {
+        IncidentClaim {
+            drone_id,
+            distance,
+            state,
+        }
+    }
+
+    #[test]
+    fn test_only_the_closest_claims_respond() {
+        let mut drone = Drone::new(2, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0);
+        drone.add_incident(incident("fire", 3.0, 4.0));
+        let own_claim = drone.claim_current_incident().unwrap();
+        assert_eq!(own_claim.to_string(), "2;5");
+
+        let ignored = drone.add_claim("other", claim(1, 1.0, ClaimState::Claimed));
+        assert_eq!(ignored, ClaimOutcome::Ignored);
+        assert!(drone.is_responder());
+
+        drone.add_claim("fire", claim(1, 6.0, ClaimState::Claimed));
+        drone.add_claim("fire", claim(3, 1.0, ClaimState::Claimed));
+        assert!(drone.is_responder());
+        drone.add_claim("fire", claim(4, 2.0, ClaimState::Committed));
+        assert!(!drone.is_responder());
+    }
+
+    #[test]
+    fn test_committed_drone_announces_itself_and_aborts_for_closer_ones() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0);
+        drone.add_incident(incident("fire", 5.0, 0.0));
+        drone.claim_current_incident();
+        let committed = drone.commit_to_current_incident().unwrap();
+        assert_eq!(committed.to_string(), "1;5;committed");
+        drone.set_status(DroneStatus::Travelling(TravelLocation::Incident));
+
+        // A drone that claims it later learns where this one is now
+        drone.travel_to(5.0, 0.0);
+        let outcome = drone.add_claim("fire", claim(2, 0.5, ClaimState::Claimed));
+        assert_eq!(
+            outcome,
+            ClaimOutcome::Announce(claim(1, 4.0, ClaimState::Committed))
+        );
+
+        // It stays while it is one of the two closest committed drones
+        assert_eq!(
+            drone.add_claim("fire", claim(3, 3.5, ClaimState::Committed)),
+            ClaimOutcome::Ignored
+        );
+        assert_eq!(
+            drone.add_claim("fire", claim(2, 0.5, ClaimState::Committed)),
+            ClaimOutcome::Aborted
+        );
+        assert_eq!(drone.status(), DroneStatus::Interrupted);
+    }
+
+    #[test]
+    fn test_free_drone_takes_over_covered_incidents() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0);
+        drone.add_incident(incident("fire", 3.0, 4.0));
+        drone.claim_current_incident();
+        drone.add_claim("fire", claim(2, 1.0, ClaimState::Committed));
+        drone.add_claim("fire", claim(3, 2.0, ClaimState::Committed));
+        assert!(!drone.is_responder());
+
+        assert!(drone.cover_current_incident());
+        assert!(drone.current_incident().is_none());
+        assert_eq!(drone.take_over_incident().map(|i| i.uuid), None);
+
+        // The farthest drone is still far away when this one is closer
+        drone.add_claim("fire", claim(3, 6.0, ClaimState::Committed));
+        assert_eq!(
+            drone.take_over_incident().map(|i| i.uuid),
+            Some("fire".to_string())
+        );
+        assert!(drone.covered_incidents().is_empty());
+        assert_eq!(queued_uuids(&drone), vec!["fire"]);
+    }
+
+    #[test]
+    fn test_covering_drone_takes_the_place_of_one_that_withdraws() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0);
+        drone.add_incident(incident("fire", 3.0, 4.0));
+        drone.claim_current_incident();
+        drone.add_claim("fire", claim(2, 1.0, ClaimState::Committed));
+        drone.add_claim("fire", claim(3, 2.0, ClaimState::Committed));
+        assert!(drone.cover_current_incident());
+        assert_eq!(drone.take_over_incident(), None);
+
+        assert_eq!(
+            drone.add_claim("fire", claim(3, 2.0, ClaimState::Withdrawn)),
+            ClaimOutcome::Ignored
+        );
+        assert_eq!(
+            drone.take_over_incident().map(|i| i.uuid),
+            Some("fire".to_string())
+        );
+    }
+
+    #[test]
+    fn test_aborted_drone_keeps_covering_the_incident() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0);
+        drone.add_incident(incident("fire", 3.0, 4.0));
+        drone.handle_event(DroneEvent::IncidentAssigned(incident("fire", 3.0, 4.0)));
+        drone.handle_event(DroneEvent::ClaimWindowClosed("fire".to_string()));
+
+        let closer = claim(2, 1.0, ClaimState::Committed);
+        drone.handle_event(DroneEvent::ClaimReceived(
+            "fire".to_string(),
+            closer.clone(),
+        ));
+        let closer = claim(3, 1.0, ClaimState::Committed);
+        assert_eq!(
+            drone.handle_event(DroneEvent::ClaimReceived("fire".to_string(), closer)),
+            vec![DroneAction::Unsubscribe(
+                IncidentTopic::Attending,
+                "fire".to_string()
+            )]
+        );
+        assert_eq!(drone.current_incident(), None);
+        assert_eq!(drone.covered_incidents().len(), 1);
+    }
+
+    #[test]
+    fn test_covered_incident_is_forgotten_once_attended() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0);
+        drone.add_incident(incident("fire", 3.0, 4.0));
+        drone.claim_current_incident();
+        drone.add_claim("fire", claim(2, 1.0, ClaimState::Committed));
+        drone.add_claim("fire", claim(3, 2.0, ClaimState::Committed));
+        assert!(drone.cover_current_incident());
+
+        let attending = |drone_id| claim(drone_id, 0.0, ClaimState::Attending);
+        assert_eq!(drone.add_claim("fire", attending(2)), ClaimOutcome::Ignored);
+        assert_eq!(
+            drone.add_claim("fire", attending(3)),
+            ClaimOutcome::Forgotten
+        );
+        assert!(drone.covered_incidents().is_empty());
+    }
+
+    #[test]
+    fn test_incidents_say_how_many_drones_they_need() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0).with_drones_per_incident(1);
+        drone.add_incident(incident("fire", 3.0, 4.0).with_required_drones(3));
+        drone.add_incident(incident("flood", 6.0, 8.0));
+        drone.claim_current_incident();
+        drone.add_claim("fire", claim(2, 1.0, ClaimState::Claimed));
+        drone.add_claim("fire", claim(3, 2.0, ClaimState::Claimed));
+        assert!(drone.is_responder());
+
+        drone.remove_current_incident();
+        drone.claim_current_incident();
+        drone.add_claim("flood", claim(2, 1.0, ClaimState::Claimed));
+        assert!(!drone.is_responder());
+    }
+
+    #[test]
+    fn test_sync_forgets_closed_incidents_and_queues_missed_ones() {
+        let mut drone = Drone::new(1, 0.0, 0.0, 0.0, 0.0, 1.0, 10.0);
+        drone.add_incident(incident("fire", 3.0, 4.0));
+        drone.add_incident(incident("flood", 6.0, 8.0));
+        drone.handle_event(DroneEvent::IncidentAssigned(incident("fire", 3.0, 4.0)));
+
+        // The incident it was travelling to was closed while it was disconnected
+        let open = vec![incident("flood", 6.0, 8.0), incident("smoke", 1.0, 0.0)];
+        let actions = drone.handle_event(DroneEvent::IncidentsSynced(open));
+        assert!(actions.contains(&DroneAction::Unsubscribe(
+            IncidentTopic::Claim,
+            "fire".to_string()
+        )));
+        let mut uuids = queued_uuids(&drone);
+        uuids.sort();
+        assert_eq!(uuids, vec!["flood", "smoke"]);
+
+        // The incidents it knows aren't queued again
+        let open = vec![incident("flood", 6.0, 8.0), incident("smoke", 1.0, 0.0)];
+        drone.handle_event(DroneEvent::IncidentsSynced(open));
+        assert_eq!(queued_uuids(&drone).len(), 2);
+    }
 }
@@ -0,0 +1,187 @@
+use std::time::{Duration, Instant};
+
+use common::drone_anchor::DroneAnchor;
+use common::drone_command::{DroneCommand, DroneCommandAck};
+use common::incident::Incident;
+
+use crate::coordination::IncidentClaim;
+use crate::utils::Position;
+
+/// Time between the steps of a drone along its route
+pub const TRAVEL_INTERVAL: Duration = Duration::from_secs(1);
+/// Time the drones that received an incident have to claim it
+pub const CLAIM_WINDOW: Duration = Duration::from_secs(2);
+/// Time a drone spends attending an incident once enough drones arrived
+pub const ATTENDING_DURATION: Duration = Duration::from_secs(10);
+/// Time a drone with an automatic anchor waits for the anchors of the other drones before
+/// choosing its own
+pub const ANCHOR_WINDOW: Duration = Duration::from_secs(2);
+
+/// Represents something that happens to a drone. The drone handles them one at a time,
+/// in the order they arrive
+#[derive(Debug, Clone, PartialEq)]
+pub enum DroneEvent {
+    /// The drone connected to the server after its process started
+    Started,
+    /// The monitor reported an incident
+    IncidentReported(Incident),
+    /// The drone takes the incident first in its queue
+    IncidentAssigned(Incident),
+    /// A drone, which may be this one, claimed an incident the drone follows
+    ClaimReceived(String, IncidentClaim),
+    /// The drones that received the incident had time to claim it
+    ClaimWindowClosed(String),
+    /// The drone arrived at the incident it travelled to
+    ArrivedAtIncident,
+    /// A drone, which may be this one, arrived at the incident the drone handles
+    PeerAttending(String),
+    /// The drone finished attending the incident
+    IncidentAttended(String),
+    /// The monitor closed the incident
+    IncidentClosed(String),
+    /// The monitor answered with the incidents that aren't resolved, after the drone connected
+    IncidentsSynced(Vec<Incident>),
+    /// The drone arrived at its anchor
+    ArrivedAtAnchor,
+    /// The battery is below the minimum level
+    BatteryLow,
+    /// The drone arrived at the charging station
+    ArrivedAtStation,
+    /// The drone arrived at the position an operator sent it to
+    ArrivedAtWaypoint,
+    /// An operator sent a command to the drone
+    Command(DroneCommand),
+    /// The drone moves one step along its route and, if it is free, takes an incident
+    Tick,
+    /// The battery loses the charge used since the last discharge
+    BatteryDischarge,
+    /// The battery charges, if the drone is at the charging station
+    BatteryRecharge,
+    /// The drone checks if its battery is below the minimum level
+    BatteryCheck,
+    /// The battery lost charge all of a sudden
+    BatteryFault(usize),
+    /// The drone checks if it can take the place of a drone going to an incident it gave up
+    ReassignmentCheck,
+    /// A drone, which may be this one, announced its anchor
+    AnchorReceived(u8, Position),
+    /// The drone had time to learn the anchors of the other drones
+    AnchorWindowClosed,
+    /// A drone, which may be this one, published its position
+    PeerMoved(u8, Position),
+    /// A drone lost its connection to the server
+    PeerDisconnected(u8),
+}
+
+/// Represents the topics of an incident the drone follows while it handles it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncidentTopic {
+    Attending,
+    Close,
+    Claim,
+}
+
+/// Represents what the drone has to do after handling an event
+#[derive(Debug, Clone, PartialEq)]
+pub enum DroneAction {
+    Subscribe(IncidentTopic, String),
+    Unsubscribe(IncidentTopic, String),
+    PublishClaim(String, IncidentClaim),
+    /// Tells the drones handling the incident that this one arrived
+    PublishAttending(String),
+    /// Tells the monitor the drone finished attending the incident
+    PublishReady(String),
+    /// Tells the monitor the drone won't attend the incident
+    PublishRejected(String),
+    /// Tells the monitor where the drone goes to recharge
+    PublishChargingStation(Position),
+    PublishCommandAck(DroneCommandAck),
+    /// Tells the monitor and the other drones where the drone waits for incidents
+    PublishAnchor(DroneAnchor),
+    /// Handles the event once the time passes
+    Schedule(DroneEvent, Duration),
+}
+
+/// Keeps the events that happen after some time, some of them again every interval
+#[derive(Debug, Default)]
+pub struct Timers {
+    timers: Vec<(Instant, DroneEvent, Option<Duration>)>,
+}
+
+impl Timers {
+    /// Creates a set of timers without events
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an event that happens once after the delay
+    pub fn schedule(&mut self, event: DroneEvent, delay: Duration, now: Instant) {
+        self.timers.push((now + delay, event, None));
+    }
+
+    /// Adds an event that happens every interval
+    pub fn repeat(&mut self, event: DroneEvent, interval: Duration, now: Instant) {
+        self.timers.push((now + interval, event, Some(interval)));
+    }
+
+    /// Returns when the next event happens
+    pub fn next_due(&self) -> Option<Instant> {
+        self.timers.iter().map(|(due, _, _)| *due).min()
+    }
+
+    /// Removes the events that are due, from the earliest one, scheduling again the ones
+    /// that repeat
+    pub fn take_due(&mut self, now: Instant) -> Vec<DroneEvent> {
+        let mut due = Vec::new();
+        let mut pending = Vec::new();
+        for (instant, event, interval) in self.timers.drain(..) {
+            if instant > now {
+                pending.push((instant, event, interval));
+                continue;
+            }
+            if let Some(interval) = interval {
+                pending.push((instant + interval, event.clone(), Some(interval)));
+            }
+            due.push((instant, event));
+        }
+        self.timers = pending;
+
+        due.sort_by_key(|(instant, _)| *instant);
+        due.into_iter().map(|(_, event)| event).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timers_return_the_due_events_in_order() {
+        let start = Instant::now();
+        let mut timers = Timers::new();
+        timers.repeat(DroneEvent::Tick, Duration::from_secs(1), start);
+        timers.schedule(
+            DroneEvent::ClaimWindowClosed("fire".to_string()),
+            Duration::from_millis(1500),
+            start,
+        );
+        assert_eq!(timers.next_due(), Some(start + Duration::from_secs(1)));
+
+        assert!(timers.take_due(start).is_empty());
+        assert_eq!(
+            timers.take_due(start + Duration::from_secs(1)),
+            vec![DroneEvent::Tick]
+        );
+        assert_eq!(
+            timers.take_due(start + Duration::from_secs(2)),
+            vec![
+                DroneEvent::ClaimWindowClosed("fire".to_string()),
+                DroneEvent::Tick
+            ]
+        );
+        assert_eq!(
+            timers.take_due(start + Duration::from_secs(3)),
+            vec![DroneEvent::Tick]
+        );
+    }
+}
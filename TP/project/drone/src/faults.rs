@@ -0,0 +1,149 @@
+use crate::utils::Position;
+
+/// Represents the faults injected into a drone to exercise how the server and the monitor
+/// handle them. Probabilities are checked every time the drone publishes its data, and every
+/// fault is disabled by default
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultModel {
+    /// Probability of closing the connection to the server, which then publishes the last will
+    /// of the drone until it reconnects
+    pub connection_drop_probability: f64,
+    /// Largest offset added to each coordinate of the position the drone reports
+    pub gps_jitter: f64,
+    /// Probability of the battery losing charge all of a sudden
+    pub battery_fault_probability: f64,
+    /// Battery units lost in a battery fault
+    pub battery_fault_drop: usize,
+    /// Probability of the drone reporting the same data for a while
+    pub freeze_probability: f64,
+    /// Updates of its data during which the drone stays frozen
+    pub freeze_duration: u64,
+    /// Seed of the faults, so a run can be repeated. Without one, every run is different
+    pub seed: Option<u64>,
+}
+
+impl Default for FaultModel {
+    fn default() -> Self {
+        FaultModel {
+            connection_drop_probability: 0.0,
+            gps_jitter: 0.0,
+            battery_fault_probability: 0.0,
+            battery_fault_drop: 30,
+            freeze_probability: 0.0,
+            freeze_duration: 10,
+            seed: None,
+        }
+    }
+}
+
+/// Decides when the faults of a model happen
+#[derive(Debug, Clone)]
+pub struct FaultInjector {
+    model: FaultModel,
+    state: u64,
+    frozen_for: u64,
+}
+
+impl FaultInjector {
+    /// Creates an injector whose faults follow the seed
+    pub fn new(model: FaultModel, seed: u64) -> Self {
+        FaultInjector {
+            model,
+            // The generator never leaves zero
+            state: seed.max(1),
+            frozen_for: 0,
+        }
+    }
+
+    /// Returns a number between 0 and 1, using xorshift
+    fn next(&mut self) -> f64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn happens(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.next() < probability
+    }
+
+    /// Returns true if the drone has to drop its connection now
+    pub fn drops_connection(&mut self) -> bool {
+        self.happens(self.model.connection_drop_probability)
+    }
+
+    /// Returns the battery units the drone loses now, if its battery fails
+    pub fn battery_fault(&mut self) -> Option<usize> {
+        if self.happens(self.model.battery_fault_probability) {
+            return Some(self.model.battery_fault_drop);
+        }
+        None
+    }
+
+    /// Returns the position the drone reports, off by up to the jitter in each coordinate
+    pub fn jitter(&mut self, position: Position) -> Position {
+        if self.model.gps_jitter <= 0.0 {
+            return position;
+        }
+        let mut offset = || (self.next() * 2.0 - 1.0) * self.model.gps_jitter;
+        Position::new(position.x + offset(), position.y + offset())
+    }
+
+    /// Returns true while the data of the drone is frozen. Every call is an update of it
+    pub fn is_frozen(&mut self) -> bool {
+        if self.frozen_for == 0 && self.happens(self.model.freeze_probability) {
+            self.frozen_for = self.model.freeze_duration;
+        }
+        if self.frozen_for == 0 {
+            return false;
+        }
+        self.frozen_for -= 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_faults_never_happen() {
+        let mut faults = FaultInjector::new(FaultModel::default(), 7);
+        for _ in 0..100 {
+            assert!(!faults.drops_connection());
+            assert_eq!(faults.battery_fault(), None);
+            assert!(!faults.is_frozen());
+        }
+        let position = faults.jitter(Position::new(1.0, 2.0));
+        assert_eq!((position.x, position.y), (1.0, 2.0));
+    }
+
+    #[test]
+    fn test_faults_follow_the_model_and_the_seed() {
+        let model = FaultModel {
+            connection_drop_probability: 1.0,
+            gps_jitter: 0.5,
+            battery_fault_probability: 1.0,
+            battery_fault_drop: 30,
+            freeze_probability: 1.0,
+            freeze_duration: 2,
+            seed: None,
+        };
+        let mut faults = FaultInjector::new(model.clone(), 7);
+        assert!(faults.drops_connection());
+        assert_eq!(faults.battery_fault(), Some(30));
+
+        let position = faults.jitter(Position::new(1.0, 2.0));
+        assert!((position.x - 1.0).abs() <= 0.5 && (position.y - 2.0).abs() <= 0.5);
+        assert_ne!(position, Position::new(1.0, 2.0));
+
+        // The same seed repeats the same faults
+        let mut repeated = FaultInjector::new(model, 7);
+        repeated.drops_connection();
+        repeated.battery_fault();
+        assert_eq!(repeated.jitter(Position::new(1.0, 2.0)), position);
+
+        assert!(faults.is_frozen());
+        assert!(faults.is_frozen());
+    }
+}
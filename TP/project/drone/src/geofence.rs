@@ -0,0 +1,51 @@
+use crate::utils::Position;
+
+/// Represents a circular area drones can't fly over
+#[derive(Debug, Clone, Copy)]
+pub struct NoFlyZone {
+    pub center: Position,
+    pub radius: f64,
+}
+
+impl NoFlyZone {
+    /// Creates a new no-fly zone
+    pub fn new(x: f64, y: f64, radius: f64) -> Self {
+        NoFlyZone {
+            center: Position::new(x, y),
+            radius,
+        }
+    }
+
+    /// Returns true if the position is inside the zone
+    pub fn contains(&self, position: &Position) -> bool {
+        self.center.distance_to(position) < self.radius
+    }
+
+    /// Returns true if the straight path between two positions crosses the zone
+    pub fn crosses(&self, from: &Position, to: &Position) -> bool {
+        let (dx, dy) = (to.x - from.x, to.y - from.y);
+        let length = dx * dx + dy * dy;
+        if length == 0.0 {
+            return self.contains(from);
+        }
+
+        let t = (((self.center.x - from.x) * dx + (self.center.y - from.y) * dy) / length)
+            .clamp(0.0, 1.0);
+        let closest = Position::new(from.x + t * dx, from.y + t * dy);
+        self.contains(&closest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zone_crossing() {
+        let zone = NoFlyZone::new(5.0, 0.0, 1.0);
+        assert!(zone.contains(&Position::new(5.5, 0.0)));
+        assert!(zone.crosses(&Position::new(0.0, 0.0), &Position::new(10.0, 0.0)));
+        assert!(!zone.crosses(&Position::new(0.0, 2.0), &Position::new(10.0, 2.0)));
+        assert!(!zone.crosses(&Position::new(0.0, 0.0), &Position::new(3.0, 0.0)));
+    }
+}
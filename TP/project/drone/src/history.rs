@@ -0,0 +1,108 @@
+use std::{collections::VecDeque, fmt};
+
+use common::drone_status::DroneStatus;
+
+use crate::utils::Position;
+
+/// Number of samples kept by default, one is taken every time the drone publishes its data
+pub const DEFAULT_HISTORY_LENGTH: usize = 120;
+
+const SAMPLE_SEPARATOR: char = '|';
+
+/// Represents the telemetry of a drone at some point
+#[derive(Debug, Clone)]
+pub struct TelemetrySample {
+    pub position: Position,
+    pub battery: usize,
+    pub status: DroneStatus,
+    /// Seconds left to reach the destination, while the drone travels
+    pub eta: Option<u64>,
+}
+
+/// Formats the sample as `x;y;status;battery`, followed by `;eta` while the drone travels
+impl fmt::Display for TelemetrySample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{};{};{};{}",
+            self.position.x, self.position.y, self.status, self.battery
+        )?;
+        if let Some(eta) = self.eta {
+            write!(f, ";{}", eta)?;
+        }
+        Ok(())
+    }
+}
+
+/// Keeps the most recent telemetry samples of a drone, dropping the oldest ones
+/// once there are as many as its length
+#[derive(Debug, Clone)]
+pub struct TelemetryHistory {
+    samples: VecDeque<TelemetrySample>,
+    length: usize,
+}
+
+impl TelemetryHistory {
+    /// Creates an empty history that keeps up to the specified number of samples
+    pub fn new(length: usize) -> Self {
+        TelemetryHistory {
+            samples: VecDeque::with_capacity(length),
+            length,
+        }
+    }
+
+    /// Adds a sample, dropping the oldest one if the history is full
+    pub fn record(&mut self, sample: TelemetrySample) {
+        self.samples.push_back(sample);
+        while self.samples.len() > self.length {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Returns the samples from the oldest to the most recent
+    pub fn samples(&self) -> impl Iterator<Item = &TelemetrySample> {
+        self.samples.iter()
+    }
+}
+
+impl Default for TelemetryHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_LENGTH)
+    }
+}
+
+/// Formats the history as the samples in the format of the drone data, separated by `|`
+impl fmt::Display for TelemetryHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let samples: Vec<String> = self.samples().map(TelemetrySample::to_string).collect();
+        write!(f, "{}", samples.join(&SAMPLE_SEPARATOR.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(x: f64, battery: usize) -> TelemetrySample {
+        TelemetrySample {
+            position: Position::new(x, 0.0),
+            battery,
+            status: DroneStatus::Free,
+            eta: None,
+        }
+    }
+
+    #[test]
+    fn test_history_keeps_the_most_recent_samples() {
+        let mut history = TelemetryHistory::new(2);
+        assert_eq!(history.to_string(), "");
+
+        history.record(sample(1.0, 100));
+        history.record(sample(2.0, 99));
+        history.record(sample(3.0, 98));
+
+        let batteries: Vec<usize> = history.samples().map(|sample| sample.battery).collect();
+        assert_eq!(batteries, vec![99, 98]);
+        assert_eq!(history.to_string(), "2;0;0;99|3;0;0;98");
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use mqtt::model::packets::publish::Publish;
+
+/// Keeps the QoS 1 messages published by the drone until the server acknowledges them,
+/// so the ones whose acknowledgement doesn't arrive in time can be sent again
+#[derive(Debug)]
+pub struct InflightPublishes {
+    /// Unacknowledged messages by packet identifier, with the last time they were sent and
+    /// the times they were resent after their timeout
+    publishes: HashMap<u16, (Publish, Instant, u32)>,
+    last_packet_identifier: u16,
+    timeout: Duration,
+    max_inflight: usize,
+    max_resends: u32,
+}
+
+impl InflightPublishes {
+    /// Creates an empty set of messages that are sent again after the specified timeout.
+    /// Unless limited, they are resent until acknowledged and as many as the packet
+    /// identifiers available can be in flight
+    pub fn new(timeout: Duration) -> Self {
+        InflightPublishes {
+            publishes: HashMap::new(),
+            last_packet_identifier: 0,
+            timeout,
+            max_inflight: u16::MAX as usize,
+            max_resends: u32::MAX,
+        }
+    }
+
+    /// Sets how many messages can be in flight at once. There can't be more than the
+    /// packet identifiers available
+    pub fn with_max_inflight(mut self, max_inflight: usize) -> Self {
+        self.max_inflight = max_inflight.clamp(1, u16::MAX as usize);
+        self
+    }
+
+    /// Sets how many times a message is resent after its timeout before giving up on it
+    pub fn with_max_resends(mut self, max_resends: u32) -> Self {
+        self.max_resends = max_resends;
+        self
+    }
+
+    /// Assigns a packet identifier that is not in flight to the message and keeps it
+    /// until it is acknowledged. If there are already too many messages in flight, the one
+    /// sent the longest ago is dropped. Returns the message with its identifier, ready to be sent
+    pub fn track(&mut self, publish: Publish, now: Instant) -> Publish {
+        if self.publishes.len() >= self.max_inflight {
+            let oldest = self
+                .publishes
+                .iter()
+                .min_by_key(|(packet_identifier, (_, sent_at, _))| (*sent_at, **packet_identifier))
+                .map(|(packet_identifier, _)| *packet_identifier);
+            if let Some(packet_identifier) = oldest {
+                println!(
+                    "Too many unacknowledged messages, dropping message {}",
+                    packet_identifier
+                );
+                self.publishes.remove(&packet_identifier);
+            }
+        }
+
+        let packet_identifier = loop {
+            self.last_packet_identifier = self.last_packet_identifier.wrapping_add(1);
+            if self.last_packet_identifier != 0
+                && !self.publishes.contains_key(&self.last_packet_identifier)
+            {
+                break self.last_packet_identifier;
+            }
+        };
+
+        let publish = Publish::new(
+            false,
+            publish.qos().clone(),
+            publish.retain(),
+            publish.topic().clone(),
+            Some(packet_identifier),
+            publish.message().clone(),
+        );
+        self.publishes
+            .insert(packet_identifier, (publish.clone(), now, 0));
+        publish
+    }
+
+    /// Forgets the message acknowledged by the server. Returns false if it wasn't in flight
+    pub fn acknowledge(&mut self, packet_identifier: u16) -> bool {
+        self.publishes.remove(&packet_identifier).is_some()
+    }
+
+    /// Returns the messages that weren't acknowledged before the timeout, marked as duplicates
+    /// and in the order they were sent, and starts their timeout again. The ones already resent
+    /// too many times are dropped instead
+    pub fn take_expired(&mut self, now: Instant) -> Vec<Publish> {
+        let timeout = self.timeout;
+        let max_resends = self.max_resends;
+        self.publishes
+            .retain(|packet_identifier, (_, sent_at, resends)| {
+                let give_up = now.duration_since(*sent_at) >= timeout && *resends >= max_resends;
+                if give_up {
+                    println!(
+                        "Message {} wasn't acknowledged after {} resends, giving up",
+                        packet_identifier, resends
+                    );
+                }
+                !give_up
+            });
+
+        let expired = self.take(now, |sent_at| now.duration_since(sent_at) >= timeout);
+        for publish in &expired {
+            if let Some((_, _, resends)) = publish
+                .package_identifier()
+                .and_then(|packet_identifier| self.publishes.get_mut(&packet_identifier))
+            {
+                *resends += 1;
+            }
+        }
+        expired
+    }
+
+    /// Returns every message in flight marked as duplicate and starts their timeout again.
+    /// They have to be sent after reconnecting, since the server may never have received them
+    pub fn take_all(&mut self, now: Instant) -> Vec<Publish> {
+        self.take(now, |_| true)
+    }
+
+    fn take(&mut self, now: Instant, resend: impl Fn(Instant) -> bool) -> Vec<Publish> {
+        let mut publishes: Vec<(Instant, u16, Publish)> = self
+            .publishes
+            .iter_mut()
+            .filter(|(_, (_, sent_at, _))| resend(*sent_at))
+            .map(|(packet_identifier, (publish, sent_at, _))| {
+                let first_sent_at = *sent_at;
+                *sent_at = now;
+                let duplicate = Publish::new(
+                    true,
+                    publish.qos().clone(),
+                    publish.retain(),
+                    publish.topic().clone(),
+                    Some(*packet_identifier),
+                    publish.message().clone(),
+                );
+                (first_sent_at, *packet_identifier, duplicate)
+            })
+            .collect();
+
+        publishes.sort_by_key(|(sent_at, packet_identifier, _)| (*sent_at, *packet_identifier));
+        publishes
+            .into_iter()
+            .map(|(_, _, publish)| publish)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mqtt::model::components::{qos::QoS, topic_name::TopicName};
+
+    fn publish(topic: &str) -> Publish {
+        Publish::new(
+            false,
+            QoS::AtLeast,
+            true,
+            TopicName::new(vec![topic.as_bytes().to_vec()], false),
+            None,
+            b"".to_vec(),
+        )
+    }
+
+    #[test]
+    fn test_tracked_messages_get_different_identifiers() {
+        let mut inflight = InflightPublishes::new(Duration::from_secs(5));
+        let now = Instant::now();
+
+        let first = inflight.track(publish("attending-incident"), now);
+        let second = inflight.track(publish("ready-incident"), now);
+
+        assert_eq!(first.package_identifier(), Some(1));
+        assert_eq!(second.package_identifier(), Some(2));
+
+        assert!(inflight.acknowledge(1));
+        assert!(!inflight.acknowledge(1));
+        assert_eq!(inflight.take_all(now).len(), 1);
+    }
+
+    #[test]
+    fn test_unacknowledged_messages_are_resent_as_duplicates_after_the_timeout() {
+        let mut inflight = InflightPublishes::new(Duration::from_secs(5));
+        let now = Instant::now();
+        inflight.track(publish("attending-incident"), now);
+        inflight.track(publish("ready-incident"), now + Duration::from_secs(1));
+
+        assert!(inflight
+            .take_expired(now + Duration::from_secs(4))
+            .is_empty());
+
+        let expired = inflight.take_expired(now + Duration::from_secs(5));
+        assert_eq!(expired.len(), 1);
+        assert!(expired[0].dup());
+        assert_eq!(expired[0].package_identifier(), Some(1));
+
+        // The timeout starts again once it is resent
+        let expired = inflight.take_expired(now + Duration::from_secs(6));
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].package_identifier(), Some(2));
+
+        inflight.acknowledge(1);
+        inflight.acknowledge(2);
+        assert!(inflight
+            .take_expired(now + Duration::from_secs(60))
+            .is_empty());
+        assert!(inflight.take_all(now).is_empty());
+    }
+
+    #[test]
+    fn test_every_message_in_flight_is_resent_after_reconnecting() {
+        let mut inflight = InflightPublishes::new(Duration::from_secs(5));
+        let now = Instant::now();
+        inflight.track(publish("attending-incident"), now);
+        inflight.track(publish("ready-incident"), now);
+
+        let identifiers: Vec<Option<u16>> = inflight
+            .take_all(now)
+            .iter()
+            .map(|publish| publish.package_identifier())
+            .collect();
+        assert_eq!(identifiers, vec![Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn test_the_oldest_message_is_dropped_when_too_many_are_in_flight() {
+        let mut inflight = InflightPublishes::new(Duration::from_secs(5)).with_max_inflight(2);
+        let now = Instant::now();
+        inflight.track(publish("attending-incident"), now);
+        inflight.track(publish("ready-incident"), now + Duration::from_secs(1));
+        inflight.track(publish("drone-data"), now + Duration::from_secs(2));
+
+        let identifiers: Vec<Option<u16>> = inflight
+            .take_all(now)
+            .iter()
+            .map(|publish| publish.package_identifier())
+            .collect();
+        assert_eq!(identifiers, vec![Some(2), Some(3)]);
+    }
+
+    #[test]
+    fn test_messages_are_given_up_after_too_many_resends() {
+        let mut inflight = InflightPublishes::new(Duration::from_secs(5)).with_max_resends(2);
+        let now = Instant::now();
+        inflight.track(publish("attending-incident"), now);
+
+        assert_eq!(inflight.take_expired(now + Duration::from_secs(5)).len(), 1);
+        assert_eq!(
+            inflight.take_expired(now + Duration::from_secs(10)).len(),
+            1
+        );
+        assert!(inflight
+            .take_expired(now + Duration::from_secs(15))
+            .is_empty());
+        assert!(!inflight.acknowledge(1));
+    }
+
+    #[test]
+    fn test_identifiers_are_never_exhausted() {
+        let mut inflight = InflightPublishes::new(Duration::from_secs(5));
+        let now = Instant::now();
+        for _ in 0..u16::MAX as usize + 10 {
+            inflight.track(publish("drone-data"), now);
+        }
+        assert_eq!(inflight.take_all(now).len(), u16::MAX as usize);
+    }
+}
@@ -1,2 +1,17 @@
+pub mod anchor;
+pub mod battery;
+pub mod connection;
+pub mod control;
+pub mod coordination;
 pub mod drone;
+pub mod event;
+pub mod faults;
+pub mod geofence;
+pub mod history;
+pub mod inflight;
+pub mod motion;
+pub mod separation;
+pub mod state;
+pub mod telemetry;
 pub mod utils;
+pub mod watchdog;
@@ -1,15 +1,37 @@
 //! The drone system is a program that simulates a single drone. It recieves messages from the monitor and
-//! reacts to them depending on the situation.
+//! reacts to them depending on the situation. A config with a fleet simulates several drones at once,
+//! each of them with its own connection to the server.
 
 use common::error::Error;
 use config::Config;
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    flag,
+};
 use std::env::args;
 use std::path::Path;
+use std::sync::{atomic::AtomicBool, Arc};
+use std::thread;
 
+mod anchor;
+mod battery;
 mod client;
 mod config;
+mod connection;
+mod control;
+mod coordination;
 mod drone;
+mod event;
+mod faults;
+mod geofence;
+mod history;
+mod inflight;
+mod motion;
+mod separation;
+mod state;
+mod telemetry;
 mod utils;
+mod watchdog;
 
 static CLIENT_ARGS: usize = 2;
 
@@ -32,9 +54,44 @@ fn main() -> Result<(), Error> {
         }
     };
 
-    if let Err(e) = client::client_run(config) {
-        return Err(Error::new(format!("Error running client: {:?}", e)));
+    // Ctrl-C lets the drone disconnect cleanly. A second one terminates it right away
+    let shutdown = Arc::new(AtomicBool::new(false));
+    for signal in [SIGINT, SIGTERM] {
+        let registered = flag::register_conditional_shutdown(signal, 1, shutdown.clone())
+            .and_then(|_| flag::register(signal, shutdown.clone()));
+        if let Err(e) = registered {
+            return Err(Error::new(format!("Error handling signals: {:?}", e)));
+        }
+    }
+
+    let handles: Vec<_> = config
+        .get_fleet()
+        .into_iter()
+        .map(|config| {
+            let shutdown = shutdown.clone();
+            let id = config.get_id();
+            (
+                id,
+                thread::spawn(move || client::client_run(config, shutdown)),
+            )
+        })
+        .collect();
+
+    // A drone that fails doesn't stop the rest of the fleet
+    let mut result = Ok(());
+    for (id, handle) in handles {
+        match handle.join() {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                eprintln!("Error running drone {}: {:?}", id, e);
+                result = Err(Error::new(format!("Error running client: {:?}", e)));
+            }
+            Err(_) => {
+                eprintln!("Drone {} panicked", id);
+                result = Err(Error::new(format!("Error running drone {}", id)));
+            }
+        }
     }
 
-    Ok(())
+    result
 }
@@ -0,0 +1,102 @@
+/// Represents how fast a drone moves along its route, in the units of the coordinates per
+/// step. Without an acceleration the drone always moves at its maximum speed. With one, it
+/// speeds up when it departs and slows down to arrive
+#[derive(Debug, Clone, PartialEq)]
+pub struct Motion {
+    max_speed: f64,
+    acceleration: Option<f64>,
+    speed: f64,
+}
+
+impl Motion {
+    /// Creates the motion of a drone that is stopped
+    pub fn new(max_speed: f64, acceleration: Option<f64>) -> Self {
+        Motion {
+            max_speed,
+            acceleration,
+            speed: 0.0,
+        }
+    }
+
+    /// Returns the speed of the drone without acceleration
+    pub fn max_speed(&self) -> f64 {
+        self.max_speed
+    }
+
+    /// Changes the maximum speed, keeping the current one below it
+    pub fn set_max_speed(&mut self, max_speed: f64) {
+        self.max_speed = max_speed;
+        self.speed = self.speed.min(max_speed);
+    }
+
+    /// Stops the drone, which starts from zero in its next trip
+    pub fn stop(&mut self) {
+        self.speed = 0.0;
+    }
+
+    /// Returns the distance the drone moves in its next step, given the distance it has left.
+    /// It slows down once it needs the rest of the distance to stop, but never below one step
+    /// of acceleration, so it always arrives
+    pub fn step(&mut self, remaining: f64) -> f64 {
+        self.speed = match self.acceleration {
+            None => self.max_speed,
+            Some(acceleration) => {
+                let braking_distance = self.speed * self.speed / (2.0 * acceleration);
+                if remaining <= braking_distance {
+                    (self.speed - acceleration).max(acceleration)
+                } else {
+                    (self.speed + acceleration).min(self.max_speed)
+                }
+            }
+        };
+        self.speed
+    }
+
+    /// Returns the steps the drone needs to travel the distance from its current speed
+    pub fn steps_to(&self, distance: f64) -> Option<u32> {
+        if self.max_speed <= 0.0 {
+            return None;
+        }
+        if self.acceleration.is_none() {
+            return Some((distance / self.max_speed).ceil() as u32);
+        }
+
+        let mut motion = self.clone();
+        let mut remaining = distance;
+        let mut steps = 0;
+        while remaining > 0.0 {
+            remaining -= motion.step(remaining);
+            steps += 1;
+        }
+        Some(steps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drone_without_acceleration_moves_at_its_maximum_speed() {
+        let mut motion = Motion::new(2.0, None);
+        assert_eq!(motion.step(10.0), 2.0);
+        assert_eq!(motion.step(1.0), 2.0);
+        assert_eq!(motion.steps_to(5.0), Some(3));
+        assert_eq!(Motion::new(0.0, None).steps_to(5.0), None);
+    }
+
+    #[test]
+    fn test_drone_speeds_up_and_slows_down() {
+        let mut motion = Motion::new(3.0, Some(1.0));
+        assert_eq!(motion.steps_to(12.0), Some(6));
+
+        let steps: Vec<f64> = [12.0, 11.0, 9.0, 6.0, 3.0, 1.0]
+            .iter()
+            .map(|remaining| motion.step(*remaining))
+            .collect();
+        assert_eq!(steps, vec![1.0, 2.0, 3.0, 3.0, 2.0, 1.0]);
+
+        motion.stop();
+        assert_eq!(motion.step(12.0), 1.0);
+    }
+}
@@ -0,0 +1,114 @@
+use common::{coordenate::Coordenate, spatial_grid::SpatialGrid};
+
+use crate::utils::Position;
+
+/// Represents the minimum distance a drone keeps from the other drones while it travels,
+/// given the last positions they published
+#[derive(Debug, Clone)]
+pub struct Separation {
+    distance: f64,
+    /// Positions of the other drones, in cells as big as the separation distance
+    peers: SpatialGrid<u8>,
+}
+
+impl Separation {
+    /// Creates a separation that doesn't know the positions of the other drones yet
+    pub fn new(distance: f64) -> Self {
+        Separation {
+            distance,
+            peers: SpatialGrid::new(distance),
+        }
+    }
+
+    /// Returns the minimum distance between the drones
+    pub fn distance(&self) -> f64 {
+        self.distance
+    }
+
+    /// Records the position of another drone
+    pub fn update_peer(&mut self, peer_id: u8, position: Position) {
+        self.peers.insert(peer_id, coordenate(&position));
+    }
+
+    /// Forgets another drone, as it lost its connection
+    pub fn remove_peer(&mut self, peer_id: u8) {
+        self.peers.remove(&peer_id);
+    }
+
+    /// Returns the position pushed away from the drones closer than the separation distance.
+    /// Two drones on top of each other, as the ones that head to the same incident from the
+    /// same direction, move to opposite sides of their heading, decided by their ids
+    pub fn avoid(&self, drone_id: u8, position: Position, heading: f64) -> Position {
+        let mut avoided = position;
+        for peer_id in self.peers.within(&coordenate(&position), self.distance) {
+            if peer_id == drone_id {
+                continue;
+            }
+            let peer = match self.peers.position(&peer_id) {
+                Some(peer) => Position::new(peer.x_coordinate, peer.y_coordinate),
+                None => continue,
+            };
+            let distance = position.distance_to(&peer);
+
+            let push = self.distance - distance;
+            let angle = if distance > 0.0 {
+                peer.angle_to(&position)
+            } else if drone_id < peer_id {
+                heading + std::f64::consts::FRAC_PI_2
+            } else {
+                heading - std::f64::consts::FRAC_PI_2
+            };
+            avoided.x += angle.cos() * push;
+            avoided.y += angle.sin() * push;
+        }
+        avoided
+    }
+}
+
+/// Returns a position as the coordenates the grid keeps
+fn coordenate(position: &Position) -> Coordenate {
+    Coordenate {
+        x_coordinate: position.x,
+        y_coordinate: position.y,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drones_keep_their_distance() {
+        let mut separation = Separation::new(1.0);
+        separation.update_peer(2, Position::new(0.0, 0.5));
+
+        let avoided = separation.avoid(1, Position::new(0.0, 0.0), 0.0);
+        assert!((avoided.distance_to(&Position::new(0.0, 0.5)) - 1.0).abs() < 1e-9);
+        // Drones far enough away don't matter, nor does the position of the drone itself
+        assert_eq!(
+            separation.avoid(2, Position::new(0.0, 0.0), 0.0),
+            Position::new(0.0, 0.0)
+        );
+        assert_eq!(
+            separation.avoid(1, Position::new(5.0, 0.0), 0.0),
+            Position::new(5.0, 0.0)
+        );
+
+        separation.remove_peer(2);
+        assert_eq!(
+            separation.avoid(1, Position::new(0.0, 0.0), 0.0),
+            Position::new(0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_drones_on_top_of_each_other_move_to_opposite_sides() {
+        let mut separation = Separation::new(1.0);
+        separation.update_peer(1, Position::new(0.0, 0.0));
+        separation.update_peer(2, Position::new(0.0, 0.0));
+
+        let first = separation.avoid(1, Position::new(0.0, 0.0), 0.0);
+        let second = separation.avoid(2, Position::new(0.0, 0.0), 0.0);
+        assert!(first.y > 0.99 && second.y < -0.99);
+    }
+}
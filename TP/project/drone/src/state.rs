@@ -0,0 +1,145 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+};
+
+use common::{drone_status::DroneStatus, incident::Incident};
+
+use crate::utils::Position;
+
+const SEPARATOR: char = ';';
+const POSITION: &str = "position";
+const BATTERY: &str = "battery";
+const STATUS: &str = "status";
+const INCIDENT: &str = "incident";
+const TEMP_FILE_EXTENSION: &str = "tmp";
+
+/// Represents what a drone needs to resume where it was after its process restarts
+#[derive(Debug, Clone)]
+pub struct DroneState {
+    pub position: Position,
+    pub battery: usize,
+    pub status: DroneStatus,
+    pub incident_queue: Vec<Incident>,
+}
+
+impl DroneState {
+    /// Parses the state from lines of `field;value`, with a line for each queued incident
+    pub fn from_string(state: &str) -> io::Result<Self> {
+        let invalid =
+            |field: &str| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid {}", field));
+
+        let mut position = None;
+        let mut battery = None;
+        let mut status = None;
+        let mut incident_queue = Vec::new();
+
+        for line in state.lines().filter(|line| !line.is_empty()) {
+            let (field, value) = line
+                .split_once(SEPARATOR)
+                .ok_or_else(|| invalid("state line"))?;
+            match field {
+                POSITION => {
+                    let (x, y) = value
+                        .split_once(SEPARATOR)
+                        .and_then(|(x, y)| Some((x.parse().ok()?, y.parse().ok()?)))
+                        .ok_or_else(|| invalid(POSITION))?;
+                    position = Some(Position::new(x, y));
+                }
+                BATTERY => battery = Some(value.parse().map_err(|_| invalid(BATTERY))?),
+                STATUS => status = Some(DroneStatus::get_status_from_str(value)),
+                INCIDENT => incident_queue
+                    .push(Incident::from_string(value.to_string()).map_err(|_| invalid(INCIDENT))?),
+                _ => return Err(invalid("state field")),
+            }
+        }
+
+        Ok(DroneState {
+            position: position.ok_or_else(|| invalid(POSITION))?,
+            battery: battery.ok_or_else(|| invalid(BATTERY))?,
+            status: status.ok_or_else(|| invalid(STATUS))?,
+            incident_queue,
+        })
+    }
+
+    /// Writes the state to a file. It goes to a temporary file that replaces the previous state
+    /// once it is written, so a crash while saving leaves the last state untouched
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let temp_path = path.with_extension(TEMP_FILE_EXTENSION);
+        let mut file = File::create(&temp_path)?;
+        file.write_all(self.to_string().as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    }
+
+    /// Reads the state from a file
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::from_string(&fs::read_to_string(path)?)
+    }
+}
+
+impl fmt::Display for DroneState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}{}{}{}{}",
+            POSITION, SEPARATOR, self.position.x, SEPARATOR, self.position.y
+        )?;
+        writeln!(f, "{}{}{}", BATTERY, SEPARATOR, self.battery)?;
+        writeln!(f, "{}{}{}", STATUS, SEPARATOR, self.status)?;
+        for incident in &self.incident_queue {
+            writeln!(f, "{}{}{}", INCIDENT, SEPARATOR, incident)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{drone_status::TravelLocation, incident::IncidentStatus};
+    use std::{env, process};
+
+    fn state() -> DroneState {
+        DroneState {
+            position: Position::new(-58.37, -34.61),
+            battery: 42,
+            status: DroneStatus::Travelling(TravelLocation::Incident),
+            incident_queue: vec![Incident::new(
+                "1234".to_string(),
+                "Fire".to_string(),
+                "Big fire".to_string(),
+                -58.36,
+                -34.6,
+                IncidentStatus::Pending,
+            )
+            .with_required_drones(3)],
+        }
+    }
+
+    #[test]
+    fn test_state_survives_a_restart() {
+        let path = env::temp_dir().join(format!("drone-state-{}.state", process::id()));
+        state().save(&path).unwrap();
+        let restored = DroneState::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(restored.position.x, -58.37);
+        assert_eq!(restored.position.y, -34.61);
+        assert_eq!(restored.battery, 42);
+        assert_eq!(
+            restored.status,
+            DroneStatus::Travelling(TravelLocation::Incident)
+        );
+        assert_eq!(restored.incident_queue, state().incident_queue);
+    }
+
+    #[test]
+    fn test_incomplete_state_is_rejected() {
+        assert!(DroneState::from_string("position;1;2\nstatus;0\n").is_err());
+        assert!(DroneState::from_string("position;1\nbattery;5\nstatus;0\n").is_err());
+        assert!(DroneState::from_string("position;1;2\nbattery;5\nstatus;0\nspeed;3\n").is_err());
+    }
+}
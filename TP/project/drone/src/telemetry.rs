@@ -0,0 +1,102 @@
+use crate::history::TelemetrySample;
+
+/// Represents how much the telemetry of a drone has to change before it is published again.
+/// A drone idle at its anchor reports the same data every update, so most of it is skipped
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublishThresholds {
+    /// Distance the drone has to move, in the units of the coordinates
+    pub position: f64,
+    /// Battery units the drone has to gain or lose
+    pub battery: usize,
+    /// Updates after which the telemetry is published even if it didn't change, so the
+    /// monitor knows the drone is still there
+    pub heartbeat: u64,
+}
+
+impl Default for PublishThresholds {
+    fn default() -> Self {
+        PublishThresholds {
+            position: 0.0,
+            battery: 1,
+            heartbeat: 10,
+        }
+    }
+}
+
+/// Decides which telemetry samples of a drone are worth publishing
+#[derive(Debug, Clone)]
+pub struct TelemetryFilter {
+    thresholds: PublishThresholds,
+    last_published: Option<TelemetrySample>,
+    skipped: u64,
+}
+
+impl TelemetryFilter {
+    /// Creates a filter that publishes the first sample it gets
+    pub fn new(thresholds: PublishThresholds) -> Self {
+        TelemetryFilter {
+            thresholds,
+            last_published: None,
+            skipped: 0,
+        }
+    }
+
+    /// Returns true if the sample has to be published: the status changed, the position or
+    /// the battery changed beyond their thresholds, or it's time for the heartbeat. Every
+    /// call is an update of the telemetry
+    pub fn should_publish(&mut self, sample: &TelemetrySample) -> bool {
+        let changed = match &self.last_published {
+            None => true,
+            Some(last) => {
+                last.status != sample.status
+                    || last.position.distance_to(&sample.position) > self.thresholds.position
+                    || last.battery.abs_diff(sample.battery) >= self.thresholds.battery
+            }
+        };
+
+        if changed || self.skipped + 1 >= self.thresholds.heartbeat {
+            self.last_published = Some(sample.clone());
+            self.skipped = 0;
+            return true;
+        }
+        self.skipped += 1;
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Position;
+    use common::drone_status::DroneStatus;
+
+    fn sample(x: f64, battery: usize, status: DroneStatus) -> TelemetrySample {
+        TelemetrySample {
+            position: Position::new(x, 0.0),
+            battery,
+            status,
+            eta: None,
+        }
+    }
+
+    #[test]
+    fn test_only_changes_and_heartbeats_are_published() {
+        let mut filter = TelemetryFilter::new(PublishThresholds {
+            position: 0.5,
+            battery: 5,
+            heartbeat: 3,
+        });
+        let idle = sample(0.0, 100, DroneStatus::Free);
+
+        assert!(filter.should_publish(&idle));
+        assert!(!filter.should_publish(&idle));
+        assert!(!filter.should_publish(&sample(0.4, 97, DroneStatus::Free)));
+        // The third update without changes is the heartbeat
+        assert!(filter.should_publish(&idle));
+
+        assert!(filter.should_publish(&sample(1.0, 100, DroneStatus::Free)));
+        assert!(filter.should_publish(&sample(1.0, 95, DroneStatus::Free)));
+        assert!(filter.should_publish(&sample(1.0, 95, DroneStatus::Recharging)));
+        assert!(!filter.should_publish(&sample(1.0, 95, DroneStatus::Recharging)));
+    }
+}
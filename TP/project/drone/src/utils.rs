@@ -1,5 +1,16 @@
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::fmt;
+
+use crate::geofence::NoFlyZone;
+
+/// Fraction of the radius of a zone the routes keep away from its border
+const ZONE_MARGIN: f64 = 0.1;
+/// Number of waypoints placed around each zone to go around it
+const WAYPOINTS_PER_ZONE: usize = 8;
+
 /// Represents a position in the 2D space
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Position {
     pub x: f64,
     pub y: f64,
@@ -28,3 +39,208 @@ impl Position {
         self.y += angle.sin() * distance;
     }
 }
+
+/// Represents the route a drone follows to reach a destination,
+/// as the waypoints it travels between in straight lines
+#[derive(Debug, Clone)]
+pub struct Path {
+    waypoints: VecDeque<Position>,
+}
+
+impl Path {
+    /// Plans the shortest route between two positions that doesn't cross the no-fly zones,
+    /// going through waypoints placed around them. Zones the start or the destination are
+    /// inside of are ignored, so a drone can always leave a zone and reach a point inside one.
+    /// If the zones leave no way through, the route goes straight to the destination
+    pub fn plan(from: Position, to: Position, zones: &[NoFlyZone]) -> Self {
+        let obstacles: Vec<&NoFlyZone> = zones
+            .iter()
+            .filter(|zone| !zone.contains(&from) && !zone.contains(&to))
+            .collect();
+        let is_clear =
+            |a: &Position, b: &Position| !obstacles.iter().any(|zone| zone.crosses(a, b));
+
+        if is_clear(&from, &to) {
+            return Path::straight(to);
+        }
+
+        // Visibility graph: the start, the destination and the waypoints around each zone,
+        // joined when the straight line between them is clear
+        let mut nodes = vec![from, to];
+        for zone in &obstacles {
+            nodes.extend(
+                waypoints_around(zone)
+                    .into_iter()
+                    .filter(|waypoint| !zones.iter().any(|zone| zone.contains(waypoint))),
+            );
+        }
+
+        let mut distances = vec![f64::INFINITY; nodes.len()];
+        let mut previous = vec![None; nodes.len()];
+        let mut visited = vec![false; nodes.len()];
+        distances[0] = 0.0;
+
+        while let Some(current) = (0..nodes.len())
+            .filter(|&node| !visited[node] && distances[node].is_finite())
+            .min_by(|&a, &b| distances[a].total_cmp(&distances[b]))
+        {
+            if current == 1 {
+                break;
+            }
+            visited[current] = true;
+
+            for next in 0..nodes.len() {
+                if visited[next] || !is_clear(&nodes[current], &nodes[next]) {
+                    continue;
+                }
+                let distance = distances[current] + nodes[current].distance_to(&nodes[next]);
+                if distance < distances[next] {
+                    distances[next] = distance;
+                    previous[next] = Some(current);
+                }
+            }
+        }
+
+        let mut waypoints = VecDeque::new();
+        let mut node = 1;
+        while let Some(previous_node) = previous[node] {
+            waypoints.push_front(nodes[node]);
+            node = previous_node;
+        }
+
+        if waypoints.is_empty() {
+            return Path::straight(to);
+        }
+        Path { waypoints }
+    }
+
+    /// Creates a route that goes straight to the destination
+    pub fn straight(to: Position) -> Self {
+        Path {
+            waypoints: VecDeque::from([to]),
+        }
+    }
+
+    /// Returns the last point of the route
+    pub fn destination(&self) -> Option<&Position> {
+        self.waypoints.back()
+    }
+
+    /// Returns the waypoints left to reach the destination
+    pub fn waypoints(&self) -> impl Iterator<Item = &Position> {
+        self.waypoints.iter()
+    }
+
+    /// Returns the distance left from the position to the destination, through the waypoints
+    pub fn remaining_distance(&self, position: &Position) -> f64 {
+        let mut from = *position;
+        let mut distance = 0.0;
+        for waypoint in self.waypoints() {
+            distance += from.distance_to(waypoint);
+            from = *waypoint;
+        }
+        distance
+    }
+
+    /// Returns true if there are no waypoints left
+    pub fn is_finished(&self) -> bool {
+        self.waypoints.is_empty()
+    }
+
+    /// Moves the position one step along the route, dropping the waypoints it reaches
+    pub fn advance(&mut self, position: &mut Position, velocity: f64) {
+        let waypoint = match self.waypoints.front() {
+            Some(waypoint) => *waypoint,
+            None => return,
+        };
+
+        if position.distance_to(&waypoint) <= velocity {
+            *position = waypoint;
+            self.waypoints.pop_front();
+        } else {
+            position.move_towards(&waypoint, velocity);
+        }
+    }
+}
+
+impl fmt::Display for Path {
+    /// Formats the waypoints as `x;y` separated by `|`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let waypoints: Vec<String> = self
+            .waypoints()
+            .map(|waypoint| format!("{};{}", waypoint.x, waypoint.y))
+            .collect();
+        write!(f, "{}", waypoints.join("|"))
+    }
+}
+
+/// Returns the corners of a polygon around the zone whose sides stay out of it
+fn waypoints_around(zone: &NoFlyZone) -> Vec<Position> {
+    let step = 2.0 * PI / WAYPOINTS_PER_ZONE as f64;
+    let radius = zone.radius * (1.0 + ZONE_MARGIN) / (step / 2.0).cos();
+    (0..WAYPOINTS_PER_ZONE)
+        .map(|corner| {
+            let angle = step * corner as f64;
+            Position::new(
+                zone.center.x + radius * angle.cos(),
+                zone.center.y + radius * angle.sin(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn follow(mut path: Path, from: Position, velocity: f64, zones: &[NoFlyZone]) -> Position {
+        let mut position = from;
+        let mut steps = 0;
+        while !path.is_finished() {
+            path.advance(&mut position, velocity);
+            assert!(!zones.iter().any(|zone| zone.contains(&position)));
+            steps += 1;
+            assert!(steps < 1000);
+        }
+        position
+    }
+
+    #[test]
+    fn test_clear_route_is_straight() {
+        let zones = [NoFlyZone::new(5.0, 5.0, 1.0)];
+        let path = Path::plan(Position::new(0.0, 0.0), Position::new(10.0, 0.0), &zones);
+        assert_eq!(path.to_string(), "10;0");
+    }
+
+    #[test]
+    fn test_route_goes_around_zones() {
+        let zones = [
+            NoFlyZone::new(5.0, 0.0, 1.0),
+            NoFlyZone::new(10.0, 1.0, 1.5),
+        ];
+        let from = Position::new(0.0, 0.0);
+        let to = Position::new(15.0, 0.0);
+        let path = Path::plan(from, to, &zones);
+        assert!(path.waypoints().count() > 1);
+
+        let mut position = from;
+        for waypoint in path.waypoints() {
+            assert!(!zones.iter().any(|zone| zone.crosses(&position, waypoint)));
+            position = *waypoint;
+        }
+
+        let end = follow(path, from, 0.25, &zones);
+        assert_eq!((end.x, end.y), (15.0, 0.0));
+    }
+
+    #[test]
+    fn test_route_leaves_and_enters_zones() {
+        let zones = [NoFlyZone::new(0.0, 0.0, 1.0)];
+
+        let path = Path::plan(Position::new(0.0, 0.0), Position::new(5.0, 0.0), &zones);
+        assert_eq!(path.to_string(), "5;0");
+
+        let path = Path::plan(Position::new(5.0, 0.0), Position::new(0.0, 0.0), &zones);
+        assert_eq!(path.to_string(), "0;0");
+    }
+}
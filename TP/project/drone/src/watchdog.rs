@@ -0,0 +1,219 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, AtomicU8, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const RUNNING: u8 = 0;
+const EXITED: u8 = 1;
+const PANICKED: u8 = 2;
+
+/// Represents what went wrong with a worker thread
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkerProblem {
+    /// The worker didn't make progress for longer than its timeout
+    Stalled(Duration),
+    /// The worker returned while the drone wasn't shutting down, as it does when it finds
+    /// a poisoned mutex
+    Exited,
+    /// The worker panicked
+    Panicked,
+}
+
+impl fmt::Display for WorkerProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WorkerProblem::Stalled(since) => write!(f, "stalled for {:?}", since),
+            WorkerProblem::Exited => write!(f, "exited"),
+            WorkerProblem::Panicked => write!(f, "panicked"),
+        }
+    }
+}
+
+/// The progress of a worker. It uses atomics instead of a mutex so a worker that panics
+/// can't poison it
+#[derive(Debug)]
+struct Progress {
+    /// Milliseconds since the watchdog started, when the worker last made progress
+    last_beat: AtomicU64,
+    state: AtomicU8,
+}
+
+/// Lets a worker thread tell the watchdog it is making progress. When the worker ends, the
+/// heartbeat is dropped and the watchdog knows if it returned or panicked
+#[derive(Debug)]
+pub struct Heartbeat {
+    progress: Arc<Progress>,
+    started: Instant,
+}
+
+impl Heartbeat {
+    /// Tells the watchdog the worker made progress
+    pub fn beat(&self) {
+        let elapsed = self.started.elapsed().as_millis() as u64;
+        self.progress.last_beat.store(elapsed, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        let state = if thread::panicking() {
+            PANICKED
+        } else {
+            EXITED
+        };
+        self.progress.state.store(state, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug)]
+struct Worker {
+    name: String,
+    timeout: Option<Duration>,
+    progress: Arc<Progress>,
+}
+
+/// Watches the heartbeats of the worker threads of a drone, to notice the ones that stop
+/// making progress
+#[derive(Debug)]
+pub struct Watchdog {
+    workers: Vec<Worker>,
+    started: Instant,
+}
+
+impl Default for Watchdog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Watchdog {
+    /// Creates a watchdog without workers
+    pub fn new() -> Self {
+        Watchdog {
+            workers: Vec::new(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Registers a worker, which has to beat at least once every timeout. A worker without
+    /// a timeout, like one that blocks waiting for packets, is only watched for ending
+    pub fn register(&mut self, name: &str, timeout: Option<Duration>) -> Heartbeat {
+        let progress = Arc::new(Progress {
+            last_beat: AtomicU64::new(self.started.elapsed().as_millis() as u64),
+            state: AtomicU8::new(RUNNING),
+        });
+        self.workers.push(Worker {
+            name: name.to_string(),
+            timeout,
+            progress: progress.clone(),
+        });
+        Heartbeat {
+            progress,
+            started: self.started,
+        }
+    }
+
+    /// Returns the time since the worker last made progress
+    fn since_last_beat(&self, worker: &Worker, now: Instant) -> Duration {
+        let last_beat =
+            self.started + Duration::from_millis(worker.progress.last_beat.load(Ordering::Relaxed));
+        now.saturating_duration_since(last_beat)
+    }
+
+    /// Returns the workers that have a problem. While the drone shuts down the workers are
+    /// expected to end, but not to stall or panic
+    pub fn check(&self, now: Instant, shutting_down: bool) -> Vec<(&str, WorkerProblem)> {
+        self.workers
+            .iter()
+            .filter_map(|worker| {
+                let problem = match worker.progress.state.load(Ordering::Relaxed) {
+                    PANICKED => WorkerProblem::Panicked,
+                    EXITED if shutting_down => return None,
+                    EXITED => WorkerProblem::Exited,
+                    _ => {
+                        let since = self.since_last_beat(worker, now);
+                        match worker.timeout {
+                            Some(timeout) if since > timeout => WorkerProblem::Stalled(since),
+                            _ => return None,
+                        }
+                    }
+                };
+                Some((worker.name.as_str(), problem))
+            })
+            .collect()
+    }
+
+    /// Returns a line per worker with its state and the time since it last made progress
+    pub fn report(&self, now: Instant) -> String {
+        self.workers
+            .iter()
+            .map(|worker| {
+                let state = match worker.progress.state.load(Ordering::Relaxed) {
+                    PANICKED => "panicked",
+                    EXITED => "exited",
+                    _ => "running",
+                };
+                format!(
+                    "{}: {}, last progress {:?} ago",
+                    worker.name,
+                    state,
+                    self.since_last_beat(worker, now)
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watchdog_notices_stalled_workers() {
+        let mut watchdog = Watchdog::new();
+        let heartbeat = watchdog.register("update", Some(Duration::from_secs(5)));
+        let _reader = watchdog.register("reader", None);
+
+        let now = Instant::now();
+        assert!(watchdog.check(now, false).is_empty());
+
+        let later = now + Duration::from_secs(10);
+        let problems = watchdog.check(later, false);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].0, "update");
+        assert!(matches!(problems[0].1, WorkerProblem::Stalled(_)));
+
+        heartbeat.beat();
+        assert!(watchdog.check(Instant::now(), false).is_empty());
+        assert!(watchdog.report(later).contains("update: running"));
+    }
+
+    #[test]
+    fn test_watchdog_notices_workers_that_end() {
+        let mut watchdog = Watchdog::new();
+        let heartbeat = watchdog.register("writer", Some(Duration::from_secs(5)));
+        drop(heartbeat);
+        let now = Instant::now();
+        assert_eq!(
+            watchdog.check(now, false),
+            vec![("writer", WorkerProblem::Exited)]
+        );
+        assert!(watchdog.check(now, true).is_empty());
+
+        let heartbeat = watchdog.register("state machine", Some(Duration::from_secs(5)));
+        let worker = thread::spawn(move || {
+            let _heartbeat = heartbeat;
+            panic!("poisoned");
+        });
+        assert!(worker.join().is_err());
+        assert!(watchdog
+            .check(now, true)
+            .contains(&("state machine", WorkerProblem::Panicked)));
+    }
+}
@@ -5,12 +5,12 @@ use std::path::Path;
 
 const BUCKET: &str = "fiuba-sauron";
 
-/// Uses AWS Rekognition to determine if an image contains an incident
-pub async fn is_incident(
+/// Uses AWS Rekognition to find the signs of incidents in an image. Returns the labels found
+/// with their confidence, or an error if the image couldn't be analyzed
+pub async fn detect_labels(
     config: &SdkConfig,
     file_path: &str,
-    confidence_threshold: f32,
-) -> Option<String> {
+) -> Result<Vec<(String, f32)>, String> {
     let s3_client = aws_sdk_s3::Client::new(config);
     let rekognition_client = aws_sdk_rekognition::Client::new(config);
 
@@ -18,23 +18,15 @@ pub async fn is_incident(
         Some(file_name) => match file_name.to_str() {
             Some(file_name) => file_name,
             None => {
-                println!("Error getting file name");
-                return None;
+                return Err("Error getting file name".to_string());
             }
         },
         None => {
-            println!("Error getting file name");
-            return None;
+            return Err("Error getting file name".to_string());
         }
     };
 
-    match upload_file(s3_client, BUCKET, file_path, file_name).await {
-        Ok(_) => {}
-        Err(e) => {
-            println!("Error uploading file: {:?}", e);
-            return None;
-        }
-    }
+    upload_file(s3_client, BUCKET, file_path, file_name).await?;
 
     let s3_image = ImageBuilder::default()
         .s3_object({
@@ -64,25 +56,18 @@ pub async fn is_incident(
 
     let response = request.send().await;
 
-    let mut best_label: Option<String> = None;
-    let mut best_confidence: f32 = confidence_threshold;
-
     if let Ok(response) = response {
-        for label in response.labels() {
-            match (label.name(), label.confidence()) {
-                (Some(name), Some(confidence)) => {
-                    if confidence > best_confidence {
-                        best_label = Some(name.to_string());
-                        best_confidence = confidence;
-                    }
-                }
-                _ => continue,
-            }
-        }
-        best_label
+        let labels = response
+            .labels()
+            .iter()
+            .filter_map(|label| match (label.name(), label.confidence()) {
+                (Some(name), Some(confidence)) => Some((name.to_string(), confidence)),
+                _ => None,
+            })
+            .collect();
+        Ok(labels)
     } else {
-        println!("Error: {:?}", response.err());
-        None
+        Err(format!("Error detecting labels: {:?}", response.err()))
     }
 }
 
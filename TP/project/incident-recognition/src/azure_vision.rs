@@ -0,0 +1,86 @@
+use serde_json::Value;
+use std::fs;
+
+use crate::vision_labels;
+
+const ANALYZE_PATH: &str = "/vision/v3.2/analyze";
+const KEY_HEADER: &str = "Ocp-Apim-Subscription-Key";
+
+/// Uses Azure Computer Vision, at the endpoint of its resource, to find the signs of incidents
+/// in an image. Returns the labels found with their confidence as AWS Rekognition gives them,
+/// or an error if the image couldn't be analyzed
+pub fn detect_labels(
+    endpoint: &str,
+    key: &str,
+    file_path: &str,
+) -> Result<Vec<(String, f32)>, String> {
+    let image = fs::read(file_path).map_err(|_| "Error reading file".to_string())?;
+    let url = format!("{}{}", endpoint.trim_end_matches('/'), ANALYZE_PATH);
+
+    let response = match ureq::post(&url)
+        .query("visualFeatures", "Tags")
+        .set(KEY_HEADER, key)
+        .set("Content-Type", "application/octet-stream")
+        .send_bytes(&image)
+    {
+        Ok(response) => response,
+        Err(ureq::Error::Status(code, response)) => {
+            let message = response
+                .into_json::<Value>()
+                .ok()
+                .and_then(|body| body["error"]["message"].as_str().map(str::to_string));
+            return Err(format!(
+                "Error detecting labels: {} {}",
+                code,
+                message.unwrap_or_default()
+            ));
+        }
+        Err(e) => return Err(format!("Error detecting labels: {}", e)),
+    };
+    let body: Value = response
+        .into_json()
+        .map_err(|e| format!("Error reading labels: {}", e))?;
+    labels_from(&body)
+}
+
+/// Reads the labels of the tags of an analysis
+fn labels_from(body: &Value) -> Result<Vec<(String, f32)>, String> {
+    let tags = body["tags"]
+        .as_array()
+        .ok_or_else(|| "Error reading labels: the analysis has no tags".to_string())?;
+    Ok(tags
+        .iter()
+        .filter_map(
+            |tag| match (tag["name"].as_str(), tag["confidence"].as_f64()) {
+                (Some(name), Some(confidence)) => {
+                    Some(vision_labels::normalize(name, confidence as f32))
+                }
+                _ => None,
+            },
+        )
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_labels_are_read_from_the_tags() {
+        let body = json!({
+            "tags": [
+                { "name": "fire", "confidence": 0.75 },
+                { "name": "car accident", "confidence": 0.5 },
+                { "name": "outdoor" }
+            ],
+            "requestId": "c2a1f9e2-7d6b-4b5a-9f1e-3d2c1b0a9f8e",
+            "metadata": { "width": 640, "height": 480, "format": "Jpeg" }
+        });
+        assert_eq!(
+            labels_from(&body).unwrap(),
+            vec![("Fire".to_string(), 75.0), ("Car Crash".to_string(), 50.0)]
+        );
+        assert!(labels_from(&json!({ "requestId": "c2a1f9e2" })).is_err());
+    }
+}
@@ -0,0 +1,296 @@
+use aws_config::SdkConfig;
+use std::{
+    env, fmt, fs,
+    path::Path,
+    process,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use crate::{
+    aws_rekognition, azure_vision, google_vision,
+    label_filter::LabelFilter,
+    local_heuristic,
+    retry::{retry, CircuitBreaker, RetryPolicy},
+    video,
+};
+
+const AWS: &str = "aws";
+const GOOGLE: &str = "google";
+const AZURE: &str = "azure";
+const LOCAL: &str = "local";
+
+/// Frames of each second of a video that are analyzed, unless the recognizer is given another
+/// rate
+const DEFAULT_FRAMES_PER_SECOND: f64 = 1.0;
+
+/// Number of the next folder the frames of a video are extracted into, so the videos analyzed
+/// at the same time don't share one
+static NEXT_FRAMES_FOLDER: AtomicUsize = AtomicUsize::new(0);
+
+/// Represents a way of recognizing incidents in images
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RecognitionBackend {
+    /// AWS Rekognition, which needs credentials and a connection to AWS
+    Aws,
+    /// Google Cloud Vision, which needs an API key
+    Google,
+    /// Azure Computer Vision, which needs the endpoint and a key of its resource
+    Azure,
+    /// The words in the name and metadata of the image, which work offline
+    Local,
+}
+
+impl RecognitionBackend {
+    /// Parses a backend, `aws`, `google`, `azure` or `local`
+    pub fn from_string(string: &str) -> Result<Self, String> {
+        match string.trim() {
+            AWS => Ok(RecognitionBackend::Aws),
+            GOOGLE => Ok(RecognitionBackend::Google),
+            AZURE => Ok(RecognitionBackend::Azure),
+            LOCAL => Ok(RecognitionBackend::Local),
+            _ => Err(format!("Invalid recognition backend: {}", string)),
+        }
+    }
+}
+
+impl fmt::Display for RecognitionBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecognitionBackend::Aws => write!(f, "{}", AWS),
+            RecognitionBackend::Google => write!(f, "{}", GOOGLE),
+            RecognitionBackend::Azure => write!(f, "{}", AZURE),
+            RecognitionBackend::Local => write!(f, "{}", LOCAL),
+        }
+    }
+}
+
+/// Represents the credentials of the vision providers other than AWS, which takes its own from
+/// the environment
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VisionCredentials {
+    pub google_api_key: Option<String>,
+    /// Endpoint of the Azure Computer Vision resource, such as
+    /// `https://<resource>.cognitiveservices.azure.com`
+    pub azure_endpoint: Option<String>,
+    pub azure_key: Option<String>,
+}
+
+impl VisionCredentials {
+    /// Returns an error if a backend needs credentials that are missing
+    pub fn check(&self, backend: RecognitionBackend) -> Result<(), String> {
+        let missing = match backend {
+            RecognitionBackend::Google if self.google_api_key.is_none() => "google_api_key",
+            RecognitionBackend::Azure if self.azure_endpoint.is_none() => "azure_endpoint",
+            RecognitionBackend::Azure if self.azure_key.is_none() => "azure_key",
+            _ => return Ok(()),
+        };
+        Err(format!("Missing {} for the {} backend", missing, backend))
+    }
+}
+
+/// Represents how the camera system recognizes incidents: the backends in the order they are
+/// tried, with retries for the calls to the vision providers and a circuit breaker for the ones
+/// to AWS
+pub struct Recognizer {
+    backends: Vec<RecognitionBackend>,
+    sdk_config: SdkConfig,
+    credentials: VisionCredentials,
+    /// Minimum confidence of the labels of the cameras without a threshold of their own
+    confidence_threshold: f32,
+    retry_policy: RetryPolicy,
+    aws_breaker: Mutex<CircuitBreaker>,
+    /// Calls to AWS that failed after their retries
+    aws_failures: AtomicU64,
+    frames_per_second: f64,
+}
+
+impl Recognizer {
+    /// Creates a recognizer
+    pub fn new(
+        backends: Vec<RecognitionBackend>,
+        sdk_config: SdkConfig,
+        confidence_threshold: f32,
+        retry_policy: RetryPolicy,
+        aws_breaker: CircuitBreaker,
+    ) -> Self {
+        Recognizer {
+            backends,
+            sdk_config,
+            credentials: VisionCredentials::default(),
+            confidence_threshold,
+            retry_policy,
+            aws_breaker: Mutex::new(aws_breaker),
+            aws_failures: AtomicU64::new(0),
+            frames_per_second: DEFAULT_FRAMES_PER_SECOND,
+        }
+    }
+
+    /// Sets how many frames of each second of a video are analyzed
+    pub fn with_frames_per_second(mut self, frames_per_second: f64) -> Self {
+        self.frames_per_second = frames_per_second;
+        self
+    }
+
+    /// Sets the credentials of Google Cloud Vision and Azure Computer Vision
+    pub fn with_vision_credentials(mut self, credentials: VisionCredentials) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// Returns the backend that is tried first
+    pub fn preferred_backend(&self) -> Option<RecognitionBackend> {
+        self.backends.first().copied()
+    }
+
+    /// Returns how many calls to AWS failed after their retries
+    pub fn aws_failures(&self) -> u64 {
+        self.aws_failures.load(Ordering::Relaxed)
+    }
+
+    /// Returns true if some backend can analyze images now, which isn't the case while AWS is
+    /// paused after repeated failures and there is no other backend
+    pub fn is_available(&self) -> bool {
+        self.backends
+            .iter()
+            .any(|backend| *backend != RecognitionBackend::Aws || self.aws_allowed())
+    }
+
+    /// Determines if an image contains an incident with the first of the backends that can
+    /// analyze it, keeping the labels the filter of its camera alerts. Returns the label of the
+    /// incident with its confidence and the backend that analyzed the image, or the error of
+    /// the last backend if none could
+    pub async fn recognize(
+        &self,
+        file_path: &str,
+        label_filter: &LabelFilter,
+    ) -> Result<(Option<(String, f32)>, RecognitionBackend), String> {
+        let mut error = "No recognition backends".to_string();
+        for backend in &self.backends {
+            let result = match backend {
+                RecognitionBackend::Aws => self.recognize_with_aws(file_path).await,
+                RecognitionBackend::Google | RecognitionBackend::Azure => {
+                    self.recognize_with_provider(*backend, file_path).await
+                }
+                RecognitionBackend::Local => local_heuristic::detect_labels(file_path),
+            };
+            match result {
+                Ok(labels) => {
+                    let label = label_filter.incident_label(&labels, self.confidence_threshold);
+                    return Ok((label, *backend));
+                }
+                Err(e) => error = format!("{} ({})", e, backend),
+            }
+        }
+        Err(error)
+    }
+
+    /// Determines if a video contains an incident by analyzing frames sampled from it, in order,
+    /// until one contains an incident. A video is a single event, so only the label of that
+    /// frame is returned
+    pub async fn recognize_video(
+        &self,
+        file_path: &str,
+        label_filter: &LabelFilter,
+    ) -> Result<(Option<(String, f32)>, RecognitionBackend), String> {
+        let frames_folder = env::temp_dir().join(format!(
+            "incident-recognition-frames-{}-{}",
+            process::id(),
+            NEXT_FRAMES_FOLDER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let result = self
+            .recognize_frames(file_path, &frames_folder, label_filter)
+            .await;
+        let _ = fs::remove_dir_all(&frames_folder);
+        result
+    }
+
+    /// Extracts the frames of a video into a folder and analyzes them, stopping at the first
+    /// with an incident
+    async fn recognize_frames(
+        &self,
+        file_path: &str,
+        frames_folder: &Path,
+        label_filter: &LabelFilter,
+    ) -> Result<(Option<(String, f32)>, RecognitionBackend), String> {
+        let frames =
+            video::extract_frames(Path::new(file_path), self.frames_per_second, frames_folder)?;
+
+        let mut result = Err("The video has no frames".to_string());
+        for frame in frames {
+            let frame = match frame.to_str() {
+                Some(frame) => frame.to_string(),
+                None => continue,
+            };
+            let (label, backend) = self.recognize(&frame, label_filter).await?;
+            if label.is_some() {
+                return Ok((label, backend));
+            }
+            result = Ok((None, backend));
+        }
+        result
+    }
+
+    /// Analyzes an image with AWS Rekognition, retrying the transient failures, unless the
+    /// circuit breaker paused the calls
+    async fn recognize_with_aws(&self, file_path: &str) -> Result<Vec<(String, f32)>, String> {
+        if !self.aws_allowed() {
+            return Err("Paused after repeated failures".to_string());
+        }
+
+        let result = retry(&self.retry_policy, || {
+            aws_rekognition::detect_labels(&self.sdk_config, file_path)
+        })
+        .await;
+
+        if let Ok(mut breaker) = self.aws_breaker.lock() {
+            breaker.record(result.is_ok());
+        }
+        if result.is_err() {
+            self.aws_failures.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// Analyzes an image with Google Cloud Vision or Azure Computer Vision, retrying the
+    /// transient failures. The requests block, so they are made out of the async threads
+    async fn recognize_with_provider(
+        &self,
+        backend: RecognitionBackend,
+        file_path: &str,
+    ) -> Result<Vec<(String, f32)>, String> {
+        self.credentials.check(backend)?;
+
+        retry(&self.retry_policy, || {
+            let credentials = self.credentials.clone();
+            let file_path = file_path.to_string();
+            async move {
+                tokio::task::spawn_blocking(move || match backend {
+                    RecognitionBackend::Google => google_vision::detect_labels(
+                        credentials.google_api_key.as_deref().unwrap_or_default(),
+                        &file_path,
+                    ),
+                    _ => azure_vision::detect_labels(
+                        credentials.azure_endpoint.as_deref().unwrap_or_default(),
+                        credentials.azure_key.as_deref().unwrap_or_default(),
+                        &file_path,
+                    ),
+                })
+                .await
+                .map_err(|e| format!("Error detecting labels: {}", e))?
+            }
+        })
+        .await
+    }
+
+    /// Returns true if the circuit breaker lets AWS be called
+    fn aws_allowed(&self) -> bool {
+        match self.aws_breaker.lock() {
+            Ok(breaker) => breaker.allows(),
+            Err(_) => false,
+        }
+    }
+}
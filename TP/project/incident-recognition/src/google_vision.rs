@@ -0,0 +1,99 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::{json, Value};
+use std::fs;
+
+use crate::vision_labels;
+
+const ANNOTATE_URL: &str = "https://vision.googleapis.com/v1/images:annotate";
+/// Most labels asked for each image
+const MAX_LABELS: u32 = 20;
+
+/// Uses Google Cloud Vision to find the signs of incidents in an image. Returns the labels
+/// found with their confidence as AWS Rekognition gives them, or an error if the image couldn't
+/// be analyzed
+pub fn detect_labels(api_key: &str, file_path: &str) -> Result<Vec<(String, f32)>, String> {
+    let image = fs::read(file_path).map_err(|_| "Error reading file".to_string())?;
+    let request = json!({
+        "requests": [{
+            "image": { "content": STANDARD.encode(image) },
+            "features": [{ "type": "LABEL_DETECTION", "maxResults": MAX_LABELS }]
+        }]
+    });
+
+    let response = match ureq::post(ANNOTATE_URL)
+        .query("key", api_key)
+        .send_json(request)
+    {
+        Ok(response) => response,
+        Err(ureq::Error::Status(code, response)) => {
+            let message = response
+                .into_json::<Value>()
+                .ok()
+                .and_then(|body| body["error"]["message"].as_str().map(str::to_string));
+            return Err(format!(
+                "Error detecting labels: {} {}",
+                code,
+                message.unwrap_or_default()
+            ));
+        }
+        Err(e) => return Err(format!("Error detecting labels: {}", e)),
+    };
+    let body: Value = response
+        .into_json()
+        .map_err(|e| format!("Error reading labels: {}", e))?;
+    labels_from(&body)
+}
+
+/// Reads the labels of the response to an annotation of a single image
+fn labels_from(body: &Value) -> Result<Vec<(String, f32)>, String> {
+    let response = &body["responses"][0];
+    if let Some(message) = response["error"]["message"].as_str() {
+        return Err(format!("Error detecting labels: {}", message));
+    }
+
+    // An image without labels has no annotations at all
+    let annotations = match response["labelAnnotations"].as_array() {
+        Some(annotations) => annotations,
+        None => return Ok(vec![]),
+    };
+    Ok(annotations
+        .iter()
+        .filter_map(|annotation| {
+            match (
+                annotation["description"].as_str(),
+                annotation["score"].as_f64(),
+            ) {
+                (Some(name), Some(score)) => Some(vision_labels::normalize(name, score as f32)),
+                _ => None,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels_are_read_from_the_annotations() {
+        let body = json!({
+            "responses": [{
+                "labelAnnotations": [
+                    { "mid": "/m/02wbm", "description": "Flame", "score": 0.875, "topicality": 0.875 },
+                    { "mid": "/m/01j61q", "description": "smoke", "score": 0.75 },
+                    { "mid": "/m/0", "description": "Sky" }
+                ]
+            }]
+        });
+        assert_eq!(
+            labels_from(&body).unwrap(),
+            vec![("Fire".to_string(), 87.5), ("Smoke".to_string(), 75.0)]
+        );
+
+        assert_eq!(labels_from(&json!({ "responses": [{}] })).unwrap(), vec![]);
+        assert!(labels_from(&json!({
+            "responses": [{ "error": { "code": 3, "message": "Bad image data." } }]
+        }))
+        .is_err());
+    }
+}
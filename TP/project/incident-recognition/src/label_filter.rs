@@ -0,0 +1,79 @@
+/// Represents which labels of an image a camera alerts as incidents. Labels are compared
+/// without case
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LabelFilter {
+    /// Minimum confidence of a label, instead of the one of the camera system
+    pub confidence_threshold: Option<f32>,
+    /// Labels that are incidents. If empty, every label not denied is
+    pub allowed_labels: Vec<String>,
+    /// Labels that are never incidents
+    pub denied_labels: Vec<String>,
+}
+
+impl LabelFilter {
+    /// Returns the label of the incident in an image, with its confidence: the most confident
+    /// of its labels that is over the threshold, allowed and not denied
+    pub fn incident_label(
+        &self,
+        labels: &[(String, f32)],
+        default_threshold: f32,
+    ) -> Option<(String, f32)> {
+        let threshold = self.confidence_threshold.unwrap_or(default_threshold);
+        labels
+            .iter()
+            .filter(|(label, confidence)| *confidence > threshold && self.allows(label))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .cloned()
+    }
+
+    /// Returns true if the label can be an incident
+    fn allows(&self, label: &str) -> bool {
+        let listed = |labels: &[String]| labels.iter().any(|l| l.eq_ignore_ascii_case(label));
+        (self.allowed_labels.is_empty() || listed(&self.allowed_labels))
+            && !listed(&self.denied_labels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incident_label_is_the_most_confident_allowed_label() {
+        let labels = vec![
+            ("Dog".to_string(), 95.0),
+            ("Fire".to_string(), 80.0),
+            ("Smoke".to_string(), 60.0),
+        ];
+
+        assert_eq!(
+            LabelFilter::default().incident_label(&labels, 50.0),
+            Some(("Dog".to_string(), 95.0))
+        );
+
+        let filter = LabelFilter {
+            confidence_threshold: None,
+            allowed_labels: vec![],
+            denied_labels: vec!["dog".to_string()],
+        };
+        assert_eq!(
+            filter.incident_label(&labels, 50.0),
+            Some(("Fire".to_string(), 80.0))
+        );
+
+        let filter = LabelFilter {
+            confidence_threshold: Some(85.0),
+            allowed_labels: vec!["Fire".to_string(), "Smoke".to_string()],
+            denied_labels: vec![],
+        };
+        assert_eq!(filter.incident_label(&labels, 50.0), None);
+        assert_eq!(
+            LabelFilter {
+                confidence_threshold: Some(50.0),
+                ..filter
+            }
+            .incident_label(&labels, 90.0),
+            Some(("Fire".to_string(), 80.0))
+        );
+    }
+}
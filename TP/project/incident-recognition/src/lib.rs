@@ -1 +1,9 @@
 pub mod aws_rekognition;
+pub mod azure_vision;
+pub mod backend;
+pub mod google_vision;
+pub mod label_filter;
+pub mod local_heuristic;
+pub mod retry;
+pub mod video;
+pub mod vision_labels;
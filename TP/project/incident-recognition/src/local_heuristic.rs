@@ -0,0 +1,99 @@
+use std::{fs, path::Path};
+
+/// Words that reveal an incident, with the label reported for each. The labels are the ones
+/// AWS Rekognition uses, so the incidents look the same whichever backend recognized them
+const KEYWORDS: [(&str, &str); 14] = [
+    ("fire", "Fire"),
+    ("flames", "Fire"),
+    ("smoke", "Smoke"),
+    ("fight", "Fighting"),
+    ("fighting", "Fighting"),
+    ("chase", "Chasing"),
+    ("chasing", "Chasing"),
+    ("weapon", "Weapon"),
+    ("gun", "Weapon"),
+    ("knife", "Weapon"),
+    ("accident", "Car Crash"),
+    ("crash", "Car Crash"),
+    ("explosion", "Explosion"),
+    ("flood", "Flood"),
+];
+
+/// Extension of the file that may go next to an image with words describing it
+const METADATA_EXTENSION: &str = "txt";
+
+/// Confidence of the labels, as a word either is in the name or metadata of the image or not
+const CONFIDENCE: f32 = 100.0;
+
+/// Finds the signs of incidents in an image without leaving the machine, from the words in its
+/// file name and in its metadata file, if it has one. Returns the labels found with their
+/// confidence, or an error if the image doesn't exist
+pub fn detect_labels(file_path: &str) -> Result<Vec<(String, f32)>, String> {
+    let path = Path::new(file_path);
+    if !path.is_file() {
+        return Err("Error reading file".to_string());
+    }
+
+    let mut text = match path.file_stem().and_then(|stem| stem.to_str()) {
+        Some(stem) => stem.to_lowercase(),
+        None => return Err("Error getting file name".to_string()),
+    };
+    if let Ok(metadata) = fs::read_to_string(path.with_extension(METADATA_EXTENSION)) {
+        text.push(' ');
+        text.push_str(&metadata.to_lowercase());
+    }
+
+    let mut labels: Vec<(String, f32)> = vec![];
+    for word in text.split(|c: char| !c.is_alphanumeric()) {
+        if let Some((_, label)) = KEYWORDS.iter().find(|(keyword, _)| *keyword == word) {
+            if !labels.iter().any(|(found, _)| found == label) {
+                labels.push((label.to_string(), CONFIDENCE));
+            }
+        }
+    }
+    Ok(labels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn test_labels_are_detected_from_the_name_and_metadata() {
+        let folder = env::temp_dir().join(format!("local-heuristic-{}", process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        let image = |name: &str| {
+            let path = folder.join(name);
+            fs::write(&path, []).unwrap();
+            path.to_str().unwrap().to_string()
+        };
+
+        let fire = image("Camera_3-fire.jpg");
+        let street = image("street.png");
+        let tagged = image("frame-0012.jpg");
+        fs::write(folder.join("frame-0012.txt"), "two people, knife, fight").unwrap();
+        let fireman = image("fireman.jpg");
+
+        let results = (
+            detect_labels(&fire),
+            detect_labels(&street),
+            detect_labels(&tagged),
+            detect_labels(&fireman),
+            detect_labels(&folder.join("missing.jpg").to_string_lossy()),
+        );
+        let _ = fs::remove_dir_all(&folder);
+
+        assert_eq!(results.0, Ok(vec![("Fire".to_string(), CONFIDENCE)]));
+        assert_eq!(results.1, Ok(vec![]));
+        assert_eq!(
+            results.2,
+            Ok(vec![
+                ("Weapon".to_string(), CONFIDENCE),
+                ("Fighting".to_string(), CONFIDENCE)
+            ])
+        );
+        assert_eq!(results.3, Ok(vec![]));
+        assert!(results.4.is_err());
+    }
+}
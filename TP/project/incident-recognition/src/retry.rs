@@ -0,0 +1,133 @@
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+/// Represents how many times an operation is attempted, and how long to wait between attempts
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    /// Wait after the first failed attempt, which doubles after each of the next ones
+    pub base_delay: Duration,
+}
+
+/// Runs the operation until it succeeds or runs out of attempts, waiting longer after each
+/// failure so a throttled service has time to recover. Returns the error of the last attempt
+pub async fn retry<T, F, Fut>(policy: &RetryPolicy, mut operation: F) -> Result<T, String>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, String>>,
+{
+    let mut delay = policy.base_delay;
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Ok(result) => return Ok(result),
+            Err(e) if attempt >= policy.attempts => return Err(e),
+            Err(_) => {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Represents a circuit breaker, which stops calling a service that keeps failing for a while
+/// instead of wasting every call on it
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: usize,
+    cooldown: Duration,
+    failures: usize,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    /// Creates a circuit breaker that opens after `threshold` failures in a row, for `cooldown`
+    pub fn new(threshold: usize, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            threshold,
+            cooldown,
+            failures: 0,
+            open_until: None,
+        }
+    }
+
+    /// Returns true if the service can be called. Once the cooldown passes a call is let
+    /// through to check if the service recovered
+    pub fn allows(&self) -> bool {
+        match self.open_until {
+            Some(open_until) => Instant::now() >= open_until,
+            None => true,
+        }
+    }
+
+    /// Records how a call to the service went. A failure after the cooldown opens the
+    /// circuit again
+    pub fn record(&mut self, success: bool) {
+        if success {
+            self.failures = 0;
+            self.open_until = None;
+            return;
+        }
+
+        self.failures += 1;
+        if self.failures >= self.threshold {
+            self.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn test_retry_stops_at_the_first_success_or_the_last_attempt() {
+        let rt = Runtime::new().unwrap();
+        let policy = RetryPolicy {
+            attempts: 3,
+            base_delay: Duration::from_millis(1),
+        };
+
+        let mut calls = 0;
+        let result = rt.block_on(retry(&policy, || {
+            calls += 1;
+            let call = calls;
+            async move {
+                if call < 2 {
+                    Err("throttled".to_string())
+                } else {
+                    Ok(call)
+                }
+            }
+        }));
+        assert_eq!(result, Ok(2));
+
+        let mut calls = 0;
+        let result: Result<(), String> = rt.block_on(retry(&policy, || {
+            calls += 1;
+            async { Err("throttled".to_string()) }
+        }));
+        assert_eq!(result, Err("throttled".to_string()));
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn test_circuit_breaker_opens_after_repeated_failures() {
+        let mut breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        breaker.record(false);
+        assert!(breaker.allows());
+        breaker.record(false);
+        assert!(!breaker.allows());
+
+        breaker.record(true);
+        assert!(breaker.allows());
+
+        let mut breaker = CircuitBreaker::new(1, Duration::ZERO);
+        breaker.record(false);
+        assert!(breaker.allows());
+    }
+}
@@ -0,0 +1,70 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Extensions of the videos whose frames can be analyzed
+const VIDEO_EXTENSIONS: [&str; 2] = ["mp4", "avi"];
+
+/// Extension of the frames extracted from the videos
+const FRAME_EXTENSION: &str = "jpg";
+
+/// Returns true if the file has the extension of a video
+pub fn is_video(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| VIDEO_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+}
+
+/// Extracts `frames_per_second` frames of each second of a video into a folder, with ffmpeg.
+/// The frames are named after the video, so the local heuristic still reads its name, and
+/// are returned in the order they appear in it
+pub fn extract_frames(
+    video: &Path,
+    frames_per_second: f64,
+    frames_folder: &Path,
+) -> Result<Vec<PathBuf>, String> {
+    let name = match video.file_stem().and_then(|stem| stem.to_str()) {
+        Some(stem) => stem,
+        None => return Err("Error getting file name".to_string()),
+    };
+    fs::create_dir_all(frames_folder).map_err(|e| format!("Error creating folder: {}", e))?;
+
+    let output = Command::new("ffmpeg")
+        .args(["-nostdin", "-loglevel", "error", "-i"])
+        .arg(video)
+        .arg("-vf")
+        .arg(format!("fps={}", frames_per_second))
+        .arg(frames_folder.join(format!("{}-%05d.{}", name, FRAME_EXTENSION)))
+        .output()
+        .map_err(|e| format!("Error running ffmpeg: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Error extracting frames: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let mut frames: Vec<PathBuf> = fs::read_dir(frames_folder)
+        .map_err(|e| format!("Error reading frames: {}", e))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == FRAME_EXTENSION))
+        .collect();
+    frames.sort();
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_videos_are_recognized_by_their_extension() {
+        assert!(is_video(Path::new("images/3/fire.mp4")));
+        assert!(is_video(Path::new("images/3/fire.AVI")));
+        assert!(!is_video(Path::new("images/3/fire.jpg")));
+        assert!(!is_video(Path::new("images/3/mp4")));
+    }
+}
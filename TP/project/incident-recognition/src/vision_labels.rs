@@ -0,0 +1,63 @@
+/// Labels of the vision providers that name the same incidents as AWS Rekognition, with the
+/// label AWS uses for each
+const SYNONYMS: [(&str, &str); 10] = [
+    ("flame", "Fire"),
+    ("wildfire", "Fire"),
+    ("firearm", "Weapon"),
+    ("gun", "Weapon"),
+    ("knife", "Weapon"),
+    ("collision", "Car Crash"),
+    ("traffic collision", "Car Crash"),
+    ("car accident", "Car Crash"),
+    ("fight", "Fighting"),
+    ("combat", "Fighting"),
+];
+
+/// Maps a label of a vision provider, with a score between 0 and 1, to the label and
+/// confidence AWS Rekognition would give: a percentage and the name AWS uses, with its
+/// words capitalized. So the label filters of the cameras work the same with every provider
+pub fn normalize(name: &str, score: f32) -> (String, f32) {
+    let name = name.trim();
+    let label = match SYNONYMS
+        .iter()
+        .find(|(synonym, _)| synonym.eq_ignore_ascii_case(name))
+    {
+        Some((_, label)) => label.to_string(),
+        None => capitalize(name),
+    };
+    (label, (score * 100.0).clamp(0.0, 100.0))
+}
+
+/// Capitalizes every word of a label
+fn capitalize(label: &str) -> String {
+    label
+        .split_whitespace()
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels_are_named_and_scored_as_aws_does() {
+        assert_eq!(normalize("fire", 0.875), ("Fire".to_string(), 87.5));
+        assert_eq!(normalize("Flame", 0.5), ("Fire".to_string(), 50.0));
+        assert_eq!(
+            normalize("traffic collision", 1.0),
+            ("Car Crash".to_string(), 100.0)
+        );
+        assert_eq!(
+            normalize(" public  safety ", 1.2),
+            ("Public Safety".to_string(), 100.0)
+        );
+    }
+}
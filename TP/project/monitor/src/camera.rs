@@ -1,5 +1,5 @@
 /// Camera struct
-use common::camera_status::CameraStatus;
+use common::{camera_data::CameraData, camera_status::CameraStatus};
 pub struct Camera {
     pub id: String,
     pub x_coordinate: f64,
@@ -7,19 +7,14 @@ pub struct Camera {
     pub status: CameraStatus,
 }
 
-impl Camera {
-    /// Creates a new camera
-    pub fn new(id: String, x_coordinate: f64, y_coordinate: f64, status_str: String) -> Self {
-        let status = match status_str.as_str() {
-            "1" => CameraStatus::Active,
-            _ => CameraStatus::Sleep,
-        };
-
+impl From<CameraData> for Camera {
+    /// Creates the camera the camera system published
+    fn from(data: CameraData) -> Self {
         Camera {
-            id,
-            x_coordinate,
-            y_coordinate,
-            status,
+            id: data.id.to_string(),
+            x_coordinate: data.x_coordinate,
+            y_coordinate: data.y_coordinate,
+            status: data.status,
         }
     }
 }
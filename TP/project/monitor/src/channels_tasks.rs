@@ -1,4 +1,12 @@
-use common::incident::Incident;
+use common::{
+    camera_command::CameraCommand,
+    camera_system_status::CameraSystemStatus,
+    coordenate::Coordenate,
+    drone_anchor::DroneAnchor,
+    drone_command::{DroneCommand, DroneCommandAck},
+    drone_log::DroneLog,
+    incident::Incident,
+};
 
 use crate::{camera::Camera, drone::Drone};
 
@@ -8,6 +16,9 @@ pub enum UIAction {
     RegistrateIncident(IncidentRegistration),
     EditIncident(IncidentEdit),
     ResolveIncident(Incident),
+    CommandDrone(DroneCommandOrder),
+    /// Adds, removes or moves a camera of the camera system
+    CommandCamera(CameraCommand),
 }
 
 /// Represents a drone registration
@@ -41,10 +52,30 @@ pub struct IncidentEdit {
     pub description: String,
 }
 
+/// Represents a command an operator sends to a drone
+#[derive(Clone)]
+pub struct DroneCommandOrder {
+    pub drone_id: String,
+    pub command: DroneCommand,
+}
+
 /// Represents the action that the monitor wants to perform
 pub enum MonitorAction {
     Drone(Drone),
-    Camera(Camera),
+    /// Every camera of the camera system, which replace the ones known before
+    Cameras(Vec<Camera>),
+    /// The cameras that changed, which replace the ones with their ids
+    CameraUpdates(Vec<Camera>),
     Incident(Incident),
     DetectedIncident(IncidentRegistration),
+    /// The answer of a drone to the last command it received
+    DroneCommandAck(String, DroneCommandAck),
+    /// The anchor a drone waits at for incidents
+    DroneAnchor(String, DroneAnchor),
+    /// The waypoints a drone still has to go through, which are none once it arrives
+    DroneRoute(String, Vec<Coordenate>),
+    /// An event a drone logged
+    DroneLog(String, DroneLog),
+    /// Whether the camera system is running, and the health of its cameras
+    CameraSystemStatus(CameraSystemStatus),
 }
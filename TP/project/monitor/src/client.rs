@@ -2,10 +2,18 @@ use std::{
     collections::HashMap,
     io::{ErrorKind, Write},
     net::TcpStream,
+    path::{Path, PathBuf},
     sync::mpsc::{channel, Receiver, Sender},
 };
 
 use common::{
+    camera_command::CameraCommand,
+    camera_data::CameraData,
+    camera_system_status::CameraSystemStatus,
+    detected_incident::DetectedIncident,
+    drone_anchor::DroneAnchor,
+    drone_command::{DroneCommand, DroneCommandAck},
+    drone_log::DroneLog,
     drone_status::DroneStatus,
     incident::{Incident, IncidentStatus},
 };
@@ -15,18 +23,20 @@ use mqtt::model::{
         topic_level::TopicLevel, topic_name::TopicName,
     },
     packet::Packet,
-    packets::{connect::Connect, publish::Publish, subscribe::Subscribe},
+    packets::{connect::Connect, puback::Puback, publish::Publish, subscribe::Subscribe},
     return_codes::connect_return_code::ConnectReturnCode,
 };
 
 use crate::{
     camera::Camera,
     channels_tasks::{
-        DroneRegistration, IncidentEdit, IncidentRegistration, MonitorAction, UIAction,
+        DroneCommandOrder, DroneRegistration, IncidentEdit, IncidentRegistration, MonitorAction,
+        UIAction,
     },
     config::Config,
     drone::Drone,
     monitor::Monitor,
+    route::waypoints_from,
     ui_application::UIApplication,
 };
 
@@ -53,10 +63,17 @@ pub fn client_run(config: Config) -> Result<(), String> {
     }
 
     let cloned_key = *config.get_key();
+    let incidents_file = config.get_incidents_file().map(PathBuf::from);
 
     // monitor start in a thread to avoid blocking the main thread
     std::thread::spawn(move || {
-        start_monitor(stream, monitor_sender, ui_receiver, &cloned_key);
+        start_monitor(
+            stream,
+            monitor_sender,
+            ui_receiver,
+            &cloned_key,
+            incidents_file,
+        );
     });
 
     // start the ui in the main thread
@@ -135,6 +152,7 @@ fn start_ui(
 }
 
 const CAMERA_DATA: &[u8] = b"camera-data";
+const CAMERA_UPDATE: &[u8] = b"camera-update";
 const DRONE_DATA: &[u8] = b"drone-data";
 const CLIENT_REGISTER: &[u8] = b"$client-register";
 const NEW_INCIDENT: &[u8] = b"new-incident";
@@ -142,18 +160,39 @@ const ATTENDING_INCIDENT: &[u8] = b"attending-incident";
 const READY_INCIDENT: &[u8] = b"ready-incident";
 const CLOSE_INCIDENT: &[u8] = b"close-incident";
 const DETECTED_INCIDENT: &[u8] = b"detected-incident";
+const DRONE_COMMAND: &[u8] = b"drone-command";
+const CAMERA_COMMAND: &[u8] = b"camera-command";
+const CAMERA_SYSTEM_STATUS: &[u8] = b"camera-system-status";
+const DRONE_ANCHOR: &[u8] = b"drone-anchor";
+const INCIDENT_SYNC: &[u8] = b"incident-sync";
+const DRONE_LOG: &[u8] = b"drone-log";
+const REJECTED_INCIDENT: &[u8] = b"rejected-incident";
+const DRONE_ROUTE: &[u8] = b"drone-route";
+const DATA: &[u8] = b"data";
 
 const SEPARATOR: char = ';';
 const ENUMARATOR: char = '|';
 
-/// Starts the monitor
+/// Starts the monitor, with the incidents of the previous run if they are kept in a file
 fn start_monitor(
     stream: TcpStream,
     monitor_sender: Sender<MonitorAction>,
     ui_reciver: Receiver<UIAction>,
     key: &[u8; 32],
+    incidents_file: Option<PathBuf>,
 ) {
-    let mut monitor = Monitor::new();
+    let mut monitor = match &incidents_file {
+        Some(incidents_file) => restore_incidents(incidents_file),
+        None => Monitor::new(),
+    };
+    for incident in monitor.incidents() {
+        if monitor_sender
+            .send(MonitorAction::Incident(incident))
+            .is_err()
+        {
+            println!("Error sending incident data to UI");
+        }
+    }
     let mut unacknowledged_publish = HashMap::new();
     let mut publish_counter = 0;
 
@@ -167,6 +206,9 @@ fn start_monitor(
     }
 
     loop {
+        // The incidents are saved whenever they change, so none is lost if the monitor stops
+        let mut incidents_changed = false;
+
         match Packet::from_bytes(&mut stream, key) {
             Ok(Packet::Puback(puback)) => {
                 let packet_id = puback.packet_identifier();
@@ -178,14 +220,27 @@ fn start_monitor(
             Ok(Packet::Publish(publish)) => {
                 let topic_name = publish.topic();
                 let topic_levels = topic_name.levels();
+                incidents_changed = matches!(
+                    topic_levels[0].as_slice(),
+                    NEW_INCIDENT | ATTENDING_INCIDENT | READY_INCIDENT | REJECTED_INCIDENT
+                );
 
                 match topic_levels[0].as_slice() {
                     DRONE_DATA => {
-                        drone_data(publish.clone(), monitor_sender.clone());
+                        drone_data(publish.clone(), &mut monitor, monitor_sender.clone());
                     }
                     CAMERA_DATA => {
                         camera_data(publish.clone(), monitor_sender.clone());
                     }
+                    CAMERA_UPDATE => {
+                        camera_update(publish.clone(), monitor_sender.clone());
+                    }
+                    CAMERA_SYSTEM_STATUS => {
+                        camera_system_status(publish.clone(), monitor_sender.clone());
+                    }
+                    NEW_INCIDENT => {
+                        known_incident(publish.clone(), &mut monitor, monitor_sender.clone());
+                    }
                     ATTENDING_INCIDENT => {
                         attend_incident(publish.clone(), &mut monitor, monitor_sender.clone());
                     }
@@ -195,17 +250,80 @@ fn start_monitor(
                     DETECTED_INCIDENT => {
                         detected_incident(publish.clone(), monitor_sender.clone());
                     }
+                    DRONE_COMMAND => {
+                        let reassignment = drone_command_ack(
+                            publish.clone(),
+                            &mut monitor,
+                            monitor_sender.clone(),
+                            publish_counter,
+                        );
+                        if let Some(reassignment) = reassignment {
+                            send_publish(
+                                &mut stream,
+                                reassignment,
+                                key,
+                                &mut unacknowledged_publish,
+                            );
+                            publish_counter += 1;
+                        }
+                    }
+                    REJECTED_INCIDENT => {
+                        let reassignment =
+                            rejected_incident(publish.clone(), &mut monitor, publish_counter);
+                        if let Some(reassignment) = reassignment {
+                            send_publish(
+                                &mut stream,
+                                reassignment,
+                                key,
+                                &mut unacknowledged_publish,
+                            );
+                            publish_counter += 1;
+                        }
+                    }
+                    DRONE_ANCHOR => {
+                        drone_anchor(publish.clone(), monitor_sender.clone());
+                    }
+                    DRONE_LOG => {
+                        drone_log(publish.clone(), monitor_sender.clone());
+                    }
+                    DRONE_ROUTE => {
+                        drone_route(publish.clone(), monitor_sender.clone());
+                    }
+                    INCIDENT_SYNC => {
+                        let answer = incident_sync(publish.clone(), &monitor);
+                        if let Some(answer) = answer {
+                            if stream.write(answer.to_bytes(key).as_slice()).is_err() {
+                                println!("Error sending publish packet");
+                            }
+                        }
+                    }
                     _ => {
                         println!("Unknown topic");
                     }
                 }
+
+                // The server stops delivering once too many messages go unacknowledged
+                if publish.qos() != &QoS::AtMost {
+                    let puback = Puback::new(publish.package_identifier());
+                    if stream.write_all(puback.to_bytes(key).as_slice()).is_err() {
+                        println!("Error sending puback packet");
+                    }
+                }
             }
 
             Ok(_) => {}
             Err(_) => {}
         }
 
-        let publish = match ui_reciver.try_recv() {
+        let action = ui_reciver.try_recv();
+        incidents_changed |= matches!(
+            action,
+            Ok(UIAction::RegistrateIncident(_)
+                | UIAction::EditIncident(_)
+                | UIAction::ResolveIncident(_))
+        );
+
+        let publish = match action {
             Ok(UIAction::RegistrateDrone(drone_registration)) => {
                 register_drone(drone_registration, publish_counter)
             }
@@ -228,26 +346,68 @@ fn start_monitor(
                 publish_counter,
                 monitor_sender.clone(),
             ),
+
+            Ok(UIAction::CommandDrone(order)) => command_drone(order, publish_counter),
+            Ok(UIAction::CommandCamera(command)) => command_camera(command, publish_counter),
             Err(_) => None,
         };
 
         if let Some(publish) = publish {
-            match stream.write(publish.to_bytes(key).as_slice()) {
-                Ok(_) => {
-                    unacknowledged_publish.insert(publish.package_identifier(), publish.clone());
-                }
-                Err(_) => {
-                    println!("Error sending publish packet");
-                }
+            send_publish(&mut stream, publish, key, &mut unacknowledged_publish);
+            publish_counter += 1;
+        }
+
+        if let Some(incidents_file) = &incidents_file {
+            if incidents_changed {
+                save_incidents(&monitor, incidents_file);
             }
+        }
+    }
+}
 
-            publish_counter += 1;
+/// Sends a publish, which is kept until the server acknowledges it
+fn send_publish(
+    stream: &mut TcpStream,
+    publish: Publish,
+    key: &[u8; 32],
+    unacknowledged_publish: &mut HashMap<Option<u16>, Publish>,
+) {
+    match stream.write_all(publish.to_bytes(key).as_slice()) {
+        Ok(_) => {
+            unacknowledged_publish.insert(publish.package_identifier(), publish);
+        }
+        Err(_) => {
+            println!("Error sending publish packet");
         }
     }
 }
 
+/// Reads the incidents of the previous run, or starts without incidents if there are none
+fn restore_incidents(incidents_file: &Path) -> Monitor {
+    if !incidents_file.exists() {
+        return Monitor::new();
+    }
+    match Monitor::load(incidents_file) {
+        Ok(monitor) => {
+            println!("Restoring the incidents from {}", incidents_file.display());
+            monitor
+        }
+        Err(e) => {
+            println!("Error restoring the incidents: {:?}", e);
+            Monitor::new()
+        }
+    }
+}
+
+/// Writes the incidents to their file
+fn save_incidents(monitor: &Monitor, incidents_file: &Path) {
+    if let Err(e) = monitor.save(incidents_file) {
+        println!("Error saving the incidents: {:?}", e);
+    }
+}
+
 /// Handles the drone data
-fn drone_data(publish: Publish, monitor_sender: Sender<MonitorAction>) {
+fn drone_data(publish: Publish, monitor: &mut Monitor, monitor_sender: Sender<MonitorAction>) {
     let topic_name = publish.topic();
     let topic_levels = topic_name.levels();
 
@@ -281,7 +441,18 @@ fn drone_data(publish: Publish, monitor_sender: Sender<MonitorAction>) {
         }
     };
 
-    let drone = Drone::new(id.clone(), status, battery, x_coordinate, y_coordinate);
+    // The drones only send their ETA while they travel
+    let eta = match splitted_content.get(4).map(|eta| eta.parse::<u64>()) {
+        Some(Ok(eta)) => Some(eta),
+        Some(Err(_)) => {
+            println!("Error parsing ETA");
+            return;
+        }
+        None => None,
+    };
+
+    monitor.update_drone(id.clone(), x_coordinate, y_coordinate);
+    let drone = Drone::new(id.clone(), status, battery, x_coordinate, y_coordinate).with_eta(eta);
 
     match monitor_sender.send(MonitorAction::Drone(drone.clone())) {
         Ok(_) => {}
@@ -291,52 +462,45 @@ fn drone_data(publish: Publish, monitor_sender: Sender<MonitorAction>) {
     }
 }
 
-/// Handles the camera data
+/// Handles the data of every camera
 fn camera_data(publish: Publish, monitor_sender: Sender<MonitorAction>) {
-    let content = publish.message();
+    let cameras = match cameras_from(&publish) {
+        Some(cameras) => cameras,
+        None => return,
+    };
 
-    let content_str = String::from_utf8_lossy(content).to_string();
-    let splitted_content: Vec<&str> = content_str.split(ENUMARATOR).collect();
+    match monitor_sender.send(MonitorAction::Cameras(cameras)) {
+        Ok(_) => {}
+        Err(_) => {
+            println!("Error sending camera data to UI");
+        }
+    }
+}
 
-    // this are camera data
-    for camera_data in splitted_content {
-        let camera_data = camera_data.split(SEPARATOR).collect::<Vec<&str>>();
-        let id = match camera_data[0].parse::<String>() {
-            Ok(id) => id,
-            Err(_) => {
-                println!("Error parsing camera id");
-                return;
-            }
-        };
-        let x_coordinate = match camera_data[1].parse::<f64>() {
-            Ok(x) => x,
-            Err(_) => {
-                println!("Error parsing x coordinate");
-                return;
-            }
-        };
-        let y_coordinate = match camera_data[2].parse::<f64>() {
-            Ok(y) => y,
-            Err(_) => {
-                println!("Error parsing y coordinate");
-                return;
-            }
-        };
-        let state = match camera_data[3].parse::<String>() {
-            Ok(state) => state,
-            Err(_) => {
-                println!("Error parsing camera state");
-                return;
-            }
-        };
+/// Handles the data of the cameras that changed
+fn camera_update(publish: Publish, monitor_sender: Sender<MonitorAction>) {
+    let cameras = match cameras_from(&publish) {
+        Some(cameras) => cameras,
+        None => return,
+    };
 
-        let camera = Camera::new(id, x_coordinate, y_coordinate, state);
+    match monitor_sender.send(MonitorAction::CameraUpdates(cameras)) {
+        Ok(_) => {}
+        Err(_) => {
+            println!("Error sending camera data to UI");
+        }
+    }
+}
 
-        match monitor_sender.send(MonitorAction::Camera(camera)) {
-            Ok(_) => {}
-            Err(_) => {
-                println!("Error sending camera data to UI");
-            }
+/// Reads the cameras of a message, a JSON list or the `id;x;y;status|...` format used before
+fn cameras_from(publish: &Publish) -> Option<Vec<Camera>> {
+    let content = String::from_utf8_lossy(publish.message());
+
+    match CameraData::list_from_string(&content) {
+        Ok(cameras) => Some(cameras.into_iter().map(Camera::from).collect()),
+        Err(e) => {
+            println!("Error parsing camera data: {:?}", e);
+            None
         }
     }
 }
@@ -409,7 +573,7 @@ fn register_incident(
     monitor_sender: Sender<MonitorAction>,
     package_identifier: u16,
 ) -> Option<Publish> {
-    let uuid = monitor.get_amount_incidents().to_string();
+    let uuid = monitor.next_uuid();
     let name = incident_registration.name.clone();
     let description = incident_registration.description.clone();
     let x_coordinate = match incident_registration.x.clone().parse() {
@@ -446,6 +610,27 @@ fn register_incident(
     }
 }
 
+/// Handles the incident retained in the server, which the monitor registered in a run whose
+/// incidents weren't saved. It is restored if it is unknown, as it was published
+fn known_incident(publish: Publish, monitor: &mut Monitor, monitor_sender: Sender<MonitorAction>) {
+    let message = String::from_utf8_lossy(publish.message()).to_string();
+    let incident = match Incident::from_string(message) {
+        Ok(incident) => incident,
+        Err(e) => {
+            println!("Error parsing incident: {:?}", e);
+            return;
+        }
+    };
+
+    if monitor.restore_incident(incident.clone(), 0)
+        && monitor_sender
+            .send(MonitorAction::Incident(incident))
+            .is_err()
+    {
+        println!("Error sending incident data to UI");
+    }
+}
+
 /// Edits an incident
 fn edit_incident(
     incident_registration: IncidentEdit,
@@ -509,27 +694,286 @@ fn resolve_incident(
     ))
 }
 
+/// Sends a command to a drone. It isn't retained, so the drone doesn't carry it out again
+/// after reconnecting
+fn command_drone(order: DroneCommandOrder, package_identifier: u16) -> Option<Publish> {
+    let topic_name = TopicName::new(
+        vec![DRONE_COMMAND.to_vec(), order.drone_id.into_bytes()],
+        false,
+    );
+    let message = order.command.to_string().into_bytes();
+    let dup = false;
+    let qos = QoS::AtLeast;
+    let retain = false;
+    let package_identifier = Some(package_identifier);
+
+    Some(Publish::new(
+        dup,
+        qos,
+        retain,
+        topic_name,
+        package_identifier,
+        message,
+    ))
+}
+
+/// Answers a drone that reconnected with the incidents that aren't resolved, so it can
+/// forget the ones closed while it was away and learn of the ones it missed
+fn incident_sync(publish: Publish, monitor: &Monitor) -> Option<Publish> {
+    let topic_levels = publish.topic().levels();
+    let drone_id = match topic_levels.get(1) {
+        Some(drone_id) => drone_id.clone(),
+        None => {
+            println!("Invalid incident sync request");
+            return None;
+        }
+    };
+
+    let topic_name = TopicName::new(vec![INCIDENT_SYNC.to_vec(), drone_id, DATA.to_vec()], false);
+    let message = monitor
+        .unresolved_incidents()
+        .iter()
+        .map(|incident| incident.to_string())
+        .collect::<Vec<String>>()
+        .join(&ENUMARATOR.to_string())
+        .into_bytes();
+    let dup = false;
+    let qos = QoS::AtMost;
+    let retain = false;
+
+    Some(Publish::new(dup, qos, retain, topic_name, None, message))
+}
+
+/// Creates the publish that sends a command to the camera system
+fn command_camera(command: CameraCommand, package_identifier: u16) -> Option<Publish> {
+    let topic_name = TopicName::new(vec![CAMERA_COMMAND.to_vec()], false);
+    let message = command.to_string().into_bytes();
+    let dup = false;
+    let qos = QoS::AtLeast;
+    let retain = false;
+    let package_identifier = Some(package_identifier);
+
+    Some(Publish::new(
+        dup,
+        qos,
+        retain,
+        topic_name,
+        package_identifier,
+        message,
+    ))
+}
+
+/// Handles the answer of a drone to a command
+fn drone_command_ack(
+    publish: Publish,
+    monitor: &mut Monitor,
+    monitor_sender: Sender<MonitorAction>,
+    package_identifier: u16,
+) -> Option<Publish> {
+    let topic_levels = publish.topic().levels();
+    let drone_id = match topic_levels.get(1) {
+        Some(drone_id) => String::from_utf8_lossy(drone_id).to_string(),
+        None => {
+            println!("Invalid drone command acknowledgment");
+            return None;
+        }
+    };
+
+    let ack = match DroneCommandAck::from_string(&String::from_utf8_lossy(publish.message())) {
+        Ok(ack) => ack,
+        Err(_) => {
+            println!("Invalid drone command acknowledgment");
+            return None;
+        }
+    };
+
+    // A drone that can't attend a reassigned incident rejects it as the first one did
+    let reassignment = match &ack.command {
+        DroneCommand::Attend(incident) if !ack.accepted => reassign_incident(
+            incident.uuid.clone(),
+            drone_id.clone(),
+            monitor,
+            package_identifier,
+        ),
+        _ => None,
+    };
+
+    match monitor_sender.send(MonitorAction::DroneCommandAck(drone_id, ack)) {
+        Ok(_) => {}
+        Err(_) => {
+            println!("Error sending drone command acknowledgment to UI");
+        }
+    }
+    reassignment
+}
+
+/// Handles a drone rejecting an incident inside one of its no-fly zones, which goes to the
+/// closest drone that didn't reject it
+fn rejected_incident(
+    publish: Publish,
+    monitor: &mut Monitor,
+    package_identifier: u16,
+) -> Option<Publish> {
+    let topic_levels = publish.topic().levels();
+    let incident_uuid = match topic_levels.get(1) {
+        Some(incident_uuid) => String::from_utf8_lossy(incident_uuid).to_string(),
+        None => {
+            println!("Invalid incident rejection");
+            return None;
+        }
+    };
+    let drone_id = String::from_utf8_lossy(publish.message()).to_string();
+
+    reassign_incident(incident_uuid, drone_id, monitor, package_identifier)
+}
+
+/// Creates the command that sends an incident a drone rejected to another drone, if one
+/// can still attend it
+fn reassign_incident(
+    incident_uuid: String,
+    drone_id: String,
+    monitor: &mut Monitor,
+    package_identifier: u16,
+) -> Option<Publish> {
+    let drone_id = monitor.reject_incident(incident_uuid.clone(), drone_id)?;
+    let incident = monitor.get_incident(&incident_uuid)?.clone();
+
+    command_drone(
+        DroneCommandOrder {
+            drone_id,
+            command: DroneCommand::Attend(incident),
+        },
+        package_identifier,
+    )
+}
+
+/// Handles the anchor a drone announced, so its coverage is drawn in the map
+fn drone_anchor(publish: Publish, monitor_sender: Sender<MonitorAction>) {
+    let topic_levels = publish.topic().levels();
+    let drone_id = match topic_levels.get(1) {
+        Some(drone_id) => String::from_utf8_lossy(drone_id).to_string(),
+        None => {
+            println!("Invalid drone anchor");
+            return;
+        }
+    };
+
+    let anchor = match DroneAnchor::from_string(&String::from_utf8_lossy(publish.message())) {
+        Ok(anchor) => anchor,
+        Err(_) => {
+            println!("Invalid drone anchor");
+            return;
+        }
+    };
+
+    match monitor_sender.send(MonitorAction::DroneAnchor(drone_id, anchor)) {
+        Ok(_) => {}
+        Err(_) => {
+            println!("Error sending drone anchor to UI");
+        }
+    }
+}
+
+/// Handles the route a drone planned, so it is drawn in the map
+fn drone_route(publish: Publish, monitor_sender: Sender<MonitorAction>) {
+    let topic_levels = publish.topic().levels();
+    let drone_id = match topic_levels.get(1) {
+        Some(drone_id) => String::from_utf8_lossy(drone_id).to_string(),
+        None => {
+            println!("Invalid drone route");
+            return;
+        }
+    };
+
+    let waypoints = match waypoints_from(&String::from_utf8_lossy(publish.message())) {
+        Some(waypoints) => waypoints,
+        None => {
+            println!("Invalid drone route");
+            return;
+        }
+    };
+
+    match monitor_sender.send(MonitorAction::DroneRoute(drone_id, waypoints)) {
+        Ok(_) => {}
+        Err(_) => {
+            println!("Error sending drone route to UI");
+        }
+    }
+}
+
+/// Handles an event a drone logged, so the operators can follow it from the drone list
+fn drone_log(publish: Publish, monitor_sender: Sender<MonitorAction>) {
+    let topic_levels = publish.topic().levels();
+    let drone_id = match topic_levels.get(1) {
+        Some(drone_id) => String::from_utf8_lossy(drone_id).to_string(),
+        None => {
+            println!("Invalid drone log");
+            return;
+        }
+    };
+
+    let log = match DroneLog::from_string(&String::from_utf8_lossy(publish.message())) {
+        Ok(log) => log,
+        Err(_) => {
+            println!("Invalid drone log");
+            return;
+        }
+    };
+
+    match monitor_sender.send(MonitorAction::DroneLog(drone_id, log)) {
+        Ok(_) => {}
+        Err(_) => {
+            println!("Error sending drone log to UI");
+        }
+    }
+}
+
+/// Handles the status of the camera system, which its will sets to offline if it goes down
+fn camera_system_status(publish: Publish, monitor_sender: Sender<MonitorAction>) {
+    let status = match CameraSystemStatus::from_string(&String::from_utf8_lossy(publish.message()))
+    {
+        Ok(status) => status,
+        Err(_) => {
+            println!("Invalid camera system status");
+            return;
+        }
+    };
+
+    match monitor_sender.send(MonitorAction::CameraSystemStatus(status)) {
+        Ok(_) => {}
+        Err(_) => {
+            println!("Error sending camera system status to UI");
+        }
+    }
+}
+
 /// Handles the autodetected incident by the camera system
 fn detected_incident(publish: Publish, monitor_sender: Sender<MonitorAction>) {
     let topic_levels = publish.topic().levels();
     let camera_id = String::from_utf8_lossy(topic_levels[1].as_slice()).to_string();
 
-    let description = format!("By AWS Rekonginition services - Camera {}", camera_id);
-
-    let data = String::from_utf8_lossy(publish.message()).to_string();
+    let data = String::from_utf8_lossy(publish.message());
+    let camera = camera_id.parse::<u8>().unwrap_or_default();
+    let incident = match DetectedIncident::from_string(&data, camera) {
+        Ok(incident) => incident,
+        Err(e) => {
+            println!("Error parsing detected incident: {:?}", e);
+            return;
+        }
+    };
 
-    let splitted_data: Vec<&str> = data.split(SEPARATOR).collect();
-    let x = splitted_data[0].to_string();
-    let y = splitted_data[1].to_string();
-    let label = splitted_data[2].to_string();
+    let mut description = format!("By AWS Rekonginition services - Camera {}", camera_id);
+    if let Some(confidence) = incident.confidence {
+        description.push_str(&format!(" ({:.1}% confidence)", confidence));
+    }
 
-    let name = format!("Autodetected incident ({})", label);
+    let name = format!("Autodetected incident ({})", incident.label);
 
     let incident_registration = IncidentRegistration {
         name,
         description,
-        x,
-        y,
+        x: incident.x_coordinate.to_string(),
+        y: incident.y_coordinate.to_string(),
     };
 
     match monitor_sender.send(MonitorAction::DetectedIncident(incident_registration)) {
@@ -545,12 +989,20 @@ fn subscribe_to_topics(stream: &mut TcpStream, key: &[u8; 32]) -> std::io::Resul
     let mut topic_filters = vec![];
 
     let topics = vec![
+        "new-incident",
         "camera-data",
+        "camera-system-status",
         "camera-update",
         "attending-incident/+",
         "drone-data/+",
         "ready-incident/+",
         "detected-incident/+",
+        "drone-command/+/ack",
+        "drone-anchor/+",
+        "incident-sync/+",
+        "drone-log/+",
+        "rejected-incident/+",
+        "drone-route/+",
     ];
 
     for topic in topics {
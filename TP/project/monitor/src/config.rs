@@ -12,6 +12,7 @@ pub struct Config {
     username: String,
     password: String,
     charging_stations: Vec<Coordenate>,
+    incidents_file: Option<String>,
 }
 
 impl Config {
@@ -90,6 +91,7 @@ impl Config {
                 .remove("password")
                 .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing password"))?,
             charging_stations,
+            incidents_file: config_map.remove("incidents_file"),
         })
     }
 
@@ -122,4 +124,9 @@ impl Config {
     pub fn get_charging_coordenates(&self) -> Vec<Coordenate> {
         self.charging_stations.clone()
     }
+
+    /// Returns the file where the incidents are kept between runs, if they are
+    pub fn get_incidents_file(&self) -> Option<&str> {
+        self.incidents_file.as_deref()
+    }
 }
@@ -0,0 +1,44 @@
+use common::drone_anchor::DroneAnchor;
+use eframe::egui::{Color32, Painter, Response, Stroke};
+use walkers::{Plugin, Position, Projector};
+
+/// Draws the area each drone covers from its anchor, so the operator can see the gaps and
+/// the overlaps between them
+pub struct Coverage {
+    anchors: Vec<DroneAnchor>,
+}
+
+impl Coverage {
+    /// Creates the coverage of the specified anchors
+    pub fn new(anchors: Vec<DroneAnchor>) -> Self {
+        Self { anchors }
+    }
+}
+
+impl Plugin for Coverage {
+    fn run(&mut self, _response: &Response, painter: Painter, projector: &Projector) {
+        for anchor in &self.anchors {
+            let center = projector
+                .project(Position::from_lon_lat(
+                    anchor.x_coordinate,
+                    anchor.y_coordinate,
+                ))
+                .to_pos2();
+            // The range is measured in coordinates, so its radius is the distance to the point
+            // that far from the anchor
+            let edge = projector
+                .project(Position::from_lon_lat(
+                    anchor.x_coordinate + anchor.active_range,
+                    anchor.y_coordinate,
+                ))
+                .to_pos2();
+
+            painter.circle(
+                center,
+                center.distance(edge),
+                Color32::from_rgba_unmultiplied(30, 144, 255, 25),
+                Stroke::new(1.0, Color32::from_rgb(30, 144, 255)),
+            );
+        }
+    }
+}
@@ -9,6 +9,8 @@ pub struct Drone {
     pub x_coordinate: f64,
     pub y_coordinate: f64,
     pub incident: Option<Incident>,
+    /// Seconds the drone needs to reach its destination, while it travels
+    pub eta: Option<u64>,
 }
 
 impl Drone {
@@ -27,6 +29,33 @@ impl Drone {
             x_coordinate,
             y_coordinate,
             incident: None,
+            eta: None,
         }
     }
+
+    /// Sets the seconds the drone needs to reach its destination
+    pub fn with_eta(mut self, eta: Option<u64>) -> Self {
+        self.eta = eta;
+        self
+    }
+
+    /// Returns the label of the drone on the map, with its ETA while it travels
+    pub fn label(&self) -> String {
+        match self.eta {
+            Some(eta) => format!("{} ETA {}s", self.id, eta),
+            None => self.id.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_travelling_drone_label_has_its_eta() {
+        let drone = Drone::new("1".to_string(), DroneStatus::Free, 100, 1.0, 1.0);
+        assert_eq!(drone.label(), "1");
+        assert_eq!(drone.with_eta(Some(35)).label(), "1 ETA 35s");
+    }
 }
@@ -2,7 +2,9 @@ pub mod camera;
 pub mod channels_tasks;
 pub mod client;
 pub mod config;
+pub mod coverage;
 pub mod drone;
 pub mod monitor;
 pub mod right_click_menu;
+pub mod route;
 pub mod ui_application;
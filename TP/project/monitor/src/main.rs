@@ -11,9 +11,11 @@ mod camera;
 mod channels_tasks;
 mod client;
 mod config;
+mod coverage;
 mod drone;
 mod monitor;
 mod right_click_menu;
+mod route;
 mod ui_application;
 
 const CLIENT_ARGS: usize = 2;
@@ -1,12 +1,26 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+};
 
-use common::incident::{Incident, IncidentStatus};
+use common::incident::{Incident, IncidentStatus, DEFAULT_REQUIRED_DRONES};
+
+const SEPARATOR: char = ';';
+const DRONE_SEPARATOR: char = ',';
+const REJECTIONS_PREFIX: &str = "rejected";
+const TEMP_FILE_EXTENSION: &str = "tmp";
 
 /// Represents the monitor that will be handling all incidents
 pub struct Monitor {
     incidents: HashMap<String, Incident>,
     open_incidents: HashMap<String, usize>,
     active_incidents: HashMap<String, usize>,
+    /// Last position of each drone
+    drones: HashMap<String, (f64, f64)>,
+    /// Drones that rejected each incident
+    rejections: HashMap<String, HashSet<String>>,
 }
 
 impl Default for Monitor {
@@ -22,6 +36,8 @@ impl Monitor {
             incidents: HashMap::new(),
             open_incidents: HashMap::new(),
             active_incidents: HashMap::new(),
+            drones: HashMap::new(),
+            rejections: HashMap::new(),
         }
     }
 
@@ -32,12 +48,13 @@ impl Monitor {
         self.open_incidents.insert(incident.uuid.clone(), 0);
     }
 
-    /// Changes the status of an incident to in progress
+    /// Counts a drone attending an incident, and changes its status to in progress
+    /// once all the drones it needs are attending it
     pub fn attend_incident(&mut self, incident_uuid: String) -> Option<Incident> {
         if let Some(incident) = self.incidents.get_mut(&incident_uuid) {
             if let Some(open_count) = self.open_incidents.get_mut(&incident_uuid) {
                 *open_count += 1;
-                if *open_count == 2 {
+                if *open_count == incident.required_drones_or(DEFAULT_REQUIRED_DRONES) {
                     self.active_incidents
                         .insert(incident_uuid.clone(), *open_count);
                     self.open_incidents.remove(&incident_uuid);
@@ -86,9 +103,165 @@ impl Monitor {
         }
     }
 
-    /// Gets the amount of incidents
-    pub fn get_amount_incidents(&self) -> usize {
-        self.incidents.len()
+    /// Updates the position of a drone
+    pub fn update_drone(&mut self, drone_id: String, x_coordinate: f64, y_coordinate: f64) {
+        self.drones.insert(drone_id, (x_coordinate, y_coordinate));
+    }
+
+    /// Counts a drone rejecting an incident and returns the closest drone that didn't reject
+    /// it, which should attend it instead. There is none once the incident isn't pending or
+    /// every drone rejected it
+    pub fn reject_incident(&mut self, incident_uuid: String, drone_id: String) -> Option<String> {
+        let incident = self.incidents.get(&incident_uuid)?;
+        let rejections = self.rejections.entry(incident_uuid).or_default();
+        rejections.insert(drone_id);
+        if incident.status != IncidentStatus::Pending {
+            return None;
+        }
+
+        let distance_to =
+            |(x, y): &(f64, f64)| (x - incident.x_coordinate).hypot(y - incident.y_coordinate);
+        self.drones
+            .iter()
+            .filter(|(drone_id, _)| !rejections.contains(*drone_id))
+            .min_by(|(_, a), (_, b)| distance_to(a).total_cmp(&distance_to(b)))
+            .map(|(drone_id, _)| drone_id.clone())
+    }
+
+    /// Returns the UUID of the next incident, after the highest one known. The incidents a
+    /// previous run registered may be restored after others
+    pub fn next_uuid(&self) -> String {
+        self.incidents
+            .keys()
+            .filter_map(|uuid| uuid.parse::<u64>().ok())
+            .max()
+            .map_or(0, |uuid| uuid + 1)
+            .to_string()
+    }
+
+    /// Registers an incident of a previous run with the drones that were attending it, unless
+    /// it is already known. Returns true if it was restored
+    pub fn restore_incident(&mut self, incident: Incident, attending_drones: usize) -> bool {
+        if self.incidents.contains_key(&incident.uuid) {
+            return false;
+        }
+
+        match incident.status {
+            IncidentStatus::Pending => {
+                self.open_incidents
+                    .insert(incident.uuid.clone(), attending_drones);
+            }
+            IncidentStatus::InProgress | IncidentStatus::Resolvable => {
+                self.active_incidents
+                    .insert(incident.uuid.clone(), attending_drones);
+            }
+            IncidentStatus::Resolved => {}
+        }
+        self.incidents.insert(incident.uuid.clone(), incident);
+        true
+    }
+
+    /// Returns the incidents, sorted by uuid
+    pub fn incidents(&self) -> Vec<Incident> {
+        let mut incidents: Vec<Incident> = self.incidents.values().cloned().collect();
+        incidents.sort_by(|a, b| a.uuid.cmp(&b.uuid));
+        incidents
+    }
+
+    /// Writes the incidents to a file, a line of `attending_drones;incident` for each, followed
+    /// by a line of `rejected;uuid;drone,drone` for each incident some drones rejected. It goes
+    /// to a temporary file that replaces the previous one once it is written, so a crash while
+    /// saving leaves the last incidents untouched
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut lines: Vec<String> = self
+            .incidents()
+            .iter()
+            .map(|incident| {
+                format!(
+                    "{}{}{}",
+                    self.attending_drones(&incident.uuid),
+                    SEPARATOR,
+                    incident
+                )
+            })
+            .collect();
+
+        let mut rejections: Vec<(&String, Vec<&str>)> = self
+            .rejections
+            .iter()
+            .map(|(incident_uuid, drones)| {
+                let mut drones: Vec<&str> = drones.iter().map(String::as_str).collect();
+                drones.sort();
+                (incident_uuid, drones)
+            })
+            .collect();
+        rejections.sort();
+        lines.extend(rejections.iter().map(|(incident_uuid, drones)| {
+            format!(
+                "{}{}{}{}{}",
+                REJECTIONS_PREFIX,
+                SEPARATOR,
+                incident_uuid,
+                SEPARATOR,
+                drones.join(&DRONE_SEPARATOR.to_string())
+            )
+        }));
+
+        let temp_path = path.with_extension(TEMP_FILE_EXTENSION);
+        let mut file = File::create(&temp_path)?;
+        file.write_all((lines.join("\n") + "\n").as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    }
+
+    /// Reads the incidents, and the drones that rejected them, saved to a file by a previous run
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Invalid incident line");
+        let mut monitor = Monitor::new();
+
+        for line in fs::read_to_string(path)?
+            .lines()
+            .filter(|line| !line.is_empty())
+        {
+            let (attending_drones, incident) = line.split_once(SEPARATOR).ok_or_else(invalid)?;
+            if attending_drones == REJECTIONS_PREFIX {
+                let (incident_uuid, drones) = incident.split_once(SEPARATOR).ok_or_else(invalid)?;
+                monitor.rejections.insert(
+                    incident_uuid.to_string(),
+                    drones
+                        .split(DRONE_SEPARATOR)
+                        .filter(|drone_id| !drone_id.is_empty())
+                        .map(str::to_string)
+                        .collect(),
+                );
+                continue;
+            }
+            let attending_drones = attending_drones.parse().map_err(|_| invalid())?;
+            let incident = Incident::from_string(incident.to_string()).map_err(|_| invalid())?;
+            monitor.restore_incident(incident, attending_drones);
+        }
+        Ok(monitor)
+    }
+
+    /// Returns how many drones are attending an incident
+    fn attending_drones(&self, incident_uuid: &str) -> usize {
+        self.open_incidents
+            .get(incident_uuid)
+            .or_else(|| self.active_incidents.get(incident_uuid))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Gets the incidents that aren't resolved, sorted by uuid
+    pub fn unresolved_incidents(&self) -> Vec<Incident> {
+        let mut incidents: Vec<Incident> = self
+            .incidents
+            .values()
+            .filter(|incident| incident.status != IncidentStatus::Resolved)
+            .cloned()
+            .collect();
+        incidents.sort_by(|a, b| a.uuid.cmp(&b.uuid));
+        incidents
     }
 }
 
@@ -133,6 +306,29 @@ mod tests {
         assert_eq!(incident.status, IncidentStatus::InProgress);
     }
 
+    #[test]
+    fn test_attend_incident_that_needs_more_drones() {
+        let mut monitor = Monitor::new();
+        let incident = Incident::new(
+            "incident1".to_string(),
+            "incident1".to_string(),
+            "incident1".to_string(),
+            1.0,
+            1.0,
+            IncidentStatus::Pending,
+        )
+        .with_required_drones(3);
+
+        monitor.new_incident(incident.clone());
+
+        let incident = monitor.attend_incident(incident.uuid).unwrap();
+        let incident = monitor.attend_incident(incident.uuid).unwrap();
+        assert_eq!(incident.status, IncidentStatus::Pending);
+
+        let incident = monitor.attend_incident(incident.uuid).unwrap();
+        assert_eq!(incident.status, IncidentStatus::InProgress);
+    }
+
     #[test]
     fn test_resolvable_incident() {
         let mut monitor = Monitor::new();
@@ -172,4 +368,136 @@ mod tests {
         let incident = monitor.get_incident(&incident.uuid).unwrap();
         assert_eq!(incident.status, IncidentStatus::Resolved);
     }
+
+    #[test]
+    fn test_unresolved_incidents() {
+        let mut monitor = Monitor::new();
+        for uuid in ["2", "1", "3"] {
+            monitor.new_incident(Incident::new(
+                uuid.to_string(),
+                "incident".to_string(),
+                "incident".to_string(),
+                1.0,
+                1.0,
+                IncidentStatus::Pending,
+            ));
+        }
+        monitor.set_resolvable_incident("1".to_string());
+        monitor.set_resolved_incident("3".to_string());
+
+        let uuids: Vec<String> = monitor
+            .unresolved_incidents()
+            .into_iter()
+            .map(|incident| incident.uuid)
+            .collect();
+        assert_eq!(uuids, vec!["1".to_string(), "2".to_string()]);
+    }
+
+    #[test]
+    fn test_rejected_incidents_are_reassigned_to_the_closest_drone() {
+        let mut monitor = Monitor::new();
+        monitor.new_incident(Incident::new(
+            "0".to_string(),
+            "incident".to_string(),
+            "incident".to_string(),
+            1.0,
+            1.0,
+            IncidentStatus::Pending,
+        ));
+        monitor.update_drone("1".to_string(), 1.0, 1.5);
+        monitor.update_drone("2".to_string(), 3.0, 1.0);
+        monitor.update_drone("3".to_string(), 1.0, 2.0);
+
+        let reassigned = monitor.reject_incident("0".to_string(), "1".to_string());
+        assert_eq!(reassigned, Some("3".to_string()));
+        let reassigned = monitor.reject_incident("0".to_string(), "3".to_string());
+        assert_eq!(reassigned, Some("2".to_string()));
+        let reassigned = monitor.reject_incident("0".to_string(), "2".to_string());
+        assert_eq!(reassigned, None);
+
+        monitor.update_drone("4".to_string(), 5.0, 5.0);
+        monitor.attend_incident("0".to_string());
+        monitor.attend_incident("0".to_string());
+        let reassigned = monitor.reject_incident("0".to_string(), "1".to_string());
+        assert_eq!(reassigned, None);
+        assert_eq!(
+            monitor.reject_incident("7".to_string(), "1".to_string()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_saved_incidents_are_restored() {
+        let mut monitor = Monitor::new();
+        for uuid in ["0", "1", "2"] {
+            monitor.new_incident(Incident::new(
+                uuid.to_string(),
+                "incident".to_string(),
+                "incident".to_string(),
+                1.0,
+                1.0,
+                IncidentStatus::Pending,
+            ));
+        }
+        monitor.attend_incident("0".to_string());
+        monitor.attend_incident("1".to_string());
+        monitor.attend_incident("1".to_string());
+        monitor.set_resolved_incident("2".to_string());
+
+        let path = std::env::temp_dir().join(format!("incidents-{}.txt", std::process::id()));
+        monitor.save(&path).unwrap();
+        let restored = Monitor::load(&path);
+        let _ = fs::remove_file(&path);
+        let mut restored = restored.unwrap();
+
+        assert_eq!(restored.incidents(), monitor.incidents());
+        assert_eq!(restored.next_uuid(), "3");
+
+        // The drone still missing from the first incident puts it in progress
+        let incident = restored.attend_incident("0".to_string()).unwrap();
+        assert_eq!(incident.status, IncidentStatus::InProgress);
+
+        let retained = Incident::new(
+            "7".to_string(),
+            "incident".to_string(),
+            "incident".to_string(),
+            1.0,
+            1.0,
+            IncidentStatus::Pending,
+        );
+        assert!(restored.restore_incident(retained.clone(), 0));
+        assert!(!restored.restore_incident(retained, 0));
+        assert_eq!(restored.next_uuid(), "8");
+    }
+
+    #[test]
+    fn test_saved_rejections_are_restored() {
+        let mut monitor = Monitor::new();
+        monitor.new_incident(Incident::new(
+            "0".to_string(),
+            "incident".to_string(),
+            "incident".to_string(),
+            1.0,
+            1.0,
+            IncidentStatus::Pending,
+        ));
+        monitor.reject_incident("0".to_string(), "1".to_string());
+        monitor.reject_incident("0".to_string(), "3".to_string());
+
+        let path = std::env::temp_dir().join(format!("rejections-{}.txt", std::process::id()));
+        monitor.save(&path).unwrap();
+        let restored = Monitor::load(&path);
+        let _ = fs::remove_file(&path);
+        let mut restored = restored.unwrap();
+
+        assert_eq!(restored.incidents(), monitor.incidents());
+        // The drones that rejected the incident before aren't picked again
+        restored.update_drone("1".to_string(), 1.0, 1.0);
+        restored.update_drone("2".to_string(), 5.0, 5.0);
+        restored.update_drone("3".to_string(), 1.0, 1.5);
+        assert_eq!(
+            restored.reject_incident("0".to_string(), "4".to_string()),
+            Some("2".to_string())
+        );
+    }
 }
@@ -0,0 +1,88 @@
+use common::coordenate::Coordenate;
+use eframe::egui::{Color32, Painter, Response, Stroke};
+use walkers::{Plugin, Position, Projector};
+
+const WAYPOINT_SEPARATOR: char = '|';
+const COORDINATE_SEPARATOR: char = ';';
+
+/// Parses the route a drone published, in the format `x;y|x;y|...`, as the waypoints it
+/// still has to go through. An empty route means the drone isn't travelling
+pub fn waypoints_from(route: &str) -> Option<Vec<Coordenate>> {
+    if route.is_empty() {
+        return Some(vec![]);
+    }
+
+    route
+        .split(WAYPOINT_SEPARATOR)
+        .map(|waypoint| {
+            let (x, y) = waypoint.split_once(COORDINATE_SEPARATOR)?;
+            Some(Coordenate {
+                x_coordinate: x.parse().ok()?,
+                y_coordinate: y.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Draws the route each drone follows, from where it is through its waypoints, so the
+/// operator can see how it goes around the no-fly zones
+pub struct Routes {
+    routes: Vec<(Coordenate, Vec<Coordenate>)>,
+}
+
+impl Routes {
+    /// Creates the routes of the specified drones, with the position each one is at
+    pub fn new(routes: Vec<(Coordenate, Vec<Coordenate>)>) -> Self {
+        Self { routes }
+    }
+}
+
+impl Plugin for Routes {
+    fn run(&mut self, _response: &Response, painter: Painter, projector: &Projector) {
+        for (position, waypoints) in &self.routes {
+            let points: Vec<_> = std::iter::once(position)
+                .chain(waypoints)
+                .map(|point| {
+                    projector
+                        .project(Position::from_lon_lat(
+                            point.x_coordinate,
+                            point.y_coordinate,
+                        ))
+                        .to_pos2()
+                })
+                .collect();
+
+            for segment in points.windows(2) {
+                painter.line_segment(
+                    [segment[0], segment[1]],
+                    Stroke::new(2.0, Color32::from_rgb(255, 140, 0)),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_routes_are_parsed() {
+        assert_eq!(
+            waypoints_from("1.5;-2|3;4"),
+            Some(vec![
+                Coordenate {
+                    x_coordinate: 1.5,
+                    y_coordinate: -2.0,
+                },
+                Coordenate {
+                    x_coordinate: 3.0,
+                    y_coordinate: 4.0,
+                },
+            ])
+        );
+        assert_eq!(waypoints_from(""), Some(vec![]));
+        assert_eq!(waypoints_from("1.5;-2|3"), None);
+        assert_eq!(waypoints_from("1.5;north"), None);
+    }
+}
@@ -3,14 +3,22 @@
 use crate::{
     camera::Camera,
     channels_tasks::{
-        DroneRegistration, IncidentEdit, IncidentRegistration, MonitorAction, UIAction,
+        DroneCommandOrder, DroneRegistration, IncidentEdit, IncidentRegistration, MonitorAction,
+        UIAction,
     },
+    coverage::Coverage,
     drone::Drone,
     right_click_menu::RightClickMenu,
+    route::Routes,
 };
 use common::{
+    camera_command::CameraCommand,
     camera_status::CameraStatus,
+    camera_system_status::{CameraHealth, CameraSystemStatus},
     coordenate::Coordenate,
+    drone_anchor::DroneAnchor,
+    drone_command::{DroneCommand, DroneCommandAck},
+    drone_log::DroneLog,
     drone_status::{DroneStatus, TravelLocation},
     incident::{Incident, IncidentStatus},
 };
@@ -20,7 +28,11 @@ use eframe::egui;
 use egui::{ComboBox, Context, Response, Ui};
 use egui_extras::{Column, TableBuilder};
 
-use std::sync::mpsc::{Receiver, Sender};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::mpsc::{Receiver, Sender},
+    time::{SystemTime, UNIX_EPOCH},
+};
 use walkers::{
     extras::{Place, Places, Style},
     sources::OpenStreetMap,
@@ -35,6 +47,9 @@ pub const INCIDENT_SYMBOL: char = '⚠';
 pub const CAMERA_SYMBOL: char = '📹';
 pub const CHARGING_STATION_SYMBOL: char = '🖧';
 
+/// Events of each drone kept to show in the drone list
+const DRONE_LOG_LENGTH: usize = 10;
+
 /// Represents the layout of the UI
 #[derive(PartialEq)]
 enum Layout {
@@ -64,6 +79,12 @@ pub struct UIApplication {
     incidents: Vec<Incident>,
     cameras: Vec<Camera>,
     charging_station_coordenates: Vec<Coordenate>,
+    command_acks: HashMap<String, DroneCommandAck>,
+    anchors: HashMap<String, DroneAnchor>,
+    routes: HashMap<String, Vec<Coordenate>>,
+    camera_to_move: Option<u8>,
+    camera_system_status: Option<CameraSystemStatus>,
+    drone_logs: HashMap<String, VecDeque<DroneLog>>,
 
     right_click_menu: RightClickMenu,
 }
@@ -106,6 +127,12 @@ impl UIApplication {
             incidents: vec![],
             cameras: vec![],
             charging_station_coordenates,
+            command_acks: HashMap::new(),
+            anchors: HashMap::new(),
+            routes: HashMap::new(),
+            camera_to_move: None,
+            camera_system_status: None,
+            drone_logs: HashMap::new(),
 
             right_click_menu: RightClickMenu::default(),
         }
@@ -122,11 +149,29 @@ fn update_drones(drones: &mut Vec<Drone>, drone: Drone) {
     };
 
     match drones.binary_search_by(compare_drones) {
+        // The will of a disconnected drone has its data from when it connected,
+        // so it stays where it was last seen
+        Ok(pos) if drone.status == DroneStatus::Disconnected => {
+            drones[pos].status = DroneStatus::Disconnected
+        }
         Ok(pos) => drones[pos] = drone, // If the drone exists, update it
         Err(pos) => drones.insert(pos, drone), // If the drone doesn't exist, insert it at the correct position
     }
 }
 
+/// Adds an event to the log of a drone, forgetting the oldest ones
+fn update_drone_logs(
+    drone_logs: &mut HashMap<String, VecDeque<DroneLog>>,
+    drone_id: String,
+    log: DroneLog,
+) {
+    let logs = drone_logs.entry(drone_id).or_default();
+    if logs.len() == DRONE_LOG_LENGTH {
+        logs.pop_front();
+    }
+    logs.push_back(log);
+}
+
 /// Updates the incidents in the UI
 fn update_incidents(incidents: &mut Vec<Incident>, incident: Incident) {
     for i in incidents.iter_mut() {
@@ -139,16 +184,14 @@ fn update_incidents(incidents: &mut Vec<Incident>, incident: Incident) {
     incidents.push(incident);
 }
 
-/// Updates the cameras in the UI
-fn update_cameras(cameras: &mut Vec<Camera>, camera: Camera) {
-    for c in cameras.iter_mut() {
-        if c.id == camera.id {
-            *c = camera;
-            return;
+/// Replaces the cameras that changed, adding the ones that weren't known
+fn update_cameras(cameras: &mut Vec<Camera>, updates: Vec<Camera>) {
+    for camera in updates {
+        match cameras.iter_mut().find(|c| c.id == camera.id) {
+            Some(known) => *known = camera,
+            None => cameras.push(camera),
         }
     }
-
-    cameras.push(camera);
 }
 
 /// Handles the incident detection and sends it to the monitor
@@ -159,13 +202,16 @@ fn detected_incident(incident_registration: IncidentRegistration, sender: &Sende
     }
 }
 
-/// Handles the right clicks in the map to open the incident registration menu with coordenates selected
+/// Handles the right clicks in the map to open the incident registration menu with coordenates selected,
+/// or to send a drone there
 fn handle_right_clicks(
     ui: &mut Ui,
     response: Response,
     right_click_menu: &mut RightClickMenu,
     map_memory: &mut MapMemory,
     new_incident_registration: &mut IncidentRegistration,
+    drones: &[Drone],
+    camera_to_move: &mut Option<u8>,
     sender: &Sender<UIAction>,
     layout: &mut Layout,
 ) {
@@ -193,6 +239,52 @@ fn handle_right_clicks(
 
                         right_click_menu.open = false;
                     }
+                    for drone in drones {
+                        if ui.button(format!("Send Drone {} Here", drone.id)).clicked() {
+                            let command = DroneCommand::Goto(
+                                right_click_menu.x_coordenate,
+                                right_click_menu.y_coordenate,
+                            );
+                            command_drone(&drone.id, command, sender);
+                            right_click_menu.open = false;
+                        }
+                    }
+                    for drone in drones {
+                        if ui
+                            .button(format!("Anchor Drone {} Here", drone.id))
+                            .clicked()
+                        {
+                            let command = DroneCommand::Anchor(
+                                right_click_menu.x_coordenate,
+                                right_click_menu.y_coordenate,
+                            );
+                            command_drone(&drone.id, command, sender);
+                            right_click_menu.open = false;
+                        }
+                    }
+                    if ui.button("Add Camera Here").clicked() {
+                        let command = CameraCommand::Add(
+                            right_click_menu.x_coordenate,
+                            right_click_menu.y_coordenate,
+                        );
+                        command_camera(command, sender);
+                        right_click_menu.open = false;
+                    }
+                    if let Some(camera_id) = *camera_to_move {
+                        if ui
+                            .button(format!("Move Camera {} Here", camera_id))
+                            .clicked()
+                        {
+                            let command = CameraCommand::Move(
+                                camera_id,
+                                right_click_menu.x_coordenate,
+                                right_click_menu.y_coordenate,
+                            );
+                            command_camera(command, sender);
+                            *camera_to_move = None;
+                            right_click_menu.open = false;
+                        }
+                    }
                     if ui.button("Cancel").clicked() {
                         right_click_menu.open = false;
                     }
@@ -208,10 +300,13 @@ fn display_incident_map(
     drones: &Vec<Drone>,
     cameras: &Vec<Camera>,
     charging_station_coordenates: &Vec<Coordenate>,
+    anchors: &HashMap<String, DroneAnchor>,
+    routes: &HashMap<String, Vec<Coordenate>>,
     tiles: &mut Tiles,
     map_memory: &mut MapMemory,
     right_click_menu: &mut RightClickMenu,
     new_incident_registration: &mut IncidentRegistration,
+    camera_to_move: &mut Option<u8>,
     layout: &mut Layout,
     sender: &Sender<UIAction>,
 ) {
@@ -219,8 +314,25 @@ fn display_incident_map(
 
     let map = Map::new(Some(tiles), map_memory, position);
 
+    // The coverage and the routes go first, so the places are drawn over them
+    let coverage_plugin = Coverage::new(anchors.values().cloned().collect());
+    let routes_plugin = Routes::new(
+        drones
+            .iter()
+            .filter_map(|drone| {
+                let position = Coordenate {
+                    x_coordinate: drone.x_coordinate,
+                    y_coordinate: drone.y_coordinate,
+                };
+                Some((position, routes.get(&drone.id)?.clone()))
+            })
+            .collect(),
+    );
     let places_plugin = update_places(incidents, drones, cameras, charging_station_coordenates);
-    let map_with_plugin = map.with_plugin(places_plugin);
+    let map_with_plugin = map
+        .with_plugin(coverage_plugin)
+        .with_plugin(routes_plugin)
+        .with_plugin(places_plugin);
 
     let response = ui.add(map_with_plugin);
 
@@ -230,6 +342,8 @@ fn display_incident_map(
         right_click_menu,
         map_memory,
         new_incident_registration,
+        drones,
+        camera_to_move,
         sender,
         layout,
     );
@@ -481,15 +595,52 @@ fn display_new_drone(
     ui.add_space(20.0);
 }
 
-/// Displays the drone list
-fn display_drone_list(ui: &mut egui::Ui, drones: &[Drone]) {
+/// Sends a command to a drone
+fn command_drone(drone_id: &str, command: DroneCommand, sender: &Sender<UIAction>) {
+    let order = DroneCommandOrder {
+        drone_id: drone_id.to_string(),
+        command,
+    };
+    match sender.send(UIAction::CommandDrone(order)) {
+        Ok(_) => {}
+        Err(_) => println!("Error sending drone command"),
+    }
+}
+
+/// Returns the health of a camera, while the camera system is online
+fn camera_health<'a>(
+    camera_system_status: Option<&'a CameraSystemStatus>,
+    camera_id: &str,
+) -> Option<&'a CameraHealth> {
+    camera_system_status?
+        .cameras()
+        .iter()
+        .find(|health| health.id.to_string() == camera_id)
+}
+
+/// Sends a command to the camera system
+fn command_camera(command: CameraCommand, sender: &Sender<UIAction>) {
+    match sender.send(UIAction::CommandCamera(command)) {
+        Ok(_) => {}
+        Err(_) => println!("Error sending camera command"),
+    }
+}
+
+/// Displays the drone list, with the commands an operator can send to each drone
+fn display_drone_list(
+    ui: &mut egui::Ui,
+    drones: &[Drone],
+    command_acks: &HashMap<String, DroneCommandAck>,
+    drone_logs: &HashMap<String, VecDeque<DroneLog>>,
+    sender: &Sender<UIAction>,
+) {
     egui::Frame::group(ui.style()).show(ui, |ui| {
         ui.label("Active Drones:");
         ui.add_space(10.0);
         TableBuilder::new(ui)
             .striped(true)
             .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
-            .columns(Column::remainder(), 4)
+            .columns(Column::remainder(), 7)
             .header(10.0, |mut header| {
                 header.col(|ui| {
                     ui.heading("ID");
@@ -503,6 +654,15 @@ fn display_drone_list(ui: &mut egui::Ui, drones: &[Drone]) {
                 header.col(|ui| {
                     ui.heading("Battery");
                 });
+                header.col(|ui| {
+                    ui.heading("Commands");
+                });
+                header.col(|ui| {
+                    ui.heading("Last Command");
+                });
+                header.col(|ui| {
+                    ui.heading("Last Event");
+                });
             })
             .body(|mut body| {
                 for drone in drones.iter() {
@@ -516,26 +676,71 @@ fn display_drone_list(ui: &mut egui::Ui, drones: &[Drone]) {
                             ui.label(position);
                         });
                         row.col(|ui| {
-                            ui.label(drone.status.to_str());
+                            match drone.eta {
+                                Some(eta) => {
+                                    ui.label(format!("{} (ETA {}s)", drone.status.to_str(), eta))
+                                }
+                                None => ui.label(drone.status.to_str()),
+                            };
                         });
                         row.col(|ui| {
                             ui.label(format!("{}%", drone.battery));
                         });
+                        row.col(|ui| {
+                            if ui.button("Hold").clicked() {
+                                command_drone(&drone.id, DroneCommand::Hold, sender);
+                            }
+                            if ui.button("Return").clicked() {
+                                command_drone(&drone.id, DroneCommand::ReturnToCentral, sender);
+                            }
+                            if ui.button("Resume").clicked() {
+                                command_drone(&drone.id, DroneCommand::Resume, sender);
+                            }
+                        });
+                        row.col(|ui| {
+                            if let Some(ack) = command_acks.get(&drone.id) {
+                                let result = if ack.accepted { "accepted" } else { "rejected" };
+                                ui.label(format!("{} ({})", ack.command, result));
+                            }
+                        });
+                        // The latest event is shown, and the rest of them on hover
+                        row.col(|ui| {
+                            if let Some(logs) = drone_logs.get(&drone.id) {
+                                let describe = |log: &DroneLog| {
+                                    format!("[{}] {}: {}", log.level, log.event, log.detail)
+                                };
+                                if let Some(last) = logs.back() {
+                                    let history: Vec<String> =
+                                        logs.iter().rev().map(describe).collect();
+                                    ui.label(describe(last)).on_hover_text(history.join("\n"));
+                                }
+                            }
+                        });
                     });
                 }
             });
     });
 }
 
-/// Displays the camera list
-fn display_camera_list(ui: &mut egui::Ui, cameras: &[Camera]) {
+/// Displays the camera list, with the commands an operator can send to each camera
+fn display_camera_list(
+    ui: &mut egui::Ui,
+    cameras: &[Camera],
+    camera_system_status: Option<&CameraSystemStatus>,
+    camera_to_move: &mut Option<u8>,
+    layout: &mut Layout,
+    sender: &Sender<UIAction>,
+) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
     egui::Frame::group(ui.style()).show(ui, |ui| {
         ui.label("Camera List");
         ui.add_space(10.0);
         TableBuilder::new(ui)
             .striped(true)
             .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
-            .columns(Column::remainder(), 3)
+            .columns(Column::remainder(), 6)
             .header(10.0, |mut header| {
                 header.col(|ui| {
                     ui.heading("ID");
@@ -546,6 +751,15 @@ fn display_camera_list(ui: &mut egui::Ui, cameras: &[Camera]) {
                 header.col(|ui| {
                     ui.heading("State");
                 });
+                header.col(|ui| {
+                    ui.heading("Last Image");
+                });
+                header.col(|ui| {
+                    ui.heading("Analysis Errors");
+                });
+                header.col(|ui| {
+                    ui.heading("Commands");
+                });
             })
             .body(|mut body| {
                 for camera in cameras.iter() {
@@ -561,6 +775,33 @@ fn display_camera_list(ui: &mut egui::Ui, cameras: &[Camera]) {
                         row.col(|ui| {
                             ui.label(camera.status.to_str().clone());
                         });
+                        let health = camera_health(camera_system_status, &camera.id);
+                        row.col(|ui| {
+                            match health.and_then(|health| health.last_image) {
+                                Some(last_image) => {
+                                    ui.label(format!("{}s ago", now.saturating_sub(last_image)))
+                                }
+                                None => ui.label("Never"),
+                            };
+                        });
+                        row.col(|ui| {
+                            if let Some(health) = health {
+                                ui.label(health.analysis_errors.to_string());
+                            }
+                        });
+                        // The camera is moved from the right click menu of the map
+                        row.col(|ui| {
+                            let Ok(camera_id) = camera.id.parse::<u8>() else {
+                                return;
+                            };
+                            if ui.button("Move").clicked() {
+                                *camera_to_move = Some(camera_id);
+                                *layout = Layout::IncidentMap;
+                            }
+                            if ui.button("Remove").clicked() {
+                                command_camera(CameraCommand::Remove(camera_id), sender);
+                            }
+                        });
                     });
                 }
             });
@@ -568,7 +809,11 @@ fn display_camera_list(ui: &mut egui::Ui, cameras: &[Camera]) {
 }
 
 /// Displays the header of the UI
-fn display_header(ui: &mut egui::Ui, current_layout: &mut Layout) {
+fn display_header(
+    ui: &mut egui::Ui,
+    current_layout: &mut Layout,
+    camera_system_status: Option<&CameraSystemStatus>,
+) {
     ui.horizontal(|ui| {
         ui.heading(egui::RichText::new("Monitoring Application").size(30.0));
         ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
@@ -578,6 +823,22 @@ fn display_header(ui: &mut egui::Ui, current_layout: &mut Layout) {
     ui.add_space(15.0);
     ui.horizontal(|ui| {
         ui.label(egui::RichText::new("Connected").color(egui::Color32::GREEN));
+        // Without cameras no incident is detected, which isn't the same as having none
+        match camera_system_status {
+            Some(CameraSystemStatus::Online(_)) => {
+                ui.label(egui::RichText::new("Camera system online").color(egui::Color32::GREEN));
+            }
+            Some(CameraSystemStatus::Degraded(_)) => {
+                ui.label(
+                    egui::RichText::new("Camera system degraded").color(egui::Color32::YELLOW),
+                )
+                .on_hover_text("Recognizing incidents with a fallback backend");
+            }
+            Some(CameraSystemStatus::Offline) => {
+                ui.label(egui::RichText::new("Camera system offline").color(egui::Color32::RED));
+            }
+            None => {}
+        }
     });
 
     ui.add_space(20.0);
@@ -613,12 +874,34 @@ impl eframe::App for UIApplication {
                 Ok(MonitorAction::Incident(incident)) => {
                     update_incidents(&mut self.incidents, incident);
                 }
-                Ok(MonitorAction::Camera(camera)) => {
-                    update_cameras(&mut self.cameras, camera);
+                Ok(MonitorAction::Cameras(cameras)) => {
+                    self.cameras = cameras;
+                }
+                Ok(MonitorAction::CameraUpdates(cameras)) => {
+                    update_cameras(&mut self.cameras, cameras);
                 }
                 Ok(MonitorAction::DetectedIncident(incident_registration)) => {
                     detected_incident(incident_registration, &self.sender);
                 }
+                Ok(MonitorAction::DroneCommandAck(drone_id, ack)) => {
+                    self.command_acks.insert(drone_id, ack);
+                }
+                Ok(MonitorAction::DroneAnchor(drone_id, anchor)) => {
+                    self.anchors.insert(drone_id, anchor);
+                }
+                Ok(MonitorAction::DroneRoute(drone_id, waypoints)) => {
+                    if waypoints.is_empty() {
+                        self.routes.remove(&drone_id);
+                    } else {
+                        self.routes.insert(drone_id, waypoints);
+                    }
+                }
+                Ok(MonitorAction::DroneLog(drone_id, log)) => {
+                    update_drone_logs(&mut self.drone_logs, drone_id, log);
+                }
+                Ok(MonitorAction::CameraSystemStatus(status)) => {
+                    self.camera_system_status = Some(status);
+                }
                 Err(_) => break,
             }
         }
@@ -626,7 +909,11 @@ impl eframe::App for UIApplication {
         ctx.request_repaint();
 
         egui::CentralPanel::default().show(ctx, |ui| {
-            display_header(ui, &mut self.current_layout);
+            display_header(
+                ui,
+                &mut self.current_layout,
+                self.camera_system_status.as_ref(),
+            );
 
             match self.current_layout {
                 Layout::IncidentMap => display_incident_map(
@@ -635,10 +922,13 @@ impl eframe::App for UIApplication {
                     &self.drones,
                     &self.cameras,
                     &self.charging_station_coordenates,
+                    &self.anchors,
+                    &self.routes,
                     &mut self.tiles,
                     &mut self.map_memory,
                     &mut self.right_click_menu,
                     &mut self.new_incident_registration,
+                    &mut self.camera_to_move,
                     &mut self.current_layout,
                     &self.sender,
                 ),
@@ -661,11 +951,24 @@ impl eframe::App for UIApplication {
                     &mut self.new_incident_edit,
                     &mut self.current_layout,
                 ),
-                Layout::DroneList => display_drone_list(ui, &self.drones),
+                Layout::DroneList => display_drone_list(
+                    ui,
+                    &self.drones,
+                    &self.command_acks,
+                    &self.drone_logs,
+                    &self.sender,
+                ),
                 Layout::NewDrone => {
                     display_new_drone(ui, &mut self.new_drone_registration, &self.sender)
                 }
-                Layout::CameraList => display_camera_list(ui, &self.cameras),
+                Layout::CameraList => display_camera_list(
+                    ui,
+                    &self.cameras,
+                    self.camera_system_status.as_ref(),
+                    &mut self.camera_to_move,
+                    &mut self.current_layout,
+                    &self.sender,
+                ),
             }
         });
     }
@@ -732,6 +1035,9 @@ fn update_places(
             DroneStatus::Travelling(TravelLocation::Incident) => Color32::from_rgb(255, 79, 0),
             DroneStatus::Recharging => Color32::GREEN,
             DroneStatus::Interrupted => Color32::BLACK,
+            DroneStatus::Disconnected => Color32::GRAY,
+            DroneStatus::Held => Color32::from_rgb(30, 144, 255),
+            DroneStatus::Travelling(TravelLocation::Waypoint) => Color32::from_rgb(30, 144, 255),
         };
 
         if activity_cordenates.contains(&(drone.x_coordinate, drone.y_coordinate)) {
@@ -760,7 +1066,7 @@ fn update_places(
 
         let place = Place {
             position: Position::from_lon_lat(drone.x_coordinate, drone.y_coordinate),
-            label: format!("  {}", drone.id.clone()),
+            label: format!("  {}", drone.label()),
             symbol: DRONE_SYMBOL,
             style: Style {
                 label_font: FontId::proportional(15.0),
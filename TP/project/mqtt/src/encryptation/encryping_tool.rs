@@ -2,8 +2,13 @@ use aes_gcm::aead::{Aead, KeyInit, OsRng};
 use aes_gcm::{Aes256Gcm, Nonce}; // Or `Aes128Gcm`
 use rand::RngCore;
 
-/// To encrypt data, ignore the first 2 bytes corresponding to the fixed header
+/// To encrypt data, ignore the first 2 bytes corresponding to the fixed header.
+/// The data is returned as it is if the key is empty
 pub fn encrypt(data: Vec<u8>, key: &[u8]) -> Result<Vec<u8>, String> {
+    if key.is_empty() {
+        return Ok(data);
+    }
+
     let key = aes_gcm::Key::<Aes256Gcm>::from_slice(key);
     let cipher = Aes256Gcm::new(key);
 
@@ -26,13 +31,20 @@ pub fn encrypt(data: Vec<u8>, key: &[u8]) -> Result<Vec<u8>, String> {
     Ok(encrypted_data)
 }
 
-/// To decrypt data
+/// To decrypt data. The data is returned as it is if the key is empty
 pub fn decrypt(encrypted_data: &[u8], key: &[u8]) -> Result<Vec<u8>, String> {
+    if key.is_empty() {
+        return Ok(encrypted_data.to_vec());
+    }
+
     let key = aes_gcm::Key::<Aes256Gcm>::from_slice(key);
     let cipher = Aes256Gcm::new(key);
 
     // Split the nonce and ciphertext
-    let (nonce, ciphertext) = encrypted_data.split_at(12);
+    let (nonce, ciphertext) = match encrypted_data.split_at_checked(12) {
+        Some(parts) => parts,
+        None => return Err("Encrypted data is shorter than the nonce".to_string()),
+    };
     let nonce = Nonce::from_slice(nonce);
 
     match cipher.decrypt(nonce, ciphertext) {
@@ -55,4 +67,19 @@ mod tests {
 
         assert_eq!(data.to_vec(), decrypted_data);
     }
+
+    #[test]
+    fn test_empty_key_leaves_data_unencrypted() {
+        let data = b"Hello world!";
+
+        let encrypted_data = encrypt(data.to_vec(), &[]).unwrap();
+        assert_eq!(encrypted_data, data.to_vec());
+        assert_eq!(decrypt(&encrypted_data, &[]).unwrap(), data.to_vec());
+    }
+
+    #[test]
+    fn test_data_shorter_than_the_nonce_is_rejected() {
+        let key = b"01234567890123456789012345678901";
+        assert!(decrypt(b"short", key).is_err());
+    }
 }
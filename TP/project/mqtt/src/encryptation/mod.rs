@@ -2,3 +2,6 @@
 pub mod encryping_tool;
 
 pub const EXTRA_DATA_SIZE: usize = 28;
+
+/// Key that leaves the packets unencrypted, framed as plain MQTT
+pub const NO_ENCRYPTION: &[u8] = &[];
@@ -34,5 +34,7 @@ pub mod model;
 /// encryptation for packet
 mod encryptation;
 
+pub use encryptation::NO_ENCRYPTION;
+
 const PROTOCOL_NAME: [u8; 4] = [b'M', b'Q', b'T', b'T'];
 const PROTOCOL_LEVEL: u8 = 0x04;
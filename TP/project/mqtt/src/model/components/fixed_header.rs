@@ -60,6 +60,16 @@ impl FixedHeader {
             _ => self.remaining_length.value(),
         }
     }
+
+    /// Returns the length of the content that follows the fixed header when it is
+    /// encrypted with the key. Without a key the content is as long as the remaining length
+    pub fn content_length(&self, key: &[u8]) -> usize {
+        if key.is_empty() {
+            self.remaining_length.value()
+        } else {
+            self.remaining_length_encrypted()
+        }
+    }
 }
 
 #[cfg(test)]
@@ -19,39 +19,51 @@ impl TopicName {
 
     /// Converts a stream of bytes into a TopicName.
     pub fn from_bytes(stream: &mut dyn Read) -> MqttResult<Self> {
+        let topic_name = Self::from_bytes_unchecked(stream)?;
+        topic_name.validate()?;
+        Ok(topic_name)
+    }
+
+    /// Converts a stream of bytes into a TopicName without validating it,
+    /// so the receiver can decide what to do with an invalid topic.
+    pub fn from_bytes_unchecked(stream: &mut dyn Read) -> MqttResult<Self> {
         let encoded_string_topic_name = EncodedString::from_bytes(stream)?;
         let bytes = encoded_string_topic_name.content();
 
-        if bytes.is_empty() {
-            return Err(MqttError::InvalidTopicName);
-        }
-
         let server_reserved = matches!(bytes.first(), Some(&SERVER_RESERVED));
 
-        let levels_bytes: Vec<Vec<u8>> = bytes
+        let levels: Vec<Vec<u8>> = bytes
             .split(|&byte| byte == FORWARD_SLASH)
             .map(|slice: &[u8]| slice.to_vec())
             .collect();
 
-        let mut levels = vec![];
-
-        for level in levels_bytes {
-            match TopicLevel::from_bytes(level)? {
-                TopicLevel::Literal(level) => levels.push(level),
-                _ => {
-                    return Err(MqttError::InvalidWildcard(
-                        "Wildcard not allowed in topic name".to_string(),
-                    ))
-                }
-            }
-        }
-
         Ok(Self {
             levels,
             server_reserved,
         })
     }
 
+    /// Checks that the topic is not empty and has no wildcards, as the names of the topics
+    /// messages are published to must be.
+    pub fn validate(&self) -> MqttResult<()> {
+        if self.levels.len() < 2 && self.levels.iter().all(Vec::is_empty) {
+            return Err(MqttError::InvalidTopicName);
+        }
+
+        for level in &self.levels {
+            if !matches!(
+                TopicLevel::from_bytes(level.clone())?,
+                TopicLevel::Literal(_)
+            ) {
+                return Err(MqttError::InvalidWildcard(
+                    "Wildcard not allowed in topic name".to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
     /// Converts the TopicName into a vector of bytes.
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut topic_bytes = vec![];
@@ -154,6 +166,31 @@ mod tests {
         assert!(TopicName::from_bytes(bytes).is_err());
     }
 
+    #[test]
+    fn test_unchecked_topic_names_are_validated_later() {
+        let bytes = &mut from_slice(b"home/+/livingroom");
+        let topic_name = TopicName::from_bytes_unchecked(bytes).unwrap();
+        assert!(matches!(
+            topic_name.validate(),
+            Err(MqttError::InvalidWildcard(_))
+        ));
+
+        let bytes = &mut from_slice(b"");
+        let topic_name = TopicName::from_bytes_unchecked(bytes).unwrap();
+        assert!(matches!(
+            topic_name.validate(),
+            Err(MqttError::InvalidTopicName)
+        ));
+
+        assert!(TopicName::new(vec![], false).validate().is_err());
+        assert!(TopicName::new(vec![b"a#".to_vec()], false)
+            .validate()
+            .is_err());
+        assert!(TopicName::new(vec![vec![], vec![]], false)
+            .validate()
+            .is_ok());
+    }
+
     #[test]
     fn test_length() {
         let bytes = &mut from_slice(b"home/livingroom");
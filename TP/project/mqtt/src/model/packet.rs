@@ -29,7 +29,7 @@ impl Packet {
         let fixed_header = FixedHeader::from_bytes(stream)?;
 
         let packet_type = fixed_header.first_byte() >> 4;
-        let remaining_length = fixed_header.remaining_length_encrypted();
+        let remaining_length = fixed_header.content_length(key);
 
         let encrypted_content = &mut vec![0; remaining_length];
         stream.read_exact(encrypted_content)?;
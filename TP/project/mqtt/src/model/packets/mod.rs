@@ -34,5 +34,7 @@ pub const PINGRESP_PACKET_TYPE: u8 = 0xD;
 pub const DISCONNECT_PACKET_TYPE: u8 = 0xE;
 
 const RESERVED_FIXED_HEADER_FLAGS: u8 = 0x00;
+/// SUBSCRIBE and UNSUBSCRIBE packets have the QoS 1 bit of their flags set
+const SUBSCRIPTION_FIXED_HEADER_FLAGS: u8 = 0x02;
 
 const DEFAULT_VARIABLE_HEADER_LENGTH: usize = 2;
@@ -45,7 +45,8 @@ impl Publish {
 
         // Variable Header
 
-        let topic = TopicName::from_bytes(stream)?;
+        // Validated by the receiver, which decides whether an invalid topic closes the connection
+        let topic = TopicName::from_bytes_unchecked(stream)?;
 
         let package_identifier = match qos {
             QoS::AtMost => None,
@@ -1,4 +1,6 @@
-use super::{DEFAULT_VARIABLE_HEADER_LENGTH, RESERVED_FIXED_HEADER_FLAGS, SUBSCRIBE_PACKET_TYPE};
+use super::{
+    DEFAULT_VARIABLE_HEADER_LENGTH, SUBSCRIBE_PACKET_TYPE, SUBSCRIPTION_FIXED_HEADER_FLAGS,
+};
 use crate::{encrypt, FixedHeader, MqttError, MqttResult, QoS, Read, RemainingLength, TopicFilter};
 
 /// Represents a SUBSCRIBE packet of MQTT. The client uses it to subscribe to one or more topics.
@@ -21,7 +23,7 @@ impl Subscribe {
         // Fixed Header
         let fixed_header_flags = fixed_header.first_byte() & 0b0000_1111;
 
-        if fixed_header_flags != RESERVED_FIXED_HEADER_FLAGS {
+        if fixed_header_flags != SUBSCRIPTION_FIXED_HEADER_FLAGS {
             return Err(MqttError::InvalidFixedHeaderFlags);
         }
 
@@ -74,7 +76,8 @@ impl Subscribe {
         }
 
         // Fixed Header
-        let mut fixed_header_bytes = vec![SUBSCRIBE_PACKET_TYPE << 4 | RESERVED_FIXED_HEADER_FLAGS];
+        let mut fixed_header_bytes =
+            vec![SUBSCRIBE_PACKET_TYPE << 4 | SUBSCRIPTION_FIXED_HEADER_FLAGS];
 
         let remaining_length_value =
             variable_header_bytes.len() as u32 + payload_bytes.len() as u32;
@@ -132,7 +135,10 @@ mod tests {
             0x00, 0x01, 0x00, 0x06, b't', b'o', b'p', b'i', b'c', b'1', 0x00,
         ]);
 
-        let fixed_header = FixedHeader::new(SUBSCRIBE_PACKET_TYPE << 4, RemainingLength::new(11));
+        let fixed_header = FixedHeader::new(
+            SUBSCRIBE_PACKET_TYPE << 4 | SUBSCRIPTION_FIXED_HEADER_FLAGS,
+            RemainingLength::new(11),
+        );
         let subscribe = Subscribe::from_bytes(fixed_header, &mut stream).unwrap();
 
         assert_eq!(subscribe.packet_identifier(), packet_identifier);
@@ -153,7 +159,7 @@ mod tests {
         let subscribe_bytes = [&fixed_header[..], &decrypted_bytes[..]].concat();
 
         let expected_bytes = vec![
-            128_u8, 0x0b, // Fixed Header
+            130_u8, 0x0b, // Fixed Header
             0x00, 0x01, // Packet Identifier
             0x00, 6_u8, b't', b'o', b'p', b'i', b'c', b'1', 0x00, // Topic Filter
         ];
@@ -1,4 +1,6 @@
-use super::{DEFAULT_VARIABLE_HEADER_LENGTH, RESERVED_FIXED_HEADER_FLAGS, UNSUBSCRIBE_PACKET_TYPE};
+use super::{
+    DEFAULT_VARIABLE_HEADER_LENGTH, SUBSCRIPTION_FIXED_HEADER_FLAGS, UNSUBSCRIBE_PACKET_TYPE,
+};
 use crate::{encrypt, FixedHeader, MqttError, MqttResult, Read, RemainingLength, TopicFilter};
 
 /// Represents an UNSUBSCRIBE packet from MQTT. The client uses it to unsubscribe from one or more topics.
@@ -23,7 +25,7 @@ impl Unsubscribe {
         // Fixed Header
         let fixed_header_flags = fixed_header.first_byte() & 0b0000_1111;
 
-        if fixed_header_flags != RESERVED_FIXED_HEADER_FLAGS {
+        if fixed_header_flags != SUBSCRIPTION_FIXED_HEADER_FLAGS {
             return Err(MqttError::InvalidFixedHeaderFlags);
         }
 
@@ -72,7 +74,7 @@ impl Unsubscribe {
 
         // Fixed Header
         let mut fixed_header_bytes =
-            vec![UNSUBSCRIBE_PACKET_TYPE << 4 | RESERVED_FIXED_HEADER_FLAGS];
+            vec![UNSUBSCRIBE_PACKET_TYPE << 4 | SUBSCRIPTION_FIXED_HEADER_FLAGS];
 
         let remaining_length_value =
             variable_header_bytes.len() as u32 + payload_bytes.len() as u32;
@@ -131,7 +133,10 @@ mod tests {
             0x00, 0x01, 0x00, 0x06, b't', b'o', b'p', b'i', b'c', b'1',
         ]);
 
-        let fixed_header = FixedHeader::new(UNSUBSCRIBE_PACKET_TYPE << 4, RemainingLength::new(10));
+        let fixed_header = FixedHeader::new(
+            UNSUBSCRIBE_PACKET_TYPE << 4 | SUBSCRIPTION_FIXED_HEADER_FLAGS,
+            RemainingLength::new(10),
+        );
         let unsubscribe = Unsubscribe::from_bytes(fixed_header, &mut stream).unwrap();
 
         assert_eq!(unsubscribe.packet_identifier(), packet_identifier);
@@ -152,7 +157,7 @@ mod tests {
         let unsubscribe_bytes = [fixed_header_bytes, &decrypted_bytes[..]].concat();
 
         let expected_bytes = vec![
-            160_u8, 10_u8, 0x00, 0x01, 0x00, 0x06, b't', b'o', b'p', b'i', b'c', b'1',
+            162_u8, 10_u8, 0x00, 0x01, 0x00, 0x06, b't', b'o', b'p', b'i', b'c', b'1',
         ];
 
         assert_eq!(unsubscribe_bytes, expected_bytes);
@@ -0,0 +1,197 @@
+use mqtt::model::components::{
+    topic_filter::TopicFilter, topic_level::TopicLevel, topic_name::TopicName,
+};
+
+const RULE_SEPARATOR: char = ',';
+const LEVEL_SEPARATOR: char = '/';
+const ALLOW_ALL: &str = "*";
+const CLIENT_ID_PLACEHOLDER: &str = "%c";
+const SERVER_RESERVED: u8 = b'$';
+
+/// Represents the topics a client is allowed to publish and subscribe to.
+/// A missing list of rules means that the client has no restrictions for that action.
+/// Rules are topic filters that may use `+`, `#` and `%c`, which is replaced by the client id
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Acl {
+    publish_rules: Option<Vec<String>>,
+    subscribe_rules: Option<Vec<String>>,
+}
+
+impl Acl {
+    /// Builds an ACL from the publish and subscribe fields of a login entry
+    /// (e.g. `drone-data/%c,attending-incident/+`). A `*` field or a missing one allows everything
+    pub fn from_fields(publish: Option<&str>, subscribe: Option<&str>) -> Self {
+        Acl {
+            publish_rules: publish.and_then(parse_rules),
+            subscribe_rules: subscribe.and_then(parse_rules),
+        }
+    }
+
+    /// Returns the publish and subscribe fields of the ACL as they are stored in the login file
+    pub fn to_fields(&self) -> (String, String) {
+        (
+            rules_to_field(&self.publish_rules),
+            rules_to_field(&self.subscribe_rules),
+        )
+    }
+
+    /// Returns true if the ACL does not restrict any action
+    pub fn is_unrestricted(&self) -> bool {
+        self.publish_rules.is_none() && self.subscribe_rules.is_none()
+    }
+
+    /// Returns true if the client is allowed to publish to the topic
+    pub fn can_publish(&self, client_id: &[u8], topic_name: &TopicName) -> bool {
+        let rules = match &self.publish_rules {
+            Some(rules) => rules,
+            None => return true,
+        };
+
+        rules
+            .iter()
+            .filter_map(|rule| rule_to_filter(rule, client_id))
+            .any(|filter| filter.match_topic_name(topic_name.clone()))
+    }
+
+    /// Returns true if the client is allowed to subscribe to the topic filter
+    pub fn can_subscribe(&self, client_id: &[u8], topic_filter: &TopicFilter) -> bool {
+        let rules = match &self.subscribe_rules {
+            Some(rules) => rules,
+            None => return true,
+        };
+
+        rules
+            .iter()
+            .filter_map(|rule| rule_to_filter(rule, client_id))
+            .any(|filter| filter_covers(&filter, topic_filter))
+    }
+}
+
+/// Parses a comma separated list of rules. Returns None if every topic is allowed
+fn parse_rules(field: &str) -> Option<Vec<String>> {
+    let field = field.trim();
+    if field == ALLOW_ALL {
+        return None;
+    }
+
+    Some(
+        field
+            .split(RULE_SEPARATOR)
+            .map(|rule| rule.trim().to_string())
+            .filter(|rule| !rule.is_empty())
+            .collect(),
+    )
+}
+
+/// Converts a list of rules back into its login file representation
+fn rules_to_field(rules: &Option<Vec<String>>) -> String {
+    match rules {
+        Some(rules) => rules.join(&RULE_SEPARATOR.to_string()),
+        None => ALLOW_ALL.to_string(),
+    }
+}
+
+/// Converts a rule into a topic filter, replacing the client id placeholder
+fn rule_to_filter(rule: &str, client_id: &[u8]) -> Option<TopicFilter> {
+    let mut levels = vec![];
+
+    for level in rule.split(LEVEL_SEPARATOR) {
+        let level_bytes = if level == CLIENT_ID_PLACEHOLDER {
+            client_id.to_vec()
+        } else {
+            level.as_bytes().to_vec()
+        };
+        levels.push(TopicLevel::from_bytes(level_bytes).ok()?);
+    }
+
+    let server_reserved = rule.as_bytes().first() == Some(&SERVER_RESERVED);
+    Some(TopicFilter::new(levels, server_reserved))
+}
+
+/// Returns true if every topic matched by `requested` is also matched by `rule`
+fn filter_covers(rule: &TopicFilter, requested: &TopicFilter) -> bool {
+    if rule.server_reserved() != requested.server_reserved() {
+        return false;
+    }
+
+    let rule_levels = rule.levels();
+    let requested_levels = requested.levels();
+
+    for (level_index, rule_level) in rule_levels.iter().enumerate() {
+        let requested_level = match requested_levels.get(level_index) {
+            Some(level) => level,
+            None => return false,
+        };
+
+        match (rule_level, requested_level) {
+            (TopicLevel::MultiLevelWildcard, _) => return true,
+            (TopicLevel::SingleLevelWildcard, TopicLevel::MultiLevelWildcard) => return false,
+            (TopicLevel::SingleLevelWildcard, _) => continue,
+            (TopicLevel::Literal(rule_level), TopicLevel::Literal(requested_level)) => {
+                if rule_level != requested_level {
+                    return false;
+                }
+            }
+            (TopicLevel::Literal(_), _) => return false,
+        }
+    }
+
+    rule_levels.len() == requested_levels.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topic_name(topic: &str) -> TopicName {
+        let levels = topic.split('/').map(|l| l.as_bytes().to_vec()).collect();
+        TopicName::new(levels, topic.starts_with('$'))
+    }
+
+    fn topic_filter(filter: &str) -> TopicFilter {
+        rule_to_filter(filter, b"").unwrap()
+    }
+
+    #[test]
+    fn test_missing_fields_allow_everything() {
+        let acl = Acl::from_fields(None, Some("*"));
+
+        assert!(acl.is_unrestricted());
+        assert!(acl.can_publish(b"1", &topic_name("drone-data/2")));
+        assert!(acl.can_subscribe(b"1", &topic_filter("#")));
+    }
+
+    #[test]
+    fn test_publish_rules_with_client_id_placeholder() {
+        let acl = Acl::from_fields(Some("drone-data/%c,attending-incident/+"), None);
+
+        assert!(acl.can_publish(b"1", &topic_name("drone-data/1")));
+        assert!(!acl.can_publish(b"1", &topic_name("drone-data/2")));
+        assert!(acl.can_publish(b"1", &topic_name("attending-incident/42")));
+        assert!(!acl.can_publish(b"1", &topic_name("new-incident")));
+    }
+
+    #[test]
+    fn test_subscribe_rules_cover_requested_filters() {
+        let acl = Acl::from_fields(None, Some("new-incident,close-incident/+"));
+
+        assert!(acl.can_subscribe(b"1", &topic_filter("new-incident")));
+        assert!(acl.can_subscribe(b"1", &topic_filter("close-incident/7")));
+        assert!(acl.can_subscribe(b"1", &topic_filter("close-incident/+")));
+        assert!(!acl.can_subscribe(b"1", &topic_filter("close-incident/#")));
+        assert!(!acl.can_subscribe(b"1", &topic_filter("#")));
+    }
+
+    #[test]
+    fn test_to_fields() {
+        let acl = Acl::from_fields(Some("drone-data/%c, ready-incident/+"), Some("*"));
+
+        assert_eq!(
+            acl.to_fields(),
+            (
+                "drone-data/%c,ready-incident/+".to_string(),
+                "*".to_string()
+            )
+        );
+    }
+}
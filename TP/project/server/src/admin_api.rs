@@ -0,0 +1,356 @@
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, Arc},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    error::ServerResult,
+    logfile::{json_string, Logger},
+    task_channel::TaskSender,
+    task_handler::Task,
+    topic_stats::TopicStats,
+};
+
+const CLIENTS_PATH: &str = "/clients";
+const RETAINED_PATH: &str = "/retained";
+const QUEUES_PATH: &str = "/queues";
+const TOPICS_PATH: &str = "/topics";
+const MAX_REQUEST_SIZE: usize = 8192;
+/// How long a request waits for its client to send it or for the task handler to answer it
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Represents a query of the admin API, answered by the task handler
+#[derive(Debug, PartialEq)]
+pub enum AdminQuery {
+    /// Lists the known clients
+    Clients,
+    /// Describes a single client
+    Client(Vec<u8>),
+    /// Lists the topics with retained messages
+    Retained,
+    /// Lists the sizes of the offline queues
+    Queues,
+    /// Lists the traffic of every topic
+    Topics,
+    /// Closes the connection of a client
+    DisconnectClient(Vec<u8>),
+}
+
+/// Represents the state of a client as the admin API shows it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientInfo {
+    pub id: String,
+    pub connected: bool,
+    pub subscriptions: Vec<String>,
+    /// Messages queued while the client is offline or its in-flight window is full
+    pub queued_messages: usize,
+    /// QoS 1 messages sent to the client that it did not acknowledge yet
+    pub inflight_messages: usize,
+    /// Packets waiting to be written to the connected client
+    pub outbound_queue: usize,
+    /// Microseconds the last packet took to be written to the client
+    pub write_latency_us: u64,
+}
+
+/// Represents the answer of the task handler to an admin query
+#[derive(Debug)]
+pub enum AdminResponse {
+    Clients(Vec<ClientInfo>),
+    /// None if the client does not exist
+    Client(Option<ClientInfo>),
+    /// Topics and the number of messages retained in them
+    Retained(Vec<(String, usize)>),
+    /// Client ids and the number of messages queued for them
+    Queues(Vec<(String, usize)>),
+    /// Topics and the messages and bytes published in them
+    Topics(Vec<(String, TopicStats)>),
+    /// False if the client was not connected
+    Disconnected(bool),
+}
+
+/// Starts the HTTP admin API in its own thread. Every request is turned into a query
+/// that the task handler answers, so the API reads the same state the broker uses.
+/// If there is a token, the requests must send it as `Authorization: Bearer <token>`
+pub fn start(
+    address: &str,
+    token: Option<&str>,
+    task_sender: TaskSender,
+    log_file: Arc<Logger>,
+) -> ServerResult<()> {
+    let listener = TcpListener::bind(address)?;
+    let token = token.map(|token| token.to_string());
+    log_file.info(&format!("Admin API listening on {}", address));
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log_file.error(&format!("Error accepting an admin API connection: {}", e));
+                    continue;
+                }
+            };
+
+            if let Err(e) = handle_connection(stream, token.as_deref(), &task_sender) {
+                log_file.warn(&format!("Error serving an admin API request: {}", e));
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Reads a request, answers it and closes the connection
+fn handle_connection(
+    mut stream: TcpStream,
+    token: Option<&str>,
+    task_sender: &TaskSender,
+) -> ServerResult<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+
+    let response = match read_request(&mut stream)? {
+        Some(request) => respond(&request, token, task_sender),
+        None => http_response("400 Bad Request", &error_body("Invalid request")),
+    };
+
+    stream.write_all(response.as_bytes())?;
+    Ok(())
+}
+
+/// Reads the request line and headers. None if the request is too large
+/// or the connection is closed before they end
+fn read_request(stream: &mut TcpStream) -> ServerResult<Option<String>> {
+    let mut request = vec![];
+    let mut buffer = [0; 1024];
+
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buffer)?;
+        if read == 0 || request.len() + read > MAX_REQUEST_SIZE {
+            return Ok(None);
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+
+    Ok(Some(String::from_utf8_lossy(&request).to_string()))
+}
+
+/// Builds the HTTP response to a request
+fn respond(request: &str, token: Option<&str>, task_sender: &TaskSender) -> String {
+    let mut lines = request.lines();
+    let (method, path) = match lines
+        .next()
+        .and_then(|line| line.split_once(' '))
+        .and_then(|(method, rest)| Some((method, rest.split(' ').next()?)))
+    {
+        Some(request_line) => request_line,
+        None => return http_response("400 Bad Request", &error_body("Invalid request")),
+    };
+
+    if let Some(token) = token {
+        let authorized = lines.any(|line| match line.split_once(':') {
+            Some((name, value)) => {
+                name.trim().eq_ignore_ascii_case("authorization")
+                    && value.trim() == format!("Bearer {}", token)
+            }
+            None => false,
+        });
+        if !authorized {
+            return http_response("401 Unauthorized", &error_body("Invalid token"));
+        }
+    }
+
+    let query = match route(method, path) {
+        Ok(query) => query,
+        Err((status, message)) => return http_response(status, &error_body(message)),
+    };
+
+    let (response_sender, response_receiver) = mpsc::channel();
+    if task_sender
+        .send(Task::AdminQuery(query, response_sender))
+        .is_err()
+    {
+        return http_response(
+            "503 Service Unavailable",
+            &error_body("The broker is shutting down"),
+        );
+    }
+
+    match response_receiver.recv_timeout(REQUEST_TIMEOUT) {
+        Ok(AdminResponse::Client(None)) => {
+            http_response("404 Not Found", &error_body("Client not found"))
+        }
+        Ok(AdminResponse::Disconnected(false)) => {
+            http_response("409 Conflict", &error_body("Client is not connected"))
+        }
+        Ok(response) => http_response("200 OK", &render(&response)),
+        Err(_) => http_response(
+            "503 Service Unavailable",
+            &error_body("The broker did not answer"),
+        ),
+    }
+}
+
+/// Returns the query of a request, or the status and message of the error if it has none
+fn route(method: &str, path: &str) -> Result<AdminQuery, (&'static str, &'static str)> {
+    let path = path.split('?').next().unwrap_or(path);
+    let client_id = path
+        .strip_prefix(CLIENTS_PATH)
+        .and_then(|rest| rest.strip_prefix('/'))
+        .filter(|client_id| !client_id.is_empty())
+        .map(percent_decode);
+
+    match (method, path, client_id) {
+        ("GET", CLIENTS_PATH, _) => Ok(AdminQuery::Clients),
+        ("GET", RETAINED_PATH, _) => Ok(AdminQuery::Retained),
+        ("GET", QUEUES_PATH, _) => Ok(AdminQuery::Queues),
+        ("GET", TOPICS_PATH, _) => Ok(AdminQuery::Topics),
+        ("GET", _, Some(client_id)) => Ok(AdminQuery::Client(client_id)),
+        ("DELETE", _, Some(client_id)) => Ok(AdminQuery::DisconnectClient(client_id)),
+        (_, CLIENTS_PATH | RETAINED_PATH | QUEUES_PATH | TOPICS_PATH, _) | (_, _, Some(_)) => {
+            Err(("405 Method Not Allowed", "Method not allowed"))
+        }
+        _ => Err(("404 Not Found", "Unknown path")),
+    }
+}
+
+/// Decodes the `%XX` escapes of a path segment, so client ids may contain slashes
+fn percent_decode(segment: &str) -> Vec<u8> {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    decoded
+}
+
+/// Renders the answer of the task handler as JSON
+fn render(response: &AdminResponse) -> String {
+    match response {
+        AdminResponse::Clients(clients) => json_array(clients.iter().map(client_json)),
+        AdminResponse::Client(client) => client
+            .as_ref()
+            .map_or_else(|| "null".to_string(), client_json),
+        AdminResponse::Retained(topics) => json_array(topics.iter().map(|(topic, messages)| {
+            format!(
+                "{{\"topic\":{},\"messages\":{}}}",
+                json_string(topic),
+                messages
+            )
+        })),
+        AdminResponse::Queues(queues) => json_array(queues.iter().map(|(client_id, size)| {
+            format!(
+                "{{\"client_id\":{},\"queued_messages\":{}}}",
+                json_string(client_id),
+                size
+            )
+        })),
+        AdminResponse::Topics(topics) => json_array(topics.iter().map(|(topic, stats)| {
+            format!(
+                "{{\"topic\":{},\"messages\":{},\"bytes\":{}}}",
+                json_string(topic),
+                stats.messages,
+                stats.bytes
+            )
+        })),
+        AdminResponse::Disconnected(disconnected) => {
+            format!("{{\"disconnected\":{}}}", disconnected)
+        }
+    }
+}
+
+fn client_json(client: &ClientInfo) -> String {
+    format!(
+        "{{\"id\":{},\"connected\":{},\"subscriptions\":{},\"queued_messages\":{},\"inflight_messages\":{},\"outbound_queue\":{},\"write_latency_us\":{}}}",
+        json_string(&client.id),
+        client.connected,
+        json_array(
+            client
+                .subscriptions
+                .iter()
+                .map(|filter| json_string(filter))
+        ),
+        client.queued_messages,
+        client.inflight_messages,
+        client.outbound_queue,
+        client.write_latency_us
+    )
+}
+
+fn json_array(values: impl Iterator<Item = String>) -> String {
+    format!("[{}]", values.collect::<Vec<String>>().join(","))
+}
+
+fn error_body(message: &str) -> String {
+    format!("{{\"error\":{}}}", json_string(message))
+}
+
+fn http_response(status: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_are_routed() {
+        assert_eq!(route("GET", "/clients"), Ok(AdminQuery::Clients));
+        assert_eq!(route("GET", "/retained?all"), Ok(AdminQuery::Retained));
+        assert_eq!(
+            route("GET", "/clients/drone-1"),
+            Ok(AdminQuery::Client(b"drone-1".to_vec()))
+        );
+        assert_eq!(
+            route("DELETE", "/clients/%24cluster%2Fnode-a"),
+            Ok(AdminQuery::DisconnectClient(b"$cluster/node-a".to_vec()))
+        );
+        assert_eq!(
+            route("POST", "/queues").unwrap_err().0,
+            "405 Method Not Allowed"
+        );
+        assert_eq!(route("GET", "/topics"), Ok(AdminQuery::Topics));
+        assert_eq!(route("GET", "/sessions").unwrap_err().0, "404 Not Found");
+    }
+
+    #[test]
+    fn test_clients_are_rendered_as_json() {
+        let response = AdminResponse::Clients(vec![ClientInfo {
+            id: "camera-\"1\"".to_string(),
+            connected: false,
+            subscriptions: vec!["new-incident".to_string(), "drone-data/+".to_string()],
+            queued_messages: 3,
+            inflight_messages: 2,
+            outbound_queue: 1,
+            write_latency_us: 250,
+        }]);
+
+        assert_eq!(
+            render(&response),
+            "[{\"id\":\"camera-\\\"1\\\"\",\"connected\":false,\"subscriptions\":[\"new-incident\",\"drone-data/+\"],\"queued_messages\":3,\"inflight_messages\":2,\"outbound_queue\":1,\"write_latency_us\":250}]"
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::Write,
+    sync::{
+        mpsc::{self, Sender},
+        Mutex,
+    },
+    thread,
+};
+
+use super::AuthBackend;
+use crate::{
+    acl::Acl,
+    error::ServerResult,
+    password::{hash_password, is_hashed, verify_password},
+};
+
+/// Represents a client ID
+type ClientId = Vec<u8>;
+/// Represents a tuple of a username, password and the topic rules of the client
+type Logins = (Vec<u8>, Vec<u8>, Acl); // username, password, acl
+/// Represents a map of client IDs to login information
+type Clients = HashMap<ClientId, Logins>;
+
+/// Represents a change to be written in the login file
+#[derive(Debug)]
+enum LoginFileEntry {
+    /// Appends a single login entry at the end of the file
+    Append(String),
+    /// Replaces the whole content of the file with the specified login entries
+    Rewrite(Vec<String>),
+}
+
+/// Authentication backend that stores the clients in a flat login file with one
+/// `client_id = username = password [= publish rules [= subscribe rules]]` entry per line
+#[derive(Debug)]
+pub struct FileAuthBackend {
+    registered_clients: Mutex<Clients>,
+    file_sender: Mutex<Sender<LoginFileEntry>>,
+}
+
+impl FileAuthBackend {
+    /// Creates a new backend loading the clients of the login file
+    pub fn new(login_file_path: &str) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let file_path = login_file_path.to_string();
+
+        let registered_clients = Self::intials_registers(&file_path);
+
+        thread::spawn(move || {
+            for entry in receiver {
+                let result = match entry {
+                    LoginFileEntry::Append(login_entry) => OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(&file_path)
+                        .and_then(|mut file| writeln!(file, "{}", login_entry)),
+                    LoginFileEntry::Rewrite(login_entries) => {
+                        let mut content = login_entries.join("\n");
+                        content.push('\n');
+                        fs::write(&file_path, content)
+                    }
+                };
+
+                if let Err(e) = result {
+                    eprintln!("Failed to write to login file: {}", e);
+                }
+            }
+        });
+
+        Self {
+            registered_clients: Mutex::new(registered_clients),
+            file_sender: Mutex::new(sender),
+        }
+    }
+
+    /// Rewrites the login file with the current registered clients
+    fn rewrite_login_file(&self, registered_clients: &Clients) -> ServerResult<()> {
+        let mut login_entries = vec![];
+        for (client_id, logins) in registered_clients {
+            login_entries.push(Self::login_entry(client_id, logins)?);
+        }
+
+        self.file_sender
+            .lock()?
+            .send(LoginFileEntry::Rewrite(login_entries))?;
+
+        Ok(())
+    }
+
+    /// Formats a client as a line of the login file
+    fn login_entry(client_id: &[u8], logins: &Logins) -> ServerResult<String> {
+        let (username, password, acl) = logins;
+
+        let client_id = String::from_utf8(client_id.to_vec())?;
+
+        let username = String::from_utf8(username.clone())?;
+
+        let password = String::from_utf8(password.clone())?;
+
+        let mut login_entry = format!("{} = {} = {}", client_id, username, password);
+
+        if !acl.is_unrestricted() {
+            let (publish_rules, subscribe_rules) = acl.to_fields();
+            login_entry.push_str(&format!(" = {} = {}", publish_rules, subscribe_rules));
+        }
+
+        Ok(login_entry)
+    }
+
+    /// Makes the initial registrations reading the configuration file
+    fn intials_registers(path: &str) -> Clients {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(_) => return HashMap::new(),
+        };
+
+        let mut registered_clients = HashMap::new();
+
+        for line in content.lines() {
+            // client_id = username = password [= publish rules [= subscribe rules]]
+            let parts: Vec<&str> = line.split('=').map(|s| s.trim()).collect();
+            if (3..=5).contains(&parts.len()) {
+                let client_id = parts[0].as_bytes().to_vec();
+                let username = parts[1].as_bytes().to_vec();
+                let password = parts[2].as_bytes().to_vec();
+                let acl = Acl::from_fields(parts.get(3).copied(), parts.get(4).copied());
+                registered_clients.insert(client_id, (username, password, acl));
+            }
+        }
+
+        registered_clients
+    }
+}
+
+impl AuthBackend for FileAuthBackend {
+    /// Verifies the credentials against the login file.
+    /// Legacy plaintext passwords are replaced by their hash after the first successful login
+    fn authenticate(
+        &self,
+        client_id: &[u8],
+        username: &[u8],
+        password: &[u8],
+    ) -> ServerResult<Option<Acl>> {
+        let mut registered_clients = self.registered_clients.lock()?;
+
+        let (acl, needs_migration) = match registered_clients.get_mut(client_id) {
+            Some((stored_username, stored_password, acl)) => {
+                if stored_username != username || !verify_password(stored_password, password) {
+                    return Ok(None);
+                }
+
+                if is_hashed(stored_password) {
+                    (acl.clone(), false)
+                } else {
+                    *stored_password = hash_password(password).into_bytes();
+                    (acl.clone(), true)
+                }
+            }
+            None => return Ok(None),
+        };
+
+        if needs_migration {
+            self.rewrite_login_file(&registered_clients)?;
+        }
+
+        Ok(Some(acl))
+    }
+
+    /// Registers a client hashing its password and appends it to the login file
+    fn register(
+        &self,
+        client_id: &[u8],
+        username: &[u8],
+        password: &[u8],
+        acl: &Acl,
+    ) -> ServerResult<()> {
+        let logins = (
+            username.to_vec(),
+            hash_password(password).into_bytes(),
+            acl.clone(),
+        );
+        let login_entry = Self::login_entry(client_id, &logins)?;
+
+        self.registered_clients
+            .lock()?
+            .insert(client_id.to_vec(), logins);
+        self.file_sender
+            .lock()?
+            .send(LoginFileEntry::Append(login_entry))?;
+
+        Ok(())
+    }
+
+    fn is_registered(&self, client_id: &[u8]) -> ServerResult<bool> {
+        Ok(self.registered_clients.lock()?.contains_key(client_id))
+    }
+
+    fn acl(&self, client_id: &[u8]) -> ServerResult<Option<Acl>> {
+        Ok(self
+            .registered_clients
+            .lock()?
+            .get(client_id)
+            .map(|(_, _, acl)| acl.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_client() {
+        let backend = FileAuthBackend::new("test_login_file.txt");
+        let client_id = b"client1".to_vec();
+        let username = b"username".to_vec();
+        let password = b"password".to_vec();
+
+        let _ = backend.register(&client_id, &username, &password, &Acl::default());
+
+        let registered_clients = backend.registered_clients.lock().unwrap();
+        let logins = registered_clients.get(&client_id).unwrap();
+        assert_eq!(logins.0, username);
+        assert!(is_hashed(&logins.1));
+        assert!(verify_password(&logins.1, &password));
+    }
+
+    #[test]
+    fn test_legacy_password_is_migrated_on_login() {
+        let backend = FileAuthBackend::new("test_login_file.txt");
+        let client_id = b"legacy".to_vec();
+        let username = b"username".to_vec();
+        let password = b"password".to_vec();
+
+        backend.registered_clients.lock().unwrap().insert(
+            client_id.clone(),
+            (username.clone(), password.clone(), Acl::default()),
+        );
+
+        assert!(backend
+            .authenticate(&client_id, &username, &password)
+            .unwrap()
+            .is_some());
+
+        let registered_clients = backend.registered_clients.lock().unwrap();
+        let logins = registered_clients.get(&client_id).unwrap();
+        assert!(is_hashed(&logins.1));
+        assert!(verify_password(&logins.1, &password));
+    }
+}
@@ -0,0 +1,68 @@
+use std::{fmt::Debug, sync::Arc};
+
+use crate::{
+    acl::Acl,
+    config::Config,
+    error::{ServerError, ServerResult},
+};
+
+/// flat login file backend
+pub mod file_backend;
+/// SQLite database backend
+pub mod sqlite_backend;
+/// HTTP webhook backend
+pub mod webhook_backend;
+
+use file_backend::FileAuthBackend;
+use sqlite_backend::SqliteAuthBackend;
+use webhook_backend::WebhookAuthBackend;
+
+const FILE_BACKEND: &str = "file";
+const SQLITE_BACKEND: &str = "sqlite";
+const WEBHOOK_BACKEND: &str = "webhook";
+
+/// Represents a storage of client credentials used to authenticate and register clients
+pub trait AuthBackend: Debug + Send + Sync {
+    /// Verifies the credentials of a client. Returns the topic rules of the client if they are valid
+    fn authenticate(
+        &self,
+        client_id: &[u8],
+        username: &[u8],
+        password: &[u8],
+    ) -> ServerResult<Option<Acl>>;
+
+    /// Registers a client with the specified credentials and topic rules
+    fn register(
+        &self,
+        client_id: &[u8],
+        username: &[u8],
+        password: &[u8],
+        acl: &Acl,
+    ) -> ServerResult<()>;
+
+    /// Returns true if the client is registered
+    fn is_registered(&self, client_id: &[u8]) -> ServerResult<bool>;
+
+    /// Returns the topic rules of a registered client without checking its credentials,
+    /// or None if the backend can't tell them
+    fn acl(&self, _client_id: &[u8]) -> ServerResult<Option<Acl>> {
+        Ok(None)
+    }
+}
+
+/// Builds the authentication backend selected in the configuration
+pub fn backend_from_config(config: &Config) -> ServerResult<Arc<dyn AuthBackend>> {
+    match config.get_auth_backend() {
+        FILE_BACKEND => Ok(Arc::new(FileAuthBackend::new(config.get_login_file()))),
+        SQLITE_BACKEND => Ok(Arc::new(SqliteAuthBackend::new(
+            config.get_auth_database(),
+        )?)),
+        WEBHOOK_BACKEND => Ok(Arc::new(WebhookAuthBackend::new(
+            config.get_auth_webhook_url(),
+        )?)),
+        backend => Err(ServerError::AuthBackend(format!(
+            "Unknown authentication backend: {}",
+            backend
+        ))),
+    }
+}
@@ -0,0 +1,207 @@
+use std::sync::Mutex;
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::AuthBackend;
+use crate::{
+    acl::Acl,
+    error::ServerResult,
+    password::{hash_password, is_hashed, verify_password},
+};
+
+const CREATE_CLIENTS_TABLE: &str = "CREATE TABLE IF NOT EXISTS clients (
+    client_id TEXT PRIMARY KEY,
+    username TEXT NOT NULL,
+    password TEXT NOT NULL,
+    publish_rules TEXT NOT NULL DEFAULT '*',
+    subscribe_rules TEXT NOT NULL DEFAULT '*'
+)";
+
+/// Authentication backend that stores the clients in a SQLite database,
+/// so the broker can share the users table of an existing deployment
+#[derive(Debug)]
+pub struct SqliteAuthBackend {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteAuthBackend {
+    /// Opens (or creates) the database at the specified path
+    pub fn new(database_path: &str) -> ServerResult<Self> {
+        Self::from_connection(Connection::open(database_path)?)
+    }
+
+    /// Creates the clients table in the connection if it does not exist
+    fn from_connection(connection: Connection) -> ServerResult<Self> {
+        connection.execute(CREATE_CLIENTS_TABLE, [])?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+impl AuthBackend for SqliteAuthBackend {
+    /// Verifies the credentials against the clients table.
+    /// Legacy plaintext passwords are replaced by their hash after the first successful login
+    fn authenticate(
+        &self,
+        client_id: &[u8],
+        username: &[u8],
+        password: &[u8],
+    ) -> ServerResult<Option<Acl>> {
+        let connection = self.connection.lock()?;
+        let client_id = String::from_utf8_lossy(client_id);
+
+        let row = connection
+            .query_row(
+                "SELECT username, password, publish_rules, subscribe_rules
+                 FROM clients WHERE client_id = ?1",
+                params![client_id],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                    ))
+                },
+            )
+            .optional()?;
+
+        let (stored_username, stored_password, publish_rules, subscribe_rules) = match row {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+
+        if stored_username.as_bytes() != username
+            || !verify_password(stored_password.as_bytes(), password)
+        {
+            return Ok(None);
+        }
+
+        if !is_hashed(stored_password.as_bytes()) {
+            connection.execute(
+                "UPDATE clients SET password = ?1 WHERE client_id = ?2",
+                params![hash_password(password), client_id],
+            )?;
+        }
+
+        Ok(Some(Acl::from_fields(
+            Some(&publish_rules),
+            Some(&subscribe_rules),
+        )))
+    }
+
+    /// Registers a client hashing its password. An existing client with the same id is replaced
+    fn register(
+        &self,
+        client_id: &[u8],
+        username: &[u8],
+        password: &[u8],
+        acl: &Acl,
+    ) -> ServerResult<()> {
+        let (publish_rules, subscribe_rules) = acl.to_fields();
+
+        self.connection.lock()?.execute(
+            "INSERT OR REPLACE INTO clients
+             (client_id, username, password, publish_rules, subscribe_rules)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                String::from_utf8_lossy(client_id),
+                String::from_utf8_lossy(username),
+                hash_password(password),
+                publish_rules,
+                subscribe_rules
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    fn is_registered(&self, client_id: &[u8]) -> ServerResult<bool> {
+        let count: i64 = self.connection.lock()?.query_row(
+            "SELECT COUNT(*) FROM clients WHERE client_id = ?1",
+            params![String::from_utf8_lossy(client_id)],
+            |row| row.get(0),
+        )?;
+
+        Ok(count > 0)
+    }
+
+    fn acl(&self, client_id: &[u8]) -> ServerResult<Option<Acl>> {
+        let rules = self
+            .connection
+            .lock()?
+            .query_row(
+                "SELECT publish_rules, subscribe_rules FROM clients WHERE client_id = ?1",
+                params![String::from_utf8_lossy(client_id)],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()?;
+
+        Ok(rules.map(|(publish_rules, subscribe_rules)| {
+            Acl::from_fields(Some(&publish_rules), Some(&subscribe_rules))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn in_memory_backend() -> SqliteAuthBackend {
+        SqliteAuthBackend::from_connection(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_register_and_authenticate() {
+        let backend = in_memory_backend();
+        let acl = Acl::from_fields(Some("drone-data/%c"), None);
+
+        backend.register(b"1", b"drone1", b"sauron", &acl).unwrap();
+
+        assert!(backend.is_registered(b"1").unwrap());
+        assert!(!backend.is_registered(b"2").unwrap());
+        assert_eq!(
+            backend.authenticate(b"1", b"drone1", b"sauron").unwrap(),
+            Some(acl.clone())
+        );
+        assert_eq!(
+            backend.authenticate(b"1", b"drone1", b"wrong").unwrap(),
+            None
+        );
+        assert_eq!(backend.acl(b"1").unwrap(), Some(acl));
+        assert_eq!(backend.acl(b"2").unwrap(), None);
+    }
+
+    #[test]
+    fn test_legacy_password_is_migrated_on_login() {
+        let backend = in_memory_backend();
+        backend
+            .connection
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO clients (client_id, username, password) VALUES ('1', 'drone1', 'sauron')",
+                [],
+            )
+            .unwrap();
+
+        assert!(backend
+            .authenticate(b"1", b"drone1", b"sauron")
+            .unwrap()
+            .is_some());
+
+        let stored_password: String = backend
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT password FROM clients WHERE client_id = '1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(is_hashed(stored_password.as_bytes()));
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+use super::AuthBackend;
+use crate::{
+    acl::Acl,
+    error::{ServerError, ServerResult},
+};
+
+const HTTP_SCHEME: &str = "http://";
+const DEFAULT_PORT: u16 = 80;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+const AUTHENTICATE_ACTION: &str = "authenticate";
+const REGISTER_ACTION: &str = "register";
+const LOOKUP_ACTION: &str = "lookup";
+
+const HTTP_OK: u16 = 200;
+const HTTP_NO_CONTENT: u16 = 204;
+const HTTP_UNAUTHORIZED: u16 = 401;
+const HTTP_FORBIDDEN: u16 = 403;
+const HTTP_NOT_FOUND: u16 = 404;
+
+const PUBLISH_RULES_FIELD: &str = "publish";
+const SUBSCRIBE_RULES_FIELD: &str = "subscribe";
+
+/// Authentication backend that delegates every decision to an external HTTP service.
+/// The service receives a JSON body with an `action` (authenticate, register or lookup) and the
+/// client credentials, and answers 2xx to accept or 401/403/404 to reject. On authentication the
+/// response body may contain `publish = <rules>` and `subscribe = <rules>` lines
+#[derive(Debug)]
+pub struct WebhookAuthBackend {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl WebhookAuthBackend {
+    /// Creates a new backend for the specified `http://host[:port]/path` url
+    pub fn new(url: &str) -> ServerResult<Self> {
+        let without_scheme = url
+            .strip_prefix(HTTP_SCHEME)
+            .ok_or_else(|| ServerError::AuthBackend(format!("Unsupported webhook url: {}", url)))?;
+
+        let (authority, path) = match without_scheme.find('/') {
+            Some(index) => (&without_scheme[..index], &without_scheme[index..]),
+            None => (without_scheme, "/"),
+        };
+
+        let (host, port) = match authority.split_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse().map_err(|_| {
+                    ServerError::AuthBackend(format!("Invalid webhook port: {}", port))
+                })?,
+            ),
+            None => (authority, DEFAULT_PORT),
+        };
+
+        if host.is_empty() {
+            return Err(ServerError::AuthBackend(format!(
+                "Missing webhook host: {}",
+                url
+            )));
+        }
+
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    /// Sends a request to the webhook and returns the status code and the body of the response
+    fn send_request(
+        &self,
+        action: &str,
+        client_id: &[u8],
+        username: &[u8],
+        password: &[u8],
+    ) -> ServerResult<(u16, String)> {
+        let body = format!(
+            "{{\"action\":\"{}\",\"client_id\":\"{}\",\"username\":\"{}\",\"password\":\"{}\"}}",
+            action,
+            json_escape(&String::from_utf8_lossy(client_id)),
+            json_escape(&String::from_utf8_lossy(username)),
+            json_escape(&String::from_utf8_lossy(password)),
+        );
+
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.path,
+            self.host,
+            body.len(),
+            body
+        );
+
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port))?;
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+        stream.write_all(request.as_bytes())?;
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+
+        parse_response(&response)
+    }
+}
+
+impl AuthBackend for WebhookAuthBackend {
+    fn authenticate(
+        &self,
+        client_id: &[u8],
+        username: &[u8],
+        password: &[u8],
+    ) -> ServerResult<Option<Acl>> {
+        let (status, body) =
+            self.send_request(AUTHENTICATE_ACTION, client_id, username, password)?;
+
+        match status {
+            HTTP_OK | HTTP_NO_CONTENT => Ok(Some(parse_acl(&body))),
+            HTTP_UNAUTHORIZED | HTTP_FORBIDDEN | HTTP_NOT_FOUND => Ok(None),
+            status => Err(unexpected_status(status)),
+        }
+    }
+
+    fn register(
+        &self,
+        client_id: &[u8],
+        username: &[u8],
+        password: &[u8],
+        _acl: &Acl,
+    ) -> ServerResult<()> {
+        let (status, _) = self.send_request(REGISTER_ACTION, client_id, username, password)?;
+
+        match status {
+            200..=299 => Ok(()),
+            status => Err(unexpected_status(status)),
+        }
+    }
+
+    fn is_registered(&self, client_id: &[u8]) -> ServerResult<bool> {
+        let (status, _) = self.send_request(LOOKUP_ACTION, client_id, b"", b"")?;
+
+        match status {
+            200..=299 => Ok(true),
+            HTTP_NOT_FOUND => Ok(false),
+            status => Err(unexpected_status(status)),
+        }
+    }
+}
+
+/// Splits an HTTP response into its status code and body
+fn parse_response(response: &str) -> ServerResult<(u16, String)> {
+    let status = response
+        .lines()
+        .next()
+        .and_then(|status_line| status_line.split_whitespace().nth(1))
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| ServerError::AuthBackend("Invalid webhook response".to_string()))?;
+
+    let body = match response.split_once("\r\n\r\n") {
+        Some((_, body)) => body.to_string(),
+        None => String::new(),
+    };
+
+    Ok((status, body))
+}
+
+/// Reads the optional topic rules of an authentication response
+fn parse_acl(body: &str) -> Acl {
+    let mut publish_rules = None;
+    let mut subscribe_rules = None;
+
+    for line in body.lines() {
+        if let Some((field, rules)) = line.split_once('=') {
+            match field.trim() {
+                PUBLISH_RULES_FIELD => publish_rules = Some(rules.trim()),
+                SUBSCRIBE_RULES_FIELD => subscribe_rules = Some(rules.trim()),
+                _ => {}
+            }
+        }
+    }
+
+    Acl::from_fields(publish_rules, subscribe_rules)
+}
+
+/// Builds the error for a status code the backend does not understand
+fn unexpected_status(status: u16) -> ServerError {
+    ServerError::AuthBackend(format!("Unexpected webhook response status: {}", status))
+}
+
+/// Escapes a string so it can be placed inside a JSON string
+fn json_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::TcpListener, thread};
+
+    #[test]
+    fn test_parse_url() {
+        let backend = WebhookAuthBackend::new("http://localhost:9000/auth").unwrap();
+        assert_eq!(backend.host, "localhost");
+        assert_eq!(backend.port, 9000);
+        assert_eq!(backend.path, "/auth");
+
+        assert!(WebhookAuthBackend::new("https://localhost/auth").is_err());
+    }
+
+    #[test]
+    fn test_authenticate_against_webhook() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buffer = [0; 1024];
+            let read = stream.read(&mut buffer).unwrap();
+            let request = String::from_utf8_lossy(&buffer[..read]);
+            assert!(request.contains("\"client_id\":\"1\""));
+
+            let body = "publish = drone-data/%c\n";
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+
+        let backend = WebhookAuthBackend::new(&format!("http://127.0.0.1:{}/auth", port)).unwrap();
+        let acl = backend.authenticate(b"1", b"drone1", b"sauron").unwrap();
+
+        assert_eq!(acl, Some(Acl::from_fields(Some("drone-data/%c"), None)));
+    }
+
+    #[test]
+    fn test_json_escape() {
+        assert_eq!(json_escape("a\"b\\c"), "a\\\"b\\\\c");
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::error::{ServerError, ServerResult};
+
+/// Version of the layout written by `write_backup`. Bump it and add a migration to
+/// `migrate` whenever the serialized data changes
+pub const BACKUP_VERSION: u32 = 1;
+
+/// Tag of the first line of the backup, which holds the version of its layout
+const VERSION_TAG: &str = "VERSION";
+/// Tag of the last line of the backup, which holds the checksum of the lines before it
+const CHECKSUM_TAG: &str = "CRC";
+const SEPARATOR: char = ';';
+const TEMP_FILE_EXTENSION: &str = "tmp";
+const CRC32_POLYNOMIAL: u32 = 0xEDB8_8320;
+
+/// Writes the serialized data in the backup file between a version header and a checksum.
+/// The data goes to a temporary file that replaces the backup once it is on disk,
+/// so a crash in the middle of the write leaves the previous backup untouched
+pub fn write_backup(backup_file_path: &str, serialized_data: &str) -> io::Result<()> {
+    let path = Path::new(backup_file_path);
+    let temp_path = path.with_extension(TEMP_FILE_EXTENSION);
+
+    let content = format!(
+        "{}{}{}\n{}",
+        VERSION_TAG, SEPARATOR, BACKUP_VERSION, serialized_data
+    );
+
+    let mut file = File::create(&temp_path)?;
+    file.write_all(content.as_bytes())?;
+    writeln!(
+        file,
+        "{}{}{:08X}",
+        CHECKSUM_TAG,
+        SEPARATOR,
+        crc32(content.as_bytes())
+    )?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)?;
+
+    // The rename is only durable once the directory is synced too
+    if let Some(directory) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        File::open(directory)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
+/// Reads the backup file and migrates its data to the current layout.
+/// Returns the version the backup was written with and the migrated data.
+/// Fails if the checksum is missing or does not match the content, or the version is unknown.
+/// Backups written before versioning have neither header nor checksum and are read as version 0
+pub fn read_backup(backup_file_path: &str) -> ServerResult<(u32, String)> {
+    let content = fs::read_to_string(backup_file_path)?;
+
+    let version_prefix = format!("{}{}", VERSION_TAG, SEPARATOR);
+    if !content.starts_with(&version_prefix) {
+        return Ok((0, migrate(0, content)?));
+    }
+
+    let content = verify_checksum(&content)?;
+    let (header, data) = content.split_once('\n').unwrap_or((content, ""));
+    let version = header[version_prefix.len()..]
+        .parse()
+        .map_err(|_| ServerError::InvalidBackup("the version is not a number".to_string()))?;
+
+    Ok((version, migrate(version, data.to_string())?))
+}
+
+/// Converts the data of a backup written with an older version to the current layout
+fn migrate(version: u32, data: String) -> ServerResult<String> {
+    match version {
+        // Unversioned backups share the layout of the first version
+        0 | BACKUP_VERSION => Ok(data),
+        version => Err(ServerError::InvalidBackup(format!(
+            "unknown version {}",
+            version
+        ))),
+    }
+}
+
+/// Returns the data of a backup without its checksum line if the checksum is valid
+fn verify_checksum(content: &str) -> ServerResult<&str> {
+    let body = content.strip_suffix('\n').unwrap_or(content);
+    let (data, checksum_line) = match body.rfind('\n') {
+        Some(index) => (&content[..index + 1], &body[index + 1..]),
+        None => ("", body),
+    };
+
+    let expected_checksum = checksum_line
+        .strip_prefix(CHECKSUM_TAG)
+        .and_then(|checksum| checksum.strip_prefix(SEPARATOR))
+        .and_then(|checksum| u32::from_str_radix(checksum, 16).ok())
+        .ok_or_else(|| ServerError::InvalidBackup("the checksum is missing".to_string()))?;
+
+    if crc32(data.as_bytes()) != expected_checksum {
+        return Err(ServerError::InvalidBackup(
+            "the checksum does not match the content".to_string(),
+        ));
+    }
+
+    Ok(data)
+}
+
+/// Computes the CRC-32 (IEEE) of the data
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (CRC32_POLYNOMIAL & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_write_and_read_backup() {
+        let path = "test_backup_file.txt";
+        let data = "R;0001;0002\nC;0003;0004\n";
+
+        write_backup(path, data).unwrap();
+        assert_eq!(
+            read_backup(path).unwrap(),
+            (BACKUP_VERSION, data.to_string())
+        );
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_unversioned_backup_is_migrated() {
+        let path = "test_unversioned_backup_file.txt";
+        let data = "R;0001;0002\nC;0003;0004\n";
+
+        fs::write(path, data).unwrap();
+        assert_eq!(read_backup(path).unwrap(), (0, data.to_string()));
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_unknown_version_is_rejected() {
+        assert!(migrate(BACKUP_VERSION + 1, String::new()).is_err());
+    }
+
+    #[test]
+    fn test_corrupted_backup_is_rejected() {
+        let data = "VERSION;1\nR;0001;0002\nC;0003;0004\n";
+        let content = format!("{}CRC;{:08X}\n", data, crc32(data.as_bytes()));
+        assert_eq!(verify_checksum(&content).unwrap(), data);
+
+        let truncated = content.replace("C;0003;0004\n", "C;00");
+        assert!(verify_checksum(&truncated).is_err());
+        assert!(verify_checksum(data).is_err());
+    }
+}
@@ -0,0 +1,472 @@
+use std::{
+    io::Write,
+    net::{Shutdown, TcpStream},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use mqtt::model::{
+    components::{
+        encoded_string::EncodedString, login::Login, qos::QoS, topic_filter::TopicFilter,
+        topic_level::TopicLevel, topic_name::TopicName,
+    },
+    packet::Packet,
+    packets::{
+        connect::Connect, pingreq::Pingreq, puback::Puback, publish::Publish, subscribe::Subscribe,
+    },
+    return_codes::connect_return_code::ConnectReturnCode,
+};
+
+use crate::{
+    config::Config,
+    error::{ServerError, ServerResult},
+    logfile::Logger,
+    task_channel::TaskSender,
+    task_handler::Task,
+};
+
+const LEVEL_SEPARATOR: char = '/';
+const SERVER_RESERVED: u8 = b'$';
+/// How long to wait before connecting again to another broker
+pub const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+/// How often another broker is pinged when there is nothing to send to it
+pub const PING_INTERVAL: Duration = Duration::from_secs(30);
+/// How many messages can wait to be forwarded before new ones are dropped
+const OUTGOING_QUEUE_SIZE: usize = 1024;
+const SUBSCRIBE_PACKET_ID: u16 = 1;
+
+/// Represents the settings of the bridge to an upstream broker.
+/// Local topics are mapped to the upstream broker under the remote prefix (e.g. `drone-data`
+/// is `site-a/drone-data` with the `site-a` prefix). The incoming and outgoing topics should
+/// not overlap, as the upstream broker would send the forwarded messages back
+#[derive(Debug, Clone)]
+pub struct BridgeSettings {
+    address: String,
+    client_id: String,
+    username: String,
+    password: String,
+    topics_in: Vec<TopicFilter>,
+    topics_out: Vec<TopicFilter>,
+    remote_prefix: Vec<Vec<u8>>,
+}
+
+impl BridgeSettings {
+    /// Reads the bridge settings from the configuration. Returns None if the bridge is disabled
+    pub fn from_config(config: &Config) -> ServerResult<Option<Self>> {
+        let address = match config.get_bridge_address() {
+            Some(address) => address.to_string(),
+            None => return Ok(None),
+        };
+
+        let remote_prefix = config
+            .get_bridge_remote_prefix()
+            .trim_end_matches(LEVEL_SEPARATOR);
+        if remote_prefix.as_bytes().first() == Some(&SERVER_RESERVED) {
+            return Err(ServerError::ArgumentError(
+                "The bridge remote prefix can't be server reserved".to_string(),
+            ));
+        }
+        let remote_prefix = if remote_prefix.is_empty() {
+            vec![]
+        } else {
+            remote_prefix
+                .split(LEVEL_SEPARATOR)
+                .map(|level| level.as_bytes().to_vec())
+                .collect()
+        };
+
+        Ok(Some(BridgeSettings {
+            address,
+            client_id: config.get_bridge_client_id().to_string(),
+            username: config.get_bridge_username().to_string(),
+            password: config.get_bridge_password().to_string(),
+            topics_in: parse_filters(config.get_bridge_topics_in())?,
+            topics_out: parse_filters(config.get_bridge_topics_out())?,
+            remote_prefix,
+        }))
+    }
+
+    /// Returns the topic a local topic has in the upstream broker
+    fn remote_topic(&self, topic_name: &TopicName) -> TopicName {
+        let mut levels = self.remote_prefix.clone();
+        levels.extend(topic_name.levels().iter().cloned());
+        TopicName::new(levels, false)
+    }
+
+    /// Returns the filter a local filter has in the upstream broker
+    fn remote_filter(&self, topic_filter: &TopicFilter) -> TopicFilter {
+        let mut levels: Vec<TopicLevel> = self
+            .remote_prefix
+            .iter()
+            .map(|level| TopicLevel::Literal(level.clone()))
+            .collect();
+        levels.extend(topic_filter.levels().iter().cloned());
+        TopicFilter::new(levels, false)
+    }
+
+    /// Returns the local topic of a topic of the upstream broker, if it is one of the incoming topics
+    fn local_topic(&self, topic_name: &TopicName) -> Option<TopicName> {
+        let levels = topic_name
+            .levels()
+            .strip_prefix(self.remote_prefix.as_slice())?;
+        let first_level = levels.first()?;
+        if first_level.first() == Some(&SERVER_RESERVED) {
+            return None;
+        }
+
+        let local_topic = TopicName::new(levels.to_vec(), false);
+        self.topics_in
+            .iter()
+            .any(|filter| filter.match_topic_name(local_topic.clone()))
+            .then_some(local_topic)
+    }
+
+    /// Returns true if the local topic is forwarded to the upstream broker
+    fn is_outgoing(&self, topic_name: &TopicName) -> bool {
+        !topic_name.server_reserved()
+            && self
+                .topics_out
+                .iter()
+                .any(|filter| filter.match_topic_name(topic_name.clone()))
+    }
+}
+
+/// Parses the topic filters of the bridge configuration. Server reserved topics can't be bridged
+fn parse_filters(filters: &[String]) -> ServerResult<Vec<TopicFilter>> {
+    let mut topic_filters = vec![];
+
+    for filter in filters {
+        if filter.as_bytes().first() == Some(&SERVER_RESERVED) {
+            return Err(ServerError::ArgumentError(format!(
+                "Server reserved topics can't be bridged: {}",
+                filter
+            )));
+        }
+
+        let mut levels = vec![];
+        for level in filter.split(LEVEL_SEPARATOR) {
+            let level = TopicLevel::from_bytes(level.as_bytes().to_vec()).map_err(|_| {
+                ServerError::ArgumentError(format!("Invalid bridge topic filter: {}", filter))
+            })?;
+            levels.push(level);
+        }
+        topic_filters.push(TopicFilter::new(levels, false));
+    }
+
+    Ok(topic_filters)
+}
+
+/// Represents the bridge to an upstream broker. The server connects to it as a client,
+/// forwards the outgoing topics to it and publishes locally the incoming topics it receives
+#[derive(Debug)]
+pub struct Bridge {
+    settings: BridgeSettings,
+    sender: SyncSender<Publish>,
+    log_file: Arc<Logger>,
+}
+
+impl Bridge {
+    /// Starts the thread that keeps the bridge connected to the upstream broker.
+    /// The messages received from it are sent to the task handler
+    pub fn start(
+        settings: BridgeSettings,
+        key: Vec<u8>,
+        task_sender: TaskSender,
+        log_file: Arc<Logger>,
+    ) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(OUTGOING_QUEUE_SIZE);
+
+        let bridge_settings = settings.clone();
+        let bridge_log_file = log_file.clone();
+        thread::spawn(move || {
+            run_bridge(bridge_settings, key, receiver, task_sender, bridge_log_file)
+        });
+
+        Bridge {
+            settings,
+            sender,
+            log_file,
+        }
+    }
+
+    /// Forwards the message to the upstream broker if its topic is one of the outgoing topics
+    pub fn forward(&self, publish: &Publish) {
+        if !self.settings.is_outgoing(publish.topic()) {
+            return;
+        }
+
+        let remote_publish = Publish::new(
+            false,
+            QoS::AtMost,
+            publish.retain(),
+            self.settings.remote_topic(publish.topic()),
+            None,
+            publish.message().clone(),
+        );
+
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(remote_publish) {
+            self.log_file.warn(&format!(
+                "Bridge queue is full. Dropping message to topic: {}",
+                publish.topic()
+            ));
+        }
+    }
+}
+
+/// Connects to the upstream broker and forwards messages until the server drops the bridge,
+/// connecting again whenever the connection is lost
+fn run_bridge(
+    settings: BridgeSettings,
+    key: Vec<u8>,
+    receiver: Receiver<Publish>,
+    task_sender: TaskSender,
+    log_file: Arc<Logger>,
+) {
+    loop {
+        let stream = match connect_to_broker(
+            &settings.address,
+            &settings.client_id,
+            &settings.username,
+            &settings.password,
+            &key,
+        ) {
+            Ok(stream) => stream,
+            Err(e) => {
+                log_file.warn(&format!(
+                    "Bridge could not connect to {}: {}. Retrying in {} seconds",
+                    settings.address,
+                    e,
+                    RECONNECT_INTERVAL.as_secs()
+                ));
+                thread::sleep(RECONNECT_INTERVAL);
+                continue;
+            }
+        };
+        log_file.info(&format!("Bridge connected to {}", settings.address));
+
+        let result = stream
+            .try_clone()
+            .map_err(ServerError::from)
+            .and_then(|reader_stream| {
+                let writer_stream = Arc::new(Mutex::new(stream));
+                subscribe_upstream(&settings, &writer_stream, &key)?;
+
+                let reader_settings = settings.clone();
+                let reader_writer_stream = writer_stream.clone();
+                let reader_key = key.clone();
+                let reader_task_sender = task_sender.clone();
+                thread::spawn(move || {
+                    read_upstream(
+                        reader_settings,
+                        reader_stream,
+                        reader_writer_stream,
+                        reader_key,
+                        reader_task_sender,
+                    )
+                });
+
+                forward_messages(&writer_stream, &receiver, &key)
+            });
+
+        match result {
+            Ok(()) => return,
+            Err(e) => log_file.warn(&format!(
+                "Bridge lost the connection to {}: {}. Reconnecting",
+                settings.address, e
+            )),
+        }
+        thread::sleep(RECONNECT_INTERVAL);
+    }
+}
+
+/// Connects as a client to another broker. No login is sent if the username is empty
+pub fn connect_to_broker(
+    address: &str,
+    client_id: &str,
+    username: &str,
+    password: &str,
+    key: &[u8],
+) -> ServerResult<TcpStream> {
+    let mut stream = TcpStream::connect(address)?;
+
+    let client_id = EncodedString::new(client_id.as_bytes().to_vec());
+    let login = if username.is_empty() {
+        None
+    } else {
+        Some(Login::new(
+            EncodedString::new(username.as_bytes().to_vec()),
+            Some(EncodedString::new(password.as_bytes().to_vec())),
+        ))
+    };
+    let connect = Connect::new(false, 0, client_id, None, login);
+    stream.write_all(&connect.to_bytes(key))?;
+
+    match Packet::from_bytes(&mut stream, key)? {
+        Packet::Connack(connack) => match connack.connect_return_code() {
+            ConnectReturnCode::ConnectionAccepted => Ok(stream),
+            code => Err(ServerError::ClientConnection(format!(
+                "Connection refused: {:?}",
+                code
+            ))),
+        },
+        _ => Err(ServerError::ClientConnection(
+            "No CONNACK received".to_string(),
+        )),
+    }
+}
+
+/// Subscribes to the incoming topics in the upstream broker
+fn subscribe_upstream(
+    settings: &BridgeSettings,
+    stream: &Mutex<TcpStream>,
+    key: &[u8],
+) -> ServerResult<()> {
+    if settings.topics_in.is_empty() {
+        return Ok(());
+    }
+
+    let topics = settings
+        .topics_in
+        .iter()
+        .map(|filter| (settings.remote_filter(filter), QoS::AtMost))
+        .collect();
+    let subscribe = Subscribe::new(SUBSCRIBE_PACKET_ID, topics);
+    stream.lock()?.write_all(&subscribe.to_bytes(key))?;
+    Ok(())
+}
+
+/// Reads the packets of the upstream broker and sends the messages of the incoming topics
+/// to the task handler. Shuts the connection down when it can't be read anymore
+fn read_upstream(
+    settings: BridgeSettings,
+    mut stream: TcpStream,
+    writer_stream: Arc<Mutex<TcpStream>>,
+    key: Vec<u8>,
+    task_sender: TaskSender,
+) {
+    loop {
+        let publish = match Packet::from_bytes(&mut stream, &key) {
+            Ok(Packet::Publish(publish)) => publish,
+            Ok(_) => continue,
+            Err(_) => {
+                let _ = stream.shutdown(Shutdown::Both);
+                return;
+            }
+        };
+
+        if &QoS::AtMost != publish.qos() {
+            let puback = Puback::new(publish.package_identifier());
+            if let Ok(mut writer_stream) = writer_stream.lock() {
+                let _ = writer_stream.write_all(&puback.to_bytes(&key));
+            }
+        }
+
+        let local_topic = match settings.local_topic(publish.topic()) {
+            Some(local_topic) => local_topic,
+            None => continue,
+        };
+        let local_publish = Publish::new(
+            false,
+            QoS::AtMost,
+            publish.retain(),
+            local_topic,
+            None,
+            publish.message().clone(),
+        );
+
+        if task_sender
+            .send(Task::BridgePublish(local_publish))
+            .is_err()
+        {
+            let _ = stream.shutdown(Shutdown::Both);
+            return;
+        }
+    }
+}
+
+/// Writes the outgoing messages to the upstream broker, pinging it when there are none.
+/// Returns Ok when the server drops the bridge, or an error when the connection is lost
+fn forward_messages(
+    stream: &Mutex<TcpStream>,
+    receiver: &Receiver<Publish>,
+    key: &[u8],
+) -> ServerResult<()> {
+    loop {
+        let bytes = match receiver.recv_timeout(PING_INTERVAL) {
+            Ok(publish) => publish.to_bytes(key),
+            Err(RecvTimeoutError::Timeout) => Pingreq::new().to_bytes(key),
+            Err(RecvTimeoutError::Disconnected) => {
+                let _ = stream.lock()?.shutdown(Shutdown::Both);
+                return Ok(());
+            }
+        };
+
+        let mut stream = stream.lock()?;
+        if let Err(e) = stream.write_all(&bytes) {
+            let _ = stream.shutdown(Shutdown::Both);
+            return Err(e.into());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(topics_in: &[&str], topics_out: &[&str], remote_prefix: &str) -> BridgeSettings {
+        let to_strings =
+            |topics: &[&str]| -> Vec<String> { topics.iter().map(|t| t.to_string()).collect() };
+
+        BridgeSettings {
+            address: "127.0.0.1:1883".to_string(),
+            client_id: "bridge".to_string(),
+            username: String::new(),
+            password: String::new(),
+            topics_in: parse_filters(&to_strings(topics_in)).unwrap(),
+            topics_out: parse_filters(&to_strings(topics_out)).unwrap(),
+            remote_prefix: remote_prefix
+                .split(LEVEL_SEPARATOR)
+                .map(|level| level.as_bytes().to_vec())
+                .collect(),
+        }
+    }
+
+    fn topic_name(topic: &str) -> TopicName {
+        TopicName::new(
+            topic
+                .split(LEVEL_SEPARATOR)
+                .map(|level| level.as_bytes().to_vec())
+                .collect(),
+            false,
+        )
+    }
+
+    #[test]
+    fn test_topics_are_mapped_under_the_remote_prefix() {
+        let settings = settings(&["new-incident/#"], &["drone-data/+"], "site-a");
+
+        assert!(settings.is_outgoing(&topic_name("drone-data/1")));
+        assert!(!settings.is_outgoing(&topic_name("camera-data")));
+        assert_eq!(
+            settings.remote_topic(&topic_name("drone-data/1")),
+            topic_name("site-a/drone-data/1")
+        );
+
+        assert_eq!(
+            settings.local_topic(&topic_name("site-a/new-incident/2")),
+            Some(topic_name("new-incident/2"))
+        );
+        assert_eq!(settings.local_topic(&topic_name("new-incident/2")), None);
+        assert_eq!(settings.local_topic(&topic_name("site-a/drone-data")), None);
+    }
+
+    #[test]
+    fn test_invalid_filters_are_rejected() {
+        assert!(parse_filters(&["$admin".to_string()]).is_err());
+        assert!(parse_filters(&["drone-data/a#".to_string()]).is_err());
+    }
+}
@@ -1,12 +1,16 @@
 use std::fmt;
-use std::io::Write;
-use std::net::TcpStream;
+use std::io::{ErrorKind, Write};
+use std::net::{Shutdown, SocketAddr};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
 use mqtt::model::components::topic_filter::TopicFilter;
-use mqtt::model::components::topic_name::TopicName;
-use mqtt::model::packets::publish::Publish;
+use mqtt::model::packets::{disconnect::Disconnect, publish::Publish};
+
+use crate::client_stats::{ClientStats, ConnectionTraffic};
+use crate::client_stream::ClientStream;
+use crate::client_writer::ClientWriter;
 
 /// Represents the state of the client in the server
 /// The client is identified by its id and has a list of subscriptions of topics
@@ -15,22 +19,57 @@ pub struct Client {
     pub id: Vec<u8>,
     pub subscriptions: Vec<TopicFilter>,
     pub alive: AtomicBool,
-    pub stream: Option<TcpStream>,
+    pub stream: Option<ClientWriter>,
+    /// True while the session restored from a backup wasn't resumed by a reconnection
+    restored: bool,
+    /// True if the session of the client lasts only as long as its connection
+    clean_session: bool,
+    /// Seconds the client may stay silent, 0 if it has no limit
+    keep_alive: u16,
+    /// Message published on behalf of the client if it loses its connection
+    will: Option<Publish>,
+    /// Address the current connection comes from. None for Unix domain sockets
+    address: Option<SocketAddr>,
+    /// Instant the client opened its current connection
+    connected_at: Instant,
+    /// Traffic of the client during its session
+    stats: ClientStats,
 }
 
 impl Client {
     pub fn new(
         id: Vec<u8>,
-        stream: Option<TcpStream>,
-        _clean_session: bool,
-        _keep_alive: u16,
+        stream: Option<ClientStream>,
+        clean_session: bool,
+        keep_alive: u16,
     ) -> Client {
         Client {
             id,
             subscriptions: Vec::new(),
             alive: AtomicBool::new(true),
-            stream,
+            address: stream.as_ref().and_then(|stream| stream.peer_addr().ok()),
+            stream: stream.map(ClientWriter::new),
+            restored: false,
+            clean_session,
+            keep_alive,
+            will: None,
+            connected_at: Instant::now(),
+            stats: ClientStats::new(),
+        }
+    }
+
+    /// Counts the packets sent to the client in the counters of its connection
+    pub fn with_traffic(mut self, traffic: Arc<ConnectionTraffic>) -> Self {
+        if let Some(stream) = &mut self.stream {
+            stream.set_traffic(traffic);
         }
+        self
+    }
+
+    /// Sets the message published on behalf of the client if it loses its connection
+    pub fn with_will(mut self, will: Option<Publish>) -> Self {
+        self.will = will;
+        self
     }
 
     pub fn new_from_backup(id: Vec<u8>, subscriptions: Vec<TopicFilter>) -> Client {
@@ -39,12 +78,21 @@ impl Client {
             subscriptions,
             alive: AtomicBool::new(true),
             stream: None,
+            restored: true,
+            clean_session: false,
+            keep_alive: 0,
+            will: None,
+            address: None,
+            connected_at: Instant::now(),
+            stats: ClientStats::new(),
         }
     }
 
     /// Adds a subscription to a client
     pub fn add_subscription(&mut self, topic: TopicFilter) {
-        self.subscriptions.push(topic);
+        if !self.subscriptions.contains(&topic) {
+            self.subscriptions.push(topic);
+        }
     }
 
     /// Unsubscribes the client from a topic
@@ -52,13 +100,6 @@ impl Client {
         self.subscriptions.retain(|t| t != topic);
     }
 
-    /// Checks if the client is subscribed to a topic
-    pub fn is_subscribed(&self, topic: &TopicName) -> bool {
-        self.subscriptions
-            .iter()
-            .any(|t| t.match_topic_name(topic.clone()))
-    }
-
     /// Sends a message to the client
     pub fn send_message(
         &self,
@@ -78,16 +119,112 @@ impl Client {
         };
         match stream.write_all(publish_packet.to_bytes(key).as_slice()) {
             Ok(_) => {
+                self.stats.record_out(publish_packet.message().len());
                 logfile.log_sent_message(message_str, client_id_str);
             }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                logfile.log_slow_consumer(message_str, client_id_str)
+            }
             Err(_) => logfile.log_sending_message_error(message_str, client_id_str),
         }
     }
 
+    /// Starts the thread that writes the packets queued for the client
+    pub fn start_writer(
+        &mut self,
+        queue_size: usize,
+        disconnect_slow_consumer: bool,
+    ) -> std::io::Result<()> {
+        match &mut self.stream {
+            Some(stream) => stream.start(queue_size, disconnect_slow_consumer),
+            None => Ok(()),
+        }
+    }
+
+    /// Closes the connection with the client. The client thread will notice it and disconnect it
+    pub fn shutdown(&self) {
+        if let Some(stream) = &self.stream {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    /// Sends a DISCONNECT packet and closes the connection once every queued packet was written
+    pub fn disconnect(&mut self, key: &[u8]) {
+        if let Some(stream) = &mut self.stream {
+            let _ = (&*stream).write_all(&Disconnect::new().to_bytes(key));
+            stream.flush_and_stop();
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    /// Marks the session as resumed. Returns true if it was restored from a backup
+    /// and this is the first reconnection since
+    pub fn resume(&mut self) -> bool {
+        std::mem::replace(&mut self.restored, false)
+    }
+
     /// Gets the id of the client
     pub fn id(&self) -> Vec<u8> {
         self.id.clone()
     }
+
+    /// Takes the connection, session flag, keep alive and will of a client that connected again
+    /// with the same id
+    pub fn reconnect(&mut self, client: Client) {
+        self.stream = client.stream;
+        self.clean_session = client.clean_session;
+        self.keep_alive = client.keep_alive;
+        self.will = client.will;
+        self.address = client.address;
+        self.connected_at = client.connected_at;
+    }
+
+    /// Returns the message published on behalf of the client if it loses its connection
+    pub fn will(&self) -> Option<&Publish> {
+        self.will.as_ref()
+    }
+
+    /// Returns true if the connection was closed because the client stopped reading
+    pub fn is_slow_consumer(&self) -> bool {
+        self.stream
+            .as_ref()
+            .is_some_and(ClientWriter::is_slow_consumer)
+    }
+
+    /// Returns true if the session of the client lasts only as long as its connection
+    pub fn clean_session(&self) -> bool {
+        self.clean_session
+    }
+
+    /// Returns how long the current connection of the client has been open
+    pub fn connection_duration(&self) -> Duration {
+        self.connected_at.elapsed()
+    }
+
+    /// Returns the time at which the client opened its current connection
+    pub fn connected_at(&self) -> SystemTime {
+        SystemTime::now() - self.connected_at.elapsed()
+    }
+
+    /// Returns the address the current connection comes from. None for Unix domain sockets
+    pub fn address(&self) -> Option<SocketAddr> {
+        self.address
+    }
+
+    /// Returns the packets that went through the current connection of the client
+    pub fn traffic(&self) -> Option<&ConnectionTraffic> {
+        self.stream.as_ref().map(ClientWriter::traffic)
+    }
+
+    /// Returns the traffic of the client during its session
+    pub fn stats(&self) -> &ClientStats {
+        &self.stats
+    }
+
+    /// Returns the seconds the client may stay silent, 0 if it has no limit
+    pub fn keep_alive(&self) -> u16 {
+        self.keep_alive
+    }
 }
 
 impl fmt::Display for Client {
@@ -112,7 +249,10 @@ impl fmt::Display for Client {
 
 #[cfg(test)]
 mod tests {
-    use std::{net::TcpListener, vec};
+    use std::{
+        net::{TcpListener, TcpStream},
+        vec,
+    };
 
     use mqtt::model::components::topic_level::TopicLevel;
 
@@ -126,7 +266,7 @@ mod tests {
 
     fn setup_client() -> Client {
         let stream = setup_stream();
-        Client::new(vec![1, 2, 3], Some(stream), true, 60)
+        Client::new(vec![1, 2, 3], Some(ClientStream::Tcp(stream)), true, 60)
     }
 
     fn setup_topic_filter() -> Vec<TopicFilter> {
@@ -153,6 +293,25 @@ mod tests {
         assert!(client.alive.load(Ordering::Relaxed));
     }
 
+    #[test]
+    fn test_restored_client_is_resumed_once() {
+        let mut client = Client::new_from_backup(vec![1, 2, 3], setup_topic_filter());
+        assert!(client.resume());
+        assert!(!client.resume());
+        assert!(!setup_client().resume());
+    }
+
+    #[test]
+    fn test_reconnection_takes_the_session_flag_of_the_new_connection() {
+        let mut client = Client::new_from_backup(vec![1, 2, 3], setup_topic_filter());
+        assert!(!client.clean_session());
+
+        client.reconnect(setup_client());
+        assert!(client.clean_session());
+        assert_eq!(client.keep_alive(), 60);
+        assert_eq!(client.subscriptions.len(), 2);
+    }
+
     #[test]
     fn test_add_subscription() {
         let mut client = setup_client();
@@ -160,6 +319,10 @@ mod tests {
         client.add_subscription(topic[0].clone());
         assert_eq!(client.subscriptions.len(), 1);
         assert_eq!(client.subscriptions[0], topic[0]);
+
+        // Subscribing again to the same topic filter does not add it twice
+        client.add_subscription(topic[0].clone());
+        assert_eq!(client.subscriptions.len(), 1);
     }
 
     #[test]
@@ -171,17 +334,6 @@ mod tests {
         assert!(client.subscriptions.is_empty());
     }
 
-    #[test]
-    fn test_is_subscribed() {
-        let mut client = setup_client();
-        let topic_filter = setup_topic_filter();
-        client.add_subscription(topic_filter[0].clone());
-        let topic_name = TopicName::new(vec![b"topic".to_vec()], false);
-        assert!(!client.is_subscribed(&topic_name));
-        let topic_name = TopicName::new(vec![b"topic".to_vec(), b"level".to_vec()], false);
-        assert!(client.is_subscribed(&topic_name));
-    }
-
     #[test]
     fn test_adding_multiple_subscriptions() {
         let mut client = setup_client();
@@ -192,4 +344,13 @@ mod tests {
         assert_eq!(client.subscriptions[0], topic_filter[0]);
         assert_eq!(client.subscriptions[1], topic_filter[1]);
     }
+
+    #[test]
+    fn test_shutdown_closes_stream() {
+        let client = setup_client();
+        client.shutdown();
+
+        let mut stream = client.stream.as_ref().unwrap();
+        assert!(stream.write_all(b"data").is_err());
+    }
 }
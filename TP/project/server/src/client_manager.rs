@@ -1,200 +1,381 @@
 use std::{
-    collections::HashMap,
-    fs::{self, OpenOptions},
+    collections::{HashMap, HashSet},
+    fmt,
     io::Write,
-    net::TcpStream,
+    net::IpAddr,
     sync::{
-        mpsc::{self, Sender},
-        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
     },
-    thread,
 };
 
 use mqtt::model::{
-    packets::{connack::Connack, connect::Connect},
+    components::{qos::QoS, topic_filter::TopicFilter, topic_name::TopicName, will::Will},
+    packets::{connack::Connack, connect::Connect, publish::Publish},
     return_codes::connect_return_code::ConnectReturnCode,
 };
 
 use crate::{
+    acl::Acl,
+    auth::AuthBackend,
     client::Client,
+    client_stream::ClientStream,
     error::{ServerError, ServerResult},
 };
 
 /// Represents a client ID
 type ClientId = Vec<u8>;
-/// Represents a tuple of a username, password, and whether the client is connected
-type Logins = (Vec<u8>, Vec<u8>, bool); // username, password, is_connected
-/// Represents a map of client IDs to login information
-type Clients = HashMap<ClientId, Logins>;
+/// Represents a map of the connected client IDs to their topic rules
+type ConnectedClients = HashMap<ClientId, Acl>;
+
+/// Prefix of the ids assigned to the clients that connect with an empty one
+const ASSIGNED_CLIENT_ID_PREFIX: &str = "auto-";
+/// Packet identifier of the wills published with QoS 1
+const WILL_PACKAGE_IDENTIFIER: u16 = 1;
+
+/// Represents the reason why a client is not allowed to connect
+#[derive(Debug, PartialEq)]
+pub enum ConnectRefusal {
+    /// The client did not send a username and password and anonymous access is disabled
+    MissingLogin,
+    /// The client id is not registered
+    UnknownClient,
+    /// The username or password do not match the registered ones
+    BadCredentials,
+    /// A client with the same id is already connected
+    AlreadyConnected,
+    /// The client sent an empty id and the server can't assign it one
+    EmptyClientId,
+    /// The client id or the address it connects from are banned
+    Banned,
+    /// The maximum number of connections was reached
+    ServerFull,
+    /// The server could not verify the credentials or set up the connection
+    Unavailable(String),
+}
+
+impl ConnectRefusal {
+    /// Returns the code the CONNACK packet answers the refused client with
+    pub fn return_code(&self) -> ConnectReturnCode {
+        match self {
+            ConnectRefusal::MissingLogin => ConnectReturnCode::NotAuthorized,
+            ConnectRefusal::BadCredentials => ConnectReturnCode::BadUsernameOrPassword,
+            ConnectRefusal::UnknownClient
+            | ConnectRefusal::AlreadyConnected
+            | ConnectRefusal::EmptyClientId
+            | ConnectRefusal::Banned => ConnectReturnCode::IdentifierRejected,
+            ConnectRefusal::ServerFull | ConnectRefusal::Unavailable(_) => {
+                ConnectReturnCode::ServerUnavailable
+            }
+        }
+    }
+}
+
+impl fmt::Display for ConnectRefusal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConnectRefusal::MissingLogin => write!(f, "no login provided"),
+            ConnectRefusal::UnknownClient => write!(f, "the client id is not registered"),
+            ConnectRefusal::BadCredentials => write!(f, "wrong username or password"),
+            ConnectRefusal::AlreadyConnected => {
+                write!(f, "a client with the same id is already connected")
+            }
+            ConnectRefusal::EmptyClientId => write!(f, "the client id is empty"),
+            ConnectRefusal::Banned => write!(f, "the client id or address are banned"),
+            ConnectRefusal::ServerFull => {
+                write!(f, "the maximum number of connections was reached")
+            }
+            ConnectRefusal::Unavailable(msg) => write!(f, "server unavailable: {}", msg),
+        }
+    }
+}
+
+impl From<ServerError> for ConnectRefusal {
+    fn from(error: ServerError) -> Self {
+        ConnectRefusal::Unavailable(error.to_string())
+    }
+}
+
+impl<T> From<PoisonError<T>> for ConnectRefusal {
+    fn from(_: PoisonError<T>) -> Self {
+        ConnectRefusal::Unavailable("poisoned lock".to_string())
+    }
+}
 
 /// Represents a manager that handles clients in the server such as registering and authenticating them
-/// and processing connect packets validating the login information
+/// and processing connect packets validating the login information.
+/// The credentials are stored by the configured authentication backend
 #[derive(Debug, Clone)]
 pub struct ClientManager {
-    registered_clients: Arc<Mutex<Clients>>,
-    file_sender: Sender<String>,
+    backend: Arc<dyn AuthBackend>,
+    connected_clients: Arc<Mutex<ConnectedClients>>,
+    anonymous_acl: Option<Acl>,
+    banned_clients: HashSet<ClientId>,
+    banned_addresses: HashSet<IpAddr>,
+    strict_mode: bool,
+    /// Maximum number of clients connected at once, if there is one
+    max_connections: Option<usize>,
+    /// Number of the next id assigned to a client that sends an empty one
+    next_assigned_id: Arc<AtomicU64>,
 }
 
 impl ClientManager {
-    /// Creates a new client manager with an empty Clients map
-    pub fn new(login_file_path: &str) -> Self {
-        let (sender, receiver) = mpsc::channel();
-        let file_path = login_file_path.to_string();
-
-        let registered_clients = Self::intials_registers(&file_path);
-
-        thread::spawn(move || {
-            let mut file = match OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(&file_path)
-            {
-                Ok(file) => file,
-                Err(e) => {
-                    eprintln!("Failed to open login file: {}", e);
-                    return;
-                }
-            };
-
-            for log_entry in receiver {
-                if let Err(e) = writeln!(file, "{}", log_entry) {
-                    eprintln!("Failed to write to login file: {}", e);
-                }
-            }
-        });
-
+    /// Creates a new client manager that stores the credentials in the specified backend.
+    /// If an anonymous ACL is given, clients without login or with unknown ids are accepted
+    /// and restricted to its topic rules
+    pub fn new(backend: Arc<dyn AuthBackend>, anonymous_acl: Option<Acl>) -> Self {
         Self {
-            registered_clients: Arc::new(Mutex::new(registered_clients)),
-            file_sender: sender,
+            backend,
+            connected_clients: Arc::new(Mutex::new(HashMap::new())),
+            anonymous_acl,
+            banned_clients: HashSet::new(),
+            banned_addresses: HashSet::new(),
+            strict_mode: false,
+            max_connections: None,
+            next_assigned_id: Arc::new(AtomicU64::new(1)),
         }
     }
 
-    /// Registers a client with the specified client ID, username, and password
-    pub fn register_client(
-        &self,
-        client_id: Vec<u8>,
-        username: Vec<u8>,
-        password: Vec<u8>,
-    ) -> ServerResult<()> {
-        self.add_client(client_id.clone(), username.clone(), password.clone())?;
-        self.save_client(client_id, username, password)?;
+    /// Follows the MQTT 3.1.1 specification strictly: a client that sends an empty id
+    /// and asks for a clean session is assigned a unique one
+    pub fn with_strict_mode(mut self, strict_mode: bool) -> Self {
+        self.strict_mode = strict_mode;
+        self
+    }
 
-        Ok(())
+    /// Sets the maximum number of clients connected at once. The clients connected beyond it
+    /// stay connected, but no other client is accepted until they are fewer
+    pub fn set_max_connections(&mut self, max_connections: Option<usize>) {
+        self.max_connections = max_connections;
     }
 
-    /// Adds a client to the registered clients map
-    fn add_client(
-        &self,
-        client_id: Vec<u8>,
-        username: Vec<u8>,
-        password: Vec<u8>,
+    /// Replaces the authentication backend and the anonymous ACL, keeping the connected clients.
+    /// Their topic rules are refreshed from the new backend, and guests get the new anonymous ACL
+    pub fn reload(
+        &mut self,
+        backend: Arc<dyn AuthBackend>,
+        anonymous_acl: Option<Acl>,
     ) -> ServerResult<()> {
-        let mut registered_clients = self.registered_clients.lock()?;
-        registered_clients.insert(
-            client_id.clone(),
-            (username.clone(), password.clone(), false),
-        );
+        let mut connected_clients = self.connected_clients.lock()?;
+
+        for (client_id, acl) in connected_clients.iter_mut() {
+            if let Some(new_acl) = backend.acl(client_id)? {
+                *acl = new_acl;
+            } else if let Some(anonymous_acl) = &anonymous_acl {
+                if !backend.is_registered(client_id)? {
+                    *acl = anonymous_acl.clone();
+                }
+            }
+        }
+        drop(connected_clients);
 
+        self.backend = backend;
+        self.anonymous_acl = anonymous_acl;
         Ok(())
     }
 
-    /// Saves a client to the login file
-    fn save_client(
+    /// Bans a client id, so it can't connect anymore
+    pub fn ban_client(&mut self, client_id: Vec<u8>) {
+        self.banned_clients.insert(client_id);
+    }
+
+    /// Bans an IP address, so no client can connect from it anymore
+    pub fn ban_address(&mut self, address: IpAddr) {
+        self.banned_addresses.insert(address);
+    }
+
+    /// Returns true if the client id or the address it connects from are banned
+    pub fn is_banned(&self, client_id: &[u8], address: Option<IpAddr>) -> bool {
+        self.banned_clients.contains(client_id)
+            || address.is_some_and(|address| self.banned_addresses.contains(&address))
+    }
+
+    /// Registers a client with the specified client ID, username, password and topic rules
+    pub fn register_client(
         &self,
         client_id: Vec<u8>,
         username: Vec<u8>,
         password: Vec<u8>,
+        acl: Acl,
     ) -> ServerResult<()> {
-        let client_id = String::from_utf8(client_id)?;
-
-        let username = String::from_utf8(username)?;
-
-        let password = String::from_utf8(password)?;
-
-        let login_entry = format!("{} = {} = {}", client_id, username, password);
-
-        self.file_sender.send(login_entry)?;
+        self.backend
+            .register(&client_id, &username, &password, &acl)
+    }
 
-        Ok(())
+    /// Returns true if a client with the specified client ID is registered
+    pub fn is_registered(&self, client_id: &[u8]) -> ServerResult<bool> {
+        self.backend.is_registered(client_id)
     }
 
-    /// Authenticates a client with the specified client ID, username, and password
+    /// Authenticates a client with the specified client ID, username, and password.
+    /// Returns the reason if the client is refused
     pub fn authenticate_client(
         &self,
         client_id: Vec<u8>,
         username: Vec<u8>,
         password: Vec<u8>,
-    ) -> ServerResult<bool> {
-        let mut registered_clients = self.registered_clients.lock()?;
+    ) -> Result<(), ConnectRefusal> {
+        if self.connected_clients.lock()?.contains_key(&client_id) {
+            return Err(ConnectRefusal::AlreadyConnected);
+        }
 
-        if let Some((stored_username, stored_password, is_connected)) =
-            registered_clients.get_mut(&client_id)
+        // The backend may take a while to answer, so the clients aren't locked meanwhile and
+        // the ones connected can go on publishing and subscribing
+        let acl = match self
+            .backend
+            .authenticate(&client_id, &username, &password)?
         {
-            if *is_connected {
-                return Ok(false);
+            Some(acl) => acl,
+            None if self.backend.is_registered(&client_id)? => {
+                return Err(ConnectRefusal::BadCredentials)
             }
+            None => return Err(ConnectRefusal::UnknownClient),
+        };
 
-            if stored_username == &username && stored_password == &password {
-                *is_connected = true;
-                return Ok(true);
-            }
+        // Another connection with the same id may have been accepted while authenticating
+        self.admit(client_id, acl)
+    }
+
+    /// Accepts a client as a guest restricted to the anonymous ACL.
+    /// Returns the reason if anonymous access is disabled or the client is already connected
+    pub fn authenticate_anonymous_client(&self, client_id: Vec<u8>) -> Result<(), ConnectRefusal> {
+        let anonymous_acl = match &self.anonymous_acl {
+            Some(acl) => acl.clone(),
+            None => return Err(ConnectRefusal::MissingLogin),
+        };
+
+        self.admit(client_id, anonymous_acl)
+    }
+
+    /// Adds an authenticated client to the connected ones, unless one with the same id is
+    /// already connected or the server is full. Both are checked under the same lock that
+    /// adds it, so concurrent connections can't go past the limit
+    fn admit(&self, client_id: Vec<u8>, acl: Acl) -> Result<(), ConnectRefusal> {
+        let mut connected_clients = self.connected_clients.lock()?;
+
+        if connected_clients.contains_key(&client_id) {
+            return Err(ConnectRefusal::AlreadyConnected);
+        }
+        if self
+            .max_connections
+            .is_some_and(|max_connections| connected_clients.len() >= max_connections)
+        {
+            return Err(ConnectRefusal::ServerFull);
         }
-        Ok(false)
+
+        connected_clients.insert(client_id, acl);
+        Ok(())
     }
 
     /// Disconnects a client with the specified client ID
     pub fn disconnect_client(&self, client_id: Vec<u8>) -> ServerResult<()> {
-        let mut registered_clients = self.registered_clients.lock()?;
-        if let Some((_, _, is_connected)) = registered_clients.get_mut(&client_id) {
-            *is_connected = false;
-        }
+        self.connected_clients.lock()?.remove(&client_id);
 
         Ok(())
     }
 
-    /// Processes a connect packet by validating the login information and authenticating the client
+    /// Returns true if the client is allowed to publish to the topic according to its ACL
+    pub fn can_publish(&self, client_id: &[u8], topic_name: &TopicName) -> ServerResult<bool> {
+        let connected_clients = self.connected_clients.lock()?;
+
+        Ok(match connected_clients.get(client_id) {
+            Some(acl) => acl.can_publish(client_id, topic_name),
+            None => false,
+        })
+    }
+
+    /// Returns true if the client is allowed to subscribe to the topic filter according to its ACL
+    pub fn can_subscribe(
+        &self,
+        client_id: &[u8],
+        topic_filter: &TopicFilter,
+    ) -> ServerResult<bool> {
+        let connected_clients = self.connected_clients.lock()?;
+
+        Ok(match connected_clients.get(client_id) {
+            Some(acl) => acl.can_subscribe(client_id, topic_filter),
+            None => false,
+        })
+    }
+
+    /// Processes a connect packet by validating the login information and authenticating the client.
+    /// A refused client is answered with the CONNACK code of the reason, which is returned
     pub fn process_connect_packet(
         &self,
         connect_packet: Connect,
-        stream: TcpStream,
+        stream: ClientStream,
         key: &[u8],
-    ) -> Option<Client> {
-        let client_id = connect_packet.client_id().content().to_vec();
-        let (username, password) = match self.get_login_info(&connect_packet) {
-            Ok(login) => login,
-            Err(_) => {
-                self.failure_connection(stream, ConnectReturnCode::BadUsernameOrPassword, key);
-                return None;
+    ) -> Result<Client, ConnectRefusal> {
+        let client_id = match self.client_id(&connect_packet) {
+            Ok(client_id) => client_id,
+            Err(refusal) => {
+                self.failure_connection(stream, refusal.return_code(), key);
+                return Err(refusal);
             }
         };
-
-        match self.authenticate_client(client_id.clone(), username, password) {
-            Ok(true) => {
-                let stream = match stream.try_clone() {
-                    Ok(stream) => stream,
-                    Err(err) => {
-                        println!("Error cloning stream: {:?}", err);
-                        return None;
-                    }
-                };
-                Some(Client::new(client_id.clone(), Some(stream), true, 0))
+        let authentication = match self.get_login_info(&connect_packet) {
+            Ok((username, password)) => match self.is_anonymous_candidate(&client_id) {
+                Ok(true) => self.authenticate_anonymous_client(client_id.clone()),
+                Ok(false) => self.authenticate_client(client_id.clone(), username, password),
+                Err(err) => Err(err.into()),
+            },
+            Err(_) if self.anonymous_acl.is_some() => {
+                self.authenticate_anonymous_client(client_id.clone())
             }
+            Err(_) => Err(ConnectRefusal::MissingLogin),
+        };
+
+        let client_stream = authentication.and_then(|_| {
+            stream
+                .try_clone()
+                .map_err(|err| ConnectRefusal::Unavailable(err.to_string()))
+        });
 
-            Ok(false) => {
-                self.failure_connection(stream, ConnectReturnCode::IdentifierRejected, key);
-                None
+        match client_stream {
+            Ok(client_stream) => Ok(Client::new(
+                client_id,
+                Some(client_stream),
+                connect_packet.clean_session(),
+                connect_packet.keep_alive(),
+            )
+            .with_will(connect_packet.will().map(will_publish))),
+            Err(refusal) => {
+                self.failure_connection(stream, refusal.return_code(), key);
+                Err(refusal)
             }
-            Err(err) => {
-                println!("Error authenticating client: {:?}", err);
-                None
-            } 
         }
     }
 
+    /// Returns the id of the client that sent the CONNECT packet. In strict mode a client that
+    /// sends an empty id is assigned one if it asks for a clean session, as it has no session to resume
+    fn client_id(&self, connect_packet: &Connect) -> Result<Vec<u8>, ConnectRefusal> {
+        let client_id = connect_packet.client_id().content();
+        if !client_id.is_empty() {
+            return Ok(client_id.to_vec());
+        }
+
+        if !self.strict_mode || !connect_packet.clean_session() {
+            return Err(ConnectRefusal::EmptyClientId);
+        }
+
+        let number = self.next_assigned_id.fetch_add(1, Ordering::Relaxed);
+        Ok(format!("{}{}", ASSIGNED_CLIENT_ID_PREFIX, number).into_bytes())
+    }
+
+    /// Returns true if a client that sent a login should be accepted as a guest,
+    /// which happens when anonymous access is enabled and its id is not registered
+    fn is_anonymous_candidate(&self, client_id: &[u8]) -> ServerResult<bool> {
+        if self.anonymous_acl.is_none() {
+            return Ok(false);
+        }
+
+        Ok(!self.is_registered(client_id)?)
+    }
+
     /// Handles a failed connection by sending a Connack packet with the specified return code
-    fn failure_connection(
+    pub fn failure_connection(
         &self,
-        mut stream: TcpStream,
+        mut stream: ClientStream,
         return_code: ConnectReturnCode,
         key: &[u8],
     ) {
@@ -217,69 +398,309 @@ impl ClientManager {
             .to_vec();
         Ok((username, password))
     }
+}
 
-    /// Makes the initial registrations reading the configuration file
-    fn intials_registers(path: &str) -> HashMap<ClientId, Logins> {
-        let content = match fs::read_to_string(path) {
-            Ok(content) => content,
-            Err(_) => return HashMap::new(),
-        };
-
-        let mut registered_clients = HashMap::new();
-
-        for line in content.lines() {
-            let parts: Vec<&str> = line.split('=').map(|s| s.trim()).collect();
-            if parts.len() == 3 {
-                let client_id = parts[0].as_bytes().to_vec();
-                let username = parts[1].as_bytes().to_vec();
-                let password = parts[2].as_bytes().to_vec();
-                registered_clients.insert(client_id, (username, password, false));
-            }
-        }
-
-        registered_clients
-    }
+/// Builds the message a will is published as
+fn will_publish(will: &Will) -> Publish {
+    let package_identifier = (&QoS::AtMost != will.qos()).then_some(WILL_PACKAGE_IDENTIFIER);
+    Publish::new(
+        false,
+        will.qos().clone(),
+        will.retain(),
+        will.topic().clone(),
+        package_identifier,
+        will.message().content().to_vec(),
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::auth::file_backend::FileAuthBackend;
+    use mqtt::model::components::encoded_string::EncodedString;
+
+    fn client_manager() -> ClientManager {
+        ClientManager::new(Arc::new(FileAuthBackend::new("test_login_file.txt")), None)
+    }
 
     #[test]
     fn test_register_client() {
-        let client_manager = ClientManager::new("test_login_file.txt");
-        let client_id = b"client1".to_vec();
-        let username = b"username".to_vec();
-        let password = b"password".to_vec();
+        let client_manager = client_manager();
+        let client_id = b"client3".to_vec();
+
+        let _ = client_manager.register_client(
+            client_id.clone(),
+            b"username".to_vec(),
+            b"password".to_vec(),
+            Acl::default(),
+        );
+
+        assert!(client_manager.is_registered(&client_id).unwrap());
+        assert!(!client_manager.is_registered(b"unregistered").unwrap());
+    }
+
+    #[test]
+    fn test_empty_client_ids_are_assigned_in_strict_mode() {
+        let connect =
+            |clean_session| Connect::new(clean_session, 0, EncodedString::new(vec![]), None, None);
+
+        assert_eq!(
+            client_manager().client_id(&connect(true)),
+            Err(ConnectRefusal::EmptyClientId)
+        );
+
+        let client_manager = client_manager().with_strict_mode(true);
+        assert_eq!(
+            client_manager.client_id(&connect(false)),
+            Err(ConnectRefusal::EmptyClientId)
+        );
+        let first_id = client_manager.client_id(&connect(true)).unwrap();
+        let second_id = client_manager.client_id(&connect(true)).unwrap();
+        assert!(first_id.starts_with(ASSIGNED_CLIENT_ID_PREFIX.as_bytes()));
+        assert_ne!(first_id, second_id);
+    }
+
+    #[test]
+    fn test_missing_login_is_not_authorized() {
+        assert_eq!(
+            ConnectRefusal::MissingLogin.return_code(),
+            ConnectReturnCode::NotAuthorized
+        );
+    }
+
+    #[test]
+    fn test_bad_credentials_are_a_bad_username_or_password() {
+        assert_eq!(
+            ConnectRefusal::BadCredentials.return_code(),
+            ConnectReturnCode::BadUsernameOrPassword
+        );
+    }
 
-        let _ =
-            client_manager.register_client(client_id.clone(), username.clone(), password.clone());
+    #[test]
+    fn test_unknown_client_is_rejected() {
+        assert_eq!(
+            ConnectRefusal::UnknownClient.return_code(),
+            ConnectReturnCode::IdentifierRejected
+        );
+    }
+
+    #[test]
+    fn test_already_connected_client_is_rejected() {
+        assert_eq!(
+            ConnectRefusal::AlreadyConnected.return_code(),
+            ConnectReturnCode::IdentifierRejected
+        );
+    }
 
-        let registered_clients = client_manager.registered_clients.lock().unwrap();
-        let logins = registered_clients.get(&client_id).unwrap();
-        assert_eq!(logins.0, username);
-        assert_eq!(logins.1, password);
-        assert!(!logins.2);
+    #[test]
+    fn test_empty_client_id_is_rejected() {
+        assert_eq!(
+            ConnectRefusal::EmptyClientId.return_code(),
+            ConnectReturnCode::IdentifierRejected
+        );
+    }
+
+    #[test]
+    fn test_banned_client_is_rejected() {
+        assert_eq!(
+            ConnectRefusal::Banned.return_code(),
+            ConnectReturnCode::IdentifierRejected
+        );
+    }
+
+    #[test]
+    fn test_full_server_is_unavailable() {
+        assert_eq!(
+            ConnectRefusal::ServerFull.return_code(),
+            ConnectReturnCode::ServerUnavailable
+        );
+    }
+
+    #[test]
+    fn test_failing_backend_is_unavailable() {
+        assert_eq!(
+            ConnectRefusal::Unavailable("timeout".to_string()).return_code(),
+            ConnectReturnCode::ServerUnavailable
+        );
     }
 
     #[test]
     fn test_authenticate_client() {
-        let client_manager = ClientManager::new("test_login_file.txt");
+        let client_manager = client_manager();
         let client_id = b"client1".to_vec();
         let username = b"username".to_vec();
         let password = b"password".to_vec();
 
-        let _ =
-            client_manager.register_client(client_id.clone(), username.clone(), password.clone());
+        let _ = client_manager.register_client(
+            client_id.clone(),
+            username.clone(),
+            password.clone(),
+            Acl::default(),
+        );
 
+        assert_eq!(
+            client_manager.authenticate_client(
+                client_id.clone(),
+                b"wrong".to_vec(),
+                password.clone()
+            ),
+            Err(ConnectRefusal::BadCredentials)
+        );
+        assert_eq!(
+            client_manager.authenticate_client(
+                client_id.clone(),
+                username.clone(),
+                b"wrong".to_vec()
+            ),
+            Err(ConnectRefusal::BadCredentials)
+        );
+        assert_eq!(
+            client_manager.authenticate_client(
+                b"unknown".to_vec(),
+                username.clone(),
+                password.clone()
+            ),
+            Err(ConnectRefusal::UnknownClient)
+        );
         assert!(client_manager
             .authenticate_client(client_id.clone(), username.clone(), password.clone())
-            .unwrap());
-        assert!(!client_manager
-            .authenticate_client(client_id.clone(), b"wrong".to_vec(), password.clone())
-            .unwrap());
+            .is_ok());
+        // A client that is already connected can not authenticate again
+        assert_eq!(
+            client_manager.authenticate_client(
+                client_id.clone(),
+                username.clone(),
+                password.clone()
+            ),
+            Err(ConnectRefusal::AlreadyConnected)
+        );
+        assert_eq!(client_manager.connected_clients.lock().unwrap().len(), 1);
+
+        client_manager.disconnect_client(client_id).unwrap();
+        assert_eq!(client_manager.connected_clients.lock().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_clients_beyond_the_maximum_are_refused() {
+        let mut client_manager = ClientManager::new(
+            Arc::new(FileAuthBackend::new("test_login_file.txt")),
+            Some(Acl::default()),
+        );
+        client_manager.set_max_connections(Some(1));
+
+        assert!(client_manager
+            .authenticate_anonymous_client(b"guest1".to_vec())
+            .is_ok());
+        assert_eq!(
+            client_manager.authenticate_anonymous_client(b"guest2".to_vec()),
+            Err(ConnectRefusal::ServerFull)
+        );
+
+        client_manager
+            .disconnect_client(b"guest1".to_vec())
+            .unwrap();
+        assert!(client_manager
+            .authenticate_anonymous_client(b"guest2".to_vec())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_client_acl() {
+        let client_manager = client_manager();
+        let client_id = b"client2".to_vec();
+        let acl = Acl::from_fields(Some("drone-data/%c"), Some("new-incident"));
+
+        let _ = client_manager.register_client(
+            client_id.clone(),
+            b"username".to_vec(),
+            b"password".to_vec(),
+            acl,
+        );
+        let _ = client_manager.authenticate_client(
+            client_id.clone(),
+            b"username".to_vec(),
+            b"password".to_vec(),
+        );
+
+        let own_topic = TopicName::new(vec![b"drone-data".to_vec(), client_id.clone()], false);
+        let other_topic = TopicName::new(vec![b"drone-data".to_vec(), b"3".to_vec()], false);
+
+        assert!(client_manager.can_publish(&client_id, &own_topic).unwrap());
         assert!(!client_manager
-            .authenticate_client(client_id.clone(), username.clone(), b"wrong".to_vec())
+            .can_publish(&client_id, &other_topic)
             .unwrap());
+        assert!(!client_manager.can_publish(b"unknown", &own_topic).unwrap());
+    }
+
+    #[test]
+    fn test_anonymous_client() {
+        let anonymous_acl = Acl::from_fields(Some(""), Some("#"));
+        let client_manager = ClientManager::new(
+            Arc::new(FileAuthBackend::new("test_login_file.txt")),
+            Some(anonymous_acl),
+        );
+        let client_id = b"guest".to_vec();
+        let topic = TopicName::new(vec![b"drone-data".to_vec(), b"1".to_vec()], false);
+
+        assert!(client_manager
+            .authenticate_anonymous_client(client_id.clone())
+            .is_ok());
+        assert_eq!(
+            client_manager.authenticate_anonymous_client(client_id.clone()),
+            Err(ConnectRefusal::AlreadyConnected)
+        );
+        assert!(!client_manager.can_publish(&client_id, &topic).unwrap());
+    }
+
+    #[test]
+    fn test_anonymous_access_disabled() {
+        let client_manager = client_manager();
+
+        assert_eq!(
+            client_manager.authenticate_anonymous_client(b"guest".to_vec()),
+            Err(ConnectRefusal::MissingLogin)
+        );
+    }
+
+    #[test]
+    fn test_reload_refreshes_connected_clients_acl() {
+        let mut client_manager = client_manager();
+        let client_id = b"client4".to_vec();
+        let topic = TopicName::new(vec![b"drone-data".to_vec(), b"3".to_vec()], false);
+
+        let _ = client_manager.register_client(
+            client_id.clone(),
+            b"username".to_vec(),
+            b"password".to_vec(),
+            Acl::default(),
+        );
+        let _ = client_manager.authenticate_client(
+            client_id.clone(),
+            b"username".to_vec(),
+            b"password".to_vec(),
+        );
+        assert!(client_manager.can_publish(&client_id, &topic).unwrap());
+
+        let backend = Arc::new(FileAuthBackend::new("test_login_file.txt"));
+        let restricted_acl = Acl::from_fields(Some("drone-data/%c"), None);
+        backend
+            .register(&client_id, b"username", b"password", &restricted_acl)
+            .unwrap();
+        client_manager.reload(backend, None).unwrap();
+
+        assert_eq!(client_manager.connected_clients.lock().unwrap().len(), 1);
+        assert!(!client_manager.can_publish(&client_id, &topic).unwrap());
+    }
+
+    #[test]
+    fn test_ban_list() {
+        let mut client_manager = client_manager();
+        let address: IpAddr = "10.0.0.1".parse().unwrap();
+        client_manager.ban_client(b"banned".to_vec());
+        client_manager.ban_address(address);
+
+        assert!(client_manager.is_banned(b"banned", None));
+        assert!(client_manager.is_banned(b"client", Some(address)));
+        assert!(!client_manager.is_banned(b"client", "10.0.0.2".parse().ok()));
     }
 }
@@ -0,0 +1,154 @@
+use std::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Represents the traffic of a client and when it was last seen. The counters are atomic
+/// because the messages are sent to the client through shared references
+#[derive(Debug)]
+pub struct ClientStats {
+    messages_in: AtomicU64,
+    bytes_in: AtomicU64,
+    messages_out: AtomicU64,
+    bytes_out: AtomicU64,
+    /// Seconds since the Unix epoch at which the client sent its last packet
+    last_seen: AtomicU64,
+    /// True if something changed since the stats were last taken
+    changed: AtomicBool,
+}
+
+impl ClientStats {
+    pub fn new() -> Self {
+        ClientStats {
+            messages_in: AtomicU64::new(0),
+            bytes_in: AtomicU64::new(0),
+            messages_out: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            last_seen: AtomicU64::new(unix_secs(SystemTime::now())),
+            changed: AtomicBool::new(true),
+        }
+    }
+
+    /// Counts a message published by the client
+    pub fn record_in(&self, payload_bytes: usize) {
+        self.messages_in.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in
+            .fetch_add(payload_bytes as u64, Ordering::Relaxed);
+        self.changed.store(true, Ordering::Relaxed);
+    }
+
+    /// Counts a message sent to the client
+    pub fn record_out(&self, payload_bytes: usize) {
+        self.messages_out.fetch_add(1, Ordering::Relaxed);
+        self.bytes_out
+            .fetch_add(payload_bytes as u64, Ordering::Relaxed);
+        self.changed.store(true, Ordering::Relaxed);
+    }
+
+    /// Remembers that the client sent a packet now
+    pub fn record_activity(&self) {
+        self.last_seen
+            .store(unix_secs(SystemTime::now()), Ordering::Relaxed);
+        self.changed.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true if something changed since the last call
+    pub fn take_changed(&self) -> bool {
+        self.changed.swap(false, Ordering::Relaxed)
+    }
+
+    pub fn messages_in(&self) -> u64 {
+        self.messages_in.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_in(&self) -> u64 {
+        self.bytes_in.load(Ordering::Relaxed)
+    }
+
+    pub fn messages_out(&self) -> u64 {
+        self.messages_out.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_out(&self) -> u64 {
+        self.bytes_out.load(Ordering::Relaxed)
+    }
+
+    /// Returns the seconds since the Unix epoch at which the client sent its last packet
+    pub fn last_seen(&self) -> u64 {
+        self.last_seen.load(Ordering::Relaxed)
+    }
+}
+
+/// Represents the packets and bytes that went through a single connection of a client.
+/// It is shared by the event loop, which reads the connection, and the writer of the client
+#[derive(Debug, Default)]
+pub struct ConnectionTraffic {
+    packets_received: AtomicU64,
+    bytes_received: AtomicU64,
+    packets_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+}
+
+impl ConnectionTraffic {
+    /// Counts a packet read from the connection
+    pub fn record_received(&self, packet_bytes: usize) {
+        self.packets_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received
+            .fetch_add(packet_bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Counts a packet written to the connection
+    pub fn record_sent(&self, packet_bytes: usize) {
+        self.packets_sent.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent
+            .fetch_add(packet_bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn packets_received(&self) -> u64 {
+        self.packets_received.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
+    }
+
+    pub fn packets_sent(&self) -> u64 {
+        self.packets_sent.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+}
+
+/// Returns the seconds since the Unix epoch of an instant
+pub fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traffic_is_counted_in_both_directions() {
+        let stats = ClientStats::new();
+        assert!(stats.take_changed());
+        assert!(!stats.take_changed());
+
+        stats.record_in(10);
+        stats.record_in(5);
+        stats.record_out(100);
+
+        assert!(stats.take_changed());
+        assert_eq!(stats.messages_in(), 2);
+        assert_eq!(stats.bytes_in(), 15);
+        assert_eq!(stats.messages_out(), 1);
+        assert_eq!(stats.bytes_out(), 100);
+
+        stats.record_activity();
+        assert!(stats.take_changed());
+        assert!(stats.last_seen() > 0);
+    }
+}
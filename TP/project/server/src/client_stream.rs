@@ -0,0 +1,111 @@
+use std::{
+    io::{self, Write},
+    net::{Shutdown, SocketAddr, TcpStream},
+    os::unix::net::UnixStream,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::websocket::WebSocketStream;
+
+/// Time a write waits for a full socket buffer to drain before giving up
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+const WRITE_RETRY_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Represents the connection with a client, which can be a plain TCP socket,
+/// a WebSocket used by browser based clients or a Unix domain socket used by local clients
+#[derive(Debug)]
+pub enum ClientStream {
+    Tcp(TcpStream),
+    WebSocket(WebSocketStream),
+    Unix(UnixStream),
+}
+
+impl ClientStream {
+    /// Creates a new handle to the same connection
+    pub fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            ClientStream::Tcp(stream) => Ok(ClientStream::Tcp(stream.try_clone()?)),
+            ClientStream::WebSocket(stream) => Ok(ClientStream::WebSocket(stream.try_clone()?)),
+            ClientStream::Unix(stream) => Ok(ClientStream::Unix(stream.try_clone()?)),
+        }
+    }
+
+    /// Shuts down the connection
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        match self {
+            ClientStream::Tcp(stream) => stream.shutdown(how),
+            ClientStream::WebSocket(stream) => stream.shutdown(how),
+            ClientStream::Unix(stream) => stream.shutdown(how),
+        }
+    }
+
+    /// Returns the address of the client. Fails for Unix domain sockets, which have no IP address
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        match self {
+            ClientStream::Tcp(stream) => stream.peer_addr(),
+            ClientStream::WebSocket(stream) => stream.peer_addr(),
+            ClientStream::Unix(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Unix domain sockets have no IP address",
+            )),
+        }
+    }
+}
+
+impl Write for &ClientStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            ClientStream::Tcp(stream) => {
+                write_all_retrying(stream, buf)?;
+                Ok(buf.len())
+            }
+            ClientStream::WebSocket(stream) => (&*stream).write(buf),
+            ClientStream::Unix(stream) => {
+                write_all_retrying(stream, buf)?;
+                Ok(buf.len())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            ClientStream::Tcp(stream) => (&*stream).flush(),
+            ClientStream::WebSocket(stream) => (&*stream).flush(),
+            ClientStream::Unix(stream) => (&*stream).flush(),
+        }
+    }
+}
+
+impl Write for ClientStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&*self).flush()
+    }
+}
+
+/// Writes the whole buffer in a non blocking socket, waiting while its send buffer is full.
+/// Sockets are non blocking because the event loop of the server reads from them
+pub fn write_all_retrying(mut stream: impl Write, mut buf: &[u8]) -> io::Result<()> {
+    let started = Instant::now();
+
+    while !buf.is_empty() {
+        match stream.write(buf) {
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::WriteZero)),
+            Ok(written) => buf = &buf[written..],
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                if started.elapsed() >= WRITE_TIMEOUT {
+                    return Err(io::Error::from(io::ErrorKind::TimedOut));
+                }
+                thread::sleep(WRITE_RETRY_INTERVAL);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(())
+}
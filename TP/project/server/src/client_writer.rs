@@ -0,0 +1,340 @@
+use std::{
+    io::{self, Write},
+    net::Shutdown,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc::{self, SyncSender, TrySendError},
+        Arc, Weak,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{client_stats::ConnectionTraffic, client_stream::ClientStream};
+
+/// Represents the state of the outbound queue of a client, shared with its writer thread
+#[derive(Debug, Default)]
+struct OutboundStats {
+    /// Packets waiting in the queue
+    queued: AtomicUsize,
+    /// Microseconds the last packet took to be written
+    write_latency: AtomicU64,
+    /// Set when the connection is closed because the client stopped reading
+    slow_consumer: AtomicBool,
+}
+
+/// Represents the sending side of a client connection.
+/// Once the writer thread is started, packets are queued and written by it, so a slow or dead
+/// socket never blocks the task handler. Before that, packets are written directly
+#[derive(Debug)]
+pub struct ClientWriter {
+    stream: Arc<ClientStream>,
+    /// Only the writer owns the sender, so the writer thread ends once it stops
+    queue: Option<Arc<SyncSender<Vec<u8>>>>,
+    thread: Option<JoinHandle<()>>,
+    disconnect_slow_consumer: bool,
+    stats: Arc<OutboundStats>,
+    traffic: Arc<ConnectionTraffic>,
+}
+
+impl ClientWriter {
+    /// Creates a writer that writes directly to the stream
+    pub fn new(stream: ClientStream) -> Self {
+        Self {
+            stream: Arc::new(stream),
+            queue: None,
+            thread: None,
+            disconnect_slow_consumer: false,
+            stats: Arc::new(OutboundStats::default()),
+            traffic: Arc::new(ConnectionTraffic::default()),
+        }
+    }
+
+    /// Counts the packets written in the counters of the connection they belong to
+    pub fn set_traffic(&mut self, traffic: Arc<ConnectionTraffic>) {
+        self.traffic = traffic;
+    }
+
+    /// Returns the counters of the packets that went through the connection
+    pub fn traffic(&self) -> &ConnectionTraffic {
+        &self.traffic
+    }
+
+    /// Starts the thread that drains the outbound queue of the client.
+    /// When the queue is full, the packet is dropped and, if `disconnect_slow_consumer` is set,
+    /// the connection is closed. A client whose socket stays blocked until the write times out
+    /// is always disconnected. In both cases the client is marked as a slow consumer
+    pub fn start(&mut self, queue_size: usize, disconnect_slow_consumer: bool) -> io::Result<()> {
+        let (sender, receiver) = mpsc::sync_channel::<Vec<u8>>(queue_size);
+        let stream = self.stream.try_clone()?;
+        let stats = self.stats.clone();
+        let traffic = self.traffic.clone();
+
+        let thread = thread::spawn(move || {
+            for packet in receiver {
+                stats.queued.fetch_sub(1, Ordering::Relaxed);
+                let started = Instant::now();
+                let result = (&stream).write_all(&packet);
+                stats
+                    .write_latency
+                    .store(started.elapsed().as_micros() as u64, Ordering::Relaxed);
+
+                match result {
+                    Ok(_) => traffic.record_sent(packet.len()),
+                    Err(err) => {
+                        if err.kind() == io::ErrorKind::TimedOut {
+                            stats.slow_consumer.store(true, Ordering::Relaxed);
+                        }
+                        let _ = stream.shutdown(Shutdown::Both);
+                        break;
+                    }
+                }
+            }
+        });
+
+        self.queue = Some(Arc::new(sender));
+        self.thread = Some(thread);
+        self.disconnect_slow_consumer = disconnect_slow_consumer;
+        Ok(())
+    }
+
+    /// Returns a handle that queues packets from other threads while the writer is running.
+    /// None if the writer thread was not started
+    pub fn handle(&self) -> Option<WriterHandle> {
+        self.queue.as_ref().map(|queue| WriterHandle {
+            queue: Arc::downgrade(queue),
+            stream: self.stream.clone(),
+            disconnect_slow_consumer: self.disconnect_slow_consumer,
+            stats: self.stats.clone(),
+        })
+    }
+
+    /// Stops accepting packets and waits until the writer thread sends the queued ones
+    pub fn flush_and_stop(&mut self) {
+        self.queue = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+
+    /// Shuts down the connection
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.stream.shutdown(how)
+    }
+
+    /// Returns the number of packets waiting to be written
+    pub fn queue_depth(&self) -> usize {
+        self.stats.queued.load(Ordering::Relaxed)
+    }
+
+    /// Returns how long the last packet took to be written
+    pub fn write_latency(&self) -> Duration {
+        Duration::from_micros(self.stats.write_latency.load(Ordering::Relaxed))
+    }
+
+    /// Returns true if the connection was closed because the client stopped reading
+    pub fn is_slow_consumer(&self) -> bool {
+        self.stats.slow_consumer.load(Ordering::Relaxed)
+    }
+}
+
+impl Write for &ClientWriter {
+    /// Queues the whole buffer as a single packet. Fails with `WouldBlock` if the queue is full
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let queue = match &self.queue {
+            Some(queue) => queue,
+            None => {
+                let written = (&*self.stream).write(buf)?;
+                self.traffic.record_sent(written);
+                return Ok(written);
+            }
+        };
+
+        enqueue(
+            queue,
+            &self.stats,
+            &self.stream,
+            self.disconnect_slow_consumer,
+            buf,
+        )?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Represents a way to queue packets for the writer thread of a client from other threads.
+/// It does not keep the writer running, so it fails with `BrokenPipe` once the writer stopped
+#[derive(Debug, Clone)]
+pub struct WriterHandle {
+    queue: Weak<SyncSender<Vec<u8>>>,
+    stream: Arc<ClientStream>,
+    disconnect_slow_consumer: bool,
+    stats: Arc<OutboundStats>,
+}
+
+impl WriterHandle {
+    /// Queues the whole buffer as a single packet. Fails with `WouldBlock` if the queue is full
+    pub fn send(&self, buf: &[u8]) -> io::Result<()> {
+        match self.queue.upgrade() {
+            Some(queue) => enqueue(
+                &queue,
+                &self.stats,
+                &self.stream,
+                self.disconnect_slow_consumer,
+                buf,
+            ),
+            None => Err(io::Error::from(io::ErrorKind::BrokenPipe)),
+        }
+    }
+
+    /// Returns true if both handles queue packets for the same writer
+    pub fn same_writer(&self, other: &WriterHandle) -> bool {
+        Weak::ptr_eq(&self.queue, &other.queue)
+    }
+}
+
+/// Queues a packet for the writer thread. If the queue is full the packet is dropped,
+/// and the connection is closed if slow consumers are disconnected
+fn enqueue(
+    queue: &SyncSender<Vec<u8>>,
+    stats: &OutboundStats,
+    stream: &ClientStream,
+    disconnect_slow_consumer: bool,
+    buf: &[u8],
+) -> io::Result<()> {
+    // Counted before sending so the writer thread never sees it below zero
+    stats.queued.fetch_add(1, Ordering::Relaxed);
+    match queue.try_send(buf.to_vec()) {
+        Ok(_) => Ok(()),
+        Err(TrySendError::Full(_)) => {
+            stats.queued.fetch_sub(1, Ordering::Relaxed);
+            if disconnect_slow_consumer {
+                stats.slow_consumer.store(true, Ordering::Relaxed);
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+            Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "Outbound queue of the client is full",
+            ))
+        }
+        Err(TrySendError::Disconnected(_)) => {
+            stats.queued.fetch_sub(1, Ordering::Relaxed);
+            Err(io::Error::from(io::ErrorKind::BrokenPipe))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::Read,
+        net::{TcpListener, TcpStream},
+    };
+
+    fn stream_pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        (server, client)
+    }
+
+    #[test]
+    fn test_queued_packets_are_written() {
+        let (server, mut client) = stream_pair();
+        let mut writer = ClientWriter::new(ClientStream::Tcp(server));
+        writer.start(10, true).unwrap();
+
+        (&writer).write_all(b"first").unwrap();
+        (&writer).write_all(b"second").unwrap();
+
+        let mut received = [0u8; 11];
+        client.read_exact(&mut received).unwrap();
+        assert_eq!(&received, b"firstsecond");
+    }
+
+    #[test]
+    fn test_flush_and_stop_sends_queued_packets() {
+        let (server, mut client) = stream_pair();
+        let mut writer = ClientWriter::new(ClientStream::Tcp(server));
+        writer.start(10, false).unwrap();
+
+        (&writer).write_all(b"last").unwrap();
+        writer.flush_and_stop();
+        writer.shutdown(Shutdown::Both).unwrap();
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).unwrap();
+        assert_eq!(received, b"last");
+        assert_eq!(writer.traffic().packets_sent(), 1);
+        assert_eq!(writer.traffic().bytes_sent(), 4);
+    }
+
+    #[test]
+    fn test_handles_do_not_keep_the_writer_running() {
+        let (server, mut client) = stream_pair();
+        let mut writer = ClientWriter::new(ClientStream::Tcp(server));
+        assert!(writer.handle().is_none());
+        writer.start(10, false).unwrap();
+
+        let handle = writer.handle().unwrap();
+        assert!(handle.same_writer(&writer.handle().unwrap()));
+        handle.send(b"direct").unwrap();
+        writer.flush_and_stop();
+
+        let err = handle.send(b"late").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+
+        writer.shutdown(Shutdown::Both).unwrap();
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).unwrap();
+        assert_eq!(received, b"direct");
+    }
+
+    #[test]
+    fn test_full_queue_rejects_packets() {
+        let (server, _client) = stream_pair();
+        // Nobody drains this queue
+        let (sender, _receiver) = mpsc::sync_channel(1);
+        let writer = ClientWriter {
+            stream: Arc::new(ClientStream::Tcp(server)),
+            queue: Some(Arc::new(sender)),
+            thread: None,
+            disconnect_slow_consumer: false,
+            stats: Arc::new(OutboundStats::default()),
+            traffic: Arc::new(ConnectionTraffic::default()),
+        };
+
+        (&writer).write_all(b"first").unwrap();
+        let err = (&writer).write_all(b"second").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(writer.queue_depth(), 1);
+        assert!(!writer.is_slow_consumer());
+    }
+
+    #[test]
+    fn test_full_queue_marks_slow_consumer() {
+        let (server, mut client) = stream_pair();
+        let (sender, _receiver) = mpsc::sync_channel(1);
+        let writer = ClientWriter {
+            stream: Arc::new(ClientStream::Tcp(server)),
+            queue: Some(Arc::new(sender)),
+            thread: None,
+            disconnect_slow_consumer: true,
+            stats: Arc::new(OutboundStats::default()),
+            traffic: Arc::new(ConnectionTraffic::default()),
+        };
+
+        (&writer).write_all(b"first").unwrap();
+        assert!((&writer).write_all(b"second").is_err());
+        assert!(writer.is_slow_consumer());
+
+        // The connection was closed
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).unwrap();
+        assert!(received.is_empty());
+    }
+}
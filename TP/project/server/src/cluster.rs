@@ -0,0 +1,319 @@
+use std::{
+    io::Write,
+    net::{Shutdown, TcpStream},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use mqtt::model::{
+    components::{qos::QoS, topic_filter::TopicFilter},
+    packet::Packet,
+    packets::{pingreq::Pingreq, puback::Puback, subscribe::Subscribe},
+};
+
+use crate::{
+    bridge::{self, PING_INTERVAL, RECONNECT_INTERVAL},
+    config::Config,
+    error::{ServerError, ServerResult},
+    logfile::Logger,
+    task_channel::TaskSender,
+    task_handler::Task,
+};
+
+/// Prefix of the client ids the servers of the cluster connect to each other with
+const CLUSTER_CLIENT_PREFIX: &str = "$cluster/";
+const SUBSCRIBE_PACKET_ID: u16 = 1;
+
+/// Returns true if the client is the link of another server of the cluster
+pub fn is_cluster_link(client_id: &[u8]) -> bool {
+    client_id.starts_with(CLUSTER_CLIENT_PREFIX.as_bytes())
+}
+
+/// Represents the settings of the cluster the server is part of
+#[derive(Debug, Clone)]
+pub struct ClusterSettings {
+    node_id: String,
+    peers: Vec<String>,
+    username: String,
+    password: String,
+}
+
+impl ClusterSettings {
+    /// Reads the cluster settings from the configuration. Returns None if the cluster is disabled
+    pub fn from_config(config: &Config) -> ServerResult<Option<Self>> {
+        let node_id = match config.get_cluster_node_id() {
+            Some(node_id) => node_id.to_string(),
+            None => return Ok(None),
+        };
+
+        if config.get_cluster_peers().is_empty() {
+            return Err(ServerError::ArgumentError(
+                "The cluster has no peers".to_string(),
+            ));
+        }
+
+        Ok(Some(ClusterSettings {
+            node_id,
+            peers: config.get_cluster_peers().to_vec(),
+            username: config.get_cluster_username().to_string(),
+            password: config.get_cluster_password().to_string(),
+        }))
+    }
+
+    /// Returns the client id the server connects to its peers with
+    fn client_id(&self) -> String {
+        format!("{}{}", CLUSTER_CLIENT_PREFIX, self.node_id)
+    }
+}
+
+/// Represents the links of the server to the other servers of the cluster.
+/// The server connects to every peer as a client and subscribes there to the topic filters
+/// its own clients are subscribed to, so the peers route the matching messages to it.
+/// The messages received through a link are only sent to the local clients, every server
+/// sends its messages to the rest of the cluster by itself
+#[derive(Debug)]
+pub struct Cluster {
+    links: Vec<Sender<TopicFilter>>,
+    shared_filters: Vec<TopicFilter>,
+}
+
+impl Cluster {
+    /// Starts a thread for each peer that keeps the server connected to it.
+    /// The messages received from the peers are sent to the task handler
+    pub fn start(
+        settings: ClusterSettings,
+        key: Vec<u8>,
+        task_sender: TaskSender,
+        log_file: Arc<Logger>,
+    ) -> Self {
+        let mut links = vec![];
+
+        for peer in &settings.peers {
+            let (sender, receiver) = mpsc::channel();
+            let link = PeerLink {
+                address: peer.clone(),
+                client_id: settings.client_id(),
+                username: settings.username.clone(),
+                password: settings.password.clone(),
+                key: key.clone(),
+                filters: vec![],
+            };
+            let task_sender = task_sender.clone();
+            let log_file = log_file.clone();
+            thread::spawn(move || link.run(receiver, task_sender, log_file));
+            links.push(sender);
+        }
+
+        Cluster {
+            links,
+            shared_filters: vec![],
+        }
+    }
+
+    /// Subscribes in every peer to the topic filter a client subscribed to, unless it was already
+    /// shared. The subscriptions of the links of other servers are not shared
+    pub fn share_subscription(&mut self, client_id: &[u8], topic_filter: &TopicFilter) {
+        if is_cluster_link(client_id) || self.shared_filters.contains(topic_filter) {
+            return;
+        }
+
+        for link in &self.links {
+            let _ = link.send(topic_filter.clone());
+        }
+        self.shared_filters.push(topic_filter.clone());
+    }
+}
+
+/// Represents the connection of the server to one of its peers
+struct PeerLink {
+    address: String,
+    client_id: String,
+    username: String,
+    password: String,
+    key: Vec<u8>,
+    /// Topic filters subscribed in the peer, subscribed again after reconnecting
+    filters: Vec<TopicFilter>,
+}
+
+impl PeerLink {
+    /// Connects to the peer and subscribes to the shared topic filters until the server
+    /// drops the cluster, connecting again whenever the connection is lost
+    fn run(
+        mut self,
+        receiver: Receiver<TopicFilter>,
+        task_sender: TaskSender,
+        log_file: Arc<Logger>,
+    ) {
+        loop {
+            let stream = match bridge::connect_to_broker(
+                &self.address,
+                &self.client_id,
+                &self.username,
+                &self.password,
+                &self.key,
+            ) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log_file.warn(&format!(
+                        "Could not connect to cluster peer {}: {}. Retrying in {} seconds",
+                        self.address,
+                        e,
+                        RECONNECT_INTERVAL.as_secs()
+                    ));
+                    thread::sleep(RECONNECT_INTERVAL);
+                    continue;
+                }
+            };
+            log_file.info(&format!("Connected to cluster peer {}", self.address));
+
+            while let Ok(topic_filter) = receiver.try_recv() {
+                self.filters.push(topic_filter);
+            }
+
+            let result = stream
+                .try_clone()
+                .map_err(ServerError::from)
+                .and_then(|reader_stream| {
+                    let writer_stream = Arc::new(Mutex::new(stream));
+                    self.subscribe(&writer_stream, self.filters.clone())?;
+
+                    let reader_writer_stream = writer_stream.clone();
+                    let key = self.key.clone();
+                    let task_sender = task_sender.clone();
+                    thread::spawn(move || {
+                        read_peer(reader_stream, reader_writer_stream, key, task_sender)
+                    });
+
+                    self.share_subscriptions(&writer_stream, &receiver)
+                });
+
+            match result {
+                Ok(()) => return,
+                Err(e) => log_file.warn(&format!(
+                    "Lost the connection to cluster peer {}: {}. Reconnecting",
+                    self.address, e
+                )),
+            }
+            thread::sleep(RECONNECT_INTERVAL);
+        }
+    }
+
+    /// Subscribes to the topic filters in the peer
+    fn subscribe(&self, stream: &Mutex<TcpStream>, filters: Vec<TopicFilter>) -> ServerResult<()> {
+        if filters.is_empty() {
+            return Ok(());
+        }
+
+        let topics = filters
+            .into_iter()
+            .map(|topic_filter| (topic_filter, QoS::AtMost))
+            .collect();
+        let subscribe = Subscribe::new(SUBSCRIBE_PACKET_ID, topics);
+
+        let mut stream = stream.lock()?;
+        if let Err(e) = stream.write_all(&subscribe.to_bytes(&self.key)) {
+            let _ = stream.shutdown(Shutdown::Both);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    /// Subscribes in the peer to the topic filters shared by the server, pinging it when there are none.
+    /// Returns Ok when the server drops the cluster, or an error when the connection is lost
+    fn share_subscriptions(
+        &mut self,
+        stream: &Mutex<TcpStream>,
+        receiver: &Receiver<TopicFilter>,
+    ) -> ServerResult<()> {
+        loop {
+            match receiver.recv_timeout(PING_INTERVAL) {
+                Ok(topic_filter) => {
+                    self.filters.push(topic_filter.clone());
+                    self.subscribe(stream, vec![topic_filter])?;
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    let mut stream = stream.lock()?;
+                    if let Err(e) = stream.write_all(&Pingreq::new().to_bytes(&self.key)) {
+                        let _ = stream.shutdown(Shutdown::Both);
+                        return Err(e.into());
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    let _ = stream.lock()?.shutdown(Shutdown::Both);
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Reads the packets of a peer and sends its messages to the task handler.
+/// Shuts the connection down when it can't be read anymore
+fn read_peer(
+    mut stream: TcpStream,
+    writer_stream: Arc<Mutex<TcpStream>>,
+    key: Vec<u8>,
+    task_sender: TaskSender,
+) {
+    loop {
+        let publish = match Packet::from_bytes(&mut stream, &key) {
+            Ok(Packet::Publish(publish)) => publish,
+            Ok(_) => continue,
+            Err(_) => {
+                let _ = stream.shutdown(Shutdown::Both);
+                return;
+            }
+        };
+
+        if &QoS::AtMost != publish.qos() {
+            let puback = Puback::new(publish.package_identifier());
+            if let Ok(mut writer_stream) = writer_stream.lock() {
+                let _ = writer_stream.write_all(&puback.to_bytes(&key));
+            }
+        }
+
+        if task_sender.send(Task::ClusterPublish(publish)).is_err() {
+            let _ = stream.shutdown(Shutdown::Both);
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mqtt::model::components::topic_level::TopicLevel;
+
+    #[test]
+    fn test_cluster_links_are_recognized() {
+        let settings = ClusterSettings {
+            node_id: "node-a".to_string(),
+            peers: vec!["127.0.0.1:5001".to_string()],
+            username: String::new(),
+            password: String::new(),
+        };
+
+        assert!(is_cluster_link(settings.client_id().as_bytes()));
+        assert!(!is_cluster_link(b"node-a"));
+    }
+
+    #[test]
+    fn test_local_subscriptions_are_shared_once() {
+        let (sender, receiver) = mpsc::channel();
+        let mut cluster = Cluster {
+            links: vec![sender],
+            shared_filters: vec![],
+        };
+        let filter = TopicFilter::new(vec![TopicLevel::Literal(b"drone-data".to_vec())], false);
+
+        cluster.share_subscription(b"$cluster/node-b", &filter);
+        cluster.share_subscription(b"camera-system", &filter);
+        cluster.share_subscription(b"monitor", &filter);
+
+        assert_eq!(receiver.try_recv().ok(), Some(filter));
+        assert!(receiver.try_recv().is_err());
+    }
+}
@@ -1,87 +1,358 @@
-use std::{fs, io, path::Path};
+use std::{
+    fmt::Display,
+    fs,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-/// Represents the configuration of the server
-#[derive(Debug, Clone)]
+use mqtt::NO_ENCRYPTION;
+use serde::{de, Deserialize, Deserializer};
+
+use crate::{
+    error::{ServerError, ServerResult},
+    logfile::{LogFormat, LogLevel},
+    network_filter::Cidr,
+};
+
+const KEY_LENGTH: usize = 32;
+const DEFAULT_AUTH_BACKEND: &str = "file";
+const DEFAULT_PERSISTENCE_BACKEND: &str = "file";
+const DEFAULT_ANONYMOUS_SUBSCRIBE_RULES: &str = "#";
+const DEFAULT_WORKER_THREADS: usize = 4;
+const DEFAULT_OUTBOUND_QUEUE_SIZE: usize = 1000;
+const DEFAULT_TASK_QUEUE_SIZE: usize = 10000;
+const DEFAULT_LOG_MAX_FILES: usize = 5;
+const DEFAULT_SEGS_TO_BACKUP: u32 = 30;
+const DEFAULT_BRIDGE_CLIENT_ID: &str = "bridge";
+
+/// Represents the configuration of the server, read from a TOML file.
+/// Every setting is optional and takes its default value if it is missing
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
-    address: String,
-    key: [u8; 32],
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(rename = "address", deserialize_with = "deserialize_list")]
+    addresses: Vec<String>,
+    websocket_address: String,
+    unix_socket_path: String,
+    key: String,
+    encryption: bool,
+    strict_mode: bool,
     log_file: String,
+    log_max_bytes: u64,
+    log_rotate_daily: bool,
+    log_max_files: usize,
+    log_level: LogLevel,
+    log_format: LogFormat,
     login_file: String,
     segs_to_disconnect: u32,
     initialize_with_backup: bool,
     backup_file: String,
     segs_to_backup: u32,
+    auth_backend: String,
+    auth_database: String,
+    auth_webhook_url: String,
+    allow_anonymous: bool,
+    anonymous_publish_rules: String,
+    anonymous_subscribe_rules: String,
+    max_connections: usize,
+    max_payload_bytes: usize,
+    disconnect_on_oversized_payload: bool,
+    disconnect_on_invalid_topic: bool,
+    fast_path: bool,
+    max_subscriptions_per_client: usize,
+    max_inflight: u16,
+    allow_multi_level_wildcard: bool,
+    retained_ttl_secs: u64,
+    worker_threads: usize,
+    outbound_queue_size: usize,
+    disconnect_slow_consumers: bool,
+    task_queue_size: usize,
+    #[serde(deserialize_with = "deserialize_list")]
+    banned_clients: Vec<String>,
+    #[serde(deserialize_with = "deserialize_list")]
+    banned_addresses: Vec<IpAddr>,
+    #[serde(deserialize_with = "deserialize_list")]
+    allowed_networks: Vec<Cidr>,
+    #[serde(deserialize_with = "deserialize_list")]
+    denied_networks: Vec<Cidr>,
+    wal_enabled: bool,
+    persistence_backend: String,
+    persistence_database: String,
+    bridge_address: String,
+    bridge_client_id: String,
+    bridge_username: String,
+    bridge_password: String,
+    #[serde(deserialize_with = "deserialize_list")]
+    bridge_topics_in: Vec<String>,
+    #[serde(deserialize_with = "deserialize_list")]
+    bridge_topics_out: Vec<String>,
+    bridge_remote_prefix: String,
+    cluster_node_id: String,
+    #[serde(deserialize_with = "deserialize_list")]
+    cluster_peers: Vec<String>,
+    cluster_username: String,
+    cluster_password: String,
+    admin_http_address: String,
+    admin_http_token: String,
 }
 
-impl Config {
-    /// Reads the configuration from a file
-    pub fn from_file(path: &Path) -> std::io::Result<Self> {
-        let content = fs::read_to_string(path)?;
-
-        let mut config = Config {
-            address: String::new(),
-            key: [0; 32],
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            path: PathBuf::new(),
+            addresses: Vec::new(),
+            websocket_address: String::new(),
+            unix_socket_path: String::new(),
+            key: String::new(),
+            encryption: true,
+            strict_mode: false,
             log_file: String::new(),
+            log_max_bytes: 0,
+            log_rotate_daily: false,
+            log_max_files: DEFAULT_LOG_MAX_FILES,
+            log_level: LogLevel::Info,
+            log_format: LogFormat::Text,
             login_file: String::new(),
             segs_to_disconnect: 0,
             initialize_with_backup: false,
             backup_file: String::new(),
-            segs_to_backup: 0,
-        };
-
-        for line in content.lines() {
-            let parts: Vec<&str> = line.split('=').map(|s| s.trim()).collect();
-            if parts.len() == 2 {
-                match parts[0] {
-                    "address" => config.address = parts[1].trim_matches('"').to_string(),
-                    "key" => {
-                        let key_str = parts[1].trim_matches('"');
-                        if key_str.len() != 32 {
-                            return Err(io::Error::new(
-                                io::ErrorKind::InvalidData,
-                                "Invalid key length",
-                            ));
-                        }
-                        let mut key = [0; 32];
-                        for (i, c) in key_str.chars().enumerate() {
-                            key[i] = c as u8;
-                        }
-                        config.key = key;
-                    }
-                    "log_file" => config.log_file = parts[1].trim_matches('"').to_string(),
-                    "login_file" => config.login_file = parts[1].trim_matches('"').to_string(),
-                    "segs_to_disconnect" => {
-                        config.segs_to_disconnect = parts[1].parse().map_err(|_| {
-                            io::Error::new(
-                                io::ErrorKind::InvalidData,
-                                "Invalid segs_to_disconnect value",
-                            )
-                        })?
-                    }
-                    "initialize_with_backup" => {
-                        config.initialize_with_backup =
-                            matches!(parts[1].to_lowercase().as_str(), "true")
-                    }
-                    "backup_file" => config.backup_file = parts[1].trim_matches('"').to_string(),
-                    "segs_to_backup" => {
-                        config.segs_to_backup = parts[1].parse().map_err(|_| {
-                            io::Error::new(
-                                io::ErrorKind::InvalidData,
-                                "Invalid segs_to_backup value",
-                            )
-                        })?
-                    }
-                    _ => {}
-                }
-            }
+            segs_to_backup: DEFAULT_SEGS_TO_BACKUP,
+            auth_backend: DEFAULT_AUTH_BACKEND.to_string(),
+            auth_database: String::new(),
+            auth_webhook_url: String::new(),
+            allow_anonymous: false,
+            anonymous_publish_rules: String::new(),
+            anonymous_subscribe_rules: DEFAULT_ANONYMOUS_SUBSCRIBE_RULES.to_string(),
+            max_connections: 0,
+            max_payload_bytes: 0,
+            disconnect_on_oversized_payload: false,
+            disconnect_on_invalid_topic: true,
+            fast_path: true,
+            max_subscriptions_per_client: 0,
+            max_inflight: 0,
+            allow_multi_level_wildcard: true,
+            retained_ttl_secs: 0,
+            worker_threads: DEFAULT_WORKER_THREADS,
+            outbound_queue_size: DEFAULT_OUTBOUND_QUEUE_SIZE,
+            disconnect_slow_consumers: true,
+            task_queue_size: DEFAULT_TASK_QUEUE_SIZE,
+            banned_clients: Vec::new(),
+            banned_addresses: Vec::new(),
+            allowed_networks: Vec::new(),
+            denied_networks: Vec::new(),
+            wal_enabled: false,
+            persistence_backend: DEFAULT_PERSISTENCE_BACKEND.to_string(),
+            persistence_database: String::new(),
+            bridge_address: String::new(),
+            bridge_client_id: DEFAULT_BRIDGE_CLIENT_ID.to_string(),
+            bridge_username: String::new(),
+            bridge_password: String::new(),
+            bridge_topics_in: Vec::new(),
+            bridge_topics_out: Vec::new(),
+            bridge_remote_prefix: String::new(),
+            cluster_node_id: String::new(),
+            cluster_peers: Vec::new(),
+            cluster_username: String::new(),
+            cluster_password: String::new(),
+            admin_http_address: String::new(),
+            admin_http_token: String::new(),
         }
+    }
+}
 
+impl Config {
+    /// Reads the configuration from a file and validates it
+    pub fn from_file(path: &Path) -> ServerResult<Self> {
+        let content = fs::read_to_string(path)?;
+        let mut config = Config::from_toml(&content)?;
+        config.path = path.to_path_buf();
         Ok(config)
     }
 
-    /// Returns the address of the server
-    pub fn get_address(&self) -> &str {
-        &self.address
+    /// Parses the configuration from the content of a TOML file and validates it. A syntax
+    /// error stops the parsing, but the settings that are unknown or have a value of the wrong
+    /// type are reported along with the problems of the rest
+    fn from_toml(content: &str) -> ServerResult<Self> {
+        let table: toml::Table = toml::from_str(content)
+            .map_err(|err| ServerError::InvalidConfig(vec![err.to_string()]))?;
+
+        // Every setting is optional, so each one can be read on its own
+        let mut problems = vec![];
+        let mut valid_settings = toml::Table::new();
+        for (name, value) in table {
+            let setting = toml::Table::from_iter([(name, value)]);
+            match toml::Value::Table(setting.clone()).try_into::<Config>() {
+                Ok(_) => valid_settings.extend(setting),
+                Err(err) => problems.push(err.to_string().trim().replace('\n', " ")),
+            }
+        }
+
+        let config: Config = toml::Value::Table(valid_settings)
+            .try_into()
+            .map_err(|err| ServerError::InvalidConfig(vec![err.to_string()]))?;
+        problems.extend(config.validate());
+        if problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(ServerError::InvalidConfig(problems))
+        }
+    }
+
+    /// Returns every problem of the settings, so they can be fixed at once
+    fn validate(&self) -> Vec<String> {
+        let mut problems = vec![];
+
+        if self.key.len() != KEY_LENGTH {
+            problems.push(format!(
+                "key must be {} bytes long, it has {}",
+                KEY_LENGTH,
+                self.key.len()
+            ));
+        }
+
+        if self.addresses.is_empty() {
+            problems.push("address must have at least one address".to_string());
+        }
+        let addresses = self
+            .addresses
+            .iter()
+            .map(|address| ("address", address.as_str()))
+            .chain(
+                self.cluster_peers
+                    .iter()
+                    .map(|peer| ("cluster_peers", peer.as_str())),
+            )
+            .chain([
+                ("websocket_address", self.websocket_address.as_str()),
+                ("bridge_address", self.bridge_address.as_str()),
+                ("admin_http_address", self.admin_http_address.as_str()),
+            ])
+            .filter(|(_, address)| !address.is_empty());
+        for (setting, address) in addresses {
+            if !has_valid_port(address) {
+                problems.push(format!(
+                    "{} {} must end with a port between 1 and 65535",
+                    setting, address
+                ));
+            }
+        }
+
+        if self.segs_to_backup == 0 {
+            problems.push("segs_to_backup must be greater than 0".to_string());
+        }
+
+        problems
+    }
+
+    /// Returns the path of the file the configuration was read from
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the names of the settings that differ from another configuration
+    /// and only take effect after restarting the server
+    pub fn settings_requiring_restart(&self, other: &Config) -> Vec<&'static str> {
+        let mut settings = vec![];
+        if self.addresses != other.addresses {
+            settings.push("address");
+        }
+        if self.websocket_address != other.websocket_address {
+            settings.push("websocket_address");
+        }
+        if self.unix_socket_path != other.unix_socket_path {
+            settings.push("unix_socket_path");
+        }
+        if self.key != other.key {
+            settings.push("key");
+        }
+        if self.encryption != other.encryption {
+            settings.push("encryption");
+        }
+        if self.strict_mode != other.strict_mode {
+            settings.push("strict_mode");
+        }
+        if self.log_file != other.log_file {
+            settings.push("log_file");
+        }
+        if self.log_max_bytes != other.log_max_bytes
+            || self.log_rotate_daily != other.log_rotate_daily
+            || self.log_max_files != other.log_max_files
+        {
+            settings.push("log rotation");
+        }
+        if self.log_level != other.log_level {
+            settings.push("log_level");
+        }
+        if self.log_format != other.log_format {
+            settings.push("log_format");
+        }
+        if self.backup_file != other.backup_file {
+            settings.push("backup_file");
+        }
+        if self.persistence_backend != other.persistence_backend {
+            settings.push("persistence_backend");
+        }
+        if self.persistence_database != other.persistence_database {
+            settings.push("persistence_database");
+        }
+        if self.wal_enabled != other.wal_enabled {
+            settings.push("wal_enabled");
+        }
+        if self.bridge_address != other.bridge_address
+            || self.bridge_client_id != other.bridge_client_id
+            || self.bridge_username != other.bridge_username
+            || self.bridge_password != other.bridge_password
+            || self.bridge_topics_in != other.bridge_topics_in
+            || self.bridge_topics_out != other.bridge_topics_out
+            || self.bridge_remote_prefix != other.bridge_remote_prefix
+        {
+            settings.push("bridge");
+        }
+        if self.cluster_node_id != other.cluster_node_id
+            || self.cluster_peers != other.cluster_peers
+            || self.cluster_username != other.cluster_username
+            || self.cluster_password != other.cluster_password
+        {
+            settings.push("cluster");
+        }
+        if self.admin_http_address != other.admin_http_address
+            || self.admin_http_token != other.admin_http_token
+        {
+            settings.push("admin_http");
+        }
+        if self.worker_threads != other.worker_threads {
+            settings.push("worker_threads");
+        }
+        if self.task_queue_size != other.task_queue_size {
+            settings.push("task_queue_size");
+        }
+        settings
+    }
+
+    /// Returns the addresses the server listens on for TCP connections
+    pub fn get_addresses(&self) -> &[String] {
+        &self.addresses
+    }
+
+    /// Returns the address of the WebSocket listener. None if it is disabled
+    pub fn get_websocket_address(&self) -> Option<&str> {
+        if self.websocket_address.is_empty() {
+            None
+        } else {
+            Some(&self.websocket_address)
+        }
+    }
+
+    /// Returns the path of the Unix domain socket listener. None if it is disabled
+    pub fn get_unix_socket_path(&self) -> Option<&str> {
+        if self.unix_socket_path.is_empty() {
+            None
+        } else {
+            Some(&self.unix_socket_path)
+        }
     }
 
     /// Returns the log file of the server
@@ -89,14 +360,63 @@ impl Config {
         &self.log_file
     }
 
+    /// Returns the size in bytes at which the log file is rotated. None if it is not rotated by size
+    pub fn get_log_max_bytes(&self) -> Option<u64> {
+        if self.log_max_bytes == 0 {
+            None
+        } else {
+            Some(self.log_max_bytes)
+        }
+    }
+
+    /// Returns whether the log file is rotated every day
+    pub fn get_log_rotate_daily(&self) -> bool {
+        self.log_rotate_daily
+    }
+
+    /// Returns the number of rotated log files kept
+    pub fn get_log_max_files(&self) -> usize {
+        self.log_max_files
+    }
+
+    /// Returns the minimum level of the entries written to the log
+    pub fn get_log_level(&self) -> LogLevel {
+        self.log_level
+    }
+
+    /// Returns the format of the log entries (text or json)
+    pub fn get_log_format(&self) -> LogFormat {
+        self.log_format
+    }
+
     /// Returns the login file of the server
     pub fn get_login_file(&self) -> &str {
         &self.login_file
     }
 
     /// Returns the key of the encryption
-    pub fn get_key(&self) -> &[u8; 32] {
-        &self.key
+    pub fn get_key(&self) -> [u8; KEY_LENGTH] {
+        let mut key = [0; KEY_LENGTH];
+        for (i, byte) in self.key.bytes().take(KEY_LENGTH).enumerate() {
+            key[i] = byte;
+        }
+        key
+    }
+
+    /// Returns the key the packets are encrypted with. Empty if encryption is disabled,
+    /// in which case the packets are framed as plain MQTT
+    pub fn get_packet_key(&self) -> &[u8] {
+        if self.encryption {
+            self.key.as_bytes()
+        } else {
+            NO_ENCRYPTION
+        }
+    }
+
+    /// Returns true if the server follows the MQTT 3.1.1 specification strictly,
+    /// enforcing the keep alive of the clients and assigning ids to clients that send none
+    pub fn get_strict_mode(&self) -> bool {
+        self.strict_mode
     }
 
     pub fn get_backup_file(&self) -> Option<String> {
@@ -114,4 +434,343 @@ impl Config {
     pub fn get_segs_to_backup(&self) -> u32 {
         self.segs_to_backup
     }
+
+    /// Returns the backend used to persist the broker state (file or sqlite)
+    pub fn get_persistence_backend(&self) -> &str {
+        &self.persistence_backend
+    }
+
+    /// Returns the path of the database used by the sqlite persistence backend
+    pub fn get_persistence_database(&self) -> &str {
+        &self.persistence_database
+    }
+
+    /// Returns the authentication backend used to store the clients (file, sqlite or webhook)
+    pub fn get_auth_backend(&self) -> &str {
+        &self.auth_backend
+    }
+
+    /// Returns the path of the database used by the sqlite authentication backend
+    pub fn get_auth_database(&self) -> &str {
+        &self.auth_database
+    }
+
+    /// Returns the url called by the webhook authentication backend
+    pub fn get_auth_webhook_url(&self) -> &str {
+        &self.auth_webhook_url
+    }
+
+    /// Returns true if clients without login or with unknown ids are accepted as guests
+    pub fn get_allow_anonymous(&self) -> bool {
+        self.allow_anonymous
+    }
+
+    /// Returns the topics that anonymous clients are allowed to publish to
+    pub fn get_anonymous_publish_rules(&self) -> &str {
+        &self.anonymous_publish_rules
+    }
+
+    /// Returns the topics that anonymous clients are allowed to subscribe to
+    pub fn get_anonymous_subscribe_rules(&self) -> &str {
+        &self.anonymous_subscribe_rules
+    }
+
+    /// Returns the maximum number of concurrent connections. None if there is no limit
+    pub fn get_max_connections(&self) -> Option<usize> {
+        if self.max_connections == 0 {
+            None
+        } else {
+            Some(self.max_connections)
+        }
+    }
+
+    /// Returns the maximum size of a publish payload. None if there is no limit
+    pub fn get_max_payload_bytes(&self) -> Option<usize> {
+        if self.max_payload_bytes == 0 {
+            None
+        } else {
+            Some(self.max_payload_bytes)
+        }
+    }
+
+    /// Returns whether a client that publishes an oversized payload is disconnected
+    pub fn get_disconnect_on_oversized_payload(&self) -> bool {
+        self.disconnect_on_oversized_payload
+    }
+
+    /// Returns whether a client that publishes to an empty topic or one with wildcards is disconnected
+    pub fn get_disconnect_on_invalid_topic(&self) -> bool {
+        self.disconnect_on_invalid_topic
+    }
+
+    /// Returns whether QoS 0 messages are sent to the subscribers without going through the task queue
+    pub fn get_fast_path(&self) -> bool {
+        self.fast_path
+    }
+
+    /// Returns the maximum number of topic filters a client can be subscribed to. None if there is no limit
+    pub fn get_max_subscriptions_per_client(&self) -> Option<usize> {
+        if self.max_subscriptions_per_client == 0 {
+            None
+        } else {
+            Some(self.max_subscriptions_per_client)
+        }
+    }
+
+    /// Returns the maximum number of unacknowledged QoS 1 messages sent to a client.
+    /// None if there is no limit
+    pub fn get_max_inflight(&self) -> Option<usize> {
+        if self.max_inflight == 0 {
+            None
+        } else {
+            Some(self.max_inflight as usize)
+        }
+    }
+
+    /// Returns whether clients can subscribe to topic filters with the multi-level wildcard
+    pub fn get_allow_multi_level_wildcard(&self) -> bool {
+        self.allow_multi_level_wildcard
+    }
+
+    /// Returns how many seconds a message stays retained. None if retained messages don't expire
+    pub fn get_retained_ttl_secs(&self) -> Option<u64> {
+        if self.retained_ttl_secs == 0 {
+            None
+        } else {
+            Some(self.retained_ttl_secs)
+        }
+    }
+
+    /// Returns the number of workers that process new connections
+    pub fn get_worker_threads(&self) -> usize {
+        self.worker_threads.max(1)
+    }
+
+    /// Returns the number of packets that can be waiting to be sent to a client
+    pub fn get_outbound_queue_size(&self) -> usize {
+        self.outbound_queue_size.max(1)
+    }
+
+    /// Returns whether a client whose outbound queue is full is disconnected
+    pub fn get_disconnect_slow_consumers(&self) -> bool {
+        self.disconnect_slow_consumers
+    }
+
+    /// Returns the number of tasks that can be waiting for the task handler
+    /// before the connections stop being read
+    pub fn get_task_queue_size(&self) -> usize {
+        self.task_queue_size.max(1)
+    }
+
+    /// Returns whether changes are appended to a write-ahead log between backups
+    pub fn get_wal_enabled(&self) -> bool {
+        self.wal_enabled
+    }
+
+    /// Returns the address of the upstream broker the server bridges to. None if it is disabled
+    pub fn get_bridge_address(&self) -> Option<&str> {
+        if self.bridge_address.is_empty() {
+            None
+        } else {
+            Some(&self.bridge_address)
+        }
+    }
+
+    /// Returns the client id the bridge connects to the upstream broker with
+    pub fn get_bridge_client_id(&self) -> &str {
+        &self.bridge_client_id
+    }
+
+    /// Returns the username the bridge logs in to the upstream broker with
+    pub fn get_bridge_username(&self) -> &str {
+        &self.bridge_username
+    }
+
+    /// Returns the password the bridge logs in to the upstream broker with
+    pub fn get_bridge_password(&self) -> &str {
+        &self.bridge_password
+    }
+
+    /// Returns the topic filters subscribed in the upstream broker and published locally
+    pub fn get_bridge_topics_in(&self) -> &[String] {
+        &self.bridge_topics_in
+    }
+
+    /// Returns the topic filters of the local messages forwarded to the upstream broker
+    pub fn get_bridge_topics_out(&self) -> &[String] {
+        &self.bridge_topics_out
+    }
+
+    /// Returns the topic prefix the bridged topics have in the upstream broker
+    pub fn get_bridge_remote_prefix(&self) -> &str {
+        &self.bridge_remote_prefix
+    }
+
+    /// Returns the id of the server in the cluster. None if the cluster is disabled
+    pub fn get_cluster_node_id(&self) -> Option<&str> {
+        if self.cluster_node_id.is_empty() {
+            None
+        } else {
+            Some(&self.cluster_node_id)
+        }
+    }
+
+    /// Returns the addresses of the other servers of the cluster
+    pub fn get_cluster_peers(&self) -> &[String] {
+        &self.cluster_peers
+    }
+
+    /// Returns the username the server logs in to the other servers of the cluster with
+    pub fn get_cluster_username(&self) -> &str {
+        &self.cluster_username
+    }
+
+    /// Returns the password the server logs in to the other servers of the cluster with
+    pub fn get_cluster_password(&self) -> &str {
+        &self.cluster_password
+    }
+
+    /// Returns the address of the HTTP admin API. None if it is disabled
+    pub fn get_admin_http_address(&self) -> Option<&str> {
+        if self.admin_http_address.is_empty() {
+            None
+        } else {
+            Some(&self.admin_http_address)
+        }
+    }
+
+    /// Returns the token the requests to the HTTP admin API must send. None if they are not authenticated
+    pub fn get_admin_http_token(&self) -> Option<&str> {
+        if self.admin_http_token.is_empty() {
+            None
+        } else {
+            Some(&self.admin_http_token)
+        }
+    }
+
+    /// Returns the ids of the clients that are not allowed to connect
+    pub fn get_banned_clients(&self) -> &[String] {
+        &self.banned_clients
+    }
+
+    /// Returns the IP addresses that are not allowed to connect
+    pub fn get_banned_addresses(&self) -> &[IpAddr] {
+        &self.banned_addresses
+    }
+
+    /// Returns the networks the connections must come from. Empty if every network is allowed
+    pub fn get_allowed_networks(&self) -> &[Cidr] {
+        &self.allowed_networks
+    }
+
+    /// Returns the networks whose connections are closed before reading any packet
+    pub fn get_denied_networks(&self) -> &[Cidr] {
+        &self.denied_networks
+    }
+}
+
+/// Returns true if the address ends with a port other than 0
+fn has_valid_port(address: &str) -> bool {
+    address
+        .rsplit_once(':')
+        .and_then(|(_, port)| port.parse::<u16>().ok())
+        .is_some_and(|port| port != 0)
+}
+
+/// Represents a list setting, written as a TOML array or as a comma separated string
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum List {
+    Array(Vec<String>),
+    CommaSeparated(String),
+}
+
+/// Deserializes a list setting, ignoring the empty entries
+fn deserialize_list<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let entries = match List::deserialize(deserializer)? {
+        List::Array(entries) => entries,
+        List::CommaSeparated(value) => value.split(',').map(str::to_string).collect(),
+    };
+
+    entries
+        .iter()
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry
+                .parse()
+                .map_err(|err| de::Error::custom(format!("invalid entry {}: {}", entry, err)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "12345678901234567890123456789012";
+
+    #[test]
+    fn test_missing_settings_take_their_default_value() {
+        let config = Config::from_toml(&format!(
+            "address=\"127.0.0.1:1883, 127.0.0.1:1884\"\nkey=\"{}\"\nbridge_topics_in=[\"a/#\", \"b\"]",
+            KEY
+        ))
+        .unwrap();
+
+        assert_eq!(config.get_addresses(), ["127.0.0.1:1883", "127.0.0.1:1884"]);
+        assert_eq!(config.get_bridge_topics_in(), ["a/#", "b"]);
+        assert_eq!(config.get_segs_to_backup(), DEFAULT_SEGS_TO_BACKUP);
+        assert_eq!(config.get_worker_threads(), DEFAULT_WORKER_THREADS);
+        assert_eq!(config.get_log_level(), LogLevel::Info);
+        assert_eq!(config.get_packet_key(), KEY.as_bytes());
+        assert!(config.get_banned_addresses().is_empty());
+    }
+
+    #[test]
+    fn test_every_problem_is_reported_at_once() {
+        let err = Config::from_toml(
+            "address=\"127.0.0.1:0\"\nkey=\"short\"\nsegs_to_backup=0\nadmin_http_address=\"localhost\"\nlog_level=\"verbose\"\nmax_connections=\"ten\"",
+        )
+        .unwrap_err();
+
+        match err {
+            ServerError::InvalidConfig(problems) => assert_eq!(
+                problems,
+                vec![
+                    "unknown log level verbose in `log_level`",
+                    "invalid type: string \"ten\", expected usize in `max_connections`",
+                    "key must be 32 bytes long, it has 5",
+                    "address 127.0.0.1:0 must end with a port between 1 and 65535",
+                    "admin_http_address localhost must end with a port between 1 and 65535",
+                    "segs_to_backup must be greater than 0",
+                ]
+            ),
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_invalid_values_and_unknown_settings_are_rejected() {
+        let base = format!("address=\"127.0.0.1:1883\"\nkey=\"{}\"\n", KEY);
+
+        for setting in [
+            "log_level=\"verbose\"",
+            "banned_addresses=\"10.0.0.1, nope\"",
+            "allowed_networks=\"10.0.0.0/33\"",
+            "max_inflight=70000",
+            "adress=\"127.0.0.1:1883\"",
+        ] {
+            assert!(
+                Config::from_toml(&format!("{}{}", base, setting)).is_err(),
+                "{} was accepted",
+                setting
+            );
+        }
+    }
 }
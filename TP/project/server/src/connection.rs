@@ -0,0 +1,275 @@
+use std::{
+    io::{self, Cursor, Read},
+    net::{Shutdown, TcpStream},
+    os::{fd::OwnedFd, unix::net::UnixStream},
+    sync::Arc,
+};
+
+use mio::{
+    event::Source,
+    net::{TcpStream as MioTcpStream, UnixStream as MioUnixStream},
+};
+use mqtt::model::{components::fixed_header::FixedHeader, packet::Packet};
+
+use crate::{
+    client_stats::ConnectionTraffic,
+    client_stream::{write_all_retrying, ClientStream},
+    error::{ServerError, ServerResult},
+    websocket::{self, Frame, WebSocketStream},
+};
+
+const READ_CHUNK_SIZE: usize = 4096;
+/// A fixed header has one byte for the type and up to four for the remaining length
+const MAX_FIXED_HEADER_SIZE: usize = 5;
+
+/// Represents the protocol a connection was accepted with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transport {
+    Tcp,
+    WebSocket,
+    Unix,
+}
+
+/// Represents the stage of a connection in the event loop
+#[derive(Debug, PartialEq)]
+pub enum ConnectionState {
+    /// Waiting for the WebSocket upgrade request
+    Handshake,
+    /// Waiting for the CONNECT packet
+    AwaitingConnect,
+    /// The CONNECT packet is being processed by a worker
+    Authenticating,
+    /// The client was accepted
+    Connected(Vec<u8>),
+    /// The client disconnected and the task handler already knows it
+    Closing,
+}
+
+/// Represents the socket the event loop reads a connection from
+#[derive(Debug)]
+enum Socket {
+    Tcp(MioTcpStream),
+    Unix(MioUnixStream),
+}
+
+/// Represents a connection handled by the event loop of the server.
+/// Bytes are read without blocking and buffered until a whole packet arrives
+#[derive(Debug)]
+pub struct Connection {
+    socket: Socket,
+    /// Handle to write to the connection. Always a plain TCP or Unix stream,
+    /// WebSocket clients are wrapped when they are handed to the rest of the server
+    stream: ClientStream,
+    transport: Transport,
+    pub state: ConnectionState,
+    /// Bytes received and not processed yet
+    incoming: Vec<u8>,
+    /// MQTT bytes received and not parsed yet. For WebSocket clients it holds the frame payloads
+    packet_data: Vec<u8>,
+    /// Packets received and sent through the connection
+    traffic: Arc<ConnectionTraffic>,
+}
+
+impl Connection {
+    /// Creates a connection from a socket accepted by the event loop.
+    /// The socket is a Unix domain socket if the transport is Unix, and a TCP socket otherwise
+    pub fn new(socket: OwnedFd, transport: Transport) -> io::Result<Self> {
+        let (socket, stream) = match transport {
+            Transport::Unix => {
+                let stream = UnixStream::from(socket);
+                stream.set_nonblocking(true)?;
+                (
+                    Socket::Unix(MioUnixStream::from_std(stream.try_clone()?)),
+                    ClientStream::Unix(stream),
+                )
+            }
+            Transport::Tcp | Transport::WebSocket => {
+                let stream = TcpStream::from(socket);
+                stream.set_nonblocking(true)?;
+                (
+                    Socket::Tcp(MioTcpStream::from_std(stream.try_clone()?)),
+                    ClientStream::Tcp(stream),
+                )
+            }
+        };
+
+        let state = match transport {
+            Transport::Tcp | Transport::Unix => ConnectionState::AwaitingConnect,
+            Transport::WebSocket => ConnectionState::Handshake,
+        };
+
+        Ok(Self {
+            socket,
+            stream,
+            transport,
+            state,
+            incoming: Vec::new(),
+            packet_data: Vec::new(),
+            traffic: Arc::new(ConnectionTraffic::default()),
+        })
+    }
+
+    /// Returns the socket to register in the event loop
+    pub fn socket(&mut self) -> &mut dyn Source {
+        match &mut self.socket {
+            Socket::Tcp(socket) => socket,
+            Socket::Unix(socket) => socket,
+        }
+    }
+
+    /// Returns a stream that the rest of the server can use to write to the client
+    pub fn client_stream(&self) -> io::Result<ClientStream> {
+        Ok(match (self.transport, self.stream.try_clone()?) {
+            (Transport::WebSocket, ClientStream::Tcp(stream)) => {
+                ClientStream::WebSocket(WebSocketStream::new(stream))
+            }
+            (_, stream) => stream,
+        })
+    }
+
+    /// Returns the counters of the packets that went through the connection
+    pub fn traffic(&self) -> Arc<ConnectionTraffic> {
+        self.traffic.clone()
+    }
+
+    /// Reads everything that is available in the socket.
+    /// Returns false if the client closed the connection
+    pub fn read_available(&mut self) -> io::Result<bool> {
+        let mut chunk = [0u8; READ_CHUNK_SIZE];
+
+        loop {
+            let result = match &mut self.socket {
+                Socket::Tcp(socket) => socket.read(&mut chunk),
+                Socket::Unix(socket) => socket.read(&mut chunk),
+            };
+
+            match result {
+                Ok(0) => return Ok(false),
+                Ok(read) => self.incoming.extend_from_slice(&chunk[..read]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(true),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Returns the next complete packet received, if any.
+    /// Fails if the client sent invalid data or closed the WebSocket
+    pub fn next_packet(&mut self, key: &[u8]) -> ServerResult<Option<Packet>> {
+        match self.transport {
+            Transport::Tcp | Transport::Unix => self.packet_data.append(&mut self.incoming),
+            Transport::WebSocket => {
+                if self.state == ConnectionState::Handshake && !self.complete_handshake()? {
+                    return Ok(None);
+                }
+                self.decode_frames()?;
+            }
+        }
+
+        let packet_length = match packet_length(&self.packet_data, key)? {
+            Some(packet_length) => packet_length,
+            None => return Ok(None),
+        };
+
+        let packet_bytes: Vec<u8> = self.packet_data.drain(..packet_length).collect();
+        self.traffic.record_received(packet_length);
+        let packet = Packet::from_bytes(&mut Cursor::new(packet_bytes), key)?;
+
+        Ok(Some(packet))
+    }
+
+    /// Answers the WebSocket upgrade request once it was completely received
+    fn complete_handshake(&mut self) -> ServerResult<bool> {
+        let request_length = match websocket::handshake_request_length(&self.incoming)? {
+            Some(request_length) => request_length,
+            None => return Ok(false),
+        };
+
+        let request: Vec<u8> = self.incoming.drain(..request_length).collect();
+        match websocket::handshake_response(&String::from_utf8_lossy(&request)) {
+            Some(response) => {
+                write_all_retrying(&self.stream, response.as_bytes())?;
+                self.state = ConnectionState::AwaitingConnect;
+                Ok(true)
+            }
+            None => {
+                let _ = write_all_retrying(&self.stream, websocket::BAD_REQUEST_RESPONSE);
+                Err(ServerError::ClientConnection(
+                    "Invalid WebSocket handshake".to_string(),
+                ))
+            }
+        }
+    }
+
+    /// Moves the payload of the received WebSocket frames to the MQTT buffer
+    fn decode_frames(&mut self) -> ServerResult<()> {
+        while let Some((frame, length)) = websocket::decode_frame(&self.incoming)? {
+            self.incoming.drain(..length);
+
+            match frame {
+                Frame::Data(payload) => self.packet_data.extend(payload),
+                Frame::Ping(payload) => {
+                    write_all_retrying(&self.stream, &websocket::pong_frame(&payload))?
+                }
+                Frame::Pong => {}
+                Frame::Close => {
+                    let _ = write_all_retrying(&self.stream, &websocket::close_frame());
+                    return Err(ServerError::ClientConnection(
+                        "WebSocket closed by the client".to_string(),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Closes the connection
+    pub fn close(&self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// Returns the length of the packet at the start of the buffer,
+/// or None if it was not completely received yet
+fn packet_length(buffer: &[u8], key: &[u8]) -> ServerResult<Option<usize>> {
+    let mut cursor = Cursor::new(buffer);
+
+    let fixed_header = match FixedHeader::from_bytes(&mut cursor) {
+        Ok(fixed_header) => fixed_header,
+        Err(_) if buffer.len() < MAX_FIXED_HEADER_SIZE => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+
+    let packet_length = cursor.position() as usize + fixed_header.content_length(key);
+    if buffer.len() < packet_length {
+        return Ok(None);
+    }
+
+    Ok(Some(packet_length))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mqtt::model::packets::pingreq::Pingreq;
+
+    const KEY: &[u8; 32] = b"12345678901234567890123456789012";
+
+    #[test]
+    fn test_packet_length_waits_for_whole_packet() {
+        let bytes = Pingreq::new().to_bytes(KEY);
+
+        assert_eq!(packet_length(&bytes[..1], KEY).unwrap(), None);
+        assert_eq!(packet_length(&bytes, KEY).unwrap(), Some(bytes.len()));
+
+        let mut two_packets = bytes.clone();
+        two_packets.extend(&bytes);
+        assert_eq!(packet_length(&two_packets, KEY).unwrap(), Some(bytes.len()));
+    }
+
+    #[test]
+    fn test_packet_length_rejects_malformed_header() {
+        assert!(packet_length(&[0x30, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF], KEY).is_err());
+    }
+}
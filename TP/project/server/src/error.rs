@@ -14,12 +14,17 @@ pub enum ServerError {
     Mqtt(MqttError),
     ArgumentError(String),
     ClientConnection(String),
-    UnsupportedPacket,
     ChannelSend(String),
     PoisonedLock,
     Utf8Error(FromUtf8Error),
     NoLoginProvided,
     NoPasswordProvided,
+    AuthBackend(String),
+    InvalidBackup(String),
+    PersistenceBackend(String),
+    Database(String),
+    /// Every problem found in the configuration file
+    InvalidConfig(Vec<String>),
 }
 
 impl fmt::Display for ServerError {
@@ -29,12 +34,18 @@ impl fmt::Display for ServerError {
             ServerError::Mqtt(err) => write!(f, "MQTT error: {:?}", err),
             ServerError::ArgumentError(msg) => write!(f, "Argument error: {}", msg),
             ServerError::ClientConnection(msg) => write!(f, "Client connection error: {}", msg),
-            ServerError::UnsupportedPacket => write!(f, "Unsupported packet error"),
             ServerError::ChannelSend(msg) => write!(f, "Channel send error: {}", msg),
             ServerError::PoisonedLock => write!(f, "Poisoned lock error"),
             ServerError::Utf8Error(err) => write!(f, "UTF-8 error: {}", err),
             ServerError::NoLoginProvided => write!(f, "No login provided"),
             ServerError::NoPasswordProvided => write!(f, "No password provided"),
+            ServerError::AuthBackend(msg) => write!(f, "Authentication backend error: {}", msg),
+            ServerError::InvalidBackup(msg) => write!(f, "Invalid backup: {}", msg),
+            ServerError::PersistenceBackend(msg) => write!(f, "Persistence backend error: {}", msg),
+            ServerError::Database(msg) => write!(f, "Database error: {}", msg),
+            ServerError::InvalidConfig(problems) => {
+                write!(f, "Invalid configuration: {}", problems.join("; "))
+            }
         }
     }
 }
@@ -68,3 +79,9 @@ impl From<FromUtf8Error> for ServerError {
         ServerError::Utf8Error(err)
     }
 }
+
+impl From<rusqlite::Error> for ServerError {
+    fn from(err: rusqlite::Error) -> Self {
+        ServerError::Database(err.to_string())
+    }
+}
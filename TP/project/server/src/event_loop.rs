@@ -0,0 +1,455 @@
+use std::{
+    collections::HashMap,
+    fs, io, net,
+    os::{
+        fd::OwnedFd,
+        unix::{fs::FileTypeExt, net::UnixListener as StdUnixListener},
+    },
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+};
+
+use mio::{
+    net::{TcpListener, UnixListener},
+    Events, Interest, Poll, Token, Waker,
+};
+use mqtt::model::packet::Packet;
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use signal_hook_mio::v1_0::Signals;
+use thread_pool::thread_pool::ThreadPool;
+
+use crate::{
+    connection::{Connection, ConnectionState, Transport},
+    error::{ServerError, ServerResult},
+    server::{disconnect_client, handle_packet, Server},
+    task_handler::{DisconnectCause, Task},
+};
+
+const WAKER: Token = Token(0);
+const SIGNALS: Token = Token(1);
+const FIRST_LISTENER: usize = 2;
+/// Tokens below this value are reserved for the listeners
+const FIRST_CONNECTION: usize = 16;
+const EVENTS_CAPACITY: usize = 1024;
+
+/// Represents a socket the event loop accepts connections from
+enum Listener {
+    Tcp(TcpListener, Transport),
+    Unix(UnixListener),
+}
+
+/// Represents the result of processing a CONNECT packet: the token of the connection
+/// and the id of the client if it was accepted
+type ConnectResult = (Token, Option<Vec<u8>>);
+
+/// Represents the event loop that reads from every connection of the server in a single thread.
+/// Complete packets are forwarded to the task handler, except CONNECT packets, which may block
+/// on the authentication backend and are processed by a bounded pool of workers
+pub struct EventLoop {
+    server: Server,
+    poll: Poll,
+    listeners: HashMap<Token, Listener>,
+    connections: HashMap<Token, Connection>,
+    next_token: usize,
+    workers: ThreadPool,
+    waker: Arc<Waker>,
+    signals: Signals,
+    connect_results_sender: Sender<ConnectResult>,
+    connect_results_receiver: Receiver<ConnectResult>,
+}
+
+impl EventLoop {
+    /// Creates the event loop binding the listeners configured in the server
+    pub fn new(server: Server) -> ServerResult<Self> {
+        let poll = Poll::new()?;
+        let waker = Arc::new(Waker::new(poll.registry(), WAKER)?);
+        let mut signals = Signals::new([SIGHUP, SIGINT, SIGTERM])?;
+        poll.registry()
+            .register(&mut signals, SIGNALS, Interest::READABLE)?;
+        let (connect_results_sender, connect_results_receiver) = mpsc::channel();
+        let workers = ThreadPool::new(server.config().get_worker_threads());
+
+        let mut event_loop = Self {
+            server,
+            poll,
+            listeners: HashMap::new(),
+            connections: HashMap::new(),
+            next_token: FIRST_CONNECTION,
+            workers,
+            waker,
+            signals,
+            connect_results_sender,
+            connect_results_receiver,
+        };
+
+        let addresses = event_loop.server.config().get_addresses().to_vec();
+        if addresses.is_empty() {
+            return Err(ServerError::ArgumentError(
+                "The server has no address to listen on".to_string(),
+            ));
+        }
+        for address in addresses {
+            event_loop.add_listener(&address, Transport::Tcp)?;
+        }
+
+        if let Some(address) = event_loop.server.config().get_websocket_address() {
+            let address = address.to_string();
+            event_loop.add_listener(&address, Transport::WebSocket)?;
+        }
+
+        if let Some(path) = event_loop.server.config().get_unix_socket_path() {
+            let path = path.to_string();
+            event_loop.add_unix_listener(&path)?;
+        }
+
+        Ok(event_loop)
+    }
+
+    /// Binds a listener and registers it in the poll
+    fn add_listener(&mut self, address: &str, transport: Transport) -> ServerResult<()> {
+        let token = self.next_listener_token()?;
+        let listener = net::TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let mut listener = TcpListener::from_std(listener);
+
+        self.poll
+            .registry()
+            .register(&mut listener, token, Interest::READABLE)?;
+        self.listeners
+            .insert(token, Listener::Tcp(listener, transport));
+
+        let message = match transport {
+            Transport::WebSocket => format!("WebSocket listener running on address: {}\n", address),
+            _ => format!("Server running on address: {}\n", address),
+        };
+        self.server.log_file().info(&message);
+
+        Ok(())
+    }
+
+    /// Binds a Unix domain socket listener and registers it in the poll.
+    /// A socket left by a previous run is replaced. Access to the listener is controlled
+    /// with the permissions of the socket file and its directory
+    fn add_unix_listener(&mut self, path: &str) -> ServerResult<()> {
+        let token = self.next_listener_token()?;
+        if fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+            fs::remove_file(path)?;
+        }
+
+        let listener = StdUnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        let mut listener = UnixListener::from_std(listener);
+
+        self.poll
+            .registry()
+            .register(&mut listener, token, Interest::READABLE)?;
+        self.listeners.insert(token, Listener::Unix(listener));
+
+        self.server.log_file().info(&format!(
+            "Unix domain socket listener running on: {}\n",
+            path
+        ));
+
+        Ok(())
+    }
+
+    /// Returns the token for a new listener
+    fn next_listener_token(&self) -> ServerResult<Token> {
+        if FIRST_LISTENER + self.listeners.len() >= FIRST_CONNECTION {
+            return Err(ServerError::ArgumentError(
+                "Too many listener addresses".to_string(),
+            ));
+        }
+        Ok(Token(FIRST_LISTENER + self.listeners.len()))
+    }
+
+    /// Runs the event loop until the poll fails or the server receives SIGINT or SIGTERM.
+    /// SIGHUP reloads the configuration
+    pub fn run(mut self) -> ServerResult<()> {
+        let mut events = Events::with_capacity(EVENTS_CAPACITY);
+
+        loop {
+            if let Err(err) = self.poll.poll(&mut events, None) {
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err.into());
+            }
+
+            for event in events.iter() {
+                match event.token() {
+                    SIGNALS => {
+                        if self.handle_signals() {
+                            return self.shutdown();
+                        }
+                    }
+                    WAKER => self.handle_connect_results(),
+                    token if self.listeners.contains_key(&token) => self.accept_connections(token),
+                    token => self.handle_readable_connection(token),
+                }
+            }
+        }
+    }
+
+    /// Handles the signals received. Returns true if the server has to shut down
+    fn handle_signals(&mut self) -> bool {
+        let mut shutdown = false;
+
+        for signal in self.signals.pending() {
+            if signal == SIGHUP {
+                // Reloading may block on the authentication backend
+                let server = self.server.clone();
+                self.workers.execute(move || {
+                    server.log_file().info("Reloading configuration");
+                    if let Err(err) = server.reload_config() {
+                        server
+                            .log_file()
+                            .error(&format!("Error reloading configuration: {:?}", err));
+                    }
+                });
+            } else {
+                shutdown = true;
+            }
+        }
+
+        shutdown
+    }
+
+    /// Stops accepting connections, waits for the CONNECT packets being processed and
+    /// lets the task handler disconnect every client and save a final backup
+    fn shutdown(self) -> ServerResult<()> {
+        let EventLoop {
+            server,
+            listeners,
+            connections,
+            workers,
+            ..
+        } = self;
+        server.log_file().info("Shutting down server");
+
+        drop(listeners);
+        if let Some(path) = server.config().get_unix_socket_path() {
+            let _ = fs::remove_file(path);
+        }
+        // Dropping the pool joins its workers
+        drop(workers);
+
+        let (done_sender, done_receiver) = mpsc::channel();
+        server
+            .client_actions_sender()
+            .send(Task::Shutdown(done_sender))?;
+        let _ = done_receiver.recv();
+
+        for connection in connections.values() {
+            connection.close();
+        }
+
+        Ok(())
+    }
+
+    /// Accepts every pending connection of a listener
+    fn accept_connections(&mut self, listener_token: Token) {
+        loop {
+            let accepted = match self.listeners.get(&listener_token) {
+                Some(Listener::Tcp(listener, transport)) => listener
+                    .accept()
+                    .map(|(socket, address)| (OwnedFd::from(socket), *transport, Some(address))),
+                Some(Listener::Unix(listener)) => listener
+                    .accept()
+                    .map(|(socket, _)| (OwnedFd::from(socket), Transport::Unix, None)),
+                None => return,
+            };
+
+            let (socket, transport, address) = match accepted {
+                Ok(accepted) => accepted,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return,
+                Err(err) => {
+                    self.server
+                        .log_file()
+                        .error(&format!("Error accepting connection: {:?}", err));
+                    return;
+                }
+            };
+
+            // Local connections through the Unix socket have no address to filter
+            if let Some(address) = address {
+                if !self.server.is_address_permitted(address.ip()) {
+                    self.server.log_file().warn(&format!(
+                        "Connection from {} rejected by the network filter",
+                        address
+                    ));
+                    continue;
+                }
+            }
+
+            self.server.log_file().info("New connection received");
+
+            if let Err(err) = self.register_connection(socket, transport) {
+                self.server
+                    .log_file()
+                    .error(&format!("Error registering connection: {:?}", err));
+            }
+        }
+    }
+
+    /// Registers an accepted connection in the poll
+    fn register_connection(&mut self, socket: OwnedFd, transport: Transport) -> ServerResult<()> {
+        let mut connection = Connection::new(socket, transport)?;
+        let token = Token(self.next_token);
+        self.next_token += 1;
+
+        self.poll
+            .registry()
+            .register(connection.socket(), token, Interest::READABLE)?;
+        self.connections.insert(token, connection);
+
+        Ok(())
+    }
+
+    /// Reads the data available in a connection and processes the complete packets
+    fn handle_readable_connection(&mut self, token: Token) {
+        let open = match self.connections.get_mut(&token) {
+            Some(connection) => match connection.read_available() {
+                Ok(open) => open,
+                Err(err) => {
+                    self.server
+                        .log_file()
+                        .error(&format!("Connection Error: {:?}", err));
+                    false
+                }
+            },
+            None => return,
+        };
+
+        match self.process_packets(token) {
+            Err(cause) => self.close_connection(token, cause),
+            Ok(()) if !open => self.close_connection(token, DisconnectCause::ConnectionLost),
+            Ok(()) => {}
+        }
+    }
+
+    /// Processes the complete packets buffered in a connection.
+    /// Returns why the connection has to be closed, if it has to
+    /// Returns false if the connection has to be closed
+    fn process_packets(&mut self, token: Token) -> Result<(), DisconnectCause> {
+        let key = self.server.config().get_packet_key().to_vec();
+
+        loop {
+            let connection = match self.connections.get_mut(&token) {
+                Some(connection) => connection,
+                None => return Err(DisconnectCause::ConnectionLost),
+            };
+
+            // Packets sent after CONNECT wait until the client is accepted
+            if connection.state == ConnectionState::Authenticating {
+                return Ok(());
+            }
+
+            let packet = match connection.next_packet(&key) {
+                Ok(Some(packet)) => packet,
+                Ok(None) => return Ok(()),
+                Err(err) => {
+                    self.server
+                        .log_file()
+                        .error(&format!("Error reading packet: {:?}", err));
+                    return Err(DisconnectCause::ProtocolError);
+                }
+            };
+
+            match &connection.state {
+                ConnectionState::Connected(client_id) => {
+                    if let Packet::Publish(publish_packet) = &packet {
+                        if self.server.publish_directly(publish_packet, client_id) {
+                            continue;
+                        }
+                    }
+                    if !handle_packet(
+                        packet,
+                        client_id.clone(),
+                        self.server.client_actions_sender().clone(),
+                        self.server.log_file().clone(),
+                    ) {
+                        // The task handler was already told that the client left and why
+                        connection.state = ConnectionState::Closing;
+                        return Err(DisconnectCause::Requested);
+                    }
+                }
+                _ => match packet {
+                    Packet::Connect(connect_packet) => {
+                        let stream = match connection.client_stream() {
+                            Ok(stream) => stream,
+                            Err(_) => return Err(DisconnectCause::ConnectionLost),
+                        };
+                        let traffic = connection.traffic();
+                        connection.state = ConnectionState::Authenticating;
+
+                        let server = self.server.clone();
+                        let connect_results_sender = self.connect_results_sender.clone();
+                        let waker = self.waker.clone();
+
+                        self.workers.execute(move || {
+                            let result = server.connect_new_client(connect_packet, stream, traffic);
+                            let _ = connect_results_sender.send((token, result));
+                            let _ = waker.wake();
+                        });
+                        return Ok(());
+                    }
+                    _ => {
+                        self.server
+                            .log_file()
+                            .error("Received an unsupported packet type");
+                        return Err(DisconnectCause::ProtocolError);
+                    }
+                },
+            }
+        }
+    }
+
+    /// Applies the results of the CONNECT packets processed by the workers
+    fn handle_connect_results(&mut self) {
+        while let Ok((token, result)) = self.connect_results_receiver.try_recv() {
+            match (self.connections.get_mut(&token), result) {
+                (Some(connection), Some(client_id)) => {
+                    connection.state = ConnectionState::Connected(client_id);
+                    if let Err(cause) = self.process_packets(token) {
+                        self.close_connection(token, cause);
+                    }
+                }
+                // The client was refused, so the task handler does not know it
+                (Some(_), None) => self.close_connection(token, DisconnectCause::ConnectionLost),
+                (None, Some(client_id)) => {
+                    // The connection was closed while the client was being authenticated
+                    self.server.log_file().info("Disconnecting client");
+                    let _ = disconnect_client(
+                        self.server.client_actions_sender().clone(),
+                        client_id,
+                        DisconnectCause::ConnectionLost,
+                    );
+                }
+                (None, None) => {}
+            }
+        }
+    }
+
+    /// Removes a connection from the poll and tells the task handler why, if the client was connected
+    fn close_connection(&mut self, token: Token, cause: DisconnectCause) {
+        let mut connection = match self.connections.remove(&token) {
+            Some(connection) => connection,
+            None => return,
+        };
+
+        let _ = self.poll.registry().deregister(connection.socket());
+        connection.close();
+
+        if let ConnectionState::Connected(client_id) = &connection.state {
+            self.server.log_file().info("Disconnecting client");
+            let _ = disconnect_client(
+                self.server.client_actions_sender().clone(),
+                client_id.clone(),
+                cause,
+            );
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use mqtt::model::{components::qos::QoS, packets::publish::Publish};
+
+use crate::{client_writer::WriterHandle, subscription_trie::SubscriptionTrie};
+
+/// Represents what is needed to route a message without the task handler
+#[derive(Debug)]
+struct RoutingSnapshot {
+    subscriptions: SubscriptionTrie,
+    /// Writers of the connected clients with no queued messages, so a message sent directly
+    /// can't overtake the ones the task handler is holding for them
+    writers: HashMap<Vec<u8>, WriterHandle>,
+    max_payload_bytes: Option<usize>,
+}
+
+/// Lets the event loop send QoS 0 messages straight to the subscribers, without waiting
+/// behind the rest of the tasks. The task handler replaces the snapshot of the subscriptions
+/// whenever they change, and readers only hold the lock to take the current one
+#[derive(Debug)]
+pub struct FastPath {
+    snapshot: RwLock<Option<Arc<RoutingSnapshot>>>,
+    key: Vec<u8>,
+}
+
+impl FastPath {
+    /// Creates a disabled fast path, which is enabled by the first snapshot
+    pub fn new(key: Vec<u8>) -> Self {
+        FastPath {
+            snapshot: RwLock::new(None),
+            key,
+        }
+    }
+
+    /// Replaces the subscriptions and writers the messages are routed with
+    pub fn update(
+        &self,
+        subscriptions: SubscriptionTrie,
+        writers: HashMap<Vec<u8>, WriterHandle>,
+        max_payload_bytes: Option<usize>,
+    ) {
+        self.replace(Some(Arc::new(RoutingSnapshot {
+            subscriptions,
+            writers,
+            max_payload_bytes,
+        })));
+    }
+
+    /// Makes every message go through the task handler
+    pub fn disable(&self) {
+        self.replace(None);
+    }
+
+    fn replace(&self, snapshot: Option<Arc<RoutingSnapshot>>) {
+        match self.snapshot.write() {
+            Ok(mut current) => *current = snapshot,
+            Err(poisoned) => *poisoned.into_inner() = snapshot,
+        }
+    }
+
+    /// Sends a message to the subscribers of its topic. Returns the clients it was sent to,
+    /// or None if it must go through the task handler: it is not a plain QoS 0 message,
+    /// it is dropped by the server or some subscriber can't receive it directly
+    pub fn publish(&self, publish_packet: &Publish) -> Option<Vec<Vec<u8>>> {
+        let topic_name = publish_packet.topic();
+        if publish_packet.qos() != &QoS::AtMost
+            || publish_packet.retain()
+            || topic_name.server_reserved()
+            || topic_name.validate().is_err()
+        {
+            return None;
+        }
+
+        let snapshot = match self.snapshot.read() {
+            Ok(snapshot) => snapshot.clone()?,
+            Err(_) => return None,
+        };
+        if snapshot
+            .max_payload_bytes
+            .is_some_and(|max_payload_bytes| publish_packet.message().len() > max_payload_bytes)
+        {
+            return None;
+        }
+
+        let mut writers = vec![];
+        for client_id in snapshot.subscriptions.subscribers(topic_name) {
+            let writer = snapshot.writers.get(&client_id)?;
+            writers.push((client_id, writer));
+        }
+
+        let bytes = publish_packet.to_bytes(&self.key);
+        Some(
+            writers
+                .into_iter()
+                .filter(|(_, writer)| writer.send(&bytes).is_ok())
+                .map(|(client_id, _)| client_id)
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{client_stream::ClientStream, client_writer::ClientWriter};
+    use mqtt::model::components::{
+        topic_filter::TopicFilter, topic_level::TopicLevel, topic_name::TopicName,
+    };
+    use mqtt::model::packet::Packet;
+    use std::{
+        net::{TcpListener, TcpStream},
+        time::Duration,
+    };
+
+    const KEY: &[u8] = b"12345678901234567890123456789012";
+
+    fn writer() -> (ClientWriter, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+
+        let mut writer = ClientWriter::new(ClientStream::Tcp(server));
+        writer.start(10, false).unwrap();
+        (writer, client)
+    }
+
+    fn topic_filter(filter: &str) -> TopicFilter {
+        let levels = filter
+            .split('/')
+            .map(|level| TopicLevel::from_bytes(level.as_bytes().to_vec()).unwrap())
+            .collect();
+        TopicFilter::new(levels, false)
+    }
+
+    fn publish(qos: QoS, topic: &str) -> Publish {
+        let levels = topic.split('/').map(|level| level.as_bytes().to_vec());
+        let packet_identifier = (qos != QoS::AtMost).then_some(1);
+        Publish::new(
+            false,
+            qos,
+            false,
+            TopicName::new(levels.collect(), false),
+            packet_identifier,
+            b"telemetry".to_vec(),
+        )
+    }
+
+    #[test]
+    fn test_qos0_messages_are_sent_to_the_subscribers() {
+        let (writer, mut client) = writer();
+        let mut subscriptions = SubscriptionTrie::new();
+        subscriptions.subscribe(&topic_filter("drone-data/#"), b"monitor");
+
+        let fast_path = FastPath::new(KEY.to_vec());
+        let message = publish(QoS::AtMost, "drone-data/1");
+        assert_eq!(fast_path.publish(&message), None);
+
+        let writers = HashMap::from([(b"monitor".to_vec(), writer.handle().unwrap())]);
+        fast_path.update(subscriptions, writers, None);
+        assert_eq!(fast_path.publish(&message), Some(vec![b"monitor".to_vec()]));
+        assert_eq!(
+            fast_path.publish(&publish(QoS::AtMost, "camera-data")),
+            Some(vec![])
+        );
+
+        match Packet::from_bytes(&mut client, KEY).unwrap() {
+            Packet::Publish(received) => assert_eq!(received.message(), message.message()),
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+    }
+
+    #[test]
+    fn test_other_messages_go_through_the_task_handler() {
+        let (writer, _client) = writer();
+        let mut subscriptions = SubscriptionTrie::new();
+        subscriptions.subscribe(&topic_filter("drone-data/#"), b"monitor");
+        subscriptions.subscribe(&topic_filter("camera-data"), b"offline");
+
+        let fast_path = FastPath::new(KEY.to_vec());
+        let writers = HashMap::from([(b"monitor".to_vec(), writer.handle().unwrap())]);
+        fast_path.update(subscriptions.clone(), writers.clone(), None);
+
+        // QoS 1 and a subscriber without a writer
+        assert_eq!(
+            fast_path.publish(&publish(QoS::AtLeast, "drone-data/1")),
+            None
+        );
+        assert_eq!(
+            fast_path.publish(&publish(QoS::AtMost, "camera-data")),
+            None
+        );
+
+        // A payload above the limit
+        fast_path.update(subscriptions, writers, Some(4));
+        assert_eq!(
+            fast_path.publish(&publish(QoS::AtMost, "drone-data/1")),
+            None
+        );
+
+        fast_path.disable();
+        assert_eq!(
+            fast_path.publish(&publish(QoS::AtMost, "drone-data/1")),
+            None
+        );
+    }
+}
@@ -1,33 +1,237 @@
-use chrono::Local;
-use mqtt::model::packets::{publish::Publish, subscribe::Subscribe, unsubscribe::Unsubscribe};
+use chrono::{Local, NaiveDate};
+use mqtt::model::{
+    components::{topic_filter::TopicFilter, topic_name::TopicName},
+    packets::{publish::Publish, unsubscribe::Unsubscribe},
+};
+use serde::Deserialize;
+
+use crate::message_trace::{Stage, Trace};
 use std::{
-    fs::OpenOptions,
-    io::Write,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    net::SocketAddr,
     sync::mpsc::{self, Sender},
     thread,
+    time::Duration,
 };
 
-const LOG_LEVEL_INFO: &str = "INFO";
-const LOG_LEVEL_ERROR: &str = "ERROR";
+const DEFAULT_MAX_FILES: usize = 5;
+
+/// Represents the severity of a log entry. Entries below the level of the logger are discarded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(try_from = "String")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    /// Parses a level from its name, ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "DEBUG" => Some(LogLevel::Debug),
+            "INFO" => Some(LogLevel::Info),
+            "WARN" => Some(LogLevel::Warn),
+            "ERROR" => Some(LogLevel::Error),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the level as written in the log
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+        }
+    }
+}
+
+impl TryFrom<String> for LogLevel {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, String> {
+        LogLevel::from_name(&name).ok_or_else(|| format!("unknown log level {}", name))
+    }
+}
+
+/// Represents how the log entries are written
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum LogFormat {
+    /// `[<timestamp>] <LEVEL>: <message>`
+    Text,
+    /// One JSON object per line with the timestamp, level, client_id, packet_type and message
+    Json,
+}
+
+impl LogFormat {
+    /// Parses a format from its name, ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "text" => Some(LogFormat::Text),
+            "json" => Some(LogFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<String> for LogFormat {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, String> {
+        LogFormat::from_name(&name).ok_or_else(|| format!("unknown log format {}", name))
+    }
+}
+
+/// Represents when the log file is rotated. The rotated files are named `<log file>.1`
+/// (the newest) up to `<log file>.<max_files>`, and older ones are removed
+#[derive(Debug, Clone, PartialEq)]
+pub struct RotationPolicy {
+    /// Size in bytes the log file can't exceed. None to disable rotation by size
+    pub max_bytes: Option<u64>,
+    /// Rotate the log file when the day changes
+    pub daily: bool,
+    /// Number of rotated files kept
+    pub max_files: usize,
+}
+
+impl Default for RotationPolicy {
+    fn default() -> Self {
+        Self {
+            max_bytes: None,
+            daily: false,
+            max_files: DEFAULT_MAX_FILES,
+        }
+    }
+}
+
+/// Writes the log entries to the file, rotating it as the policy says
+struct LogWriter {
+    path: String,
+    policy: RotationPolicy,
+    file: File,
+    size: u64,
+    opened_on: NaiveDate,
+}
+
+impl LogWriter {
+    fn open(path: &str, policy: RotationPolicy) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        let opened_on = match file.metadata()?.modified() {
+            Ok(modified) => chrono::DateTime::<Local>::from(modified).date_naive(),
+            Err(_) => Local::now().date_naive(),
+        };
+
+        Ok(Self {
+            path: path.to_string(),
+            policy,
+            file,
+            size,
+            opened_on,
+        })
+    }
+
+    fn write(&mut self, log_entry: &str, today: NaiveDate) -> io::Result<()> {
+        let entry_size = log_entry.len() as u64 + 1;
+        let day_changed = self.policy.daily && today != self.opened_on;
+        let too_large = self
+            .policy
+            .max_bytes
+            .is_some_and(|max_bytes| self.size > 0 && self.size + entry_size > max_bytes);
+
+        if day_changed || too_large {
+            self.rotate(today)?;
+        }
+
+        writeln!(self.file, "{}", log_entry)?;
+        self.size += entry_size;
+        Ok(())
+    }
+
+    /// Shifts the rotated files, moves the current one to `<log file>.1` and starts an empty one
+    fn rotate(&mut self, today: NaiveDate) -> io::Result<()> {
+        let rotated_path = |index: usize| format!("{}.{}", self.path, index);
+
+        let _ = fs::remove_file(rotated_path(self.policy.max_files.max(1)));
+        for index in (1..self.policy.max_files).rev() {
+            let _ = fs::rename(rotated_path(index), rotated_path(index + 1));
+        }
+        if self.policy.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            fs::rename(&self.path, rotated_path(1))?;
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        self.opened_on = today;
+        Ok(())
+    }
+}
+
+/// Represents what happened during a connection of a client, logged when it ends
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionSummary {
+    /// Address the client connected from. None for Unix domain sockets
+    pub address: Option<SocketAddr>,
+    pub duration: Duration,
+    pub packets_received: u64,
+    pub bytes_received: u64,
+    pub packets_sent: u64,
+    pub bytes_sent: u64,
+    /// Subscriptions the client held when it disconnected
+    pub subscriptions: usize,
+    pub reason: String,
+}
+
+impl SessionSummary {
+    /// Returns the fields of the summary with their values written as JSON
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            (
+                "address",
+                self.address.map_or("null".to_string(), |address| {
+                    json_string(&address.to_string())
+                }),
+            ),
+            (
+                "duration_secs",
+                format!("{:.3}", self.duration.as_secs_f64()),
+            ),
+            ("packets_received", self.packets_received.to_string()),
+            ("bytes_received", self.bytes_received.to_string()),
+            ("packets_sent", self.packets_sent.to_string()),
+            ("bytes_sent", self.bytes_sent.to_string()),
+            ("subscriptions", self.subscriptions.to_string()),
+            ("reason", json_string(&self.reason)),
+        ]
+    }
+}
 
 /// Represents a logger that writes to a file
 #[derive(Debug, Clone)]
 pub struct Logger {
     sender: Sender<String>,
+    level: LogLevel,
+    format: LogFormat,
 }
 
 impl Logger {
-    /// Creates a new logger that writes to the specified file
-    pub fn new(log_file_path: &str) -> Self {
-        let (sender, receiver) = mpsc::channel();
+    /// Creates a new logger that writes to the specified file, rotating it with the policy
+    pub fn with_rotation(log_file_path: &str, policy: RotationPolicy) -> Self {
+        let (sender, receiver) = mpsc::channel::<String>();
         let file_path = log_file_path.to_string();
         thread::spawn(move || {
-            let mut file = match OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(&file_path)
-            {
-                Ok(file) => file,
+            let mut writer = match LogWriter::open(&file_path, policy) {
+                Ok(writer) => writer,
                 Err(e) => {
                     eprintln!("Failed to open log file: {}", e);
                     return;
@@ -35,37 +239,107 @@ impl Logger {
             };
 
             for log_entry in receiver {
-                if let Err(e) = writeln!(file, "{}", log_entry) {
+                if let Err(e) = writer.write(&log_entry, Local::now().date_naive()) {
                     eprintln!("Failed to write to log file: {}", e);
                 }
             }
         });
 
-        Logger { sender }
+        Logger {
+            sender,
+            level: LogLevel::Info,
+            format: LogFormat::Text,
+        }
+    }
+
+    /// Sets the minimum level of the entries written
+    pub fn with_level(mut self, level: LogLevel) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets the format of the entries written
+    pub fn with_format(mut self, format: LogFormat) -> Self {
+        self.format = format;
+        self
     }
 
     /// Logs a message with the specified level
-    pub fn log(&self, level: &str, message: &str) {
+    pub fn log(&self, level: LogLevel, message: &str) {
+        self.log_entry(level, None, None, message);
+    }
+
+    /// Logs a message about a client and, optionally, the packet it refers to.
+    /// In the JSON format they are written as separate fields
+    fn log_entry(
+        &self,
+        level: LogLevel,
+        client_id: Option<&[u8]>,
+        packet_type: Option<&str>,
+        message: &str,
+    ) {
+        self.log_entry_with_fields(level, client_id, packet_type, message, &[]);
+    }
+
+    /// Logs an entry with extra fields, whose values are already written as JSON.
+    /// In the text format only the message is written, so it should include them
+    fn log_entry_with_fields(
+        &self,
+        level: LogLevel,
+        client_id: Option<&[u8]>,
+        packet_type: Option<&str>,
+        message: &str,
+        fields: &[(&str, String)],
+    ) {
+        if level < self.level {
+            return;
+        }
+
         let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-        let log_entry = format!("[{}] {}: {}", timestamp, level, message);
+        let log_entry = match self.format {
+            LogFormat::Text => format!("[{}] {}: {}", timestamp, level.name(), message),
+            LogFormat::Json => format_json(
+                &timestamp,
+                level,
+                client_id.map(String::from_utf8_lossy).as_deref(),
+                packet_type,
+                message,
+                fields,
+            ),
+        };
         match self.sender.send(log_entry) {
             Ok(_) => {}
             Err(e) => eprintln!("Failed to send log entry: {}", e),
         };
     }
 
+    /// Logs a debug message
+    pub fn debug(&self, message: &str) {
+        self.log(LogLevel::Debug, message);
+    }
+
     /// Logs an info message
     pub fn info(&self, message: &str) {
-        self.log(LOG_LEVEL_INFO, message);
+        self.log(LogLevel::Info, message);
+    }
+
+    /// Logs a warning message
+    pub fn warn(&self, message: &str) {
+        self.log(LogLevel::Warn, message);
     }
 
     /// Logs an error message
     pub fn error(&self, message: &str) {
-        self.log(LOG_LEVEL_ERROR, message);
+        self.log(LogLevel::Error, message);
     }
 
-    /// Logs a custom message for successful subscriptions
-    pub fn log_successful_subscription(&self, client_id: &[u8], subscribe_packet: &Subscribe) {
+    /// Logs a custom message for the topic filters a client was subscribed to. The denied
+    /// ones of the same packet are logged by `log_subscription_denied`
+    pub fn log_successful_subscription(&self, client_id: &[u8], topic_filters: &[TopicFilter]) {
+        if topic_filters.is_empty() {
+            return;
+        }
+
         let client_id_str = match std::str::from_utf8(client_id) {
             Ok(id) => id,
             Err(_) => {
@@ -74,21 +348,18 @@ impl Logger {
             }
         };
 
-        let topics_str = subscribe_packet
-            .topics()
-            .iter()
-            .fold(String::new(), |acc, (topic, _)| {
-                match std::str::from_utf8(topic.to_string().as_bytes()) {
-                    Ok(topic_str) => acc + topic_str + ", ",
-                    Err(_) => acc + "Invalid UTF-8 topic, ",
-                }
-            });
+        let topics_str = topic_filters.iter().fold(String::new(), |acc, topic| {
+            match std::str::from_utf8(topic.to_string().as_bytes()) {
+                Ok(topic_str) => acc + topic_str + ", ",
+                Err(_) => acc + "Invalid UTF-8 topic, ",
+            }
+        });
 
         let message = format!(
             "Client {} subscribed to topics {}",
             client_id_str, topics_str
         );
-        self.info(&message);
+        self.log_entry(LogLevel::Info, Some(client_id), Some("Subscribe"), &message);
     }
 
     /// Logs a custom message for successful unsubscriptions
@@ -113,7 +384,12 @@ impl Logger {
             "Client {} unsubscribed to topics {}",
             client_id_str, topics_str
         );
-        self.info(&message);
+        self.log_entry(
+            LogLevel::Info,
+            Some(client_id),
+            Some("Unsubscribe"),
+            &message,
+        );
     }
 
     /// Logs a custom message for successful publish
@@ -124,7 +400,7 @@ impl Logger {
             String::from_utf8_lossy(publish_packet.message()),
             String::from_utf8_lossy(publish_packet.topic().to_string().as_bytes())
         );
-        self.info(message.as_str());
+        self.log_entry(LogLevel::Info, Some(client_id), Some("Publish"), &message);
     }
 
     /// Logs a custom message for successful disconnection
@@ -133,7 +409,22 @@ impl Logger {
             "Client {} does not exist",
             String::from_utf8_lossy(client_id)
         );
-        self.error(message.as_str());
+        self.log_entry(LogLevel::Error, Some(client_id), None, &message);
+    }
+
+    /// Logs a custom message for a packet received from a client
+    pub fn log_received_packet(&self, packet_type: &str, client_id: &[u8]) {
+        let message = format!(
+            "Received {} packet from client: {}",
+            packet_type,
+            String::from_utf8_lossy(client_id)
+        );
+        self.log_entry(
+            LogLevel::Debug,
+            Some(client_id),
+            Some(packet_type),
+            &message,
+        );
     }
 
     /// Logs a custom message for successful disconnection
@@ -143,7 +434,12 @@ impl Logger {
             packet_type,
             String::from_utf8_lossy(client_id)
         );
-        self.info(message.as_str());
+        self.log_entry(
+            LogLevel::Debug,
+            Some(client_id),
+            Some(packet_type),
+            &message,
+        );
     }
 
     /// Logs a custom message for successful disconnection
@@ -153,7 +449,12 @@ impl Logger {
             packet_type,
             String::from_utf8_lossy(client_id)
         );
-        self.error(message.as_str());
+        self.log_entry(
+            LogLevel::Error,
+            Some(client_id),
+            Some(packet_type),
+            &message,
+        );
     }
 
     /// Logs a custom message for successful disconnection
@@ -163,19 +464,48 @@ impl Logger {
             String::from_utf8_lossy(client_id),
             packet_type
         );
-        self.error(message.as_str());
+        self.log_entry(
+            LogLevel::Error,
+            Some(client_id),
+            Some(packet_type),
+            &message,
+        );
     }
 
     /// Logs a custom message for successful disconnection
     pub fn log_sent_message(&self, message: String, client_id: String) {
         let message = format!("Sent message: {} to client {}", message, client_id,);
-        self.info(message.as_str());
+        self.log_entry(
+            LogLevel::Debug,
+            Some(client_id.as_bytes()),
+            Some("Publish"),
+            &message,
+        );
     }
 
     /// Logs a custom message for successful disconnection
     pub fn log_sending_message_error(&self, message: String, client_id: String) {
         let message = format!("Error sending message: {} to client {}", message, client_id);
-        self.error(message.as_str());
+        self.log_entry(
+            LogLevel::Error,
+            Some(client_id.as_bytes()),
+            Some("Publish"),
+            &message,
+        );
+    }
+
+    /// Logs a custom message for a message dropped because the client is not reading fast enough
+    pub fn log_slow_consumer(&self, message: String, client_id: String) {
+        let message = format!(
+            "Outbound queue of client {} is full. Message dropped: {}",
+            client_id, message
+        );
+        self.log_entry(
+            LogLevel::Warn,
+            Some(client_id.as_bytes()),
+            Some("Publish"),
+            &message,
+        );
     }
 
     /// Logs a custom message for client registration
@@ -184,6 +514,271 @@ impl Logger {
             "Client with id {} has been registered successfully",
             String::from_utf8_lossy(client_id)
         );
-        self.info(message.as_str());
+        self.log_entry(LogLevel::Info, Some(client_id), None, &message);
+    }
+
+    /// Logs a custom message for a rejected subscription and the reason it was rejected
+    pub fn log_subscription_denied(
+        &self,
+        client_id: &[u8],
+        topic_filter: &TopicFilter,
+        reason: &str,
+    ) {
+        let message = format!(
+            "Client {} is not allowed to subscribe to topic {}: {}",
+            String::from_utf8_lossy(client_id),
+            topic_filter,
+            reason
+        );
+        self.log_entry(LogLevel::Warn, Some(client_id), Some("Subscribe"), &message);
+    }
+
+    /// Logs a custom message for a publish dropped by the client ACL
+    pub fn log_publish_denied(&self, client_id: &[u8], topic_name: &TopicName) {
+        let message = format!(
+            "Client {} is not allowed to publish to topic {}. Message dropped",
+            String::from_utf8_lossy(client_id),
+            topic_name
+        );
+        self.log_entry(LogLevel::Warn, Some(client_id), Some("Publish"), &message);
+    }
+
+    /// Logs the summary of a connection of a client that ended
+    pub fn log_session_summary(&self, client_id: &[u8], summary: &SessionSummary) {
+        let fields = summary.fields();
+        let message = format!(
+            "Session of client {} ended: {}",
+            String::from_utf8_lossy(client_id),
+            fields
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect::<Vec<String>>()
+                .join(" ")
+        );
+        self.log_entry_with_fields(LogLevel::Info, Some(client_id), None, &message, &fields);
+    }
+
+    /// Logs the time a traced message took to go through a stage, measured from its reception
+    /// for the task queue and the delivery to a client. Only written at the debug level
+    pub fn log_trace_hop(
+        &self,
+        trace: &Trace,
+        stage: Stage,
+        client_id: Option<&[u8]>,
+        elapsed: Duration,
+    ) {
+        if self.level > LogLevel::Debug {
+            return;
+        }
+
+        let elapsed_micros = elapsed.as_micros();
+        let message = match client_id {
+            Some(client_id) => format!(
+                "Message {} was handed to client {} after {}us ({})",
+                trace.id(),
+                String::from_utf8_lossy(client_id),
+                elapsed_micros,
+                stage
+            ),
+            None => format!(
+                "Message {} spent {}us in {}",
+                trace.id(),
+                elapsed_micros,
+                stage
+            ),
+        };
+        let fields = [
+            ("trace_id", trace.id().to_string()),
+            ("stage", json_string(stage.name())),
+            ("elapsed_us", elapsed_micros.to_string()),
+        ];
+        self.log_entry_with_fields(LogLevel::Debug, client_id, None, &message, &fields);
+    }
+
+    /// Logs a custom message for a publish dropped because its topic is empty or has wildcards
+    pub fn log_invalid_topic(&self, client_id: &[u8], topic_name: &TopicName, reason: &str) {
+        let message = format!(
+            "Client {} published to invalid topic \"{}\": {}. Message dropped",
+            String::from_utf8_lossy(client_id),
+            topic_name,
+            reason
+        );
+        self.log_entry(LogLevel::Warn, Some(client_id), Some("Publish"), &message);
+    }
+
+    /// Logs a custom message for a publish dropped because its payload is too large
+    pub fn log_payload_too_large(&self, client_id: &[u8], topic_name: &TopicName, size: usize) {
+        let message = format!(
+            "Client {} published {} bytes to topic {}, above the payload limit. Message dropped",
+            String::from_utf8_lossy(client_id),
+            size,
+            topic_name
+        );
+        self.log_entry(LogLevel::Warn, Some(client_id), Some("Publish"), &message);
+    }
+}
+
+/// Formats a log entry as a JSON object. Missing fields are written as null,
+/// and the extra fields are appended after the message
+fn format_json(
+    timestamp: &str,
+    level: LogLevel,
+    client_id: Option<&str>,
+    packet_type: Option<&str>,
+    message: &str,
+    fields: &[(&str, String)],
+) -> String {
+    let optional = |value: Option<&str>| value.map_or("null".to_string(), json_string);
+    let extra: String = fields
+        .iter()
+        .map(|(name, value)| format!(",{}:{}", json_string(name), value))
+        .collect();
+
+    format!(
+        "{{\"timestamp\":{},\"level\":{},\"client_id\":{},\"packet_type\":{},\"message\":{}{}}}",
+        json_string(timestamp),
+        json_string(level.name()),
+        optional(client_id),
+        optional(packet_type),
+        json_string(message),
+        extra
+    )
+}
+
+/// Quotes a string as a JSON string, escaping the characters that need it
+pub fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_lines(path: &str) -> Vec<String> {
+        fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn remove_logs(path: &str) {
+        let _ = fs::remove_file(path);
+        for index in 1..=3 {
+            let _ = fs::remove_file(format!("{}.{}", path, index));
+        }
+    }
+
+    #[test]
+    fn test_json_format() {
+        assert_eq!(
+            format_json(
+                "2024-06-01 10:00:00",
+                LogLevel::Warn,
+                Some("drone-1"),
+                None,
+                "Say \"hi\"\n",
+                &[]
+            ),
+            "{\"timestamp\":\"2024-06-01 10:00:00\",\"level\":\"WARN\",\"client_id\":\"drone-1\",\"packet_type\":null,\"message\":\"Say \\\"hi\\\"\\n\"}"
+        );
+    }
+
+    #[test]
+    fn test_session_summary_fields() {
+        let summary = SessionSummary {
+            address: Some("10.0.0.7:51000".parse().unwrap()),
+            duration: Duration::from_millis(12_500),
+            packets_received: 4,
+            bytes_received: 120,
+            packets_sent: 3,
+            bytes_sent: 64,
+            subscriptions: 2,
+            reason: "connection lost".to_string(),
+        };
+
+        assert_eq!(
+            format_json(
+                "2024-06-01 10:00:00",
+                LogLevel::Info,
+                Some("drone-1"),
+                None,
+                "Session ended",
+                &summary.fields()
+            ),
+            "{\"timestamp\":\"2024-06-01 10:00:00\",\"level\":\"INFO\",\"client_id\":\"drone-1\",\"packet_type\":null,\"message\":\"Session ended\",\"address\":\"10.0.0.7:51000\",\"duration_secs\":12.500,\"packets_received\":4,\"bytes_received\":120,\"packets_sent\":3,\"bytes_sent\":64,\"subscriptions\":2,\"reason\":\"connection lost\"}"
+        );
+    }
+
+    #[test]
+    fn test_levels_are_ordered_and_parsed() {
+        assert!(LogLevel::Debug < LogLevel::Info);
+        assert!(LogLevel::Warn < LogLevel::Error);
+        assert_eq!(LogLevel::from_name("warn"), Some(LogLevel::Warn));
+        assert_eq!(LogLevel::from_name("verbose"), None);
+        assert_eq!(LogFormat::from_name("JSON"), Some(LogFormat::Json));
+    }
+
+    #[test]
+    fn test_rotation_by_size_keeps_max_files() {
+        let path = "test_log_rotation_size.log";
+        remove_logs(path);
+        let today = Local::now().date_naive();
+        let policy = RotationPolicy {
+            max_bytes: Some(10),
+            daily: false,
+            max_files: 2,
+        };
+
+        let mut writer = LogWriter::open(path, policy).unwrap();
+        for entry in ["entry 1", "entry 2", "entry 3", "entry 4"] {
+            writer.write(entry, today).unwrap();
+        }
+
+        assert_eq!(read_lines(path), vec!["entry 4"]);
+        assert_eq!(read_lines(&format!("{}.1", path)), vec!["entry 3"]);
+        assert_eq!(read_lines(&format!("{}.2", path)), vec!["entry 2"]);
+        assert!(fs::metadata(format!("{}.3", path)).is_err());
+
+        remove_logs(path);
+    }
+
+    #[test]
+    fn test_daily_rotation() {
+        let path = "test_log_rotation_daily.log";
+        remove_logs(path);
+        let today = Local::now().date_naive();
+        let policy = RotationPolicy {
+            max_bytes: None,
+            daily: true,
+            max_files: 1,
+        };
+
+        let mut writer = LogWriter::open(path, policy).unwrap();
+        writer.write("yesterday", today).unwrap();
+        writer.write("still yesterday", today).unwrap();
+        writer.write("today", today.succ_opt().unwrap()).unwrap();
+
+        assert_eq!(read_lines(path), vec!["today"]);
+        assert_eq!(
+            read_lines(&format!("{}.1", path)),
+            vec!["yesterday", "still yesterday"]
+        );
+
+        remove_logs(path);
     }
 }
@@ -7,13 +7,35 @@ use server::Server;
 use std::env;
 use std::path::Path;
 
+mod acl;
+mod admin_api;
+mod auth;
+mod backup;
+mod bridge;
 mod client;
 mod client_manager;
+mod client_stats;
+mod client_stream;
+mod client_writer;
+mod cluster;
 mod config;
+mod connection;
 mod error;
+mod event_loop;
+mod fast_path;
 mod logfile;
+mod message_trace;
+mod network_filter;
+mod outbound_queue;
+mod password;
+mod persistence;
 mod server;
+mod subscription_trie;
+mod task_channel;
 mod task_handler;
+mod topic_stats;
+mod wal;
+mod websocket;
 
 static SERVER_ARGS: usize = 2;
 
@@ -0,0 +1,236 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+static NEXT_TRACE_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Identifies a message received by the server and remembers when it arrived,
+/// so the time it spends in each hop until it reaches the subscribers can be measured
+#[derive(Debug, Clone, Copy)]
+pub struct Trace {
+    id: u64,
+    received_at: Instant,
+}
+
+impl Trace {
+    /// Starts the trace of a message that was just received
+    pub fn start() -> Self {
+        Trace {
+            id: NEXT_TRACE_ID.fetch_add(1, Ordering::Relaxed),
+            received_at: Instant::now(),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the time since the message was received
+    pub fn elapsed(&self) -> Duration {
+        self.received_at.elapsed()
+    }
+}
+
+/// Represents the hops a message goes through in the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// From the moment the message is received until the task handler takes it
+    TaskQueue,
+    /// Sending the message to the connected subscribers and queueing it for the rest
+    Routing,
+    /// From the moment the message is received until it is handed to the writer of a subscriber,
+    /// including the time it waits in the queue of a disconnected or slow subscriber
+    Delivery,
+    /// Storing a change of the state in the persistence backend
+    Backup,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 4] = [
+        Stage::TaskQueue,
+        Stage::Routing,
+        Stage::Delivery,
+        Stage::Backup,
+    ];
+
+    /// Returns the name of the stage used in logs and $SYS topics
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::TaskQueue => "task_queue",
+            Stage::Routing => "routing",
+            Stage::Delivery => "delivery",
+            Stage::Backup => "backup",
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Upper bounds of the buckets of the histograms, in microseconds.
+/// Slower durations fall in a last bucket without bound
+const BUCKET_BOUNDS_MICROS: [u64; 9] = [
+    100, 500, 1_000, 5_000, 10_000, 50_000, 100_000, 500_000, 1_000_000,
+];
+
+/// Counts durations in buckets of increasing width.
+/// The counters are atomic, so durations can be counted through a shared reference
+#[derive(Debug, Default)]
+pub struct LatencyHistogram {
+    buckets: [AtomicU64; BUCKET_BOUNDS_MICROS.len() + 1],
+    count: AtomicU64,
+    total_micros: AtomicU64,
+    max_micros: AtomicU64,
+}
+
+impl LatencyHistogram {
+    /// Counts a duration in its bucket
+    pub fn record(&self, duration: Duration) {
+        let micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        let bucket = BUCKET_BOUNDS_MICROS
+            .iter()
+            .position(|&bound| micros <= bound)
+            .unwrap_or(BUCKET_BOUNDS_MICROS.len());
+
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total_micros.fetch_add(micros, Ordering::Relaxed);
+        self.max_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    /// Returns the number of durations counted
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the average of the durations in microseconds
+    pub fn mean_micros(&self) -> u64 {
+        self.total_micros
+            .load(Ordering::Relaxed)
+            .checked_div(self.count())
+            .unwrap_or(0)
+    }
+
+    /// Returns the longest duration in microseconds
+    pub fn max_micros(&self) -> u64 {
+        self.max_micros.load(Ordering::Relaxed)
+    }
+
+    /// Returns the upper bound of the bucket that holds the percentile, in microseconds.
+    /// The longest duration is returned for the bucket without bound
+    pub fn percentile_micros(&self, percentile: u64) -> u64 {
+        let rank = (self.count() * percentile).div_ceil(100).max(1);
+        let max_micros = self.max_micros();
+        let mut counted = 0;
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            counted += count.load(Ordering::Relaxed);
+            if counted >= rank {
+                return BUCKET_BOUNDS_MICROS
+                    .get(bucket)
+                    .map_or(max_micros, |&bound| bound.min(max_micros));
+            }
+        }
+        0
+    }
+
+    /// Returns the count of each bucket as lines of `<=bound_us count`
+    pub fn buckets(&self) -> String {
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(|(bucket, count)| {
+                let count = count.load(Ordering::Relaxed);
+                match BUCKET_BOUNDS_MICROS.get(bucket) {
+                    Some(bound) => format!("<={}us {}", bound, count),
+                    None => format!(">{}us {}", BUCKET_BOUNDS_MICROS[bucket - 1], count),
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+/// Keeps a latency histogram for each stage and remembers the ones that changed
+/// since they were last taken
+#[derive(Debug, Default)]
+pub struct MessageLatencies {
+    histograms: [LatencyHistogram; Stage::ALL.len()],
+    changed: [AtomicBool; Stage::ALL.len()],
+}
+
+impl MessageLatencies {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts the time a message spent in a stage
+    pub fn record(&self, stage: Stage, duration: Duration) {
+        self.histograms[stage as usize].record(duration);
+        self.changed[stage as usize].store(true, Ordering::Relaxed);
+    }
+
+    /// Returns the histogram of a stage
+    pub fn histogram(&self, stage: Stage) -> &LatencyHistogram {
+        &self.histograms[stage as usize]
+    }
+
+    /// Returns the stages whose histograms changed since the last call, with their histograms
+    pub fn take_changed(&self) -> Vec<(Stage, &LatencyHistogram)> {
+        Stage::ALL
+            .into_iter()
+            .filter(|&stage| self.changed[stage as usize].swap(false, Ordering::Relaxed))
+            .map(|stage| (stage, self.histogram(stage)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_counts_durations_in_buckets() {
+        let histogram = LatencyHistogram::default();
+        for micros in [50, 80, 300, 2_000, 2_000_000] {
+            histogram.record(Duration::from_micros(micros));
+        }
+
+        assert_eq!(histogram.count(), 5);
+        assert_eq!(histogram.max_micros(), 2_000_000);
+        assert_eq!(histogram.mean_micros(), 400_486);
+        assert_eq!(histogram.percentile_micros(50), 500);
+        assert_eq!(histogram.percentile_micros(10), 100);
+        assert_eq!(histogram.percentile_micros(99), 2_000_000);
+        assert!(histogram.buckets().starts_with("<=100us 2\n<=500us 1\n"));
+        assert!(histogram.buckets().ends_with(">1000000us 1"));
+
+        assert_eq!(LatencyHistogram::default().percentile_micros(99), 0);
+    }
+
+    #[test]
+    fn test_only_changed_stages_are_taken() {
+        let latencies = MessageLatencies::new();
+        latencies.record(Stage::Routing, Duration::from_micros(10));
+        latencies.record(Stage::Delivery, Duration::from_micros(20));
+
+        let changed: Vec<Stage> = latencies
+            .take_changed()
+            .into_iter()
+            .map(|(stage, _)| stage)
+            .collect();
+        assert_eq!(changed, vec![Stage::Routing, Stage::Delivery]);
+        assert!(latencies.take_changed().is_empty());
+        assert_eq!(latencies.histogram(Stage::Routing).count(), 1);
+        assert_eq!(latencies.histogram(Stage::TaskQueue).count(), 0);
+    }
+
+    #[test]
+    fn test_traces_have_different_ids() {
+        assert_ne!(Trace::start().id(), Trace::start().id());
+    }
+}
@@ -0,0 +1,139 @@
+use std::{fmt, net::IpAddr, str::FromStr};
+
+/// Represents a network in CIDR notation, like `10.0.0.0/24`. A single address is a network
+/// with the full prefix length
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix_length: u32,
+}
+
+impl Cidr {
+    /// Returns true if the address belongs to the network
+    pub fn contains(&self, address: IpAddr) -> bool {
+        match (self.network, address.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => prefix_matches(
+                network.to_bits().into(),
+                address.to_bits().into(),
+                32,
+                self.prefix_length,
+            ),
+            (IpAddr::V6(network), IpAddr::V6(address)) => prefix_matches(
+                network.to_bits(),
+                address.to_bits(),
+                128,
+                self.prefix_length,
+            ),
+            _ => false,
+        }
+    }
+}
+
+/// Returns true if the first bits of both addresses are equal
+fn prefix_matches(network: u128, address: u128, bits: u32, prefix_length: u32) -> bool {
+    let host_bits = bits - prefix_length;
+    network.checked_shr(host_bits).unwrap_or(0) == address.checked_shr(host_bits).unwrap_or(0)
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (network, prefix_length) = match value.split_once('/') {
+            Some((network, prefix_length)) => (network, Some(prefix_length)),
+            None => (value, None),
+        };
+
+        let network: IpAddr = network
+            .parse()
+            .map_err(|_| format!("invalid network address {}", network))?;
+        let max_prefix_length = if network.is_ipv4() { 32 } else { 128 };
+
+        let prefix_length = match prefix_length {
+            Some(prefix_length) => prefix_length
+                .parse()
+                .ok()
+                .filter(|prefix_length| *prefix_length <= max_prefix_length)
+                .ok_or_else(|| format!("invalid prefix length {}", prefix_length))?,
+            None => max_prefix_length,
+        };
+
+        Ok(Cidr {
+            network: network.to_canonical(),
+            prefix_length,
+        })
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_length)
+    }
+}
+
+/// Returns true if a connection from the address is accepted. The denied networks take
+/// precedence, and if there are allowed networks the address must belong to one of them
+pub fn is_permitted(address: IpAddr, allowed: &[Cidr], denied: &[Cidr]) -> bool {
+    if denied.iter().any(|network| network.contains(address)) {
+        return false;
+    }
+    allowed.is_empty() || allowed.iter().any(|network| network.contains(address))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(value: &str) -> Cidr {
+        value.parse().unwrap()
+    }
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_networks_contain_their_addresses() {
+        let subnet = cidr("192.168.10.0/24");
+        assert!(subnet.contains(ip("192.168.10.1")));
+        assert!(subnet.contains(ip("192.168.10.255")));
+        assert!(!subnet.contains(ip("192.168.11.1")));
+        assert!(!subnet.contains(ip("fe80::1")));
+
+        // IPv4 clients of a dual stack listener have mapped addresses
+        assert!(subnet.contains(ip("::ffff:192.168.10.7")));
+
+        assert!(cidr("10.0.0.5").contains(ip("10.0.0.5")));
+        assert!(!cidr("10.0.0.5").contains(ip("10.0.0.6")));
+        assert!(cidr("0.0.0.0/0").contains(ip("8.8.8.8")));
+        assert!(cidr("fd00::/8").contains(ip("fd12:3456::1")));
+        assert!(!cidr("fd00::/8").contains(ip("fe80::1")));
+    }
+
+    #[test]
+    fn test_invalid_networks_are_rejected() {
+        for value in [
+            "10.0.0.0/33",
+            "fd00::/129",
+            "10.0.0/24",
+            "10.0.0.0/x",
+            "drones",
+        ] {
+            assert!(value.parse::<Cidr>().is_err(), "{} was accepted", value);
+        }
+    }
+
+    #[test]
+    fn test_denied_networks_take_precedence() {
+        let allowed = [cidr("10.0.0.0/16")];
+        let denied = [cidr("10.0.5.0/24")];
+
+        assert!(is_permitted(ip("10.0.1.1"), &allowed, &denied));
+        assert!(!is_permitted(ip("10.0.5.1"), &allowed, &denied));
+        assert!(!is_permitted(ip("172.16.0.1"), &allowed, &denied));
+
+        // Without allowed networks every address that is not denied is accepted
+        assert!(is_permitted(ip("172.16.0.1"), &[], &denied));
+        assert!(!is_permitted(ip("10.0.5.1"), &[], &denied));
+    }
+}
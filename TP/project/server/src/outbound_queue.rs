@@ -0,0 +1,489 @@
+use std::collections::{HashMap, VecDeque};
+
+use mqtt::model::{components::qos::QoS, packets::publish::Publish};
+
+use crate::message_trace::Trace;
+
+/// Represents what happened to a message delivered to a client
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Delivery {
+    /// The client is disconnected or its in-flight window is full, so the message waits in its queue
+    Queued,
+    /// The message was sent, after the given number of messages that were waiting in the queue
+    Sent { flushed: usize },
+}
+
+/// Keeps the messages waiting to be delivered to each client in the order they were published.
+/// A message is never sent to a client before the ones queued for it, so every subscriber
+/// receives the messages of a topic in order even across reconnections.
+/// If there is an in-flight limit, the QoS 1 messages get a packet identifier of the client and
+/// no more than the limit are sent until the client acknowledges them
+#[derive(Debug, Default)]
+pub struct OutboundQueues {
+    /// Queued messages with the trace of the ones received since the server started
+    queues: HashMap<Vec<u8>, VecDeque<(Publish, Option<Trace>)>>,
+    /// Unacknowledged messages sent to each client, tracked only if there is an in-flight limit
+    inflight: HashMap<Vec<u8>, InflightWindow>,
+    max_inflight: Option<usize>,
+}
+
+/// Messages sent to a client that were not acknowledged yet, in the order they were sent
+#[derive(Debug, Default)]
+struct InflightWindow {
+    messages: VecDeque<Publish>,
+    last_packet_identifier: u16,
+}
+
+impl InflightWindow {
+    /// Returns a packet identifier that is not in flight
+    fn next_packet_identifier(&mut self) -> u16 {
+        loop {
+            self.last_packet_identifier = self.last_packet_identifier.wrapping_add(1);
+            if self.last_packet_identifier != 0 && !self.contains(self.last_packet_identifier) {
+                return self.last_packet_identifier;
+            }
+        }
+    }
+
+    fn contains(&self, packet_identifier: u16) -> bool {
+        self.messages
+            .iter()
+            .any(|message| message.package_identifier() == Some(packet_identifier))
+    }
+
+    /// Removes the message with the packet identifier. Returns true if it was in flight
+    fn remove(&mut self, packet_identifier: u16) -> bool {
+        let length = self.messages.len();
+        self.messages
+            .retain(|message| message.package_identifier() != Some(packet_identifier));
+        self.messages.len() < length
+    }
+}
+
+impl OutboundQueues {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of unacknowledged QoS 1 messages sent to each client
+    pub fn set_max_inflight(&mut self, max_inflight: Option<usize>) {
+        if max_inflight.is_none() {
+            self.inflight.clear();
+        }
+        self.max_inflight = max_inflight;
+    }
+
+    /// Delivers a message to a client. If it is connected, the messages queued for it are sent
+    /// first and then the message, otherwise the message is queued behind them.
+    /// It is also queued if the in-flight window of the client is full.
+    /// The trace of each message is handed with it when it is sent
+    pub fn deliver(
+        &mut self,
+        client_id: &[u8],
+        publish_packet: Publish,
+        trace: Option<Trace>,
+        connected: bool,
+        send: impl FnMut(Publish, Option<Trace>),
+    ) -> Delivery {
+        self.queues
+            .entry(client_id.to_vec())
+            .or_default()
+            .push_back((publish_packet, trace));
+        if !connected {
+            return Delivery::Queued;
+        }
+
+        match self.flush(client_id, send) {
+            sent if sent > 0 && self.len(client_id) == 0 => Delivery::Sent { flushed: sent - 1 },
+            _ => Delivery::Queued,
+        }
+    }
+
+    /// Sends the messages queued for a connected client, in order, until its in-flight window
+    /// is full. Returns the number of messages sent
+    pub fn flush(
+        &mut self,
+        client_id: &[u8],
+        mut send: impl FnMut(Publish, Option<Trace>),
+    ) -> usize {
+        let queue = match self.queues.get_mut(client_id) {
+            Some(queue) => queue,
+            None => return 0,
+        };
+
+        let mut sent = 0;
+        while let Some((publish_packet, trace)) = queue.pop_front() {
+            let publish_packet = match self.max_inflight {
+                Some(max_inflight) if publish_packet.qos() != &QoS::AtMost => {
+                    let window = self.inflight.entry(client_id.to_vec()).or_default();
+                    if window.messages.len() >= max_inflight {
+                        queue.push_front((publish_packet, trace));
+                        break;
+                    }
+                    let publish_packet = Publish::new(
+                        publish_packet.dup(),
+                        publish_packet.qos().clone(),
+                        publish_packet.retain(),
+                        publish_packet.topic().clone(),
+                        Some(window.next_packet_identifier()),
+                        publish_packet.message().clone(),
+                    );
+                    window.messages.push_back(publish_packet.clone());
+                    publish_packet
+                }
+                _ => publish_packet,
+            };
+            send(publish_packet, trace);
+            sent += 1;
+        }
+
+        if queue.is_empty() {
+            self.queues.remove(client_id);
+        }
+        sent
+    }
+
+    /// Releases the slot of an acknowledged message and sends the messages that fit in the
+    /// in-flight window. Returns true if this emptied the queue of the client
+    pub fn acknowledge(
+        &mut self,
+        client_id: &[u8],
+        packet_identifier: u16,
+        send: impl FnMut(Publish, Option<Trace>),
+    ) -> bool {
+        let acknowledged = self
+            .inflight
+            .get_mut(client_id)
+            .is_some_and(|window| window.remove(packet_identifier));
+
+        acknowledged && self.flush(client_id, send) > 0 && self.len(client_id) == 0
+    }
+
+    /// Forgets the messages in flight to a client, as they are lost with its connection
+    pub fn clear_inflight(&mut self, client_id: &[u8]) {
+        self.inflight.remove(client_id);
+    }
+
+    /// Sends again the messages in flight to a client that resumed its session, in the order
+    /// they were sent, with the DUP flag and the same packet identifiers as the client may have
+    /// received them. They keep their slots until the client acknowledges them.
+    /// Returns the number of messages sent
+    pub fn resend_inflight(&self, client_id: &[u8], mut send: impl FnMut(Publish)) -> usize {
+        let window = match self.inflight.get(client_id) {
+            Some(window) => window,
+            None => return 0,
+        };
+
+        for message in &window.messages {
+            send(Publish::new(
+                true,
+                message.qos().clone(),
+                message.retain(),
+                message.topic().clone(),
+                message.package_identifier(),
+                message.message().clone(),
+            ));
+        }
+        window.messages.len()
+    }
+
+    /// Returns the number of unacknowledged messages sent to a client
+    pub fn inflight(&self, client_id: &[u8]) -> usize {
+        self.inflight
+            .get(client_id)
+            .map_or(0, |window| window.messages.len())
+    }
+
+    /// Queues a message without trace behind the ones already waiting for the client
+    pub fn push(&mut self, client_id: Vec<u8>, publish_packet: Publish) {
+        self.queues
+            .entry(client_id)
+            .or_default()
+            .push_back((publish_packet, None));
+    }
+
+    /// Removes the messages queued for a client and returns them in the order they were published
+    pub fn take(&mut self, client_id: &[u8]) -> Vec<Publish> {
+        self.queues
+            .remove(client_id)
+            .into_iter()
+            .flatten()
+            .map(|(publish_packet, _)| publish_packet)
+            .collect()
+    }
+
+    /// Returns the messages queued for a client in the order they were published
+    pub fn queued(&self, client_id: &[u8]) -> impl Iterator<Item = &Publish> {
+        self.queues
+            .get(client_id)
+            .into_iter()
+            .flatten()
+            .map(|(publish_packet, _)| publish_packet)
+    }
+
+    /// Returns the number of messages queued for a client
+    pub fn len(&self, client_id: &[u8]) -> usize {
+        self.queues.get(client_id).map_or(0, VecDeque::len)
+    }
+
+    /// Returns the clients with queued messages and their messages
+    pub fn iter(&self) -> impl Iterator<Item = (&Vec<u8>, impl Iterator<Item = &Publish>)> {
+        self.queues
+            .iter()
+            .filter(|(_, queue)| !queue.is_empty())
+            .map(|(client_id, queue)| {
+                (
+                    client_id,
+                    queue.iter().map(|(publish_packet, _)| publish_packet),
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mqtt::model::components::{qos::QoS, topic_name::TopicName};
+
+    use super::*;
+
+    const CLIENT: &[u8] = b"drone-1";
+
+    fn publish_with_qos(topic: &str, message: &str, qos: QoS) -> Publish {
+        let package_identifier = match qos {
+            QoS::AtMost => None,
+            _ => Some(500),
+        };
+        Publish::new(
+            false,
+            qos,
+            false,
+            TopicName::new(vec![topic.as_bytes().to_vec()], false),
+            package_identifier,
+            message.as_bytes().to_vec(),
+        )
+    }
+
+    fn publish(topic: &str, message: &str) -> Publish {
+        publish_with_qos(topic, message, QoS::AtMost)
+    }
+
+    fn messages(received: &[Publish]) -> Vec<String> {
+        received
+            .iter()
+            .map(|publish| String::from_utf8_lossy(publish.message()).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_connected_client_receives_messages_immediately() {
+        let mut queues = OutboundQueues::new();
+        let mut received = vec![];
+
+        let delivery = queues.deliver(CLIENT, publish("a", "1"), None, true, |p, _| {
+            received.push(p)
+        });
+
+        assert_eq!(delivery, Delivery::Sent { flushed: 0 });
+        assert_eq!(messages(&received), vec!["1"]);
+        assert_eq!(queues.len(CLIENT), 0);
+    }
+
+    #[test]
+    fn test_queued_messages_are_sent_before_live_ones_after_reconnecting() {
+        let mut queues = OutboundQueues::new();
+        let mut received = vec![];
+
+        queues.deliver(CLIENT, publish("a", "1"), None, true, |p, _| {
+            received.push(p)
+        });
+
+        // The client disconnects and messages of two topics are queued
+        for (topic, message) in [("a", "2"), ("b", "3"), ("a", "4")] {
+            let delivery = queues.deliver(CLIENT, publish(topic, message), None, false, |_, _| {
+                panic!("a disconnected client must not receive messages")
+            });
+            assert_eq!(delivery, Delivery::Queued);
+        }
+        assert_eq!(queues.len(CLIENT), 3);
+
+        // The first live message after reconnecting goes after the queued ones
+        let delivery = queues.deliver(CLIENT, publish("a", "5"), None, true, |p, _| {
+            received.push(p)
+        });
+
+        assert_eq!(delivery, Delivery::Sent { flushed: 3 });
+        assert_eq!(messages(&received), vec!["1", "2", "3", "4", "5"]);
+        assert_eq!(queues.len(CLIENT), 0);
+    }
+
+    #[test]
+    fn test_session_resumed_with_flushed_queue_keeps_order() {
+        let mut queues = OutboundQueues::new();
+        queues.push(CLIENT.to_vec(), publish("a", "1"));
+        queues.push(CLIENT.to_vec(), publish("a", "2"));
+
+        let mut received = vec![];
+        assert_eq!(queues.flush(CLIENT, |p, _| received.push(p)), 2);
+        queues.deliver(CLIENT, publish("a", "3"), None, true, |p, _| {
+            received.push(p)
+        });
+
+        assert_eq!(messages(&received), vec!["1", "2", "3"]);
+        assert!(queues.take(CLIENT).is_empty());
+    }
+
+    #[test]
+    fn test_inflight_window_holds_messages_until_they_are_acknowledged() {
+        let mut queues = OutboundQueues::new();
+        queues.set_max_inflight(Some(2));
+        for message in ["1", "2", "3"] {
+            queues.push(
+                CLIENT.to_vec(),
+                publish_with_qos("a", message, QoS::AtLeast),
+            );
+        }
+        queues.push(CLIENT.to_vec(), publish("a", "4"));
+
+        let mut received = vec![];
+        assert_eq!(queues.flush(CLIENT, |p, _| received.push(p)), 2);
+        assert_eq!(messages(&received), vec!["1", "2"]);
+        assert_eq!(received[0].package_identifier(), Some(1));
+        assert_eq!(received[1].package_identifier(), Some(2));
+        assert_eq!(queues.inflight(CLIENT), 2);
+
+        // Live messages wait behind the held ones, even the QoS 0 ones
+        let delivery = queues.deliver(CLIENT, publish("a", "5"), None, true, |p, _| {
+            received.push(p)
+        });
+        assert_eq!(delivery, Delivery::Queued);
+        assert_eq!(queues.len(CLIENT), 3);
+
+        // An unknown packet identifier does not release any slot
+        assert!(!queues.acknowledge(CLIENT, 9, |p, _| received.push(p)));
+        assert_eq!(received.len(), 2);
+
+        assert!(queues.acknowledge(CLIENT, 1, |p, _| received.push(p)));
+        assert_eq!(messages(&received), vec!["1", "2", "3", "4", "5"]);
+        assert_eq!(received[2].package_identifier(), Some(3));
+        assert_eq!(queues.inflight(CLIENT), 2);
+    }
+
+    #[test]
+    fn test_inflight_window_is_cleared_when_the_client_reconnects() {
+        let mut queues = OutboundQueues::new();
+        queues.set_max_inflight(Some(1));
+        let mut received = vec![];
+
+        queues.deliver(
+            CLIENT,
+            publish_with_qos("a", "1", QoS::AtLeast),
+            None,
+            true,
+            |p, _| received.push(p),
+        );
+        let delivery = queues.deliver(
+            CLIENT,
+            publish_with_qos("a", "2", QoS::AtLeast),
+            None,
+            true,
+            |p, _| received.push(p),
+        );
+        assert_eq!(delivery, Delivery::Queued);
+
+        queues.clear_inflight(CLIENT);
+        assert_eq!(queues.flush(CLIENT, |p, _| received.push(p)), 1);
+        assert_eq!(messages(&received), vec!["1", "2"]);
+    }
+
+    #[test]
+    fn test_inflight_messages_are_sent_again_when_the_session_resumes() {
+        let mut queues = OutboundQueues::new();
+        queues.set_max_inflight(Some(2));
+        let mut received = vec![];
+
+        for message in ["1", "2"] {
+            queues.deliver(
+                CLIENT,
+                publish_with_qos("a", message, QoS::AtLeast),
+                None,
+                true,
+                |p, _| received.push(p),
+            );
+        }
+        assert!(!queues.acknowledge(CLIENT, 1, |p, _| received.push(p)));
+
+        // The client disconnects before acknowledging the second message
+        queues.deliver(
+            CLIENT,
+            publish_with_qos("a", "3", QoS::AtLeast),
+            None,
+            false,
+            |_, _| panic!("a disconnected client must not receive messages"),
+        );
+
+        let mut resent = vec![];
+        assert_eq!(queues.resend_inflight(CLIENT, |p| resent.push(p)), 1);
+        assert_eq!(messages(&resent), vec!["2"]);
+        assert!(resent[0].dup());
+        assert_eq!(resent[0].package_identifier(), Some(2));
+        assert_eq!(queues.inflight(CLIENT), 1);
+
+        assert_eq!(queues.flush(CLIENT, |p, _| resent.push(p)), 1);
+        assert_eq!(messages(&resent), vec!["2", "3"]);
+        assert!(!resent[1].dup());
+        assert_eq!(resent[1].package_identifier(), Some(3));
+
+        assert!(!queues.acknowledge(CLIENT, 2, |p, _| resent.push(p)));
+        assert_eq!(queues.inflight(CLIENT), 1);
+    }
+
+    #[test]
+    fn test_messages_are_not_tracked_without_inflight_limit() {
+        let mut queues = OutboundQueues::new();
+        let mut received = vec![];
+
+        for message in ["1", "2", "3"] {
+            queues.deliver(
+                CLIENT,
+                publish_with_qos("a", message, QoS::AtLeast),
+                None,
+                true,
+                |p, _| received.push(p),
+            );
+        }
+
+        assert_eq!(messages(&received), vec!["1", "2", "3"]);
+        assert_eq!(received[0].package_identifier(), Some(500));
+        assert_eq!(queues.inflight(CLIENT), 0);
+    }
+
+    #[test]
+    fn test_queues_of_different_clients_are_independent() {
+        let mut queues = OutboundQueues::new();
+        queues.push(CLIENT.to_vec(), publish("a", "1"));
+
+        let mut received = vec![];
+        let delivery = queues.deliver(b"drone-2", publish("a", "2"), None, true, |p, _| {
+            received.push(p)
+        });
+
+        assert_eq!(delivery, Delivery::Sent { flushed: 0 });
+        assert_eq!(messages(&received), vec!["2"]);
+        assert_eq!(queues.len(CLIENT), 1);
+        assert_eq!(queues.iter().count(), 1);
+    }
+
+    #[test]
+    fn test_traces_are_handed_with_the_queued_messages() {
+        let mut queues = OutboundQueues::new();
+        let trace = Trace::start();
+        queues.push(CLIENT.to_vec(), publish("a", "1"));
+        queues.deliver(CLIENT, publish("a", "2"), Some(trace), false, |_, _| {});
+
+        let mut traces = vec![];
+        queues.flush(CLIENT, |_, trace| {
+            traces.push(trace.map(|trace| trace.id()))
+        });
+        assert_eq!(traces, vec![None, Some(trace.id())]);
+    }
+}
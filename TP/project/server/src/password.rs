@@ -0,0 +1,118 @@
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+const HASH_PREFIX: &str = "sha256$";
+const HASH_SEPARATOR: char = '$';
+const SALT_SIZE: usize = 16;
+
+/// Hashes a password with a random salt. The result has the format `sha256$<salt>$<hash>`
+/// with both the salt and the hash hex encoded
+pub fn hash_password(password: &[u8]) -> String {
+    let mut salt = [0u8; SALT_SIZE];
+    rand::thread_rng().fill_bytes(&mut salt);
+
+    hash_with_salt(password, &salt)
+}
+
+/// Returns true if the stored password is a hash generated by `hash_password`
+pub fn is_hashed(stored_password: &[u8]) -> bool {
+    stored_password.starts_with(HASH_PREFIX.as_bytes())
+}
+
+/// Verifies a password against the stored one. Legacy plaintext entries are compared directly
+pub fn verify_password(stored_password: &[u8], password: &[u8]) -> bool {
+    if !is_hashed(stored_password) {
+        return constant_time_eq(stored_password, password);
+    }
+
+    let stored = String::from_utf8_lossy(stored_password);
+    let salt = match stored[HASH_PREFIX.len()..].split(HASH_SEPARATOR).next() {
+        Some(salt) => salt,
+        None => return false,
+    };
+
+    let salt = match hex_to_bytes(salt) {
+        Ok(salt) => salt,
+        Err(_) => return false,
+    };
+
+    constant_time_eq(hash_with_salt(password, &salt).as_bytes(), stored_password)
+}
+
+/// Hashes a password with the specified salt
+fn hash_with_salt(password: &[u8], salt: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(password);
+    let hash = hasher.finalize();
+
+    format!(
+        "{}{}{}{}",
+        HASH_PREFIX,
+        bytes_to_hex(salt),
+        HASH_SEPARATOR,
+        bytes_to_hex(&hash)
+    )
+}
+
+/// Compares two slices without returning early, so the comparison time does not leak the content
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Convert a slice of bytes to a hexadecimal string
+fn bytes_to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Convert a hexadecimal string to a vector of bytes
+fn hex_to_bytes(hex: &str) -> Result<Vec<u8>, String> {
+    // Only ASCII can be hexadecimal, and it makes every index a character boundary
+    if !hex.is_ascii() || !hex.len().is_multiple_of(2) {
+        return Err("Invalid hexadecimal string".to_string());
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| e.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_and_verify_password() {
+        let hash = hash_password(b"sauron");
+
+        assert!(is_hashed(hash.as_bytes()));
+        assert!(verify_password(hash.as_bytes(), b"sauron"));
+        assert!(!verify_password(hash.as_bytes(), b"saruman"));
+    }
+
+    #[test]
+    fn test_hashes_are_salted() {
+        assert_ne!(hash_password(b"sauron"), hash_password(b"sauron"));
+    }
+
+    #[test]
+    fn test_verify_legacy_plaintext_password() {
+        assert!(!is_hashed(b"sauron"));
+        assert!(verify_password(b"sauron", b"sauron"));
+        assert!(!verify_password(b"sauron", b"sauro"));
+    }
+
+    #[test]
+    fn test_hash_with_non_ascii_salt_is_rejected() {
+        let stored = "sha256$0é0$00".as_bytes();
+
+        assert!(is_hashed(stored));
+        assert!(!verify_password(stored, b"sauron"));
+        assert!(hex_to_bytes("aé").is_err());
+    }
+}
@@ -0,0 +1,427 @@
+use std::{
+    io::Cursor,
+    path::PathBuf,
+    sync::{mpsc, Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+use mqtt::model::{
+    components::{topic_filter::TopicFilter, topic_name::TopicName},
+    packet::Packet,
+    packets::publish::Publish,
+};
+
+use super::{PersistenceBackend, StateChange};
+use crate::{
+    backup,
+    error::ServerResult,
+    logfile::Logger,
+    wal::{self, WriteAheadLog},
+};
+
+const RETAINED_MESSAGES_TAG: &str = "R";
+const OFFLINE_MESSAGES_TAG: &str = "O";
+const CLIENTS_TAG: &str = "C";
+const RETAINED_MESSAGES_CLEARED_TAG: &str = "-R";
+const OFFLINE_MESSAGES_CLEARED_TAG: &str = "-O";
+const UNSUBSCRIBED_TAG: &str = "-C";
+/// Sequence number of the last write-ahead log record included in a backup
+const WAL_SEQUENCE_TAG: &str = "W";
+const SEPARATOR: char = ';';
+
+/// Channel to send the backup writer the serialized data with its write-ahead log sequence number,
+/// and the handle of its thread
+type BackupWriter = (mpsc::Sender<(String, u64)>, JoinHandle<()>);
+
+/// Persistence backend that periodically writes the whole state to a backup file.
+/// If the write-ahead log is enabled, every change is also appended to it as soon as it happens,
+/// so the changes made since the last backup survive a crash
+#[derive(Debug)]
+pub struct FilePersistenceBackend {
+    backup_file: String,
+    key: [u8; 32],
+    wal_enabled: bool,
+    log_file: Arc<Logger>,
+    wal: Mutex<Option<WriteAheadLog>>,
+    backup_writer: Mutex<Option<BackupWriter>>,
+}
+
+impl FilePersistenceBackend {
+    /// Creates a backend that stores the state in the specified backup file
+    pub fn new(
+        backup_file: String,
+        key: [u8; 32],
+        wal_enabled: bool,
+        log_file: Arc<Logger>,
+    ) -> Self {
+        Self {
+            backup_file,
+            key,
+            wal_enabled,
+            log_file,
+            wal: Mutex::new(None),
+            backup_writer: Mutex::new(None),
+        }
+    }
+
+    fn wal_path(&self) -> PathBuf {
+        wal::wal_path(&self.backup_file)
+    }
+
+    /// Opens the write-ahead log to append the changes that follow the specified sequence number
+    fn open_wal(&self, last_sequence: u64) -> ServerResult<()> {
+        *self.wal.lock()? = Some(WriteAheadLog::open(&self.wal_path(), last_sequence + 1)?);
+        Ok(())
+    }
+
+    /// Rotates the write-ahead log before taking a backup.
+    /// Returns the sequence number of the last change included in the backup
+    fn rotate_wal(&self) -> ServerResult<u64> {
+        match self.wal.lock()?.as_mut() {
+            Some(wal) => Ok(wal.rotate()?),
+            None => Ok(0),
+        }
+    }
+
+    /// Serialize the state to a string, including the sequence number of the
+    /// last write-ahead log record it reflects
+    fn serialize(&self, state: &[StateChange], wal_sequence: u64) -> String {
+        let mut serialized_data = String::new();
+
+        if self.wal_enabled {
+            serialized_data.push_str(&backup_record(
+                WAL_SEQUENCE_TAG,
+                &wal_sequence.to_be_bytes(),
+                &[],
+            ));
+            serialized_data.push('\n');
+        }
+
+        for change in state {
+            serialized_data.push_str(&encode_change(change, &self.key));
+            serialized_data.push('\n');
+        }
+
+        serialized_data
+    }
+}
+
+impl PersistenceBackend for FilePersistenceBackend {
+    /// Reads the backup file and, if it is enabled, replays the changes logged after it was taken.
+    /// If the backup is unreadable the write-ahead log is still replayed, as it may hold changes
+    fn load(&self) -> ServerResult<Vec<StateChange>> {
+        let mut changes = vec![];
+        let mut wal_sequence = 0;
+
+        match backup::read_backup(&self.backup_file) {
+            Ok((version, data)) => {
+                if version < backup::BACKUP_VERSION {
+                    self.log_file.info(&format!(
+                        "Migrating backup from format version {} to {}",
+                        version,
+                        backup::BACKUP_VERSION
+                    ));
+                }
+
+                for line in data.lines() {
+                    if let Some(sequence) = decode_wal_sequence(line) {
+                        wal_sequence = sequence;
+                    } else if let Some(change) = decode_change(line, &self.key) {
+                        changes.push(change);
+                    }
+                }
+            }
+            Err(err) if !self.wal_enabled => return Err(err),
+            Err(err) => self.log_file.error(&format!(
+                "Error loading backup file {}: {}. Restoring only the write-ahead log",
+                self.backup_file, err
+            )),
+        }
+
+        if !self.wal_enabled {
+            return Ok(changes);
+        }
+
+        let mut replayed = 0;
+        for (sequence, record) in wal::read_records(&self.wal_path())? {
+            if sequence <= wal_sequence {
+                continue;
+            }
+            if let Some(change) = decode_change(&record, &self.key) {
+                changes.push(change);
+            }
+            wal_sequence = sequence;
+            replayed += 1;
+        }
+        self.log_file.info(&format!(
+            "Replayed {} changes from the write-ahead log",
+            replayed
+        ));
+
+        self.open_wal(wal_sequence)?;
+        Ok(changes)
+    }
+
+    /// Removes the write-ahead log. The backup file is replaced by the next snapshot
+    fn clear(&self) -> ServerResult<()> {
+        if self.wal_enabled {
+            wal::remove_all(&self.wal_path())?;
+            self.open_wal(0)?;
+        }
+        Ok(())
+    }
+
+    /// Appends the change to the write-ahead log, if it is enabled
+    fn record(&self, change: &StateChange) -> ServerResult<()> {
+        if let Some(wal) = self.wal.lock()?.as_mut() {
+            wal.append(&encode_change(change, &self.key))?;
+        }
+        Ok(())
+    }
+
+    fn takes_snapshots(&self) -> bool {
+        true
+    }
+
+    /// Sends the snapshot to the backup writer thread, which is started with the first one
+    fn save_snapshot(&self, state: Vec<StateChange>) -> ServerResult<()> {
+        let wal_sequence = self.rotate_wal()?;
+        let serialized_data = self.serialize(&state, wal_sequence);
+
+        let mut backup_writer = self.backup_writer.lock()?;
+        let (backup_sender, _) = backup_writer.get_or_insert_with(|| {
+            spawn_backup_writer(
+                self.backup_file.clone(),
+                self.wal_enabled.then(|| self.wal_path()),
+                self.log_file.clone(),
+            )
+        });
+        backup_sender.send((serialized_data, wal_sequence))?;
+        Ok(())
+    }
+
+    /// Writes the final backup. The backup writer is stopped first so a pending backup
+    /// can't overwrite the final one
+    fn close(&self, state: Vec<StateChange>) -> ServerResult<()> {
+        if let Some((backup_sender, backup_thread)) = self.backup_writer.lock()?.take() {
+            drop(backup_sender);
+            let _ = backup_thread.join();
+        }
+
+        self.log_file.info("Saving final backup");
+        let wal_sequence = self.rotate_wal()?;
+        backup::write_backup(&self.backup_file, &self.serialize(&state, wal_sequence))?;
+
+        if self.wal_enabled {
+            wal::remove_segments(&self.wal_path(), wal_sequence)?;
+        }
+        Ok(())
+    }
+}
+
+/// Spawns the thread that writes the backups, as I/O operations are blocking.
+/// Once a backup is written, the write-ahead log segments it includes are removed.
+/// Returns the channel to send it the serialized data and the handle of the thread
+fn spawn_backup_writer(
+    backup_file_path: String,
+    wal_path: Option<PathBuf>,
+    log_file: Arc<Logger>,
+) -> BackupWriter {
+    let (sender, receiver) = mpsc::channel::<(String, u64)>();
+
+    let thread = thread::spawn(move || {
+        for (serialized_data, wal_sequence) in receiver {
+            if let Err(e) = backup::write_backup(&backup_file_path, &serialized_data) {
+                log_file.error(&format!("Failed to write backup file: {}", e));
+                continue;
+            }
+
+            if let Some(wal_path) = &wal_path {
+                if let Err(e) = wal::remove_segments(wal_path, wal_sequence) {
+                    log_file.error(&format!("Failed to compact the write-ahead log: {}", e));
+                }
+            }
+        }
+    });
+
+    (sender, thread)
+}
+
+/// Formats a change as a record of the backup
+fn encode_change(change: &StateChange, key: &[u8; 32]) -> String {
+    match change {
+        StateChange::Subscribed(client_id, topic_filter) => {
+            backup_record(CLIENTS_TAG, client_id, &topic_filter.to_bytes())
+        }
+        StateChange::Unsubscribed(client_id, topic_filter) => {
+            backup_record(UNSUBSCRIBED_TAG, client_id, &topic_filter.to_bytes())
+        }
+        StateChange::Retained(publish) => backup_record(
+            RETAINED_MESSAGES_TAG,
+            &publish.topic().to_bytes(),
+            &publish.to_bytes(key),
+        ),
+        StateChange::RetainedCleared(topic_name) => {
+            backup_record(RETAINED_MESSAGES_CLEARED_TAG, &topic_name.to_bytes(), &[])
+        }
+        StateChange::Queued(client_id, publish) => {
+            backup_record(OFFLINE_MESSAGES_TAG, client_id, &publish.to_bytes(key))
+        }
+        StateChange::QueueCleared(client_id) => {
+            backup_record(OFFLINE_MESSAGES_CLEARED_TAG, client_id, &[])
+        }
+    }
+}
+
+/// Parses a record of the backup. Returns None if it is invalid
+fn decode_change(record: &str, key: &[u8; 32]) -> Option<StateChange> {
+    let (record_type, entry_key, value) = split_record(record)?;
+
+    match record_type {
+        CLIENTS_TAG => Some(StateChange::Subscribed(
+            entry_key,
+            TopicFilter::from_bytes(&mut Cursor::new(value)).ok()?,
+        )),
+        UNSUBSCRIBED_TAG => Some(StateChange::Unsubscribed(
+            entry_key,
+            TopicFilter::from_bytes(&mut Cursor::new(value)).ok()?,
+        )),
+        RETAINED_MESSAGES_TAG => Some(StateChange::Retained(decode_publish(value, key)?)),
+        RETAINED_MESSAGES_CLEARED_TAG => Some(StateChange::RetainedCleared(
+            TopicName::from_bytes(&mut Cursor::new(entry_key)).ok()?,
+        )),
+        OFFLINE_MESSAGES_TAG => Some(StateChange::Queued(entry_key, decode_publish(value, key)?)),
+        OFFLINE_MESSAGES_CLEARED_TAG => Some(StateChange::QueueCleared(entry_key)),
+        _ => None,
+    }
+}
+
+/// Parses the line of the backup that holds the write-ahead log sequence number
+fn decode_wal_sequence(record: &str) -> Option<u64> {
+    match split_record(record)? {
+        (WAL_SEQUENCE_TAG, sequence, _) => Some(u64::from_be_bytes(sequence.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+fn decode_publish(bytes: Vec<u8>, key: &[u8; 32]) -> Option<Publish> {
+    match Packet::from_bytes(&mut Cursor::new(bytes), key) {
+        Ok(Packet::Publish(publish)) => Some(publish),
+        _ => None,
+    }
+}
+
+/// Formats a record of the backup as `<tag>;<hex key>;<hex value>`
+fn backup_record(tag: &str, key: &[u8], value: &[u8]) -> String {
+    format!(
+        "{}{}{}{}{}",
+        tag,
+        SEPARATOR,
+        bytes_to_hex(key),
+        SEPARATOR,
+        bytes_to_hex(value)
+    )
+}
+
+/// Splits a record of the backup into its tag, key and value
+fn split_record(record: &str) -> Option<(&str, Vec<u8>, Vec<u8>)> {
+    let parts: Vec<&str> = record.split(SEPARATOR).collect();
+    if parts.len() != 3 {
+        return None;
+    }
+
+    Some((
+        parts[0],
+        hex_to_bytes(parts[1]).ok()?,
+        hex_to_bytes(parts[2]).ok()?,
+    ))
+}
+
+/// Convert a slice of bytes to a hexadecimal string
+fn bytes_to_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join("")
+}
+
+/// Convert a hexadecimal string to a vector of bytes
+fn hex_to_bytes(hex: &str) -> Result<Vec<u8>, String> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| e.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logfile::RotationPolicy;
+    use mqtt::model::components::{qos::QoS, topic_level::TopicLevel};
+    use std::fs;
+
+    const KEY: [u8; 32] = [7; 32];
+
+    fn retained_publish(message: &[u8]) -> Publish {
+        Publish::new(
+            false,
+            QoS::AtMost,
+            true,
+            TopicName::new(vec![b"camera-data".to_vec()], false),
+            None,
+            message.to_vec(),
+        )
+    }
+
+    #[test]
+    fn test_changes_survive_backup_and_write_ahead_log() {
+        let backup_file = "test_file_persistence_backup.txt";
+        let log_file = Arc::new(Logger::with_rotation(
+            "test_file_persistence.log",
+            RotationPolicy::default(),
+        ));
+        let filter = TopicFilter::new(vec![TopicLevel::Literal(b"drone-data".to_vec())], false);
+
+        let backend = FilePersistenceBackend::new(backup_file.to_string(), KEY, true, log_file);
+        backend.clear().unwrap();
+
+        // Included in the final backup
+        let subscribed = StateChange::Subscribed(b"a".to_vec(), filter.clone());
+        backend.record(&subscribed).unwrap();
+        backend.close(vec![subscribed]).unwrap();
+
+        // Only in the write-ahead log
+        backend
+            .record(&StateChange::Retained(retained_publish(b"on")))
+            .unwrap();
+        backend
+            .record(&StateChange::Unsubscribed(b"a".to_vec(), filter.clone()))
+            .unwrap();
+
+        let changes = backend.load().unwrap();
+        assert_eq!(changes.len(), 3);
+        assert!(
+            matches!(&changes[0], StateChange::Subscribed(id, f) if id == b"a" && f == &filter)
+        );
+        assert!(matches!(&changes[1], StateChange::Retained(p) if p.message() == b"on"));
+        assert!(matches!(&changes[2], StateChange::Unsubscribed(id, _) if id == b"a"));
+
+        let _ = wal::remove_all(&wal::wal_path(backup_file));
+        let _ = fs::remove_file(backup_file);
+        let _ = fs::remove_file("test_file_persistence.log");
+    }
+
+    #[test]
+    fn test_invalid_records_are_skipped() {
+        let record = encode_change(&StateChange::QueueCleared(b"a".to_vec()), &KEY);
+        assert!(matches!(
+            decode_change(&record, &KEY),
+            Some(StateChange::QueueCleared(id)) if id == b"a"
+        ));
+
+        assert!(decode_change("X;00;00", &KEY).is_none());
+        assert!(decode_change("O;0", &KEY).is_none());
+        assert_eq!(decode_wal_sequence("W;000000000000002A;"), Some(42));
+    }
+}
@@ -0,0 +1,97 @@
+use std::{fmt::Debug, sync::Arc};
+
+use mqtt::model::{
+    components::{topic_filter::TopicFilter, topic_name::TopicName},
+    packets::publish::Publish,
+};
+
+use crate::{
+    config::Config,
+    error::{ServerError, ServerResult},
+    logfile::Logger,
+};
+
+/// backup file backend
+pub mod file_backend;
+/// SQLite database backend
+pub mod sqlite_backend;
+
+use file_backend::FilePersistenceBackend;
+use sqlite_backend::SqlitePersistenceBackend;
+
+const FILE_BACKEND: &str = "file";
+const SQLITE_BACKEND: &str = "sqlite";
+
+/// Represents a client ID
+type ClientId = Vec<u8>;
+
+/// Represents a change of the broker state that outlives the connections
+#[derive(Debug, Clone)]
+pub enum StateChange {
+    /// A client subscribed to a topic filter
+    Subscribed(ClientId, TopicFilter),
+    /// A client unsubscribed from a topic filter
+    Unsubscribed(ClientId, TopicFilter),
+    /// A message was retained in its topic
+    Retained(Publish),
+    /// The retained messages of a topic were cleared
+    RetainedCleared(TopicName),
+    /// A message was queued for a disconnected client
+    Queued(ClientId, Publish),
+    /// The queued messages of a client were delivered
+    QueueCleared(ClientId),
+}
+
+/// Represents a storage of the broker state (subscriptions, retained and offline messages)
+/// used to restore it after the server restarts
+pub trait PersistenceBackend: Debug + Send {
+    /// Returns the stored state as the changes that rebuild it, in order
+    fn load(&self) -> ServerResult<Vec<StateChange>>;
+
+    /// Discards the stored state, so the server starts empty
+    fn clear(&self) -> ServerResult<()>;
+
+    /// Stores a change of the state as soon as it happens
+    fn record(&self, change: &StateChange) -> ServerResult<()>;
+
+    /// Returns true if the backend needs a periodic snapshot of the whole state
+    fn takes_snapshots(&self) -> bool {
+        false
+    }
+
+    /// Stores a snapshot of the whole state
+    fn save_snapshot(&self, _state: Vec<StateChange>) -> ServerResult<()> {
+        Ok(())
+    }
+
+    /// Stores the final state before the server exits
+    fn close(&self, _state: Vec<StateChange>) -> ServerResult<()> {
+        Ok(())
+    }
+}
+
+/// Builds the persistence backend selected in the configuration.
+/// Returns None if the file backend is selected without a backup file
+pub fn backend_from_config(
+    config: &Config,
+    log_file: Arc<Logger>,
+) -> ServerResult<Option<Box<dyn PersistenceBackend>>> {
+    match config.get_persistence_backend() {
+        FILE_BACKEND => Ok(config.get_backup_file().map(|backup_file| {
+            Box::new(FilePersistenceBackend::new(
+                backup_file,
+                config.get_key(),
+                config.get_wal_enabled(),
+                log_file,
+            )) as Box<dyn PersistenceBackend>
+        })),
+        SQLITE_BACKEND => Ok(Some(Box::new(SqlitePersistenceBackend::new(
+            config.get_persistence_database(),
+            config.get_key(),
+        )?))),
+        backend => Err(ServerError::PersistenceBackend(format!(
+            "Unknown persistence backend: {}",
+            backend
+        ))),
+    }
+}
@@ -0,0 +1,233 @@
+use std::{io::Cursor, sync::Mutex};
+
+use mqtt::model::{
+    components::topic_filter::TopicFilter, packet::Packet, packets::publish::Publish,
+};
+use rusqlite::{params, Connection};
+
+use super::{PersistenceBackend, StateChange};
+use crate::error::{ServerError, ServerResult};
+
+/// Clients are stored through their subscriptions, as it is all the state kept for them.
+/// Messages are stored as the encrypted packets the clients receive
+const CREATE_TABLES: &str = "
+CREATE TABLE IF NOT EXISTS subscriptions (
+    client_id TEXT NOT NULL,
+    topic_filter TEXT NOT NULL,
+    encoded_filter BLOB NOT NULL,
+    PRIMARY KEY (client_id, topic_filter)
+);
+CREATE TABLE IF NOT EXISTS retained_messages (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    topic TEXT NOT NULL,
+    packet BLOB NOT NULL
+);
+CREATE TABLE IF NOT EXISTS offline_messages (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    client_id TEXT NOT NULL,
+    packet BLOB NOT NULL
+);";
+
+/// Persistence backend that stores every change in a SQLite database as soon as it happens,
+/// so no snapshots are needed and the state can be queried while the server runs
+#[derive(Debug)]
+pub struct SqlitePersistenceBackend {
+    connection: Mutex<Connection>,
+    key: [u8; 32],
+}
+
+impl SqlitePersistenceBackend {
+    /// Opens (or creates) the database at the specified path
+    pub fn new(database_path: &str, key: [u8; 32]) -> ServerResult<Self> {
+        Self::from_connection(Connection::open(database_path)?, key)
+    }
+
+    /// Creates the tables in the connection if they do not exist
+    fn from_connection(connection: Connection, key: [u8; 32]) -> ServerResult<Self> {
+        connection.execute_batch(CREATE_TABLES)?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+            key,
+        })
+    }
+
+    fn decode_publish(&self, bytes: Vec<u8>) -> ServerResult<Publish> {
+        match Packet::from_bytes(&mut Cursor::new(bytes), &self.key)? {
+            Packet::Publish(publish) => Ok(publish),
+            _ => Err(ServerError::PersistenceBackend(
+                "Stored message is not a PUBLISH packet".to_string(),
+            )),
+        }
+    }
+}
+
+impl PersistenceBackend for SqlitePersistenceBackend {
+    /// Reads the subscriptions, then the retained and offline messages in the order they were stored
+    fn load(&self) -> ServerResult<Vec<StateChange>> {
+        let connection = self.connection.lock()?;
+        let mut changes = vec![];
+
+        let mut statement = connection
+            .prepare("SELECT client_id, encoded_filter FROM subscriptions ORDER BY rowid")?;
+        let rows = statement.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?))
+        })?;
+        for row in rows {
+            let (client_id, encoded_filter) = row?;
+            let topic_filter = TopicFilter::from_bytes(&mut Cursor::new(encoded_filter))?;
+            changes.push(StateChange::Subscribed(
+                client_id.into_bytes(),
+                topic_filter,
+            ));
+        }
+
+        let mut statement =
+            connection.prepare("SELECT packet FROM retained_messages ORDER BY id")?;
+        let rows = statement.query_map([], |row| row.get::<_, Vec<u8>>(0))?;
+        for row in rows {
+            changes.push(StateChange::Retained(self.decode_publish(row?)?));
+        }
+
+        let mut statement =
+            connection.prepare("SELECT client_id, packet FROM offline_messages ORDER BY id")?;
+        let rows = statement.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?))
+        })?;
+        for row in rows {
+            let (client_id, packet) = row?;
+            changes.push(StateChange::Queued(
+                client_id.into_bytes(),
+                self.decode_publish(packet)?,
+            ));
+        }
+
+        Ok(changes)
+    }
+
+    fn clear(&self) -> ServerResult<()> {
+        self.connection.lock()?.execute_batch(
+            "DELETE FROM subscriptions;
+             DELETE FROM retained_messages;
+             DELETE FROM offline_messages;",
+        )?;
+        Ok(())
+    }
+
+    fn record(&self, change: &StateChange) -> ServerResult<()> {
+        let connection = self.connection.lock()?;
+
+        match change {
+            StateChange::Subscribed(client_id, topic_filter) => connection.execute(
+                "INSERT OR IGNORE INTO subscriptions (client_id, topic_filter, encoded_filter)
+                 VALUES (?1, ?2, ?3)",
+                params![
+                    String::from_utf8_lossy(client_id),
+                    topic_filter.to_string(),
+                    topic_filter.to_bytes()
+                ],
+            )?,
+            StateChange::Unsubscribed(client_id, topic_filter) => connection.execute(
+                "DELETE FROM subscriptions WHERE client_id = ?1 AND topic_filter = ?2",
+                params![String::from_utf8_lossy(client_id), topic_filter.to_string()],
+            )?,
+            StateChange::Retained(publish) => connection.execute(
+                "INSERT INTO retained_messages (topic, packet) VALUES (?1, ?2)",
+                params![publish.topic().to_string(), publish.to_bytes(&self.key)],
+            )?,
+            StateChange::RetainedCleared(topic_name) => connection.execute(
+                "DELETE FROM retained_messages WHERE topic = ?1",
+                params![topic_name.to_string()],
+            )?,
+            StateChange::Queued(client_id, publish) => connection.execute(
+                "INSERT INTO offline_messages (client_id, packet) VALUES (?1, ?2)",
+                params![
+                    String::from_utf8_lossy(client_id),
+                    publish.to_bytes(&self.key)
+                ],
+            )?,
+            StateChange::QueueCleared(client_id) => connection.execute(
+                "DELETE FROM offline_messages WHERE client_id = ?1",
+                params![String::from_utf8_lossy(client_id)],
+            )?,
+        };
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mqtt::model::components::{qos::QoS, topic_level::TopicLevel, topic_name::TopicName};
+
+    const KEY: [u8; 32] = [7; 32];
+
+    fn in_memory_backend() -> SqlitePersistenceBackend {
+        SqlitePersistenceBackend::from_connection(Connection::open_in_memory().unwrap(), KEY)
+            .unwrap()
+    }
+
+    fn publish(topic: &[u8], message: &[u8]) -> Publish {
+        Publish::new(
+            false,
+            QoS::AtMost,
+            true,
+            TopicName::new(vec![topic.to_vec()], false),
+            None,
+            message.to_vec(),
+        )
+    }
+
+    #[test]
+    fn test_recorded_changes_are_loaded() {
+        let backend = in_memory_backend();
+        let filter = TopicFilter::new(vec![TopicLevel::Literal(b"drone-data".to_vec())], false);
+
+        backend
+            .record(&StateChange::Subscribed(b"a".to_vec(), filter.clone()))
+            .unwrap();
+        backend
+            .record(&StateChange::Subscribed(b"b".to_vec(), filter.clone()))
+            .unwrap();
+        backend
+            .record(&StateChange::Unsubscribed(b"b".to_vec(), filter.clone()))
+            .unwrap();
+        backend
+            .record(&StateChange::Retained(publish(b"camera-data", b"1")))
+            .unwrap();
+        backend
+            .record(&StateChange::Retained(publish(b"new-incident", b"2")))
+            .unwrap();
+        backend
+            .record(&StateChange::RetainedCleared(TopicName::new(
+                vec![b"camera-data".to_vec()],
+                false,
+            )))
+            .unwrap();
+        backend
+            .record(&StateChange::Queued(
+                b"a".to_vec(),
+                publish(b"drone-data", b"3"),
+            ))
+            .unwrap();
+
+        let changes = backend.load().unwrap();
+        assert_eq!(changes.len(), 3);
+        assert!(
+            matches!(&changes[0], StateChange::Subscribed(id, f) if id == b"a" && f == &filter)
+        );
+        assert!(matches!(&changes[1], StateChange::Retained(p) if p.message() == b"2"));
+        assert!(
+            matches!(&changes[2], StateChange::Queued(id, p) if id == b"a" && p.message() == b"3")
+        );
+
+        backend
+            .record(&StateChange::QueueCleared(b"a".to_vec()))
+            .unwrap();
+        assert_eq!(backend.load().unwrap().len(), 2);
+
+        backend.clear().unwrap();
+        assert!(backend.load().unwrap().is_empty());
+    }
+}
@@ -1,39 +1,55 @@
 use std::{
-    net::{TcpListener, TcpStream},
-    sync::{
-        mpsc::{self, Sender},
-        Arc, RwLock,
-    },
-    thread,
+    net::IpAddr,
+    sync::{Arc, RwLock},
 };
 
 pub use mqtt::model::{
     packet::Packet,
-    packets::{connect::Connect, publish::Publish, subscribe::Subscribe, unsubscribe::Unsubscribe},
+    packets::{
+        connect::Connect, puback::Puback, publish::Publish, subscribe::Subscribe,
+        unsubscribe::Unsubscribe,
+    },
 };
 
-use crate::{client::Client, client_manager::ClientManager};
+use crate::{
+    acl::Acl,
+    admin_api, auth,
+    bridge::{Bridge, BridgeSettings},
+    client::Client,
+    client_manager::{ClientManager, ConnectRefusal},
+    client_stats::ConnectionTraffic,
+    client_stream::ClientStream,
+    cluster::{Cluster, ClusterSettings},
+    event_loop::EventLoop,
+    fast_path::FastPath,
+    message_trace::Trace,
+    network_filter,
+};
 
 use super::{
     config::Config,
-    error::{ServerError, ServerResult},
-    logfile::Logger,
-    task_handler::{Task, TaskHandler},
+    error::ServerResult,
+    logfile::{Logger, RotationPolicy},
+    task_channel::{task_channel, TaskSender},
+    task_handler::{DisconnectCause, Task, TaskHandler},
 };
 
 /// Represents the MQTT server that will be handling all messages
 /// The server has a configuration, a channel to send messages to clients, a log file, and a client manager
 /// The server will be listening for incoming connections and handling them
-/// It creates a new client for each connection and a new thread for each client
+/// All the connections are read by an event loop, and CONNECT packets are processed by a pool of workers
+#[derive(Clone)]
 pub struct Server {
-    /// Configuration of the server
-    config: Config,
+    /// Configuration of the server, replaced when it is reloaded
+    config: Arc<RwLock<Config>>,
     /// Channel to send messages to clients
-    client_actions_sender: Sender<Task>,
+    client_actions_sender: TaskSender,
     /// Log file to log messages
     log_file: Arc<Logger>,
     /// Manages the registered clients in the server
     client_manager: Arc<RwLock<ClientManager>>,
+    /// Routes QoS 0 messages without going through the task handler
+    fast_path: Arc<FastPath>,
 }
 
 impl Server {
@@ -41,153 +57,270 @@ impl Server {
     /// Set up the client manager and the task handler thread
     /// Returns a ServerResult with the server if successful
     pub fn new(config: Config) -> ServerResult<Self> {
-        let (client_actions_sender, client_actions_receiver) = mpsc::channel();
+        let (client_actions_sender, client_actions_receiver) =
+            task_channel(config.get_task_queue_size());
 
-        let log_file = Arc::new(Logger::new(config.get_log_file()));
-        let client_manager = ClientManager::new(config.get_login_file());
+        let log_file = Arc::new(
+            Logger::with_rotation(
+                config.get_log_file(),
+                RotationPolicy {
+                    max_bytes: config.get_log_max_bytes(),
+                    daily: config.get_log_rotate_daily(),
+                    max_files: config.get_log_max_files(),
+                },
+            )
+            .with_level(config.get_log_level())
+            .with_format(config.get_log_format()),
+        );
+        let auth_backend = auth::backend_from_config(&config)?;
+        let mut client_manager = ClientManager::new(auth_backend, anonymous_acl(&config))
+            .with_strict_mode(config.get_strict_mode());
+        client_manager.set_max_connections(config.get_max_connections());
+        apply_ban_list(&mut client_manager, &config);
         // let backup_file = config.get_backup_file();
         let client_manager = Arc::new(RwLock::new(client_manager));
 
+        let bridge = BridgeSettings::from_config(&config)?.map(|settings| {
+            Bridge::start(
+                settings,
+                config.get_packet_key().to_vec(),
+                client_actions_sender.clone(),
+                log_file.clone(),
+            )
+        });
+
+        let cluster = ClusterSettings::from_config(&config)?.map(|settings| {
+            Cluster::start(
+                settings,
+                config.get_packet_key().to_vec(),
+                client_actions_sender.clone(),
+                log_file.clone(),
+            )
+        });
+
+        let fast_path = Arc::new(FastPath::new(config.get_packet_key().to_vec()));
         let task_handler = TaskHandler::new(
             client_actions_receiver,
             &config,
             client_manager.clone(),
             log_file.clone(),
+            bridge,
+            cluster,
+            fast_path.clone(),
         );
 
         task_handler.initialize_task_handler_thread();
 
+        if let Some(address) = config.get_admin_http_address() {
+            admin_api::start(
+                address,
+                config.get_admin_http_token(),
+                client_actions_sender.clone(),
+                log_file.clone(),
+            )?;
+        }
+
         Ok(Server {
-            config,
+            config: Arc::new(RwLock::new(config)),
             client_actions_sender,
             log_file,
             client_manager,
+            fast_path,
         })
     }
 
-    /// Starts the server
+    /// Starts the server event loop, which serves every connection from a single thread.
+    /// Returns once the server was shut down by SIGINT or SIGTERM
     pub fn server_run(&self) -> ServerResult<()> {
-        let address = self.config.get_address();
-        let key = self.config.get_key();
-
-        self.log_file
-            .info(&format!("Server running on address: {}\n", address));
-        let listener = TcpListener::bind(address)?;
-
-        for stream_result in listener.incoming() {
-            match stream_result {
-                Ok(stream) => {
-                    self.log_file.info("New connection received");
-                    self.handle_new_connection(stream, key)?;
-                }
-                Err(err) => {
-                    self.log_file
-                        .error(&format!("Error accepting connection: {:?}", err));
-                }
-            }
-        }
-
-        Ok(())
+        EventLoop::new(self.clone())?.run()
     }
 
-    /// Handles a new connection by checking if it is a valid packet
-    pub fn handle_new_connection(&self, mut stream: TcpStream, key: &[u8; 32]) -> ServerResult<()> {
-        match Packet::from_bytes(&mut stream, key) {
-            Ok(packet) => self.handle_incoming_packet(packet, stream)?,
-            Err(err) => {
-                self.log_file
-                    .error(&format!("Error reading packet: {:?}", err));
-            }
+    /// Sends a QoS 0 message straight to its subscribers if the fast path can route it,
+    /// and tells the task handler to account for it. Returns false if the message has to
+    /// go through the task handler instead
+    pub fn publish_directly(&self, publish_packet: &Publish, client_id: &[u8]) -> bool {
+        let trace = Trace::start();
+        let allowed = match self.client_manager.read() {
+            Ok(client_manager) => client_manager
+                .can_publish(client_id, publish_packet.topic())
+                .unwrap_or(false),
+            Err(_) => false,
+        };
+        if !allowed {
+            return false;
         }
-        Ok(())
-    }
 
-    /// Handles an incoming packet from a connection. If it is a Connect packet, it will create a new client. Otherwise, it will log an error.
-    pub fn handle_incoming_packet(&self, packet: Packet, stream: TcpStream) -> ServerResult<()> {
-        match packet {
-            Packet::Connect(connect_packet) => self.connect_new_client(connect_packet, stream),
-            _ => {
-                self.log_file.error("Received an unsupported packet type");
-                Err(ServerError::UnsupportedPacket)
-            }
-        }
+        let subscribers = match self.fast_path.publish(publish_packet) {
+            Some(subscribers) => subscribers,
+            None => return false,
+        };
+        let elapsed = trace.elapsed();
+
+        self.log_file.log_received_packet("Publish", client_id);
+        let _ = self.client_actions_sender.send(Task::PublishedDirectly(
+            Box::new(publish_packet.clone()),
+            client_id.to_vec(),
+            subscribers,
+            trace,
+            elapsed,
+        ));
+        true
     }
 
-    /// Establishes a new connection with a client by creating a new client and a new thread for it
+    /// Authenticates a client that sent a CONNECT packet and registers it in the task handler.
+    /// Returns the id of the client if it was accepted
     pub fn connect_new_client(
         &self,
         connect_packet: Connect,
-        stream: TcpStream,
-    ) -> ServerResult<()> {
+        stream: ClientStream,
+        traffic: Arc<ConnectionTraffic>,
+    ) -> Option<Vec<u8>> {
         let message = format!(
             "Received Connect Packet from client with ID: {}",
             connect_packet.client_id()
         );
         self.log_file.info(&message);
 
-        let client_manager = self.client_manager.read().map_err(|_| {
-            ServerError::ClientConnection("Failed to acquire read lock".to_string())
-        })?;
+        let config = self.config();
+        let client_manager = match self.client_manager.read() {
+            Ok(client_manager) => client_manager,
+            Err(_) => {
+                self.log_file.error("Failed to acquire read lock");
+                return None;
+            }
+        };
 
-        let stream_clone = stream.try_clone()?;
+        let client_id = connect_packet.client_id().content().to_vec();
+        let address = stream.peer_addr().ok().map(|address| address.ip());
+        if client_manager.is_banned(&client_id, address) {
+            self.log_file.warn(&format!(
+                "Banned client {} tried to connect from {:?}. Connection rejected",
+                String::from_utf8_lossy(&client_id),
+                address
+            ));
+            client_manager.failure_connection(
+                stream,
+                ConnectRefusal::Banned.return_code(),
+                config.get_packet_key(),
+            );
+            return None;
+        }
 
-        match client_manager.process_connect_packet(
-            connect_packet,
-            stream_clone,
-            self.config.get_key(),
-        ) {
-            Some(new_client) => {
+        match client_manager.process_connect_packet(connect_packet, stream, config.get_packet_key())
+        {
+            Ok(new_client) => {
+                let mut new_client = new_client.with_traffic(traffic);
+                if let Err(err) = new_client.start_writer(
+                    config.get_outbound_queue_size(),
+                    config.get_disconnect_slow_consumers(),
+                ) {
+                    self.log_file
+                        .error(&format!("Error starting client writer: {:?}", err));
+                    return None;
+                }
                 self.log_file.info("Client connected successfully");
 
                 let client_id = new_client.id();
-                handle_connect(self.client_actions_sender.clone(), new_client)?;
-                self.create_new_client_thread(
-                    self.client_actions_sender.clone(),
-                    stream,
-                    client_id,
-                    self.log_file.clone(),
-                );
+                match handle_connect(self.client_actions_sender.clone(), new_client) {
+                    Ok(_) => Some(client_id),
+                    Err(err) => {
+                        self.log_file
+                            .error(&format!("Error connecting client: {:?}", err));
+                        None
+                    }
+                }
             }
-            None => {
-                self.log_file.error("Error connecting client");
+            Err(refusal) => {
+                self.log_file.warn(&format!(
+                    "Client {} refused with {:?}: {}",
+                    String::from_utf8_lossy(&client_id),
+                    refusal.return_code(),
+                    refusal
+                ));
+                None
             }
         }
+    }
+
+    /// Re-reads the configuration file and the credentials of the clients without dropping
+    /// the connections. Settings that need a restart are logged and ignored until then
+    pub fn reload_config(&self) -> ServerResult<()> {
+        let current_config = self.config();
+        let new_config = Config::from_file(current_config.get_path())?;
+
+        let auth_backend = auth::backend_from_config(&new_config)?;
+        let mut client_manager = self.client_manager.write()?;
+        client_manager.reload(auth_backend, anonymous_acl(&new_config))?;
+        client_manager.set_max_connections(new_config.get_max_connections());
+        apply_ban_list(&mut client_manager, &new_config);
+        drop(client_manager);
+
+        self.client_actions_sender
+            .send(Task::ReloadConfig(Box::new(new_config.clone())))?;
+
+        for setting in current_config.settings_requiring_restart(&new_config) {
+            self.log_file.warn(&format!(
+                "Setting {} changed, it will be applied after restarting the server",
+                setting
+            ));
+        }
+
+        *self.config.write()? = new_config;
+        self.log_file.info("Configuration reloaded");
         Ok(())
     }
 
-    /// Creates a new thread for a client
-    pub fn create_new_client_thread(
-        &self,
-        sender_to_task_channel: std::sync::mpsc::Sender<Task>,
-        mut stream: TcpStream,
-        client_id: Vec<u8>,
-        log_file: Arc<Logger>,
-    ) {
-        let key = *self.config.get_key();
-
-        thread::spawn(move || {
-            loop {
-                let packet = Packet::from_bytes(&mut stream, &key);
-                match packet {
-                    Ok(packet) => {
-                        if !handle_packet(
-                            packet,
-                            client_id.clone(),
-                            sender_to_task_channel.clone(),
-                            log_file.clone(),
-                        ) {
-                            break;
-                        }
-                    }
-                    Err(err) => {
-                        log_file.error(&format!("Connection Error: {:?}", err));
-                        break;
-                    }
-                }
-            }
-            log_file.info("Disconnecting client");
-            disconnect_client(sender_to_task_channel, client_id).unwrap_or(false);
-        });
+    /// Returns the current configuration of the server
+    pub fn config(&self) -> Config {
+        match self.config.read() {
+            Ok(config) => config.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Returns true if the configured networks accept connections from the address
+    pub fn is_address_permitted(&self, address: IpAddr) -> bool {
+        let config = match self.config.read() {
+            Ok(config) => config,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        network_filter::is_permitted(
+            address,
+            config.get_allowed_networks(),
+            config.get_denied_networks(),
+        )
+    }
+
+    /// Returns the log file of the server
+    pub fn log_file(&self) -> &Arc<Logger> {
+        &self.log_file
+    }
+
+    /// Returns the channel used to send tasks to the task handler
+    pub fn client_actions_sender(&self) -> &TaskSender {
+        &self.client_actions_sender
+    }
+}
+
+/// Builds the ACL of the guests if anonymous access is enabled
+fn anonymous_acl(config: &Config) -> Option<Acl> {
+    if config.get_allow_anonymous() {
+        Some(Acl::from_fields(
+            Some(config.get_anonymous_publish_rules()),
+            Some(config.get_anonymous_subscribe_rules()),
+        ))
+    } else {
+        None
+    }
+}
+
+/// Bans the client ids and addresses listed in the configuration. Bans made at runtime are kept
+fn apply_ban_list(client_manager: &mut ClientManager, config: &Config) {
+    for client_id in config.get_banned_clients() {
+        client_manager.ban_client(client_id.as_bytes().to_vec());
+    }
+    for address in config.get_banned_addresses() {
+        client_manager.ban_address(*address);
     }
 }
 
@@ -195,16 +328,10 @@ impl Server {
 pub fn handle_packet(
     packet: Packet,
     client_id: Vec<u8>,
-    sender_to_task_channel: std::sync::mpsc::Sender<Task>,
+    sender_to_task_channel: TaskSender,
     log_file: Arc<Logger>,
 ) -> bool {
-    let log_message = |packet_type: &str| {
-        log_file.info(&format!(
-            "Received {} packet from client: {}",
-            packet_type,
-            String::from_utf8_lossy(&client_id)
-        ));
-    };
+    let log_message = |packet_type: &str| log_file.log_received_packet(packet_type, &client_id);
     match packet {
         Packet::Publish(publish_packet) => {
             log_message("Publish");
@@ -219,46 +346,57 @@ pub fn handle_packet(
             handle_unsubscribe(unsubscribe_packet, sender_to_task_channel, client_id)
                 .unwrap_or(false)
         }
+        Packet::Puback(puback_packet) => {
+            log_message("Puback");
+            handle_puback(puback_packet, sender_to_task_channel, client_id).unwrap_or(false)
+        }
         Packet::Pingreq(_) => {
             log_message("Pingreq");
             handle_pingreq(sender_to_task_channel, client_id).unwrap_or(false)
         }
         Packet::Disconnect(_) => {
             log_message("Disconnect");
-            disconnect_client(sender_to_task_channel, client_id).unwrap_or(false)
+            disconnect_client(
+                sender_to_task_channel,
+                client_id,
+                DisconnectCause::Requested,
+            )
+            .unwrap_or(false)
         }
         _ => {
             log_file.error("Unsupported packet type");
             log_file.info("Disconnecting client");
-            disconnect_client(sender_to_task_channel, client_id).unwrap_or(false);
+            disconnect_client(
+                sender_to_task_channel,
+                client_id,
+                DisconnectCause::ProtocolError,
+            )
+            .unwrap_or(false);
             false
         }
     }
 }
 
 /// Handles a CONNECT packet
-pub fn handle_connect(
-    sender_to_topics_channel: std::sync::mpsc::Sender<Task>,
-    client: Client,
-) -> ServerResult<bool> {
-    sender_to_topics_channel.send(Task::ConnectClient(client))?;
+pub fn handle_connect(sender_to_topics_channel: TaskSender, client: Client) -> ServerResult<bool> {
+    sender_to_topics_channel.send(Task::ConnectClient(Box::new(client)))?;
     Ok(true)
 }
 
 /// Handles a PUBLISH packet
 pub fn handle_publish(
     publish_packet: Publish,
-    sender_to_topics_channel: std::sync::mpsc::Sender<Task>,
+    sender_to_topics_channel: TaskSender,
     client_id: Vec<u8>,
 ) -> ServerResult<bool> {
-    sender_to_topics_channel.send(Task::Publish(publish_packet, client_id))?;
+    sender_to_topics_channel.send(Task::Publish(publish_packet, client_id, Trace::start()))?;
     Ok(true)
 }
 
 /// Handles a SUBSCRIBE packet
 pub fn handle_subscribe(
     subscribe_packet: Subscribe,
-    sender_to_task_channel: std::sync::mpsc::Sender<Task>,
+    sender_to_task_channel: TaskSender,
     client_id: Vec<u8>,
 ) -> ServerResult<bool> {
     sender_to_task_channel.send(Task::SubscribeClient(subscribe_packet, client_id))?;
@@ -269,7 +407,7 @@ pub fn handle_subscribe(
 /// Handles an UNSUBSCRIBE packet
 pub fn handle_unsubscribe(
     unsubscribe_packet: Unsubscribe,
-    sender_to_task_channel: std::sync::mpsc::Sender<Task>,
+    sender_to_task_channel: TaskSender,
     client_id: Vec<u8>,
 ) -> ServerResult<bool> {
     sender_to_task_channel.send(Task::UnsubscribeClient(unsubscribe_packet, client_id))?;
@@ -277,20 +415,33 @@ pub fn handle_unsubscribe(
     Ok(true)
 }
 
+/// Handles a PUBACK packet of a message sent to the client
+pub fn handle_puback(
+    puback_packet: Puback,
+    sender_to_task_channel: TaskSender,
+    client_id: Vec<u8>,
+) -> ServerResult<bool> {
+    if let Some(packet_identifier) = puback_packet.packet_identifier() {
+        sender_to_task_channel.send(Task::AcknowledgeMessage(packet_identifier, client_id))?;
+    }
+    Ok(true)
+}
+
 /// Handles a PINGREQ packet
 pub fn handle_pingreq(
-    sender_to_task_channel: std::sync::mpsc::Sender<Task>,
+    sender_to_task_channel: TaskSender,
     client_id: Vec<u8>,
 ) -> ServerResult<bool> {
     sender_to_task_channel.send(Task::RespondPing(client_id))?;
     Ok(true)
 }
 
-/// Handles a DISCONNECT packet
+/// Tells the task handler that the connection of a client ended and why
 pub fn disconnect_client(
-    sender_to_task_channel: std::sync::mpsc::Sender<Task>,
+    sender_to_task_channel: TaskSender,
     client_id: Vec<u8>,
+    cause: DisconnectCause,
 ) -> ServerResult<bool> {
-    sender_to_task_channel.send(Task::DisconnectClient(client_id))?;
+    sender_to_task_channel.send(Task::DisconnectClient(client_id, cause))?;
     Ok(false)
 }
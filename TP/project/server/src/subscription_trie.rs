@@ -0,0 +1,230 @@
+use std::collections::{HashMap, HashSet};
+
+use mqtt::model::components::{
+    topic_filter::TopicFilter, topic_level::TopicLevel, topic_name::TopicName,
+};
+
+/// Represents a client ID
+type ClientId = Vec<u8>;
+
+/// Represents a level of the subscription trie
+#[derive(Debug, Default, Clone)]
+struct TrieNode {
+    /// Children reached with a literal level
+    literals: HashMap<Vec<u8>, TrieNode>,
+    /// Child reached with a single level wildcard
+    single_level_wildcard: Option<Box<TrieNode>>,
+    /// Clients subscribed to a filter that ends with a multi level wildcard at this level
+    multi_level_subscribers: HashSet<ClientId>,
+    /// Clients subscribed to a filter that ends at this level
+    subscribers: HashSet<ClientId>,
+}
+
+impl TrieNode {
+    fn is_empty(&self) -> bool {
+        self.literals.is_empty()
+            && self.single_level_wildcard.is_none()
+            && self.multi_level_subscribers.is_empty()
+            && self.subscribers.is_empty()
+    }
+
+    fn insert(&mut self, levels: &[TopicLevel], client_id: &[u8]) {
+        match levels.split_first() {
+            None => {
+                self.subscribers.insert(client_id.to_vec());
+            }
+            Some((TopicLevel::MultiLevelWildcard, _)) => {
+                self.multi_level_subscribers.insert(client_id.to_vec());
+            }
+            Some((TopicLevel::SingleLevelWildcard, rest)) => self
+                .single_level_wildcard
+                .get_or_insert_with(Box::default)
+                .insert(rest, client_id),
+            Some((TopicLevel::Literal(level), rest)) => self
+                .literals
+                .entry(level.clone())
+                .or_default()
+                .insert(rest, client_id),
+        }
+    }
+
+    /// Removes the client from the filter, pruning the nodes that become empty
+    fn remove(&mut self, levels: &[TopicLevel], client_id: &[u8]) {
+        match levels.split_first() {
+            None => {
+                self.subscribers.remove(client_id);
+            }
+            Some((TopicLevel::MultiLevelWildcard, _)) => {
+                self.multi_level_subscribers.remove(client_id);
+            }
+            Some((TopicLevel::SingleLevelWildcard, rest)) => {
+                if let Some(child) = &mut self.single_level_wildcard {
+                    child.remove(rest, client_id);
+                    if child.is_empty() {
+                        self.single_level_wildcard = None;
+                    }
+                }
+            }
+            Some((TopicLevel::Literal(level), rest)) => {
+                if let Some(child) = self.literals.get_mut(level) {
+                    child.remove(rest, client_id);
+                    if child.is_empty() {
+                        self.literals.remove(level);
+                    }
+                }
+            }
+        }
+    }
+
+    fn collect(&self, levels: &[Vec<u8>], subscribers: &mut HashSet<ClientId>) {
+        // A multi level wildcard also matches its parent level
+        subscribers.extend(self.multi_level_subscribers.iter().cloned());
+
+        match levels.split_first() {
+            None => subscribers.extend(self.subscribers.iter().cloned()),
+            Some((level, rest)) => {
+                if let Some(child) = self.literals.get(level) {
+                    child.collect(rest, subscribers);
+                }
+                if let Some(child) = &self.single_level_wildcard {
+                    child.collect(rest, subscribers);
+                }
+            }
+        }
+    }
+}
+
+/// Represents the subscriptions of every client indexed by topic level,
+/// so routing a message costs as much as the depth of its topic instead of the number of clients.
+/// Topics reserved by the server (starting with `$`) are kept apart, as they only match
+/// filters that are also reserved
+#[derive(Debug, Default, Clone)]
+pub struct SubscriptionTrie {
+    root: TrieNode,
+    server_reserved_root: TrieNode,
+    /// Number of times the subscriptions changed
+    generation: u64,
+}
+
+impl SubscriptionTrie {
+    /// Creates an empty trie
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribes a client to a topic filter
+    pub fn subscribe(&mut self, topic_filter: &TopicFilter, client_id: &[u8]) {
+        self.generation += 1;
+        self.root_for(topic_filter.server_reserved())
+            .insert(topic_filter.levels(), client_id);
+    }
+
+    /// Unsubscribes a client from a topic filter
+    pub fn unsubscribe(&mut self, topic_filter: &TopicFilter, client_id: &[u8]) {
+        self.generation += 1;
+        self.root_for(topic_filter.server_reserved())
+            .remove(topic_filter.levels(), client_id);
+    }
+
+    /// Returns a number that changes every time a client subscribes or unsubscribes
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Returns the clients subscribed to a filter that matches the topic
+    pub fn subscribers(&self, topic_name: &TopicName) -> HashSet<ClientId> {
+        let root = if topic_name.server_reserved() {
+            &self.server_reserved_root
+        } else {
+            &self.root
+        };
+
+        let mut subscribers = HashSet::new();
+        root.collect(topic_name.levels(), &mut subscribers);
+        subscribers
+    }
+
+    fn root_for(&mut self, server_reserved: bool) -> &mut TrieNode {
+        if server_reserved {
+            &mut self.server_reserved_root
+        } else {
+            &mut self.root
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(levels: &[&str]) -> TopicFilter {
+        let levels = levels
+            .iter()
+            .map(|level| TopicLevel::from_bytes(level.as_bytes().to_vec()).unwrap())
+            .collect();
+        TopicFilter::new(levels, false)
+    }
+
+    fn topic(levels: &[&str]) -> TopicName {
+        let levels = levels
+            .iter()
+            .map(|level| level.as_bytes().to_vec())
+            .collect();
+        TopicName::new(levels, false)
+    }
+
+    #[test]
+    fn test_literal_and_wildcard_matching() {
+        let mut trie = SubscriptionTrie::new();
+        trie.subscribe(&filter(&["drone-data", "1"]), b"a");
+        trie.subscribe(&filter(&["drone-data", "+"]), b"b");
+        trie.subscribe(&filter(&["drone-data", "#"]), b"c");
+        trie.subscribe(&filter(&["#"]), b"d");
+        trie.subscribe(&filter(&["new-incident"]), b"e");
+
+        let subscribers = trie.subscribers(&topic(&["drone-data", "1"]));
+        let expected: HashSet<ClientId> = [b"a", b"b", b"c", b"d"]
+            .iter()
+            .map(|id| id.to_vec())
+            .collect();
+        assert_eq!(subscribers, expected);
+
+        let subscribers = trie.subscribers(&topic(&["drone-data"]));
+        let expected: HashSet<ClientId> = [b"c", b"d"].iter().map(|id| id.to_vec()).collect();
+        assert_eq!(subscribers, expected);
+
+        assert!(!trie
+            .subscribers(&topic(&["drone-data", "1", "extra"]))
+            .contains(&b"b"[..]));
+    }
+
+    #[test]
+    fn test_unsubscribe_prunes_trie() {
+        let mut trie = SubscriptionTrie::new();
+        trie.subscribe(&filter(&["camera-data", "+"]), b"a");
+        trie.unsubscribe(&filter(&["camera-data", "+"]), b"a");
+
+        assert!(trie.subscribers(&topic(&["camera-data", "1"])).is_empty());
+        assert!(trie.root.is_empty());
+    }
+
+    #[test]
+    fn test_server_reserved_topics_only_match_reserved_filters() {
+        let mut trie = SubscriptionTrie::new();
+        trie.subscribe(&filter(&["#"]), b"a");
+        trie.subscribe(
+            &TopicFilter::new(
+                vec![
+                    TopicLevel::Literal(b"$SYS".to_vec()),
+                    TopicLevel::MultiLevelWildcard,
+                ],
+                true,
+            ),
+            b"b",
+        );
+
+        let sys_topic = TopicName::new(vec![b"$SYS".to_vec(), b"broker".to_vec()], true);
+        let subscribers = trie.subscribers(&sys_topic);
+        assert_eq!(subscribers, HashSet::from([b"b".to_vec()]));
+    }
+}
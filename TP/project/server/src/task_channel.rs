@@ -0,0 +1,110 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    mpsc::{self, Receiver, RecvTimeoutError, SendError, SyncSender},
+    Arc,
+};
+use std::time::Duration;
+
+use crate::task_handler::Task;
+
+/// Creates the bounded channel that feeds the task handler.
+/// Once `capacity` tasks are waiting, senders block until the task handler catches up
+pub fn task_channel(capacity: usize) -> (TaskSender, TaskReceiver) {
+    let (sender, receiver) = mpsc::sync_channel(capacity);
+    let queued_tasks = Arc::new(AtomicUsize::new(0));
+
+    (
+        TaskSender {
+            sender,
+            queued_tasks: queued_tasks.clone(),
+        },
+        TaskReceiver {
+            receiver,
+            queued_tasks,
+        },
+    )
+}
+
+/// Represents the sending side of the task channel
+#[derive(Debug, Clone)]
+pub struct TaskSender {
+    sender: SyncSender<Task>,
+    queued_tasks: Arc<AtomicUsize>,
+}
+
+impl TaskSender {
+    /// Sends a task to the task handler, blocking while the channel is full
+    pub fn send(&self, task: Task) -> Result<(), SendError<Task>> {
+        self.queued_tasks.fetch_add(1, Ordering::Relaxed);
+        let result = self.sender.send(task);
+        if result.is_err() {
+            self.queued_tasks.fetch_sub(1, Ordering::Relaxed);
+        }
+        result
+    }
+}
+
+/// Represents the receiving side of the task channel
+#[derive(Debug)]
+pub struct TaskReceiver {
+    receiver: Receiver<Task>,
+    queued_tasks: Arc<AtomicUsize>,
+}
+
+impl TaskReceiver {
+    /// Waits for the next task up to the given timeout
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Task, RecvTimeoutError> {
+        let task = self.receiver.recv_timeout(timeout)?;
+        self.queued_tasks.fetch_sub(1, Ordering::Relaxed);
+        Ok(task)
+    }
+
+    /// Returns the number of tasks waiting to be handled
+    pub fn queue_depth(&self) -> usize {
+        self.queued_tasks.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task_handler::DisconnectCause;
+    use std::thread;
+
+    #[test]
+    fn test_queue_depth_follows_sent_and_received_tasks() {
+        let (sender, receiver) = task_channel(4);
+
+        sender.send(Task::RespondPing(b"a".to_vec())).unwrap();
+        sender
+            .send(Task::DisconnectClient(
+                b"a".to_vec(),
+                DisconnectCause::Requested,
+            ))
+            .unwrap();
+        assert_eq!(receiver.queue_depth(), 2);
+
+        receiver.recv_timeout(Duration::from_millis(10)).unwrap();
+        assert_eq!(receiver.queue_depth(), 1);
+    }
+
+    #[test]
+    fn test_full_channel_blocks_sender() {
+        let (sender, receiver) = task_channel(1);
+        sender.send(Task::RespondPing(b"a".to_vec())).unwrap();
+
+        let blocked_sender = sender.clone();
+        let handle = thread::spawn(move || {
+            blocked_sender
+                .send(Task::RespondPing(b"b".to_vec()))
+                .unwrap();
+        });
+
+        thread::sleep(Duration::from_millis(50));
+        assert!(!handle.is_finished());
+
+        receiver.recv_timeout(Duration::from_millis(10)).unwrap();
+        handle.join().unwrap();
+        assert_eq!(receiver.queue_depth(), 1);
+    }
+}
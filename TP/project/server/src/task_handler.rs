@@ -1,20 +1,39 @@
 use std::{
     collections::{HashMap, HashSet, VecDeque},
-    io::{Read, Write},
-    sync::{mpsc, Arc, RwLock},
-    thread,
+    fmt,
+    io::Write,
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc, RwLock,
+    },
     time::{Duration, Instant},
 };
 
 use crate::{
-    client::Client, client_manager::ClientManager, config::Config, error::ServerResult,
-    logfile::Logger,
+    acl::Acl,
+    admin_api::{AdminQuery, AdminResponse, ClientInfo},
+    bridge::Bridge,
+    client::Client,
+    client_manager::ClientManager,
+    client_stats::unix_secs,
+    client_writer::{ClientWriter, WriterHandle},
+    cluster::{self, Cluster},
+    config::Config,
+    error::ServerResult,
+    fast_path::FastPath,
+    logfile::{Logger, SessionSummary},
+    message_trace::{MessageLatencies, Stage, Trace},
+    outbound_queue::{Delivery, OutboundQueues},
+    persistence::{self, PersistenceBackend, StateChange},
+    subscription_trie::SubscriptionTrie,
+    task_channel::TaskReceiver,
+    topic_stats::TopicStatsTracker,
 };
 
-use mqtt::model::packet::Packet;
-
 use mqtt::model::{
-    components::{qos::QoS, topic_filter::TopicFilter, topic_name::TopicName},
+    components::{
+        qos::QoS, topic_filter::TopicFilter, topic_level::TopicLevel, topic_name::TopicName,
+    },
     packets::{
         connack::Connack, pingresp::Pingresp, puback::Puback, publish::Publish, suback::Suback,
         subscribe::Subscribe, unsuback::Unsuback, unsubscribe::Unsubscribe,
@@ -22,134 +41,265 @@ use mqtt::model::{
     return_codes::{connect_return_code::ConnectReturnCode, suback_return_code::SubackReturnCode},
 };
 
-use std::fs::File;
+/// Represents why the connection of a client ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisconnectCause {
+    /// The client sent a DISCONNECT packet
+    Requested,
+    /// The client sent a packet that could not be read or is not allowed
+    ProtocolError,
+    /// The connection was closed or failed without a DISCONNECT packet
+    ConnectionLost,
+}
+
+impl fmt::Display for DisconnectCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DisconnectCause::Requested => write!(f, "sent DISCONNECT"),
+            DisconnectCause::ProtocolError => write!(f, "protocol error"),
+            DisconnectCause::ConnectionLost => write!(f, "connection lost"),
+        }
+    }
+}
 
 /// Represents the different tasks that the task handler can perform
 pub enum Task {
     SubscribeClient(Subscribe, Vec<u8>),
     UnsubscribeClient(Unsubscribe, Vec<u8>),
-    Publish(Publish, Vec<u8>),
-    ConnectClient(Client),
-    DisconnectClient(Vec<u8>),
+    /// Publishes a message received from a client, traced since it was read
+    Publish(Publish, Vec<u8>, Trace),
+    ConnectClient(Box<Client>),
+    DisconnectClient(Vec<u8>, DisconnectCause),
     RespondPing(Vec<u8>),
+    /// Releases the in-flight slot of a message the client acknowledged with a PUBACK
+    AcknowledgeMessage(u16, Vec<u8>),
+    /// Accounts for a QoS 0 message the event loop sent to its subscribers through the fast path,
+    /// with the time it took to hand it to their writers
+    PublishedDirectly(Box<Publish>, Vec<u8>, Vec<Vec<u8>>, Trace, Duration),
+    /// Publishes locally a message received from the upstream broker of the bridge
+    BridgePublish(Publish),
+    /// Publishes locally a message received from another server of the cluster
+    ClusterPublish(Publish),
+    /// Answers a query of the admin API through the channel
+    AdminQuery(AdminQuery, mpsc::Sender<AdminResponse>),
+    /// Applies the settings of a reloaded configuration
+    ReloadConfig(Box<Config>),
+    /// Disconnects every client and saves a final backup, then answers through the channel
+    Shutdown(mpsc::Sender<()>),
 }
 
 const ADMIN_ID: &[u8] = b"admin";
 const CLIENT_REGISTER: &[u8] = b"$client-register";
+const ADMIN_TOPIC: &[u8] = b"$admin";
+const ADMIN_KICK: &[u8] = b"kick";
+const ADMIN_CLIENTS: &[u8] = b"clients";
+const ADMIN_RETAINED: &[u8] = b"retained";
+const ADMIN_CLEAR: &[u8] = b"clear";
+const ADMIN_BAN: &[u8] = b"ban";
+const ADMIN_BAN_IP: &[u8] = b"ip";
 const SEPARATOR: u8 = b';';
 
-const RETAINED_MESSAGES_TAG: &str = "R";
-const OFFLINE_MESSAGES_TAG: &str = "O";
-const CLIENTS_TAG: &str = "C";
+const SYS_CONNECTED_CLIENTS: &str = "$SYS/broker/clients/connected";
+const SYS_QUEUED_TASKS: &str = "$SYS/broker/tasks/queued";
+const SYS_CLIENT_LIST: &str = "$SYS/broker/clients/list";
+/// Root of the message and byte counters of every topic
+const SYS_TOPICS: &str = "$SYS/topics";
+/// Root of the stats of every connected client
+const SYS_CLIENTS: &str = "$SYS/clients";
+/// Root of the latency histograms of the stages a message goes through
+const SYS_LATENCY: &str = "$SYS/broker/latency";
+/// How often the depth of the task queue and the topic counters are published
+const SYS_METRICS_INTERVAL: Duration = Duration::from_secs(1);
+/// How often the retained messages older than the TTL are removed
+const RETAINED_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+/// How often the clients that exceeded their keep alive are disconnected in strict mode
+const KEEP_ALIVE_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
 
 /// Represents the task handler that will handle all the tasks that the server needs to process
 #[derive(Debug)]
 pub struct TaskHandler {
-    client_actions_receiver_channel: mpsc::Receiver<Task>,
+    client_actions_receiver_channel: TaskReceiver,
     clients: RwLock<HashMap<Vec<u8>, Client>>,
+    subscriptions: SubscriptionTrie,
     active_connections: HashSet<Vec<u8>>,
-    offline_messages: HashMap<Vec<u8>, VecDeque<Publish>>,
-    retained_messages: HashMap<TopicName, VecDeque<Publish>>,
+    /// Messages waiting to be delivered to each client, in the order they were published
+    outbound_queues: OutboundQueues,
+    /// Retained messages of each topic with the instant they were retained
+    retained_messages: HashMap<TopicName, VecDeque<(Instant, Publish)>>,
+    topic_stats: TopicStatsTracker,
+    latencies: MessageLatencies,
     log_file: Arc<Logger>,
     client_manager: Arc<RwLock<ClientManager>>,
-    key: [u8; 32],
-    backup_file: Option<String>,
+    key: Vec<u8>,
+    persistence: Option<Box<dyn PersistenceBackend>>,
+    bridge: Option<Bridge>,
+    cluster: Option<Cluster>,
     segs_to_backup: u32,
+    max_payload_bytes: Option<usize>,
+    disconnect_on_oversized_payload: bool,
+    disconnect_on_invalid_topic: bool,
+    max_subscriptions_per_client: Option<usize>,
+    allow_multi_level_wildcard: bool,
+    retained_ttl: Option<Duration>,
+    /// Enforces the keep alive of the clients
+    strict_mode: bool,
+    /// Instant each connected client sent its last packet, tracked in strict mode
+    last_activity: HashMap<Vec<u8>, Instant>,
+    fast_path: Arc<FastPath>,
+    fast_path_enabled: bool,
+    /// Generation of the subscriptions and the writers last shared with the fast path
+    fast_path_shared: Option<(u64, HashMap<Vec<u8>, WriterHandle>)>,
 }
 
 impl TaskHandler {
     /// Creates a new task handler with the specified receiver channel, logger and client manager
     pub fn default(
-        receiver_channel: mpsc::Receiver<Task>,
+        receiver_channel: TaskReceiver,
         log_file: Arc<Logger>,
         client_manager: Arc<RwLock<ClientManager>>,
-        key: [u8; 32],
+        key: Vec<u8>,
         segs_to_backup: u32,
-        backup_file: Option<String>,
+        persistence: Option<Box<dyn PersistenceBackend>>,
+        fast_path: Arc<FastPath>,
     ) -> Self {
         TaskHandler {
             client_actions_receiver_channel: receiver_channel,
             clients: RwLock::new(HashMap::new()),
+            subscriptions: SubscriptionTrie::new(),
             active_connections: HashSet::new(),
-            offline_messages: HashMap::new(),
+            outbound_queues: OutboundQueues::new(),
             retained_messages: HashMap::new(),
+            topic_stats: TopicStatsTracker::new(),
+            latencies: MessageLatencies::new(),
             log_file,
             client_manager,
             key,
-            backup_file,
+            persistence,
+            bridge: None,
+            cluster: None,
             segs_to_backup,
+            max_payload_bytes: None,
+            disconnect_on_oversized_payload: false,
+            disconnect_on_invalid_topic: true,
+            max_subscriptions_per_client: None,
+            allow_multi_level_wildcard: true,
+            retained_ttl: None,
+            strict_mode: false,
+            last_activity: HashMap::new(),
+            fast_path,
+            fast_path_enabled: false,
+            fast_path_shared: None,
         }
     }
 
+    /// Creates a new task handler with the specified configuration, restoring the persisted state if enabled.
+    /// The messages of the outgoing topics are forwarded through the bridge, if there is one,
+    /// and the subscriptions are shared with the cluster, if the server is part of one.
+    /// The fast path receives a copy of the subscriptions whenever they change
     pub fn new(
-        client_actions_receiver_channel: mpsc::Receiver<Task>,
+        client_actions_receiver_channel: TaskReceiver,
         config: &Config,
         client_manager: Arc<RwLock<ClientManager>>,
         log_file: Arc<Logger>,
+        bridge: Option<Bridge>,
+        cluster: Option<Cluster>,
+        fast_path: Arc<FastPath>,
     ) -> Self {
-        let backup_file = config.get_backup_file();
-        let initialize_with_backup = config.get_initialize_with_backup();
-        let key = *config.get_key();
-        let segs_to_backup = config.get_segs_to_backup();
+        let persistence = match persistence::backend_from_config(config, log_file.clone()) {
+            Ok(persistence) => persistence,
+            Err(e) => {
+                log_file.error(&format!("{}. The broker state won't be persisted", e));
+                None
+            }
+        };
+
+        let mut task_handler = Self::default(
+            client_actions_receiver_channel,
+            log_file,
+            client_manager,
+            config.get_packet_key().to_vec(),
+            config.get_segs_to_backup(),
+            persistence,
+            fast_path,
+        );
+        task_handler.bridge = bridge;
+        task_handler.cluster = cluster;
+        task_handler.strict_mode = config.get_strict_mode();
+        task_handler.apply_config(config);
+        task_handler.restore_state(config.get_initialize_with_backup());
+        task_handler
+    }
+
+    /// Rebuilds the state from the persistence backend, or discards what it stores if the server
+    /// starts without backup
+    fn restore_state(&mut self, initialize_with_backup: bool) {
+        let persistence = match &self.persistence {
+            Some(persistence) => persistence,
+            None => {
+                self.log_file.info("Initializing server without backup");
+                return;
+            }
+        };
 
         if !initialize_with_backup {
-            log_file.info("Initializing server without backup");
-            return TaskHandler::default(
-                client_actions_receiver_channel,
-                log_file,
-                client_manager,
-                key,
-                segs_to_backup,
-                backup_file,
-            );
-        }
-
-        match backup_file {
-            Some(backup_file_string) => match File::open(backup_file_string.clone()) {
-                Ok(mut file) => {
-                    let mut data = String::new();
-                    if file.read_to_string(&mut data).is_err() {
-                        log_file
-                            .info("Error reading backup file. Initializing server without backup");
-                        return TaskHandler::default(
-                            client_actions_receiver_channel,
-                            log_file,
-                            client_manager,
-                            key,
-                            segs_to_backup,
-                            Some(backup_file_string.clone()),
-                        );
-                    }
+            self.log_file.info("Initializing server without backup");
+            if let Err(e) = persistence.clear() {
+                self.log_file
+                    .error(&format!("Failed to clear the persisted state: {}", e));
+            }
+            return;
+        }
 
-                    log_file.info("Initializing server with backup");
-                    TaskHandler::deserialize(
-                        &data,
-                        client_manager.clone(),
-                        log_file.clone(),
-                        config,
-                        client_actions_receiver_channel,
-                    )
+        match persistence.load() {
+            Ok(changes) => {
+                self.log_file.info("Initializing server with backup");
+                for change in changes {
+                    self.apply_change(change);
                 }
-                Err(_) => {
-                    log_file.info("Error opening backup file. Initializing server without backup");
-                    TaskHandler::default(
-                        client_actions_receiver_channel,
-                        log_file,
-                        client_manager,
-                        key,
-                        segs_to_backup,
-                        Some(backup_file_string.clone()),
-                    )
-                }
-            },
-            None => TaskHandler::default(
-                client_actions_receiver_channel,
-                log_file,
-                client_manager,
-                key,
-                segs_to_backup,
-                backup_file,
-            ),
+            }
+            Err(e) => self.log_file.error(&format!(
+                "Error loading the persisted state: {}. Initializing server without backup",
+                e
+            )),
+        }
+    }
+
+    /// Stores a change of the state in the persistence backend, if there is one,
+    /// and counts how long it took in the latency of the backups
+    fn persist(&self, change: StateChange) {
+        if let Some(persistence) = &self.persistence {
+            let started_at = Instant::now();
+            if let Err(e) = persistence.record(&change) {
+                self.log_file
+                    .error(&format!("Failed to persist a change of the state: {}", e));
+            }
+            self.latencies.record(Stage::Backup, started_at.elapsed());
+        }
+    }
+
+    /// Counts the time a traced message spent in a stage and logs the hop
+    fn record_hop(&self, trace: &Trace, stage: Stage, elapsed: Duration) {
+        self.latencies.record(stage, elapsed);
+        self.log_file.log_trace_hop(trace, stage, None, elapsed);
+    }
+
+    /// Applies the settings that can change while the server is running
+    fn apply_config(&mut self, config: &Config) {
+        self.segs_to_backup = config.get_segs_to_backup();
+        self.max_payload_bytes = config.get_max_payload_bytes();
+        self.disconnect_on_oversized_payload = config.get_disconnect_on_oversized_payload();
+        self.disconnect_on_invalid_topic = config.get_disconnect_on_invalid_topic();
+        self.max_subscriptions_per_client = config.get_max_subscriptions_per_client();
+        self.allow_multi_level_wildcard = config.get_allow_multi_level_wildcard();
+        self.retained_ttl = config.get_retained_ttl_secs().map(Duration::from_secs);
+        self.outbound_queues
+            .set_max_inflight(config.get_max_inflight());
+
+        // Messages for the bridge are forwarded by the task handler
+        self.fast_path_enabled = config.get_fast_path() && self.bridge.is_none();
+        self.fast_path_shared = None;
+        if !self.fast_path_enabled {
+            self.fast_path.disable();
         }
     }
 
@@ -162,24 +312,74 @@ impl TaskHandler {
 
     /// Runs the task handler in a loop
     pub fn run(mut self) {
-        let backup_interval = Duration::from_secs(self.segs_to_backup as u64);
         let mut last_backup = std::time::Instant::now();
+        let mut last_metrics = Instant::now();
+        let mut last_retained_sweep = Instant::now();
+        let mut last_keep_alive_sweep = Instant::now();
+        let mut published_queue_depth = None;
 
         loop {
-            match self.client_actions_receiver_channel.recv() {
+            match self
+                .client_actions_receiver_channel
+                .recv_timeout(SYS_METRICS_INTERVAL)
+            {
+                Ok(Task::Shutdown(done_sender)) => {
+                    self.shutdown();
+                    let _ = done_sender.send(());
+                    return;
+                }
                 Ok(task) => {
                     if let Err(e) = self.handle_task(task) {
                         self.log_file.error(e.to_string().as_str());
                     }
                 }
-                Err(_) => {
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
                     std::thread::sleep(Duration::from_secs(1));
                     continue;
                 }
             }
 
-            if self.backup_file.is_some() && last_backup.elapsed() >= backup_interval {
-                self.log_file.info("Backing up server data");
+            if last_metrics.elapsed() >= SYS_METRICS_INTERVAL {
+                let queue_depth = self.client_actions_receiver_channel.queue_depth();
+                if published_queue_depth != Some(queue_depth) {
+                    if let Err(e) = self.publish_queue_depth(queue_depth) {
+                        self.log_file.error(e.to_string().as_str());
+                    }
+                    published_queue_depth = Some(queue_depth);
+                }
+                if let Err(e) = self.publish_topic_stats() {
+                    self.log_file.error(e.to_string().as_str());
+                }
+                if let Err(e) = self.publish_client_stats() {
+                    self.log_file.error(e.to_string().as_str());
+                }
+                if let Err(e) = self.publish_latencies() {
+                    self.log_file.error(e.to_string().as_str());
+                }
+                last_metrics = Instant::now();
+            }
+
+            if last_retained_sweep.elapsed() >= RETAINED_SWEEP_INTERVAL {
+                self.expire_retained_messages();
+                last_retained_sweep = Instant::now();
+            }
+
+            if self.strict_mode && last_keep_alive_sweep.elapsed() >= KEEP_ALIVE_SWEEP_INTERVAL {
+                if let Err(e) = self.disconnect_silent_clients() {
+                    self.log_file.error(e.to_string().as_str());
+                }
+                last_keep_alive_sweep = Instant::now();
+            }
+
+            let takes_snapshots = self
+                .persistence
+                .as_ref()
+                .is_some_and(|persistence| persistence.takes_snapshots());
+            if takes_snapshots
+                && last_backup.elapsed() >= Duration::from_secs(self.segs_to_backup as u64)
+            {
+                self.log_file.debug("Backing up server data");
                 self.backup_data();
                 last_backup = Instant::now();
             }
@@ -188,40 +388,187 @@ impl TaskHandler {
 
     /// Handles all possible tasks that the server can receive
     fn handle_task(&mut self, task: Task) -> ServerResult<()> {
-        match task {
+        self.record_activity(&task);
+
+        let result = match task {
             Task::SubscribeClient(subscribe, client_id) => self.subscribe(subscribe, client_id),
             Task::UnsubscribeClient(unsubscribe, client_id) => {
                 self.unsubscribe(unsubscribe, client_id)
             }
-            Task::Publish(publish, client_id) => self.publish(&publish, client_id),
-            Task::ConnectClient(client) => self.handle_new_client_connection(client),
-            Task::DisconnectClient(client_id) => self.handle_client_disconnected(client_id),
+            Task::Publish(publish, client_id, trace) => self.publish(&publish, client_id, trace),
+            Task::PublishedDirectly(publish, client_id, subscribers, trace, elapsed) => {
+                self.account_direct_publish(&publish, &client_id, subscribers, trace, elapsed)
+            }
+            Task::ConnectClient(client) => self.handle_new_client_connection(*client),
+            Task::DisconnectClient(client_id, cause) => {
+                self.handle_client_disconnected(client_id, cause)
+            }
             Task::RespondPing(client_id) => self.respond_ping(client_id),
+            Task::AcknowledgeMessage(packet_identifier, client_id) => {
+                self.acknowledge_message(packet_identifier, client_id)
+            }
+            Task::BridgePublish(publish) => self.publish_from_bridge(&publish),
+            Task::ClusterPublish(publish) => self.publish_from_cluster(&publish),
+            Task::AdminQuery(query, response_sender) => {
+                self.answer_admin_query(query, response_sender)
+            }
+            Task::ReloadConfig(config) => {
+                self.apply_config(&config);
+                Ok(())
+            }
+            // The run loop stops before handling it
+            Task::Shutdown(_) => Ok(()),
+        };
+
+        self.refresh_fast_path();
+        result
+    }
+
+    /// Shares the subscriptions and the writers of the clients that can receive messages
+    /// directly with the fast path, if they changed since they were last shared.
+    /// Clients with queued messages are left out, so no message overtakes the queued ones
+    fn refresh_fast_path(&mut self) {
+        if !self.fast_path_enabled {
+            return;
+        }
+
+        let writers: HashMap<Vec<u8>, WriterHandle> = match self.clients.read() {
+            Ok(clients) => self
+                .active_connections
+                .iter()
+                .filter(|client_id| self.outbound_queues.len(client_id) == 0)
+                .filter_map(|client_id| {
+                    let writer = clients.get(client_id)?.stream.as_ref()?.handle()?;
+                    Some((client_id.clone(), writer))
+                })
+                .collect(),
+            Err(_) => return,
+        };
+
+        let generation = self.subscriptions.generation();
+        let unchanged =
+            self.fast_path_shared
+                .as_ref()
+                .is_some_and(|(shared_generation, shared_writers)| {
+                    *shared_generation == generation
+                        && shared_writers.len() == writers.len()
+                        && writers.iter().all(|(client_id, writer)| {
+                            shared_writers
+                                .get(client_id)
+                                .is_some_and(|shared| shared.same_writer(writer))
+                        })
+                });
+        if unchanged {
+            return;
+        }
+
+        self.fast_path.update(
+            self.subscriptions.clone(),
+            writers.clone(),
+            self.max_payload_bytes,
+        );
+        self.fast_path_shared = Some((generation, writers));
+    }
+
+    /// Sends DISCONNECT to the connected clients and stores the final state before the server exits
+    fn shutdown(&mut self) {
+        self.log_file
+            .info("Disconnecting clients before shutting down");
+        match self.clients.write() {
+            Ok(mut clients) => {
+                for client_id in self.active_connections.drain() {
+                    if let Some(client) = clients.get_mut(&client_id) {
+                        client.disconnect(&self.key);
+                    }
+                }
+            }
+            Err(_) => self
+                .log_file
+                .error("Error disconnecting clients on shutdown"),
+        }
+
+        if let Some(persistence) = &self.persistence {
+            if let Err(e) = persistence.close(self.snapshot()) {
+                self.log_file
+                    .error(&format!("Failed to save the final state: {}", e));
+            }
         }
     }
 
     /// Subscribe a client_id into a set of topics given a Subscribe packet
-    pub fn subscribe(&self, subscribe_packet: Subscribe, client_id: Vec<u8>) -> ServerResult<()> {
+    pub fn subscribe(
+        &mut self,
+        subscribe_packet: Subscribe,
+        client_id: Vec<u8>,
+    ) -> ServerResult<()> {
         let mut clients = self.clients.write()?;
 
         if let Some(client) = clients.get_mut(&client_id) {
-            self.suback(subscribe_packet.packet_identifier(), client);
-
-            self.log_file
-                .log_successful_subscription(&client_id, &subscribe_packet);
+            let client_manager = self.client_manager.read()?;
+            let mut return_codes = vec![];
+            let mut allowed_topics: Vec<TopicFilter> = vec![];
+            let mut subscriptions = client.subscriptions.len();
+            // The admin and the links of the cluster are not limited
+            let limited = client_id != ADMIN_ID && !cluster::is_cluster_link(&client_id);
 
             for (topic_filter, _) in subscribe_packet.topics() {
-                client.add_subscription(topic_filter.clone());
-
-                // Send the retained message if it exists
-                for (topic_name, retained_messages) in &self.retained_messages {
-                    if topic_filter.match_topic_name(topic_name.clone()) {
-                        for message in retained_messages {
-                            client.send_message(message.clone(), &self.log_file, &self.key);
+                let is_new = !client.subscriptions.contains(&topic_filter)
+                    && !allowed_topics.contains(&topic_filter);
+
+                let denied_reason = if !client_manager.can_subscribe(&client_id, &topic_filter)? {
+                    Some("denied by its ACL")
+                } else if limited
+                    && !self.allow_multi_level_wildcard
+                    && topic_filter
+                        .levels()
+                        .contains(&TopicLevel::MultiLevelWildcard)
+                {
+                    Some("multi-level wildcards are not allowed")
+                } else if limited
+                    && is_new
+                    && self
+                        .max_subscriptions_per_client
+                        .is_some_and(|max_subscriptions| subscriptions >= max_subscriptions)
+                {
+                    Some("too many subscriptions")
+                } else {
+                    None
+                };
+
+                match denied_reason {
+                    Some(reason) => {
+                        self.log_file
+                            .log_subscription_denied(&client_id, &topic_filter, reason);
+                        return_codes.push(SubackReturnCode::Failure);
+                    }
+                    None => {
+                        return_codes.push(SubackReturnCode::SuccessMaximumQoS0);
+                        if is_new {
+                            subscriptions += 1;
                         }
+                        allowed_topics.push(topic_filter);
                     }
                 }
             }
+            drop(client_manager);
+
+            self.suback(subscribe_packet.packet_identifier(), return_codes, client);
+
+            self.log_file
+                .log_successful_subscription(&client_id, &allowed_topics);
+
+            for topic_filter in allowed_topics {
+                self.subscriptions.subscribe(&topic_filter, &client_id);
+                self.persist(StateChange::Subscribed(
+                    client_id.clone(),
+                    topic_filter.clone(),
+                ));
+                if let Some(cluster) = &mut self.cluster {
+                    cluster.share_subscription(&client_id, &topic_filter);
+                }
+                self.send_retained_messages(client, &topic_filter);
+                client.add_subscription(topic_filter);
+            }
         } else {
             self.log_file.log_client_does_not_exist(&client_id);
         }
@@ -230,7 +577,7 @@ impl TaskHandler {
 
     /// Unsubscribe a client_id from a set of topics given an Unsubscribe packet
     pub fn unsubscribe(
-        &self,
+        &mut self,
         unsubscribe_packet: Unsubscribe,
         client_id: Vec<u8>,
     ) -> ServerResult<()> {
@@ -238,6 +585,11 @@ impl TaskHandler {
 
         if let Some(client) = clients.get_mut(&client_id) {
             for topic_filter in unsubscribe_packet.topics() {
+                self.subscriptions.unsubscribe(topic_filter, &client_id);
+                self.persist(StateChange::Unsubscribed(
+                    client_id.clone(),
+                    topic_filter.clone(),
+                ));
                 client.remove_subscription(topic_filter);
             }
 
@@ -252,49 +604,60 @@ impl TaskHandler {
     }
 
     /// Publish a message to all clients subscribed to the topic of the Publish packet
-    pub fn publish(&mut self, publish_packet: &Publish, client_id: Vec<u8>) -> ServerResult<()> {
+    pub fn publish(
+        &mut self,
+        publish_packet: &Publish,
+        client_id: Vec<u8>,
+        trace: Trace,
+    ) -> ServerResult<()> {
+        self.record_hop(&trace, Stage::TaskQueue, trace.elapsed());
         let topic_name = publish_packet.topic();
 
-        if topic_name.server_reserved() {
-            self.handle_server_reserved_topic(publish_packet, client_id);
-            return Ok(());
+        if let Some(client) = self.clients.read()?.get(&client_id) {
+            client.stats().record_in(publish_packet.message().len());
         }
 
-        if publish_packet.retain() {
-            self.retained_messages
-                .entry(topic_name.clone())
-                .or_default()
-                .push_back(publish_packet.clone());
+        if let Err(err) = topic_name.validate() {
+            return self.reject_invalid_topic(publish_packet, &client_id, &err.to_string());
         }
 
-        let mut clients = vec![];
+        if !self
+            .client_manager
+            .read()?
+            .can_publish(&client_id, topic_name)?
+        {
+            self.log_file.log_publish_denied(&client_id, topic_name);
+
+            // MQTT 3.1.1 has no negative acknowledgement, so the message is dropped silently
+            if &QoS::AtMost != publish_packet.qos() {
+                if let Some(client) = self.clients.write()?.get_mut(&client_id) {
+                    self.puback(publish_packet.package_identifier(), client);
+                }
+            }
+            return Ok(());
+        }
 
-        for client in self.clients.read()?.values() {
-            if client.is_subscribed(topic_name) {
-                clients.push(client.id());
+        if let Some(max_payload_bytes) = self.max_payload_bytes {
+            let payload_size = publish_packet.message().len();
+            if payload_size > max_payload_bytes {
+                self.reject_oversized_publish(publish_packet, &client_id, payload_size)?;
+                return Ok(());
             }
         }
 
-        if clients.is_empty() {
-            let message = format!("No clients subscribed to topic: {}", topic_name);
-            self.log_file.error(message.as_str());
-            return Ok(());
+        if topic_name.server_reserved() {
+            return self.handle_server_reserved_topic(publish_packet, client_id);
         }
 
-        self.log_file
-            .log_successful_publish(&client_id, publish_packet);
+        if let Some(bridge) = &self.bridge {
+            bridge.forward(publish_packet);
+        }
 
-        for client_id in clients {
-            if let Some(client) = self.clients.read()?.get(&client_id) {
-                if self.active_connections.contains(&client_id) {
-                    client.send_message(publish_packet.clone(), &self.log_file, &self.key);
-                } else {
-                    self.offline_messages
-                        .entry(client_id.clone())
-                        .or_default()
-                        .push_back(publish_packet.clone());
-                }
-            }
+        // A message nobody is subscribed to is still acknowledged, otherwise the client
+        // would resend it forever
+        if self.route_message(publish_packet, false, trace)? {
+            self.log_file
+                .log_successful_publish(&client_id, publish_packet);
         }
 
         let mut clients = self.clients.write()?;
@@ -306,97 +669,573 @@ impl TaskHandler {
             }
         }
 
-        let clients_retained_messages = self.offline_messages.get(&client_id);
-        let client = match clients.get_mut(&client_id) {
-            Some(client) => client,
-            None => {
-                self.log_file.log_client_does_not_exist(&client_id);
-                return Ok(());
-            }
-        };
+        Ok(())
+    }
 
-        if let Some(clients_retained_messages) = clients_retained_messages {
-            self.handle_retained_messages(client, clients_retained_messages);
-            match self.offline_messages.get_mut(&client_id) {
-                Some(queue) => queue.clear(),
-                None => {
-                    self.log_file.error("Error clearing offline messages");
-                }
+    /// Updates the stats and logs of a message the event loop already sent to the subscribers
+    fn account_direct_publish(
+        &mut self,
+        publish_packet: &Publish,
+        client_id: &[u8],
+        subscribers: Vec<Vec<u8>>,
+        trace: Trace,
+        elapsed: Duration,
+    ) -> ServerResult<()> {
+        if !subscribers.is_empty() {
+            self.latencies.record(Stage::Delivery, elapsed);
+            self.log_file
+                .log_trace_hop(&trace, Stage::Delivery, None, elapsed);
+        }
+
+        let payload_bytes = publish_packet.message().len();
+        self.topic_stats
+            .record(publish_packet.topic(), payload_bytes);
+
+        let clients = self.clients.read()?;
+        if let Some(client) = clients.get(client_id) {
+            client.stats().record_in(payload_bytes);
+        }
+        for subscriber in subscribers {
+            if let Some(client) = clients.get(&subscriber) {
+                client.stats().record_out(payload_bytes);
+                self.log_file.log_sent_message(
+                    String::from_utf8_lossy(publish_packet.message()).to_string(),
+                    String::from_utf8_lossy(&subscriber).to_string(),
+                );
             }
         }
 
+        self.log_file
+            .log_successful_publish(client_id, publish_packet);
+        Ok(())
+    }
+
+    /// Publishes locally a message received from the upstream broker of the bridge.
+    /// It is not forwarded back, so it does not loop between the brokers
+    fn publish_from_bridge(&mut self, publish_packet: &Publish) -> ServerResult<()> {
+        if let Err(err) = publish_packet.topic().validate() {
+            self.log_file.warn(&format!(
+                "Message from the bridge to invalid topic \"{}\" dropped: {}",
+                publish_packet.topic(),
+                err
+            ));
+            return Ok(());
+        }
+        if self.route_message(publish_packet, false, Trace::start())? {
+            self.log_file.debug(&format!(
+                "Message from the bridge published to topic: {}",
+                publish_packet.topic()
+            ));
+        }
         Ok(())
     }
 
-    /// Handle a server reserved topic (e.g. $client-register)
-    pub fn handle_server_reserved_topic(&self, publish_packet: &Publish, client_id: Vec<u8>) {
+    /// Publishes locally a message received from another server of the cluster.
+    /// It is only sent to the local clients, as the server that received it sends it
+    /// to the rest of the cluster
+    fn publish_from_cluster(&mut self, publish_packet: &Publish) -> ServerResult<()> {
+        if let Err(err) = publish_packet.topic().validate() {
+            self.log_file.warn(&format!(
+                "Message from the cluster to invalid topic \"{}\" dropped: {}",
+                publish_packet.topic(),
+                err
+            ));
+            return Ok(());
+        }
+        if self.route_message(publish_packet, true, Trace::start())? {
+            self.log_file.debug(&format!(
+                "Message from the cluster published to topic: {}",
+                publish_packet.topic()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Retains the message if needed and sends it to the subscribers of its topic,
+    /// queueing it for the disconnected ones. Messages of the cluster are not sent to the links
+    /// of other servers. Returns false if there are no subscribers.
+    /// The trace of the message goes with it to the queue of each subscriber
+    fn route_message(
+        &mut self,
+        publish_packet: &Publish,
+        from_cluster: bool,
+        trace: Trace,
+    ) -> ServerResult<bool> {
+        let started_at = Instant::now();
         let topic_name = publish_packet.topic();
-        let levels = topic_name.levels();
+        self.topic_stats
+            .record(topic_name, publish_packet.message().len());
+
+        if publish_packet.retain() {
+            self.persist(StateChange::Retained(publish_packet.clone()));
+            self.retained_messages
+                .entry(topic_name.clone())
+                .or_default()
+                .push_back((Instant::now(), publish_packet.clone()));
+        }
+
+        let mut clients = self.subscriptions.subscribers(topic_name);
+        if from_cluster {
+            clients.retain(|client_id| !cluster::is_cluster_link(client_id));
+        }
+
+        if clients.is_empty() {
+            let message = format!("No clients subscribed to topic: {}", topic_name);
+            self.log_file.warn(message.as_str());
+            return Ok(false);
+        }
+
+        let mut changes = vec![];
+        let subscribers = self.clients.read()?;
+        for client_id in clients {
+            if let Some(client) = subscribers.get(&client_id) {
+                let delivery = self.outbound_queues.deliver(
+                    &client_id,
+                    publish_packet.clone(),
+                    Some(trace),
+                    self.active_connections.contains(&client_id),
+                    |message, message_trace| {
+                        client.send_message(message, &self.log_file, &self.key);
+                        record_delivery(&self.latencies, &self.log_file, message_trace, &client_id);
+                    },
+                );
+                match delivery {
+                    Delivery::Queued => {
+                        changes.push(StateChange::Queued(client_id, publish_packet.clone()))
+                    }
+                    Delivery::Sent { flushed } if flushed > 0 => {
+                        changes.push(StateChange::QueueCleared(client_id))
+                    }
+                    Delivery::Sent { .. } => {}
+                }
+            }
+        }
+        drop(subscribers);
+        self.record_hop(&trace, Stage::Routing, started_at.elapsed());
+
+        for change in changes {
+            self.persist(change);
+        }
+
+        Ok(true)
+    }
+
+    /// Handle a server reserved topic ($client-register or an $admin command)
+    pub fn handle_server_reserved_topic(
+        &mut self,
+        publish_packet: &Publish,
+        client_id: Vec<u8>,
+    ) -> ServerResult<()> {
+        let levels = publish_packet.topic().levels();
 
         if client_id != ADMIN_ID {
             self.log_file.error("Client is not admin");
+            return Ok(());
+        }
+
+        match levels.as_slice() {
+            [level] if level == CLIENT_REGISTER => {
+                self.register_client(publish_packet.message());
+                Ok(())
+            }
+            [level, command @ ..] if level == ADMIN_TOPIC => self.handle_admin_command(command),
+            _ => {
+                self.log_file
+                    .error("Invalid topic for server reserved topic");
+                Ok(())
+            }
+        }
+    }
+
+    /// Registers the client described in a $client-register message
+    fn register_client(&self, message: &[u8]) {
+        //  split username, password and the optional topic rules by SEPARATOR
+        let split = message.split(|&c| c == SEPARATOR).collect::<Vec<&[u8]>>();
+
+        if !(3..=5).contains(&split.len()) {
+            self.log_file
+                .error("Invalid message for client registration");
             return;
         }
 
-        if levels.len() == 1 && levels[0] == CLIENT_REGISTER {
-            let message = publish_packet.message();
-            //  split username and password by SEPARATOR
-            let split = message.split(|&c| c == SEPARATOR).collect::<Vec<&[u8]>>();
+        let client_id = split[0].to_vec();
+        let username = split[1].to_vec();
+        let password = split[2].to_vec();
+        let publish_rules = split.get(3).map(|rules| String::from_utf8_lossy(rules));
+        let subscribe_rules = split.get(4).map(|rules| String::from_utf8_lossy(rules));
+        let acl = Acl::from_fields(publish_rules.as_deref(), subscribe_rules.as_deref());
 
-            if split.len() != 3 {
+        let client_manager = self.client_manager.write().unwrap();
+
+        match client_manager.is_registered(&client_id) {
+            Ok(true) => {
+                self.log_file.info("Client already registered");
+            }
+            Ok(false) => {
+                match client_manager.register_client(client_id.clone(), username, password, acl) {
+                    Ok(_) => self.log_file.log_client_registrated(&client_id),
+                    Err(e) => self.log_file.error(e.to_string().as_str()),
+                }
+            }
+            Err(e) => {
+                self.log_file.error(e.to_string().as_str());
+            }
+        }
+    }
+
+    /// Runs a command published by the admin under $admin:
+    /// `kick/<client-id>`, `ban/<client-id>`, `ban/ip/<address>`, `clients` or `retained/clear/<topic>`
+    fn handle_admin_command(&mut self, command: &[Vec<u8>]) -> ServerResult<()> {
+        match command {
+            [level, client_id] if level == ADMIN_KICK => self.kick_client(client_id),
+            [level, client_id] if level == ADMIN_BAN => self.ban_client(client_id),
+            [ban, ip, address] if ban == ADMIN_BAN && ip == ADMIN_BAN_IP => {
+                self.ban_address(address)
+            }
+            [level] if level == ADMIN_CLIENTS => self.publish_client_list(),
+            [retained, clear, topic @ ..]
+                if retained == ADMIN_RETAINED && clear == ADMIN_CLEAR && !topic.is_empty() =>
+            {
+                self.clear_retained_messages(TopicName::new(topic.to_vec(), false));
+                Ok(())
+            }
+            _ => {
+                self.log_file.error("Invalid admin command");
+                Ok(())
+            }
+        }
+    }
+
+    /// Closes the connection of a client. The event loop will notice it and disconnect it
+    fn kick_client(&self, client_id: &[u8]) -> ServerResult<()> {
+        let client_id_str = String::from_utf8_lossy(client_id);
+
+        match self.clients.read()?.get(client_id) {
+            Some(client) if self.active_connections.contains(client_id) => {
+                client.shutdown();
                 self.log_file
-                    .error("Invalid message for client registration");
+                    .info(&format!("Client {} kicked by the admin", client_id_str));
+            }
+            _ => self
+                .log_file
+                .error(&format!("Client {} is not connected", client_id_str)),
+        }
+
+        Ok(())
+    }
+
+    /// Answers a query of the admin API with the state held by the task handler
+    fn answer_admin_query(
+        &self,
+        query: AdminQuery,
+        response_sender: mpsc::Sender<AdminResponse>,
+    ) -> ServerResult<()> {
+        let response = match query {
+            AdminQuery::Clients => {
+                let mut clients: Vec<ClientInfo> = self
+                    .clients
+                    .read()?
+                    .iter()
+                    .map(|(client_id, client)| self.client_info(client_id, client))
+                    .collect();
+                clients.sort_by(|a, b| a.id.cmp(&b.id));
+                AdminResponse::Clients(clients)
+            }
+            AdminQuery::Client(client_id) => AdminResponse::Client(
+                self.clients
+                    .read()?
+                    .get(&client_id)
+                    .map(|client| self.client_info(&client_id, client)),
+            ),
+            AdminQuery::Retained => {
+                let mut topics: Vec<(String, usize)> = self
+                    .retained_messages
+                    .iter()
+                    .filter(|(_, messages)| !messages.is_empty())
+                    .map(|(topic_name, messages)| (topic_name.to_string(), messages.len()))
+                    .collect();
+                topics.sort();
+                AdminResponse::Retained(topics)
+            }
+            AdminQuery::Topics => AdminResponse::Topics(self.topic_stats.all()),
+            AdminQuery::Queues => {
+                let mut queues: Vec<(String, usize)> = self
+                    .outbound_queues
+                    .iter()
+                    .map(|(client_id, queue)| {
+                        (
+                            String::from_utf8_lossy(client_id).to_string(),
+                            queue.count(),
+                        )
+                    })
+                    .collect();
+                queues.sort();
+                AdminResponse::Queues(queues)
+            }
+            AdminQuery::DisconnectClient(client_id) => {
+                let connected = self.active_connections.contains(&client_id);
+                if connected {
+                    self.kick_client(&client_id)?;
+                }
+                AdminResponse::Disconnected(connected)
+            }
+        };
+
+        let _ = response_sender.send(response);
+        Ok(())
+    }
+
+    /// Describes a client for the admin API
+    fn client_info(&self, client_id: &[u8], client: &Client) -> ClientInfo {
+        ClientInfo {
+            id: String::from_utf8_lossy(client_id).to_string(),
+            connected: self.active_connections.contains(client_id),
+            subscriptions: client
+                .subscriptions
+                .iter()
+                .map(|topic_filter| topic_filter.to_string())
+                .collect(),
+            queued_messages: self.outbound_queues.len(client_id),
+            inflight_messages: self.outbound_queues.inflight(client_id),
+            outbound_queue: client.stream.as_ref().map_or(0, ClientWriter::queue_depth),
+            write_latency_us: client
+                .stream
+                .as_ref()
+                .map_or(0, |stream| stream.write_latency().as_micros() as u64),
+        }
+    }
+
+    /// Bans a client id and kicks the client if it is connected
+    fn ban_client(&self, client_id: &[u8]) -> ServerResult<()> {
+        self.client_manager.write()?.ban_client(client_id.to_vec());
+        self.log_file.info(&format!(
+            "Client {} banned by the admin",
+            String::from_utf8_lossy(client_id)
+        ));
+
+        if self.active_connections.contains(client_id) {
+            self.kick_client(client_id)?;
+        }
+        Ok(())
+    }
+
+    /// Bans an IP address. Clients already connected from it are not kicked
+    fn ban_address(&self, address: &[u8]) -> ServerResult<()> {
+        let address_str = String::from_utf8_lossy(address);
+        match address_str.parse() {
+            Ok(address) => {
+                self.client_manager.write()?.ban_address(address);
+                self.log_file
+                    .info(&format!("Address {} banned by the admin", address_str));
+            }
+            Err(_) => self
+                .log_file
+                .error(&format!("Invalid address to ban: {}", address_str)),
+        }
+        Ok(())
+    }
+
+    /// Publishes the known clients and whether they are connected in the $SYS tree
+    fn publish_client_list(&mut self) -> ServerResult<()> {
+        let mut client_list: Vec<String> = self
+            .clients
+            .read()?
+            .keys()
+            .map(|client_id| {
+                let state = if self.active_connections.contains(client_id) {
+                    "connected"
+                } else {
+                    "disconnected"
+                };
+                format!("{} {}", String::from_utf8_lossy(client_id), state)
+            })
+            .collect();
+        client_list.sort();
+
+        self.publish_system_message(SYS_CLIENT_LIST, client_list.join("\n"))
+    }
+
+    /// Remembers when a client sent a packet, for its stats and to enforce its keep alive in strict mode
+    fn record_activity(&mut self, task: &Task) {
+        let client_id = match task {
+            Task::SubscribeClient(_, client_id)
+            | Task::UnsubscribeClient(_, client_id)
+            | Task::Publish(_, client_id, _)
+            | Task::PublishedDirectly(_, client_id, ..)
+            | Task::RespondPing(client_id)
+            | Task::AcknowledgeMessage(_, client_id) => client_id.clone(),
+            Task::ConnectClient(client) => client.id(),
+            Task::DisconnectClient(client_id, _) => {
+                self.last_activity.remove(client_id);
                 return;
             }
+            _ => return,
+        };
 
-            let client_id = split[0].to_vec();
-            let username = split[1].to_vec();
-            let password = split[2].to_vec();
+        if let Ok(clients) = self.clients.read() {
+            if let Some(client) = clients.get(&client_id) {
+                client.stats().record_activity();
+            }
+        }
+        if self.strict_mode {
+            self.last_activity.insert(client_id, Instant::now());
+        }
+    }
 
-            let client_manager = self.client_manager.write().unwrap();
+    /// Closes the connection of the clients that sent no packet in one and a half times
+    /// their keep alive, as the specification requires. The event loop will notice it and disconnect them
+    fn disconnect_silent_clients(&mut self) -> ServerResult<()> {
+        let clients = self.clients.read()?;
+        let silent_clients: Vec<Vec<u8>> = self
+            .last_activity
+            .iter()
+            .filter(|(client_id, last_activity)| {
+                clients.get(*client_id).is_some_and(|client| {
+                    let keep_alive = Duration::from_secs(client.keep_alive() as u64);
+                    !keep_alive.is_zero() && last_activity.elapsed() > keep_alive * 3 / 2
+                })
+            })
+            .map(|(client_id, _)| client_id.clone())
+            .collect();
+
+        for client_id in silent_clients {
+            self.last_activity.remove(&client_id);
+            if let Some(client) = clients.get(&client_id) {
+                self.log_file.info(&format!(
+                    "Disconnecting client {} for exceeding its keep alive of {} seconds",
+                    String::from_utf8_lossy(&client_id),
+                    client.keep_alive()
+                ));
+                client.shutdown();
+            }
+        }
 
-            match client_manager.authenticate_client(
-                client_id.clone(),
-                username.clone(),
-                password.clone(),
-            ) {
-                Ok(true) => {
-                    self.log_file.info("Client already registered");
-                }
-                Ok(false) => {
-                    self.log_file.log_client_registrated(&client_id.clone());
-                    let _ = client_manager.register_client(client_id, username, password);
-                }
-                Err(e) => {
-                    self.log_file.error(e.to_string().as_str());
+        Ok(())
+    }
+
+    /// Removes the retained messages of a topic
+    fn clear_retained_messages(&mut self, topic_name: TopicName) {
+        self.persist(StateChange::RetainedCleared(topic_name.clone()));
+        match self.retained_messages.remove(&topic_name) {
+            Some(messages) => self.log_file.info(&format!(
+                "Cleared {} retained messages of topic {}",
+                messages.len(),
+                topic_name
+            )),
+            None => self
+                .log_file
+                .error(&format!("No retained messages in topic {}", topic_name)),
+        }
+    }
+
+    /// Removes the retained messages older than the TTL, if there is one.
+    /// The messages of the server reserved topics don't expire
+    fn expire_retained_messages(&mut self) {
+        let retained_ttl = match self.retained_ttl {
+            Some(retained_ttl) => retained_ttl,
+            None => return,
+        };
+
+        let mut expired_topics = vec![];
+        for (topic_name, messages) in self.retained_messages.iter_mut() {
+            if topic_name.server_reserved() {
+                continue;
+            }
+
+            let retained = messages.len();
+            // Messages are retained in order, so the oldest ones are at the front
+            while messages
+                .front()
+                .is_some_and(|(retained_at, _)| retained_at.elapsed() >= retained_ttl)
+            {
+                messages.pop_front();
+            }
+            if messages.len() != retained {
+                expired_topics.push((topic_name.clone(), retained - messages.len()));
+            }
+        }
+
+        for (topic_name, expired) in expired_topics {
+            self.log_file.debug(&format!(
+                "{} retained messages of topic {} expired",
+                expired, topic_name
+            ));
+
+            // The backend has no way to remove single messages, so the remaining ones are stored again
+            self.persist(StateChange::RetainedCleared(topic_name.clone()));
+            let remaining: Vec<Publish> = match self.retained_messages.get(&topic_name) {
+                Some(messages) => messages
+                    .iter()
+                    .map(|(_, message)| message.clone())
+                    .collect(),
+                None => continue,
+            };
+            if remaining.is_empty() {
+                self.retained_messages.remove(&topic_name);
+            }
+            for message in remaining {
+                self.persist(StateChange::Retained(message));
+            }
+        }
+    }
+
+    /// Sends a client the retained messages of the topics that match the filter
+    fn send_retained_messages(&self, client: &Client, topic_filter: &TopicFilter) {
+        for (topic_name, retained_messages) in &self.retained_messages {
+            if topic_filter.match_topic_name(topic_name.clone()) {
+                for (_, message) in retained_messages {
+                    client.send_message(message.clone(), &self.log_file, &self.key);
                 }
             }
-        } else {
-            self.log_file
-                .error("Invalid topic for server reserved topic");
         }
     }
 
-    /// Handle retained messages for a client
-    pub fn handle_retained_messages(
-        &self,
-        client: &mut Client,
-        retained_messages: &VecDeque<Publish>,
-    ) {
-        for message in retained_messages {
-            client.send_message(message.clone(), &self.log_file, &self.key);
+    /// Resumes the session of a client that connected again. If its subscriptions were restored
+    /// from a backup, the retained messages of their topics are sent again, except for the topics
+    /// with queued messages as those are newer. The queued messages are delivered afterwards
+    fn resume_session(&self, client: &mut Client) {
+        if client.resume() {
+            self.log_file.info(&format!(
+                "Resuming restored session of client {}",
+                String::from_utf8_lossy(&client.id)
+            ));
+
+            let queued_topics: HashSet<&TopicName> = self
+                .outbound_queues
+                .queued(&client.id)
+                .map(|message| message.topic())
+                .collect();
+
+            for (topic_name, retained_messages) in &self.retained_messages {
+                let subscribed = client
+                    .subscriptions
+                    .iter()
+                    .any(|topic_filter| topic_filter.match_topic_name(topic_name.clone()));
+                if subscribed && !queued_topics.contains(topic_name) {
+                    for (_, message) in retained_messages {
+                        client.send_message(message.clone(), &self.log_file, &self.key);
+                    }
+                }
+            }
         }
     }
 
     /// Handle a new client connection
     pub fn handle_new_client_connection(&mut self, client: Client) -> ServerResult<()> {
-        let connack_packet = Connack::new(true, ConnectReturnCode::ConnectionAccepted);
-        let connack_packet_vec = connack_packet.to_bytes(&self.key);
-        let connack_packet_bytes = connack_packet_vec.as_slice();
-
         let client_id = client.id();
         let mut clients = self.clients.write()?;
 
+        // The session is resumed only if the client asked to keep it and it has subscriptions
+        // or queued messages, otherwise the client has to subscribe again
+        let session_present = !client.clean_session()
+            && (clients
+                .get(&client_id)
+                .is_some_and(|old_client| !old_client.subscriptions.is_empty())
+                || self.outbound_queues.len(&client_id) > 0);
+        let connack_packet = Connack::new(session_present, ConnectReturnCode::ConnectionAccepted);
+        let connack_packet_vec = connack_packet.to_bytes(&self.key);
+        let connack_packet_bytes = connack_packet_vec.as_slice();
+
         if clients.contains_key(&client_id) {
             let message = format!("Client {} reconnected", String::from_utf8_lossy(&client_id));
             self.log_file.info(message.as_str());
@@ -407,12 +1246,12 @@ impl TaskHandler {
                     return Ok(());
                 }
             };
-            old_client.stream = client.stream;
+            old_client.reconnect(client);
         } else {
             clients.entry(client_id.clone()).or_insert(client);
         }
 
-        let client = match clients.get(&client_id) {
+        let client = match clients.get_mut(&client_id) {
             Some(client) => client,
             None => {
                 self.log_file.log_client_does_not_exist(&client_id);
@@ -438,20 +1277,75 @@ impl TaskHandler {
                 );
                 self.log_file.info(message.as_str());
                 self.log_file.log_info_sent_packet("Connack", &client_id);
+
+                self.resume_session(client);
+
+                // In strict mode the messages in flight of a resumed session are sent again before
+                // the queued ones, as the client may not have received them. Otherwise they were
+                // lost with the previous connection
+                if self.strict_mode && !client.clean_session() {
+                    self.outbound_queues.resend_inflight(&client_id, |message| {
+                        client.send_message(message, &self.log_file, &self.key);
+                    });
+                } else {
+                    self.outbound_queues.clear_inflight(&client_id);
+                }
+                let queued = self.outbound_queues.len(&client_id);
+                self.outbound_queues.flush(&client_id, |message, trace| {
+                    client.send_message(message, &self.log_file, &self.key);
+                    record_delivery(&self.latencies, &self.log_file, trace, &client_id);
+                });
+                if queued > 0 && self.outbound_queues.len(&client_id) == 0 {
+                    self.persist(StateChange::QueueCleared(client_id.clone()));
+                }
             }
             Err(_) => self
                 .log_file
                 .log_error_sending_packet("Connack", &client_id),
         };
+        drop(clients);
+
+        self.publish_connected_clients()
+    }
+
+    /// Releases the in-flight slot of the message acknowledged by a client and sends it
+    /// the queued messages that fit in its window
+    fn acknowledge_message(
+        &mut self,
+        packet_identifier: u16,
+        client_id: Vec<u8>,
+    ) -> ServerResult<()> {
+        let clients = self.clients.read()?;
+        let client = match clients.get(&client_id) {
+            Some(client) => client,
+            None => {
+                self.log_file.log_client_does_not_exist(&client_id);
+                return Ok(());
+            }
+        };
+
+        let emptied =
+            self.outbound_queues
+                .acknowledge(&client_id, packet_identifier, |message, trace| {
+                    client.send_message(message, &self.log_file, &self.key);
+                    record_delivery(&self.latencies, &self.log_file, trace, &client_id);
+                });
+        drop(clients);
+
+        if emptied {
+            self.persist(StateChange::QueueCleared(client_id));
+        }
         Ok(())
     }
 
-    /// Send a suback packet to a client
-    pub fn suback(&self, package_identifier: u16, client: &mut Client) {
-        let suback_packet = Suback::new(
-            package_identifier,
-            vec![SubackReturnCode::SuccessMaximumQoS0],
-        );
+    /// Send a suback packet to a client with a return code for each requested topic
+    pub fn suback(
+        &self,
+        package_identifier: u16,
+        return_codes: Vec<SubackReturnCode>,
+        client: &mut Client,
+    ) {
+        let suback_packet = Suback::new(package_identifier, return_codes);
         let suback_packet_vec = suback_packet.to_bytes(&self.key);
         let suback_packet_bytes = suback_packet_vec.as_slice();
 
@@ -556,199 +1450,402 @@ impl TaskHandler {
     }
 
     /// Handle a client disconnection
-    pub fn handle_client_disconnected(&mut self, client_id: Vec<u8>) -> ServerResult<()> {
+    /// The will of the client is published unless it sent a DISCONNECT packet,
+    /// and its session is discarded if it asked for a clean session
+    pub fn handle_client_disconnected(
+        &mut self,
+        client_id: Vec<u8>,
+        cause: DisconnectCause,
+    ) -> ServerResult<()> {
         self.active_connections.remove(&client_id);
+
+        let (slow_consumer, clean_session, summary) = match self.clients.read()?.get(&client_id) {
+            Some(client) => (
+                client.is_slow_consumer(),
+                client.clean_session(),
+                session_summary(client, &cause),
+            ),
+            None => {
+                self.log_file.log_client_does_not_exist(&client_id);
+                return Ok(());
+            }
+        };
+
+        if slow_consumer {
+            self.log_file.warn(&format!(
+                "Client {} was disconnected for not reading its messages",
+                String::from_utf8_lossy(&client_id)
+            ));
+        }
+        self.log_file.log_session_summary(&client_id, &summary);
+
+        if cause != DisconnectCause::Requested {
+            self.publish_will(&client_id)?;
+        }
+
         self.client_manager
             .write()?
             .disconnect_client(client_id.clone())?;
+
+        if clean_session {
+            self.discard_session(&client_id)?;
+        }
+        self.publish_connected_clients()
+    }
+
+    /// Removes the subscriptions and the queued messages of a client whose session
+    /// lasts only as long as its connection
+    fn discard_session(&mut self, client_id: &[u8]) -> ServerResult<()> {
+        let client = match self.clients.write()?.remove(client_id) {
+            Some(client) => client,
+            None => return Ok(()),
+        };
+
+        for topic_filter in &client.subscriptions {
+            self.subscriptions.unsubscribe(topic_filter, client_id);
+            self.persist(StateChange::Unsubscribed(
+                client_id.to_vec(),
+                topic_filter.clone(),
+            ));
+        }
+
+        self.outbound_queues.clear_inflight(client_id);
+        if !self.outbound_queues.take(client_id).is_empty() {
+            self.persist(StateChange::QueueCleared(client_id.to_vec()));
+        }
+
+        // The stats of the client would stay retained forever
+        let stats_root: Vec<Vec<u8>> = SYS_CLIENTS
+            .split('/')
+            .map(|level| level.as_bytes().to_vec())
+            .chain([String::from_utf8_lossy(client_id).as_bytes().to_vec()])
+            .collect();
+        self.retained_messages
+            .retain(|topic_name, _| !topic_name.levels().starts_with(&stats_root));
+
+        self.log_file.info(&format!(
+            "Discarded the session of client {}",
+            String::from_utf8_lossy(client_id)
+        ));
         Ok(())
     }
 
-    /// Serialize the task handler data to a string
-    fn serialize(&self) -> String {
-        let mut serialized_data = String::new();
+    /// Publishes the will of a client that lost its connection, if it has one
+    /// and is allowed to publish in its topic
+    fn publish_will(&mut self, client_id: &[u8]) -> ServerResult<()> {
+        let will = match self.clients.read()?.get(client_id).and_then(Client::will) {
+            Some(will) => will.clone(),
+            None => return Ok(()),
+        };
 
-        // Serialize offline_messages
-        for (client, queue) in &self.offline_messages {
-            for publish in queue {
-                serialized_data.push_str(&format!(
-                    "{};{};{}\n",
-                    OFFLINE_MESSAGES_TAG,
-                    bytes_to_hex(client),
-                    bytes_to_hex(&publish.to_bytes(&self.key))
+        let topic_name = will.topic();
+        if topic_name.server_reserved()
+            || !self
+                .client_manager
+                .read()?
+                .can_publish(client_id, topic_name)?
+        {
+            self.log_file.log_publish_denied(client_id, topic_name);
+            return Ok(());
+        }
+
+        self.log_file.info(&format!(
+            "Publishing the will of client {} in topic {}",
+            String::from_utf8_lossy(client_id),
+            topic_name
+        ));
+        if let Some(bridge) = &self.bridge {
+            bridge.forward(&will);
+        }
+        self.route_message(&will, false, Trace::start())?;
+        Ok(())
+    }
+
+    /// Drops a publish to an empty topic or one with wildcards, which no subscription would match.
+    /// The publisher still receives the acknowledgement its QoS requires, unless it is disconnected
+    fn reject_invalid_topic(
+        &self,
+        publish_packet: &Publish,
+        client_id: &[u8],
+        reason: &str,
+    ) -> ServerResult<()> {
+        self.log_file
+            .log_invalid_topic(client_id, publish_packet.topic(), reason);
+
+        if let Some(client) = self.clients.write()?.get_mut(client_id) {
+            if self.disconnect_on_invalid_topic {
+                self.log_file.info(&format!(
+                    "Disconnecting client {} for publishing to an invalid topic",
+                    String::from_utf8_lossy(client_id)
                 ));
+                client.shutdown();
+            } else if &QoS::AtMost != publish_packet.qos() {
+                self.puback(publish_packet.package_identifier(), client);
             }
         }
 
-        // Serialize retained_messages
-        for (topic_name, messages) in &self.retained_messages {
-            for message in messages {
-                serialized_data.push_str(&format!(
-                    "{};{};{}\n",
-                    RETAINED_MESSAGES_TAG,
-                    bytes_to_hex(&topic_name.to_bytes()),
-                    bytes_to_hex(&message.to_bytes(&self.key))
+        Ok(())
+    }
+
+    /// Drops a publish whose payload exceeds the configured limit. The publisher still receives
+    /// the acknowledgement its QoS requires, unless it is disconnected because of the message
+    fn reject_oversized_publish(
+        &self,
+        publish_packet: &Publish,
+        client_id: &[u8],
+        payload_size: usize,
+    ) -> ServerResult<()> {
+        self.log_file
+            .log_payload_too_large(client_id, publish_packet.topic(), payload_size);
+
+        if let Some(client) = self.clients.write()?.get_mut(client_id) {
+            if self.disconnect_on_oversized_payload {
+                self.log_file.info(&format!(
+                    "Disconnecting client {} for exceeding the payload limit",
+                    String::from_utf8_lossy(client_id)
                 ));
+                client.shutdown();
+            } else if &QoS::AtMost != publish_packet.qos() {
+                self.puback(publish_packet.package_identifier(), client);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Publishes the number of connected clients in the $SYS tree
+    fn publish_connected_clients(&mut self) -> ServerResult<()> {
+        let connected_clients = self.active_connections.len().to_string();
+        self.publish_system_message(SYS_CONNECTED_CLIENTS, connected_clients)
+    }
+
+    /// Publishes the number of tasks waiting to be handled in the $SYS tree
+    fn publish_queue_depth(&mut self, queue_depth: usize) -> ServerResult<()> {
+        self.publish_system_message(SYS_QUEUED_TASKS, queue_depth.to_string())
+    }
+
+    /// Publishes in the $SYS tree the message and byte counters of the topics with new traffic
+    fn publish_topic_stats(&mut self) -> ServerResult<()> {
+        for (topic_name, stats) in self.topic_stats.take_changed() {
+            let topic = format!("{}/{}", SYS_TOPICS, topic_name);
+            self.publish_system_message(
+                &format!("{}/messages", topic),
+                stats.messages.to_string(),
+            )?;
+            self.publish_system_message(&format!("{}/bytes", topic), stats.bytes.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Publishes in the $SYS tree the traffic, subscriptions, connection time and last activity
+    /// of the connected clients whose stats changed
+    fn publish_client_stats(&mut self) -> ServerResult<()> {
+        let mut changed = vec![];
+        for (client_id, client) in self.clients.read()?.iter() {
+            if !self.active_connections.contains(client_id) || !client.stats().take_changed() {
+                continue;
+            }
+
+            let stats = client.stats();
+            changed.push((
+                String::from_utf8_lossy(client_id).to_string(),
+                [
+                    ("messages_in", stats.messages_in()),
+                    ("messages_out", stats.messages_out()),
+                    ("bytes_in", stats.bytes_in()),
+                    ("bytes_out", stats.bytes_out()),
+                    ("subscriptions", client.subscriptions.len() as u64),
+                    ("connected_at", unix_secs(client.connected_at())),
+                    ("last_seen", stats.last_seen()),
+                ],
+            ));
+        }
+
+        for (client_id, metrics) in changed {
+            for (metric, value) in metrics {
+                self.publish_system_message(
+                    &format!("{}/{}/{}", SYS_CLIENTS, client_id, metric),
+                    value.to_string(),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Publishes in the $SYS tree the latency histograms of the stages with new messages
+    fn publish_latencies(&mut self) -> ServerResult<()> {
+        let mut messages = vec![];
+        for (stage, histogram) in self.latencies.take_changed() {
+            let topic = format!("{}/{}", SYS_LATENCY, stage);
+            for (metric, value) in [
+                ("count", histogram.count()),
+                ("mean_us", histogram.mean_micros()),
+                ("p50_us", histogram.percentile_micros(50)),
+                ("p99_us", histogram.percentile_micros(99)),
+                ("max_us", histogram.max_micros()),
+            ] {
+                messages.push((format!("{}/{}", topic, metric), value.to_string()));
+            }
+            messages.push((format!("{}/histogram", topic), histogram.buckets()));
+        }
+
+        for (topic, message) in messages {
+            self.publish_system_message(&topic, message)?;
+        }
+        Ok(())
+    }
+
+    /// Publishes a retained message generated by the server in a $SYS topic.
+    /// Only the latest value of each $SYS topic is kept
+    fn publish_system_message(&mut self, topic: &str, message: String) -> ServerResult<()> {
+        let levels = topic
+            .split('/')
+            .map(|level| level.as_bytes().to_vec())
+            .collect();
+        let topic_name = TopicName::new(levels, true);
+        let publish_packet = Publish::new(
+            false,
+            QoS::AtMost,
+            true,
+            topic_name.clone(),
+            None,
+            message.into_bytes(),
+        );
+
+        self.retained_messages.insert(
+            topic_name.clone(),
+            VecDeque::from([(Instant::now(), publish_packet.clone())]),
+        );
+
+        let clients = self.clients.read()?;
+        for client_id in self.subscriptions.subscribers(&topic_name) {
+            if let Some(client) = clients.get(&client_id) {
+                if self.active_connections.contains(&client_id) {
+                    client.send_message(publish_packet.clone(), &self.log_file, &self.key);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the state as the changes that rebuild it. $SYS values are generated again by the server
+    fn snapshot(&self) -> Vec<StateChange> {
+        let mut state = vec![];
+
+        for (client, queue) in self.outbound_queues.iter() {
+            for publish in queue {
+                state.push(StateChange::Queued(client.clone(), publish.clone()));
+            }
+        }
+
+        for (topic_name, messages) in &self.retained_messages {
+            if topic_name.server_reserved() {
+                continue;
+            }
+
+            for (_, message) in messages {
+                state.push(StateChange::Retained(message.clone()));
             }
         }
 
-        // Serialize clients
         let clients_read = match self.clients.read() {
             Ok(clients) => clients,
             Err(_) => {
                 self.log_file
                     .error("Error reading clients for serialization");
-                return serialized_data;
+                return state;
             }
         };
 
         for (id, client) in clients_read.iter() {
             for sub in &client.subscriptions {
-                serialized_data.push_str(&format!(
-                    "{};{};{}\n",
-                    CLIENTS_TAG,
-                    bytes_to_hex(id),
-                    bytes_to_hex(&sub.to_bytes())
-                ));
+                state.push(StateChange::Subscribed(id.clone(), sub.clone()));
             }
         }
 
-        serialized_data
+        state
     }
 
-    /// Builds a TaskHandler from a backup file string
-    fn deserialize(
-        serialized_data: &str,
-        client_manager: Arc<RwLock<ClientManager>>,
-        log_file: Arc<Logger>,
-        config: &Config,
-        receiver_channel: mpsc::Receiver<Task>,
-    ) -> TaskHandler {
-        let key = *config.get_key();
-        let mut offline_messages = HashMap::new();
-        let mut retained_messages = HashMap::new();
-        let mut clients = HashMap::new();
-
-        for line in serialized_data.lines() {
-            let parts: Vec<&str> = line.split(';').collect();
-            if parts.len() != 3 {
-                continue;
-            }
-
-            let record_type = parts[0];
-            let entry_key = match hex_to_bytes(parts[1]) {
-                Ok(k) => k,
-                Err(_) => continue,
-            };
-            let value = match hex_to_bytes(parts[2]) {
-                Ok(v) => v,
-                Err(_) => continue,
-            };
-
-            let mut value_stream = std::io::Cursor::new(value);
-
-            match record_type {
-                OFFLINE_MESSAGES_TAG => {
-                    let publish = match Packet::from_bytes(&mut value_stream, &key) {
-                        Ok(Packet::Publish(publish)) => publish,
-                        _ => continue,
-                    };
-                    offline_messages
-                        .entry(entry_key)
-                        .or_insert_with(VecDeque::new)
-                        .push_back(publish);
+    /// Applies a change restored from the persistence backend to the state
+    fn apply_change(&mut self, change: StateChange) {
+        match change {
+            StateChange::Subscribed(client_id, topic_filter) => {
+                self.subscriptions.subscribe(&topic_filter, &client_id);
+                if let Some(cluster) = &mut self.cluster {
+                    cluster.share_subscription(&client_id, &topic_filter);
                 }
-                RETAINED_MESSAGES_TAG => {
-                    let mut entry_stream = std::io::Cursor::new(entry_key);
-
-                    let topic_name = TopicName::from_bytes(&mut entry_stream);
-                    let topic_name = match topic_name {
-                        Ok(topic_name) => topic_name,
-                        Err(_) => {
-                            println!("Error deserializing topic name");
-                            continue;
-                        }
-                    };
-                    let message = match Packet::from_bytes(&mut value_stream, &key) {
-                        Ok(Packet::Publish(publish)) => publish,
-                        _ => continue,
-                    };
-                    retained_messages
-                        .entry(topic_name)
-                        .or_insert_with(VecDeque::new)
-                        .push_back(message);
-                }
-                CLIENTS_TAG => {
-                    let subscription = TopicFilter::from_bytes(&mut value_stream);
-                    let subscription = match subscription {
-                        Ok(subscription) => subscription,
-                        Err(_) => {
-                            println!("Error deserializing subscription");
-                            continue;
-                        }
-                    };
+                if let Ok(mut clients) = self.clients.write() {
                     clients
-                        .entry(entry_key.clone())
-                        .or_insert_with(|| Client::new_from_backup(entry_key.clone(), Vec::new()))
-                        .subscriptions
-                        .push(subscription);
+                        .entry(client_id.clone())
+                        .or_insert_with(|| Client::new_from_backup(client_id, Vec::new()))
+                        .add_subscription(topic_filter);
                 }
-                _ => {}
             }
-        }
-
-        let clients_lock = RwLock::new(clients);
-
-        TaskHandler {
-            client_actions_receiver_channel: receiver_channel,
-            clients: clients_lock,
-            active_connections: HashSet::new(),
-            offline_messages,
-            retained_messages,
-            log_file,
-            client_manager,
-            key,
-            backup_file: config.get_backup_file(),
-            segs_to_backup: config.get_segs_to_backup(),
+            StateChange::Unsubscribed(client_id, topic_filter) => {
+                self.subscriptions.unsubscribe(&topic_filter, &client_id);
+                if let Ok(mut clients) = self.clients.write() {
+                    if let Some(client) = clients.get_mut(&client_id) {
+                        client.remove_subscription(&topic_filter);
+                    }
+                }
+            }
+            StateChange::Retained(publish) => {
+                // The age of restored messages counts from the restart
+                self.retained_messages
+                    .entry(publish.topic().clone())
+                    .or_default()
+                    .push_back((Instant::now(), publish));
+            }
+            StateChange::RetainedCleared(topic_name) => {
+                self.retained_messages.remove(&topic_name);
+            }
+            StateChange::Queued(client_id, publish) => {
+                self.outbound_queues.push(client_id, publish);
+            }
+            StateChange::QueueCleared(client_id) => {
+                self.outbound_queues.take(&client_id);
+            }
         }
     }
 
+    /// Sends a snapshot of the task handler data to the persistence backend
     pub fn backup_data(&self) {
-        let backup_file_path_copy = match &self.backup_file {
-            Some(file) => file.clone(),
-            None => return,
-        };
-        let serialized_data = self.serialize();
-
-        // Spawn a thread to serialize and write the data to a file as I/O operations are blocking
-        thread::spawn(move || {
-            let mut file = match File::create(backup_file_path_copy) {
-                Ok(file) => file,
-                Err(e) => {
-                    eprintln!("Failed to create/open backup file: {}", e);
-                    return;
-                }
-            };
-            if let Err(e) = file.write_all(serialized_data.as_bytes()) {
-                eprintln!("Failed to write to backup file: {}", e);
+        if let Some(persistence) = &self.persistence {
+            if let Err(e) = persistence.save_snapshot(self.snapshot()) {
+                self.log_file
+                    .error(&format!("Failed to back up server data: {}", e));
             }
-        });
+        }
     }
 }
 
-/// Convert a slice of bytes to a hexadecimal string
-fn bytes_to_hex(bytes: &[u8]) -> String {
-    bytes
-        .iter()
-        .map(|b| format!("{:02X}", b))
-        .collect::<Vec<_>>()
-        .join("")
+/// Summarizes the connection of a client that is being disconnected
+fn session_summary(client: &Client, cause: &DisconnectCause) -> SessionSummary {
+    let traffic = client.traffic();
+    SessionSummary {
+        address: client.address(),
+        duration: client.connection_duration(),
+        packets_received: traffic.map_or(0, |traffic| traffic.packets_received()),
+        bytes_received: traffic.map_or(0, |traffic| traffic.bytes_received()),
+        packets_sent: traffic.map_or(0, |traffic| traffic.packets_sent()),
+        bytes_sent: traffic.map_or(0, |traffic| traffic.bytes_sent()),
+        subscriptions: client.subscriptions.len(),
+        reason: cause.to_string(),
+    }
 }
 
-/// Convert a hexadecimal string to a vector of bytes
-fn hex_to_bytes(hex: &str) -> Result<Vec<u8>, String> {
-    (0..hex.len())
-        .step_by(2)
-        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| e.to_string()))
-        .collect()
+/// Counts the time since a traced message was received until it was handed to the writer
+/// of a client and logs the hop. Messages restored from a backup have no trace
+fn record_delivery(
+    latencies: &MessageLatencies,
+    log_file: &Logger,
+    trace: Option<Trace>,
+    client_id: &[u8],
+) {
+    if let Some(trace) = trace {
+        let elapsed = trace.elapsed();
+        latencies.record(Stage::Delivery, elapsed);
+        log_file.log_trace_hop(&trace, Stage::Delivery, Some(client_id), elapsed);
+    }
 }
@@ -0,0 +1,100 @@
+use std::collections::{HashMap, HashSet};
+
+use mqtt::model::components::topic_name::TopicName;
+
+/// Represents the traffic of a topic since the server started
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TopicStats {
+    pub messages: u64,
+    /// Bytes of the payloads of the messages
+    pub bytes: u64,
+}
+
+/// Counts the messages published in every topic and remembers the topics
+/// whose counters changed since they were last taken
+#[derive(Debug, Default)]
+pub struct TopicStatsTracker {
+    stats: HashMap<TopicName, TopicStats>,
+    changed: HashSet<TopicName>,
+}
+
+impl TopicStatsTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a message published in the topic
+    pub fn record(&mut self, topic_name: &TopicName, payload_bytes: usize) {
+        let stats = self.stats.entry(topic_name.clone()).or_default();
+        stats.messages += 1;
+        stats.bytes += payload_bytes as u64;
+        self.changed.insert(topic_name.clone());
+    }
+
+    /// Returns the topics whose counters changed since the last call, with their counters
+    pub fn take_changed(&mut self) -> Vec<(TopicName, TopicStats)> {
+        self.changed
+            .drain()
+            .filter_map(|topic_name| {
+                let stats = *self.stats.get(&topic_name)?;
+                Some((topic_name, stats))
+            })
+            .collect()
+    }
+
+    /// Returns the counters of every topic, sorted by topic
+    pub fn all(&self) -> Vec<(String, TopicStats)> {
+        let mut stats: Vec<(String, TopicStats)> = self
+            .stats
+            .iter()
+            .map(|(topic_name, stats)| (topic_name.to_string(), *stats))
+            .collect();
+        stats.sort_by(|a, b| a.0.cmp(&b.0));
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_and_bytes_are_counted_per_topic() {
+        let drone_data = TopicName::new(vec![b"drone-data".to_vec()], false);
+        let camera_data = TopicName::new(vec![b"camera-data".to_vec()], false);
+        let mut tracker = TopicStatsTracker::new();
+
+        tracker.record(&drone_data, 10);
+        tracker.record(&drone_data, 5);
+        tracker.record(&camera_data, 100);
+
+        assert_eq!(
+            tracker.all(),
+            vec![
+                (
+                    "camera-data".to_string(),
+                    TopicStats {
+                        messages: 1,
+                        bytes: 100
+                    }
+                ),
+                (
+                    "drone-data".to_string(),
+                    TopicStats {
+                        messages: 2,
+                        bytes: 15
+                    }
+                ),
+            ]
+        );
+
+        assert_eq!(tracker.take_changed().len(), 2);
+        assert!(tracker.take_changed().is_empty());
+
+        tracker.record(&drone_data, 1);
+        let changed = tracker.take_changed();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].0, drone_data);
+        assert_eq!(changed[0].1.messages, 3);
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::backup::crc32;
+
+const WAL_FILE_EXTENSION: &str = "wal";
+const SEPARATOR: char = ';';
+
+/// Represents the write-ahead log of the task handler. Every change to the persisted state is
+/// appended as soon as it happens, so a crash of the server doesn't lose the changes made since
+/// the last backup. Each line holds `<sequence>;<record>;<checksum>`.
+/// When a backup is taken the log is rotated into a segment named after its last sequence number,
+/// and segments are removed once a backup that includes them is on disk
+#[derive(Debug)]
+pub struct WriteAheadLog {
+    path: PathBuf,
+    file: File,
+    next_sequence: u64,
+}
+
+impl WriteAheadLog {
+    /// Opens the log to append records numbered from `next_sequence`
+    pub fn open(path: &Path, next_sequence: u64) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            next_sequence: next_sequence.max(1),
+        })
+    }
+
+    /// Appends a record to the log
+    pub fn append(&mut self, record: &str) -> io::Result<()> {
+        let line = format!("{:X}{}{}", self.next_sequence, SEPARATOR, record);
+        writeln!(
+            self.file,
+            "{}{}{:08X}",
+            line,
+            SEPARATOR,
+            crc32(line.as_bytes())
+        )?;
+        self.next_sequence += 1;
+        Ok(())
+    }
+
+    /// Returns the sequence number of the last record appended
+    pub fn last_sequence(&self) -> u64 {
+        self.next_sequence - 1
+    }
+
+    /// Moves the records appended so far to a segment and starts an empty log.
+    /// Returns the sequence number of the last record of the segment
+    pub fn rotate(&mut self) -> io::Result<u64> {
+        let last_sequence = self.last_sequence();
+
+        if self.file.metadata()?.len() > 0 {
+            fs::rename(&self.path, segment_path(&self.path, last_sequence))?;
+            self.file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+        }
+
+        Ok(last_sequence)
+    }
+}
+
+/// Returns the path of the log that complements a backup file
+pub fn wal_path(backup_file_path: &str) -> PathBuf {
+    PathBuf::from(format!("{}.{}", backup_file_path, WAL_FILE_EXTENSION))
+}
+
+/// Reads the records of the segments and the log in order, with their sequence numbers.
+/// A file is read until its first invalid line, as it can only be the one a crash interrupted
+pub fn read_records(path: &Path) -> io::Result<Vec<(u64, String)>> {
+    let mut files: Vec<PathBuf> = segments(path)?.into_iter().map(|(_, path)| path).collect();
+    files.push(path.to_path_buf());
+
+    let mut records = vec![];
+    for file in files {
+        let content = match fs::read_to_string(&file) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+
+        records.extend(content.lines().map_while(parse_line));
+    }
+
+    Ok(records)
+}
+
+/// Removes the segments whose records are all included in a backup
+pub fn remove_segments(path: &Path, up_to_sequence: u64) -> io::Result<()> {
+    for (last_sequence, segment) in segments(path)? {
+        if last_sequence <= up_to_sequence {
+            fs::remove_file(segment)?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes the log and all its segments
+pub fn remove_all(path: &Path) -> io::Result<()> {
+    remove_segments(path, u64::MAX)?;
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Returns the path of the segment whose last record has the sequence number
+fn segment_path(path: &Path, last_sequence: u64) -> PathBuf {
+    let mut segment = path.as_os_str().to_os_string();
+    segment.push(format!(".{}", last_sequence));
+    PathBuf::from(segment)
+}
+
+/// Returns the segments of the log sorted by their last sequence number
+fn segments(path: &Path) -> io::Result<Vec<(u64, PathBuf)>> {
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let prefix = match path.file_name() {
+        Some(file_name) => format!("{}.", file_name.to_string_lossy()),
+        None => return Ok(vec![]),
+    };
+
+    let mut segments = vec![];
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if let Some(Ok(last_sequence)) = file_name.strip_prefix(&prefix).map(str::parse) {
+            segments.push((last_sequence, entry.path()));
+        }
+    }
+
+    segments.sort();
+    Ok(segments)
+}
+
+/// Parses a line of the log, returning None if it is incomplete or corrupted
+fn parse_line(line: &str) -> Option<(u64, String)> {
+    let (content, checksum) = line.rsplit_once(SEPARATOR)?;
+    if u32::from_str_radix(checksum, 16).ok()? != crc32(content.as_bytes()) {
+        return None;
+    }
+
+    let (sequence, record) = content.split_once(SEPARATOR)?;
+    Some((u64::from_str_radix(sequence, 16).ok()?, record.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_survive_rotation_until_removed() {
+        let path = wal_path("test_wal_rotation.txt");
+        let _ = remove_all(&path);
+
+        let mut wal = WriteAheadLog::open(&path, 1).unwrap();
+        wal.append("R;01;02").unwrap();
+        wal.append("C;03;04").unwrap();
+        let last_sequence = wal.rotate().unwrap();
+        wal.append("-C;03;04").unwrap();
+
+        assert_eq!(last_sequence, 2);
+        assert_eq!(
+            read_records(&path).unwrap(),
+            vec![
+                (1, "R;01;02".to_string()),
+                (2, "C;03;04".to_string()),
+                (3, "-C;03;04".to_string())
+            ]
+        );
+
+        remove_segments(&path, last_sequence).unwrap();
+        assert_eq!(
+            read_records(&path).unwrap(),
+            vec![(3, "-C;03;04".to_string())]
+        );
+
+        remove_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_interrupted_line_is_ignored() {
+        let path = wal_path("test_wal_interrupted.txt");
+        let _ = remove_all(&path);
+
+        let mut wal = WriteAheadLog::open(&path, 7).unwrap();
+        wal.append("O;01;02").unwrap();
+        wal.file.write_all(b"8;O;01;0").unwrap();
+
+        assert_eq!(
+            read_records(&path).unwrap(),
+            vec![(7, "O;01;02".to_string())]
+        );
+
+        remove_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,327 @@
+use std::{
+    io::{self, Write},
+    net::{Shutdown, SocketAddr, TcpStream},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha1::{Digest, Sha1};
+
+use crate::client_stream::write_all_retrying;
+
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const MQTT_SUBPROTOCOL: &str = "mqtt";
+pub const BAD_REQUEST_RESPONSE: &[u8] = b"HTTP/1.1 400 Bad Request\r\nConnection: close\r\n\r\n";
+const MAX_HANDSHAKE_SIZE: usize = 8192;
+/// Largest MQTT packet (maximum remaining length plus the fixed header)
+const MAX_FRAME_SIZE: u64 = 268_435_455 + 5;
+
+const CONTINUATION_OPCODE: u8 = 0x0;
+const TEXT_OPCODE: u8 = 0x1;
+const BINARY_OPCODE: u8 = 0x2;
+const CLOSE_OPCODE: u8 = 0x8;
+const PING_OPCODE: u8 = 0x9;
+const PONG_OPCODE: u8 = 0xA;
+
+const FIN_BIT: u8 = 0x80;
+const MASK_BIT: u8 = 0x80;
+const OPCODE_MASK: u8 = 0x0F;
+const LENGTH_MASK: u8 = 0x7F;
+const EXTENDED_16_BITS_LENGTH: u8 = 126;
+const EXTENDED_64_BITS_LENGTH: u8 = 127;
+
+/// Represents a frame received from a WebSocket client
+#[derive(Debug, PartialEq)]
+pub enum Frame {
+    /// Part of the MQTT byte stream
+    Data(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong,
+    Close,
+}
+
+/// Represents the sending side of a server WebSocket connection carrying MQTT packets.
+/// Every write is sent as a single binary frame
+#[derive(Debug)]
+pub struct WebSocketStream {
+    stream: TcpStream,
+}
+
+impl WebSocketStream {
+    /// Wraps a connection that already completed the opening handshake
+    pub fn new(stream: TcpStream) -> Self {
+        Self { stream }
+    }
+
+    /// Creates a new handle to the same connection
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self {
+            stream: self.stream.try_clone()?,
+        })
+    }
+
+    /// Shuts down the underlying connection
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.stream.shutdown(how)
+    }
+
+    /// Returns the address of the client
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+}
+
+impl Write for &WebSocketStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        write_all_retrying(&self.stream, &encode_frame(BINARY_OPCODE, buf))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&self.stream).flush()
+    }
+}
+
+impl Write for WebSocketStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&*self).flush()
+    }
+}
+
+/// Returns the length of the upgrade request at the start of the buffer,
+/// or None if its headers were not completely received yet
+pub fn handshake_request_length(buffer: &[u8]) -> io::Result<Option<usize>> {
+    match buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+        Some(position) => Ok(Some(position + 4)),
+        None if buffer.len() >= MAX_HANDSHAKE_SIZE => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "WebSocket handshake too large",
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Decodes the frame at the start of the buffer and returns it with its length in bytes.
+/// Returns None if the frame was not completely received yet
+pub fn decode_frame(buffer: &[u8]) -> io::Result<Option<(Frame, usize)>> {
+    if buffer.len() < 2 {
+        return Ok(None);
+    }
+
+    let opcode = buffer[0] & OPCODE_MASK;
+    let masked = buffer[1] & MASK_BIT != 0;
+    let (length, mut offset) = match buffer[1] & LENGTH_MASK {
+        EXTENDED_16_BITS_LENGTH => match buffer.get(2..4) {
+            Some(length) => (u16::from_be_bytes([length[0], length[1]]) as u64, 4),
+            None => return Ok(None),
+        },
+        EXTENDED_64_BITS_LENGTH => match buffer.get(2..10) {
+            Some(length) => {
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(length);
+                (u64::from_be_bytes(bytes), 10)
+            }
+            None => return Ok(None),
+        },
+        length => (length as u64, 2),
+    };
+
+    if length > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "WebSocket frame too large",
+        ));
+    }
+
+    let mut mask = [0u8; 4];
+    if masked {
+        match buffer.get(offset..offset + 4) {
+            Some(mask_bytes) => mask.copy_from_slice(mask_bytes),
+            None => return Ok(None),
+        }
+        offset += 4;
+    }
+
+    let end = offset + length as usize;
+    let mut payload = match buffer.get(offset..end) {
+        Some(payload) => payload.to_vec(),
+        None => return Ok(None),
+    };
+
+    if masked {
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+    }
+
+    let frame = match opcode {
+        CONTINUATION_OPCODE | TEXT_OPCODE | BINARY_OPCODE => Frame::Data(payload),
+        PING_OPCODE => Frame::Ping(payload),
+        PONG_OPCODE => Frame::Pong,
+        CLOSE_OPCODE => Frame::Close,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Unsupported WebSocket opcode",
+            ))
+        }
+    };
+
+    Ok(Some((frame, end)))
+}
+
+/// Builds the answer to a ping frame
+pub fn pong_frame(payload: &[u8]) -> Vec<u8> {
+    encode_frame(PONG_OPCODE, payload)
+}
+
+/// Builds the frame that closes the connection
+pub fn close_frame() -> Vec<u8> {
+    encode_frame(CLOSE_OPCODE, &[])
+}
+
+/// Builds the response that accepts an upgrade request. None if the request is not valid
+pub fn handshake_response(request: &str) -> Option<String> {
+    let mut lines = request.lines();
+    if !lines.next()?.starts_with("GET ") {
+        return None;
+    }
+
+    let mut key = None;
+    let mut upgrade = false;
+    let mut mqtt_protocol = false;
+
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            match name.trim().to_lowercase().as_str() {
+                "upgrade" => upgrade = value.eq_ignore_ascii_case("websocket"),
+                "sec-websocket-key" => key = Some(value),
+                "sec-websocket-protocol" => {
+                    mqtt_protocol = value
+                        .split(',')
+                        .any(|protocol| protocol.trim() == MQTT_SUBPROTOCOL)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if !upgrade {
+        return None;
+    }
+
+    let mut response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n",
+        accept_key(key?)
+    );
+    if mqtt_protocol {
+        response.push_str(&format!("Sec-WebSocket-Protocol: {}\r\n", MQTT_SUBPROTOCOL));
+    }
+    response.push_str("\r\n");
+
+    Some(response)
+}
+
+/// Computes the Sec-WebSocket-Accept value for a client key
+fn accept_key(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.as_bytes());
+    hasher.update(WEBSOCKET_GUID.as_bytes());
+    STANDARD.encode(hasher.finalize())
+}
+
+/// Encodes an unmasked frame, as sent by the server
+fn encode_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![FIN_BIT | opcode];
+
+    match payload.len() {
+        length if length < EXTENDED_16_BITS_LENGTH as usize => frame.push(length as u8),
+        length if length <= u16::MAX as usize => {
+            frame.push(EXTENDED_16_BITS_LENGTH);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(EXTENDED_64_BITS_LENGTH);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+
+    frame.extend_from_slice(payload);
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_key() {
+        // Example of RFC 6455
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn test_handshake_response() {
+        let request = "GET /mqtt HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Protocol: mqtt\r\n\r\n";
+        let response = handshake_response(request).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 101"));
+        assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+        assert!(response.contains("Sec-WebSocket-Protocol: mqtt"));
+
+        assert!(handshake_response("GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").is_none());
+    }
+
+    #[test]
+    fn test_handshake_request_length() {
+        assert_eq!(
+            handshake_request_length(b"GET / HTTP/1.1\r\n").unwrap(),
+            None
+        );
+        assert_eq!(
+            handshake_request_length(b"GET / HTTP/1.1\r\n\r\nrest").unwrap(),
+            Some(18)
+        );
+    }
+
+    #[test]
+    fn test_decode_masked_frame() {
+        let mask = [1, 2, 3, 4];
+        let payload: Vec<u8> = b"mqtt"
+            .iter()
+            .enumerate()
+            .map(|(i, b)| b ^ mask[i % 4])
+            .collect();
+        let mut frame = vec![FIN_BIT | BINARY_OPCODE, MASK_BIT | 4];
+        frame.extend_from_slice(&mask);
+        frame.extend_from_slice(&payload);
+
+        assert_eq!(decode_frame(&frame[..5]).unwrap(), None);
+        assert_eq!(
+            decode_frame(&frame).unwrap(),
+            Some((Frame::Data(b"mqtt".to_vec()), frame.len()))
+        );
+    }
+
+    #[test]
+    fn test_encode_frame() {
+        assert_eq!(
+            encode_frame(BINARY_OPCODE, b"ack"),
+            vec![FIN_BIT | BINARY_OPCODE, 3, b'a', b'c', b'k']
+        );
+
+        let frame = encode_frame(BINARY_OPCODE, &[0; 200]);
+        assert_eq!(&frame[..4], &[FIN_BIT | BINARY_OPCODE, 126, 0, 200]);
+        assert_eq!(
+            decode_frame(&frame).unwrap(),
+            Some((Frame::Data(vec![0; 200]), 204))
+        );
+    }
+}
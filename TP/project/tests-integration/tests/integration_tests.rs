@@ -38,9 +38,9 @@ fn test_new_incident() {
     let mut camera_system = CameraSystem::new();
     let camera = Camera::new(1_u8, 1.5, 1.5, 3.0);
     camera_system.add_camera(camera);
-    let camera_data1 = camera_system.cameras_data();
+    let camera_data1 = camera_system.cameras_data()[0].to_string();
     camera_system.new_incident(incident.clone());
-    let camera_data2 = camera_system.cameras_data();
+    let camera_data2 = camera_system.cameras_data()[0].to_string();
     assert_eq!(camera_data1, "1;1.5;1.5;0");
     assert_eq!(camera_data2, "1;1.5;1.5;1");
 }
@@ -77,9 +77,9 @@ fn test_new_incident_gets_attended() {
     let mut camera_system = CameraSystem::new();
     let camera = Camera::new(1_u8, 1.5, 1.5, 5.0);
     camera_system.add_camera(camera);
-    let camera_data1 = camera_system.cameras_data();
+    let camera_data1 = camera_system.cameras_data()[0].to_string();
     camera_system.new_incident(incident.clone());
-    let camera_data2 = camera_system.cameras_data();
+    let camera_data2 = camera_system.cameras_data()[0].to_string();
     assert_eq!(camera_data1, "1;1.5;1.5;0");
     assert_eq!(camera_data2, "1;1.5;1.5;1");
 